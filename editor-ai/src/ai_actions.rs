@@ -1,3 +1,4 @@
+use super::models::{AIMessage, AIRole};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +37,108 @@ pub enum AIAction {
     },
 }
 
+impl AIAction {
+    /// 执行这个动作的请求：一条说明任务的系统消息，之后是对话或代码
+    pub fn messages(&self) -> Vec<AIMessage> {
+        let code_task = |task: String, language: &str, code: &str| {
+            vec![
+                AIMessage {
+                    role: AIRole::System,
+                    content: format!("You are an expert {} programmer. {}", language, task),
+                },
+                AIMessage {
+                    role: AIRole::User,
+                    content: format!("```{}\n{}\n```", language, code),
+                },
+            ]
+        };
+        match self {
+            AIAction::GenerateCode {
+                context,
+                language,
+                cursor_position: (line, column),
+            } => code_task(
+                format!(
+                    "Write the code that belongs at line {}, column {} of this file. Reply \
+                    with the code only.",
+                    line + 1,
+                    column + 1
+                ),
+                language,
+                context,
+            ),
+            AIAction::ExplainCode { code, language } => code_task(
+                "Explain what this code does and how it works.".to_string(),
+                language,
+                code,
+            ),
+            AIAction::RefactorCode {
+                code,
+                language,
+                refactoring_type,
+            } => code_task(
+                format!(
+                    "Refactor this code ({:?}) without changing its behaviour. Reply with \
+                    the new code in one fenced block.",
+                    refactoring_type
+                ),
+                language,
+                code,
+            ),
+            AIAction::FixBugs {
+                code,
+                language,
+                error_message,
+            } => code_task(
+                match error_message {
+                    Some(error) => format!(
+                        "Fix the bug behind this error: {}. Reply with the fixed code in one \
+                        fenced block and a short explanation.",
+                        error
+                    ),
+                    None => "Find and fix the bugs in this code. Reply with the fixed code in \
+                        one fenced block and a short explanation."
+                        .to_string(),
+                },
+                language,
+                code,
+            ),
+            AIAction::GenerateTests {
+                code,
+                language,
+                test_framework,
+            } => code_task(
+                format!("Write {} tests covering this code.", test_framework),
+                language,
+                code,
+            ),
+            AIAction::GenerateDocumentation { code, language } => code_task(
+                "Write documentation comments for this code in the language's usual style."
+                    .to_string(),
+                language,
+                code,
+            ),
+            AIAction::Chat {
+                message,
+                conversation_history,
+            } => conversation_history
+                .iter()
+                .map(|entry| AIMessage {
+                    role: match entry.role {
+                        ChatRole::User => AIRole::User,
+                        ChatRole::Assistant => AIRole::Assistant,
+                    },
+                    content: entry.content.clone(),
+                })
+                .chain(std::iter::once(AIMessage {
+                    role: AIRole::User,
+                    content: message.clone(),
+                }))
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RefactoringType {
     ExtractFunction,
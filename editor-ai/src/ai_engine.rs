@@ -1,8 +1,13 @@
+use super::ai_actions::AIAction;
+use super::code_search::{parse_citations, Citation, CodeIndex, CodeSearchAnswer};
+use super::composer::{composer_messages, parse_edit_blocks, ComposerReply, WorkingSet};
+use super::inline_thread::{extract_code, rewrite_messages, thread_messages, ThreadContext};
 use super::models::{AIContext, AIMessage, AIRequest, AIResponse, AIRole};
-use editor_infra::config::{AIConfig, AIProviderConfig, PredefinedModelConfig};
+use editor_infra::config::{AIConfig, AIProviderConfig, PredefinedModelConfig, WorkflowConfig};
+use editor_infra::telemetry::{Metric, Metrics};
 use reqwest::Client;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use thiserror::Error;
 use tokio::sync::RwLock;
 
@@ -27,20 +32,33 @@ pub enum AIEngineError {
 #[derive(Debug, Clone)]
 pub struct AIEngine {
     config: Arc<RwLock<AIConfig>>,
-    http_client: Client,
+    /// 第一次请求时才创建，加载 TLS 根证书较慢，不应拖慢启动
+    http_client: Arc<OnceLock<Client>>,
     #[allow(dead_code)]
     model_cache: Arc<RwLock<HashMap<String, PredefinedModelConfig>>>,
+    metrics: Option<Metrics>,
 }
 
 impl AIEngine {
     pub fn new(config: AIConfig) -> Self {
         Self {
             config: Arc::new(RwLock::new(config)),
-            http_client: Client::new(),
+            http_client: Arc::new(OnceLock::new()),
             model_cache: Arc::new(RwLock::new(HashMap::new())),
+            metrics: None,
         }
     }
 
+    fn http_client(&self) -> &Client {
+        self.http_client.get_or_init(Client::new)
+    }
+
+    /// 记录每次模型请求的耗时，仅保存在本地
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     pub async fn generate_completion(
         &self,
         context: AIContext,
@@ -119,6 +137,153 @@ impl AIEngine {
         }
     }
 
+    /// 自然语言代码搜索：先检索索引，再由 LLM 重新排序并给出带引用的回答
+    pub async fn answer_code_query(
+        &self,
+        query: &str,
+        index: &CodeIndex,
+        model_name: Option<&str>,
+    ) -> Result<CodeSearchAnswer, AIEngineError> {
+        let hits = index.search(query, 12);
+        if hits.is_empty() {
+            return Ok(CodeSearchAnswer {
+                answer: "No matching code found in the workspace index.".to_string(),
+                citations: Vec::new(),
+            });
+        }
+
+        let mut candidates = String::new();
+        for hit in &hits {
+            candidates.push_str(&format!(
+                "### {}:{}-{}\n```\n{}\n```\n\n",
+                index.display_path(&hit.chunk.path),
+                hit.chunk.start_line,
+                hit.chunk.end_line,
+                hit.chunk.text
+            ));
+        }
+
+        let messages = vec![
+            AIMessage {
+                role: AIRole::System,
+                content: "You answer questions about a codebase using only the provided snippets. \
+                    Rank the snippets by relevance, answer concisely, and cite every location you \
+                    rely on as `path:start-end` using the exact paths and line numbers given."
+                    .to_string(),
+            },
+            AIMessage {
+                role: AIRole::User,
+                content: format!(
+                    "## Candidate Snippets\n{}## Question\n{}",
+                    candidates, query
+                ),
+            },
+        ];
+
+        let answer = self.generate_chat_completion(messages, model_name).await?;
+        let mut citations = parse_citations(&answer, index);
+
+        // 模型未给出可识别的引用时，退回到检索排名最高的片段
+        if citations.is_empty() {
+            citations = hits
+                .iter()
+                .take(3)
+                .map(|hit| Citation {
+                    path: hit.chunk.path.clone(),
+                    start_line: hit.chunk.start_line,
+                    end_line: hit.chunk.end_line,
+                })
+                .collect();
+        }
+
+        Ok(CodeSearchAnswer { answer, citations })
+    }
+
+    /// Composer 模式：按指令对给出的文件提出修改，`files` 为 (路径, 当前内容)
+    pub async fn propose_edits(
+        &self,
+        instruction: &str,
+        files: &[(String, String)],
+        working_set: &WorkingSet,
+        model_name: Option<&str>,
+    ) -> Result<ComposerReply, AIEngineError> {
+        let messages = composer_messages(instruction, files, working_set);
+        let reply = self.generate_chat_completion(messages, model_name).await?;
+        Ok(parse_edit_blocks(&reply))
+    }
+
+    /// 执行一个 AI 动作，返回模型的回复
+    pub async fn run_action(
+        &self,
+        action: &AIAction,
+        model_name: Option<&str>,
+    ) -> Result<String, AIEngineError> {
+        self.generate_chat_completion(action.messages(), model_name)
+            .await
+    }
+
+    /// 在锚定于一段代码的对话中回答问题，`history` 为此前的问答
+    pub async fn reply_in_thread(
+        &self,
+        context: &ThreadContext,
+        history: &[AIMessage],
+        question: &str,
+        model_name: Option<&str>,
+    ) -> Result<String, AIEngineError> {
+        let messages = thread_messages(context, history, question);
+        self.generate_chat_completion(messages, model_name).await
+    }
+
+    /// 按对话改写整个代码范围，返回新代码
+    pub async fn rewrite_thread_range(
+        &self,
+        context: &ThreadContext,
+        history: &[AIMessage],
+        instruction: &str,
+        model_name: Option<&str>,
+    ) -> Result<String, AIEngineError> {
+        let messages = rewrite_messages(context, history, instruction);
+        let reply = self.generate_chat_completion(messages, model_name).await?;
+        Ok(extract_code(&reply))
+    }
+
+    /// 依次执行 workflow 的各个步骤，上一步的输出通过 `{{input}}` 传给下一步
+    pub async fn run_workflow(
+        &self,
+        workflow: &WorkflowConfig,
+        input: &str,
+    ) -> Result<String, AIEngineError> {
+        let mut current = input.to_string();
+
+        for step in &workflow.steps {
+            let agent = {
+                let cfg = self.config.read().await;
+                cfg.agents.get(&step.agent).cloned().ok_or_else(|| {
+                    AIEngineError::ConfigError(format!(
+                        "Agent {} not found for workflow {}",
+                        step.agent, workflow.name
+                    ))
+                })?
+            };
+
+            let messages = vec![
+                AIMessage {
+                    role: AIRole::System,
+                    content: agent.system_prompt.clone(),
+                },
+                AIMessage {
+                    role: AIRole::User,
+                    content: step.input_template.replace("{{input}}", &current),
+                },
+            ];
+            current = self
+                .generate_chat_completion(messages, Some(&agent.model))
+                .await?;
+        }
+
+        Ok(current)
+    }
+
     async fn build_messages(
         &self,
         context: AIContext,
@@ -159,7 +324,7 @@ impl AIEngine {
                     project_context.dependencies.join(", ")
                 ));
             }
-            message.push_str("\n");
+            message.push('\n');
         }
 
         // 添加文件信息
@@ -223,6 +388,22 @@ impl AIEngine {
         &self,
         provider_config: &AIProviderConfig,
         request: &AIRequest,
+    ) -> Result<AIResponse, AIEngineError> {
+        let response = self.post_request(provider_config, request);
+        match &self.metrics {
+            Some(metrics) => {
+                metrics
+                    .measure(Metric::AiRequest(request.model.clone()), response)
+                    .await
+            }
+            None => response.await,
+        }
+    }
+
+    async fn post_request(
+        &self,
+        provider_config: &AIProviderConfig,
+        request: &AIRequest,
     ) -> Result<AIResponse, AIEngineError> {
         let url = match provider_config.provider_type {
             editor_infra::config::AIProviderType::Ollama => {
@@ -233,7 +414,7 @@ impl AIEngine {
             }
         };
 
-        let mut http_request = self.http_client.post(&url).json(request);
+        let mut http_request = self.http_client().post(&url).json(request);
 
         // 添加 API key（如果需要）
         if let Some(api_key) = &provider_config.api_key {
@@ -319,7 +500,7 @@ impl AIEngine {
             }
         };
 
-        let response = self.http_client.get(&url).send().await?;
+        let response = self.http_client().get(&url).send().await?;
         Ok(response.status().is_success())
     }
 }
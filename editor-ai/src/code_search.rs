@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// 哈希词袋向量的桶数
+const TERM_BUCKETS: usize = 256;
+const CHUNK_LINES: usize = 40;
+const CHUNK_OVERLAP: usize = 10;
+
+/// 索引中的一个代码片段，行号从 1 开始（闭区间）
+#[derive(Debug, Clone)]
+pub struct CodeChunk {
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    terms: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub chunk: CodeChunk,
+    pub score: f32,
+}
+
+/// AI 回答中引用的文件位置，行号从 1 开始（闭区间）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Citation {
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl Citation {
+    pub fn label(&self) -> String {
+        let name = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.display().to_string());
+        if self.start_line == self.end_line {
+            format!("{}:{}", name, self.start_line)
+        } else {
+            format!("{}:{}-{}", name, self.start_line, self.end_line)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeSearchAnswer {
+    pub answer: String,
+    pub citations: Vec<Citation>,
+}
+
+/// 基于哈希词袋向量的代码检索索引。
+///
+/// Chunks are scored by the cosine of hashed bag-of-identifier term vectors, a
+/// lexical match computed locally rather than a learned embedding, so the index
+/// works without an embeddings endpoint; the LLM re-ranks the hits.
+#[derive(Debug, Clone, Default)]
+pub struct CodeIndex {
+    root: Option<PathBuf>,
+    chunks: Vec<CodeChunk>,
+}
+
+impl CodeIndex {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self {
+            root,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// 索引占用的大致内存（字节）：片段文本和向量
+    pub fn approximate_bytes(&self) -> usize {
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                std::mem::size_of::<CodeChunk>()
+                    + chunk.text.len()
+                    + chunk.path.as_os_str().len()
+                    + chunk.terms.len() * std::mem::size_of::<f32>()
+            })
+            .sum()
+    }
+
+    /// 将文件切分为重叠的行窗口并加入索引，替换该文件原有的片段
+    pub fn add_file(&mut self, path: &Path, content: &str) {
+        self.remove_file(path);
+
+        let lines: Vec<&str> = content.lines().collect();
+        if lines.is_empty() {
+            return;
+        }
+
+        let step = CHUNK_LINES - CHUNK_OVERLAP;
+        let mut start = 0;
+        loop {
+            let end = (start + CHUNK_LINES).min(lines.len());
+            let text = lines[start..end].join("\n");
+            let terms = term_vector(&format!("{} {}", path.display(), text));
+            self.chunks.push(CodeChunk {
+                path: path.to_path_buf(),
+                start_line: start + 1,
+                end_line: end,
+                text,
+                terms,
+            });
+            if end == lines.len() {
+                break;
+            }
+            start += step;
+        }
+    }
+
+    /// 移除文件的全部片段，例如文件已被删除
+    pub fn remove_file(&mut self, path: &Path) {
+        self.chunks.retain(|chunk| chunk.path != path);
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query_terms = term_vector(query);
+        let mut hits: Vec<SearchHit> = self
+            .chunks
+            .iter()
+            .map(|chunk| SearchHit {
+                score: cosine_similarity(&query_terms, &chunk.terms),
+                chunk: chunk.clone(),
+            })
+            .filter(|hit| hit.score > 0.0)
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        hits.truncate(limit);
+        hits
+    }
+
+    /// 用于提示词和引用的展示路径（相对于索引根目录）
+    pub fn display_path(&self, path: &Path) -> String {
+        self.root
+            .as_ref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// 将 AI 回答中的路径映射回索引中的文件，无法识别的引用返回 None
+    pub fn resolve_path(&self, cited: &str) -> Option<PathBuf> {
+        let cited = cited.trim_start_matches("./");
+        self.chunks
+            .iter()
+            .map(|chunk| &chunk.path)
+            .find(|path| self.display_path(path) == cited || path.as_path() == Path::new(cited))
+            .cloned()
+    }
+}
+
+/// 从 AI 回答中解析 `path:line` 或 `path:start-end` 形式的引用
+pub fn parse_citations(answer: &str, index: &CodeIndex) -> Vec<Citation> {
+    let mut citations = Vec::new();
+
+    for raw in answer.split(|c: char| c.is_whitespace() || "`()[]<>,;\"'".contains(c)) {
+        let token = raw.trim_end_matches(['.', ':']);
+        let Some((path_part, range_part)) = token.rsplit_once(':') else {
+            continue;
+        };
+        let (start, end) = match range_part.split_once('-') {
+            Some((start, end)) => (start.parse::<usize>(), end.parse::<usize>()),
+            None => (range_part.parse::<usize>(), range_part.parse::<usize>()),
+        };
+        let (Ok(start_line), Ok(end_line)) = (start, end) else {
+            continue;
+        };
+        if start_line == 0 || end_line < start_line {
+            continue;
+        }
+        let Some(path) = index.resolve_path(path_part) else {
+            continue;
+        };
+
+        let citation = Citation {
+            path,
+            start_line,
+            end_line,
+        };
+        if !citations.contains(&citation) {
+            citations.push(citation);
+        }
+    }
+
+    citations
+}
+
+/// 将文本映射为定长的哈希词袋向量（已归一化）
+pub fn term_vector(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; TERM_BUCKETS];
+
+    for token in tokenize(text) {
+        let mut hasher = DefaultHasher::new();
+        token.hash(&mut hasher);
+        let bucket = (hasher.finish() % TERM_BUCKETS as u64) as usize;
+        vector[bucket] += 1.0;
+    }
+
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for value in &mut vector {
+            *value /= norm;
+        }
+    }
+    vector
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+/// 按标识符切分并拆开 snake_case / camelCase，统一转为小写
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if word.is_empty() {
+            continue;
+        }
+        let mut current = String::new();
+        let mut prev_lower = false;
+        for ch in word.chars() {
+            if ch == '_' || (ch.is_uppercase() && prev_lower) {
+                if current.len() > 1 {
+                    tokens.push(current.to_lowercase());
+                }
+                current.clear();
+            }
+            if ch != '_' {
+                current.push(ch);
+            }
+            prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        }
+        if current.len() > 1 {
+            tokens.push(current.to_lowercase());
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updating_a_file_replaces_its_chunks() {
+        let mut index = CodeIndex::new(Some(PathBuf::from("/w")));
+        index.add_file(Path::new("/w/a.rs"), "fn parse_config() {}\n");
+        index.add_file(Path::new("/w/b.rs"), "fn render_tree() {}\n");
+        assert_eq!(index.len(), 2);
+        assert_eq!(
+            index.search("parse config", 1)[0].chunk.path,
+            Path::new("/w/a.rs")
+        );
+
+        index.add_file(Path::new("/w/a.rs"), "fn load_theme() {}\n");
+        assert_eq!(index.len(), 2);
+        assert!(index
+            .search("parse config", 5)
+            .iter()
+            .all(|hit| hit.chunk.path != Path::new("/w/a.rs")));
+
+        index.remove_file(Path::new("/w/b.rs"));
+        assert_eq!(index.len(), 1);
+        assert!(index.search("render tree", 5).is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+use super::ai_actions::AIPatch;
+use super::models::{AIMessage, AIRole};
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+/// 提交审阅意见、要求模型据此修订时使用的指令
+pub const REVISE_INSTRUCTION: &str = "Revise the proposed edits according to the review.";
+
+/// Composer 模式中一处修改的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeStatus {
+    /// 模型提出、尚未处理
+    Proposed,
+    /// 已应用到缓冲区（未写回磁盘）
+    Accepted,
+    /// 已拒绝，或应用后又撤回
+    Reverted,
+    /// 无法应用，例如原始代码已不在文件中
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ComposerChange {
+    pub patch: AIPatch,
+    pub status: ChangeStatus,
+    /// 审阅时写下的评论，随下一轮请求发给模型
+    pub comment: String,
+}
+
+/// 一轮修改请求的回复：模型的说明和解析出的补丁
+#[derive(Debug, Clone, Default)]
+pub struct ComposerReply {
+    pub summary: String,
+    pub patches: Vec<AIPatch>,
+}
+
+/// Composer 模式的工作集：多轮指令累积下来的修改，按文件分组显示，逐处接受或撤回
+#[derive(Debug, Clone, Default)]
+pub struct WorkingSet {
+    pub instructions: Vec<String>,
+    pub summary: String,
+    pub changes: Vec<ComposerChange>,
+}
+
+impl WorkingSet {
+    /// 记录新一轮的回复。已接受的修改保留，其余的由新提议取代；评论已随请求发出，一并清空
+    pub fn apply_reply(&mut self, instruction: String, reply: ComposerReply) {
+        self.instructions.push(instruction);
+        self.summary = reply.summary;
+        self.changes
+            .retain(|change| change.status == ChangeStatus::Accepted);
+        for change in &mut self.changes {
+            change.comment.clear();
+        }
+        self.changes
+            .extend(reply.patches.into_iter().map(|patch| ComposerChange {
+                patch,
+                status: ChangeStatus::Proposed,
+                comment: String::new(),
+            }));
+    }
+
+    /// 上一轮的审阅意见：拒绝的修改和所有评论，没有时为空
+    pub fn review_feedback(&self) -> String {
+        let mut feedback = String::new();
+        for change in &self.changes {
+            let rejected = change.status == ChangeStatus::Reverted;
+            let comment = change.comment.trim();
+            if !rejected && comment.is_empty() {
+                continue;
+            }
+            let status = match &change.status {
+                ChangeStatus::Proposed => "pending",
+                ChangeStatus::Accepted => "accepted",
+                ChangeStatus::Reverted => "rejected",
+                ChangeStatus::Failed(_) => "failed to apply",
+            };
+            let first_line =
+                |code: &str| code.lines().next().unwrap_or_default().trim().to_string();
+            let code = if change.patch.old_code.is_empty() {
+                first_line(&change.patch.new_code)
+            } else {
+                first_line(&change.patch.old_code)
+            };
+            feedback.push_str(&format!(
+                "- {} ({}), edit starting `{}`",
+                change.patch.file_path, status, code
+            ));
+            if !comment.is_empty() {
+                feedback.push_str(&format!(": {}", comment));
+            }
+            feedback.push('\n');
+        }
+        feedback
+    }
+
+    /// 涉及的文件，按第一次出现的顺序
+    pub fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = Vec::new();
+        for change in &self.changes {
+            if !files.contains(&change.patch.file_path.as_str()) {
+                files.push(&change.patch.file_path);
+            }
+        }
+        files
+    }
+
+    /// 某个文件的修改及其在 `changes` 中的下标
+    pub fn changes_in<'a>(
+        &'a self,
+        file_path: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a ComposerChange)> + 'a {
+        self.changes
+            .iter()
+            .enumerate()
+            .filter(move |(_, change)| change.patch.file_path == file_path)
+    }
+
+    pub fn count(&self, status: &ChangeStatus) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| &change.status == status)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty() && self.changes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// 构建Composer 模式的请求：要求模型以 SEARCH/REPLACE 块给出修改，并附上相关文件的当前内容
+/// 和已接受的修改，便于在上一轮的基础上继续迭代
+pub fn composer_messages(
+    instruction: &str,
+    files: &[(String, String)],
+    working_set: &WorkingSet,
+) -> Vec<AIMessage> {
+    let system = format!(
+        "You are a coding assistant that edits files instead of chatting. Reply with one short \
+        paragraph describing the change, then one block per edit:\n\n\
+        FILE: <path as given>\n{SEARCH_MARKER}\n<exact lines from the file>\n{DIVIDER}\n\
+        <replacement lines>\n{REPLACE_MARKER}\n\n\
+        The SEARCH part must match the current file exactly and be unique in it. Leave it \
+        empty to create a new file. Do not put anything else outside the blocks."
+    );
+
+    let mut content = String::new();
+    for (path, text) in files {
+        content.push_str(&format!("### {}\n```\n{}\n```\n\n", path, text));
+    }
+    let accepted: Vec<&ComposerChange> = working_set
+        .changes
+        .iter()
+        .filter(|change| change.status == ChangeStatus::Accepted)
+        .collect();
+    if !working_set.instructions.is_empty() {
+        content.push_str("## Earlier Instructions\n");
+        for earlier in &working_set.instructions {
+            content.push_str(&format!("- {}\n", earlier));
+        }
+        content.push_str(&format!(
+            "{} earlier edits were accepted and are already in the files above.\n\n",
+            accepted.len()
+        ));
+    }
+    let feedback = working_set.review_feedback();
+    if !feedback.is_empty() {
+        content.push_str(&format!(
+            "## Review of the Last Round\nRejected edits must not be proposed again unless a \
+            comment asks for them.\n{}\n",
+            feedback
+        ));
+    }
+    content.push_str(&format!("## Instruction\n{}", instruction));
+
+    vec![
+        AIMessage {
+            role: AIRole::System,
+            content: system,
+        },
+        AIMessage {
+            role: AIRole::User,
+            content,
+        },
+    ]
+}
+
+/// 解析回复中的 SEARCH/REPLACE 块；块外的文字作为说明。不完整的块被忽略
+pub fn parse_edit_blocks(reply: &str) -> ComposerReply {
+    let mut summary = Vec::new();
+    let mut patches = Vec::new();
+    let mut file_path: Option<String> = None;
+    let mut lines = reply.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if let Some(path) = trimmed.strip_prefix("FILE:") {
+            file_path = Some(path.trim().trim_matches('`').to_string());
+            continue;
+        }
+        if trimmed != SEARCH_MARKER {
+            if !trimmed.starts_with("```") && file_path.is_none() {
+                summary.push(line);
+            }
+            continue;
+        }
+        let Some(path) = file_path.clone() else {
+            continue;
+        };
+
+        let mut old_code = Vec::new();
+        let mut new_code = Vec::new();
+        let mut in_replace = false;
+        let mut complete = false;
+        for line in lines.by_ref() {
+            match line.trim_end() {
+                DIVIDER if !in_replace => in_replace = true,
+                REPLACE_MARKER if in_replace => {
+                    complete = true;
+                    break;
+                }
+                _ if in_replace => new_code.push(line),
+                _ => old_code.push(line),
+            }
+        }
+        if !complete {
+            break;
+        }
+        let old_code = old_code.join("\n");
+        let new_code = new_code.join("\n");
+        let line_count = old_code.lines().count().max(1);
+        patches.push(AIPatch::new(
+            path,
+            old_code,
+            new_code,
+            String::new(),
+            (0, line_count),
+        ));
+    }
+
+    let summary = summary.join("\n").trim().to_string();
+    let description = summary.lines().next().unwrap_or_default();
+    for patch in &mut patches {
+        patch.description = description.to_string();
+    }
+    ComposerReply { summary, patches }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_blocks_and_keeps_accepted_changes_across_rounds() {
+        let reply = "Rename the helper and add a test.\n\n\
+            FILE: src/lib.rs\n\
+            <<<<<<< SEARCH\n\
+            fn helper() {}\n\
+            =======\n\
+            fn assist() {}\n\
+            >>>>>>> REPLACE\n\
+            FILE: `tests/new.rs`\n\
+            <<<<<<< SEARCH\n\
+            =======\n\
+            #[test]\n\
+            fn works() {}\n\
+            >>>>>>> REPLACE\n\
+            FILE: src/main.rs\n\
+            <<<<<<< SEARCH\n\
+            truncated";
+        let parsed = parse_edit_blocks(reply);
+        assert_eq!(parsed.summary, "Rename the helper and add a test.");
+        assert_eq!(parsed.patches.len(), 2);
+        assert_eq!(parsed.patches[0].old_code, "fn helper() {}");
+        assert_eq!(parsed.patches[0].new_code, "fn assist() {}");
+        assert_eq!(parsed.patches[1].file_path, "tests/new.rs");
+        assert_eq!(parsed.patches[1].old_code, "");
+        assert_eq!(parsed.patches[1].new_code, "#[test]\nfn works() {}");
+
+        let mut set = WorkingSet::default();
+        set.apply_reply("rename".to_string(), parsed);
+        assert_eq!(set.files(), ["src/lib.rs", "tests/new.rs"]);
+        set.changes[0].status = ChangeStatus::Accepted;
+        set.apply_reply("again".to_string(), ComposerReply::default());
+        assert_eq!(set.changes.len(), 1);
+        assert_eq!(set.count(&ChangeStatus::Accepted), 1);
+
+        let messages = composer_messages("more", &[], &set);
+        assert!(messages[1].content.contains("- rename\n- again\n"));
+        assert!(messages[1].content.ends_with("## Instruction\nmore"));
+        assert!(!messages[1].content.contains("## Review"));
+
+        set.apply_reply("split".to_string(), parse_edit_blocks(reply));
+        set.changes[1].status = ChangeStatus::Reverted;
+        set.changes[2].comment = "Name it `works_end_to_end`".to_string();
+        assert_eq!(
+            set.review_feedback(),
+            "- src/lib.rs (rejected), edit starting `fn helper() {}`\n\
+            - tests/new.rs (pending), edit starting `#[test]`: Name it `works_end_to_end`\n"
+        );
+        let messages = composer_messages(REVISE_INSTRUCTION, &[], &set);
+        assert!(messages[1]
+            .content
+            .contains("## Review of the Last Round\n"));
+        set.apply_reply(REVISE_INSTRUCTION.to_string(), ComposerReply::default());
+        assert!(set.review_feedback().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use super::models::{AIMessage, AIRole};
+
+/// 锚定在一段代码上的对话所讨论的代码：每次请求时取这段范围的最新内容
+#[derive(Debug, Clone, Default)]
+pub struct ThreadContext {
+    pub file_path: String,
+    pub language: String,
+    /// 范围第一行的行号（从 1 开始）
+    pub start_line: usize,
+    pub code: String,
+}
+
+impl ThreadContext {
+    fn describe(&self) -> String {
+        let end_line = self.start_line + self.code.lines().count().max(1) - 1;
+        format!(
+            "## Code ({}:{}-{})\n```{}\n{}\n```",
+            self.file_path, self.start_line, end_line, self.language, self.code
+        )
+    }
+}
+
+/// 构建内联对话的请求：代码范围作为系统消息，之后是此前的问答和新问题。
+/// 代码可能在两轮之间被修改，所以总是附上当前内容而不是第一次提问时的
+pub fn thread_messages(
+    context: &ThreadContext,
+    history: &[AIMessage],
+    question: &str,
+) -> Vec<AIMessage> {
+    let mut messages = vec![AIMessage {
+        role: AIRole::System,
+        content: format!(
+            "You are discussing one range of code with the developer. The range may change \
+            between questions; always answer about its current content, shown below. Keep \
+            answers short and refer to lines by their number.\n\n{}",
+            context.describe()
+        ),
+    }];
+    messages.extend(history.iter().cloned());
+    messages.push(AIMessage {
+        role: AIRole::User,
+        content: question.to_string(),
+    });
+    messages
+}
+
+/// 构建改写请求：要求模型按对话给出整个范围的新代码，只回复一个代码块
+pub fn rewrite_messages(
+    context: &ThreadContext,
+    history: &[AIMessage],
+    instruction: &str,
+) -> Vec<AIMessage> {
+    let mut messages = thread_messages(context, history, instruction);
+    if let Some(last) = messages.last_mut() {
+        last.content.push_str(
+            "\n\nRewrite the whole range accordingly. Reply with the new code for the range \
+            only, in a single fenced code block, keeping the indentation of the original.",
+        );
+    }
+    messages
+}
+
+/// 回复中第一个代码块的内容；没有代码块时取整个回复
+pub fn extract_code(reply: &str) -> String {
+    let mut lines = reply
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("```"));
+    if lines.next().is_none() {
+        return reply.trim_matches('\n').to_string();
+    }
+    lines
+        .take_while(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_ask_for_the_current_range_and_read_back_one_block() {
+        let context = ThreadContext {
+            file_path: "src/lib.rs".to_string(),
+            language: "rust".to_string(),
+            start_line: 3,
+            code: "fn a() {}\nfn b() {}".to_string(),
+        };
+        let history = [AIMessage {
+            role: AIRole::Assistant,
+            content: "They are empty.".to_string(),
+        }];
+        let messages = rewrite_messages(&context, &history, "Fill them in");
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0]
+            .content
+            .contains("## Code (src/lib.rs:3-4)\n```rust\n"));
+        assert!(messages[2].content.starts_with("Fill them in\n\nRewrite"));
+
+        let reply = "Here you go:\n```rust\n    fn a() { 1 }\n```\nDone.";
+        assert_eq!(extract_code(reply), "    fn a() { 1 }");
+        assert_eq!(extract_code("\nfn a() {}\n"), "fn a() {}");
+    }
+}
@@ -1,7 +1,13 @@
 pub mod ai_actions;
 pub mod ai_engine;
+pub mod code_search;
+pub mod composer;
+pub mod inline_thread;
 pub mod models;
 
 pub use ai_actions::{AIAction, AIPatch, AISuggestion};
 pub use ai_engine::{AIEngine, AIEngineError};
+pub use code_search::{Citation, CodeIndex, CodeSearchAnswer};
+pub use composer::{ChangeStatus, ComposerChange, ComposerReply, WorkingSet};
+pub use inline_thread::ThreadContext;
 pub use models::{AIModel, AIProvider};
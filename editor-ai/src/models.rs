@@ -86,6 +86,44 @@ pub struct AIMessage {
     pub content: String,
 }
 
+/// 消息中的一段：普通文本，或带语言标记的代码块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageBlock {
+    Text(String),
+    Code { language: String, code: String },
+}
+
+impl AIMessage {
+    /// 按 Markdown 的围栏代码块拆分内容，没有闭合的代码块一直延续到结尾
+    pub fn blocks(&self) -> Vec<MessageBlock> {
+        let mut blocks = Vec::new();
+        let mut text: Vec<&str> = Vec::new();
+        let mut lines = self.content.lines();
+        while let Some(line) = lines.next() {
+            let Some(language) = line.trim_start().strip_prefix("```") else {
+                text.push(line);
+                continue;
+            };
+            if !text.is_empty() {
+                blocks.push(MessageBlock::Text(text.join("\n")));
+                text.clear();
+            }
+            let code: Vec<&str> = lines
+                .by_ref()
+                .take_while(|line| !line.trim_start().starts_with("```"))
+                .collect();
+            blocks.push(MessageBlock::Code {
+                language: language.trim().to_string(),
+                code: code.join("\n"),
+            });
+        }
+        if !text.is_empty() {
+            blocks.push(MessageBlock::Text(text.join("\n")));
+        }
+        blocks
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum AIRole {
     #[serde(rename = "system")]
@@ -273,7 +311,7 @@ impl AIContext {
                     project.dependencies.join(", ")
                 ));
             }
-            prompt.push_str("\n");
+            prompt.push('\n');
         }
 
         // 添加文件信息
@@ -286,7 +324,7 @@ impl AIContext {
             prompt.push_str(&format!("Path: {}\n", path.display()));
         }
         prompt.push_str(&format!("Lines: {}\n", self.file_info.line_count));
-        prompt.push_str("\n");
+        prompt.push('\n');
 
         // 添加文件内容
         prompt.push_str("## File Content\n");
@@ -328,9 +366,15 @@ impl AIContext {
         file_path: Option<PathBuf>,
         language: String,
     ) -> Result<Self> {
-        let file_content = buffer.get_text().await;
-        let line_count = buffer.line_count().await;
+        Self::from_snapshot(&buffer.snapshot().await, file_path, language)
+    }
 
+    /// 从快照构建上下文，调用方可以先释放缓冲区锁
+    pub fn from_snapshot(
+        snapshot: &editor_core_text::BufferSnapshot,
+        file_path: Option<PathBuf>,
+        language: String,
+    ) -> Result<Self> {
         let file_info = FileInfo {
             path: file_path.clone(),
             name: file_path
@@ -340,13 +384,13 @@ impl AIContext {
                 .as_ref()
                 .and_then(|p| p.extension().map(|e| e.to_string_lossy().to_string())),
             language,
-            line_count,
+            line_count: snapshot.line_count(),
         };
 
-        let cursor_info = Self::get_cursor_info(buffer).await?;
-        let selection_info = Self::get_selection_info(buffer).await?;
+        let cursor_info = Self::get_cursor_info(snapshot);
+        let selection_info = Self::get_selection_info(snapshot);
 
-        let mut context = Self::new(file_content, file_info, cursor_info);
+        let mut context = Self::new(snapshot.text(), file_info, cursor_info);
 
         if let Some(selection) = selection_info {
             context = context.with_selection(selection);
@@ -359,79 +403,64 @@ impl AIContext {
         Ok(context)
     }
 
-    async fn get_cursor_info(buffer: &editor_core_text::Buffer) -> Result<CursorInfo> {
-        let cursors = buffer.get_cursors();
-        if let Some(cursor) = cursors.first() {
-            // 计算字符位置（需要根据实际 Buffer 实现调整）
-            let position_in_file = buffer.cursor_char_index(*cursor).await;
-
-            Ok(CursorInfo {
-                line: cursor.line,
-                column: cursor.column,
-                position_in_file,
-            })
-        } else {
-            Ok(CursorInfo {
+    fn get_cursor_info(snapshot: &editor_core_text::BufferSnapshot) -> CursorInfo {
+        match snapshot.selections().first() {
+            Some(selection) => CursorInfo {
+                line: selection.active.line,
+                column: selection.active.column,
+                position_in_file: snapshot.cursor_to_char(selection.active),
+            },
+            None => CursorInfo {
                 line: 0,
                 column: 0,
                 position_in_file: 0,
-            })
+            },
         }
     }
 
-    async fn get_selection_info(
-        buffer: &editor_core_text::Buffer,
-    ) -> Result<Option<SelectionInfo>> {
-        let selections = buffer.get_selections();
-        if selections.is_empty() {
-            return Ok(None);
-        }
-
-        let selection = &selections[0];
+    fn get_selection_info(snapshot: &editor_core_text::BufferSnapshot) -> Option<SelectionInfo> {
+        let selection = snapshot.selections().first()?;
         if selection.is_collapsed() {
-            return Ok(None);
+            return None;
         }
 
-        let text = Self::extract_selection_text(buffer, selection).await?;
-
-        Ok(Some(SelectionInfo {
-            text,
+        Some(SelectionInfo {
+            text: snapshot.selected_text(selection),
             start_line: selection.start().line,
             start_column: selection.start().column,
             end_line: selection.end().line,
             end_column: selection.end().column,
             is_multiline: selection.start().line != selection.end().line,
-        }))
+        })
     }
+}
 
-    async fn extract_selection_text(
-        buffer: &editor_core_text::Buffer,
-        selection: &editor_core_text::Selection,
-    ) -> Result<String> {
-        let start = selection.start();
-        let end = selection.end();
-
-        // 获取整个文本文本和对应的字符位置
-        let full_text = buffer.get_text().await;
-        let start_char_idx = buffer.cursor_char_index(start).await;
-        let end_char_idx = buffer.cursor_char_index(end).await;
-        let (start_char_idx, end_char_idx) = if start_char_idx <= end_char_idx {
-            (start_char_idx, end_char_idx)
-        } else {
-            (end_char_idx, start_char_idx)
-        };
-
-        // 将字符索引转换为字节索引
-        let to_byte = |char_idx: usize| -> usize {
-            full_text
-                .char_indices()
-                .nth(char_idx)
-                .map(|(idx, _)| idx)
-                .unwrap_or_else(|| full_text.len())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_split_into_text_and_code_blocks() {
+        let message = AIMessage {
+            role: AIRole::Assistant,
+            content:
+                "Try this:\n```rust\nfn main() {}\n\nlet x = 1;\n```\nThen run:\n```\ncargo run"
+                    .to_string(),
         };
-
-        let start_byte = to_byte(start_char_idx);
-        let end_byte = to_byte(end_char_idx);
-        Ok(full_text[start_byte..end_byte].to_string())
+        assert_eq!(
+            message.blocks(),
+            [
+                MessageBlock::Text("Try this:".to_string()),
+                MessageBlock::Code {
+                    language: "rust".to_string(),
+                    code: "fn main() {}\n\nlet x = 1;".to_string(),
+                },
+                MessageBlock::Text("Then run:".to_string()),
+                MessageBlock::Code {
+                    language: String::new(),
+                    code: "cargo run".to_string(),
+                },
+            ]
+        );
     }
 }
@@ -1,14 +1,114 @@
-use editor_core_text::Buffer;
+use crate::closed_tabs::{ClosedTab, ClosedTabs, Draft};
+use crate::edit_journal::EditJournals;
+use crate::edit_preview::EditPreview;
+use crate::edit_queue::EditQueue;
+use crate::file_info::FileInfo;
+use crate::file_watcher::{FileChange, FileChangeKind};
+use crate::local_history::{LocalHistory, Snapshot};
+use editor_core_text::{
+    Buffer, BufferMemory, BufferSnapshot, Clock, EditLog, LineDiff, LineEnding, LineMap,
+    NavigationHistory, Selection, SystemClock,
+};
+use futures::future::BoxFuture;
 use std::collections::HashMap;
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex, RwLock};
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Default)]
+pub struct BufferMetadata {
+    pub untitled: bool,
+    pub read_only: bool,
+    /// The file on disk is not writable by the current user. Unlike remote buffers,
+    /// these can be unlocked with [`BufferManager::allow_editing`].
+    pub write_protected: bool,
+    /// Made read-only by the user; unlocked like write-protected files.
+    pub locked: bool,
+    pub display_name: Option<String>,
+    pub language: Option<String>,
+    /// Kept leftmost and skipped by "Close Others" / "Close All".
+    pub pinned: bool,
+    /// The file changed on disk while the buffer had unsaved edits. Cleared by saving
+    /// or by [`BufferManager::reload_from_disk`].
+    pub conflicted: bool,
+}
+
+/// Memory held for one open buffer, for the memory diagnostics panel.
+#[derive(Debug, Clone)]
+pub struct BufferMemoryReport {
+    pub path: PathBuf,
+    pub memory: BufferMemory,
+    /// The file as last read or saved, kept to show unsaved changes.
+    pub saved_text_bytes: usize,
+    /// Time since the buffer was last current or edited.
+    pub idle: Duration,
+    /// Caches were dropped and will be rebuilt when needed.
+    pub evicted: bool,
+}
+
+/// When a buffer was last seen in use, for evicting the caches of idle buffers.
+#[derive(Debug, Clone, Copy)]
+struct BufferUsage {
+    version: usize,
+    touched: Instant,
+    evicted: bool,
+}
+
+/// Edits made by AI agents or workflows rather than the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentEditEvent {
+    /// `line` is the 0-based line where the edit starts.
+    Edited {
+        agent: String,
+        path: PathBuf,
+        line: usize,
+    },
+    Finished {
+        agent: String,
+    },
+}
+
+/// What became of an open buffer after its file changed on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiskChangeEvent {
+    /// The buffer had no unsaved edits and was reloaded; `map` moves lines to where they went.
+    Reloaded { path: PathBuf, map: LineMap },
+    /// The buffer has unsaved edits and the file no longer matches what they were made on.
+    Conflicted { path: PathBuf },
+    /// The file is gone; the buffer keeps its text and can be saved again.
+    Deleted { path: PathBuf },
+}
+
+/// Where the selections were in a file that was left for another one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavigationEntry {
+    pub path: PathBuf,
+    pub selections: Vec<Selection>,
+}
+
 #[derive(Debug, Clone)]
 pub struct BufferManager {
     buffers: Arc<RwLock<HashMap<PathBuf, Arc<Mutex<Buffer>>>>>,
     current_buffer: Arc<RwLock<Option<PathBuf>>>,
+    metadata: Arc<RwLock<HashMap<PathBuf, BufferMetadata>>>,
+    agent_events: broadcast::Sender<AgentEditEvent>,
+    history: Option<LocalHistory>,
+    closed_tabs: Arc<Mutex<ClosedTabs>>,
+    tab_order: Arc<RwLock<Vec<PathBuf>>>,
+    /// File content as last read from or written to disk, to diff unsaved edits against.
+    saved_texts: Arc<RwLock<HashMap<PathBuf, Arc<str>>>>,
+    usage: Arc<RwLock<HashMap<PathBuf, BufferUsage>>>,
+    clock: Arc<dyn Clock>,
+    /// Record an edit log for every buffer opened from now on.
+    record_edits: bool,
+    journals: Option<EditJournals>,
+    /// Files left by switching buffers; jumps within a buffer are kept by the buffer.
+    navigation: Arc<Mutex<NavigationHistory<NavigationEntry>>>,
+    /// Taken synchronously so edits keep the order they were submitted in.
+    edit_queues: Arc<std::sync::Mutex<HashMap<PathBuf, EditQueue>>>,
 }
 
 impl BufferManager {
@@ -16,36 +116,354 @@ impl BufferManager {
         Self {
             buffers: Arc::new(RwLock::new(HashMap::new())),
             current_buffer: Arc::new(RwLock::new(None)),
+            metadata: Arc::new(RwLock::new(HashMap::new())),
+            agent_events: broadcast::channel(256).0,
+            history: None,
+            closed_tabs: Arc::new(Mutex::new(ClosedTabs::default())),
+            tab_order: Arc::new(RwLock::new(Vec::new())),
+            saved_texts: Arc::new(RwLock::new(HashMap::new())),
+            usage: Arc::new(RwLock::new(HashMap::new())),
+            clock: Arc::new(SystemClock),
+            record_edits: false,
+            journals: None,
+            navigation: Arc::new(Mutex::new(NavigationHistory::default())),
+            edit_queues: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Give every buffer opened from now on `clock`, e.g. a manual one in tests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record every edit to the buffers opened from now on, for bug reports.
+    pub fn with_edit_log(mut self, enabled: bool) -> Self {
+        self.record_edits = enabled;
+        self
+    }
+
+    fn buffer_from_text(&self, text: &str) -> Buffer {
+        let mut buffer = Buffer::from_text(text);
+        buffer.set_clock(self.clock.clone());
+        if self.record_edits {
+            let model = buffer.text_model();
+            model.set_edit_log(Some(EditLog::new(text, model.version())));
         }
+        buffer
+    }
+
+    /// Journal the edits to every file opened from now on, to replay them or recover
+    /// unsaved text after a crash.
+    pub fn with_edit_journals(mut self, journals: EditJournals) -> Self {
+        self.journals = Some(journals);
+        self
+    }
+
+    pub fn edit_journals(&self) -> Option<&EditJournals> {
+        self.journals.as_ref()
+    }
+
+    /// Keep a local snapshot of every file on save.
+    pub fn with_local_history(mut self, history: LocalHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    pub fn local_history(&self) -> Option<&LocalHistory> {
+        self.history.as_ref()
     }
 
     pub async fn open_file(&self, file_path: &Path) -> Result<(), std::io::Error> {
         let content = std::fs::read_to_string(file_path)?;
-        let buffer = Arc::new(Mutex::new(Buffer::from_text(&content)));
+        let mut buffer = self.buffer_from_text(&content);
+        self.remember_saved(file_path, &content).await;
+        // Like local history, a journal that can't be started must not stop the file
+        // from opening.
+        if let Some(journals) = &self.journals {
+            let model = buffer.text_model();
+            if let Ok(journal) = journals.start(file_path, &content, model.version()) {
+                model.set_journal(Some(journal));
+            }
+        }
+
+        let write_protected = FileInfo::read(file_path)
+            .map(|info| !info.writable)
+            .unwrap_or(false);
+        let mut metadata = self.metadata.write().await;
+        if write_protected {
+            let meta = metadata.entry(file_path.to_path_buf()).or_default();
+            meta.read_only = true;
+            meta.write_protected = true;
+        } else if let Some(meta) = metadata.get_mut(file_path) {
+            if meta.write_protected {
+                meta.read_only = false;
+                meta.write_protected = false;
+            }
+        }
+        buffer.set_read_only(metadata.get(file_path).is_some_and(|meta| meta.read_only));
+        drop(metadata);
 
         let mut buffers = self.buffers.write().await;
-        buffers.insert(file_path.to_path_buf(), buffer);
+        buffers.insert(file_path.to_path_buf(), Arc::new(Mutex::new(buffer)));
+        drop(buffers);
+        self.make_current(file_path).await;
 
-        let mut current = self.current_buffer.write().await;
-        *current = Some(file_path.to_path_buf());
+        Ok(())
+    }
+
+    pub async fn is_write_protected(&self, file_path: &Path) -> bool {
+        let metadata = self.metadata.read().await;
+        metadata
+            .get(file_path)
+            .map(|meta| meta.write_protected)
+            .unwrap_or(false)
+    }
 
+    /// Unlock a write-protected or locked buffer for editing. Saving may still fail on disk.
+    pub async fn allow_editing(&self, file_path: &Path) -> Result<(), std::io::Error> {
+        let mut metadata = self.metadata.write().await;
+        match metadata.get_mut(file_path) {
+            Some(meta) if meta.write_protected || meta.locked => {
+                meta.read_only = false;
+                meta.locked = false;
+            }
+            Some(meta) if meta.read_only => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "Buffer is read-only",
+                ))
+            }
+            _ => {}
+        }
+        drop(metadata);
+        if let Some(handle) = self.get_buffer(file_path).await {
+            handle.lock().await.set_read_only(false);
+        }
         Ok(())
     }
 
+    /// The edits recorded for `file_path` so far, if recording is on for it.
+    pub async fn edit_log(&self, file_path: &Path) -> Option<EditLog> {
+        let handle = self.get_buffer(file_path).await?;
+        let model = handle.lock().await.text_model();
+        model.edit_log()
+    }
+
+    /// Start recording edits to `file_path` from its current text, for a buffer opened
+    /// before recording was turned on. Returns false if there is no such buffer.
+    pub async fn start_edit_log(&self, file_path: &Path) -> bool {
+        let Some(handle) = self.get_buffer(file_path).await else {
+            return false;
+        };
+        let model = handle.lock().await.text_model();
+        model.start_edit_log().await;
+        true
+    }
+
+    /// This session's journal of `file_path`, read back from disk.
+    pub async fn journal(&self, file_path: &Path) -> Result<Option<EditLog>, std::io::Error> {
+        if let Some(handle) = self.get_buffer(file_path).await {
+            handle.lock().await.text_model().flush_journal()?;
+        }
+        match &self.journals {
+            Some(journals) => journals.read(file_path),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces the text of `file_path` with where its crashed journal left it, as one
+    /// undoable edit that leaves the buffer unsaved. Replays as far as the journal
+    /// fits the file; returns false when there was no crashed journal.
+    pub async fn recover_from_journal(&self, file_path: &Path) -> Result<bool, std::io::Error> {
+        let Some(journals) = &self.journals else {
+            return Ok(false);
+        };
+        let Some(log) = journals.crashed(file_path)? else {
+            return Ok(false);
+        };
+        if self.get_buffer(file_path).await.is_none() {
+            self.open_file(file_path).await?;
+        }
+        let handle = self
+            .get_buffer(file_path)
+            .await
+            .ok_or_else(|| std::io::Error::other("Buffer not found"))?;
+        let (text, _) = log.final_text();
+        let mut buffer = handle.lock().await;
+        let len = buffer.text_model().len().await;
+        buffer.replace_range(0, len, &text).await;
+        drop(buffer);
+        journals.dismiss_crashed(file_path)?;
+        Ok(true)
+    }
+
+    /// Make a buffer read-only until [`BufferManager::allow_editing`] unlocks it.
+    pub async fn lock_editing(&self, file_path: &Path) {
+        let mut metadata = self.metadata.write().await;
+        let meta = metadata.entry(file_path.to_path_buf()).or_default();
+        if !meta.read_only {
+            meta.read_only = true;
+            meta.locked = true;
+        }
+        drop(metadata);
+        if let Some(handle) = self.get_buffer(file_path).await {
+            handle.lock().await.set_read_only(true);
+        }
+    }
+
     pub async fn create_new_buffer(&self) -> PathBuf {
         let temp_path = PathBuf::from(format!("untitled-{}", Uuid::new_v4()));
-        let buffer = Arc::new(Mutex::new(Buffer::new()));
+        let buffer = Arc::new(Mutex::new(self.buffer_from_text("")));
 
         let mut buffers = self.buffers.write().await;
         buffers.insert(temp_path.clone(), buffer);
 
-        let mut current = self.current_buffer.write().await;
-        *current = Some(temp_path.clone());
+        let mut metadata = self.metadata.write().await;
+        metadata.insert(
+            temp_path.clone(),
+            BufferMetadata {
+                untitled: true,
+                ..Default::default()
+            },
+        );
+
+        drop((buffers, metadata));
+        self.make_current(&temp_path).await;
 
         temp_path
     }
 
+    /// Open fetched remote content as a read-only buffer keyed and named by its URL.
+    pub async fn open_remote(&self, url: &str, content: &str) -> PathBuf {
+        let key = PathBuf::from(url);
+        let mut buffer = self.buffer_from_text(content);
+        buffer.set_read_only(true);
+        let buffer = Arc::new(Mutex::new(buffer));
+
+        let mut buffers = self.buffers.write().await;
+        buffers.insert(key.clone(), buffer);
+
+        let mut metadata = self.metadata.write().await;
+        metadata.insert(
+            key.clone(),
+            BufferMetadata {
+                read_only: true,
+                display_name: Some(url.to_string()),
+                ..Default::default()
+            },
+        );
+
+        drop((buffers, metadata));
+        self.make_current(&key).await;
+
+        key
+    }
+
+    pub async fn is_read_only(&self, file_path: &Path) -> bool {
+        let metadata = self.metadata.read().await;
+        metadata
+            .get(file_path)
+            .map(|meta| meta.read_only)
+            .unwrap_or(false)
+    }
+
+    pub async fn is_untitled(&self, file_path: &Path) -> bool {
+        let metadata = self.metadata.read().await;
+        metadata
+            .get(file_path)
+            .map(|meta| meta.untitled)
+            .unwrap_or(false)
+    }
+
+    /// Rename the display name of an untitled buffer. Saved files keep their file name.
+    pub async fn rename_untitled(
+        &self,
+        file_path: &Path,
+        name: &str,
+    ) -> Result<(), std::io::Error> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Buffer name cannot be empty",
+            ));
+        }
+
+        let mut metadata = self.metadata.write().await;
+        match metadata.get_mut(file_path) {
+            Some(meta) if meta.untitled => {
+                meta.display_name = Some(name.to_string());
+                Ok(())
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Only untitled buffers can be renamed",
+            )),
+        }
+    }
+
+    /// Override the language used for highlighting, LSP and AI prompts.
+    pub async fn set_language(
+        &self,
+        file_path: &Path,
+        language: &str,
+    ) -> Result<(), std::io::Error> {
+        if !self.buffers.read().await.contains_key(file_path) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Buffer not found",
+            ));
+        }
+
+        let language = language.trim().to_lowercase();
+        let mut metadata = self.metadata.write().await;
+        let meta = metadata.entry(file_path.to_path_buf()).or_default();
+        meta.language = if language.is_empty() {
+            None
+        } else {
+            Some(language)
+        };
+        Ok(())
+    }
+
+    pub async fn display_name(&self, file_path: &Path) -> String {
+        let metadata = self.metadata.read().await;
+        if let Some(name) = metadata
+            .get(file_path)
+            .and_then(|meta| meta.display_name.clone())
+        {
+            return name;
+        }
+        if metadata
+            .get(file_path)
+            .map(|meta| meta.untitled)
+            .unwrap_or(false)
+        {
+            return "Untitled".to_string();
+        }
+        file_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| file_path.display().to_string())
+    }
+
+    pub async fn language(&self, file_path: &Path) -> String {
+        let metadata = self.metadata.read().await;
+        metadata
+            .get(file_path)
+            .and_then(|meta| meta.language.clone())
+            .unwrap_or_else(|| language_from_path(file_path).to_string())
+    }
+
     pub async fn save_file(&self, file_path: &Path) -> Result<(), std::io::Error> {
+        if self.is_read_only(file_path).await {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "Buffer is read-only",
+            ));
+        }
+
         let buffer_handle = {
             let buffers = self.buffers.read().await;
             buffers.get(file_path).cloned()
@@ -53,10 +471,156 @@ impl BufferManager {
 
         if let Some(buffer_handle) = buffer_handle {
             let mut buffer = buffer_handle.lock().await;
-            let content = buffer.get_text().await;
+            // Edits that bypassed the buffer's line ending, e.g. from a language server,
+            // are written with it too.
+            let text = buffer.get_text().await;
+            let content = buffer.line_ending().normalize(&text).into_owned();
             std::fs::write(file_path, &content)?;
             buffer.mark_clean();
+            self.remember_saved(file_path, &content).await;
+            // The saved text is on disk now, so its edits need no journal.
+            if let Some(journals) = &self.journals {
+                let model = buffer.text_model();
+                if model.journal_path().is_some() {
+                    model.set_journal(None);
+                    model.set_journal(journals.restart(file_path, &text, model.version()).ok());
+                }
+            }
+            if let Some(meta) = self.metadata.write().await.get_mut(file_path) {
+                meta.conflicted = false;
+            }
+
+            // A failed snapshot must never fail the save itself.
+            if let Some(history) = &self.history {
+                let _ = history.record(file_path, &content);
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-reads an open file after it changed on disk, keeping selections on their lines.
+    ///
+    /// Buffers with unsaved edits are left alone. Returns the line mapping when the
+    /// content actually changed.
+    pub async fn reload_file(&self, file_path: &Path) -> Result<Option<LineMap>, std::io::Error> {
+        let Some(handle) = self.get_buffer(file_path).await else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(file_path)?;
+
+        let reloaded = self.edit_queue(file_path).submit(async {
+            let mut buffer = handle.lock().await;
+            if buffer.is_dirty() {
+                return None;
+            }
+            let map = buffer.reload(&content).await;
+            buffer.mark_clean();
+            Some(map)
+        });
+        let Some(map) = reloaded.await else {
+            return Ok(None);
+        };
+        self.remember_saved(file_path, &content).await;
+        Ok((!map.is_identity()).then_some(map))
+    }
+
+    /// Brings open buffers in line with files that changed on disk, e.g. as reported by
+    /// a [`crate::FileWatcher`]. Clean buffers are reloaded; buffers with unsaved edits
+    /// are kept as they are and marked conflicted when the file really differs from the
+    /// text they started from. Changes to files that aren't open are ignored.
+    pub async fn apply_file_changes(&self, changes: &[FileChange]) -> Vec<DiskChangeEvent> {
+        let mut events = Vec::new();
+        for change in changes {
+            let Some(handle) = self.get_buffer(&change.path).await else {
+                continue;
+            };
+            let path = change.path.clone();
+            if change.kind == FileChangeKind::Deleted {
+                events.push(DiskChangeEvent::Deleted { path });
+                continue;
+            }
+            // A file that can't be read, e.g. while it is being written, is picked up
+            // again by its next change.
+            if !handle.lock().await.is_dirty() {
+                if let Ok(Some(map)) = self.reload_file(&path).await {
+                    events.push(DiskChangeEvent::Reloaded { path, map });
+                }
+                continue;
+            }
+
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            // Our own saves change the file too, but leave it equal to the saved text.
+            let saved = self.saved_texts.read().await.get(&path).cloned();
+            if saved.as_deref() == Some(content.as_str()) {
+                continue;
+            }
+            self.metadata
+                .write()
+                .await
+                .entry(path.clone())
+                .or_default()
+                .conflicted = true;
+            events.push(DiskChangeEvent::Conflicted { path });
+        }
+        events
+    }
+
+    pub async fn is_conflicted(&self, file_path: &Path) -> bool {
+        self.metadata
+            .read()
+            .await
+            .get(file_path)
+            .is_some_and(|meta| meta.conflicted)
+    }
+
+    /// Replaces the buffer with the file on disk, dropping unsaved edits; the reload can
+    /// still be undone. Resolves a conflict in favor of the file.
+    pub async fn reload_from_disk(
+        &self,
+        file_path: &Path,
+    ) -> Result<Option<LineMap>, std::io::Error> {
+        let handle = self
+            .get_buffer(file_path)
+            .await
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Buffer not found"))?;
+        let content = std::fs::read_to_string(file_path)?;
+        let map = self
+            .edit_queue(file_path)
+            .submit(async {
+                let mut buffer = handle.lock().await;
+                let map = buffer.reload(&content).await;
+                buffer.mark_clean();
+                map
+            })
+            .await;
+        self.remember_saved(file_path, &content).await;
+        if let Some(meta) = self.metadata.write().await.get_mut(file_path) {
+            meta.conflicted = false;
         }
+        Ok((!map.is_identity()).then_some(map))
+    }
+
+    /// Replace the buffer content with a snapshot. The buffer is left dirty until saved.
+    pub async fn restore_snapshot(&self, snapshot: &Snapshot) -> Result<(), std::io::Error> {
+        let history = self.history.as_ref().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::Unsupported, "Local history is disabled")
+        })?;
+        let content = history.read(snapshot)?;
+
+        let handle = match self.get_buffer(&snapshot.file_path).await {
+            Some(handle) => handle,
+            None => {
+                self.open_file(&snapshot.file_path).await?;
+                self.get_buffer(&snapshot.file_path)
+                    .await
+                    .ok_or_else(|| std::io::Error::other("Buffer not found"))?
+            }
+        };
+        self.edit_queue(&snapshot.file_path)
+            .submit(async { handle.lock().await.set_text(&content).await })
+            .await;
         Ok(())
     }
 
@@ -74,7 +638,22 @@ impl BufferManager {
 
     pub async fn close_file(&self, file_path: &Path) -> Result<(), std::io::Error> {
         let mut buffers = self.buffers.write().await;
-        buffers.remove(file_path);
+        let closed = buffers.remove(file_path);
+        if let (Some(journals), Some(closed)) = (&self.journals, closed) {
+            closed.lock().await.text_model().set_journal(None);
+            journals.discard(file_path)?;
+        }
+        self.metadata.write().await.remove(file_path);
+        self.saved_texts.write().await.remove(file_path);
+        self.usage.write().await.remove(file_path);
+        self.navigation
+            .lock()
+            .await
+            .retain(|entry| entry.path != file_path);
+        self.edit_queues
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(file_path);
 
         let mut current = self.current_buffer.write().await;
         if current.as_ref() == Some(&file_path.to_path_buf()) {
@@ -84,6 +663,211 @@ impl BufferManager {
         Ok(())
     }
 
+    /// Moves the open buffers of `from`, or of the files under it when it is a
+    /// directory, to where the file now is after being renamed or moved on disk. Text,
+    /// unsaved edits, undo history and tab position are kept. Returns the old and new
+    /// path of every moved buffer, e.g. to tell language servers.
+    pub async fn rename_path(&self, from: &Path, to: &Path) -> Vec<(PathBuf, PathBuf)> {
+        let mut buffers = self.buffers.write().await;
+        let moved: Vec<(PathBuf, PathBuf)> = buffers
+            .keys()
+            .filter_map(|path| {
+                let rest = path.strip_prefix(from).ok()?;
+                let target = if rest.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(rest)
+                };
+                Some((path.clone(), target))
+            })
+            .collect();
+        if moved.is_empty() {
+            return moved;
+        }
+
+        for (old, new) in &moved {
+            let handle = buffers.remove(old).expect("collected from the map");
+            if let Some(journals) = &self.journals {
+                // The journal is kept per path, so it starts over from the current text.
+                let buffer = handle.lock().await;
+                let model = buffer.text_model();
+                model.set_journal(None);
+                let _ = journals.discard(old);
+                let text = buffer.get_text().await;
+                if let Ok(journal) = journals.start(new, &text, model.version()) {
+                    model.set_journal(Some(journal));
+                }
+            }
+            buffers.insert(new.clone(), handle);
+        }
+        drop(buffers);
+
+        let rename = |path: &mut PathBuf| {
+            if let Some((_, new)) = moved.iter().find(|(old, _)| old == path) {
+                *path = new.clone();
+            }
+        };
+        fn rekey<T>(map: &mut HashMap<PathBuf, T>, moved: &[(PathBuf, PathBuf)]) {
+            for (old, new) in moved {
+                if let Some(value) = map.remove(old) {
+                    map.insert(new.clone(), value);
+                }
+            }
+        }
+        rekey(&mut *self.metadata.write().await, &moved);
+        rekey(&mut *self.saved_texts.write().await, &moved);
+        rekey(&mut *self.usage.write().await, &moved);
+        rekey(
+            &mut self.edit_queues.lock().unwrap_or_else(|e| e.into_inner()),
+            &moved,
+        );
+        self.tab_order.write().await.iter_mut().for_each(rename);
+        self.navigation
+            .lock()
+            .await
+            .for_each_mut(|entry| rename(&mut entry.path));
+        if let Some(current) = self.current_buffer.write().await.as_mut() {
+            rename(current);
+        }
+        moved
+    }
+
+    /// Closes the buffers of `path`, or of the files under it when it is a directory,
+    /// e.g. after it was deleted. Returns the closed paths.
+    pub async fn close_files_under(&self, path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+        let closed: Vec<PathBuf> = self
+            .buffers
+            .read()
+            .await
+            .keys()
+            .filter(|open| open.starts_with(path))
+            .cloned()
+            .collect();
+        for open in &closed {
+            self.close_file(open).await?;
+        }
+        Ok(closed)
+    }
+
+    /// Close a buffer and remember it for [`BufferManager::reopen_closed_tab`].
+    /// Untitled and unsaved content is kept as a draft.
+    pub async fn close_tab(
+        &self,
+        file_path: &Path,
+        scroll_line: usize,
+    ) -> Result<(), std::io::Error> {
+        let tab = self.tab_state(file_path, scroll_line).await?;
+        self.close_file(file_path).await?;
+        self.closed_tabs.lock().await.push(tab);
+        Ok(())
+    }
+
+    async fn tab_state(
+        &self,
+        file_path: &Path,
+        scroll_line: usize,
+    ) -> Result<ClosedTab, std::io::Error> {
+        let handle = self
+            .get_buffer(file_path)
+            .await
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Buffer not found"))?;
+        let meta = self
+            .metadata
+            .read()
+            .await
+            .get(file_path)
+            .cloned()
+            .unwrap_or_default();
+
+        let buffer = handle.lock().await;
+        let draft = if meta.untitled || buffer.is_dirty() {
+            Some(Draft {
+                text: buffer.get_text().await,
+                display_name: meta.display_name,
+                language: meta.language,
+            })
+        } else {
+            None
+        };
+        Ok(ClosedTab {
+            path: file_path.to_path_buf(),
+            untitled: meta.untitled,
+            selections: buffer.get_selections().to_vec(),
+            scroll_line,
+            draft,
+        })
+    }
+
+    /// The open tabs in tab order, in the form [`BufferManager::restore_tab`] takes, for
+    /// checkpointing the session. Read-only buffers such as fetched remote content are
+    /// left out. Scroll positions are kept by the view; tabs missing from
+    /// `scroll_lines` are saved scrolled to the top.
+    pub async fn open_tab_states(&self, scroll_lines: &HashMap<PathBuf, usize>) -> Vec<ClosedTab> {
+        let mut tabs = Vec::new();
+        for path in self.get_open_files().await {
+            if self.is_read_only(&path).await {
+                continue;
+            }
+            let scroll_line = scroll_lines.get(&path).copied().unwrap_or_default();
+            if let Ok(tab) = self.tab_state(&path, scroll_line).await {
+                tabs.push(tab);
+            }
+        }
+        tabs
+    }
+
+    /// Open `tab` again with its draft and selections and make it current, e.g. from
+    /// a session checkpoint. Untitled tabs get a fresh path; the returned tab carries
+    /// the path it was opened under.
+    pub async fn restore_tab(&self, mut tab: ClosedTab) -> Result<ClosedTab, std::io::Error> {
+        if tab.untitled {
+            tab.path = self.create_new_buffer().await;
+        } else {
+            self.open_file(&tab.path).await?;
+        }
+        self.apply_tab_state(&tab, true).await?;
+        Ok(tab)
+    }
+
+    /// Reopen the most recently closed tab and make it current. The returned tab
+    /// carries the path it was reopened under.
+    pub async fn reopen_closed_tab(&self) -> Result<Option<ClosedTab>, std::io::Error> {
+        let Some(tab) = self.closed_tabs.lock().await.pop() else {
+            return Ok(None);
+        };
+
+        if !tab.untitled && self.get_buffer(&tab.path).await.is_some() {
+            // Opened again since it was closed; what is in the buffer now wins.
+            self.set_current_buffer(&tab.path).await?;
+            self.apply_tab_state(&tab, false).await?;
+            return Ok(Some(tab));
+        }
+        self.restore_tab(tab).await.map(Some)
+    }
+
+    async fn apply_tab_state(
+        &self,
+        tab: &ClosedTab,
+        restore_draft: bool,
+    ) -> Result<(), std::io::Error> {
+        let handle = self
+            .get_buffer(&tab.path)
+            .await
+            .ok_or_else(|| std::io::Error::other("Buffer not found"))?;
+        let mut buffer = handle.lock().await;
+        if let Some(draft) = tab.draft.as_ref().filter(|_| restore_draft) {
+            if buffer.get_text().await != draft.text {
+                buffer.set_text(&draft.text).await;
+            }
+            let mut metadata = self.metadata.write().await;
+            let meta = metadata.entry(tab.path.clone()).or_default();
+            meta.display_name = draft.display_name.clone();
+            meta.language = draft.language.clone();
+        }
+        buffer.restore_selections(tab.selections.clone()).await;
+        Ok(())
+    }
+
     pub async fn get_current_buffer(&self) -> Option<Arc<Mutex<Buffer>>> {
         let current = self.current_buffer.read().await;
         let buffers = self.buffers.read().await;
@@ -94,8 +878,8 @@ impl BufferManager {
     pub async fn set_current_buffer(&self, file_path: &Path) -> Result<(), std::io::Error> {
         let buffers = self.buffers.read().await;
         if buffers.contains_key(file_path) {
-            let mut current = self.current_buffer.write().await;
-            *current = Some(file_path.to_path_buf());
+            drop(buffers);
+            self.make_current(file_path).await;
             Ok(())
         } else {
             Err(std::io::Error::new(
@@ -105,11 +889,390 @@ impl BufferManager {
         }
     }
 
+    /// Makes `file_path` current, remembering where the previous buffer was left so
+    /// [`BufferManager::navigate`] can go back to it.
+    async fn make_current(&self, file_path: &Path) {
+        let previous = self.get_current_file_path().await;
+        if let Some(left) = self
+            .location(previous.filter(|path| path != file_path))
+            .await
+        {
+            self.navigation.lock().await.push(left);
+        }
+        *self.current_buffer.write().await = Some(file_path.to_path_buf());
+    }
+
+    async fn location(&self, path: Option<PathBuf>) -> Option<NavigationEntry> {
+        let path = path?;
+        let handle = self.get_buffer(&path).await?;
+        let selections = handle.lock().await.get_selections().to_vec();
+        Some(NavigationEntry { path, selections })
+    }
+
+    /// Goes back to where the caret was before the last jump, or forward again after
+    /// going back: within the current buffer first, then to the files left before it.
+    /// Returns the file that is current afterwards, or `None` if there was nowhere to go.
+    pub async fn navigate(&self, forward: bool) -> Option<PathBuf> {
+        let current = self.get_current_file_path().await?;
+        let handle = self.get_buffer(&current).await?;
+        let moved = self
+            .edit_queue(&current)
+            .submit(async { handle.lock().await.navigate_selections(forward).await });
+        if moved.await {
+            return Some(current);
+        }
+
+        let here = self.location(Some(current)).await?;
+        let place = {
+            let mut navigation = self.navigation.lock().await;
+            if forward {
+                navigation.forward(here)
+            } else {
+                navigation.back(here)
+            }
+        }?;
+        let handle = self.get_buffer(&place.path).await?;
+        handle
+            .lock()
+            .await
+            .restore_selections(place.selections)
+            .await;
+        *self.current_buffer.write().await = Some(place.path.clone());
+        Some(place.path)
+    }
+
     pub async fn get_buffer(&self, file_path: &Path) -> Option<Arc<Mutex<Buffer>>> {
         let buffers = self.buffers.read().await;
         buffers.get(file_path).cloned()
     }
 
+    /// The queue that orders the edits to `file_path`, shared by everyone editing it.
+    pub fn edit_queue(&self, file_path: &Path) -> EditQueue {
+        self.edit_queues
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(file_path.to_path_buf())
+            .or_default()
+            .clone()
+    }
+
+    /// Queues `edit` behind every edit submitted for `file_path` so far and runs it on
+    /// the buffer when its turn comes. Resolves to `None` if the buffer was closed by then.
+    pub fn submit_edit<F, R>(&self, file_path: &Path, edit: F) -> impl Future<Output = Option<R>>
+    where
+        F: for<'a> FnOnce(&'a mut Buffer) -> BoxFuture<'a, R>,
+    {
+        let buffers = self.buffers.clone();
+        let path = file_path.to_path_buf();
+        self.edit_queue(file_path).submit(async move {
+            let handle = buffers.read().await.get(&path).cloned()?;
+            let mut buffer = handle.lock().await;
+            Some(edit(&mut buffer).await)
+        })
+    }
+
+    /// Open a second view of an already open file. The returned buffer shares the
+    /// text model with the registered one, so edits in either are visible in both.
+    pub async fn duplicate_buffer(&self, file_path: &Path) -> Option<Arc<Mutex<Buffer>>> {
+        let handle = self.get_buffer(file_path).await?;
+        let duplicate = handle.lock().await.duplicate();
+        Some(Arc::new(Mutex::new(duplicate)))
+    }
+
+    pub fn subscribe_agent_edits(&self) -> broadcast::Receiver<AgentEditEvent> {
+        self.agent_events.subscribe()
+    }
+
+    /// Replace the first occurrence of `old_text` in a file on behalf of an agent.
+    /// The file is loaded in the background if needed; the current buffer is unchanged.
+    /// Returns the 0-based line where the edit starts.
+    pub async fn apply_agent_edit(
+        &self,
+        agent: &str,
+        file_path: &Path,
+        old_text: &str,
+        new_text: &str,
+    ) -> Result<usize, std::io::Error> {
+        if old_text.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Agent edit has no anchor text",
+            ));
+        }
+        if self.is_read_only(file_path).await {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "Buffer is read-only",
+            ));
+        }
+
+        let handle = self.background_buffer(file_path).await?;
+        let edited = self.edit_queue(file_path).submit(async {
+            let mut buffer = handle.lock().await;
+            let text = buffer.get_text().await;
+            let byte_idx = text.find(old_text).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "Agent edit anchor not found in buffer",
+                )
+            })?;
+            let prefix = &text[..byte_idx];
+            // One undo step per agent edit, never merged with the user's typing.
+            buffer.begin_transaction();
+            buffer
+                .replace_range(prefix.chars().count(), old_text.chars().count(), new_text)
+                .await;
+            buffer.end_transaction();
+            Ok::<_, std::io::Error>(prefix.matches('\n').count())
+        });
+        let line = edited.await?;
+
+        let _ = self.agent_events.send(AgentEditEvent::Edited {
+            agent: agent.to_string(),
+            path: file_path.to_path_buf(),
+            line,
+        });
+        Ok(line)
+    }
+
+    /// The buffer for `file_path`, loading the file without making it current if needed.
+    async fn background_buffer(
+        &self,
+        file_path: &Path,
+    ) -> Result<Arc<Mutex<Buffer>>, std::io::Error> {
+        if let Some(handle) = self.get_buffer(file_path).await {
+            return Ok(handle);
+        }
+        let content = std::fs::read_to_string(file_path)?;
+        let handle = Arc::new(Mutex::new(self.buffer_from_text(&content)));
+        self.remember_saved(file_path, &content).await;
+        let mut buffers = self.buffers.write().await;
+        Ok(buffers
+            .entry(file_path.to_path_buf())
+            .or_insert(handle)
+            .clone())
+    }
+
+    async fn remember_saved(&self, file_path: &Path, content: &str) {
+        self.saved_texts
+            .write()
+            .await
+            .insert(file_path.to_path_buf(), Arc::from(content));
+    }
+
+    /// Line changes from the file as last read or saved to the buffer's current text.
+    /// Line endings are ignored. `None` for buffers without a file on disk.
+    pub async fn unsaved_changes(&self, file_path: &Path) -> Option<LineDiff> {
+        let cached = self.saved_texts.read().await.get(file_path).cloned();
+        let saved = match cached {
+            Some(saved) => saved,
+            // Evicted while the buffer was idle and clean, so the file still matches it.
+            None if file_path.is_file() => {
+                let content = std::fs::read_to_string(file_path).ok()?;
+                self.remember_saved(file_path, &content).await;
+                Arc::from(content)
+            }
+            None => return None,
+        };
+        let handle = self.get_buffer(file_path).await?;
+        let text = handle.lock().await.get_text().await;
+        Some(LineDiff::new(
+            &LineEnding::Lf.normalize(&saved),
+            &LineEnding::Lf.normalize(&text),
+        ))
+    }
+
+    /// Records which buffers are in use: the current one and any edited since the last
+    /// call. Returns the usage of every open buffer.
+    async fn sweep_usage(&self) -> HashMap<PathBuf, BufferUsage> {
+        let now = self.clock.now();
+        let current = self.current_buffer.read().await.clone();
+        let handles: Vec<_> = self
+            .buffers
+            .read()
+            .await
+            .iter()
+            .map(|(path, handle)| (path.clone(), handle.clone()))
+            .collect();
+        let mut versions = Vec::with_capacity(handles.len());
+        for (path, handle) in handles {
+            let version = handle.lock().await.text_model().version();
+            versions.push((path, version));
+        }
+
+        let mut usage = self.usage.write().await;
+        usage.retain(|path, _| versions.iter().any(|(open, _)| open == path));
+        for (path, version) in versions {
+            let in_use = current.as_ref() == Some(&path);
+            let entry = usage.entry(path).or_insert(BufferUsage {
+                version,
+                touched: now,
+                evicted: false,
+            });
+            if in_use || entry.version != version {
+                *entry = BufferUsage {
+                    version,
+                    touched: now,
+                    evicted: false,
+                };
+            }
+        }
+        usage.clone()
+    }
+
+    /// Memory held by every open buffer, in tab order.
+    pub async fn memory_report(&self) -> Vec<BufferMemoryReport> {
+        let usage = self.sweep_usage().await;
+        let now = self.clock.now();
+        let mut report = Vec::new();
+        for path in self.get_open_files().await {
+            let Some(handle) = self.get_buffer(&path).await else {
+                continue;
+            };
+            let memory = handle.lock().await.memory_usage().await;
+            let saved_text_bytes = self
+                .saved_texts
+                .read()
+                .await
+                .get(&path)
+                .map_or(0, |text| text.len());
+            let (idle, evicted) = usage.get(&path).map_or((Duration::ZERO, false), |entry| {
+                (now.saturating_duration_since(entry.touched), entry.evicted)
+            });
+            report.push(BufferMemoryReport {
+                path,
+                memory,
+                saved_text_bytes,
+                idle,
+                evicted,
+            });
+        }
+        report
+    }
+
+    /// Drops the caches of clean buffers that have not been current or edited for
+    /// `idle`: the saved copy of the file, which is read back from disk when needed,
+    /// and the rope's spare room. The text and undo history are kept. Returns the
+    /// buffers that were evicted.
+    pub async fn evict_idle_caches(&self, idle: Duration) -> Vec<PathBuf> {
+        let now = self.clock.now();
+        let mut evicted = Vec::new();
+        for (path, entry) in self.sweep_usage().await {
+            if entry.evicted || now.saturating_duration_since(entry.touched) < idle {
+                continue;
+            }
+            let Some(handle) = self.get_buffer(&path).await else {
+                continue;
+            };
+            let buffer = handle.lock().await;
+            // The saved copy is what unsaved edits are shown against.
+            if buffer.is_dirty() {
+                continue;
+            }
+            buffer.text_model().shrink_to_fit().await;
+            drop(buffer);
+            self.saved_texts.write().await.remove(&path);
+            if let Some(entry) = self.usage.write().await.get_mut(&path) {
+                entry.evicted = true;
+            }
+            evicted.push(path);
+        }
+        evicted.sort();
+        evicted
+    }
+
+    /// The open buffer's text, or the file on disk when it isn't open.
+    pub async fn file_snapshot(&self, file_path: &Path) -> Result<BufferSnapshot, std::io::Error> {
+        match self.get_buffer(file_path).await {
+            Some(handle) => Ok(handle.lock().await.snapshot().await),
+            None => {
+                let content = std::fs::read_to_string(file_path)?;
+                Ok(Buffer::from_text(&content).snapshot().await)
+            }
+        }
+    }
+
+    /// Fills in the text each hunk of `preview` replaces, from the open buffer or the file.
+    pub async fn load_preview_text(&self, preview: &mut EditPreview) -> Result<(), std::io::Error> {
+        for file in &mut preview.files {
+            let snapshot = self.file_snapshot(&file.path).await?;
+            for hunk in &mut file.hunks {
+                hunk.old_text = snapshot
+                    .slice(snapshot.cursor_to_char(hunk.start)..snapshot.cursor_to_char(hunk.end));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the accepted hunks of `preview`, as one undo step per file, without saving.
+    /// Nothing is applied if a file is read-only or changed since the preview was loaded.
+    /// Returns the files that were edited.
+    pub async fn apply_preview(
+        &self,
+        preview: &EditPreview,
+    ) -> Result<Vec<PathBuf>, std::io::Error> {
+        let files: Vec<_> = preview
+            .files
+            .iter()
+            .filter(|file| file.accepted_hunks().next().is_some())
+            .collect();
+        for file in &files {
+            if self.is_read_only(&file.path).await {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    format!("{} is read-only", file.path.display()),
+                ));
+            }
+            let snapshot = self.file_snapshot(&file.path).await?;
+            let stale = file.accepted_hunks().any(|hunk| {
+                snapshot
+                    .slice(snapshot.cursor_to_char(hunk.start)..snapshot.cursor_to_char(hunk.end))
+                    != hunk.old_text
+            });
+            if stale {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{} changed since the preview", file.path.display()),
+                ));
+            }
+        }
+
+        let mut edited = Vec::with_capacity(files.len());
+        for file in files {
+            let handle = self.background_buffer(&file.path).await?;
+            self.edit_queue(&file.path)
+                .submit(async {
+                    let mut buffer = handle.lock().await;
+                    let snapshot = buffer.snapshot().await;
+                    buffer.begin_transaction();
+                    // Back to front so earlier positions stay valid.
+                    for hunk in file.accepted_hunks().collect::<Vec<_>>().into_iter().rev() {
+                        let start = snapshot.cursor_to_char(hunk.start);
+                        let len = snapshot.cursor_to_char(hunk.end) - start;
+                        buffer.replace_range(start, len, &hunk.new_text).await;
+                    }
+                    buffer.end_transaction();
+                })
+                .await;
+            if let (Some(agent), Some(first)) = (&preview.agent, file.accepted_hunks().next()) {
+                let _ = self.agent_events.send(AgentEditEvent::Edited {
+                    agent: agent.clone(),
+                    path: file.path.clone(),
+                    line: first.start.line,
+                });
+            }
+            edited.push(file.path.clone());
+        }
+        Ok(edited)
+    }
+
+    /// Signal that an agent run has finished touching files.
+    pub fn finish_agent_edits(&self, agent: &str) {
+        let _ = self.agent_events.send(AgentEditEvent::Finished {
+            agent: agent.to_string(),
+        });
+    }
+
     pub async fn has_unsaved_changes(&self) -> bool {
         let handles: Vec<_> = {
             let buffers = self.buffers.read().await;
@@ -142,9 +1305,88 @@ impl BufferManager {
         unsaved
     }
 
+    /// Open files in tab order: pinned first, then in the order they were opened.
     pub async fn get_open_files(&self) -> Vec<PathBuf> {
         let buffers = self.buffers.read().await;
-        buffers.keys().cloned().collect()
+        let mut order = self.tab_order.write().await;
+        order.retain(|path| buffers.contains_key(path));
+        let mut added: Vec<PathBuf> = buffers
+            .keys()
+            .filter(|path| !order.contains(path))
+            .cloned()
+            .collect();
+        added.sort();
+        order.extend(added);
+
+        let metadata = self.metadata.read().await;
+        let (mut pinned, unpinned): (Vec<PathBuf>, Vec<PathBuf>) = order
+            .iter()
+            .cloned()
+            .partition(|path| metadata.get(path).is_some_and(|meta| meta.pinned));
+        pinned.extend(unpinned);
+        pinned
+    }
+
+    pub async fn is_pinned(&self, file_path: &Path) -> bool {
+        let metadata = self.metadata.read().await;
+        metadata
+            .get(file_path)
+            .map(|meta| meta.pinned)
+            .unwrap_or(false)
+    }
+
+    /// Pinned files in tab order.
+    pub async fn pinned_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for path in self.get_open_files().await {
+            if self.is_pinned(&path).await {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    pub async fn set_pinned(&self, file_path: &Path, pinned: bool) -> Result<(), std::io::Error> {
+        if !self.buffers.read().await.contains_key(file_path) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Buffer not found",
+            ));
+        }
+        // Pinned tabs keep the order they were pinned in.
+        let mut order = self.tab_order.write().await;
+        order.retain(|path| path != file_path);
+        order.push(file_path.to_path_buf());
+        drop(order);
+
+        let mut metadata = self.metadata.write().await;
+        metadata.entry(file_path.to_path_buf()).or_default().pinned = pinned;
+        Ok(())
+    }
+
+    /// Close every unpinned tab except `keep`. Returns the closed files.
+    pub async fn close_others(&self, keep: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+        self.close_unpinned(Some(keep)).await
+    }
+
+    /// Close every unpinned tab. Returns the closed files.
+    pub async fn close_all(&self) -> Result<Vec<PathBuf>, std::io::Error> {
+        self.close_unpinned(None).await
+    }
+
+    async fn close_unpinned(&self, keep: Option<&Path>) -> Result<Vec<PathBuf>, std::io::Error> {
+        let mut closed = Vec::new();
+        for path in self.get_open_files().await {
+            if Some(path.as_path()) == keep || self.is_pinned(&path).await {
+                continue;
+            }
+            self.close_tab(&path, 0).await?;
+            closed.push(path);
+        }
+        if let Some(keep) = keep {
+            self.set_current_buffer(keep).await?;
+        }
+        Ok(closed)
     }
 
     pub async fn get_current_file_path(&self) -> Option<PathBuf> {
@@ -153,8 +1395,158 @@ impl BufferManager {
     }
 }
 
+/// Map a file extension to an LSP language identifier, falling back to plain text.
+pub fn language_from_path(path: &Path) -> &'static str {
+    editor_infra::LanguagePack::for_path(path).map_or("plaintext", |pack| pack.id)
+}
+
 impl Default for BufferManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core_text::{CaseTransform, Cursor};
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn queued_edits_apply_in_submission_order() {
+        let manager = BufferManager::new();
+        let path = manager.create_new_buffer().await;
+
+        let insert = manager.submit_edit(&path, |buffer| {
+            async move {
+                // Slower than the edits behind it, which must still wait for it.
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                buffer.insert_text_at_cursor("hello world").await;
+            }
+            .boxed()
+        });
+        let upper = manager.submit_edit(&path, |buffer| {
+            async move {
+                let word = Selection::new(Cursor::new(0, 0), Cursor::new(0, 5));
+                buffer.set_selection(word).await;
+                buffer.transform_case(CaseTransform::Upper).await
+            }
+            .boxed()
+        });
+        let cut = manager.submit_edit(&path, |buffer| buffer.cut_selections().boxed());
+
+        let cut = tokio::spawn(cut);
+        let upper = tokio::spawn(upper);
+        insert.await.unwrap();
+        assert_eq!(upper.await.unwrap(), Some(true));
+        assert_eq!(cut.await.unwrap(), Some(vec!["HELLO".to_string()]));
+
+        let buffer = manager.get_buffer(&path).await.unwrap();
+        assert_eq!(buffer.lock().await.get_text().await, " world");
+    }
+
+    /// An edit that sleeps before inserting `text`, so anything racing it would win.
+    fn slow_insert(
+        manager: &BufferManager,
+        path: &Path,
+        text: &'static str,
+    ) -> impl Future<Output = Option<()>> {
+        manager.submit_edit(path, move |buffer| {
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                buffer.insert_text_at_cursor(text).await;
+            }
+            .boxed()
+        })
+    }
+
+    fn temp_file(content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fusang-buffers-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn agent_edits_wait_for_queued_user_edits() {
+        let manager = BufferManager::new();
+        let path = manager.create_new_buffer().await;
+
+        let insert = slow_insert(&manager, &path, "hello world");
+        let agent = {
+            let manager = manager.clone();
+            let path = path.clone();
+            tokio::spawn(async move {
+                manager
+                    .apply_agent_edit("agent", &path, "world", "there")
+                    .await
+            })
+        };
+        insert.await.unwrap();
+        // The anchor only exists once the user's insert is in.
+        assert_eq!(agent.await.unwrap().unwrap(), 0);
+
+        let buffer = manager.get_buffer(&path).await.unwrap();
+        assert_eq!(buffer.lock().await.get_text().await, "hello there");
+    }
+
+    #[tokio::test]
+    async fn reloads_wait_for_queued_user_edits() {
+        let path = temp_file("one");
+        let manager = BufferManager::new();
+        manager.open_file(&path).await.unwrap();
+        std::fs::write(&path, "two").unwrap();
+
+        let insert = slow_insert(&manager, &path, "x");
+        let reload = {
+            let manager = manager.clone();
+            let path = path.clone();
+            tokio::spawn(async move { manager.reload_file(&path).await })
+        };
+        insert.await.unwrap();
+        // The queued edit left the buffer dirty, so the reload keeps it.
+        assert_eq!(reload.await.unwrap().unwrap(), None);
+
+        let buffer = manager.get_buffer(&path).await.unwrap();
+        assert_eq!(buffer.lock().await.get_text().await, "xone");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recovery_replays_a_journal_up_to_a_line_cut_short() {
+        let path = temp_file("one\n");
+        let journals = EditJournals::new(path.parent().unwrap().join("journals"));
+
+        let session = BufferManager::new().with_edit_journals(journals.clone());
+        session.open_file(&path).await.unwrap();
+        let buffer = session.get_buffer(&path).await.unwrap();
+        buffer.lock().await.replace_range(4, 0, "two\n").await;
+        buffer.lock().await.replace_range(0, 3, "ONE").await;
+        assert_eq!(session.journal(&path).await.unwrap().unwrap().len(), 2);
+        let journal = buffer.lock().await.text_model().journal_path().unwrap();
+        drop(buffer);
+        drop(session);
+
+        // The session crashed halfway through writing its last edit.
+        let written = std::fs::read_to_string(&journal).unwrap();
+        std::fs::write(&journal, &written[..written.len() - 10]).unwrap();
+
+        let restarted = BufferManager::new().with_edit_journals(journals);
+        restarted.open_file(&path).await.unwrap();
+        assert!(restarted.recover_from_journal(&path).await.unwrap());
+        let buffer = restarted.get_buffer(&path).await.unwrap();
+        assert_eq!(buffer.lock().await.get_text().await, "one\ntwo\n");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn edits_to_closed_buffers_are_not_applied() {
+        let manager = BufferManager::new();
+        let path = manager.create_new_buffer().await;
+
+        manager.close_file(&path).await.unwrap();
+
+        assert_eq!(slow_insert(&manager, &path, "lost").await, None);
+    }
+}
@@ -0,0 +1,157 @@
+use editor_core_text::{Cursor, Selection};
+use editor_infra::{SessionSelection, SessionTab};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CAPACITY: usize = 20;
+
+/// Unsaved content of a closed buffer, restored when the tab is reopened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Draft {
+    pub text: String,
+    pub display_name: Option<String>,
+    pub language: Option<String>,
+}
+
+/// Everything needed to bring a closed tab back where it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedTab {
+    /// For untitled buffers this is only a key; they get a fresh one when reopened.
+    pub path: PathBuf,
+    pub untitled: bool,
+    pub selections: Vec<Selection>,
+    /// First visible line.
+    pub scroll_line: usize,
+    pub draft: Option<Draft>,
+}
+
+impl From<&ClosedTab> for SessionTab {
+    fn from(tab: &ClosedTab) -> Self {
+        let draft = tab.draft.as_ref();
+        SessionTab {
+            path: tab.path.clone(),
+            untitled: tab.untitled,
+            selections: tab
+                .selections
+                .iter()
+                .map(|selection| SessionSelection {
+                    anchor_line: selection.anchor.line,
+                    anchor_column: selection.anchor.column,
+                    active_line: selection.active.line,
+                    active_column: selection.active.column,
+                })
+                .collect(),
+            scroll_line: tab.scroll_line,
+            draft: draft.map(|draft| draft.text.clone()),
+            display_name: draft.and_then(|draft| draft.display_name.clone()),
+            language: draft.and_then(|draft| draft.language.clone()),
+        }
+    }
+}
+
+impl From<SessionTab> for ClosedTab {
+    fn from(tab: SessionTab) -> Self {
+        let (display_name, language) = (tab.display_name, tab.language);
+        ClosedTab {
+            path: tab.path,
+            untitled: tab.untitled,
+            selections: tab
+                .selections
+                .iter()
+                .map(|selection| {
+                    Selection::new(
+                        Cursor::new(selection.anchor_line, selection.anchor_column),
+                        Cursor::new(selection.active_line, selection.active_column),
+                    )
+                })
+                .collect(),
+            scroll_line: tab.scroll_line,
+            draft: tab.draft.map(|text| Draft {
+                text,
+                display_name,
+                language,
+            }),
+        }
+    }
+}
+
+/// Recently closed tabs, most recent last. The oldest are dropped past the capacity.
+#[derive(Debug)]
+pub struct ClosedTabs {
+    entries: VecDeque<ClosedTab>,
+    capacity: usize,
+}
+
+impl ClosedTabs {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Remembers `tab`, replacing an older entry for the same file.
+    pub fn push(&mut self, tab: ClosedTab) {
+        if !tab.untitled {
+            self.forget(&tab.path);
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(tab);
+    }
+
+    pub fn pop(&mut self) -> Option<ClosedTab> {
+        self.entries.pop_back()
+    }
+
+    /// Drops entries for `path`, e.g. once the file is opened again by other means.
+    pub fn forget(&mut self, path: &Path) {
+        self.entries.retain(|entry| entry.path != path);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for ClosedTabs {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(path: &str, untitled: bool) -> ClosedTab {
+        ClosedTab {
+            path: PathBuf::from(path),
+            untitled,
+            selections: Vec::new(),
+            scroll_line: 0,
+            draft: None,
+        }
+    }
+
+    #[test]
+    fn reopens_most_recent_first_without_duplicates() {
+        let mut tabs = ClosedTabs::new(3);
+        tabs.push(tab("a.rs", false));
+        tabs.push(tab("b.rs", false));
+        tabs.push(tab("a.rs", false));
+        tabs.push(tab("untitled-1", true));
+        tabs.push(tab("c.rs", false));
+
+        assert_eq!(tabs.len(), 3);
+        assert_eq!(tabs.pop().unwrap().path, PathBuf::from("c.rs"));
+        assert_eq!(tabs.pop().unwrap().path, PathBuf::from("untitled-1"));
+        assert_eq!(tabs.pop().unwrap().path, PathBuf::from("a.rs"));
+        assert!(tabs.is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::local_history::stable_hash;
+use editor_core_text::{EditJournal, EditLog};
+use std::path::{Path, PathBuf};
+
+const CURRENT_JOURNAL: &str = "journal.jsonl";
+const CRASHED_JOURNAL: &str = "crashed.jsonl";
+const SOURCE_FILE: &str = "source";
+
+/// Journals of the edits made to each open file since it was last saved, kept on disk
+/// so they can be replayed to audit how a file changed, or to get back unsaved text
+/// after a crash.
+///
+/// Each file gets a directory under `root`, keyed by the same stable hash as
+/// [`crate::LocalHistory`]. Saving a file starts its journal over and closing it
+/// deletes the journal. A journal still there when the file is opened again, whose
+/// edits end in text other than the file's, is kept aside as the crashed journal until
+/// it is recovered or dismissed.
+#[derive(Debug, Clone)]
+pub struct EditJournals {
+    root: PathBuf,
+}
+
+impl EditJournals {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn default_location() -> PathBuf {
+        editor_infra::paths::data_dir().join("journals")
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Starts this session's journal of `file_path` from `text`, setting aside the
+    /// previous session's journal if it holds text the file doesn't.
+    pub fn start(
+        &self,
+        file_path: &Path,
+        text: &str,
+        version: usize,
+    ) -> std::io::Result<EditJournal> {
+        let dir = self.file_dir(file_path);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join(SOURCE_FILE),
+            file_path.to_string_lossy().as_bytes(),
+        )?;
+
+        let current = dir.join(CURRENT_JOURNAL);
+        if let Some(log) = read_journal(&current)? {
+            if !log.is_empty() && log.final_text().0 != text {
+                std::fs::rename(&current, dir.join(CRASHED_JOURNAL))?;
+            }
+        }
+        self.restart(file_path, text, version)
+    }
+
+    /// Starts the journal of `file_path` over from `text`, once the edits before it
+    /// are saved.
+    pub fn restart(
+        &self,
+        file_path: &Path,
+        text: &str,
+        version: usize,
+    ) -> std::io::Result<EditJournal> {
+        let current = self.file_dir(file_path).join(CURRENT_JOURNAL);
+        remove_if_exists(&current)?;
+        EditJournal::create(&current, text, version)
+    }
+
+    /// The edits to `file_path` written so far this session.
+    pub fn read(&self, file_path: &Path) -> std::io::Result<Option<EditLog>> {
+        read_journal(&self.file_dir(file_path).join(CURRENT_JOURNAL))
+    }
+
+    /// The edits of a session that ended without closing `file_path`, if they left it
+    /// with text that isn't on disk.
+    pub fn crashed(&self, file_path: &Path) -> std::io::Result<Option<EditLog>> {
+        read_journal(&self.file_dir(file_path).join(CRASHED_JOURNAL))
+    }
+
+    /// Files with a crashed journal.
+    pub fn crashed_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let dir = entry?.path();
+            if !dir.join(CRASHED_JOURNAL).exists() {
+                continue;
+            }
+            if let Ok(source) = std::fs::read_to_string(dir.join(SOURCE_FILE)) {
+                files.push(PathBuf::from(source));
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn dismiss_crashed(&self, file_path: &Path) -> std::io::Result<()> {
+        remove_if_exists(&self.file_dir(file_path).join(CRASHED_JOURNAL))
+    }
+
+    /// Deletes this session's journal, when the file is closed.
+    pub fn discard(&self, file_path: &Path) -> std::io::Result<()> {
+        let dir = self.file_dir(file_path);
+        remove_if_exists(&dir.join(CURRENT_JOURNAL))?;
+        if !dir.join(CRASHED_JOURNAL).exists() {
+            remove_if_exists(&dir.join(SOURCE_FILE))?;
+            let _ = std::fs::remove_dir(&dir);
+        }
+        Ok(())
+    }
+
+    fn file_dir(&self, file_path: &Path) -> PathBuf {
+        self.root.join(format!(
+            "{:016x}",
+            stable_hash(&file_path.to_string_lossy())
+        ))
+    }
+}
+
+fn read_journal(path: &Path) -> std::io::Result<Option<EditLog>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    EditLog::from_journal(&text)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
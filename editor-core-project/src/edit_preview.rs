@@ -0,0 +1,167 @@
+use editor_core_text::Cursor;
+use std::path::{Path, PathBuf};
+
+/// One replacement offered for review, in the file's line and column coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewHunk {
+    pub start: Cursor,
+    pub end: Cursor,
+    /// The text being replaced, filled in by [`crate::BufferManager::load_preview_text`].
+    pub old_text: String,
+    pub new_text: String,
+    pub accepted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    /// Sorted by position, front to back.
+    pub hunks: Vec<PreviewHunk>,
+}
+
+impl FilePreview {
+    pub fn accepted_hunks(&self) -> impl Iterator<Item = &PreviewHunk> {
+        self.hunks.iter().filter(|hunk| hunk.accepted)
+    }
+
+    pub fn all_accepted(&self) -> bool {
+        self.hunks.iter().all(|hunk| hunk.accepted)
+    }
+}
+
+/// Edits to several files shown before they are applied, so each file or hunk can be
+/// left out. Language server edits, multi-file replace and AI patches all go through it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditPreview {
+    pub title: String,
+    /// Set for AI patches so the edits are reported like other agent edits.
+    pub agent: Option<String>,
+    pub files: Vec<FilePreview>,
+}
+
+impl EditPreview {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            agent: None,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn with_agent(mut self, agent: impl Into<String>) -> Self {
+        self.agent = Some(agent.into());
+        self
+    }
+
+    /// Adds a replacement of `start..end` in `path`, accepted by default.
+    pub fn push(&mut self, path: &Path, start: Cursor, end: Cursor, new_text: impl Into<String>) {
+        let hunk = PreviewHunk {
+            start,
+            end,
+            old_text: String::new(),
+            new_text: new_text.into(),
+            accepted: true,
+        };
+        let file = match self.files.iter().position(|file| file.path == path) {
+            Some(index) => &mut self.files[index],
+            None => {
+                self.files.push(FilePreview {
+                    path: path.to_path_buf(),
+                    hunks: Vec::new(),
+                });
+                self.files.last_mut().unwrap()
+            }
+        };
+        let key = |cursor: Cursor| (cursor.line, cursor.column);
+        let index = file
+            .hunks
+            .partition_point(|other| key(other.start) <= key(hunk.start));
+        file.hunks.insert(index, hunk);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn hunk_count(&self) -> usize {
+        self.files.iter().map(|file| file.hunks.len()).sum()
+    }
+
+    pub fn accepted_count(&self) -> usize {
+        self.files
+            .iter()
+            .map(|file| file.accepted_hunks().count())
+            .sum()
+    }
+
+    /// Accepts every hunk of the file, or none if they were all accepted.
+    pub fn toggle_file(&mut self, file: usize) {
+        if let Some(file) = self.files.get_mut(file) {
+            let accepted = !file.all_accepted();
+            for hunk in &mut file.hunks {
+                hunk.accepted = accepted;
+            }
+        }
+    }
+
+    pub fn toggle_hunk(&mut self, file: usize, hunk: usize) {
+        if let Some(hunk) = self
+            .files
+            .get_mut(file)
+            .and_then(|file| file.hunks.get_mut(hunk))
+        {
+            hunk.accepted = !hunk.accepted;
+        }
+    }
+}
+
+/// Where `needle` first occurs in `text`, as a cursor range.
+pub fn locate(text: &str, needle: &str) -> Option<(Cursor, Cursor)> {
+    let byte_idx = text.find(needle)?;
+    let cursor_at = |byte_idx: usize| {
+        let prefix = &text[..byte_idx];
+        let line_start = prefix.rfind('\n').map_or(0, |idx| idx + 1);
+        Cursor::new(
+            prefix.matches('\n').count(),
+            prefix[line_start..].chars().count(),
+        )
+    };
+    Some((cursor_at(byte_idx), cursor_at(byte_idx + needle.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_hunks_by_file_and_toggles_them() {
+        let mut preview = EditPreview::new("Rename");
+        let a = Path::new("/work/a.rs");
+        preview.push(a, Cursor::new(4, 0), Cursor::new(4, 3), "new");
+        preview.push(
+            Path::new("/work/b.rs"),
+            Cursor::new(0, 0),
+            Cursor::new(0, 1),
+            "x",
+        );
+        preview.push(a, Cursor::new(1, 2), Cursor::new(1, 5), "new");
+
+        assert_eq!(preview.files.len(), 2);
+        assert_eq!(preview.files[0].hunks[0].start, Cursor::new(1, 2));
+        assert_eq!(preview.accepted_count(), 3);
+
+        preview.toggle_hunk(0, 1);
+        assert_eq!(preview.accepted_count(), 2);
+        preview.toggle_file(0);
+        assert_eq!(preview.accepted_count(), 3);
+        preview.toggle_file(0);
+        assert_eq!(preview.accepted_count(), 1);
+        assert_eq!(preview.hunk_count(), 3);
+
+        assert_eq!(
+            locate("fn a() {}\nlet b = 1;\n", "b = 1"),
+            Some((Cursor::new(1, 4), Cursor::new(1, 9)))
+        );
+        assert_eq!(locate("abc", "x"), None);
+    }
+}
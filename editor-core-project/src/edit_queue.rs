@@ -0,0 +1,118 @@
+use futures::channel::oneshot;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Runs the edits submitted for one buffer one at a time, in the order they were
+/// submitted rather than the order their tasks happen to be polled in.
+#[derive(Clone, Default)]
+pub struct EditQueue {
+    /// Completes once the last submitted edit has run or was dropped.
+    tail: Arc<Mutex<Option<Shared<BoxFuture<'static, ()>>>>>,
+}
+
+impl EditQueue {
+    /// Takes the next place in line right away; the returned future waits for every
+    /// edit submitted before it, then runs `edit` and resolves to its output, which
+    /// tells the caller the edit was applied. Dropping the future gives up the place
+    /// without letting later edits overtake earlier ones.
+    pub fn submit<Fut: Future>(&self, edit: Fut) -> impl Future<Output = Fut::Output> {
+        let (done, finished) = oneshot::channel::<()>();
+        let previous = {
+            let mut tail = self.tail.lock().unwrap_or_else(|e| e.into_inner());
+            let previous = tail.take();
+            let after = previous.clone();
+            *tail = Some(
+                async move {
+                    if let Some(after) = after {
+                        after.await;
+                    }
+                    let _ = finished.await;
+                }
+                .boxed()
+                .shared(),
+            );
+            previous
+        };
+        async move {
+            if let Some(previous) = previous {
+                previous.await;
+            }
+            let output = edit.await;
+            drop(done);
+            output
+        }
+    }
+
+    /// Resolves once every edit submitted so far has run.
+    pub fn flushed(&self) -> impl Future<Output = ()> {
+        self.submit(async {})
+    }
+}
+
+impl fmt::Debug for EditQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EditQueue").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn record(
+        queue: &EditQueue,
+        log: &Arc<Mutex<Vec<usize>>>,
+        n: usize,
+        delay: u64,
+    ) -> impl Future<Output = usize> {
+        let log = log.clone();
+        queue.submit(async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            log.lock().unwrap().push(n);
+            n
+        })
+    }
+
+    #[tokio::test]
+    async fn edits_run_in_submission_order() {
+        let queue = EditQueue::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let first = record(&queue, &log, 1, 30);
+        let second = record(&queue, &log, 2, 0);
+
+        // Polled out of order, the later edit still waits for the earlier one.
+        let second = tokio::spawn(second);
+        assert_eq!(first.await, 1);
+        assert_eq!(second.await.unwrap(), 2);
+        assert_eq!(*log.lock().unwrap(), [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn dropped_edits_give_up_their_place() {
+        let queue = EditQueue::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let first = record(&queue, &log, 1, 30);
+        drop(record(&queue, &log, 2, 0));
+        let third = tokio::spawn(record(&queue, &log, 3, 0));
+
+        assert_eq!(first.await, 1);
+        assert_eq!(third.await.unwrap(), 3);
+        assert_eq!(*log.lock().unwrap(), [1, 3]);
+    }
+
+    #[tokio::test]
+    async fn flushed_waits_for_every_submitted_edit() {
+        let queue = EditQueue::default();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let first = tokio::spawn(record(&queue, &log, 1, 30));
+        let second = tokio::spawn(record(&queue, &log, 2, 0));
+
+        queue.flushed().await;
+        assert_eq!(*log.lock().unwrap(), [1, 2]);
+        first.await.unwrap();
+        second.await.unwrap();
+    }
+}
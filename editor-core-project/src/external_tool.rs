@@ -0,0 +1,163 @@
+use editor_infra::config::{ExternalToolConfig, ToolOutput};
+use std::io;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// A user-defined command run from the tools list, e.g. `sort`, `jq .` or a project script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalTool {
+    pub name: String,
+    pub command: String,
+    /// `$FILE`, `$SELECTION` and `$WORKSPACE` are replaced from the [`ToolContext`].
+    pub args: Vec<String>,
+    pub output: ToolOutput,
+    pub timeout: Duration,
+}
+
+/// What the placeholders of a tool's arguments expand to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolContext {
+    pub file: Option<PathBuf>,
+    pub selection: String,
+    pub workspace: PathBuf,
+}
+
+/// The outcome of a tool that ran to completion, successfully or not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolRun {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ExternalTool {
+    pub fn from_config(config: &ExternalToolConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            command: config.command.clone(),
+            args: config.args.clone(),
+            output: config.output,
+            timeout: Duration::from_secs(config.timeout_seconds),
+        }
+    }
+
+    /// Each argument with its placeholders expanded. Arguments are passed to the command
+    /// directly, never through a shell, so a selection can't inject extra arguments.
+    pub fn expand_args(&self, context: &ToolContext) -> Vec<String> {
+        let file = context
+            .file
+            .as_ref()
+            .map(|file| file.display().to_string())
+            .unwrap_or_default();
+        let workspace = context.workspace.display().to_string();
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.replace("$FILE", &file)
+                    .replace("$WORKSPACE", &workspace)
+                    .replace("$SELECTION", &context.selection)
+            })
+            .collect()
+    }
+
+    /// Runs the command in the workspace with the selection on stdin. The process is
+    /// killed when it outlives the timeout.
+    pub async fn run(&self, context: &ToolContext) -> io::Result<ToolRun> {
+        let mut command = Command::new(&self.command);
+        command
+            .args(self.expand_args(context))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if context.workspace.is_dir() {
+            command.current_dir(&context.workspace);
+        }
+        let mut child = command.spawn().map_err(|e| {
+            io::Error::new(e.kind(), format!("failed to run {}: {}", self.command, e))
+        })?;
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::other("tool stdin unavailable"))?;
+        let input = context.selection.clone();
+        let writer = tokio::spawn(async move {
+            stdin.write_all(input.as_bytes()).await?;
+            stdin.shutdown().await
+        });
+
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "{} timed out after {}s",
+                        self.name,
+                        self.timeout.as_secs_f32()
+                    ),
+                )
+            })??;
+        // Tools that ignore stdin close it early; that is not an error.
+        let _ = writer.await;
+
+        Ok(ToolRun {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn tool(command: &str, args: &[&str]) -> ExternalTool {
+        ExternalTool {
+            name: command.to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            output: ToolOutput::Panel,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn expands_placeholders_and_pipes_the_selection() {
+        let context = ToolContext {
+            file: Some(PathBuf::from("/tmp/src/main.rs")),
+            selection: "b\na\n".to_string(),
+            workspace: PathBuf::from("/tmp"),
+        };
+        let echo = tool("echo", &["$FILE:$SELECTION", "$WORKSPACE"]);
+        assert_eq!(
+            echo.expand_args(&context),
+            ["/tmp/src/main.rs:b\na\n", "/tmp"]
+        );
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let sorted = tool("sort", &[]).run(&context).await.unwrap();
+            assert!(sorted.success);
+            assert_eq!(sorted.stdout, "a\nb\n");
+
+            let failing = tool("sh", &["-c", "echo oops >&2; exit 3"])
+                .run(&context)
+                .await
+                .unwrap();
+            assert!(!failing.success);
+            assert_eq!(failing.stderr, "oops\n");
+
+            let slow = ExternalTool {
+                timeout: Duration::from_millis(100),
+                ..tool("sleep", &["5"])
+            };
+            let error = slow.run(&context).await.unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        });
+    }
+}
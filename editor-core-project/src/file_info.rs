@@ -0,0 +1,100 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Size, modification time and permissions of a file on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// `rwxr-xr-x` style on Unix; `rw-`/`r--` elsewhere.
+    pub permissions: String,
+    /// Whether the current user can write to the file.
+    pub writable: bool,
+    pub is_symlink: bool,
+}
+
+impl FileInfo {
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let is_symlink = std::fs::symlink_metadata(path)
+            .map(|meta| meta.file_type().is_symlink())
+            .unwrap_or(false);
+
+        // Permission bits don't account for ownership or ACLs, so ask the OS directly.
+        let writable = if metadata.is_dir() {
+            !metadata.permissions().readonly()
+        } else {
+            std::fs::OpenOptions::new().write(true).open(path).is_ok()
+        };
+
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            permissions: permission_string(&metadata),
+            writable,
+            is_symlink,
+        })
+    }
+
+    pub fn size_label(&self) -> String {
+        const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+        let mut size = self.size as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} B", self.size)
+        } else {
+            format!("{:.1} {}", size, UNITS[unit])
+        }
+    }
+}
+
+#[cfg(unix)]
+fn permission_string(metadata: &std::fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode();
+    (0..9)
+        .map(|bit| {
+            if mode & (1 << (8 - bit)) == 0 {
+                '-'
+            } else {
+                ['r', 'w', 'x'][bit % 3]
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn permission_string(metadata: &std::fs::Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "r--".to_string()
+    } else {
+        "rw-".to_string()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn reads_size_and_permission_bits() {
+        let path = std::env::temp_dir().join(format!("fusang-info-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, vec![b'x'; 2048]).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let info = FileInfo::read(&path).unwrap();
+        assert_eq!(info.size, 2048);
+        assert_eq!(info.size_label(), "2.0 KB");
+        assert_eq!(info.permissions, "rw-r-----");
+        assert!(info.modified.is_some());
+        assert!(!info.is_symlink);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
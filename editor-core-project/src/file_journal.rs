@@ -0,0 +1,412 @@
+use crate::workspace::{remove_path, DeleteMode};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_MAX_ENTRIES: usize = 100;
+
+/// A file-system change recorded by [`FileJournal`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileOperation {
+    Created {
+        path: PathBuf,
+    },
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// `backup` holds the deleted file or folder until the entry is evicted. It is `None`
+    /// when the file went to the system trash, which undo restores it from.
+    Deleted {
+        path: PathBuf,
+        backup: Option<PathBuf>,
+    },
+}
+
+impl FileOperation {
+    pub fn description(&self) -> String {
+        match self {
+            FileOperation::Created { path } => format!("Create {}", path.display()),
+            FileOperation::Renamed { from, to } => {
+                format!("Rename {} to {}", from.display(), to.display())
+            }
+            FileOperation::Deleted { path, .. } => format!("Delete {}", path.display()),
+        }
+    }
+}
+
+/// Journal of file operations made from the editor, so the last one can be undone.
+///
+/// Files deleted in [`DeleteMode::Trash`] go to the system trash and undo restores them
+/// from there. Where the system trash can't be restored from (macOS), or it is turned off,
+/// they are moved into `trash_dir` instead and that backup is purged once its entry falls
+/// off the end of the journal. Permanent deletes are not journaled.
+#[derive(Debug)]
+pub struct FileJournal {
+    trash_dir: PathBuf,
+    entries: Vec<FileOperation>,
+    max_entries: usize,
+    system_trash: bool,
+}
+
+impl FileJournal {
+    pub fn new(trash_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            trash_dir: trash_dir.into(),
+            entries: Vec::new(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            system_trash: SYSTEM_TRASH_RESTORES,
+        }
+    }
+
+    pub fn default_location() -> PathBuf {
+        editor_infra::paths::data_dir().join("file-trash")
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    /// Keep trashed files only in the journal's own backup, e.g. where no system trash exists.
+    pub fn with_system_trash(mut self, enabled: bool) -> Self {
+        self.system_trash = enabled && SYSTEM_TRASH_RESTORES;
+        self
+    }
+
+    pub fn last(&self) -> Option<&FileOperation> {
+        self.entries.last()
+    }
+
+    pub fn create_file(&mut self, path: &Path, content: &str) -> std::io::Result<()> {
+        if path.exists() {
+            return Err(already_exists(path));
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        self.record_created(path);
+        Ok(())
+    }
+
+    pub fn create_dir(&mut self, path: &Path) -> std::io::Result<()> {
+        if path.exists() {
+            return Err(already_exists(path));
+        }
+        std::fs::create_dir_all(path)?;
+        self.record_created(path);
+        Ok(())
+    }
+
+    /// Record a file that was created elsewhere, e.g. written by an AI agent.
+    pub fn record_created(&mut self, path: &Path) {
+        self.push(FileOperation::Created {
+            path: path.to_path_buf(),
+        });
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) -> std::io::Result<()> {
+        if to.exists() {
+            return Err(already_exists(to));
+        }
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_path(from, to)?;
+        self.push(FileOperation::Renamed {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    /// Delete `path` and return the mode that was applied.
+    pub fn delete(&mut self, path: &Path, mode: DeleteMode) -> std::io::Result<DeleteMode> {
+        if mode == DeleteMode::Permanent {
+            remove_path(path, mode)?;
+            return Ok(mode);
+        }
+
+        let backup = if self.system_trash {
+            remove_path(path, DeleteMode::Trash)?;
+            None
+        } else {
+            Some(self.move_to_trash(path)?)
+        };
+        self.push(FileOperation::Deleted {
+            path: path.to_path_buf(),
+            backup,
+        });
+        Ok(mode)
+    }
+
+    /// Revert the most recent operation. The entry stays in the journal if undoing fails.
+    pub fn undo_last(&mut self) -> std::io::Result<Option<FileOperation>> {
+        let Some(operation) = self.entries.last().cloned() else {
+            return Ok(None);
+        };
+
+        match &operation {
+            // The file may have been edited since, so keep a copy rather than removing it.
+            FileOperation::Created { path } => {
+                if path.exists() {
+                    self.move_to_trash(path)?;
+                }
+            }
+            FileOperation::Renamed { from, to } => {
+                if from.exists() {
+                    return Err(already_exists(from));
+                }
+                move_path(to, from)?;
+            }
+            FileOperation::Deleted { path, backup } => {
+                if path.exists() {
+                    return Err(already_exists(path));
+                }
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                match backup {
+                    Some(backup) => {
+                        move_path(backup, path)?;
+                        if let Some(slot) = backup.parent() {
+                            let _ = std::fs::remove_dir(slot);
+                        }
+                    }
+                    None => restore_from_system_trash(path)?,
+                }
+            }
+        }
+
+        self.entries.pop();
+        Ok(Some(operation))
+    }
+
+    fn push(&mut self, operation: FileOperation) {
+        self.entries.push(operation);
+        while self.entries.len() > self.max_entries {
+            if let FileOperation::Deleted {
+                backup: Some(backup),
+                ..
+            } = self.entries.remove(0)
+            {
+                if let Some(slot) = backup.parent() {
+                    let _ = std::fs::remove_dir_all(slot);
+                }
+            }
+        }
+    }
+
+    fn move_to_trash(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let backup = self.new_slot(path)?;
+        move_path(path, &backup)?;
+        Ok(backup)
+    }
+
+    /// A fresh backup location for `path` inside the journal's trash directory.
+    fn new_slot(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let name = path.file_name().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Cannot delete {}", path.display()),
+            )
+        })?;
+        let slot = self.trash_dir.join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&slot)?;
+        Ok(slot.join(name))
+    }
+}
+
+/// Whether files sent to the system trash can be listed and put back on this platform.
+const SYSTEM_TRASH_RESTORES: bool = cfg!(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+));
+
+/// Put back the most recently trashed item that came from `path`.
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn restore_from_system_trash(path: &Path) -> std::io::Result<()> {
+    let item = trash::os_limited::list()
+        .map_err(std::io::Error::other)?
+        .into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} is no longer in the trash", path.display()),
+            )
+        })?;
+    trash::os_limited::restore_all([item]).map_err(std::io::Error::other)
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore_from_system_trash(path: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("Cannot restore {} from the system trash", path.display()),
+    ))
+}
+
+fn already_exists(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    )
+}
+
+/// `rename`, falling back to copy-and-remove when the paths are on different devices.
+pub(crate) fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(from, to)?;
+    if std::fs::symlink_metadata(from)?.is_dir() {
+        std::fs::remove_dir_all(from)
+    } else {
+        std::fs::remove_file(from)
+    }
+}
+
+/// Copy a file or tree without following symlinks: links are recreated as links, so a
+/// link cycle or a link out of the tree is never walked.
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    let file_type = std::fs::symlink_metadata(from)?.file_type();
+    if file_type.is_symlink() {
+        copy_symlink(from, to)
+    } else if file_type.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(from)?;
+    if from.is_dir() {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_reverts_create_rename_and_delete() {
+        let root = std::env::temp_dir().join(format!("fusang-journal-{}", uuid::Uuid::new_v4()));
+        let mut journal = FileJournal::new(root.join("trash")).with_system_trash(false);
+        let a = root.join("work/a.txt");
+        let b = root.join("work/b.txt");
+
+        journal.create_file(&a, "hello").unwrap();
+        journal.rename(&a, &b).unwrap();
+        assert_eq!(
+            journal.delete(&b, DeleteMode::Trash).unwrap(),
+            DeleteMode::Trash
+        );
+        assert!(!a.exists() && !b.exists());
+
+        assert!(matches!(
+            journal.undo_last().unwrap(),
+            Some(FileOperation::Deleted { .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "hello");
+
+        journal.undo_last().unwrap();
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "hello");
+        assert!(!b.exists());
+
+        journal.undo_last().unwrap();
+        assert!(!a.exists());
+        assert_eq!(journal.undo_last().unwrap(), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn undo_restores_deleted_directory_with_symlinks() {
+        let root = std::env::temp_dir().join(format!("fusang-journal-{}", uuid::Uuid::new_v4()));
+        let mut journal = FileJournal::new(root.join("trash")).with_system_trash(false);
+        let dir = root.join("work/dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "hello").unwrap();
+        std::os::unix::fs::symlink("..", dir.join("parent")).unwrap();
+
+        journal.delete(&dir, DeleteMode::Trash).unwrap();
+        assert!(!dir.exists());
+        assert!(root.join("work").exists());
+
+        journal.undo_last().unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("a.txt")).unwrap(), "hello");
+        assert_eq!(
+            std::fs::read_link(dir.join("parent")).unwrap(),
+            Path::new("..")
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_recreates_symlinks_instead_of_following_them() {
+        let root = std::env::temp_dir().join(format!("fusang-journal-{}", uuid::Uuid::new_v4()));
+        let from = root.join("from");
+        std::fs::create_dir_all(from.join("sub")).unwrap();
+        std::fs::write(from.join("sub/a.txt"), "hello").unwrap();
+        std::os::unix::fs::symlink("..", from.join("sub/parent")).unwrap();
+        std::os::unix::fs::symlink("a.txt", from.join("sub/link.txt")).unwrap();
+
+        let to = root.join("to");
+        copy_recursive(&from, &to).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(to.join("sub/a.txt")).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            std::fs::read_link(to.join("sub/parent")).unwrap(),
+            Path::new("..")
+        );
+        assert_eq!(
+            std::fs::read_link(to.join("sub/link.txt")).unwrap(),
+            Path::new("a.txt")
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+/// A `path:line:column` style reference found in free text.
+///
+/// Recognises `src/foo.rs:42:10`, `src/foo.rs:42`, `src/foo.rs(42,10)` and bare paths.
+/// Line and column are 1-based, as printed by compilers and test runners.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReference {
+    pub path: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl FileReference {
+    /// Parses a single token such as `src/foo.rs:42:10`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let token = text
+            .trim()
+            .trim_start_matches(['(', '[', '<', '"', '\'', '`'])
+            .trim_end_matches(['.', ',', ';', ':', ']', '>', '"', '\'', '`']);
+
+        if let Some(reference) = Self::parse_parenthesized(token) {
+            return Some(reference);
+        }
+
+        let mut path = token.trim_end_matches(')');
+        let mut numbers = Vec::new();
+        while numbers.len() < 2 {
+            match path.rsplit_once(':') {
+                Some((rest, tail))
+                    if !tail.is_empty() && tail.bytes().all(|b| b.is_ascii_digit()) =>
+                {
+                    numbers.push(tail.parse::<usize>().ok()?);
+                    path = rest;
+                }
+                _ => break,
+            }
+        }
+        numbers.reverse();
+
+        Self::new(path, numbers.first().copied(), numbers.get(1).copied())
+    }
+
+    /// Finds the reference under `column` (a char index) in `line`.
+    pub fn at(line: &str, column: usize) -> Option<Self> {
+        let chars: Vec<char> = line.chars().collect();
+        if column >= chars.len() || chars[column].is_whitespace() {
+            return None;
+        }
+
+        let start = chars[..column]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |idx| idx + 1);
+        let end = chars[column..]
+            .iter()
+            .position(|c| c.is_whitespace())
+            .map_or(chars.len(), |idx| column + idx);
+
+        Self::parse(&chars[start..end].iter().collect::<String>())
+    }
+
+    /// All references in `text`, in order of appearance.
+    pub fn find_all(text: &str) -> Vec<Self> {
+        let mut references: Vec<Self> = Vec::new();
+        for reference in text.split_whitespace().filter_map(Self::parse) {
+            if !references.contains(&reference) {
+                references.push(reference);
+            }
+        }
+        references
+    }
+
+    pub fn label(&self) -> String {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{}:{}:{}", self.path, line, column),
+            (Some(line), None) => format!("{}:{}", self.path, line),
+            _ => self.path.clone(),
+        }
+    }
+
+    fn parse_parenthesized(token: &str) -> Option<Self> {
+        let inner = token.strip_suffix(')')?;
+        let (path, position) = inner.rsplit_once('(')?;
+        let mut parts = position.split(',').map(|part| part.trim().parse::<usize>());
+        let line = parts.next()?.ok()?;
+        let column = match parts.next() {
+            Some(column) => Some(column.ok()?),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Self::new(path, Some(line), column)
+    }
+
+    fn new(path: &str, line: Option<usize>, column: Option<usize>) -> Option<Self> {
+        if path.is_empty() || path.contains("://") {
+            return None;
+        }
+
+        // Require something path-like so that times ("10:30") and prose don't match.
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        let has_extension = file_name
+            .rsplit_once('.')
+            .is_some_and(|(stem, ext)| !stem.is_empty() && !ext.is_empty());
+        if !has_extension && !path.contains('/') {
+            return None;
+        }
+
+        Some(Self {
+            path: path.to_string(),
+            line: line.filter(|l| *l > 0),
+            column: column.filter(|c| *c > 0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Workspace;
+
+    fn reference(path: &str, line: Option<usize>, column: Option<usize>) -> FileReference {
+        FileReference {
+            path: path.to_string(),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn parses_common_reference_formats() {
+        assert_eq!(
+            FileReference::parse("src/foo.rs:42:10"),
+            Some(reference("src/foo.rs", Some(42), Some(10)))
+        );
+        assert_eq!(
+            FileReference::parse("(src/foo.rs:42)."),
+            Some(reference("src/foo.rs", Some(42), None))
+        );
+        assert_eq!(
+            FileReference::parse("Foo.cs(7,3):"),
+            Some(reference("Foo.cs", Some(7), Some(3)))
+        );
+        assert_eq!(FileReference::parse("10:30"), None);
+        assert_eq!(FileReference::parse("https://example.com/a.rs"), None);
+
+        let line = "error at --> src/lib.rs:3:5 while compiling";
+        assert_eq!(
+            FileReference::at(line, 16),
+            Some(reference("src/lib.rs", Some(3), Some(5)))
+        );
+        assert_eq!(FileReference::at(line, 2), None);
+        assert_eq!(
+            FileReference::find_all("see a.rs:1 and b/c.py:2, then a.rs:1"),
+            vec![
+                reference("a.rs", Some(1), None),
+                reference("b/c.py", Some(2), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_references_against_workspace_roots() {
+        let root = std::env::temp_dir().join(format!("fusang-refs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("crates/a/src")).unwrap();
+        std::fs::create_dir_all(root.join("crates/b/src")).unwrap();
+        std::fs::write(root.join("crates/a/src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("crates/b/src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("crates/a/src/main.rs"), "").unwrap();
+
+        let workspace = Workspace::single_root(&root).unwrap();
+        assert_eq!(
+            workspace.resolve_reference("crates/a/src/lib.rs"),
+            vec![root.join("crates/a/src/lib.rs")]
+        );
+        assert_eq!(
+            workspace.resolve_reference("src/main.rs"),
+            vec![root.join("crates/a/src/main.rs")]
+        );
+        assert_eq!(workspace.resolve_reference("src/lib.rs").len(), 2);
+        assert!(workspace.resolve_reference("src/missing.rs").is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
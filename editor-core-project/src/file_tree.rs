@@ -1,3 +1,7 @@
+use crate::file_journal::FileJournal;
+use crate::workspace::DeleteMode;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
@@ -8,10 +12,17 @@ pub enum FileTreeNode {
         path: PathBuf,
         children: HashMap<String, FileTreeNode>,
         expanded: bool,
+        /// Set when this entry is a symbolic link.
+        link_target: Option<PathBuf>,
+        /// Matched by an ignore file or exclude pattern; only listed with
+        /// [`FileTreeOptions::show_ignored`], and not read until loaded.
+        ignored: bool,
     },
     File {
         name: String,
         path: PathBuf,
+        link_target: Option<PathBuf>,
+        ignored: bool,
     },
 }
 
@@ -38,6 +49,24 @@ impl FileTreeNode {
         matches!(self, FileTreeNode::File { .. })
     }
 
+    pub fn is_symlink(&self) -> bool {
+        self.link_target().is_some()
+    }
+
+    pub fn link_target(&self) -> Option<&Path> {
+        match self {
+            FileTreeNode::Directory { link_target, .. } => link_target.as_deref(),
+            FileTreeNode::File { link_target, .. } => link_target.as_deref(),
+        }
+    }
+
+    pub fn is_ignored(&self) -> bool {
+        match self {
+            FileTreeNode::Directory { ignored, .. } => *ignored,
+            FileTreeNode::File { ignored, .. } => *ignored,
+        }
+    }
+
     pub fn is_expanded(&self) -> bool {
         match self {
             FileTreeNode::Directory { expanded, .. } => *expanded,
@@ -66,67 +95,95 @@ impl FileTreeNode {
     }
 }
 
+/// What a file operation on a [`FileTree`] changed on disk, for the caller to bring
+/// open buffers and language servers in step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileTreeEvent {
+    Created {
+        path: PathBuf,
+        is_dir: bool,
+    },
+    /// `mode` is the one applied, which may differ from the one asked for.
+    Deleted {
+        path: PathBuf,
+        mode: DeleteMode,
+    },
+}
+
+/// What a [`FileTree`] lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTreeOptions {
+    /// Symlinked directories are only descended into when set, and never when they
+    /// point back at one of their own ancestors.
+    pub follow_symlinks: bool,
+    /// Leave out what `.gitignore`, `.ignore`, `.git/info/exclude` and the global
+    /// gitignore ignore.
+    pub respect_ignore_files: bool,
+    /// Gitignore-style patterns relative to the root that are always left out, e.g.
+    /// `dist/` or `*.log`.
+    pub exclude: Vec<String>,
+    /// List ignored entries anyway, marked with [`FileTreeNode::is_ignored`].
+    pub show_ignored: bool,
+}
+
+impl Default for FileTreeOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: true,
+            respect_ignore_files: true,
+            exclude: Vec::new(),
+            show_ignored: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileTree {
     root: FileTreeNode,
+    options: FileTreeOptions,
 }
 
 impl FileTree {
     pub fn new(root_path: PathBuf) -> Result<Self, std::io::Error> {
+        Self::with_options(root_path, FileTreeOptions::default())
+    }
+
+    /// Ignored directories are never read while building, so `node_modules` or
+    /// `target` cost one entry each; see [`FileTree::load_children`].
+    pub fn with_options(
+        root_path: PathBuf,
+        options: FileTreeOptions,
+    ) -> Result<Self, std::io::Error> {
         let root_name = root_path
             .file_name()
             .and_then(|n| n.to_str())
             .unwrap_or("root")
             .to_string();
 
-        let root = Self::build_tree(&root_path, &root_name)?;
-        Ok(Self { root })
-    }
-
-    fn build_tree(path: &Path, name: &str) -> Result<FileTreeNode, std::io::Error> {
-        if path.is_dir() {
-            let mut children = HashMap::new();
-
-            for entry in std::fs::read_dir(path)? {
-                let entry = entry?;
-                let entry_path = entry.path();
-                let entry_name = entry_path
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("")
-                    .to_string();
-
-                // Skip hidden files and directories (starting with .)
-                if entry_name.starts_with('.') {
-                    continue;
-                }
-
-                let node = if entry_path.is_dir() {
-                    Self::build_tree(&entry_path, &entry_name)?
-                } else {
-                    FileTreeNode::File {
-                        name: entry_name.clone(),
-                        path: entry_path,
-                    }
-                };
-
-                children.insert(entry_name, node);
-            }
-
-            Ok(FileTreeNode::Directory {
-                name: name.to_string(),
-                path: path.to_path_buf(),
-                children,
-                expanded: true, // Default to expanded
-            })
-        } else {
-            Ok(FileTreeNode::File {
-                name: name.to_string(),
-                path: path.to_path_buf(),
-            })
+        let mut builder = TreeBuilder::new(&root_path, &options);
+        if options.respect_ignore_files {
+            builder.ignores.push(Gitignore::global().0);
         }
+        let root = builder.build(&root_path, &root_name, None, false)?;
+        Ok(Self { root, options })
     }
 
+    /// Reads the entries of an ignored directory that was listed without them.
+    /// Everything in it is ignored too, and its subdirectories are again left unread.
+    pub fn load_children(&mut self, path: &Path) -> Result<(), std::io::Error> {
+        let root_path = self.root.path().to_path_buf();
+        let mut builder = TreeBuilder::new(&root_path, &self.options);
+        let Some(FileTreeNode::Directory {
+            children,
+            ignored: true,
+            ..
+        }) = Self::find_node_in_tree_mut(&mut self.root, path)
+        else {
+            return Ok(());
+        };
+        *children = builder.read_children(path, true)?;
+        Ok(())
+    }
     pub fn root(&self) -> &FileTreeNode {
         &self.root
     }
@@ -181,10 +238,116 @@ impl FileTree {
         None
     }
 
+    /// Moves the node at `from` to `to` after it was renamed or moved on disk, without
+    /// reading the directories again. A node moved out of the tree, or into a directory
+    /// that isn't read, is dropped. Returns whether the tree changed.
+    pub fn move_node(&mut self, from: &Path, to: &Path) -> bool {
+        let Some(mut node) = from
+            .parent()
+            .and_then(|parent| self.take_child(parent, from))
+        else {
+            return false;
+        };
+        let name = to
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("")
+            .to_string();
+        relocate(&mut node, &name, to);
+        if let Some(children) = to
+            .parent()
+            .and_then(|parent| self.find_node_mut(parent))
+            .and_then(FileTreeNode::children_mut)
+        {
+            children.insert(name, node);
+        }
+        true
+    }
+
+    /// Creates a file with `content` through `journal`, so it can be undone, along with
+    /// missing parent directories, and adds it to the tree.
+    pub fn create_file(
+        &mut self,
+        journal: &mut FileJournal,
+        path: &Path,
+        content: &str,
+    ) -> Result<FileTreeEvent, std::io::Error> {
+        journal.create_file(path, content)?;
+        self.insert_path(path);
+        Ok(FileTreeEvent::Created {
+            path: path.to_path_buf(),
+            is_dir: false,
+        })
+    }
+
+    /// Creates a directory through `journal`, like [`FileTree::create_file`].
+    pub fn create_dir(
+        &mut self,
+        journal: &mut FileJournal,
+        path: &Path,
+    ) -> Result<FileTreeEvent, std::io::Error> {
+        journal.create_dir(path)?;
+        self.insert_path(path);
+        Ok(FileTreeEvent::Created {
+            path: path.to_path_buf(),
+            is_dir: true,
+        })
+    }
+
+    /// Deletes a file or directory through `journal` and removes it from the tree.
+    pub fn delete(
+        &mut self,
+        journal: &mut FileJournal,
+        path: &Path,
+        mode: DeleteMode,
+    ) -> Result<FileTreeEvent, std::io::Error> {
+        let mode = journal.delete(path, mode)?;
+        if let Some(parent) = path.parent() {
+            self.take_child(parent, path);
+        }
+        Ok(FileTreeEvent::Deleted {
+            path: path.to_path_buf(),
+            mode,
+        })
+    }
+
+    /// Adds `path`, which was just created on disk, below the deepest directory of the
+    /// tree it is in, together with the directories created for it.
+    fn insert_path(&mut self, path: &Path) {
+        let root_path = self.root.path().to_path_buf();
+        let Ok(relative) = path.strip_prefix(&root_path) else {
+            return;
+        };
+        let mut parent = root_path.clone();
+        for component in relative.components() {
+            let child = parent.join(component);
+            if self.find_node(&child).is_none() {
+                let name = component.as_os_str().to_string_lossy().into_owned();
+                let mut builder = TreeBuilder::new(&root_path, &self.options);
+                let Ok(node) = builder.build(&child, &name, None, false) else {
+                    return;
+                };
+                if let Some(children) = self
+                    .find_node_mut(&parent)
+                    .and_then(FileTreeNode::children_mut)
+                {
+                    children.insert(name, node);
+                }
+                return;
+            }
+            parent = child;
+        }
+    }
+
+    fn take_child(&mut self, parent: &Path, path: &Path) -> Option<FileTreeNode> {
+        let name = path.file_name()?.to_str()?;
+        self.find_node_mut(parent)?.children_mut()?.remove(name)
+    }
+
     pub fn refresh(&mut self) -> Result<(), std::io::Error> {
         let root_path = self.root.path().to_path_buf();
 
-        *self = Self::new(root_path)?;
+        *self = Self::with_options(root_path, self.options.clone())?;
         Ok(())
     }
 
@@ -227,4 +390,329 @@ impl FileTree {
             }
         }
     }
+
+    /// Visible nodes with their depth, directories first and then by name.
+    pub fn visible_entries(&self) -> Vec<(usize, &FileTreeNode)> {
+        let mut entries = Vec::new();
+        Self::collect_visible_entries(&self.root, 0, &mut entries);
+        entries
+    }
+
+    fn collect_visible_entries<'a>(
+        node: &'a FileTreeNode,
+        depth: usize,
+        entries: &mut Vec<(usize, &'a FileTreeNode)>,
+    ) {
+        entries.push((depth, node));
+
+        if let FileTreeNode::Directory {
+            children, expanded, ..
+        } = node
+        {
+            if *expanded {
+                let mut sorted: Vec<&FileTreeNode> = children.values().collect();
+                sorted.sort_by(|a, b| {
+                    b.is_directory()
+                        .cmp(&a.is_directory())
+                        .then_with(|| a.name().to_lowercase().cmp(&b.name().to_lowercase()))
+                });
+                for child in sorted {
+                    Self::collect_visible_entries(child, depth + 1, entries);
+                }
+            }
+        }
+    }
+}
+
+/// Gives `node` a new name and path, and its descendants the paths under it.
+fn relocate(node: &mut FileTreeNode, new_name: &str, new_path: &Path) {
+    match node {
+        FileTreeNode::Directory {
+            name,
+            path,
+            children,
+            ..
+        } => {
+            *name = new_name.to_string();
+            *path = new_path.to_path_buf();
+            for (child_name, child) in children.iter_mut() {
+                relocate(child, child_name, &new_path.join(child_name));
+            }
+        }
+        FileTreeNode::File { name, path, .. } => {
+            *name = new_name.to_string();
+            *path = new_path.to_path_buf();
+        }
+    }
+}
+
+/// Walks directories for [`FileTree`], keeping the ignore files of the directories
+/// it is in.
+struct TreeBuilder<'a> {
+    options: &'a FileTreeOptions,
+    exclude: Option<Gitignore>,
+    /// Innermost last; the innermost file with a matching pattern decides.
+    ignores: Vec<Gitignore>,
+    /// Canonical paths of the directories being read, to stop at symlink cycles.
+    ancestors: Vec<PathBuf>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn new(root: &Path, options: &'a FileTreeOptions) -> Self {
+        let exclude = (!options.exclude.is_empty()).then(|| {
+            let mut builder = GitignoreBuilder::new(root);
+            for pattern in &options.exclude {
+                // An invalid pattern shouldn't disable the valid ones.
+                let _ = builder.add_line(None, pattern);
+            }
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        });
+        Self {
+            options,
+            exclude,
+            ignores: Vec::new(),
+            ancestors: Vec::new(),
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.matched(path, is_dir).is_ignore())
+        {
+            return true;
+        }
+        for ignore in self.ignores.iter().rev() {
+            match ignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    fn build(
+        &mut self,
+        path: &Path,
+        name: &str,
+        link_target: Option<PathBuf>,
+        ignored: bool,
+    ) -> Result<FileTreeNode, std::io::Error> {
+        if path.is_dir() {
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            let descend = !ignored
+                && (link_target.is_none() || self.options.follow_symlinks)
+                && !self.ancestors.contains(&canonical);
+
+            let mut children = HashMap::new();
+            if descend {
+                self.ancestors.push(canonical);
+                let ignore_files = self.options.respect_ignore_files
+                    && self.push_ignore_files(path, self.ancestors.len() == 1);
+                let read = self.read_children(path, false);
+                if ignore_files {
+                    self.ignores.pop();
+                }
+                self.ancestors.pop();
+                children = read?;
+            }
+
+            Ok(FileTreeNode::Directory {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                children,
+                // Real directories start expanded; linked and ignored ones start collapsed.
+                expanded: link_target.is_none() && !ignored,
+                link_target,
+                ignored,
+            })
+        } else {
+            Ok(FileTreeNode::File {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                link_target,
+                ignored,
+            })
+        }
+    }
+
+    /// Adds the ignore files of `dir`, if it has any; `.ignore` wins over
+    /// `.gitignore`, which wins over `.git/info/exclude` at the root.
+    fn push_ignore_files(&mut self, dir: &Path, is_root: bool) -> bool {
+        let mut files = Vec::new();
+        if is_root {
+            files.push(dir.join(".git/info/exclude"));
+        }
+        files.push(dir.join(".gitignore"));
+        files.push(dir.join(".ignore"));
+        files.retain(|file| file.is_file());
+        if files.is_empty() {
+            return false;
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        for file in files {
+            // Malformed lines are skipped; the rest of the file still applies.
+            let _ = builder.add(file);
+        }
+        match builder.build() {
+            Ok(ignore) => {
+                self.ignores.push(ignore);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// The entries of `dir`; with `ignored` set all of them are ignored.
+    fn read_children(
+        &mut self,
+        dir: &Path,
+        ignored: bool,
+    ) -> Result<HashMap<String, FileTreeNode>, std::io::Error> {
+        let mut children = HashMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let entry_path = entry.path();
+            let entry_name = entry_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("")
+                .to_string();
+
+            // Skip hidden files and directories (starting with .)
+            if entry_name.starts_with('.') {
+                continue;
+            }
+
+            let target = if entry.file_type()?.is_symlink() {
+                std::fs::read_link(&entry_path).ok()
+            } else {
+                None
+            };
+
+            let is_dir = entry_path.is_dir();
+            let ignored = ignored || self.is_ignored(&entry_path, is_dir);
+            if ignored && !self.options.show_ignored {
+                continue;
+            }
+            let is_link = target.is_some();
+            let node = match self.build(&entry_path, &entry_name, target, ignored) {
+                Ok(node) => node,
+                // An unreadable link target shouldn't hide the rest of the tree.
+                Err(_) if is_link => continue,
+                Err(e) => return Err(e),
+            };
+            children.insert(entry_name, node);
+        }
+        Ok(children)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::Workspace;
+
+    #[test]
+    fn symlink_cycles_terminate_and_are_marked() {
+        let root = std::env::temp_dir().join(format!("fusang-links-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("src/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("alias")).unwrap();
+
+        let tree = FileTree::new(root.clone()).unwrap();
+        let looped = tree.find_node(&root.join("src/loop")).unwrap();
+        assert!(looped.is_symlink());
+        assert!(looped.children().unwrap().is_empty());
+        let alias = tree.find_node(&root.join("alias")).unwrap();
+        assert_eq!(alias.children().unwrap().len(), 2);
+
+        let unfollowed = FileTree::with_options(
+            root.clone(),
+            FileTreeOptions {
+                follow_symlinks: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let alias = unfollowed.find_node(&root.join("alias")).unwrap();
+        assert!(alias.children().unwrap().is_empty());
+
+        // `alias` and `src` are the same directory, so each file is listed once.
+        let workspace = Workspace::single_root(&root).unwrap();
+        assert_eq!(workspace.get_files().unwrap().len(), 1);
+        let workspace = workspace.with_follow_symlinks(false);
+        assert_eq!(
+            workspace.get_files().unwrap(),
+            vec![root.join("src/lib.rs")]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ignore_files_and_excludes_hide_or_mark_entries() {
+        let root = std::env::temp_dir().join(format!("fusang-ignored-{}", uuid::Uuid::new_v4()));
+        for dir in ["src", "target/debug", "web/dist", "vendor"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n!keep.log\n").unwrap();
+        std::fs::write(root.join("web/.ignore"), "dist\n").unwrap();
+        for file in [
+            "src/lib.rs",
+            "target/debug/app",
+            "a.log",
+            "keep.log",
+            "web/index.js",
+            "web/dist/bundle.js",
+            "vendor/lib.c",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let options = FileTreeOptions {
+            exclude: vec!["vendor/".to_string()],
+            ..Default::default()
+        };
+
+        let tree = FileTree::with_options(root.clone(), options.clone()).unwrap();
+        for hidden in ["target", "a.log", "web/dist", "vendor"] {
+            assert!(tree.find_node(&root.join(hidden)).is_none(), "{}", hidden);
+        }
+        for shown in ["src/lib.rs", "keep.log", "web/index.js"] {
+            assert!(!tree.find_node(&root.join(shown)).unwrap().is_ignored());
+        }
+
+        let mut tree = FileTree::with_options(
+            root.clone(),
+            FileTreeOptions {
+                show_ignored: true,
+                ..options
+            },
+        )
+        .unwrap();
+        assert!(tree.find_node(&root.join("a.log")).unwrap().is_ignored());
+        assert!(tree.find_node(&root.join("vendor")).unwrap().is_ignored());
+        let target = tree.find_node(&root.join("target")).unwrap();
+        assert!(target.is_ignored() && !target.is_expanded());
+        assert!(target.children().unwrap().is_empty());
+        tree.load_children(&root.join("target")).unwrap();
+        let debug = tree.find_node(&root.join("target/debug")).unwrap();
+        assert!(debug.is_ignored());
+        assert!(debug.children().unwrap().is_empty());
+
+        let unfiltered = FileTree::with_options(
+            root.clone(),
+            FileTreeOptions {
+                respect_ignore_files: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(unfiltered.get_all_files().len(), 7);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
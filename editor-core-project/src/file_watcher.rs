@@ -0,0 +1,260 @@
+use crate::workspace::{WalkOptions, Workspace};
+use notify_debouncer_full::notify::event::{CreateKind, MetadataKind, ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{self, EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long a burst of events, e.g. an atomic save or a `git checkout`, may settle
+/// before it is reported as one batch.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeKind {
+    Created,
+    Changed,
+    Deleted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub kind: FileChangeKind,
+}
+
+/// Reports files created, changed or deleted under a workspace, using the platform's
+/// notification API through a debouncer.
+///
+/// Directories skipped by the [`WalkOptions`] are skipped here too, so builds writing
+/// to `target` don't wake the editor. Watching stops when the watcher is dropped.
+pub struct FileWatcher {
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+    batches: mpsc::UnboundedReceiver<Vec<FileChange>>,
+}
+
+impl FileWatcher {
+    /// Starts watching every root of the workspace recursively.
+    pub fn new(workspace: Workspace, options: WalkOptions) -> notify::Result<Self> {
+        let (sender, batches) = mpsc::unbounded_channel();
+        let roots = workspace.root_paths.clone();
+        let handler = move |result: DebounceEventResult| {
+            // Errors, e.g. a watch limit reached deep in the tree, leave the rest of
+            // the workspace watched; there is no one to report them to here.
+            let Ok(events) = result else {
+                return;
+            };
+            let changes = changes_of(&events, |path| is_watched(&roots, &options, path));
+            if !changes.is_empty() {
+                let _ = sender.send(changes);
+            }
+        };
+        let config = notify::Config::default().with_follow_symlinks(workspace.follow_symlinks);
+        let mut debouncer = notify_debouncer_full::new_debouncer_opt(
+            DEBOUNCE,
+            None,
+            handler,
+            RecommendedCache::new(),
+            config,
+        )?;
+        for root in &workspace.root_paths {
+            debouncer.watch(root, RecursiveMode::Recursive)?;
+        }
+        Ok(Self {
+            _debouncer: debouncer,
+            batches,
+        })
+    }
+
+    /// The next batch of changes, sorted by path, once there is one. `None` once the
+    /// debouncer has stopped.
+    pub async fn changes(&mut self) -> Option<Vec<FileChange>> {
+        self.batches.recv().await
+    }
+}
+
+/// Whether `path` lies under a root and outside the directories the walk skips.
+fn is_watched(roots: &[PathBuf], options: &WalkOptions, path: &Path) -> bool {
+    let Some(relative) = roots.iter().find_map(|root| path.strip_prefix(root).ok()) else {
+        return false;
+    };
+    let Some(parent) = relative.parent() else {
+        return true;
+    };
+    parent.components().all(|component| {
+        let name = component.as_os_str().to_string_lossy();
+        let hidden = options.skip_hidden && name.starts_with('.');
+        !hidden && !options.skip_dirs.iter().any(|dir| *dir == name)
+    })
+}
+
+/// Folds debounced events into one change per file. A file deleted and created again
+/// within the batch was replaced, which is a change; one created and deleted again
+/// never needs reporting.
+fn changes_of(events: &[DebouncedEvent], watched: impl Fn(&Path) -> bool) -> Vec<FileChange> {
+    let mut changes = BTreeMap::new();
+    let mut record = |path: &Path, kind: FileChangeKind| {
+        if !watched(path) {
+            return;
+        }
+        let merged = match (changes.get(path), kind) {
+            (Some(FileChangeKind::Created), FileChangeKind::Deleted) => None,
+            (Some(FileChangeKind::Created), _) => Some(FileChangeKind::Created),
+            (Some(FileChangeKind::Deleted), FileChangeKind::Created) => {
+                Some(FileChangeKind::Changed)
+            }
+            _ => Some(kind),
+        };
+        match merged {
+            Some(kind) => changes.insert(path.to_path_buf(), kind),
+            None => changes.remove(path),
+        };
+    };
+
+    for event in events {
+        let paths = &event.paths;
+        match event.kind {
+            EventKind::Create(CreateKind::Folder) => {}
+            EventKind::Create(_) => paths
+                .iter()
+                .filter(|path| !path.is_dir())
+                .for_each(|path| record(path, FileChangeKind::Created)),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+                record(&paths[0], FileChangeKind::Deleted);
+                if !paths[1].is_dir() {
+                    record(&paths[1], FileChangeKind::Created);
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in paths {
+                    if !path.exists() {
+                        record(path, FileChangeKind::Deleted);
+                    } else if !path.is_dir() {
+                        record(path, FileChangeKind::Created);
+                    }
+                }
+            }
+            EventKind::Modify(ModifyKind::Metadata(kind)) if kind != MetadataKind::WriteTime => {}
+            EventKind::Modify(_) => paths
+                .iter()
+                .filter(|path| !path.is_dir())
+                .for_each(|path| record(path, FileChangeKind::Changed)),
+            EventKind::Remove(_) => paths
+                .iter()
+                .for_each(|path| record(path, FileChangeKind::Deleted)),
+            EventKind::Access(_) | EventKind::Any | EventKind::Other => {}
+        }
+    }
+
+    changes
+        .into_iter()
+        .map(|(path, kind)| FileChange { path, kind })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{DataChange, RemoveKind};
+    use std::time::Instant;
+
+    fn event(kind: EventKind, paths: &[&str]) -> DebouncedEvent {
+        let mut event = notify::Event::new(kind);
+        for path in paths {
+            event = event.add_path(PathBuf::from(path));
+        }
+        DebouncedEvent::new(event, Instant::now())
+    }
+
+    #[test]
+    fn folds_events_into_one_change_per_file() {
+        let events = [
+            event(EventKind::Create(CreateKind::File), &["/w/new.rs"]),
+            event(
+                EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                &["/w/new.rs"],
+            ),
+            event(EventKind::Create(CreateKind::File), &["/w/gone.rs"]),
+            event(EventKind::Remove(RemoveKind::File), &["/w/gone.rs"]),
+            event(EventKind::Remove(RemoveKind::File), &["/w/saved.rs"]),
+            event(EventKind::Create(CreateKind::File), &["/w/saved.rs"]),
+            event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                &["/w/old.rs", "/w/renamed.rs"],
+            ),
+            event(
+                EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)),
+                &["/w/read.rs"],
+            ),
+            event(
+                EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                &["/w/target/debug/out"],
+            ),
+        ];
+        let changes: Vec<_> = changes_of(&events, |path| !path.starts_with("/w/target"))
+            .into_iter()
+            .map(|change| (change.path, change.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (PathBuf::from("/w/new.rs"), FileChangeKind::Created),
+                (PathBuf::from("/w/old.rs"), FileChangeKind::Deleted),
+                (PathBuf::from("/w/renamed.rs"), FileChangeKind::Created),
+                (PathBuf::from("/w/saved.rs"), FileChangeKind::Changed),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_directories_the_walk_skips() {
+        let roots = [PathBuf::from("/w")];
+        let options = WalkOptions {
+            skip_hidden: true,
+            skip_dirs: vec!["target".to_string()],
+            ..Default::default()
+        };
+        assert!(is_watched(&roots, &options, Path::new("/w/src/lib.rs")));
+        assert!(is_watched(&roots, &options, Path::new("/w/.env")));
+        assert!(!is_watched(&roots, &options, Path::new("/w/.git/HEAD")));
+        assert!(!is_watched(&roots, &options, Path::new("/w/target/x")));
+        assert!(!is_watched(&roots, &options, Path::new("/elsewhere/a.rs")));
+    }
+
+    #[tokio::test]
+    async fn reports_created_changed_and_deleted_files() {
+        let root = std::env::temp_dir().join(format!("fusang-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        std::fs::write(root.join("old.rs"), "").unwrap();
+
+        let workspace = Workspace::single_root(&root).unwrap();
+        let mut watcher = FileWatcher::new(workspace, WalkOptions::default()).unwrap();
+
+        std::fs::write(root.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+        std::fs::remove_file(root.join("old.rs")).unwrap();
+        std::fs::write(root.join("new.rs"), "").unwrap();
+
+        let expected = BTreeMap::from([
+            (root.join("Cargo.toml"), FileChangeKind::Changed),
+            (root.join("new.rs"), FileChangeKind::Created),
+            (root.join("old.rs"), FileChangeKind::Deleted),
+        ]);
+        let mut seen = BTreeMap::new();
+        let deadline = Duration::from_secs(10);
+        while seen != expected {
+            let batch = tokio::time::timeout(deadline, watcher.changes())
+                .await
+                .unwrap_or_else(|_| panic!("missing changes, saw {seen:?}"))
+                .unwrap();
+            for change in batch {
+                // A write may arrive as its own batch after the creation it follows.
+                seen.entry(change.path).or_insert(change.kind);
+            }
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,418 @@
+use crate::local_history::DiffLine;
+use editor_infra::config::{ForgeConfig, ForgeKind};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ForgeError {
+    #[error("No access token for {0}; set one in the config or the environment")]
+    MissingToken(String),
+    #[error("HTTP request failed: {0}")]
+    RequestFailed(#[from] reqwest::Error),
+    #[error("Server returned status {status}: {message}")]
+    Status { status: u16, message: String },
+    #[error("Unexpected response: {0}")]
+    Response(#[from] serde_json::Error),
+}
+
+/// A repository hosted on GitHub or GitLab, as found from a git remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForgeRepository {
+    pub kind: ForgeKind,
+    pub host: String,
+    /// `owner/repo`, or `group/subgroup/project` on GitLab.
+    pub path: String,
+    pub api_url: String,
+    token: Option<String>,
+}
+
+impl ForgeRepository {
+    /// Recognizes remotes like `git@github.com:owner/repo.git` or
+    /// `https://gitlab.com/group/project`. github.com and gitlab.com are known; other
+    /// hosts need an entry in `forges`.
+    pub fn from_remote(url: &str, forges: &[ForgeConfig]) -> Option<Self> {
+        let (host, path) = split_remote(url)?;
+        let config = forges.iter().find(|forge| forge.host == host);
+        let kind = match (config, host.as_str()) {
+            (Some(config), _) => config.kind,
+            (None, "github.com") => ForgeKind::GitHub,
+            (None, "gitlab.com") => ForgeKind::GitLab,
+            _ => return None,
+        };
+        let api_url = match (config.and_then(|config| config.api_url.clone()), kind) {
+            (Some(api_url), _) => api_url.trim_end_matches('/').to_string(),
+            (None, ForgeKind::GitHub) if host == "github.com" => {
+                "https://api.github.com".to_string()
+            }
+            (None, ForgeKind::GitHub) => format!("https://{}/api/v3", host),
+            (None, ForgeKind::GitLab) => format!("https://{}/api/v4", host),
+        };
+        let token = config.and_then(|config| config.token.clone()).or_else(|| {
+            let variable = match kind {
+                ForgeKind::GitHub => "GITHUB_TOKEN",
+                ForgeKind::GitLab => "GITLAB_TOKEN",
+            };
+            std::env::var(variable)
+                .ok()
+                .filter(|token| !token.is_empty())
+        });
+        Some(Self {
+            kind,
+            host,
+            path,
+            api_url,
+            token,
+        })
+    }
+
+    /// What the forge calls a pull request, for labels.
+    pub fn noun(&self) -> &'static str {
+        match self.kind {
+            ForgeKind::GitHub => "PR",
+            ForgeKind::GitLab => "MR",
+        }
+    }
+
+    /// The issue's page on the forge's website.
+    pub fn issue_url(&self, number: u64) -> String {
+        match self.kind {
+            ForgeKind::GitHub => format!("https://{}/{}/issues/{}", self.host, self.path, number),
+            ForgeKind::GitLab => {
+                format!("https://{}/{}/-/issues/{}", self.host, self.path, number)
+            }
+        }
+    }
+
+    fn project_url(&self) -> String {
+        match self.kind {
+            ForgeKind::GitHub => format!("{}/repos/{}", self.api_url, self.path),
+            ForgeKind::GitLab => format!(
+                "{}/projects/{}",
+                self.api_url,
+                self.path.replace('/', "%2F")
+            ),
+        }
+    }
+}
+
+/// `(host, path)` of a remote URL, without a `.git` suffix.
+fn split_remote(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let (authority, path) = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?,
+        // scp-like syntax: `git@host:path`
+        None => url.split_once(':')?,
+    };
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?.to_lowercase();
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    (!host.is_empty() && path.contains('/')).then(|| (host, path.to_string()))
+}
+
+/// An open pull request (GitHub) or merge request (GitLab).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub author: String,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub url: String,
+}
+
+impl PullRequest {
+    /// The ref the forge publishes the request's head under, fetchable from the remote
+    /// even when the branch lives in a fork.
+    pub fn head_ref(&self, kind: ForgeKind) -> String {
+        match kind {
+            ForgeKind::GitHub => format!("pull/{}/head", self.number),
+            ForgeKind::GitLab => format!("merge-requests/{}/head", self.number),
+        }
+    }
+
+    /// The local branch the request is checked out into.
+    pub fn local_branch(&self, kind: ForgeKind) -> String {
+        match kind {
+            ForgeKind::GitHub => format!("pr-{}", self.number),
+            ForgeKind::GitLab => format!("mr-{}", self.number),
+        }
+    }
+}
+
+/// One file of a diff, as lines for the diff viewer. Hunk headers are context lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: String,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Splits a unified diff, as `git diff` prints it, into its files.
+pub fn parse_unified_diff(text: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut in_hunk = false;
+    for line in text.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            // `a/old b/new`; the new path names the file
+            let path = header
+                .rsplit_once(" b/")
+                .map_or(header, |(_, path)| path)
+                .to_string();
+            files.push(FileDiff {
+                path,
+                lines: Vec::new(),
+            });
+            in_hunk = false;
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            in_hunk = true;
+            file.lines.push(DiffLine::Context(line.to_string()));
+        } else if !in_hunk {
+            // `index`, `---`, `+++`, mode and rename lines
+        } else if let Some(added) = line.strip_prefix('+') {
+            file.lines.push(DiffLine::Added(added.to_string()));
+        } else if let Some(removed) = line.strip_prefix('-') {
+            file.lines.push(DiffLine::Removed(removed.to_string()));
+        } else if let Some(context) = line.strip_prefix(' ') {
+            file.lines.push(DiffLine::Context(context.to_string()));
+        }
+    }
+    files
+}
+
+/// Lists, diffs and comments on the pull requests of a [`ForgeRepository`] through
+/// the forge's REST API, authenticated with a personal access token.
+#[derive(Debug, Clone)]
+pub struct ForgeClient {
+    repository: ForgeRepository,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl ForgeClient {
+    pub fn new(repository: ForgeRepository) -> Result<Self, ForgeError> {
+        let token = repository
+            .token
+            .clone()
+            .ok_or_else(|| ForgeError::MissingToken(repository.host.clone()))?;
+        Ok(Self {
+            repository,
+            token,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn repository(&self) -> &ForgeRepository {
+        &self.repository
+    }
+
+    /// The open requests, newest first.
+    pub async fn pull_requests(&self) -> Result<Vec<PullRequest>, ForgeError> {
+        let url = match self.repository.kind {
+            ForgeKind::GitHub => format!("{}/pulls?state=open", self.repository.project_url()),
+            ForgeKind::GitLab => format!(
+                "{}/merge_requests?state=opened",
+                self.repository.project_url()
+            ),
+        };
+        let body = self.send(self.client.get(url)).await?;
+        Ok(parse_pull_requests(self.repository.kind, &body)?)
+    }
+
+    /// The request's changes as a unified diff.
+    pub async fn diff(&self, number: u64) -> Result<String, ForgeError> {
+        let project = self.repository.project_url();
+        match self.repository.kind {
+            ForgeKind::GitHub => {
+                let request = self
+                    .client
+                    .get(format!("{}/pulls/{}", project, number))
+                    .header(reqwest::header::ACCEPT, "application/vnd.github.diff");
+                self.send(request).await
+            }
+            ForgeKind::GitLab => {
+                let url = format!("{}/merge_requests/{}/changes", project, number);
+                let body = self.send(self.client.get(url)).await?;
+                Ok(gitlab_changes_diff(&body)?)
+            }
+        }
+    }
+
+    /// Posts `body` as a comment on the request's conversation.
+    pub async fn comment(&self, number: u64, body: &str) -> Result<(), ForgeError> {
+        let project = self.repository.project_url();
+        let url = match self.repository.kind {
+            ForgeKind::GitHub => format!("{}/issues/{}/comments", project, number),
+            ForgeKind::GitLab => format!("{}/merge_requests/{}/notes", project, number),
+        };
+        let request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(json!({ "body": body }).to_string());
+        self.send(request).await.map(drop)
+    }
+
+    /// The title of an issue. On GitHub pull requests share their numbers with issues,
+    /// so `#123` may name either.
+    pub async fn issue_title(&self, number: u64) -> Result<String, ForgeError> {
+        let url = format!("{}/issues/{}", self.repository.project_url(), number);
+        let body = self.send(self.client.get(url)).await?;
+        let value: Value = serde_json::from_str(&body)?;
+        Ok(value["title"].as_str().unwrap_or_default().to_string())
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<String, ForgeError> {
+        let request = match self.repository.kind {
+            ForgeKind::GitHub => request
+                .bearer_auth(&self.token)
+                .header(reqwest::header::USER_AGENT, "fusang"),
+            ForgeKind::GitLab => request.header("PRIVATE-TOKEN", &self.token),
+        };
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            // Both forges explain errors in a `message` field.
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|value| value["message"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            return Err(ForgeError::Status {
+                status: status.as_u16(),
+                message,
+            });
+        }
+        Ok(body)
+    }
+}
+
+fn parse_pull_requests(kind: ForgeKind, body: &str) -> serde_json::Result<Vec<PullRequest>> {
+    let values: Vec<Value> = serde_json::from_str(body)?;
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    Ok(values
+        .iter()
+        .map(|value| match kind {
+            ForgeKind::GitHub => PullRequest {
+                number: value["number"].as_u64().unwrap_or_default(),
+                title: text(&value["title"]),
+                author: text(&value["user"]["login"]),
+                source_branch: text(&value["head"]["ref"]),
+                target_branch: text(&value["base"]["ref"]),
+                url: text(&value["html_url"]),
+            },
+            ForgeKind::GitLab => PullRequest {
+                number: value["iid"].as_u64().unwrap_or_default(),
+                title: text(&value["title"]),
+                author: text(&value["author"]["username"]),
+                source_branch: text(&value["source_branch"]),
+                target_branch: text(&value["target_branch"]),
+                url: text(&value["web_url"]),
+            },
+        })
+        .collect())
+}
+
+/// GitLab hands out the hunks of each file separately; joins them into the unified
+/// diff GitHub returns.
+fn gitlab_changes_diff(body: &str) -> serde_json::Result<String> {
+    let value: Value = serde_json::from_str(body)?;
+    let mut diff = String::new();
+    for change in value["changes"].as_array().into_iter().flatten() {
+        let old_path = change["old_path"].as_str().unwrap_or_default();
+        let new_path = change["new_path"].as_str().unwrap_or_default();
+        diff.push_str(&format!(
+            "diff --git a/{0} b/{1}\n--- a/{0}\n+++ b/{1}\n",
+            old_path, new_path
+        ));
+        diff.push_str(change["diff"].as_str().unwrap_or_default());
+        if !diff.ends_with('\n') {
+            diff.push('\n');
+        }
+    }
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_remotes_and_parses_requests() {
+        let github = ForgeRepository::from_remote("git@github.com:owner/repo.git", &[]).unwrap();
+        assert_eq!(github.kind, ForgeKind::GitHub);
+        assert_eq!(github.path, "owner/repo");
+        assert_eq!(
+            github.project_url(),
+            "https://api.github.com/repos/owner/repo"
+        );
+
+        let gitlab =
+            ForgeRepository::from_remote("https://gitlab.com/group/sub/project", &[]).unwrap();
+        assert_eq!(
+            gitlab.project_url(),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Fproject"
+        );
+
+        let forges = [ForgeConfig {
+            host: "git.example.com".to_string(),
+            kind: ForgeKind::GitLab,
+            token: Some("secret".to_string()),
+            api_url: None,
+        }];
+        let hosted =
+            ForgeRepository::from_remote("ssh://git@git.example.com:2222/team/app.git", &forges)
+                .unwrap();
+        assert_eq!(hosted.api_url, "https://git.example.com/api/v4");
+        assert_eq!(hosted.path, "team/app");
+        assert!(ForgeClient::new(hosted).is_ok());
+        assert!(ForgeRepository::from_remote("git@example.org:a/b.git", &[]).is_none());
+
+        let pulls = parse_pull_requests(
+            ForgeKind::GitHub,
+            r#"[{"number": 7, "title": "Fix", "user": {"login": "ann"},
+                 "head": {"ref": "fix"}, "base": {"ref": "main"}, "html_url": "u"}]"#,
+        )
+        .unwrap();
+        assert_eq!(pulls[0].number, 7);
+        assert_eq!(pulls[0].author, "ann");
+        assert_eq!(pulls[0].head_ref(ForgeKind::GitHub), "pull/7/head");
+        let merges = parse_pull_requests(
+            ForgeKind::GitLab,
+            r#"[{"iid": 3, "title": "Add", "author": {"username": "bo"},
+                 "source_branch": "add", "target_branch": "main", "web_url": "u"}]"#,
+        )
+        .unwrap();
+        assert_eq!(merges[0].source_branch, "add");
+        assert_eq!(merges[0].local_branch(ForgeKind::GitLab), "mr-3");
+    }
+
+    #[test]
+    fn splits_unified_diffs_into_files() {
+        let diff = gitlab_changes_diff(
+            r#"{"changes": [
+                {"old_path": "a.rs", "new_path": "a.rs", "diff": "@@ -1,2 +1,2 @@\n keep\n-old\n+new\n"},
+                {"old_path": "b.rs", "new_path": "c.rs", "diff": "@@ -1 +1 @@\n-x\n+y"}
+            ]}"#,
+        )
+        .unwrap();
+        let files = parse_unified_diff(&diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "a.rs");
+        assert_eq!(
+            files[0].lines,
+            [
+                DiffLine::Context("@@ -1,2 +1,2 @@".to_string()),
+                DiffLine::Context("keep".to_string()),
+                DiffLine::Removed("old".to_string()),
+                DiffLine::Added("new".to_string()),
+            ]
+        );
+        assert_eq!(files[1].path, "c.rs");
+        assert_eq!(files[1].lines.len(), 3);
+    }
+}
@@ -0,0 +1,147 @@
+use editor_infra::config::FormatterConfig;
+use std::io;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// A command that reads source from stdin and writes the formatted source to stdout,
+/// such as `rustfmt`, `black -` or `prettier --stdin-filepath`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalFormatter {
+    pub command: String,
+    /// `{file}` is replaced with the path of the file being formatted.
+    pub args: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl ExternalFormatter {
+    pub fn from_config(config: &FormatterConfig) -> Self {
+        Self {
+            command: config.command.clone(),
+            args: config.args.clone(),
+            timeout: Duration::from_secs(config.timeout_seconds),
+        }
+    }
+
+    /// The formatter configured for `language`, if any.
+    pub fn for_language(formatters: &[FormatterConfig], language: &str) -> Option<Self> {
+        formatters
+            .iter()
+            .find(|config| config.language == language)
+            .map(Self::from_config)
+    }
+
+    /// Runs the command in the file's directory so it picks up project settings such as
+    /// `rustfmt.toml`. The process is killed when it outlives the timeout.
+    pub async fn format(&self, text: &str, path: &Path) -> io::Result<String> {
+        let file = path.display().to_string();
+        let args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace("{file}", &file))
+            .collect();
+        let mut command = Command::new(&self.command);
+        command
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = path.parent().filter(|dir| dir.is_dir()) {
+            command.current_dir(dir);
+        }
+        let mut child = command.spawn().map_err(|e| {
+            io::Error::new(e.kind(), format!("failed to run {}: {}", self.command, e))
+        })?;
+
+        // Written from a separate task so a formatter that streams its output while
+        // reading can't block on a full stdout pipe.
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::other("formatter stdin unavailable"))?;
+        let input = text.to_string();
+        let writer = tokio::spawn(async move {
+            stdin.write_all(input.as_bytes()).await?;
+            stdin.shutdown().await
+        });
+
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "{} timed out after {}s",
+                        self.command,
+                        self.timeout.as_secs_f32()
+                    ),
+                )
+            })??;
+        // A broken pipe only means the formatter exited early; its status says why.
+        let _ = writer.await;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or("no output");
+            return Err(io::Error::other(format!(
+                "{} failed ({}): {}",
+                self.command, output.status, reason
+            )));
+        }
+        String::from_utf8(output.stdout).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} produced invalid UTF-8", self.command),
+            )
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn formatter(command: &str, args: &[&str], timeout: Duration) -> ExternalFormatter {
+        ExternalFormatter {
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            timeout,
+        }
+    }
+
+    #[test]
+    fn pipes_text_through_the_command_and_enforces_the_timeout() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let path = Path::new("/tmp/example.rs");
+            let upper = formatter("tr", &["a-z", "A-Z"], Duration::from_secs(5));
+            assert_eq!(
+                upper.format("fn main() {}\n", path).await.unwrap(),
+                "FN MAIN() {}\n"
+            );
+
+            let echo = formatter("sh", &["-c", "echo $0", "{file}"], Duration::from_secs(5));
+            assert_eq!(echo.format("", path).await.unwrap(), "/tmp/example.rs\n");
+
+            let failing = formatter(
+                "sh",
+                &["-c", "echo bad syntax >&2; exit 1"],
+                Duration::from_secs(5),
+            );
+            let error = failing.format("x", path).await.unwrap_err();
+            assert!(error.to_string().contains("bad syntax"));
+
+            let slow = formatter("sleep", &["5"], Duration::from_millis(100));
+            let error = slow.format("x", path).await.unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+            let missing = formatter("fusang-no-such-formatter", &[], Duration::from_secs(1));
+            assert!(missing.format("x", path).await.is_err());
+        });
+    }
+}
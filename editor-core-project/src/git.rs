@@ -0,0 +1,611 @@
+use editor_core_text::LineDiff;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::process::Command;
+
+#[derive(Error, Debug)]
+pub enum GitError {
+    #[error("Failed to run git: {0}")]
+    Io(#[from] io::Error),
+    #[error("git {command} failed: {message}")]
+    Command { command: String, message: String },
+}
+
+/// An entry of `git stash list`, newest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stash {
+    pub index: usize,
+    pub message: String,
+}
+
+impl Stash {
+    /// The name git knows the stash by, e.g. `stash@{0}`.
+    pub fn reference(&self) -> String {
+        format!("stash@{{{}}}", self.index)
+    }
+}
+
+/// A working tree of the repository; the main one comes first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worktree {
+    pub path: PathBuf,
+    /// Short branch name, or `None` when the worktree is on a detached HEAD.
+    pub branch: Option<String>,
+    pub head: String,
+}
+
+/// A local branch and when it was last committed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    pub name: String,
+    pub last_commit: SystemTime,
+    /// Checked out in this working tree.
+    pub current: bool,
+}
+
+/// How a file differs from HEAD, as `git status` reports it. Staged and unstaged
+/// changes are not told apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Modified,
+    Added,
+    Deleted,
+    Renamed,
+    Untracked,
+    Conflicted,
+}
+
+/// The state of the working tree, for markers in the file tree and the status bar.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitStatus {
+    /// `None` on a detached HEAD.
+    pub branch: Option<String>,
+    /// Changed files by absolute path; unchanged ones are left out.
+    pub files: BTreeMap<PathBuf, FileStatus>,
+}
+
+impl GitStatus {
+    pub fn file(&self, path: &Path) -> Option<FileStatus> {
+        self.files.get(path).copied()
+    }
+
+    /// Whether any file under `dir` changed, for marking folders.
+    pub fn has_changes_in(&self, dir: &Path) -> bool {
+        // Paths order by component, so descendants follow `dir` directly.
+        self.files
+            .range(dir.to_path_buf()..)
+            .next()
+            .is_some_and(|(path, _)| path.starts_with(dir))
+    }
+}
+
+/// Who last changed a line, as `git blame` reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameLine {
+    /// All zeros for lines that aren't committed yet.
+    pub commit: String,
+    pub author: String,
+    pub time: SystemTime,
+    /// The first line of the commit message.
+    pub summary: String,
+}
+
+impl BlameLine {
+    pub fn is_committed(&self) -> bool {
+        self.commit.bytes().any(|b| b != b'0')
+    }
+
+    /// The abbreviated commit hash, as `git log --oneline` shows it.
+    pub fn short_commit(&self) -> &str {
+        &self.commit[..self.commit.len().min(7)]
+    }
+}
+
+/// A git repository, driven through the `git` command so the user's config, hooks and
+/// credentials apply as they do in a terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRepository {
+    root: PathBuf,
+}
+
+impl GitRepository {
+    /// The repository containing `path`, found by looking for `.git` in it and its
+    /// ancestors. Linked worktrees have a `.git` file instead of a directory.
+    pub fn discover(path: &Path) -> Option<Self> {
+        path.ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(|root| Self {
+                root: root.to_path_buf(),
+            })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The current branch and the files that differ from HEAD, untracked ones included.
+    pub async fn status(&self) -> Result<GitStatus, GitError> {
+        let output = self
+            .run(&[
+                "status",
+                "--porcelain=v1",
+                "-z",
+                "--branch",
+                "--untracked-files=all",
+            ])
+            .await?;
+        let mut status = GitStatus::default();
+        let mut entries = output.split('\0');
+        while let Some(entry) = entries.next() {
+            if let Some(header) = entry.strip_prefix("## ") {
+                status.branch = branch_from_header(header);
+                continue;
+            }
+            // `XY path`, followed by the original path for renames and copies
+            let (Some(code), Some(path)) = (entry.get(..2), entry.get(3..)) else {
+                continue;
+            };
+            let file_status = match code.as_bytes() {
+                [b'?', b'?'] => FileStatus::Untracked,
+                [b'U', _] | [_, b'U'] | [b'A', b'A'] | [b'D', b'D'] => FileStatus::Conflicted,
+                [b'R', _] | [_, b'R'] => {
+                    entries.next();
+                    FileStatus::Renamed
+                }
+                [b'C', _] | [_, b'C'] => {
+                    entries.next();
+                    FileStatus::Added
+                }
+                [b'A', _] => FileStatus::Added,
+                [b'D', _] | [_, b'D'] => FileStatus::Deleted,
+                _ => FileStatus::Modified,
+            };
+            status.files.insert(self.root.join(path), file_status);
+        }
+        Ok(status)
+    }
+
+    /// The content of `path` at HEAD, converted the way a checkout would (line endings,
+    /// filters), or `None` when HEAD doesn't have the file.
+    pub async fn head_text(&self, path: &Path) -> Result<Option<String>, GitError> {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let spec = format!("HEAD:{}", relative.to_string_lossy().replace('\\', "/"));
+        if self.run(&["cat-file", "-e", &spec]).await.is_err() {
+            return Ok(None);
+        }
+        self.run(&["cat-file", "--filters", &spec]).await.map(Some)
+    }
+
+    /// The blocks of `text`, the buffer of `path`, that differ from HEAD. Every line
+    /// counts as added when HEAD doesn't have the file.
+    pub async fn changed_hunks(&self, path: &Path, text: &str) -> Result<LineDiff, GitError> {
+        let head = self.head_text(path).await?.unwrap_or_default();
+        Ok(LineDiff::new(&head, text))
+    }
+
+    /// The commit that last changed each line of `path` as saved in the working tree.
+    /// Lines changed since HEAD get an uncommitted entry.
+    pub async fn blame(&self, path: &Path) -> Result<Vec<BlameLine>, GitError> {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let relative = relative.to_string_lossy();
+        let output = self.run(&["blame", "--porcelain", "--", &relative]).await?;
+
+        // Commit details are only given the first time a commit shows up.
+        let mut commits: HashMap<String, BlameLine> = HashMap::new();
+        let mut lines = Vec::new();
+        let mut current: Option<String> = None;
+        for line in output.lines() {
+            if line.starts_with('\t') {
+                if let Some(commit) = current.take() {
+                    lines.push(commits[&commit].clone());
+                }
+                continue;
+            }
+            let Some(commit) = &current else {
+                // `<sha> <original line> <final line> [<lines in group>]`
+                let sha = line.split(' ').next().unwrap_or_default().to_string();
+                commits.entry(sha.clone()).or_insert_with(|| BlameLine {
+                    commit: sha.clone(),
+                    author: String::new(),
+                    time: UNIX_EPOCH,
+                    summary: String::new(),
+                });
+                current = Some(sha);
+                continue;
+            };
+            let entry = commits.get_mut(commit).expect("inserted with the header");
+            if let Some(author) = line.strip_prefix("author ") {
+                entry.author = author.to_string();
+            } else if let Some(time) = line.strip_prefix("author-time ") {
+                let seconds = time.parse().unwrap_or_default();
+                entry.time = UNIX_EPOCH + Duration::from_secs(seconds);
+            } else if let Some(summary) = line.strip_prefix("summary ") {
+                entry.summary = summary.to_string();
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Files with unresolved merge conflicts, relative to the root.
+    pub async fn conflicted_files(&self) -> Result<Vec<PathBuf>, GitError> {
+        let output = self
+            .run(&["diff", "--name-only", "--diff-filter=U"])
+            .await?;
+        Ok(output.lines().map(PathBuf::from).collect())
+    }
+
+    pub async fn stashes(&self) -> Result<Vec<Stash>, GitError> {
+        let output = self.run(&["stash", "list", "--format=%gd%x00%gs"]).await?;
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let (reference, message) = line.split_once('\0')?;
+                let index = reference
+                    .strip_prefix("stash@{")?
+                    .strip_suffix('}')?
+                    .parse()
+                    .ok()?;
+                Some(Stash {
+                    index,
+                    message: message.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Stashes the local changes, untracked files included.
+    pub async fn stash(&self, message: &str) -> Result<(), GitError> {
+        let mut args = vec!["stash", "push", "--include-untracked"];
+        if !message.is_empty() {
+            args.extend(["--message", message]);
+        }
+        self.run(&args).await.map(drop)
+    }
+
+    /// Applies a stash, and drops it afterwards when `pop` is set. A pop that runs into
+    /// conflicts keeps the stash, as git does.
+    pub async fn apply_stash(&self, index: usize, pop: bool) -> Result<(), GitError> {
+        let reference = format!("stash@{{{}}}", index);
+        let command = if pop { "pop" } else { "apply" };
+        self.run(&["stash", command, &reference]).await.map(drop)
+    }
+
+    pub async fn drop_stash(&self, index: usize) -> Result<(), GitError> {
+        let reference = format!("stash@{{{}}}", index);
+        self.run(&["stash", "drop", &reference]).await.map(drop)
+    }
+
+    pub async fn worktrees(&self) -> Result<Vec<Worktree>, GitError> {
+        let output = self.run(&["worktree", "list", "--porcelain"]).await?;
+        let mut worktrees = Vec::new();
+        for block in output.split("\n\n") {
+            let mut worktree = Worktree {
+                path: PathBuf::new(),
+                branch: None,
+                head: String::new(),
+            };
+            for line in block.lines() {
+                if let Some(path) = line.strip_prefix("worktree ") {
+                    worktree.path = PathBuf::from(path);
+                } else if let Some(head) = line.strip_prefix("HEAD ") {
+                    worktree.head = head.to_string();
+                } else if let Some(branch) = line.strip_prefix("branch ") {
+                    let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+                    worktree.branch = Some(branch.to_string());
+                }
+            }
+            if !worktree.path.as_os_str().is_empty() {
+                worktrees.push(worktree);
+            }
+        }
+        Ok(worktrees)
+    }
+
+    /// Checks `branch` out into a new worktree at `path`, creating the branch from HEAD
+    /// when it doesn't exist yet.
+    pub async fn add_worktree(&self, path: &Path, branch: &str) -> Result<(), GitError> {
+        let path = path.to_string_lossy();
+        let local = format!("refs/heads/{}", branch);
+        let exists = self
+            .run(&["rev-parse", "--verify", "--quiet", &local])
+            .await
+            .is_ok();
+        let args: Vec<&str> = if exists {
+            vec!["worktree", "add", &path, branch]
+        } else {
+            vec!["worktree", "add", "-b", branch, &path]
+        };
+        self.run(&args).await.map(drop)
+    }
+
+    /// The local branches, by name.
+    pub async fn branches(&self) -> Result<Vec<Branch>, GitError> {
+        let output = self
+            .run(&[
+                "for-each-ref",
+                "--format=%(HEAD)%00%(refname:short)%00%(committerdate:unix)",
+                "refs/heads",
+            ])
+            .await?;
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\0');
+                let head = fields.next()?;
+                let name = fields.next()?;
+                let seconds = fields.next()?.parse().ok()?;
+                Some(Branch {
+                    name: name.to_string(),
+                    last_commit: UNIX_EPOCH + Duration::from_secs(seconds),
+                    current: head == "*",
+                })
+            })
+            .collect())
+    }
+
+    /// The URL the remote `name` fetches from.
+    pub async fn remote_url(&self, name: &str) -> Result<String, GitError> {
+        let output = self.run(&["remote", "get-url", name]).await?;
+        Ok(output.trim().to_string())
+    }
+
+    /// Fetches `refspec` from `remote` and checks it out as `branch`, resetting the
+    /// branch when it already exists.
+    pub async fn checkout_remote_ref(
+        &self,
+        remote: &str,
+        refspec: &str,
+        branch: &str,
+    ) -> Result<(), GitError> {
+        self.run(&["fetch", remote, refspec]).await?;
+        self.run(&["checkout", "-B", branch, "FETCH_HEAD"])
+            .await
+            .map(drop)
+    }
+
+    async fn run(&self, args: &[&str]) -> Result<String, GitError> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&self.root)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let message = if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            };
+            return Err(GitError::Command {
+                command: args.first().copied().unwrap_or_default().to_string(),
+                message: message.trim().to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// The branch in the header of `git status --branch`, e.g. `main...origin/main [ahead 1]`
+/// or `No commits yet on main`.
+fn branch_from_header(header: &str) -> Option<String> {
+    if header.starts_with("HEAD (no branch)") {
+        return None;
+    }
+    let header = header
+        .strip_prefix("No commits yet on ")
+        .or_else(|| header.strip_prefix("Initial commit on "))
+        .unwrap_or(header);
+    let branch = header.split("...").next()?.split(' ').next()?;
+    Some(branch.to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stashes_and_worktrees_round_trip() {
+        let base = std::env::temp_dir().join(format!("fusang-git-{}", uuid::Uuid::new_v4()));
+        let root = base.join("repo");
+        std::fs::create_dir_all(&root).unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&root)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "--quiet", "--initial-branch=main"]);
+        // Stashing commits too, so the repository needs an identity of its own.
+        git(&["config", "user.name", "Fusang"]);
+        git(&["config", "user.email", "fusang@example.com"]);
+        std::fs::write(root.join("a.txt"), "one\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "init"]);
+
+        let repo = GitRepository::discover(&root.join("a.txt")).unwrap();
+        assert_eq!(repo.root(), root);
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            std::fs::write(root.join("a.txt"), "two\n").unwrap();
+            std::fs::write(root.join("new.txt"), "new\n").unwrap();
+            repo.stash("wip").await.unwrap();
+            assert_eq!(
+                std::fs::read_to_string(root.join("a.txt")).unwrap(),
+                "one\n"
+            );
+            assert!(!root.join("new.txt").exists());
+            let stashes = repo.stashes().await.unwrap();
+            assert_eq!(stashes.len(), 1);
+            assert_eq!(stashes[0].reference(), "stash@{0}");
+            assert!(stashes[0].message.ends_with("wip"));
+
+            repo.apply_stash(0, false).await.unwrap();
+            assert_eq!(
+                std::fs::read_to_string(root.join("a.txt")).unwrap(),
+                "two\n"
+            );
+            assert_eq!(repo.stashes().await.unwrap().len(), 1);
+            repo.drop_stash(0).await.unwrap();
+            assert!(repo.stashes().await.unwrap().is_empty());
+            assert!(repo.conflicted_files().await.unwrap().is_empty());
+            assert!(matches!(
+                repo.apply_stash(0, true).await,
+                Err(GitError::Command { .. })
+            ));
+
+            let linked = base.join("feature");
+            repo.add_worktree(&linked, "feature").await.unwrap();
+            let worktrees = repo.worktrees().await.unwrap();
+            assert_eq!(worktrees.len(), 2);
+            assert_eq!(worktrees[0].branch.as_deref(), Some("main"));
+            assert_eq!(worktrees[1].branch.as_deref(), Some("feature"));
+            assert!(linked.join("a.txt").exists());
+            let branches = repo.branches().await.unwrap();
+            let names: Vec<_> = branches
+                .iter()
+                .map(|branch| (branch.name.as_str(), branch.current))
+                .collect();
+            assert_eq!(names, [("feature", false), ("main", true)]);
+            assert!(branches[0].last_commit > UNIX_EPOCH);
+            assert_eq!(
+                GitRepository::discover(&linked).unwrap().root(),
+                linked.as_path()
+            );
+        });
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn status_reports_branch_files_and_hunks() {
+        let root = std::env::temp_dir().join(format!("fusang-git-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&root)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "--quiet", "--initial-branch=main"]);
+        git(&["config", "user.name", "Fusang"]);
+        git(&["config", "user.email", "fusang@example.com"]);
+        let repo = GitRepository::discover(&root).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        std::fs::write(root.join("src/a.txt"), "one\ntwo\nthree\n").unwrap();
+        let unborn = runtime.block_on(repo.status()).unwrap();
+        assert_eq!(unborn.branch.as_deref(), Some("main"));
+        assert_eq!(
+            unborn.file(&root.join("src/a.txt")),
+            Some(FileStatus::Untracked)
+        );
+
+        std::fs::write(root.join("old.txt"), "old\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "init"]);
+        std::fs::write(root.join("src/a.txt"), "one\n2\nthree\nfour\n").unwrap();
+        std::fs::write(root.join("new.txt"), "new\n").unwrap();
+        git(&["mv", "old.txt", "renamed.txt"]);
+
+        runtime.block_on(async {
+            let status = repo.status().await.unwrap();
+            assert_eq!(status.branch.as_deref(), Some("main"));
+            assert_eq!(status.files.len(), 3);
+            assert_eq!(
+                status.file(&root.join("src/a.txt")),
+                Some(FileStatus::Modified)
+            );
+            assert_eq!(
+                status.file(&root.join("new.txt")),
+                Some(FileStatus::Untracked)
+            );
+            assert_eq!(
+                status.file(&root.join("renamed.txt")),
+                Some(FileStatus::Renamed)
+            );
+            assert!(status.has_changes_in(&root.join("src")));
+            assert!(!status.has_changes_in(&root.join("sr")));
+
+            let path = root.join("src/a.txt");
+            let text = std::fs::read_to_string(&path).unwrap();
+            let hunks = repo.changed_hunks(&path, &text).await.unwrap();
+            let kinds: Vec<_> = hunks
+                .changes()
+                .iter()
+                .map(|c| (c.new.clone(), c.kind()))
+                .collect();
+            assert_eq!(
+                kinds,
+                [
+                    (1..2, editor_core_text::LineChangeKind::Modified),
+                    (3..4, editor_core_text::LineChangeKind::Added),
+                ]
+            );
+            assert_eq!(repo.head_text(&root.join("new.txt")).await.unwrap(), None);
+            let added = repo
+                .changed_hunks(&root.join("new.txt"), "new\n")
+                .await
+                .unwrap();
+            assert_eq!(
+                added.kind_at(0),
+                Some(editor_core_text::LineChangeKind::Added)
+            );
+        });
+        git(&["checkout", "--quiet", "--detach"]);
+        assert_eq!(runtime.block_on(repo.status()).unwrap().branch, None);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn blames_committed_and_uncommitted_lines() {
+        let root = std::env::temp_dir().join(format!("fusang-git-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&root)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "--quiet", "--initial-branch=main"]);
+        git(&["config", "user.name", "Fusang"]);
+        git(&["config", "user.email", "fusang@example.com"]);
+        let path = root.join("a.txt");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "first\n\nbody"]);
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        git(&["commit", "--quiet", "-am", "second"]);
+        std::fs::write(&path, "one\n2\nthree\n").unwrap();
+
+        let repo = GitRepository::discover(&root).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let blame = runtime.block_on(repo.blame(&path)).unwrap();
+        let summaries: Vec<_> = blame.iter().map(|line| line.summary.as_str()).collect();
+        assert_eq!(summaries[0], "first");
+        assert_eq!(summaries[2], "second");
+        assert!(blame[0].is_committed());
+        assert!(!blame[1].is_committed());
+        assert_eq!(blame[0].author, "Fusang");
+        assert_eq!(blame[0].short_commit().len(), 7);
+        assert!(blame[2].time > UNIX_EPOCH);
+        assert!(runtime
+            .block_on(repo.blame(&root.join("missing.txt")))
+            .is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
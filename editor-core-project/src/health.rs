@@ -0,0 +1,203 @@
+use crate::git::Branch;
+use crate::workspace::{WalkOptions, WalkSummary, Workspace};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthOptions {
+    /// Files above this size are reported as large and not searched for TODOs.
+    pub large_file_bytes: u64,
+    /// Branches without commits for this long are reported as stale.
+    pub stale_branch_age: Duration,
+    /// How many files each list keeps.
+    pub top_files: usize,
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        Self {
+            large_file_bytes: 1024 * 1024,
+            stale_branch_age: Duration::from_secs(90 * 24 * 60 * 60),
+            top_files: 10,
+        }
+    }
+}
+
+/// How many of something a file has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCount {
+    pub path: PathBuf,
+    pub count: usize,
+}
+
+/// A summary of what needs attention in a workspace: the files with the most
+/// diagnostics and TODO markers, the largest files and branches nobody committed to
+/// in a while.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    pub generated_at: SystemTime,
+    pub walk: WalkSummary,
+    /// Most first, like every list of the report.
+    pub diagnostics: Vec<FileCount>,
+    pub diagnostics_total: usize,
+    /// `TODO`, `FIXME` and `HACK` markers.
+    pub todos: Vec<FileCount>,
+    pub todos_total: usize,
+    /// Sizes in bytes.
+    pub large_files: Vec<(PathBuf, u64)>,
+    /// Oldest first; the checked out branch is never stale.
+    pub stale_branches: Vec<Branch>,
+}
+
+impl HealthReport {
+    /// Walks the workspace for TODO markers and large files, and combines them with
+    /// the diagnostics per file and the branches gathered by the caller. Blocks until
+    /// the walk is done; run it off the UI thread.
+    pub fn build(
+        workspace: &Workspace,
+        walk: &WalkOptions,
+        options: &HealthOptions,
+        diagnostics: HashMap<PathBuf, usize>,
+        branches: Vec<Branch>,
+    ) -> Self {
+        let marker = Regex::new(r"\b(TODO|FIXME|HACK)\b").expect("valid pattern");
+        let mut todos = Vec::new();
+        let mut large_files = Vec::new();
+        let summary = workspace.walk_files(walk, |batch| {
+            for path in batch {
+                let Ok(metadata) = std::fs::metadata(&path) else {
+                    continue;
+                };
+                if metadata.len() > options.large_file_bytes {
+                    large_files.push((path, metadata.len()));
+                    continue;
+                }
+                let Ok(text) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+                let count = marker.find_iter(&text).count();
+                if count > 0 {
+                    todos.push(FileCount { path, count });
+                }
+            }
+            true
+        });
+
+        let generated_at = SystemTime::now();
+        let mut stale_branches: Vec<Branch> = branches
+            .into_iter()
+            .filter(|branch| {
+                !branch.current
+                    && generated_at
+                        .duration_since(branch.last_commit)
+                        .is_ok_and(|age| age >= options.stale_branch_age)
+            })
+            .collect();
+        stale_branches.sort_by_key(|branch| branch.last_commit);
+        large_files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        large_files.truncate(options.top_files);
+
+        let diagnostics = diagnostics
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(path, count)| FileCount { path, count })
+            .collect();
+        let (diagnostics, diagnostics_total) = top(diagnostics, options.top_files);
+        let (todos, todos_total) = top(todos, options.top_files);
+        Self {
+            generated_at,
+            walk: summary,
+            diagnostics,
+            diagnostics_total,
+            todos,
+            todos_total,
+            large_files,
+            stale_branches,
+        }
+    }
+}
+
+/// The `limit` files with the highest counts, and the sum of all counts.
+fn top(mut files: Vec<FileCount>, limit: usize) -> (Vec<FileCount>, usize) {
+    let total = files.iter().map(|file| file.count).sum();
+    files.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.path.cmp(&b.path)));
+    files.truncate(limit);
+    (files, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn reports_todos_large_files_diagnostics_and_stale_branches() {
+        let root = std::env::temp_dir().join(format!("fusang-health-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("src/a.rs"),
+            "// TODO: one\n// FIXME two\nlet todo_list = 1; // HACK\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("src/b.rs"), "// TODO(me): later\n").unwrap();
+        std::fs::write(root.join("src/clean.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("big.bin"), vec![b'x'; 2048]).unwrap();
+
+        let diagnostics = HashMap::from([
+            (root.join("src/a.rs"), 1),
+            (root.join("src/b.rs"), 4),
+            (root.join("src/clean.rs"), 0),
+        ]);
+        let branch = |name: &str, last_commit, current| Branch {
+            name: name.to_string(),
+            last_commit,
+            current,
+        };
+        let branches = vec![
+            branch("main", UNIX_EPOCH, true),
+            branch("fresh", SystemTime::now(), false),
+            branch("newer", UNIX_EPOCH + Duration::from_secs(60), false),
+            branch("old", UNIX_EPOCH, false),
+        ];
+        let options = HealthOptions {
+            large_file_bytes: 1024,
+            top_files: 1,
+            ..Default::default()
+        };
+        let report = HealthReport::build(
+            &Workspace::single_root(&root).unwrap(),
+            &WalkOptions::default(),
+            &options,
+            diagnostics,
+            branches,
+        );
+
+        assert_eq!(
+            report.todos,
+            [FileCount {
+                path: root.join("src/a.rs"),
+                count: 3
+            }]
+        );
+        assert_eq!(report.todos_total, 4);
+        assert_eq!(
+            report.diagnostics,
+            [FileCount {
+                path: root.join("src/b.rs"),
+                count: 4
+            }]
+        );
+        assert_eq!(report.diagnostics_total, 5);
+        assert_eq!(report.large_files, [(root.join("big.bin"), 2048)]);
+        let stale: Vec<_> = report
+            .stale_branches
+            .iter()
+            .map(|branch| branch.name.as_str())
+            .collect();
+        assert_eq!(stale, ["old", "newer"]);
+        assert_eq!(report.walk.files, 4);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
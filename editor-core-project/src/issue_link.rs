@@ -0,0 +1,191 @@
+use crate::forge::ForgeRepository;
+use editor_infra::config::IssueTrackerConfig;
+use regex::Regex;
+use std::ops::Range;
+use std::path::Path;
+
+/// An issue mentioned in free text, e.g. `#123` or `JIRA-456`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssueReference {
+    /// Char columns of the mention in its line.
+    pub columns: Range<usize>,
+    pub id: String,
+    /// The issue number on the repository's forge, for `#123` style mentions.
+    pub number: Option<u64>,
+    pub url: String,
+}
+
+/// Finds issue mentions and the tracker pages they link to. Configured trackers come
+/// first; `#123` links to the issues of the forge the repository is hosted on.
+#[derive(Debug, Clone)]
+pub struct IssueLinker {
+    trackers: Vec<(Regex, String)>,
+    forge: Option<ForgeRepository>,
+    forge_pattern: Regex,
+}
+
+impl IssueLinker {
+    /// Trackers restricted to a `workspace` that doesn't contain `workspace_root` are
+    /// left out.
+    pub fn new(
+        trackers: &[IssueTrackerConfig],
+        workspace_root: &Path,
+        forge: Option<ForgeRepository>,
+    ) -> Result<Self, regex::Error> {
+        let trackers = trackers
+            .iter()
+            .filter(|tracker| {
+                tracker
+                    .workspace
+                    .as_ref()
+                    .is_none_or(|dir| workspace_root.starts_with(dir))
+            })
+            .map(|tracker| Ok((Regex::new(&tracker.pattern)?, tracker.url.clone())))
+            .collect::<Result<_, regex::Error>>()?;
+        Ok(Self {
+            trackers,
+            forge,
+            forge_pattern: Regex::new(r"#(\d+)\b").expect("valid pattern"),
+        })
+    }
+
+    /// The forge `#123` mentions link to.
+    pub fn forge(&self) -> Option<&ForgeRepository> {
+        self.forge.as_ref()
+    }
+
+    /// Nothing can be linked: no tracker applies and the repository isn't on a forge.
+    pub fn is_empty(&self) -> bool {
+        self.trackers.is_empty() && self.forge.is_none()
+    }
+
+    /// The mentions in `text`, in order. Where mentions overlap the earlier one wins,
+    /// and configured trackers win over the forge at the same position.
+    pub fn find_all(&self, text: &str) -> Vec<IssueReference> {
+        let mut found: Vec<(Range<usize>, String, Option<u64>, String)> = Vec::new();
+        for (pattern, url) in &self.trackers {
+            for captures in pattern.captures_iter(text) {
+                let whole = captures.get(0).expect("group 0 always matches");
+                if whole.is_empty() {
+                    continue;
+                }
+                let mut link = String::new();
+                captures.expand(url, &mut link);
+                found.push((whole.range(), whole.as_str().to_string(), None, link));
+            }
+        }
+        if let Some(forge) = &self.forge {
+            for captures in self.forge_pattern.captures_iter(text) {
+                let whole = captures.get(0).expect("group 0 always matches");
+                // `&#123;` is a character reference and `a#1` part of a word
+                let preceded_by_word = text[..whole.start()]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|c| c.is_alphanumeric() || c == '&' || c == '_');
+                let Ok(number) = captures[1].parse::<u64>() else {
+                    continue;
+                };
+                if !preceded_by_word {
+                    found.push((
+                        whole.range(),
+                        whole.as_str().to_string(),
+                        Some(number),
+                        forge.issue_url(number),
+                    ));
+                }
+            }
+        }
+        // Stable, so configured trackers stay ahead of the forge at the same start.
+        found.sort_by_key(|(range, ..)| range.start);
+
+        let mut references: Vec<IssueReference> = Vec::new();
+        let mut covered = 0;
+        for (range, id, number, url) in found {
+            if range.start < covered {
+                continue;
+            }
+            covered = range.end;
+            let start = text[..range.start].chars().count();
+            references.push(IssueReference {
+                columns: start..start + id.chars().count(),
+                id,
+                number,
+                url,
+            });
+        }
+        references
+    }
+
+    /// The mention under `column` (a char index) in `line`.
+    pub fn at(&self, line: &str, column: usize) -> Option<IssueReference> {
+        self.find_all(line)
+            .into_iter()
+            .find(|reference| reference.columns.contains(&column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_configured_and_forge_mentions() {
+        let trackers = [
+            IssueTrackerConfig {
+                pattern: r"\b(JIRA)-(\d+)\b".to_string(),
+                url: "https://jira.example.com/browse/$1-$2".to_string(),
+                workspace: None,
+            },
+            IssueTrackerConfig {
+                pattern: r"\bOPS-\d+\b".to_string(),
+                url: "https://ops.example.com/$0".to_string(),
+                workspace: Some("/work/ops".into()),
+            },
+        ];
+        let forge = ForgeRepository::from_remote("git@github.com:owner/repo.git", &[]);
+        let linker = IssueLinker::new(&trackers, Path::new("/work/app"), forge).unwrap();
+
+        let line = "// fixes #12 and JIRA-456, not OPS-1 or &#39; or a#3 (#7)";
+        let found: Vec<_> = linker
+            .find_all(line)
+            .into_iter()
+            .map(|reference| (reference.columns, reference.id, reference.url))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    9..12,
+                    "#12".to_string(),
+                    "https://github.com/owner/repo/issues/12".to_string()
+                ),
+                (
+                    17..25,
+                    "JIRA-456".to_string(),
+                    "https://jira.example.com/browse/JIRA-456".to_string()
+                ),
+                (
+                    54..56,
+                    "#7".to_string(),
+                    "https://github.com/owner/repo/issues/7".to_string()
+                ),
+            ]
+        );
+        assert_eq!(linker.at(line, 10).unwrap().number, Some(12));
+        assert_eq!(linker.at(line, 20).unwrap().number, None);
+        assert_eq!(linker.at(line, 5), None);
+
+        let ops = IssueLinker::new(&trackers, Path::new("/work/ops/api"), None).unwrap();
+        assert_eq!(ops.find_all("OPS-1 #2").len(), 1);
+        assert!(IssueLinker::new(&[], Path::new("/"), None)
+            .unwrap()
+            .is_empty());
+
+        let invalid = [IssueTrackerConfig {
+            pattern: "(".to_string(),
+            url: String::new(),
+            workspace: None,
+        }];
+        assert!(IssueLinker::new(&invalid, Path::new("/"), None).is_err());
+    }
+}
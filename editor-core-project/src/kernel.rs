@@ -0,0 +1,358 @@
+use crate::notebook::CellOutput;
+use serde_json::{json, Value};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+/// Runs cells in a Python process that keeps its globals between them. It speaks a
+/// small subset of the Jupyter messaging protocol as JSON lines over its own copies of
+/// stdin and stdout: `execute_request` in, then `stream`, `display_data`,
+/// `execute_result` and `error` messages with the usual content, ending with
+/// `execute_reply`. A `status` message says the kernel is ready. Cells get an empty stdin, and what they write straight to fd 1 is
+/// dropped, so neither can get in the way of a message. SIGINT raises
+/// `KeyboardInterrupt` in the running cell and is ignored between cells.
+const DRIVER: &str = r#"
+import ast, base64, contextlib, io, json, os, signal, sys, traceback
+
+_in = os.fdopen(os.dup(0), "r", encoding="utf-8")
+_out = os.fdopen(os.dup(1), "w", encoding="utf-8")
+_null = os.open(os.devnull, os.O_RDWR)
+os.dup2(_null, 0)
+os.dup2(_null, 1)
+sys.stdin = open(os.devnull)
+_running = False
+
+def _interrupt(signum, frame):
+    if _running:
+        raise KeyboardInterrupt
+
+signal.signal(signal.SIGINT, _interrupt)
+
+_parent = None
+_count = 0
+_ns = {"__name__": "__main__"}
+
+def _send(msg_type, content):
+    _out.write(json.dumps({"parent": _parent, "msg_type": msg_type, "content": content}) + "\n")
+    _out.flush()
+
+class _Stream(io.TextIOBase):
+    def __init__(self, name):
+        self.name = name
+    def write(self, text):
+        if text:
+            _send("stream", {"name": self.name, "text": text})
+        return len(text)
+
+def _bundle(value):
+    data = {"text/plain": repr(value)}
+    for mime, method in (("text/html", "_repr_html_"), ("image/png", "_repr_png_"),
+                         ("image/jpeg", "_repr_jpeg_")):
+        render = getattr(value, method, None)
+        if callable(render):
+            try:
+                rendered = render()
+            except Exception:
+                rendered = None
+            if isinstance(rendered, bytes):
+                rendered = base64.b64encode(rendered).decode()
+            if rendered is not None:
+                data[mime] = rendered
+    return data
+
+def display(value):
+    _send("display_data", {"data": _bundle(value), "metadata": {}})
+
+_ns["display"] = display
+_send("status", {"execution_state": "idle"})
+
+for _line in _in:
+    _msg = json.loads(_line)
+    _parent = _msg["msg_id"]
+    _count += 1
+    _status = "ok"
+    with contextlib.redirect_stdout(_Stream("stdout")), contextlib.redirect_stderr(_Stream("stderr")):
+        try:
+            _running = True
+            _tree = ast.parse(_msg["content"]["code"], "<cell>", "exec")
+            _last = None
+            if _tree.body and isinstance(_tree.body[-1], ast.Expr):
+                _last = ast.Expression(_tree.body.pop().value)
+            exec(compile(_tree, "<cell>", "exec"), _ns)
+            if _last is not None:
+                _value = eval(compile(_last, "<cell>", "eval"), _ns)
+                if _value is not None:
+                    _send("execute_result", {"execution_count": _count, "data": _bundle(_value), "metadata": {}})
+        except BaseException as _e:
+            _running = False
+            _status = "error"
+            _send("error", {"ename": type(_e).__name__, "evalue": str(_e),
+                            "traceback": [_l.rstrip("\n") for _l in traceback.format_exception(type(_e), _e, _e.__traceback__)]})
+        finally:
+            _running = False
+    _send("execute_reply", {"status": _status, "execution_count": _count})
+"#;
+
+/// What running one cell produced.
+#[derive(Debug, Clone, Default)]
+pub struct Execution {
+    pub outputs: Vec<CellOutput>,
+    pub execution_count: Option<u64>,
+    /// The cell raised; its error is among the outputs.
+    pub failed: bool,
+}
+
+/// Interrupts the cell a [`PythonKernel`] is running, without waiting for
+/// [`PythonKernel::execute`] to give the kernel back.
+#[derive(Debug, Clone, Copy)]
+pub struct KernelInterrupt {
+    pid: Option<u32>,
+}
+
+impl KernelInterrupt {
+    /// Sends SIGINT to the kernel, so the running cell ends with a `KeyboardInterrupt`
+    /// error. Does nothing between cells.
+    pub fn interrupt(&self) -> std::io::Result<()> {
+        let pid = self
+            .pid
+            .ok_or_else(|| std::io::Error::other("kernel has exited"))?;
+        send_sigint(pid)
+    }
+}
+
+#[cfg(unix)]
+fn send_sigint(pid: u32) -> std::io::Result<()> {
+    let pid = libc::pid_t::try_from(pid).map_err(std::io::Error::other)?;
+    // SAFETY: kill only sends a signal; it has no memory-safety requirements.
+    if unsafe { libc::kill(pid, libc::SIGINT) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn send_sigint(_pid: u32) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "interrupting the kernel needs signals",
+    ))
+}
+
+#[derive(Debug)]
+pub struct PythonKernel {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+}
+
+impl PythonKernel {
+    /// Starts `python` (e.g. `python3`) with an empty namespace, and waits until it can
+    /// take cells and interrupts.
+    pub async fn start(python: &str) -> std::io::Result<Self> {
+        let mut child = Command::new(python)
+            .arg("-u")
+            .arg("-c")
+            .arg(DRIVER)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| std::io::Error::other("kernel has no stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| std::io::Error::other("kernel has no stdout"))?;
+        let mut stdout = BufReader::new(stdout).lines();
+        while let Some(line) = stdout.next_line().await? {
+            let ready = serde_json::from_str::<Value>(&line)
+                .is_ok_and(|message| message["msg_type"] == "status");
+            if ready {
+                return Ok(Self {
+                    child,
+                    stdin,
+                    stdout,
+                    next_id: 0,
+                });
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "kernel exited",
+        ))
+    }
+
+    /// A handle that interrupts this kernel while [`execute`](Self::execute) holds it.
+    pub fn interrupt_handle(&self) -> KernelInterrupt {
+        KernelInterrupt {
+            pid: self.child.id(),
+        }
+    }
+
+    /// Runs `code` and collects its outputs. A cell that never finishes blocks until
+    /// it is interrupted, or the kernel is dropped or shut down.
+    pub async fn execute(&mut self, code: &str) -> std::io::Result<Execution> {
+        self.next_id += 1;
+        let msg_id = format!("execute-{}", self.next_id);
+        let request = json!({
+            "msg_id": msg_id,
+            "msg_type": "execute_request",
+            "content": {"code": code},
+        });
+        self.stdin
+            .write_all(format!("{}\n", request).as_bytes())
+            .await?;
+        self.stdin.flush().await?;
+
+        let mut execution = Execution::default();
+        while let Some(line) = self.stdout.next_line().await? {
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if message["parent"].as_str() != Some(msg_id.as_str()) {
+                continue;
+            }
+            let msg_type = message["msg_type"].as_str().unwrap_or_default();
+            let content = &message["content"];
+            if msg_type == "execute_reply" {
+                execution.execution_count = content["execution_count"].as_u64();
+                execution.failed = content["status"] != "ok";
+                return Ok(execution);
+            }
+            if let Some(output) = CellOutput::from_json(msg_type, content) {
+                // Consecutive writes to one stream read as one output, like Jupyter shows them.
+                match (execution.outputs.last_mut(), output) {
+                    (
+                        Some(CellOutput::Stream { name, text }),
+                        CellOutput::Stream {
+                            name: new_name,
+                            text: new_text,
+                        },
+                    ) if *name == new_name => text.push_str(&new_text),
+                    (_, output) => execution.outputs.push(output),
+                }
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "kernel exited",
+        ))
+    }
+
+    pub async fn shutdown(mut self) -> std::io::Result<()> {
+        self.child.kill().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A kernel on `python3`, or `None` with a note when there is none to run cells with.
+    async fn start_kernel() -> Option<PythonKernel> {
+        match PythonKernel::start("python3").await {
+            Ok(kernel) => Some(kernel),
+            Err(e) => {
+                eprintln!("skipping: cannot start python3: {}", e);
+                None
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_state_between_cells() {
+        let Some(mut kernel) = start_kernel().await else {
+            return;
+        };
+        let first = kernel
+            .execute("x = 40\nprint('a')\nprint('b')\nx + 2")
+            .await
+            .unwrap();
+        assert!(!first.failed);
+        let texts: Vec<_> = first.outputs.iter().filter_map(CellOutput::text).collect();
+        assert_eq!(texts, ["a\nb\n", "42"]);
+        assert_eq!(first.execution_count, Some(1));
+
+        let second = kernel.execute("x * 2").await.unwrap();
+        assert_eq!(second.outputs[0].text().as_deref(), Some("80"));
+        assert_eq!(second.execution_count, Some(2));
+        kernel.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_errors_and_keeps_running() {
+        let Some(mut kernel) = start_kernel().await else {
+            return;
+        };
+        let failed = kernel.execute("1 / 0").await.unwrap();
+        assert!(failed.failed);
+        assert!(failed.outputs[0]
+            .text()
+            .unwrap()
+            .ends_with("ZeroDivisionError: division by zero"));
+        assert!(kernel.execute("def f(:").await.unwrap().failed);
+        assert!(!kernel.execute("1").await.unwrap().failed);
+        kernel.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cells_cannot_read_or_write_the_protocol() {
+        let Some(mut kernel) = start_kernel().await else {
+            return;
+        };
+        let read = kernel.execute("input()").await.unwrap();
+        assert!(read.failed);
+        assert!(read.outputs[0]
+            .text()
+            .unwrap()
+            .ends_with("EOFError: EOF when reading a line"));
+
+        let raw = kernel
+            .execute("import os\nos.write(1, b'not json\\n')\nprint('ok')")
+            .await
+            .unwrap();
+        assert!(!raw.failed);
+        let texts: Vec<_> = raw.outputs.iter().filter_map(CellOutput::text).collect();
+        assert_eq!(texts, ["ok\n"]);
+        kernel.shutdown().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn interrupt_stops_the_running_cell() {
+        let Some(mut kernel) = start_kernel().await else {
+            return;
+        };
+        let interrupt = kernel.interrupt_handle();
+        // Between cells the signal is ignored.
+        interrupt.interrupt().unwrap();
+        assert!(!kernel.execute("x = 1").await.unwrap().failed);
+
+        let interrupter = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            interrupt.interrupt().unwrap();
+        });
+        let stopped = kernel
+            .execute("import time\nwhile True:\n    time.sleep(0.01)")
+            .await
+            .unwrap();
+        interrupter.await.unwrap();
+        assert!(stopped.failed);
+        assert!(stopped.outputs[0]
+            .text()
+            .unwrap()
+            .ends_with("KeyboardInterrupt"));
+        assert_eq!(
+            kernel.execute("x").await.unwrap().outputs[0]
+                .text()
+                .as_deref(),
+            Some("1")
+        );
+        kernel.shutdown().await.unwrap();
+    }
+}
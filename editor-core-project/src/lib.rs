@@ -1,7 +1,52 @@
 pub mod buffer_manager;
+pub mod closed_tabs;
+pub mod edit_journal;
+pub mod edit_preview;
+pub mod edit_queue;
+pub mod external_tool;
+pub mod file_info;
+pub mod file_journal;
+pub mod file_reference;
 pub mod file_tree;
+pub mod file_watcher;
+pub mod forge;
+pub mod formatter;
+pub mod git;
+pub mod health;
+pub mod issue_link;
+pub mod kernel;
+pub mod local_history;
+pub mod notebook;
+pub mod project_search;
+pub mod recent;
+pub mod remote;
 pub mod workspace;
 
-pub use buffer_manager::BufferManager;
-pub use file_tree::{FileTree, FileTreeNode};
-pub use workspace::{Workspace, WorkspaceError};
+pub use buffer_manager::{
+    language_from_path, AgentEditEvent, BufferManager, BufferMemoryReport, BufferMetadata,
+    DiskChangeEvent, NavigationEntry,
+};
+pub use closed_tabs::{ClosedTab, ClosedTabs, Draft};
+pub use edit_journal::EditJournals;
+pub use edit_preview::{EditPreview, FilePreview, PreviewHunk};
+pub use edit_queue::EditQueue;
+pub use external_tool::{ExternalTool, ToolContext, ToolRun};
+pub use file_info::FileInfo;
+pub use file_journal::{FileJournal, FileOperation};
+pub use file_reference::FileReference;
+pub use file_tree::{FileTree, FileTreeEvent, FileTreeNode, FileTreeOptions};
+pub use file_watcher::{FileChange, FileChangeKind, FileWatcher};
+pub use forge::{
+    parse_unified_diff, FileDiff, ForgeClient, ForgeError, ForgeRepository, PullRequest,
+};
+pub use formatter::ExternalFormatter;
+pub use git::{BlameLine, Branch, FileStatus, GitError, GitRepository, GitStatus, Stash, Worktree};
+pub use health::{FileCount, HealthOptions, HealthReport};
+pub use issue_link::{IssueLinker, IssueReference};
+pub use kernel::{Execution, KernelInterrupt, PythonKernel};
+pub use local_history::{changed_words, diff_lines, DiffLine, LocalHistory, Snapshot};
+pub use notebook::{CellKind, CellOutput, Notebook, NotebookCell, NotebookError};
+pub use project_search::{ProjectMatch, ProjectSearch, ProjectSearchOptions, ProjectSearchResults};
+pub use recent::{RecentEntry, RecentHistory};
+pub use remote::{is_remote_url, RemoteError, RemoteFetcher};
+pub use workspace::{DeleteMode, WalkOptions, WalkSummary, Workspace, WorkspaceError};
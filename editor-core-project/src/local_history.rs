@@ -0,0 +1,249 @@
+use editor_core_text::WordDiff;
+use similar::{ChangeTag, TextDiff};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_MAX_SNAPSHOTS: usize = 50;
+const SNAPSHOT_EXTENSION: &str = "snap";
+const SOURCE_FILE: &str = "source";
+
+/// A saved version of a file kept by [`LocalHistory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub file_path: PathBuf,
+    pub timestamp: SystemTime,
+    storage_path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Context(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Timestamped copies of files taken on every save, independent of any VCS.
+///
+/// Each file gets its own directory under `root`, keyed by a stable hash of its path,
+/// holding at most `max_snapshots` versions. Older versions are pruned first.
+#[derive(Debug, Clone)]
+pub struct LocalHistory {
+    root: PathBuf,
+    max_snapshots: usize,
+}
+
+impl LocalHistory {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_snapshots: DEFAULT_MAX_SNAPSHOTS,
+        }
+    }
+
+    pub fn default_location() -> PathBuf {
+        editor_infra::paths::data_dir().join("history")
+    }
+
+    pub fn with_max_snapshots(mut self, max_snapshots: usize) -> Self {
+        self.max_snapshots = max_snapshots.max(1);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Store `content` as the newest snapshot of `file_path`. Returns `None` when it is
+    /// identical to the previous snapshot.
+    pub fn record(&self, file_path: &Path, content: &str) -> std::io::Result<Option<Snapshot>> {
+        let existing = self.snapshots(file_path)?;
+        if let Some(latest) = existing.first() {
+            if self.read(latest)? == content {
+                return Ok(None);
+            }
+        }
+
+        let dir = self.file_dir(file_path);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join(SOURCE_FILE),
+            file_path.to_string_lossy().as_bytes(),
+        )?;
+
+        // Never go backwards, even if the clock does or two saves land in the same millisecond.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let latest = existing
+            .first()
+            .and_then(|s| s.timestamp.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() + 1)
+            .unwrap_or(0);
+        let millis = now.max(latest);
+
+        let storage_path = dir.join(format!("{}.{}", millis, SNAPSHOT_EXTENSION));
+        std::fs::write(&storage_path, content)?;
+
+        for stale in existing.iter().skip(self.max_snapshots.saturating_sub(1)) {
+            let _ = std::fs::remove_file(&stale.storage_path);
+        }
+
+        Ok(Some(Snapshot {
+            file_path: file_path.to_path_buf(),
+            timestamp: UNIX_EPOCH + Duration::from_millis(millis as u64),
+            storage_path,
+        }))
+    }
+
+    /// Snapshots of `file_path`, newest first.
+    pub fn snapshots(&self, file_path: &Path) -> std::io::Result<Vec<Snapshot>> {
+        let dir = self.file_dir(file_path);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SNAPSHOT_EXTENSION) {
+                continue;
+            }
+            let Some(millis) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            snapshots.push(Snapshot {
+                file_path: file_path.to_path_buf(),
+                timestamp: UNIX_EPOCH + Duration::from_millis(millis),
+                storage_path: path,
+            });
+        }
+
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.timestamp));
+        Ok(snapshots)
+    }
+
+    pub fn read(&self, snapshot: &Snapshot) -> std::io::Result<String> {
+        std::fs::read_to_string(&snapshot.storage_path)
+    }
+
+    pub fn clear(&self, file_path: &Path) -> std::io::Result<()> {
+        let dir = self.file_dir(file_path);
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    fn file_dir(&self, file_path: &Path) -> PathBuf {
+        self.root.join(format!(
+            "{:016x}",
+            stable_hash(&file_path.to_string_lossy())
+        ))
+    }
+}
+
+/// Line diff from `old` to `new`.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| {
+            let line = change.value().trim_end_matches(['\r', '\n']).to_string();
+            match change.tag() {
+                ChangeTag::Equal => DiffLine::Context(line),
+                ChangeTag::Insert => DiffLine::Added(line),
+                ChangeTag::Delete => DiffLine::Removed(line),
+            }
+        })
+        .collect()
+}
+
+/// Byte ranges to emphasize in each line of `diff`: the words that changed between a
+/// removed line and the added line that replaced it. Within a block the n-th removed
+/// line is paired with the n-th added one; context and unpaired lines get no ranges.
+pub fn changed_words(diff: &[DiffLine]) -> Vec<Vec<Range<usize>>> {
+    let mut words = vec![Vec::new(); diff.len()];
+    let mut idx = 0;
+    while idx < diff.len() {
+        let removed_end = idx
+            + diff[idx..]
+                .iter()
+                .take_while(|line| matches!(line, DiffLine::Removed(_)))
+                .count();
+        let added_end = removed_end
+            + diff[removed_end..]
+                .iter()
+                .take_while(|line| matches!(line, DiffLine::Added(_)))
+                .count();
+        if added_end == idx {
+            idx += 1;
+            continue;
+        }
+        for (old_idx, new_idx) in (idx..removed_end).zip(removed_end..added_end) {
+            if let (DiffLine::Removed(old), DiffLine::Added(new)) = (&diff[old_idx], &diff[new_idx])
+            {
+                let changed = WordDiff::new(old, new);
+                words[old_idx] = changed.old;
+                words[new_idx] = changed.new;
+            }
+        }
+        idx = added_end;
+    }
+    words
+}
+
+/// FNV-1a, so history directories stay valid across builds.
+pub(crate) fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_skips_duplicates_and_prunes_oldest() {
+        let root = std::env::temp_dir().join(format!("fusang-history-{}", uuid::Uuid::new_v4()));
+        let history = LocalHistory::new(&root).with_max_snapshots(2);
+        let file = Path::new("/project/src/main.rs");
+
+        assert!(history.record(file, "one").unwrap().is_some());
+        assert!(history.record(file, "one").unwrap().is_none());
+        history.record(file, "two").unwrap();
+        history.record(file, "three").unwrap();
+
+        let snapshots = history.snapshots(file).unwrap();
+        let contents: Vec<String> = snapshots.iter().map(|s| history.read(s).unwrap()).collect();
+        assert_eq!(contents, vec!["three", "two"]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn changed_words_pair_removed_and_added_lines() {
+        let diff = diff_lines("a\nlet x = 1;\ngone\nb\n", "a\nlet x = 2;\nb\nnew\n");
+        let marked: Vec<(&DiffLine, Vec<(usize, usize)>)> = diff
+            .iter()
+            .zip(changed_words(&diff))
+            .map(|(line, words)| (line, words.iter().map(|r| (r.start, r.end)).collect()))
+            .collect();
+        assert_eq!(
+            marked,
+            [
+                (&DiffLine::Context("a".into()), vec![]),
+                (&DiffLine::Removed("let x = 1;".into()), vec![(8, 10)]),
+                (&DiffLine::Removed("gone".into()), vec![]),
+                (&DiffLine::Added("let x = 2;".into()), vec![(8, 10)]),
+                (&DiffLine::Context("b".into()), vec![]),
+                (&DiffLine::Added("new".into()), vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use base64::Engine;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NotebookError {
+    #[error("Invalid notebook JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported notebook: {0}")]
+    Format(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellKind {
+    Code,
+    Markdown,
+    Raw,
+}
+
+impl CellKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            CellKind::Code => "code",
+            CellKind::Markdown => "markdown",
+            CellKind::Raw => "raw",
+        }
+    }
+}
+
+/// One output of a code cell, in the shape both `.ipynb` files and kernels use.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellOutput {
+    Stream {
+        /// `stdout` or `stderr`.
+        name: String,
+        text: String,
+    },
+    /// The value of the cell's last expression (`execute_result`) or anything else it
+    /// displayed (`display_data`), keyed by MIME type.
+    Display {
+        data: Map<String, Value>,
+        metadata: Value,
+        /// Set for `execute_result`.
+        execution_count: Option<u64>,
+    },
+    Error {
+        ename: String,
+        evalue: String,
+        traceback: Vec<String>,
+    },
+}
+
+impl CellOutput {
+    /// Reads one output as stored in a notebook or sent by a kernel, where the type is
+    /// `output_type` or the message type.
+    pub fn from_json(output_type: &str, content: &Value) -> Option<Self> {
+        match output_type {
+            "stream" => Some(CellOutput::Stream {
+                name: content["name"].as_str().unwrap_or("stdout").to_string(),
+                text: multiline(&content["text"]),
+            }),
+            "execute_result" | "display_data" => Some(CellOutput::Display {
+                data: content["data"].as_object().cloned().unwrap_or_default(),
+                metadata: content.get("metadata").cloned().unwrap_or(json!({})),
+                execution_count: (output_type == "execute_result")
+                    .then(|| content["execution_count"].as_u64())
+                    .flatten(),
+            }),
+            "error" => Some(CellOutput::Error {
+                ename: content["ename"].as_str().unwrap_or_default().to_string(),
+                evalue: content["evalue"].as_str().unwrap_or_default().to_string(),
+                traceback: content["traceback"]
+                    .as_array()
+                    .map(|lines| {
+                        lines
+                            .iter()
+                            .filter_map(|line| line.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            CellOutput::Stream { name, text } => json!({
+                "output_type": "stream",
+                "name": name,
+                "text": split_lines(text),
+            }),
+            CellOutput::Display {
+                data,
+                metadata,
+                execution_count,
+            } => {
+                let mut output = json!({
+                    "data": data,
+                    "metadata": metadata,
+                    "output_type": "display_data",
+                });
+                if execution_count.is_some() {
+                    output["output_type"] = json!("execute_result");
+                    output["execution_count"] = json!(execution_count);
+                }
+                output
+            }
+            CellOutput::Error {
+                ename,
+                evalue,
+                traceback,
+            } => json!({
+                "output_type": "error",
+                "ename": ename,
+                "evalue": evalue,
+                "traceback": traceback,
+            }),
+        }
+    }
+
+    /// The output as plain text: streams and errors as they are, displays by their
+    /// `text/plain` form or, failing that, their HTML with the tags removed.
+    pub fn text(&self) -> Option<String> {
+        match self {
+            CellOutput::Stream { text, .. } => Some(text.clone()),
+            CellOutput::Display { data, .. } => data
+                .get("text/plain")
+                .map(multiline)
+                .or_else(|| self.html().map(|html| strip_tags(&html))),
+            CellOutput::Error {
+                ename,
+                evalue,
+                traceback,
+            } => Some(if traceback.is_empty() {
+                format!("{}: {}", ename, evalue)
+            } else {
+                strip_ansi(&traceback.join("\n"))
+            }),
+        }
+    }
+
+    pub fn html(&self) -> Option<String> {
+        match self {
+            CellOutput::Display { data, .. } => data.get("text/html").map(multiline),
+            _ => None,
+        }
+    }
+
+    /// The first PNG or JPEG image in a display, decoded, with its MIME type.
+    pub fn image(&self) -> Option<(&'static str, Vec<u8>)> {
+        let CellOutput::Display { data, .. } = self else {
+            return None;
+        };
+        ["image/png", "image/jpeg"].into_iter().find_map(|mime| {
+            let encoded: String = multiline(data.get(mime)?)
+                .chars()
+                .filter(|ch| !ch.is_whitespace())
+                .collect();
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .ok()?;
+            Some((mime, bytes))
+        })
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, CellOutput::Error { .. })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NotebookCell {
+    pub kind: CellKind,
+    pub source: String,
+    pub outputs: Vec<CellOutput>,
+    pub execution_count: Option<u64>,
+    /// Fields this model doesn't use, such as `metadata` and `id`, written back as read.
+    extra: Map<String, Value>,
+}
+
+impl NotebookCell {
+    pub fn new(kind: CellKind, source: &str) -> Self {
+        let mut extra = Map::new();
+        extra.insert("metadata".to_string(), json!({}));
+        Self {
+            kind,
+            source: source.to_string(),
+            outputs: Vec::new(),
+            execution_count: None,
+            extra,
+        }
+    }
+
+    /// The text of every error output, to explain or fix the cell with.
+    pub fn error_text(&self) -> Option<String> {
+        let errors: Vec<String> = self
+            .outputs
+            .iter()
+            .filter(|output| output.is_error())
+            .filter_map(CellOutput::text)
+            .collect();
+        (!errors.is_empty()).then(|| errors.join("\n"))
+    }
+
+    fn from_json(cell: &Value) -> Result<Self, NotebookError> {
+        let mut extra = cell
+            .as_object()
+            .cloned()
+            .ok_or_else(|| NotebookError::Format("a cell is not an object".to_string()))?;
+        let kind = match extra.remove("cell_type").as_ref().and_then(Value::as_str) {
+            Some("code") => CellKind::Code,
+            Some("markdown") => CellKind::Markdown,
+            Some("raw") => CellKind::Raw,
+            other => {
+                return Err(NotebookError::Format(format!(
+                    "unknown cell type {:?}",
+                    other
+                )))
+            }
+        };
+        let source = extra.remove("source").map(|source| multiline(&source));
+        let outputs = extra
+            .remove("outputs")
+            .and_then(|outputs| outputs.as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|output| CellOutput::from_json(output["output_type"].as_str()?, output))
+            .collect();
+        let execution_count = extra
+            .remove("execution_count")
+            .and_then(|count| count.as_u64());
+        Ok(Self {
+            kind,
+            source: source.unwrap_or_default(),
+            outputs,
+            execution_count,
+            extra,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let mut cell = self.extra.clone();
+        cell.insert("cell_type".to_string(), json!(self.kind.as_str()));
+        cell.insert("source".to_string(), json!(split_lines(&self.source)));
+        if self.kind == CellKind::Code {
+            cell.insert("execution_count".to_string(), json!(self.execution_count));
+            let outputs: Vec<Value> = self.outputs.iter().map(CellOutput::to_json).collect();
+            cell.insert("outputs".to_string(), json!(outputs));
+        }
+        Value::Object(cell)
+    }
+}
+
+/// A Jupyter notebook (`.ipynb`, format 4) as a list of cells. Metadata is kept as
+/// read so saving doesn't lose anything other tools put there.
+#[derive(Debug, Clone)]
+pub struct Notebook {
+    pub cells: Vec<NotebookCell>,
+    metadata: Value,
+    nbformat_minor: u64,
+}
+
+impl Default for Notebook {
+    fn default() -> Self {
+        Self {
+            cells: vec![NotebookCell::new(CellKind::Code, "")],
+            metadata: json!({
+                "kernelspec": {"display_name": "Python 3", "language": "python", "name": "python3"},
+                "language_info": {"name": "python"},
+            }),
+            nbformat_minor: 5,
+        }
+    }
+}
+
+impl Notebook {
+    pub fn parse(json: &str) -> Result<Self, NotebookError> {
+        let notebook: Value = serde_json::from_str(json)?;
+        match notebook["nbformat"].as_u64() {
+            Some(4) => {}
+            other => {
+                return Err(NotebookError::Format(format!(
+                    "nbformat {:?}, only 4 is supported",
+                    other
+                )))
+            }
+        }
+        let cells = notebook["cells"]
+            .as_array()
+            .ok_or_else(|| NotebookError::Format("no cells".to_string()))?
+            .iter()
+            .map(NotebookCell::from_json)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            cells,
+            metadata: notebook.get("metadata").cloned().unwrap_or(json!({})),
+            nbformat_minor: notebook["nbformat_minor"].as_u64().unwrap_or(5),
+        })
+    }
+
+    /// The notebook in the layout Jupyter writes: one-space indent, trailing newline.
+    pub fn to_json(&self) -> String {
+        let cells: Vec<Value> = self.cells.iter().map(NotebookCell::to_json).collect();
+        let notebook = json!({
+            "cells": cells,
+            "metadata": self.metadata,
+            "nbformat": 4,
+            "nbformat_minor": self.nbformat_minor,
+        });
+        let mut out = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+        serde::Serialize::serialize(&notebook, &mut serializer).unwrap_or_default();
+        let mut json = String::from_utf8(out).unwrap_or_default();
+        json.push('\n');
+        json
+    }
+
+    /// The language of the code cells, from the kernel spec.
+    pub fn language(&self) -> String {
+        self.metadata["kernelspec"]["language"]
+            .as_str()
+            .or_else(|| self.metadata["language_info"]["name"].as_str())
+            .unwrap_or("python")
+            .to_string()
+    }
+
+    /// Inserts an empty cell at `index` (clamped) and returns where it went.
+    pub fn insert_cell(&mut self, index: usize, kind: CellKind) -> usize {
+        let index = index.min(self.cells.len());
+        self.cells.insert(index, NotebookCell::new(kind, ""));
+        index
+    }
+
+    pub fn remove_cell(&mut self, index: usize) -> Option<NotebookCell> {
+        (index < self.cells.len()).then(|| self.cells.remove(index))
+    }
+
+    /// Moves a cell one place up or down; false at either end.
+    pub fn move_cell(&mut self, index: usize, up: bool) -> bool {
+        let target = if up {
+            index.checked_sub(1)
+        } else {
+            Some(index + 1)
+        };
+        match target {
+            Some(target) if index < self.cells.len() && target < self.cells.len() => {
+                self.cells.swap(index, target);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Notebook text is a string or a list of lines that already end in `\n`.
+fn multiline(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(ch),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Tracebacks from IPython kernels are colored with ANSI escapes.
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\u{1b}' {
+            for ch in chars.by_ref() {
+                if ch.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(ch);
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {"cell_type": "markdown", "id": "a1", "metadata": {}, "source": ["# Title\n", "text"]},
+  {
+   "cell_type": "code", "execution_count": 3, "id": "b2", "metadata": {"tags": ["x"]},
+   "outputs": [
+    {"name": "stdout", "output_type": "stream", "text": ["hi\n"]},
+    {"data": {"image/png": "iVBORw0K\nGgo=", "text/plain": ["<Figure>"]},
+     "metadata": {}, "output_type": "display_data"},
+    {"data": {"text/html": "<b>1 &amp; 2</b>"}, "execution_count": 3, "metadata": {},
+     "output_type": "execute_result"},
+    {"ename": "ValueError", "evalue": "bad", "output_type": "error",
+     "traceback": ["\u001b[0;31mValueError\u001b[0m: bad"]}
+   ],
+   "source": "print('hi')\nx"
+  }
+ ],
+ "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn round_trips_cells_outputs_and_unknown_fields() {
+        let notebook = Notebook::parse(NOTEBOOK).unwrap();
+        assert_eq!(notebook.language(), "python");
+        assert_eq!(notebook.cells.len(), 2);
+        assert_eq!(notebook.cells[0].kind, CellKind::Markdown);
+        assert_eq!(notebook.cells[0].source, "# Title\ntext");
+
+        let code = &notebook.cells[1];
+        assert_eq!(code.execution_count, Some(3));
+        let texts: Vec<_> = code.outputs.iter().filter_map(CellOutput::text).collect();
+        assert_eq!(texts, ["hi\n", "<Figure>", "1 & 2", "ValueError: bad"]);
+        let (mime, bytes) = code.outputs[1].image().unwrap();
+        assert_eq!((mime, &bytes[..4]), ("image/png", &b"\x89PNG"[..]));
+        assert_eq!(code.error_text().unwrap(), "ValueError: bad");
+
+        let saved = Notebook::parse(&notebook.to_json()).unwrap();
+        assert_eq!(saved.to_json(), notebook.to_json());
+        let value: Value = serde_json::from_str(&notebook.to_json()).unwrap();
+        assert_eq!(value["cells"][1]["id"], "b2");
+        assert_eq!(value["cells"][1]["metadata"]["tags"][0], "x");
+        assert_eq!(value["cells"][1]["source"], json!(["print('hi')\n", "x"]));
+        assert!(value["cells"][0].get("outputs").is_none());
+
+        let mut edited = saved;
+        assert_eq!(edited.insert_cell(9, CellKind::Code), 2);
+        assert!(edited.move_cell(2, true));
+        assert!(!edited.move_cell(2, false));
+        assert_eq!(edited.cells[1].source, "");
+        assert!(edited.remove_cell(1).is_some());
+        assert!(Notebook::parse(r#"{"nbformat": 3, "cells": []}"#).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::workspace::Workspace;
+use editor_core_text::{Cursor, SearchQuery};
+use ignore::{WalkBuilder, WalkState};
+use ropey::Rope;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Bytes looked at to tell binary files from text, like git and ripgrep do.
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectSearchOptions {
+    /// Lines shown before and after each match.
+    pub context_lines: usize,
+    /// The search stops once this many matches were found.
+    pub max_matches: usize,
+    /// Larger files are skipped, as are files that aren't UTF-8 text.
+    pub max_file_bytes: u64,
+    pub follow_symlinks: bool,
+    /// Directory names that are never entered, e.g. `target` or `node_modules`.
+    pub skip_dirs: Vec<String>,
+}
+
+impl Default for ProjectSearchOptions {
+    fn default() -> Self {
+        Self {
+            context_lines: 2,
+            max_matches: 10_000,
+            max_file_bytes: 1024 * 1024,
+            follow_symlinks: true,
+            skip_dirs: Vec::new(),
+        }
+    }
+}
+
+/// A match in a workspace file. Lines and columns count from 0, columns in chars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectMatch {
+    pub path: PathBuf,
+    pub start: Cursor,
+    pub end: Cursor,
+    /// The line the match starts on, without its line break.
+    pub line_text: String,
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectSearchResults {
+    /// Sorted by path, then position.
+    pub matches: Vec<ProjectMatch>,
+    pub files_searched: usize,
+    /// The search stopped at [`ProjectSearchOptions::max_matches`].
+    pub limit_reached: bool,
+}
+
+impl ProjectSearchResults {
+    /// The matches grouped by file, for a results panel.
+    pub fn by_file(&self) -> Vec<(&Path, &[ProjectMatch])> {
+        self.matches
+            .chunk_by(|a, b| a.path == b.path)
+            .map(|matches| (matches[0].path.as_path(), matches))
+            .collect()
+    }
+}
+
+/// Searches every text file of a workspace for a [`SearchQuery`], the way ripgrep
+/// does: files are walked and searched on several threads, hidden files and whatever
+/// `.gitignore`, `.ignore` and `.git/info/exclude` exclude are skipped. Ignore files
+/// apply even outside a git repository.
+#[derive(Debug, Clone)]
+pub struct ProjectSearch {
+    query: SearchQuery,
+    options: ProjectSearchOptions,
+}
+
+impl ProjectSearch {
+    pub fn new(query: SearchQuery, options: ProjectSearchOptions) -> Self {
+        Self { query, options }
+    }
+
+    /// Blocks until the walk is done; run it off the UI thread.
+    pub fn run(&self, workspace: &Workspace) -> ProjectSearchResults {
+        let Some((first, rest)) = workspace.root_paths.split_first() else {
+            return ProjectSearchResults::default();
+        };
+        let mut builder = WalkBuilder::new(first);
+        for root in rest {
+            builder.add(root);
+        }
+        let skip_dirs = self.options.skip_dirs.clone();
+        builder
+            .follow_links(self.options.follow_symlinks)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                !entry.file_type().is_some_and(|kind| kind.is_dir())
+                    || !skip_dirs
+                        .iter()
+                        .any(|dir| entry.file_name() == dir.as_str())
+            });
+
+        let matches = Mutex::new(Vec::new());
+        let found = AtomicUsize::new(0);
+        let files = AtomicUsize::new(0);
+        let limit_reached = AtomicBool::new(false);
+        builder.build_parallel().run(|| {
+            Box::new(|entry| {
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                    return WalkState::Continue;
+                }
+                let Some(file_matches) = self.search_file(entry.path()) else {
+                    return WalkState::Continue;
+                };
+                files.fetch_add(1, Ordering::Relaxed);
+                if file_matches.is_empty() {
+                    return WalkState::Continue;
+                }
+                let total =
+                    found.fetch_add(file_matches.len(), Ordering::Relaxed) + file_matches.len();
+                matches
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .extend(file_matches);
+                if total >= self.options.max_matches {
+                    limit_reached.store(true, Ordering::Relaxed);
+                    return WalkState::Quit;
+                }
+                WalkState::Continue
+            })
+        });
+
+        let mut matches = matches.into_inner().unwrap_or_else(|e| e.into_inner());
+        matches.sort_by(|a, b| {
+            (&a.path, a.start.line, a.start.column).cmp(&(&b.path, b.start.line, b.start.column))
+        });
+        matches.truncate(self.options.max_matches);
+        ProjectSearchResults {
+            matches,
+            files_searched: files.into_inner(),
+            limit_reached: limit_reached.into_inner(),
+        }
+    }
+
+    /// The matches in one file, or `None` when it is too large, binary or unreadable.
+    fn search_file(&self, path: &Path) -> Option<Vec<ProjectMatch>> {
+        let mut file = std::fs::File::open(path).ok()?;
+        if file.metadata().ok()?.len() > self.options.max_file_bytes {
+            return None;
+        }
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).ok()?;
+        if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+            return None;
+        }
+        let text = String::from_utf8(bytes).ok()?;
+        let rope = Rope::from_str(&text);
+
+        let line_text = |line: usize| {
+            let text = rope.line(line).to_string();
+            text.trim_end_matches(['\n', '\r']).to_string()
+        };
+        let context = self.options.context_lines;
+        let last_line = rope.len_lines().saturating_sub(1);
+        let matches = self
+            .query
+            .find_all(&rope)
+            .into_iter()
+            .map(|found| {
+                let line = found.start_position.line;
+                ProjectMatch {
+                    path: path.to_path_buf(),
+                    start: found.start_position,
+                    end: found.end_position,
+                    line_text: line_text(line),
+                    context_before: (line.saturating_sub(context)..line)
+                        .map(line_text)
+                        .collect(),
+                    context_after: (line + 1..=(line + context).min(last_line))
+                        .map(line_text)
+                        .collect(),
+                }
+            })
+            .collect();
+        Some(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core_text::SearchOptions;
+
+    #[test]
+    fn searches_text_files_and_skips_ignored_ones() {
+        let root = std::env::temp_dir().join(format!("fusang-search-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::create_dir_all(root.join(".cache")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(
+            root.join("src/main.rs"),
+            "fn main() {\n    let total = 1;\n    println!(\"{}\", total);\n}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("notes.txt"), "no match here\nTotal: 3\n").unwrap();
+        std::fs::write(root.join("build.log"), "total").unwrap();
+        std::fs::write(root.join("target/out.rs"), "total").unwrap();
+        std::fs::write(root.join(".cache/data"), "total").unwrap();
+        std::fs::write(root.join("image.bin"), b"total\0\x01").unwrap();
+
+        let query = SearchQuery::new("total", SearchOptions::default()).unwrap();
+        let search = ProjectSearch::new(
+            query,
+            ProjectSearchOptions {
+                context_lines: 1,
+                skip_dirs: vec!["target".to_string()],
+                ..Default::default()
+            },
+        );
+        let results = search.run(&Workspace::single_root(&root).unwrap());
+
+        let found: Vec<_> = results
+            .matches
+            .iter()
+            .map(|m| {
+                let path = m.path.strip_prefix(&root).unwrap().to_path_buf();
+                (path, m.start.line, m.start.column)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (PathBuf::from("notes.txt"), 1, 0),
+                (PathBuf::from("src/main.rs"), 1, 8),
+                (PathBuf::from("src/main.rs"), 2, 19),
+            ]
+        );
+        let first = &results.matches[1];
+        assert_eq!(first.line_text, "    let total = 1;");
+        assert_eq!(first.context_before, ["fn main() {"]);
+        assert_eq!(first.context_after, ["    println!(\"{}\", total);"]);
+        assert_eq!(results.matches[0].context_after, [""]);
+        assert_eq!(results.by_file().len(), 2);
+        assert!(!results.limit_reached);
+
+        let limited = ProjectSearch::new(
+            SearchQuery::new("total", SearchOptions::default()).unwrap(),
+            ProjectSearchOptions {
+                max_matches: 1,
+                ..Default::default()
+            },
+        )
+        .run(&Workspace::single_root(&root).unwrap());
+        assert_eq!(limited.matches.len(), 1);
+        assert!(limited.limit_reached);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How many files and how many workspaces are remembered.
+const MAX_RECENT: usize = 50;
+
+/// A file or workspace and when it was last opened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentEntry {
+    pub path: PathBuf,
+    pub opened_at: SystemTime,
+}
+
+/// Recently opened files and workspaces, most recent first, kept across restarts for
+/// Quick Open and the welcome screen.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentHistory {
+    #[serde(default)]
+    files: Vec<RecentEntry>,
+    #[serde(default)]
+    workspaces: Vec<RecentEntry>,
+}
+
+impl RecentHistory {
+    pub fn default_location() -> PathBuf {
+        editor_infra::paths::data_dir().join("recent.json")
+    }
+
+    /// An empty history when the file doesn't exist yet.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes a temporary file first, so a crash can't leave a torn history behind.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&temp, path)
+    }
+
+    pub fn record_file(&mut self, path: &Path) {
+        record(&mut self.files, path);
+    }
+
+    pub fn record_workspace(&mut self, path: &Path) {
+        record(&mut self.workspaces, path);
+    }
+
+    /// Forgets a file or workspace, e.g. one that was deleted.
+    pub fn remove(&mut self, path: &Path) {
+        self.files.retain(|entry| entry.path != path);
+        self.workspaces.retain(|entry| entry.path != path);
+    }
+
+    pub fn files(&self) -> &[RecentEntry] {
+        &self.files
+    }
+
+    pub fn workspaces(&self) -> &[RecentEntry] {
+        &self.workspaces
+    }
+
+    /// The recent files under `root`, most recent first.
+    pub fn files_in<'a>(&'a self, root: &'a Path) -> impl Iterator<Item = &'a Path> + 'a {
+        self.files
+            .iter()
+            .map(|entry| entry.path.as_path())
+            .filter(move |path| path.starts_with(root))
+    }
+}
+
+fn record(entries: &mut Vec<RecentEntry>, path: &Path) {
+    entries.retain(|entry| entry.path != path);
+    entries.insert(
+        0,
+        RecentEntry {
+            path: path.to_path_buf(),
+            opened_at: SystemTime::now(),
+        },
+    );
+    entries.truncate(MAX_RECENT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_most_recent_first_and_round_trips() {
+        let mut history = RecentHistory::default();
+        history.record_workspace(Path::new("/work/app"));
+        history.record_file(Path::new("/work/app/src/main.rs"));
+        history.record_file(Path::new("/other/notes.md"));
+        history.record_file(Path::new("/work/app/src/lib.rs"));
+        history.record_file(Path::new("/work/app/src/main.rs"));
+
+        let files: Vec<_> = history.files().iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            files,
+            [
+                PathBuf::from("/work/app/src/main.rs"),
+                PathBuf::from("/work/app/src/lib.rs"),
+                PathBuf::from("/other/notes.md"),
+            ]
+        );
+        let in_app: Vec<_> = history.files_in(Path::new("/work/app")).collect();
+        assert_eq!(in_app.len(), 2);
+
+        for i in 0..MAX_RECENT + 5 {
+            history.record_workspace(&PathBuf::from(format!("/work/{}", i)));
+        }
+        assert_eq!(history.workspaces().len(), MAX_RECENT);
+        history.remove(Path::new("/other/notes.md"));
+        assert_eq!(history.files().len(), 2);
+
+        let dir = std::env::temp_dir().join(format!("fusang-recent-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("recent.json");
+        assert_eq!(
+            RecentHistory::load(&path).unwrap(),
+            RecentHistory::default()
+        );
+        history.save(&path).unwrap();
+        assert_eq!(RecentHistory::load(&path).unwrap(), history);
+        std::fs::write(&path, "not json").unwrap();
+        assert!(RecentHistory::load(&path).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
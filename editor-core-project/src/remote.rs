@@ -0,0 +1,112 @@
+use crate::local_history::stable_hash;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use thiserror::Error;
+
+const MAX_REMOTE_BYTES: usize = 5 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum RemoteError {
+    #[error("Unsupported URL: {0}")]
+    UnsupportedUrl(String),
+    #[error("HTTP request failed: {0}")]
+    RequestFailed(#[from] reqwest::Error),
+    #[error("Server returned status {0}")]
+    Status(u16),
+    #[error("Remote file is larger than {} bytes", MAX_REMOTE_BYTES)]
+    TooLarge,
+    #[error("Remote file is not valid UTF-8 text")]
+    NotText,
+}
+
+pub fn is_remote_url(text: &str) -> bool {
+    text.starts_with("https://") || text.starts_with("http://")
+}
+
+/// Fetches remote text files for read-only viewing.
+///
+/// Responses are cached on disk with their ETag. Cached copies are revalidated with
+/// `If-None-Match`, and served as-is when the network is unavailable.
+#[derive(Debug, Clone)]
+pub struct RemoteFetcher {
+    /// Created on the first fetch; building a client loads TLS roots, which slows startup.
+    client: Arc<OnceLock<reqwest::Client>>,
+    cache_dir: PathBuf,
+}
+
+impl RemoteFetcher {
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            client: Arc::new(OnceLock::new()),
+            cache_dir: cache_dir.into(),
+        }
+    }
+
+    pub fn default_location() -> PathBuf {
+        editor_infra::paths::data_dir().join("remote-cache")
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<String, RemoteError> {
+        if !is_remote_url(url) {
+            return Err(RemoteError::UnsupportedUrl(url.to_string()));
+        }
+
+        let key = format!("{:016x}", stable_hash(url));
+        let body_path = self.cache_dir.join(format!("{}.body", key));
+        let etag_path = self.cache_dir.join(format!("{}.etag", key));
+        let cached = std::fs::read_to_string(&body_path).ok();
+
+        let mut request = self.client.get_or_init(reqwest::Client::new).get(url);
+        if cached.is_some() {
+            if let Ok(etag) = std::fs::read_to_string(&etag_path) {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => return cached.ok_or(RemoteError::RequestFailed(e)),
+        };
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                return Ok(cached);
+            }
+        }
+        if !response.status().is_success() {
+            return Err(RemoteError::Status(response.status().as_u16()));
+        }
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > MAX_REMOTE_BYTES)
+        {
+            return Err(RemoteError::TooLarge);
+        }
+
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let bytes = response.bytes().await?;
+        if bytes.len() > MAX_REMOTE_BYTES {
+            return Err(RemoteError::TooLarge);
+        }
+        let text = String::from_utf8(bytes.to_vec()).map_err(|_| RemoteError::NotText)?;
+
+        // Caching is best effort; a read-only cache dir must not break viewing.
+        if std::fs::create_dir_all(&self.cache_dir).is_ok() {
+            let _ = std::fs::write(&body_path, &text);
+            match etag {
+                Some(etag) => {
+                    let _ = std::fs::write(&etag_path, etag);
+                }
+                None => {
+                    let _ = std::fs::remove_file(&etag_path);
+                }
+            }
+        }
+
+        Ok(text)
+    }
+}
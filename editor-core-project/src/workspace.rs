@@ -1,10 +1,67 @@
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+const WALK_BATCH_SIZE: usize = 512;
+
+/// How deleted files are disposed of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeleteMode {
+    /// Move to the operating system's trash / recycle bin.
+    #[default]
+    Trash,
+    Permanent,
+}
+
+impl DeleteMode {
+    pub fn from_config(delete_permanently: bool) -> Self {
+        if delete_permanently {
+            DeleteMode::Permanent
+        } else {
+            DeleteMode::Trash
+        }
+    }
+}
+
+pub(crate) fn remove_path(path: &Path, mode: DeleteMode) -> std::io::Result<()> {
+    match mode {
+        DeleteMode::Trash => trash::delete(path).map_err(std::io::Error::other),
+        DeleteMode::Permanent if path.is_dir() => std::fs::remove_dir_all(path),
+        DeleteMode::Permanent => std::fs::remove_file(path),
+    }
+}
+
+/// Bounds for [`Workspace::walk_files`], so huge repositories can't stall the caller.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+    pub max_files: Option<usize>,
+    pub timeout: Option<Duration>,
+    /// Skip dot-directories below the roots, e.g. `.git`.
+    pub skip_hidden: bool,
+    /// Directory names that are never entered, e.g. `target` or `node_modules`.
+    pub skip_dirs: Vec<String>,
+}
+
+/// How a [`Workspace::walk_files`] run ended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkSummary {
+    pub files: usize,
+    pub limit_reached: bool,
+    pub timed_out: bool,
+    pub cancelled: bool,
+}
+
+impl WalkSummary {
+    pub fn is_complete(&self) -> bool {
+        !self.limit_reached && !self.timed_out && !self.cancelled
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Workspace {
     pub root_paths: Vec<PathBuf>,
     pub name: String,
+    pub follow_symlinks: bool,
 }
 
 impl Workspace {
@@ -30,13 +87,22 @@ impl Workspace {
             }
         });
 
-        Ok(Self { root_paths, name })
+        Ok(Self {
+            root_paths,
+            name,
+            follow_symlinks: true,
+        })
     }
 
     pub fn single_root<P: AsRef<Path>>(path: P) -> Result<Self, WorkspaceError> {
         Self::new(vec![path.as_ref().to_path_buf()], None)
     }
 
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
     pub fn contains_file(&self, file_path: &Path) -> bool {
         self.root_paths
             .iter()
@@ -54,21 +120,123 @@ impl Workspace {
 
     pub fn get_files(&self) -> Result<Vec<PathBuf>, WorkspaceError> {
         let mut files = Vec::new();
+        self.walk_files(&WalkOptions::default(), |batch| {
+            files.extend(batch);
+            true
+        });
+        Ok(files)
+    }
 
-        for root in &self.root_paths {
+    /// Walks the workspace and hands files to `on_batch` in chunks as they are found,
+    /// so callers can show partial results. Returning `false` from `on_batch` stops
+    /// the walk.
+    pub fn walk_files(
+        &self,
+        options: &WalkOptions,
+        mut on_batch: impl FnMut(Vec<PathBuf>) -> bool,
+    ) -> WalkSummary {
+        let started = Instant::now();
+        let mut summary = WalkSummary::default();
+        let mut batch = Vec::new();
+
+        // Canonical directories already walked: stops symlink cycles and avoids
+        // listing a directory twice when several links point at it.
+        let mut visited = std::collections::HashSet::new();
+        'roots: for root in &self.root_paths {
             let entries = walkdir::WalkDir::new(root)
-                .follow_links(true)
+                .follow_links(self.follow_symlinks)
                 .into_iter()
+                .filter_entry(|entry| {
+                    if !entry.file_type().is_dir() {
+                        return true;
+                    }
+                    if entry.depth() > 0 {
+                        let name = entry.file_name().to_string_lossy();
+                        if (options.skip_hidden && name.starts_with('.'))
+                            || options.skip_dirs.iter().any(|dir| *dir == name)
+                        {
+                            return false;
+                        }
+                    }
+                    visited.insert(
+                        entry
+                            .path()
+                            .canonicalize()
+                            .unwrap_or_else(|_| entry.path().to_path_buf()),
+                    )
+                })
                 .filter_map(|e| e.ok());
 
             for entry in entries {
-                if entry.file_type().is_file() {
-                    files.push(entry.path().to_path_buf());
+                if options
+                    .timeout
+                    .is_some_and(|timeout| started.elapsed() >= timeout)
+                {
+                    summary.timed_out = true;
+                    break 'roots;
+                }
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                if options.max_files.is_some_and(|max| summary.files >= max) {
+                    summary.limit_reached = true;
+                    break 'roots;
+                }
+
+                summary.files += 1;
+                batch.push(entry.path().to_path_buf());
+                if batch.len() >= WALK_BATCH_SIZE && !on_batch(std::mem::take(&mut batch)) {
+                    summary.cancelled = true;
+                    return summary;
                 }
             }
         }
 
-        Ok(files)
+        if !batch.is_empty() && !on_batch(batch) {
+            summary.cancelled = true;
+        }
+        summary
+    }
+
+    /// Resolves a path as written in compiler output or chat text to workspace files.
+    ///
+    /// Exact matches under a root win; otherwise every file whose path ends with the
+    /// given components is returned, so callers can ask the user to pick one.
+    pub fn resolve_reference(&self, path_text: &str) -> Vec<PathBuf> {
+        let path = Path::new(path_text.trim_start_matches("./"));
+        if path.is_absolute() {
+            return if path.is_file() {
+                vec![path.to_path_buf()]
+            } else {
+                Vec::new()
+            };
+        }
+
+        let exact: Vec<PathBuf> = self
+            .root_paths
+            .iter()
+            .map(|root| root.join(path))
+            .filter(|candidate| candidate.is_file())
+            .collect();
+        if !exact.is_empty() {
+            return exact;
+        }
+
+        let mut matches: Vec<PathBuf> = self
+            .get_files()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|file| {
+                let hidden = self.relative_path(file).is_some_and(|relative| {
+                    relative
+                        .components()
+                        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+                });
+                !hidden && file.ends_with(path)
+            })
+            .collect();
+        matches.sort();
+        matches
     }
 
     pub fn find_files_by_extension(&self, extension: &str) -> Result<Vec<PathBuf>, WorkspaceError> {
@@ -87,7 +255,7 @@ impl Workspace {
     }
 
     pub fn create_file(&self, relative_path: &Path, content: &str) -> Result<(), WorkspaceError> {
-        for root in &self.root_paths {
+        if let Some(root) = self.root_paths.first() {
             let full_path = root.join(relative_path);
             if let Some(parent) = full_path.parent() {
                 std::fs::create_dir_all(parent)?;
@@ -98,12 +266,41 @@ impl Workspace {
         Err(WorkspaceError::PathNotFound(relative_path.to_path_buf()))
     }
 
-    pub fn delete_file(&self, relative_path: &Path) -> Result<(), WorkspaceError> {
+    /// Renames or moves `from` to `to`, both relative to the root that has `from`.
+    /// Missing parent directories of `to` are created; an existing `to` is an error.
+    pub fn rename(&self, from: &Path, to: &Path) -> Result<PathBuf, WorkspaceError> {
+        for root in &self.root_paths {
+            let source = root.join(from);
+            if !source.exists() {
+                continue;
+            }
+            let target = root.join(to);
+            if target.exists() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", target.display()),
+                )
+                .into());
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            crate::file_journal::move_path(&source, &target)?;
+            return Ok(target);
+        }
+        Err(WorkspaceError::PathNotFound(from.to_path_buf()))
+    }
+
+    pub fn delete_file(
+        &self,
+        relative_path: &Path,
+        mode: DeleteMode,
+    ) -> Result<DeleteMode, WorkspaceError> {
         for root in &self.root_paths {
             let full_path = root.join(relative_path);
             if full_path.exists() {
-                std::fs::remove_file(&full_path)?;
-                return Ok(());
+                remove_path(&full_path, mode)?;
+                return Ok(mode);
             }
         }
         Err(WorkspaceError::PathNotFound(relative_path.to_path_buf()))
@@ -119,3 +316,54 @@ pub enum WorkspaceError {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_streams_batches_and_stops_at_the_limit() {
+        let root = std::env::temp_dir().join(format!("fusang-walk-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join(".git/HEAD"), "").unwrap();
+        std::fs::write(root.join("node_modules/pkg/index.js"), "").unwrap();
+        for i in 0..(WALK_BATCH_SIZE + 10) {
+            std::fs::write(root.join(format!("src/{}.rs", i)), "").unwrap();
+        }
+
+        let workspace = Workspace::single_root(&root).unwrap();
+        let options = WalkOptions {
+            skip_hidden: true,
+            skip_dirs: vec!["node_modules".to_string()],
+            ..Default::default()
+        };
+        let mut batches = Vec::new();
+        let summary = workspace.walk_files(&options, |batch| {
+            batches.push(batch.len());
+            true
+        });
+        assert!(summary.is_complete());
+        assert_eq!(summary.files, WALK_BATCH_SIZE + 10);
+        assert_eq!(batches, vec![WALK_BATCH_SIZE, 10]);
+
+        let limited = WalkOptions {
+            max_files: Some(5),
+            ..options.clone()
+        };
+        let mut files = Vec::new();
+        let summary = workspace.walk_files(&limited, |batch| {
+            files.extend(batch);
+            true
+        });
+        assert!(summary.limit_reached);
+        assert_eq!(files.len(), 5);
+
+        let summary = workspace.walk_files(&options, |_| false);
+        assert!(summary.cancelled);
+        assert_eq!(workspace.get_files().unwrap().len(), WALK_BATCH_SIZE + 12);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+use crate::IndentStyle;
+use std::ops::Range;
+use tree_sitter::Node;
+
+/// How [`ArgumentList::layout`] arranges a list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgumentLayout {
+    /// One argument per line, a level deeper than the line of the opening bracket.
+    Split,
+    /// All arguments on the line of the brackets.
+    Joined,
+    /// Joined when that keeps the line within this many columns, split otherwise.
+    Fit(usize),
+}
+
+/// The comma-separated contents of a pair of brackets: call arguments, parameters,
+/// array and tuple elements, or the fields of a struct literal.
+///
+/// Found on the tree-sitter syntax tree of the language, so it needs no language
+/// server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentList {
+    /// Byte offsets of the opening and closing bracket.
+    open: usize,
+    close: usize,
+    /// Trimmed, without the separating commas.
+    items: Vec<String>,
+    trailing_comma: bool,
+    /// Leading whitespace of the line with the opening bracket.
+    indent: String,
+    /// Chars of that line up to and including the opening bracket.
+    prefix_width: usize,
+    /// Chars of the closing bracket's line from the bracket on.
+    suffix_width: usize,
+}
+
+impl ArgumentList {
+    /// The innermost list around the byte `offset` of `text`, a file in `language`.
+    /// Braces only count when they hold a comma, so blocks are passed over, and so
+    /// are lists with a comment between the brackets, since moving it could comment
+    /// out code. `None` outside lists, for empty brackets, and for languages without
+    /// a tree-sitter grammar.
+    pub fn find(text: &str, offset: usize, language: &str) -> Option<Self> {
+        let tree = crate::grammar::parse(text, language)?;
+        let mut node = tree.root_node().descendant_for_byte_range(offset, offset);
+        while let Some(current) = node {
+            if let Some(list) = Self::from_node(text, current, offset) {
+                return Some(list);
+            }
+            node = current.parent();
+        }
+        None
+    }
+
+    /// The list `node` holds when it is delimited by a pair of brackets around
+    /// `offset`, split at its own commas; commas inside nested nodes, strings
+    /// included, belong to those nodes.
+    fn from_node(text: &str, node: Node, offset: usize) -> Option<Self> {
+        let count = node.child_count();
+        let (first, last) = (node.child(0)?, node.child(count.checked_sub(1)?)?);
+        if count < 2 || first.is_named() || last.is_named() {
+            return None;
+        }
+        let closing = match first.kind() {
+            "(" => ")",
+            "[" => "]",
+            "{" => "}",
+            _ => return None,
+        };
+        let (open, close) = (first.start_byte(), last.start_byte());
+        if last.kind() != closing || !(open < offset && offset <= close) || has_comment(node) {
+            return None;
+        }
+
+        let mut items = Vec::new();
+        let mut start = first.end_byte();
+        for i in 1..count - 1 {
+            let child = node.child(i)?;
+            if !child.is_named() && child.kind() == "," {
+                items.push(text[start..child.start_byte()].trim().to_string());
+                start = child.end_byte();
+            }
+        }
+        let rest = text[start..close].trim();
+        let trailing_comma = rest.is_empty() && !items.is_empty();
+        if !rest.is_empty() {
+            items.push(rest.to_string());
+        }
+        if items.is_empty() || (closing == "}" && items.len() < 2) {
+            return None;
+        }
+
+        let line_start = text[..open].rfind('\n').map_or(0, |at| at + 1);
+        let line_end = text[close..].find('\n').map_or(text.len(), |at| close + at);
+        let indent: String = text[line_start..open]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        Some(Self {
+            open,
+            close,
+            items,
+            trailing_comma,
+            indent,
+            prefix_width: text[line_start..=open].chars().count(),
+            suffix_width: text[close..line_end].trim_end().chars().count(),
+        })
+    }
+
+    /// Byte range of the text between the brackets, which [`ArgumentList::layout`]
+    /// replaces.
+    pub fn range(&self) -> Range<usize> {
+        self.open + 1..self.close
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    /// The text between the brackets in `layout`. A trailing comma is kept when
+    /// split; when joined it is only kept for a single item, where `(a,)` may be a
+    /// tuple.
+    pub fn layout(&self, layout: ArgumentLayout, style: IndentStyle, line_break: &str) -> String {
+        let joined = self.joined();
+        let split = match layout {
+            ArgumentLayout::Split => true,
+            ArgumentLayout::Joined => false,
+            ArgumentLayout::Fit(max_width) => {
+                self.prefix_width + joined.chars().count() + self.suffix_width > max_width
+            }
+        };
+        if !split {
+            return joined;
+        }
+
+        let inner = format!("{}{}", self.indent, style.unit());
+        let mut text = String::new();
+        for (i, item) in self.items.iter().enumerate() {
+            text.push_str(line_break);
+            text.push_str(&inner);
+            text.push_str(item);
+            if i + 1 < self.items.len() || self.trailing_comma {
+                text.push(',');
+            }
+        }
+        text.push_str(line_break);
+        text.push_str(&self.indent);
+        text
+    }
+
+    fn joined(&self) -> String {
+        let mut text = self
+            .items
+            .iter()
+            .map(|item| collapse_lines(item))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if self.trailing_comma && self.items.len() == 1 {
+            text.push(',');
+        }
+        text
+    }
+}
+
+/// Whether a comment sits anywhere inside `node`.
+fn has_comment(node: Node) -> bool {
+    let mut cursor = node.walk();
+    let has = node
+        .children(&mut cursor)
+        .any(|child| child.kind().contains("comment") || has_comment(child));
+    has
+}
+
+/// Puts an item that spans several lines on one, dropping the line breaks right
+/// inside its own brackets.
+fn collapse_lines(item: &str) -> String {
+    let mut text = String::new();
+    for (i, line) in item.lines().map(str::trim).enumerate() {
+        if i > 0
+            && !text.ends_with(['(', '[', '{'])
+            && !line.starts_with([')', ']', '}'])
+            && !line.is_empty()
+        {
+            text.push(' ');
+        }
+        text.push_str(line);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relayout(text: &str, cursor: &str, language: &str, layout: ArgumentLayout) -> String {
+        let offset = text.find(cursor).unwrap();
+        let list = ArgumentList::find(text, offset, language).unwrap();
+        let mut out = text.to_string();
+        out.replace_range(
+            list.range(),
+            &list.layout(layout, IndentStyle::Spaces(4), "\n"),
+        );
+        out
+    }
+
+    #[test]
+    fn splits_joins_and_fits_argument_lists() {
+        let text = "    let x = call(a, g(b, c), \"d, (e\", [f]);\n";
+        let split = relayout(text, "a,", "rust", ArgumentLayout::Split);
+        assert_eq!(
+            split,
+            "    let x = call(\n        a,\n        g(b, c),\n        \"d, (e\",\n        [f]\n    );\n"
+        );
+        assert_eq!(relayout(&split, "g(", "rust", ArgumentLayout::Joined), text);
+        // The innermost list around the caret.
+        assert_eq!(
+            relayout(text, "c)", "rust", ArgumentLayout::Split),
+            "    let x = call(a, g(\n        b,\n        c\n    ), \"d, (e\", [f]);\n"
+        );
+        assert_eq!(relayout(text, "a,", "rust", ArgumentLayout::Fit(40)), split);
+        assert_eq!(
+            relayout(&split, "a,", "rust", ArgumentLayout::Fit(80)),
+            text
+        );
+
+        let trailing = "v = [\n  1,\n  2,\n]\n";
+        assert_eq!(
+            relayout(trailing, "1", "python", ArgumentLayout::Joined),
+            "v = [1, 2]\n"
+        );
+        assert_eq!(
+            relayout("t = (1,)", "1", "python", ArgumentLayout::Split),
+            "t = (\n    1,\n)"
+        );
+        assert_eq!(
+            relayout("t = (1,)", "1", "python", ArgumentLayout::Joined),
+            "t = (1,)"
+        );
+
+        // Blocks and commented lists are left alone; struct literals are lists.
+        let block = "fn f() { g(a) }";
+        assert_eq!(
+            relayout(block, "a", "rust", ArgumentLayout::Split),
+            "fn f() { g(\n    a\n) }"
+        );
+        assert!(ArgumentList::find(block, block.find("g(").unwrap(), "rust").is_none());
+        assert_eq!(
+            relayout("P { x: 1, y: 2 }", "x", "rust", ArgumentLayout::Split),
+            "P {\n    x: 1,\n    y: 2\n}"
+        );
+        let commented = "f(a, // first\n  b)";
+        assert!(ArgumentList::find(commented, 2, "rust").is_none());
+        assert!(ArgumentList::find("f()", 2, "rust").is_none());
+        assert!(ArgumentList::find("x = 1", 2, "rust").is_none());
+
+        // Any language with a grammar; brackets in strings are not lists.
+        assert_eq!(
+            relayout(
+                "foo(\"(\", [b, c]);",
+                "b",
+                "javascript",
+                ArgumentLayout::Split
+            ),
+            "foo(\"(\", [\n    b,\n    c\n]);"
+        );
+        assert_eq!(
+            relayout("fmt.Println(a, b)", "a", "go", ArgumentLayout::Split),
+            "fmt.Println(\n    a,\n    b\n)"
+        );
+        assert!(ArgumentList::find("f(a, b)", 2, "plaintext").is_none());
+    }
+}
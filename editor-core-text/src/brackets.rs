@@ -0,0 +1,83 @@
+use ropey::Rope;
+
+/// Brackets that are auto-closed and matched.
+pub const BRACKET_PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Quotes are auto-closed but not matched, since they open and close with the same char.
+pub const QUOTES: [char; 3] = ['"', '\'', '`'];
+
+/// How far to look for a match before giving up, so huge files stay responsive.
+const MAX_SCAN_CHARS: usize = 100_000;
+
+/// The char that closes `ch`, for opening brackets and quotes.
+pub fn closing_for(ch: char) -> Option<char> {
+    if QUOTES.contains(&ch) {
+        return Some(ch);
+    }
+    BRACKET_PAIRS
+        .iter()
+        .find(|(open, _)| *open == ch)
+        .map(|(_, close)| *close)
+}
+
+pub fn is_closing(ch: char) -> bool {
+    QUOTES.contains(&ch) || BRACKET_PAIRS.iter().any(|(_, close)| *close == ch)
+}
+
+/// Char index of the bracket matching the one at `char_idx`.
+///
+/// Brackets inside strings and comments are counted like any other.
+pub fn find_matching(rope: &Rope, char_idx: usize) -> Option<usize> {
+    let ch = rope.get_char(char_idx)?;
+    if let Some(&(open, close)) = BRACKET_PAIRS.iter().find(|(open, _)| *open == ch) {
+        let end = (char_idx + MAX_SCAN_CHARS).min(rope.len_chars());
+        let mut depth = 0usize;
+        for (offset, c) in rope.slice(char_idx + 1..end).chars().enumerate() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                if depth == 0 {
+                    return Some(char_idx + 1 + offset);
+                }
+                depth -= 1;
+            }
+        }
+        return None;
+    }
+
+    let &(open, close) = BRACKET_PAIRS.iter().find(|(_, close)| *close == ch)?;
+    let start = char_idx.saturating_sub(MAX_SCAN_CHARS);
+    let mut depth = 0usize;
+    let mut idx = char_idx;
+    for c in rope.chars_at(char_idx).reversed().take(char_idx - start) {
+        idx -= 1;
+        if c == close {
+            depth += 1;
+        } else if c == open {
+            if depth == 0 {
+                return Some(idx);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_nested_brackets_in_both_directions() {
+        let rope = Rope::from_str("f(a[0], {b: (c)})\n)");
+        assert_eq!(find_matching(&rope, 1), Some(16));
+        assert_eq!(find_matching(&rope, 16), Some(1));
+        assert_eq!(find_matching(&rope, 3), Some(5));
+        assert_eq!(find_matching(&rope, 15), Some(8));
+        assert_eq!(find_matching(&rope, 18), None);
+        assert_eq!(find_matching(&rope, 0), None);
+        assert_eq!(closing_for('"'), Some('"'));
+        assert_eq!(closing_for('{'), Some('}'));
+        assert!(is_closing(']'));
+    }
+}
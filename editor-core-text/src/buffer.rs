@@ -1,5 +1,22 @@
-use super::{cursor::Cursor, selection::Selection, text_model::TextModel};
+use super::{
+    brackets,
+    case::CaseTransform,
+    clock::{Clock, SystemClock},
+    cursor::{Cursor, CursorMovement},
+    indent::IndentStyle,
+    line_ending::{is_line_break, strip_line_break, LineEnding},
+    line_map::LineMap,
+    marks::{Anchor, Bias, MarkName},
+    navigation::NavigationHistory,
+    selection::Selection,
+    snapshot::BufferSnapshot,
+    text_model::TextModel,
+    wrap::WrapLayout,
+};
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::mem::size_of;
+use std::ops::Range;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -12,6 +29,21 @@ pub struct Buffer {
     undo_stack: Vec<UndoRecord>,
     redo_stack: Vec<UndoRecord>,
     undo_stack_cost: usize,
+    transaction: Option<Transaction>,
+    line_ending: LineEnding,
+    indent_style: Option<IndentStyle>,
+    clock: Arc<dyn Clock>,
+    read_only: bool,
+    /// Selections left by jumps, for navigating back and forward.
+    jumps: NavigationHistory<Vec<Selection>>,
+}
+
+/// Undo records collected between [`Buffer::begin_transaction`] and
+/// [`Buffer::end_transaction`].
+#[derive(Debug, Clone, Default)]
+struct Transaction {
+    depth: usize,
+    records: Vec<UndoRecord>,
 }
 
 #[derive(Debug, Clone)]
@@ -23,6 +55,30 @@ struct DeleteEdit {
     deleted_text: String,
 }
 
+/// Approximate heap use of a buffer, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferMemory {
+    pub text_bytes: usize,
+    /// What the rope has allocated for the text, including spare room for edits.
+    pub rope_bytes: usize,
+    /// Undo and redo history.
+    pub undo_bytes: usize,
+    pub edit_log_bytes: usize,
+}
+
+impl BufferMemory {
+    pub fn total(&self) -> usize {
+        self.rope_bytes + self.undo_bytes + self.edit_log_bytes
+    }
+}
+
+/// Direction for [`Buffer::move_lines`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineDirection {
+    Up,
+    Down,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum DeleteDirection {
     Backward,
@@ -54,6 +110,17 @@ enum UndoRecord {
         after_selections: Vec<Selection>,
         timestamp: Instant,
     },
+    /// A transaction, undone and redone as one step. Never merged with its neighbours.
+    Group {
+        records: Vec<UndoRecord>,
+        timestamp: Instant,
+    },
+    /// A whole-buffer line ending conversion; the buffer's ending flips back with the text.
+    LineEnding {
+        edit: Box<UndoRecord>,
+        before: LineEnding,
+        after: LineEnding,
+    },
 }
 
 impl UndoRecord {
@@ -61,6 +128,8 @@ impl UndoRecord {
         match self {
             UndoRecord::Insert { timestamp, .. } => *timestamp,
             UndoRecord::Delete { timestamp, .. } => *timestamp,
+            UndoRecord::Group { timestamp, .. } => *timestamp,
+            UndoRecord::LineEnding { edit, .. } => edit.timestamp(),
         }
     }
 
@@ -102,6 +171,33 @@ impl UndoRecord {
                     + after_cursors.len() * size_of::<Cursor>()
                     + after_selections.len() * size_of::<Selection>()
             }
+            UndoRecord::Group { records, .. } => records.iter().map(UndoRecord::cost).sum(),
+            UndoRecord::LineEnding { edit, .. } => edit.cost(),
+        }
+    }
+
+    /// Where redoing this record leaves the carets.
+    fn set_after(&mut self, cursors: Vec<Cursor>, selections: Vec<Selection>) {
+        match self {
+            UndoRecord::Insert {
+                after_cursors,
+                after_selections,
+                ..
+            }
+            | UndoRecord::Delete {
+                after_cursors,
+                after_selections,
+                ..
+            } => {
+                *after_cursors = cursors;
+                *after_selections = selections;
+            }
+            UndoRecord::Group { records, .. } => {
+                if let Some(last) = records.last_mut() {
+                    last.set_after(cursors, selections);
+                }
+            }
+            UndoRecord::LineEnding { edit, .. } => edit.set_after(cursors, selections),
         }
     }
 
@@ -129,6 +225,8 @@ impl UndoRecord {
                         return false;
                     }
 
+                    // `other` is positioned after this record's insertions.
+                    let mut shift = 0;
                     for i in 0..edits_a.len() {
                         let edit_a = &edits_a[i];
                         let edit_b = &edits_b[i];
@@ -137,9 +235,10 @@ impl UndoRecord {
                             return false;
                         }
                         let len_a = text_a.chars().count();
-                        if edit_b.start_char_idx != edit_a.start_char_idx + len_a {
+                        if edit_b.start_char_idx != edit_a.start_char_idx + shift + len_a {
                             return false;
                         }
+                        shift += len_a;
                     }
 
                     for i in 0..texts_a.len() {
@@ -176,20 +275,23 @@ impl UndoRecord {
                     }
 
                     let mut direction: Option<MergeDir> = None;
+                    let mut merged = edits_a.clone();
+                    // `other` is positioned after this record's deletions.
+                    let mut shift = 0;
 
-                    for (edit_a, edit_b) in edits_a.iter_mut().zip(edits_b.iter()) {
+                    for (edit_a, edit_b) in merged.iter_mut().zip(edits_b.iter()) {
                         if edit_a.index != edit_b.index {
                             return false;
                         }
 
-                        let start_a = edit_a.start_char_idx;
-                        let end_a = edit_a.start_char_idx + edit_a.len;
+                        let start_a = edit_a.start_char_idx - shift;
                         let start_b = edit_b.start_char_idx;
                         let end_b = edit_b.start_char_idx + edit_b.len;
+                        shift += edit_a.len;
 
                         let current_dir = if end_b == start_a {
                             MergeDir::Backward
-                        } else if end_a == start_b {
+                        } else if start_a == start_b {
                             MergeDir::Forward
                         } else {
                             return false;
@@ -203,7 +305,7 @@ impl UndoRecord {
 
                         match current_dir {
                             MergeDir::Backward => {
-                                edit_a.start_char_idx = start_b;
+                                edit_a.start_char_idx = start_b + shift - edit_a.len;
                                 edit_a.len += edit_b.len;
                                 edit_a.deleted_text =
                                     format!("{}{}", edit_b.deleted_text, edit_a.deleted_text);
@@ -217,19 +319,23 @@ impl UndoRecord {
                         }
                     }
 
+                    *edits_a = merged;
                     *after_cursors_a = after_cursors.clone();
                     *after_selections_a = after_selections.clone();
                     true
                 } else {
                     false
                 }
-            } // _ => false,
+            }
+            UndoRecord::Group { .. } | UndoRecord::LineEnding { .. } => false,
         }
     }
 }
 
 const COALESCE_WINDOW: Duration = Duration::from_millis(750);
 const UNDO_STACK_BUDGET_BYTES: usize = 5 * 1024 * 1024; // ~5MB
+/// Moving the primary caret across more lines than this is a jump.
+const JUMP_LINES: usize = 10;
 
 impl Buffer {
     pub fn new() -> Self {
@@ -241,35 +347,166 @@ impl Buffer {
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             undo_stack_cost: 0,
+            transaction: None,
+            line_ending: LineEnding::default(),
+            indent_style: None,
+            clock: Arc::new(SystemClock),
+            read_only: false,
+            jumps: NavigationHistory::default(),
         }
     }
 
+    /// The line ending and indent style are detected from `text`.
     pub fn from_text(text: &str) -> Self {
+        let mut buffer = Self::with_model(Arc::new(TextModel::from_str(text)));
+        buffer.line_ending = LineEnding::detect(text);
+        buffer.indent_style = IndentStyle::detect(text);
+        buffer
+    }
+
+    /// Create a buffer that edits an existing model, e.g. a second view of the same file.
+    /// Cursors and undo history are per buffer; the text is shared.
+    pub fn with_model(text_model: Arc<TextModel>) -> Self {
         Self {
-            text_model: Arc::new(TextModel::from_str(text)),
+            text_model,
             cursors: vec![Cursor::zero()],
             selections: vec![Selection::single(Cursor::zero())],
             is_dirty: false,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             undo_stack_cost: 0,
+            transaction: None,
+            line_ending: LineEnding::default(),
+            indent_style: None,
+            clock: Arc::new(SystemClock),
+            read_only: false,
+            jumps: NavigationHistory::default(),
+        }
+    }
+
+    /// Use `clock` to decide which edits are close enough in time to undo together.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn text_model(&self) -> Arc<TextModel> {
+        self.text_model.clone()
+    }
+
+    /// Create another buffer over the same model, starting at this buffer's cursors.
+    pub fn duplicate(&self) -> Self {
+        let mut buffer = Self::with_model(self.text_model.clone());
+        buffer.cursors = self.cursors.clone();
+        buffer.selections = self.selections.clone();
+        buffer.is_dirty = self.is_dirty;
+        buffer.line_ending = self.line_ending;
+        buffer.indent_style = self.indent_style;
+        buffer.clock = self.clock.clone();
+        buffer.read_only = self.read_only;
+        buffer
+    }
+
+    pub fn version(&self) -> usize {
+        self.text_model.version()
+    }
+
+    /// Receive the model version after every edit made through any buffer sharing the model.
+    pub fn watch_version(&self) -> tokio::sync::watch::Receiver<usize> {
+        self.text_model.watch_version()
+    }
+
+    /// Receive each edit made through any buffer sharing the model, as it happens.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<crate::TextChange> {
+        self.text_model.subscribe()
+    }
+
+    pub async fn search(
+        &self,
+        query: &str,
+        options: crate::SearchOptions,
+    ) -> Result<Vec<crate::SearchMatch>, crate::SearchError> {
+        let query = crate::SearchQuery::new(query, options)?;
+        Ok(self.text_model.search(&query).await)
+    }
+
+    /// Like [`Buffer::search`], keeping only matches wholly inside one of `scope`.
+    pub async fn search_in(
+        &self,
+        query: &str,
+        options: crate::SearchOptions,
+        scope: &[Range<Cursor>],
+    ) -> Result<Vec<crate::SearchMatch>, crate::SearchError> {
+        let query = crate::SearchQuery::new(query, options)?;
+        let scope = self.char_ranges(scope).await;
+        Ok(self.text_model.search_in(&query, &scope).await)
+    }
+
+    /// Replaces every match, or only those inside `scope`, as one undo step and
+    /// returns how many were replaced. Regex replacements may refer to groups as `$1`.
+    pub async fn replace_all(
+        &mut self,
+        query: &str,
+        options: crate::SearchOptions,
+        replacement: &str,
+        scope: Option<&[Range<Cursor>]>,
+    ) -> Result<usize, crate::SearchError> {
+        let query = crate::SearchQuery::new(query, options)?;
+        let matches = match scope {
+            Some(scope) => {
+                let scope = self.char_ranges(scope).await;
+                self.text_model.search_in(&query, &scope).await
+            }
+            None => self.text_model.search(&query).await,
+        };
+        if self.read_only || matches.is_empty() {
+            return Ok(0);
+        }
+        let mut edits = Vec::with_capacity(matches.len());
+        for found in &matches {
+            let matched = self.text_model.get_text_range(found.start, found.end).await;
+            let text = query.replacement(&matched, replacement).into_owned();
+            edits.push((found.start..found.end, text));
+        }
+        self.replace_ranges(&edits).await;
+        Ok(edits.len())
+    }
+
+    async fn char_ranges(&self, ranges: &[Range<Cursor>]) -> Vec<Range<usize>> {
+        let mut chars = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let start = self.clamp_cursor(range.start).await;
+            let end = self.clamp_cursor(range.end).await;
+            chars.push(self.cursor_char_index(start).await..self.cursor_char_index(end).await);
         }
+        chars
+    }
+
+    /// An immutable copy of the text and selections for reading off the buffer lock.
+    pub async fn snapshot(&self) -> BufferSnapshot {
+        let (rope, version) = self.text_model.snapshot().await;
+        BufferSnapshot::new(rope, version, self.selections.clone())
     }
 
     pub async fn get_text(&self) -> String {
         self.text_model.get_text().await
     }
 
+    /// Line breaks in `text` are written with the buffer's line ending.
     pub async fn insert_text_at_cursor(&mut self, text: &str) {
+        if self.read_only {
+            return;
+        }
         if self.selections.is_empty() {
             return;
         }
         if text.is_empty() {
             return;
         }
+        let text = self.line_ending.normalize(text);
+        let text = text.as_ref();
 
         struct SelectionEdit {
-            index: usize,
+            indices: Vec<usize>,
             start_char_idx: usize,
             end_char_idx: usize,
             collapsed: bool,
@@ -298,7 +535,7 @@ impl Buffer {
             };
 
             edits.push(SelectionEdit {
-                index,
+                indices: vec![index],
                 start_char_idx,
                 end_char_idx,
                 collapsed,
@@ -306,16 +543,15 @@ impl Buffer {
             });
         }
 
-        let touched_indices: Vec<usize> = edits.iter().map(|edit| edit.index).collect();
-
         // Normalize overlapping selections by merging their ranges.
-        edits.sort_by(|a, b| a.start_char_idx.cmp(&b.start_char_idx));
+        edits.sort_by_key(|edit| edit.start_char_idx);
         let mut normalized: Vec<SelectionEdit> = Vec::with_capacity(edits.len());
         for edit in edits {
             if let Some(last) = normalized.last_mut() {
                 if edit.start_char_idx < last.end_char_idx {
                     last.end_char_idx = last.end_char_idx.max(edit.end_char_idx);
                     last.collapsed = false;
+                    last.indices.extend(edit.indices);
                     last.replaced_text = self
                         .text_model
                         .get_text_range(last.start_char_idx, last.end_char_idx)
@@ -338,8 +574,7 @@ impl Buffer {
             .collect::<Vec<_>>();
 
         // Apply edits from the end of the buffer to avoid adjusting subsequent char indices
-        normalized.sort_by(|a, b| b.start_char_idx.cmp(&a.start_char_idx));
-        for edit in &normalized {
+        for edit in normalized.iter().rev() {
             if edit.collapsed {
                 self.text_model.insert(edit.start_char_idx, text).await;
             } else {
@@ -351,7 +586,18 @@ impl Buffer {
         }
 
         self.is_dirty = true;
-        self.update_cursors_after_insert(&touched_indices, text);
+
+        // Each caret lands after its own insertion, shifted by the edits before it.
+        let inserted_len = text.chars().count();
+        let mut shift = 0isize;
+        let mut placements = Vec::with_capacity(self.selections.len());
+        for edit in &normalized {
+            let removed = edit.end_char_idx - edit.start_char_idx;
+            let position = (edit.start_char_idx as isize + shift) as usize + inserted_len;
+            placements.extend(edit.indices.iter().map(|index| (*index, position)));
+            shift += inserted_len as isize - removed as isize;
+        }
+        self.place_cursors(placements).await;
 
         let after_cursors = self.cursors.clone();
         let after_selections = self.selections.clone();
@@ -363,7 +609,7 @@ impl Buffer {
             })
             .collect();
 
-        let timestamp = Instant::now();
+        let timestamp = self.clock.now();
         self.record_operation(UndoRecord::Insert {
             edits: replace_edits,
             inserted_texts,
@@ -380,20 +626,380 @@ impl Buffer {
             return;
         }
 
-        self.set_cursor(Cursor::new(line, column));
+        self.set_cursor(Cursor::new(line, column)).await;
         self.insert_text_at_cursor(text).await;
     }
 
+    /// Types a single char, closing brackets and quotes and stepping over a closing
+    /// char that is already there.
+    pub async fn insert_char(&mut self, ch: char) {
+        if self.read_only {
+            return;
+        }
+        let collapsed = self
+            .selections
+            .iter()
+            .all(|selection| selection.is_collapsed());
+        if !collapsed {
+            self.insert_text_at_cursor(&ch.to_string()).await;
+            return;
+        }
+
+        let mut neighbours = Vec::with_capacity(self.cursors.len());
+        for cursor in &self.cursors {
+            let idx = self.cursor_char_index(*cursor).await;
+            let before = match idx {
+                0 => None,
+                idx => self.text_model.get_char(idx - 1).await,
+            };
+            neighbours.push((before, self.text_model.get_char(idx).await));
+        }
+
+        if brackets::is_closing(ch) && neighbours.iter().all(|(_, next)| *next == Some(ch)) {
+            self.move_cursors_by(1);
+            return;
+        }
+
+        let Some(close) = brackets::closing_for(ch) else {
+            self.insert_text_at_cursor(&ch.to_string()).await;
+            return;
+        };
+        let is_quote = close == ch;
+        let can_close = neighbours.iter().all(|(before, next)| {
+            let next_ok = next.is_none_or(|c| c.is_whitespace() || brackets::is_closing(c));
+            let before_ok = !is_quote || before.is_none_or(|c| !c.is_alphanumeric() && c != ch);
+            next_ok && before_ok
+        });
+        if !can_close {
+            self.insert_text_at_cursor(&ch.to_string()).await;
+            return;
+        }
+
+        self.insert_text_at_cursor(&format!("{}{}", ch, close))
+            .await;
+        self.move_cursors_by(-1);
+        let (cursors, selections) = (self.cursors.clone(), self.selections.clone());
+        if let Some(UndoRecord::Insert {
+            after_cursors,
+            after_selections,
+            ..
+        }) = self.last_record_mut()
+        {
+            *after_cursors = cursors;
+            *after_selections = selections;
+        }
+    }
+
+    /// The bracket under or just before `cursor` and its partner, in document order.
+    pub async fn matching_bracket(&self, cursor: Cursor) -> Option<(Cursor, Cursor)> {
+        let idx = self.cursor_char_index(cursor).await;
+        let mut candidates = vec![idx];
+        if idx > 0 {
+            candidates.push(idx - 1);
+        }
+        for candidate in candidates {
+            if let Some(other) = self.text_model.matching_bracket(candidate).await {
+                let (start, end) = (candidate.min(other), candidate.max(other));
+                return Some((
+                    self.char_index_to_cursor(start).await,
+                    self.char_index_to_cursor(end).await,
+                ));
+            }
+        }
+        None
+    }
+
+    async fn char_index_to_cursor(&self, char_idx: usize) -> Cursor {
+        let line = self.text_model.char_to_line(char_idx).await;
+        Cursor::new(line, char_idx - self.text_model.line_to_char(line).await)
+    }
+
+    /// Sets a bookmark at `cursor`, moving the mark if `name` is already in use. Marks
+    /// are shared by every buffer on the same text and move along with its edits.
+    pub async fn set_mark(&mut self, name: MarkName, cursor: Cursor) {
+        let cursor = self.clamp_cursor(cursor).await;
+        let char_idx = self.cursor_char_index(cursor).await;
+        self.text_model
+            .update_marks(|marks| marks.set(name, char_idx));
+    }
+
+    pub fn remove_mark(&mut self, name: &MarkName) -> bool {
+        self.text_model.update_marks(|marks| marks.remove(name))
+    }
+
+    pub async fn mark(&self, name: &MarkName) -> Option<Cursor> {
+        let char_idx = self.text_model.update_marks(|marks| marks.get(name))?;
+        Some(self.char_index_to_cursor(char_idx).await)
+    }
+
+    /// Every bookmark in text order.
+    pub async fn marks(&self) -> Vec<(MarkName, Cursor)> {
+        let marks: Vec<_> = self.text_model.update_marks(|marks| {
+            marks
+                .iter()
+                .map(|mark| (mark.name.clone(), mark.char_idx))
+                .collect()
+        });
+        let mut positions = Vec::with_capacity(marks.len());
+        for (name, char_idx) in marks {
+            positions.push((name, self.char_index_to_cursor(char_idx).await));
+        }
+        positions
+    }
+
+    /// Tracks `start..end` across edits, for every buffer on the same text. See
+    /// [`crate::TrackedRanges`] for how edits at its ends are treated.
+    pub async fn track_range(&self, start: Cursor, end: Cursor) -> u64 {
+        let start = self.cursor_char_index(self.clamp_cursor(start).await).await;
+        let end = self.cursor_char_index(self.clamp_cursor(end).await).await;
+        self.text_model
+            .update_tracked_ranges(|ranges| ranges.track(start..end.max(start)))
+    }
+
+    pub async fn tracked_range(&self, id: u64) -> Option<Range<Cursor>> {
+        let range = self
+            .text_model
+            .update_tracked_ranges(|ranges| ranges.get(id))?;
+        Some(
+            self.char_index_to_cursor(range.start).await
+                ..self.char_index_to_cursor(range.end).await,
+        )
+    }
+
+    pub fn untrack_range(&self, id: u64) -> bool {
+        self.text_model
+            .update_tracked_ranges(|ranges| ranges.remove(id))
+    }
+
+    /// Anchors `cursor` so it moves with edits, for every buffer on the same text.
+    /// `bias` picks the side of text inserted right at it.
+    pub async fn create_anchor(&self, cursor: Cursor, bias: Bias) -> Anchor {
+        let char_idx = self
+            .cursor_char_index(self.clamp_cursor(cursor).await)
+            .await;
+        self.text_model
+            .update_anchors(|anchors| anchors.create(char_idx, bias))
+    }
+
+    pub async fn anchor_cursor(&self, anchor: Anchor) -> Option<Cursor> {
+        let char_idx = self
+            .text_model
+            .update_anchors(|anchors| anchors.get(anchor))?;
+        Some(self.char_index_to_cursor(char_idx).await)
+    }
+
+    pub fn remove_anchor(&self, anchor: Anchor) -> bool {
+        self.text_model
+            .update_anchors(|anchors| anchors.remove(anchor))
+    }
+
+    /// The lowest digit that is not bound to a mark yet.
+    pub fn free_mark_number(&self) -> Option<u8> {
+        self.text_model.update_marks(|marks| marks.free_number())
+    }
+
+    /// The next bookmark after `from`, or the previous one before it, wrapping around.
+    pub async fn next_mark(&self, from: Cursor, forward: bool) -> Option<(MarkName, Cursor)> {
+        let char_idx = self.cursor_char_index(self.clamp_cursor(from).await).await;
+        let (name, target) = self.text_model.update_marks(|marks| {
+            marks
+                .next(char_idx, forward)
+                .map(|mark| (mark.name.clone(), mark.char_idx))
+        })?;
+        Some((name, self.char_index_to_cursor(target).await))
+    }
+
+    /// Moves every collapsed cursor along its line without editing.
+    fn move_cursors_by(&mut self, delta: isize) {
+        for cursor in &mut self.cursors {
+            cursor.column = cursor.column.saturating_add_signed(delta);
+        }
+        self.selections = self
+            .cursors
+            .iter()
+            .copied()
+            .map(Selection::single)
+            .collect();
+    }
+
     pub async fn insert_line_break(&mut self) {
         self.insert_text_at_cursor("\n").await;
     }
 
-    pub async fn insert_tab(&mut self, tab_size: usize) {
-        let spaces = " ".repeat(tab_size);
-        self.insert_text_at_cursor(&spaces).await;
+    pub async fn insert_tab(&mut self, style: IndentStyle) {
+        self.insert_text_at_cursor(&style.unit()).await;
+    }
+
+    /// Breaks the line at every selection and indents the new line like the current
+    /// one, a level deeper after an opening bracket. A closing bracket right after the
+    /// caret moves to a line of its own below.
+    pub async fn insert_line_break_and_indent(&mut self, style: IndentStyle) {
+        if self.read_only {
+            return;
+        }
+        let line_break = self.line_ending.as_str();
+        let mut selections = self.selections.clone();
+        selections.sort_by_key(|selection| {
+            let start = selection.start();
+            (start.line, start.column)
+        });
+
+        let mut edits = Vec::with_capacity(selections.len());
+        let mut carets = Vec::with_capacity(selections.len());
+        for selection in selections {
+            let start = self.clamp_cursor(selection.start()).await;
+            let end = self.clamp_cursor(selection.end()).await;
+            let line = self.get_line(start.line).await.unwrap_or_default();
+            let before: String = line.chars().take(start.column).collect();
+            let indent: String = before
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let opens = matches!(before.trim_end().chars().last(), Some('{' | '(' | '['));
+            let inner = if opens {
+                format!("{}{}", indent, style.unit())
+            } else {
+                indent.clone()
+            };
+
+            let start_idx = self.cursor_char_index(start).await;
+            let end_idx = self.cursor_char_index(end).await;
+            let closes = opens
+                && matches!(
+                    self.text_model.get_char(end_idx).await,
+                    Some('}' | ')' | ']')
+                );
+            let mut text = format!("{}{}", line_break, inner);
+            carets.push(text.chars().count());
+            if closes {
+                text.push_str(line_break);
+                text.push_str(&indent);
+            }
+            edits.push((start_idx..end_idx, text));
+        }
+
+        let mut shift = 0isize;
+        let mut positions = Vec::with_capacity(edits.len());
+        for ((range, text), caret) in edits.iter().zip(carets) {
+            positions.push(range.start.saturating_add_signed(shift) + caret);
+            shift += text.chars().count() as isize - range.len() as isize;
+        }
+        self.replace_ranges(&edits).await;
+
+        let mut selections = Vec::with_capacity(positions.len());
+        for position in positions {
+            selections.push(Selection::single(self.char_index_to_cursor(position).await));
+        }
+        self.set_selections(selections).await;
+        self.record_selections_after_edit();
+    }
+
+    /// Sizes of the text and selections and how far into the text the primary caret is.
+    pub async fn stats(&self) -> crate::BufferStats {
+        self.snapshot().await.stats()
+    }
+
+    /// The text between two positions, in either order. Positions past the end of a
+    /// line or of the text are clamped.
+    pub async fn get_text_range(&self, start: Cursor, end: Cursor) -> String {
+        let start = self.cursor_char_index(self.clamp_cursor(start).await).await;
+        let end = self.cursor_char_index(self.clamp_cursor(end).await).await;
+        self.text_model
+            .get_text_range(start.min(end), start.max(end))
+            .await
+    }
+
+    /// The text `selection` covers, empty for a bare caret.
+    pub async fn get_text_in_selection(&self, selection: &Selection) -> String {
+        self.get_text_range(selection.start(), selection.end())
+            .await
+    }
+
+    /// The text of every selection in document order, empty for bare carets.
+    pub async fn selected_texts(&self) -> Vec<String> {
+        let mut selections = self.selections.clone();
+        selections.sort_by_key(|selection| {
+            let start = selection.start();
+            (start.line, start.column)
+        });
+        let mut texts = Vec::with_capacity(selections.len());
+        for selection in &selections {
+            texts.push(self.get_text_in_selection(selection).await);
+        }
+        texts
+    }
+
+    /// Deletes the selected text as one undo step and returns it, like
+    /// [`Buffer::selected_texts`]. Bare carets are left alone.
+    pub async fn cut_selections(&mut self) -> Vec<String> {
+        let texts = self.selected_texts().await;
+        if self.read_only {
+            return texts;
+        }
+        let edits: Vec<(Range<usize>, String)> = self
+            .selection_char_ranges()
+            .await
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| (range, String::new()))
+            .collect();
+        self.replace_ranges(&edits).await;
+        texts
+    }
+
+    /// Replaces every selection with pasted text as one undo step. With as many pieces
+    /// as selections each selection gets its own piece, otherwise every selection gets
+    /// all of them, one per line. Carets end up after the pasted text.
+    pub async fn paste(&mut self, pieces: &[String]) {
+        if self.read_only || pieces.is_empty() {
+            return;
+        }
+        let ranges = self.selection_char_ranges().await;
+        let per_selection = pieces.len() == ranges.len();
+        let joined = pieces.join("\n");
+        let mut edits = Vec::with_capacity(ranges.len());
+        for (index, range) in ranges.into_iter().enumerate() {
+            let text = if per_selection {
+                &pieces[index]
+            } else {
+                &joined
+            };
+            edits.push((range, self.line_ending.normalize(text).into_owned()));
+        }
+
+        let mut shift = 0isize;
+        let mut positions = Vec::with_capacity(edits.len());
+        for (range, text) in &edits {
+            let inserted = text.chars().count();
+            positions.push(range.start.saturating_add_signed(shift) + inserted);
+            shift += inserted as isize - range.len() as isize;
+        }
+        self.replace_ranges(&edits).await;
+
+        let mut selections = Vec::with_capacity(positions.len());
+        for position in positions {
+            selections.push(Selection::single(self.char_index_to_cursor(position).await));
+        }
+        self.set_selections(selections).await;
+        self.record_selections_after_edit();
+    }
+
+    /// Char range of every selection, sorted by position.
+    async fn selection_char_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::with_capacity(self.selections.len());
+        for selection in &self.selections {
+            let start = self.clamp_cursor(selection.start()).await;
+            let end = self.clamp_cursor(selection.end()).await;
+            ranges.push(self.cursor_char_index(start).await..self.cursor_char_index(end).await);
+        }
+        ranges.sort_by_key(|range| range.start);
+        ranges
     }
 
     pub async fn delete_backward(&mut self) {
+        if self.read_only {
+            return;
+        }
         let edits = self.collect_delete_edits(DeleteDirection::Backward).await;
         if edits.is_empty() {
             return;
@@ -403,7 +1009,7 @@ impl Buffer {
         self.apply_delete_edits(edits.clone()).await;
         let after_cursors = self.cursors.clone();
         let after_selections = self.selections.clone();
-        let timestamp = Instant::now();
+        let timestamp = self.clock.now();
         self.record_operation(UndoRecord::Delete {
             edits,
             before_cursors,
@@ -415,6 +1021,9 @@ impl Buffer {
     }
 
     pub async fn delete_forward(&mut self) {
+        if self.read_only {
+            return;
+        }
         let edits = self.collect_delete_edits(DeleteDirection::Forward).await;
         if edits.is_empty() {
             return;
@@ -424,7 +1033,7 @@ impl Buffer {
         self.apply_delete_edits(edits.clone()).await;
         let after_cursors = self.cursors.clone();
         let after_selections = self.selections.clone();
-        let timestamp = Instant::now();
+        let timestamp = self.clock.now();
         self.record_operation(UndoRecord::Delete {
             edits,
             before_cursors,
@@ -435,92 +1044,500 @@ impl Buffer {
         });
     }
 
-    fn update_cursors_after_insert(&mut self, affected_indices: &[usize], inserted_text: &str) {
-        let mut inserted_len = 0;
-        let mut newline_count = 0;
-        let mut last_line_len = 0;
-
-        for ch in inserted_text.chars() {
-            inserted_len += 1;
-            if ch == '\n' {
-                newline_count += 1;
-                last_line_len = 0;
-            } else {
-                last_line_len += 1;
-            }
+    /// Comments out every line touched by a selection with `token`, or uncomments them
+    /// when each non-blank one already starts with it. Blank lines are left alone and
+    /// the toggle is one undo step. `false` when there was nothing to toggle.
+    pub async fn toggle_line_comment(&mut self, token: &str) -> bool {
+        if self.read_only || token.is_empty() {
+            return false;
         }
-
-        let mut updates = Vec::with_capacity(affected_indices.len());
-
-        for &index in affected_indices {
-            if index >= self.selections.len() || index >= self.cursors.len() {
-                continue;
+        let mut lines = Vec::new();
+        for (first, last) in self.selected_line_ranges() {
+            for line_idx in first..=last {
+                let Some(text) = self.get_line(line_idx).await else {
+                    continue;
+                };
+                let content = strip_line_break(&text);
+                let body = content.trim_start_matches([' ', '\t']);
+                if !body.is_empty() {
+                    let indent = content.chars().count() - body.chars().count();
+                    lines.push((line_idx, indent, body.starts_with(token), body.to_string()));
+                }
             }
+        }
+        if lines.is_empty() {
+            return false;
+        }
 
-            let selection = self.selections[index];
-            let mut new_cursor = if selection.is_collapsed() {
-                selection.active
-            } else {
-                selection.start()
-            };
-
-            if newline_count == 0 {
-                new_cursor.column += inserted_len;
+        let uncomment = lines.iter().all(|(_, _, commented, _)| *commented);
+        let column = lines
+            .iter()
+            .map(|(_, indent, ..)| *indent)
+            .min()
+            .unwrap_or(0);
+        let token_len = token.chars().count();
+        let mut edits = Vec::with_capacity(lines.len());
+        for (line_idx, indent, _, body) in lines {
+            let line_start = self.text_model.line_to_char(line_idx).await;
+            if uncomment {
+                let spaced = body[token.len()..].starts_with(' ');
+                let start = line_start + indent;
+                edits.push((start..start + token_len + spaced as usize, String::new()));
             } else {
-                new_cursor.line += newline_count;
-                new_cursor.column = last_line_len;
+                let start = line_start + column;
+                edits.push((start..start, format!("{} ", token)));
             }
+        }
+        self.replace_ranges(&edits).await;
+        true
+    }
 
-            updates.push((index, new_cursor));
+    /// Delete every line touched by a selection.
+    pub async fn delete_lines(&mut self) {
+        if self.read_only {
+            return;
         }
+        let ranges = self.selected_line_ranges();
+        let (Some(&(first, _)), Some(&(_, last))) = (ranges.first(), ranges.last()) else {
+            return;
+        };
 
-        for (index, new_cursor) in updates {
-            if let Some(cursor_slot) = self.cursors.get_mut(index) {
-                *cursor_slot = new_cursor;
-            }
-            if let Some(selection_slot) = self.selections.get_mut(index) {
-                *selection_slot = Selection::single(new_cursor);
-            }
+        let mut lines = self.line_block(first, last).await;
+        for &(start, end) in ranges.iter().rev() {
+            lines.drain(start - first..=end - first);
         }
+
+        let selections = self
+            .selections
+            .iter()
+            .map(|selection| {
+                let line = selection.start().line;
+                let removed_above: usize = ranges
+                    .iter()
+                    .filter(|(_, end)| *end < line)
+                    .map(|(start, end)| end - start + 1)
+                    .sum();
+                let own_start = ranges
+                    .iter()
+                    .find(|(start, end)| (*start..=*end).contains(&line))
+                    .map_or(line, |(start, _)| *start);
+                Selection::single(Cursor::new(
+                    own_start - removed_above,
+                    selection.active.column,
+                ))
+            })
+            .collect();
+
+        self.replace_line_block(first, last, lines, selections)
+            .await;
     }
 
-    async fn collect_delete_edits(&self, direction: DeleteDirection) -> Vec<DeleteEdit> {
-        if self.selections.is_empty() {
-            return Vec::new();
+    /// Insert a copy of the selected lines below them; selections move to the copy.
+    pub async fn duplicate_lines(&mut self) {
+        if self.read_only {
+            return;
         }
+        let ranges = self.selected_line_ranges();
+        let (Some(&(first, _)), Some(&(_, last))) = (ranges.first(), ranges.last()) else {
+            return;
+        };
 
-        let mut edits = Vec::with_capacity(self.selections.len());
-
-        for (index, selection) in self.selections.iter().enumerate() {
-            if selection.is_collapsed() {
-                if let Some(edit) = self
-                    .build_collapsed_delete_edit(index, selection.active, direction)
-                    .await
-                {
-                    edits.push(edit);
-                }
-            } else {
-                let start = selection.start();
-                let end = selection.end();
-                let start_char_idx = self.text_model.line_to_char(start.line).await + start.column;
-                let end_char_idx = self.text_model.line_to_char(end.line).await + end.column;
-                if end_char_idx > start_char_idx {
-                    let deleted_text = self
-                        .text_model
-                        .get_text_range(start_char_idx, end_char_idx)
-                        .await;
-                    edits.push(DeleteEdit {
-                        index,
-                        start_char_idx,
-                        len: end_char_idx - start_char_idx,
-                        new_cursor: start,
-                        deleted_text,
-                    });
-                }
-            }
+        let mut lines = self.line_block(first, last).await;
+        for &(start, end) in ranges.iter().rev() {
+            let copy: Vec<String> = lines[start - first..=end - first].to_vec();
+            let at = end - first + 1;
+            lines.splice(at..at, copy);
         }
 
-        edits
+        let selections = self
+            .selections
+            .iter()
+            .map(|selection| {
+                let line = selection.start().line;
+                let shift: usize = ranges
+                    .iter()
+                    .filter(|(start, _)| *start <= line)
+                    .map(|(start, end)| end - start + 1)
+                    .sum();
+                Self::shift_selection(*selection, shift as isize)
+            })
+            .collect();
+
+        self.replace_line_block(first, last, lines, selections)
+            .await;
+    }
+
+    /// Moves every selection's caret; with `extend` the anchors stay put. `wrap` makes
+    /// Up, Down and the page movements move by visual row; a page is `page_rows` rows.
+    pub async fn move_selections(
+        &mut self,
+        movement: CursorMovement,
+        extend: bool,
+        wrap: Option<&WrapLayout>,
+        page_rows: usize,
+    ) {
+        let line_count = self.line_count().await;
+        let mut moved = Vec::with_capacity(self.get_selections().len());
+
+        for current in self.get_selections().to_vec() {
+            // Another view sharing the text may have shortened it since.
+            let mut cursor = current.active.clamp_to(self).await;
+            match movement {
+                CursorMovement::Left => {
+                    if cursor.column > 0 {
+                        cursor.column -= 1;
+                    } else if cursor.line > 0 {
+                        cursor.line -= 1;
+                        cursor.column = self.line_len_without_newline(cursor.line).await;
+                    }
+                }
+                CursorMovement::Right => {
+                    let len = self.line_len_without_newline(cursor.line).await;
+                    if cursor.column < len {
+                        cursor.column += 1;
+                    } else if cursor.line + 1 < line_count {
+                        cursor.line += 1;
+                        cursor.column = 0;
+                    } else {
+                        cursor.column = len;
+                    }
+                }
+                CursorMovement::Up | CursorMovement::Down if wrap.is_some() => {
+                    let delta = if movement == CursorMovement::Up {
+                        -1
+                    } else {
+                        1
+                    };
+                    if let Some(wrap) = wrap {
+                        cursor = wrap.move_vertically(cursor, delta);
+                    }
+                }
+                CursorMovement::Up if cursor.line > 0 => {
+                    cursor.line -= 1;
+                    let len = self.line_len_without_newline(cursor.line).await;
+                    cursor.column = cursor.column.min(len);
+                }
+                CursorMovement::Down => {
+                    let next_line = cursor.line + 1;
+                    if next_line < line_count {
+                        cursor.line = next_line;
+                        let len = self.line_len_without_newline(cursor.line).await;
+                        cursor.column = cursor.column.min(len);
+                    }
+                }
+                CursorMovement::PageUp | CursorMovement::PageDown => {
+                    let rows = page_rows.max(1) as isize;
+                    let delta = if movement == CursorMovement::PageUp {
+                        -rows
+                    } else {
+                        rows
+                    };
+                    match wrap {
+                        Some(wrap) => cursor = wrap.move_vertically(cursor, delta),
+                        None => {
+                            cursor.line = cursor
+                                .line
+                                .saturating_add_signed(delta)
+                                .min(line_count.saturating_sub(1));
+                            let len = self.line_len_without_newline(cursor.line).await;
+                            cursor.column = cursor.column.min(len);
+                        }
+                    }
+                }
+                // Home goes to the first non-whitespace char, or to column 0 when
+                // already there.
+                CursorMovement::Home => {
+                    let indent = self.line_indent_len(cursor.line).await;
+                    cursor.column = if cursor.column == indent { 0 } else { indent };
+                }
+                CursorMovement::LineStart => {
+                    cursor.column = 0;
+                }
+                CursorMovement::LineEnd | CursorMovement::End => {
+                    cursor.column = self.line_len_without_newline(cursor.line).await;
+                }
+                CursorMovement::DocumentStart => {
+                    cursor = Cursor::zero();
+                }
+                CursorMovement::DocumentEnd => {
+                    cursor.line = line_count.saturating_sub(1);
+                    cursor.column = self.line_len_without_newline(cursor.line).await;
+                }
+                _ => {}
+            }
+
+            moved.push(if extend {
+                Selection::new(current.anchor, cursor)
+            } else {
+                Selection::single(cursor)
+            });
+        }
+
+        if moved.is_empty() {
+            moved.push(Selection::single(Cursor::zero()));
+        }
+        self.set_selections(moved).await;
+    }
+
+    /// Swap the selected lines with the line above or below them.
+    pub async fn move_lines(&mut self, direction: LineDirection) {
+        if self.read_only {
+            return;
+        }
+        let ranges = self.selected_line_ranges();
+        let (Some(&(first, _)), Some(&(_, last))) = (ranges.first(), ranges.last()) else {
+            return;
+        };
+        let line_count = self.text_model.line_count().await;
+
+        let (block_first, block_last, shift) = match direction {
+            LineDirection::Up if first > 0 => (first - 1, last, -1),
+            LineDirection::Down if last + 1 < line_count => (first, last + 1, 1),
+            _ => return,
+        };
+
+        let mut lines = self.line_block(block_first, block_last).await;
+        for &(start, end) in &ranges {
+            let (start, end) = (start - block_first, end - block_first);
+            match direction {
+                LineDirection::Up => lines[start - 1..=end].rotate_left(1),
+                LineDirection::Down => lines[start..=end + 1].rotate_right(1),
+            }
+        }
+
+        let selections = self
+            .selections
+            .iter()
+            .map(|selection| Self::shift_selection(*selection, shift))
+            .collect();
+
+        self.replace_line_block(block_first, block_last, lines, selections)
+            .await;
+    }
+
+    /// Line ranges covered by the selections, sorted and with touching ranges merged.
+    /// A selection that ends at column 0 does not include its last line.
+    fn selected_line_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = self
+            .selections
+            .iter()
+            .map(|selection| {
+                let (start, end) = (selection.start(), selection.end());
+                if end.line > start.line && end.column == 0 {
+                    (start.line, end.line - 1)
+                } else {
+                    (start.line, end.line)
+                }
+            })
+            .collect();
+        ranges.sort();
+
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    fn shift_selection(selection: Selection, lines: isize) -> Selection {
+        let shift =
+            |cursor: Cursor| Cursor::new(cursor.line.saturating_add_signed(lines), cursor.column);
+        Selection::new(shift(selection.anchor), shift(selection.active))
+    }
+
+    /// Char range of lines `first..=last`, without the line break that ends `last`.
+    async fn line_block_range(&self, first: usize, last: usize) -> (usize, usize) {
+        let start = self.text_model.line_to_char(first).await;
+        let end = if last + 1 < self.text_model.line_count().await {
+            let next = self.text_model.line_to_char(last + 1).await;
+            next - self.line_break_len_before(next).await
+        } else {
+            self.text_model.len().await
+        };
+        (start, end)
+    }
+
+    /// Chars of the line break that ends right before `char_idx`, 0 if none does.
+    /// `\r\n` is one break of two chars, like in the rope's line indices.
+    async fn line_break_len_before(&self, char_idx: usize) -> usize {
+        match char_idx.checked_sub(1) {
+            Some(before) => match self.text_model.get_char(before).await {
+                Some('\n')
+                    if before > 0 && self.text_model.get_char(before - 1).await == Some('\r') =>
+                {
+                    2
+                }
+                Some(ch) if is_line_break(ch) => 1,
+                _ => 0,
+            },
+            None => 0,
+        }
+    }
+
+    /// Lines `first..=last` without their line breaks, one entry per rope line so
+    /// callers can index the block by line number.
+    async fn line_block(&self, first: usize, last: usize) -> Vec<String> {
+        let mut lines = Vec::with_capacity(last + 1 - first);
+        self.text_model
+            .for_each_line(first..last + 1, |_, line| lines.push(line.to_string()))
+            .await;
+        lines
+    }
+
+    /// Replace lines `first..=last` as one undoable edit, then place the selections.
+    async fn replace_line_block(
+        &mut self,
+        first: usize,
+        last: usize,
+        lines: Vec<String>,
+        selections: Vec<Selection>,
+    ) {
+        let (mut start, mut end) = self.line_block_range(first, last).await;
+        // Removing every line also removes one of the line breaks around the block.
+        if lines.is_empty() {
+            if end < self.text_model.len().await {
+                end = self.text_model.line_to_char(last + 1).await;
+            } else {
+                start -= self.line_break_len_before(start).await;
+            }
+        }
+
+        let new_text = lines.join(self.line_ending.as_str());
+        let replaced_text = self.text_model.get_text_range(start, end).await;
+        if replaced_text == new_text {
+            return;
+        }
+
+        let before_cursors = self.cursors.clone();
+        let before_selections = self.selections.clone();
+        self.text_model.replace(start, end - start, &new_text).await;
+        self.is_dirty = true;
+
+        let line_count = self.text_model.line_count().await;
+        let mut clamped = Vec::with_capacity(selections.len());
+        for selection in selections {
+            let mut cursors = [selection.anchor, selection.active];
+            for cursor in &mut cursors {
+                cursor.line = cursor.line.min(line_count.saturating_sub(1));
+                let len = self
+                    .text_model
+                    .get_line(cursor.line)
+                    .await
+                    .map(|line| strip_line_break(&line).chars().count())
+                    .unwrap_or(0);
+                cursor.column = cursor.column.min(len);
+            }
+            clamped.push(Selection::new(cursors[0], cursors[1]));
+        }
+        self.cursors = clamped.iter().map(|selection| selection.active).collect();
+        self.selections = clamped;
+
+        self.record_operation(UndoRecord::Insert {
+            edits: vec![ReplaceEdit {
+                start_char_idx: start,
+                replaced_text,
+            }],
+            inserted_texts: vec![new_text],
+            before_cursors,
+            before_selections,
+            after_cursors: self.cursors.clone(),
+            after_selections: self.selections.clone(),
+            timestamp: self.clock.now(),
+        });
+    }
+
+    /// Moves the cursor at each selection index to a char index in the current text.
+    /// Carets that end up on the same spot are merged.
+    async fn place_cursors(&mut self, placements: Vec<(usize, usize)>) {
+        for (index, char_idx) in placements {
+            let cursor = self.char_index_to_cursor(char_idx).await;
+            if let Some(cursor_slot) = self.cursors.get_mut(index) {
+                *cursor_slot = cursor;
+            }
+            if let Some(selection_slot) = self.selections.get_mut(index) {
+                *selection_slot = Selection::single(cursor);
+            }
+        }
+        self.merge_overlapping_selections();
+    }
+
+    /// Joins selections that overlap so that multi-cursor edits never touch the same
+    /// text twice.
+    fn merge_overlapping_selections(&mut self) {
+        if self.cursors.len() != self.selections.len() {
+            return;
+        }
+        loop {
+            let mut merged: Vec<Selection> = Vec::with_capacity(self.selections.len());
+            for selection in &self.selections {
+                match merged.iter_mut().find(|kept| kept.overlaps(selection)) {
+                    Some(kept) => *kept = kept.merge(selection),
+                    None => merged.push(*selection),
+                }
+            }
+            let done = merged.len() == self.selections.len();
+            self.selections = merged;
+            if done {
+                break;
+            }
+        }
+        self.cursors = self
+            .selections
+            .iter()
+            .map(|selection| selection.active)
+            .collect();
+    }
+
+    async fn collect_delete_edits(&self, direction: DeleteDirection) -> Vec<DeleteEdit> {
+        if self.selections.is_empty() {
+            return Vec::new();
+        }
+
+        let mut edits = Vec::with_capacity(self.selections.len());
+
+        for (index, selection) in self.selections.iter().enumerate() {
+            if selection.is_collapsed() {
+                if let Some(edit) = self
+                    .build_collapsed_delete_edit(index, selection.active, direction)
+                    .await
+                {
+                    edits.push(edit);
+                }
+            } else {
+                let start = selection.start();
+                let end = selection.end();
+                let start_char_idx = self.text_model.line_to_char(start.line).await + start.column;
+                let end_char_idx = self.text_model.line_to_char(end.line).await + end.column;
+                if end_char_idx > start_char_idx {
+                    let deleted_text = self
+                        .text_model
+                        .get_text_range(start_char_idx, end_char_idx)
+                        .await;
+                    edits.push(DeleteEdit {
+                        index,
+                        start_char_idx,
+                        len: end_char_idx - start_char_idx,
+                        new_cursor: start,
+                        deleted_text,
+                    });
+                }
+            }
+        }
+
+        // Two carets can target the same char; delete it once.
+        edits.sort_by_key(|edit| edit.start_char_idx);
+        let mut end = 0;
+        edits.retain(|edit| {
+            let keep = edit.start_char_idx >= end;
+            if keep {
+                end = edit.start_char_idx + edit.len;
+            }
+            keep
+        });
+        edits
     }
 
     async fn build_collapsed_delete_edit(
@@ -547,20 +1564,18 @@ impl Buffer {
                     if line_start == 0 {
                         return None;
                     }
-                    let prev_line_length = self
-                        .text_model
-                        .get_line(cursor.line - 1)
-                        .await
-                        .map(|line| line.chars().count())
-                        .unwrap_or(0);
+                    // Join with the previous line, taking a `\r\n` out whole.
+                    let prev_line_length = self.line_len_without_newline(cursor.line - 1).await;
+                    let break_start =
+                        self.text_model.line_to_char(cursor.line - 1).await + prev_line_length;
                     let deleted_text = self
                         .text_model
-                        .get_text_range(line_start - 1, line_start)
+                        .get_text_range(break_start, line_start)
                         .await;
                     Some(DeleteEdit {
                         index,
-                        start_char_idx: line_start - 1,
-                        len: 1,
+                        start_char_idx: break_start,
+                        len: line_start - break_start,
                         new_cursor: Cursor::new(cursor.line - 1, prev_line_length),
                         deleted_text,
                     })
@@ -579,11 +1594,18 @@ impl Buffer {
                 let char_idx = line_offset + cursor.column;
 
                 if cursor.column < current_line_length {
-                    let deleted_text = self.text_model.get_text_range(char_idx, char_idx + 1).await;
+                    let mut len = 1;
+                    if self.text_model.get_text_range(char_idx, char_idx + 2).await == "\r\n" {
+                        len = 2;
+                    }
+                    let deleted_text = self
+                        .text_model
+                        .get_text_range(char_idx, char_idx + len)
+                        .await;
                     Some(DeleteEdit {
                         index,
                         start_char_idx: char_idx,
-                        len: 1,
+                        len,
                         new_cursor: cursor,
                         deleted_text,
                     })
@@ -612,20 +1634,36 @@ impl Buffer {
             return;
         }
 
-        edits.sort_by(|a, b| b.start_char_idx.cmp(&a.start_char_idx));
+        // Where every caret goes, in pre-delete char indices; carets without an edit stay put.
+        let mut positions = Vec::with_capacity(self.selections.len());
+        for (index, selection) in self.selections.iter().enumerate() {
+            let position = match edits.iter().find(|edit| edit.index == index) {
+                Some(edit) => edit.start_char_idx,
+                None => self.cursor_char_index(selection.active).await,
+            };
+            positions.push((index, position));
+        }
+
+        edits.sort_by_key(|edit| Reverse(edit.start_char_idx));
         for edit in &edits {
             self.text_model.remove(edit.start_char_idx, edit.len).await;
         }
         self.is_dirty = true;
 
-        for edit in edits {
-            if let Some(cursor_slot) = self.cursors.get_mut(edit.index) {
-                *cursor_slot = edit.new_cursor;
-            }
-            if let Some(selection_slot) = self.selections.get_mut(edit.index) {
-                *selection_slot = Selection::single(edit.new_cursor);
-            }
-        }
+        let placements = positions
+            .into_iter()
+            .map(|(index, position)| {
+                let removed_before: usize = edits
+                    .iter()
+                    .map(|edit| {
+                        let end = (edit.start_char_idx + edit.len).min(position);
+                        end.saturating_sub(edit.start_char_idx)
+                    })
+                    .sum();
+                (index, position - removed_before)
+            })
+            .collect();
+        self.place_cursors(placements).await;
     }
 
     pub fn get_cursors(&self) -> &[Cursor] {
@@ -636,70 +1674,705 @@ impl Buffer {
         &self.selections
     }
 
-    pub fn set_cursor(&mut self, cursor: Cursor) {
+    /// Positions outside the text are clamped into it, see [`Cursor::clamp_to`]; the same
+    /// goes for the other selection setters.
+    pub async fn set_cursor(&mut self, cursor: Cursor) {
+        let cursor = cursor.clamp_to(self).await;
+        self.record_jump(&[Selection::single(cursor)]);
         self.cursors = vec![cursor];
         self.selections = vec![Selection::single(cursor)];
     }
 
-    pub fn add_cursor(&mut self, cursor: Cursor) {
-        self.cursors.push(cursor);
-        self.selections.push(Selection::single(cursor));
+    /// Adds a caret, which becomes the primary one. A caret on an existing selection is
+    /// merged into it.
+    pub async fn add_cursor(&mut self, cursor: Cursor) {
+        let cursor = cursor.clamp_to(self).await;
+        let mut selections = self.selections.clone();
+        selections.push(Selection::single(cursor));
+        self.replace_selections(selections);
+    }
+
+    pub async fn set_selection(&mut self, selection: Selection) {
+        let selection = self.clamp_selection(selection).await;
+        self.record_jump(&[selection]);
+        self.selections = vec![selection];
+        self.cursors = vec![selection.active];
+    }
+
+    /// Replaces every selection. The last one is the primary selection.
+    pub async fn set_selections(&mut self, selections: Vec<Selection>) {
+        if selections.is_empty() {
+            return;
+        }
+        let mut clamped = Vec::with_capacity(selections.len());
+        for selection in selections {
+            clamped.push(self.clamp_selection(selection).await);
+        }
+        self.record_jump(&clamped);
+        self.replace_selections(clamped);
+    }
+
+    async fn clamp_selection(&self, selection: Selection) -> Selection {
+        Selection::new(
+            selection.anchor.clamp_to(self).await,
+            selection.active.clamp_to(self).await,
+        )
+    }
+
+    fn replace_selections(&mut self, selections: Vec<Selection>) {
+        self.cursors = selections
+            .iter()
+            .map(|selection| selection.active)
+            .collect();
+        self.selections = selections;
+        self.merge_overlapping_selections();
+    }
+
+    /// Like [`Buffer::set_selections`], but moves positions past the end of the text
+    /// back inside it, e.g. when restoring selections saved for an older version.
+    /// Not recorded as a jump.
+    pub async fn restore_selections(&mut self, selections: Vec<Selection>) {
+        if selections.is_empty() {
+            return;
+        }
+        let mut clamped = Vec::with_capacity(selections.len());
+        for selection in selections {
+            clamped.push(self.clamp_selection(selection).await);
+        }
+        self.replace_selections(clamped);
     }
 
-    pub fn set_selection(&mut self, selection: Selection) {
-        self.selections = vec![selection];
-        self.cursors = vec![selection.active];
-    }
+    /// Remembers the current selections if moving to `to` takes the primary caret
+    /// across more than [`JUMP_LINES`] lines.
+    fn record_jump(&mut self, to: &[Selection]) {
+        let (Some(from), Some(to)) = (self.selections.last(), to.last()) else {
+            return;
+        };
+        if from.active.line.abs_diff(to.active.line) > JUMP_LINES {
+            self.jumps.push(self.selections.clone());
+        }
+    }
+
+    pub fn jumps(&self) -> &NavigationHistory<Vec<Selection>> {
+        &self.jumps
+    }
+
+    /// Puts the selections back where they were before the last jump, or forward again
+    /// after going back. Returns whether there was anywhere to go.
+    pub async fn navigate_selections(&mut self, forward: bool) -> bool {
+        let current = self.selections.clone();
+        let place = if forward {
+            self.jumps.forward(current)
+        } else {
+            self.jumps.back(current)
+        };
+        match place {
+            Some(selections) => {
+                self.restore_selections(selections).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a caret on the line above or below every existing caret, keeping its column
+    /// where the line is long enough.
+    pub async fn add_cursors_vertically(&mut self, direction: LineDirection) {
+        let line_count = self.text_model.line_count().await;
+        let mut selections = self.selections.clone();
+        for selection in &self.selections {
+            let cursor = selection.active;
+            let line = match direction {
+                LineDirection::Up if cursor.line > 0 => cursor.line - 1,
+                LineDirection::Down if cursor.line + 1 < line_count => cursor.line + 1,
+                _ => continue,
+            };
+            let line_len = self.line_len_without_newline(line).await;
+            let added = Selection::single(Cursor::new(line, cursor.column.min(line_len)));
+            if !selections.contains(&added) {
+                selections.push(added);
+            }
+        }
+        self.set_selections(selections).await;
+    }
+
+    /// Columns of the word touching `cursor`, if there is one.
+    async fn word_columns_at(&self, cursor: Cursor) -> Option<(usize, usize)> {
+        let line = self.text_model.get_line(cursor.line).await?;
+        let chars: Vec<char> = line.chars().collect();
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let column = cursor.column.min(chars.len());
+        let start = column
+            - chars[..column]
+                .iter()
+                .rev()
+                .take_while(|c| is_word(c))
+                .count();
+        let end = column + chars[column..].iter().take_while(|c| is_word(c)).count();
+        (start < end).then_some((start, end))
+    }
+
+    /// The word touching `cursor`: letters, digits and `_` on its line.
+    pub async fn word_at(&self, cursor: Cursor) -> Option<Range<Cursor>> {
+        let (start, end) = self.word_columns_at(cursor).await?;
+        Some(Cursor::new(cursor.line, start)..Cursor::new(cursor.line, end))
+    }
+
+    /// The line of `cursor` including its line break, so selecting it and typing
+    /// replaces the whole line. The last line ends at the end of the buffer.
+    pub async fn line_range_at(&self, cursor: Cursor) -> Range<Cursor> {
+        let line = cursor
+            .line
+            .min(self.text_model.line_count().await.saturating_sub(1));
+        Cursor::new(line, 0)..self.line_end_with_break(line).await
+    }
+
+    /// The run of non-blank lines around `cursor`, with the line break after it.
+    /// `None` on a blank line.
+    pub async fn paragraph_at(&self, cursor: Cursor) -> Option<Range<Cursor>> {
+        let line_count = self.text_model.line_count().await;
+        let is_blank = |text: Option<String>| text.is_none_or(|text| text.trim().is_empty());
+        if cursor.line >= line_count || is_blank(self.text_model.get_line(cursor.line).await) {
+            return None;
+        }
+        let mut first = cursor.line;
+        while first > 0 && !is_blank(self.text_model.get_line(first - 1).await) {
+            first -= 1;
+        }
+        let mut last = cursor.line;
+        while last + 1 < line_count && !is_blank(self.text_model.get_line(last + 1).await) {
+            last += 1;
+        }
+        Some(Cursor::new(first, 0)..self.line_end_with_break(last).await)
+    }
+
+    async fn line_end_with_break(&self, line: usize) -> Cursor {
+        if line + 1 < self.text_model.line_count().await {
+            Cursor::new(line + 1, 0)
+        } else {
+            Cursor::new(line, self.line_len_without_newline(line).await)
+        }
+    }
+
+    /// Converts the selected text, or the word under each bare caret, as one undo
+    /// step. Returns whether any text changed.
+    pub async fn transform_case(&mut self, transform: CaseTransform) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let mut ranges = Vec::with_capacity(self.selections.len());
+        for selection in &self.selections {
+            if !selection.is_collapsed() {
+                continue;
+            }
+            let caret = self.clamp_cursor(selection.active).await;
+            if let Some((start, end)) = self.word_columns_at(caret).await {
+                let line_start = self.text_model.line_to_char(caret.line).await;
+                ranges.push(line_start + start..line_start + end);
+            }
+        }
+        ranges.extend(
+            self.selection_char_ranges()
+                .await
+                .into_iter()
+                .filter(|range| !range.is_empty()),
+        );
+        ranges.sort_by_key(|range| range.start);
+        // Two carets in one word would otherwise convert it twice.
+        ranges.dedup_by(|next, previous| next.start < previous.end);
+
+        let mut edits = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let text = self.text_model.get_text_range(range.start, range.end).await;
+            let converted = transform.apply(&text);
+            if converted != text {
+                edits.push((range, converted));
+            }
+        }
+        if edits.is_empty() {
+            return false;
+        }
+        self.replace_ranges(&edits).await;
+        true
+    }
+
+    /// Lays out the innermost argument list around the primary caret, as one undo
+    /// step. Returns whether any text changed.
+    pub async fn layout_arguments(
+        &mut self,
+        language: &str,
+        layout: crate::ArgumentLayout,
+        style: IndentStyle,
+    ) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let Some(primary) = self.selections.last().copied() else {
+            return false;
+        };
+        let caret = self.clamp_cursor(primary.active).await;
+        let caret_idx = self.cursor_char_index(caret).await;
+        let text = self.text_model.get_text().await;
+        let offset = text
+            .char_indices()
+            .nth(caret_idx)
+            .map_or(text.len(), |(at, _)| at);
+        let Some(list) = crate::ArgumentList::find(&text, offset, language) else {
+            return false;
+        };
+        let replacement = list.layout(layout, style, self.line_ending.as_str());
+        let range = list.range();
+        if text[range.clone()] == replacement {
+            return false;
+        }
+        let start = text[..range.start].chars().count();
+        let end = start + text[range].chars().count();
+        self.replace_ranges(&[(start..end, replacement)]).await;
+        true
+    }
+
+    /// Selects the word under the primary caret, or adds a selection at the next
+    /// occurrence of the primary selection's text, wrapping at the end of the buffer.
+    /// Returns whether the selections changed.
+    pub async fn add_next_occurrence(&mut self) -> bool {
+        let Some(primary) = self.selections.last().copied() else {
+            return false;
+        };
+
+        if primary.is_collapsed() {
+            let Some((start, end)) = self.word_columns_at(primary.active).await else {
+                return false;
+            };
+            let line_idx = primary.active.line;
+            let last = self.selections.len() - 1;
+            self.selections[last] =
+                Selection::new(Cursor::new(line_idx, start), Cursor::new(line_idx, end));
+            self.cursors = self.selections.iter().map(|s| s.active).collect();
+            // Another caret may sit inside the word.
+            self.merge_overlapping_selections();
+            return true;
+        }
+
+        let end = self.cursor_char_index(primary.end()).await;
+        let needle = self.get_text_in_selection(&primary).await;
+        let options = crate::SearchOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let Ok(query) = crate::SearchQuery::new(&needle, options) else {
+            return false;
+        };
+        let matches = self.text_model.search(&query).await;
+        // Matches that overlap a selection, like the second `aa` in `aaa`, would make
+        // the edits of the selections collide.
+        let selected = self.selection_char_ranges().await;
+        let taken = |m: &crate::SearchMatch| {
+            selected
+                .iter()
+                .any(|range| range.start < m.end && m.start < range.end)
+        };
+        let next = matches
+            .iter()
+            .filter(|m| m.start >= end)
+            .chain(matches.iter().filter(|m| m.start < end))
+            .find(|m| !taken(m));
+        let Some(next) = next else {
+            return false;
+        };
+
+        self.selections
+            .push(Selection::new(next.start_position, next.end_position));
+        self.cursors.push(next.end_position);
+        true
+    }
+
+    /// Splits each multi-line selection into one selection per line, with the caret at
+    /// the end of each line's part.
+    pub async fn split_selection_into_lines(&mut self) {
+        let mut selections = Vec::with_capacity(self.selections.len());
+        for selection in &self.selections {
+            let (start, end) = (selection.start(), selection.end());
+            if start.line == end.line {
+                selections.push(*selection);
+                continue;
+            }
+            for line in start.line..=end.line {
+                let from = if line == start.line { start.column } else { 0 };
+                let to = if line == end.line {
+                    end.column
+                } else {
+                    self.line_len_without_newline(line).await
+                };
+                if line == end.line && to == 0 {
+                    continue;
+                }
+                selections.push(Selection::new(
+                    Cursor::new(line, from),
+                    Cursor::new(line, to),
+                ));
+            }
+        }
+        self.set_selections(selections).await;
+    }
+
+    async fn line_len_without_newline(&self, line: usize) -> usize {
+        self.text_model
+            .get_line(line)
+            .await
+            .map(|text| strip_line_break(&text).chars().count())
+            .unwrap_or(0)
+    }
+
+    /// Chars of leading whitespace on `line`.
+    async fn line_indent_len(&self, line: usize) -> usize {
+        self.text_model
+            .get_line(line)
+            .await
+            .map(|text| {
+                strip_line_break(&text)
+                    .chars()
+                    .take_while(|ch| ch.is_whitespace())
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    pub async fn cursor_char_index(&self, cursor: Cursor) -> usize {
+        self.text_model.line_to_char(cursor.line).await + cursor.column
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    pub async fn memory_usage(&self) -> BufferMemory {
+        let (text_bytes, rope_bytes) = self.text_model.memory_usage().await;
+        let redo_bytes: usize = self.redo_stack.iter().map(UndoRecord::cost).sum();
+        BufferMemory {
+            text_bytes,
+            rope_bytes,
+            undo_bytes: self.undo_stack_cost + redo_bytes,
+            edit_log_bytes: self.text_model.edit_log_bytes(),
+        }
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// The indent style detected when the text was loaded, if it had enough indentation
+    /// to tell.
+    pub fn indent_style(&self) -> Option<IndentStyle> {
+        self.indent_style
+    }
+
+    pub fn set_indent_style(&mut self, style: Option<IndentStyle>) {
+        self.indent_style = style;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// A read-only buffer ignores edits, undo and redo. `set_text` and `reload` still
+    /// replace the text so it can follow the file on disk.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Rewrites every line break to `line_ending` as a single undoable edit. Returns false
+    /// when the buffer already uses it throughout.
+    pub async fn set_line_ending(&mut self, line_ending: LineEnding) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let old = self.text_model.get_text().await;
+        let new = match line_ending.normalize(&old) {
+            Cow::Borrowed(_) if line_ending == self.line_ending => return false,
+            new => new.into_owned(),
+        };
+        let before = self.line_ending;
+        let before_cursors = self.cursors.clone();
+        let before_selections = self.selections.clone();
+
+        if new != old {
+            self.text_model.replace(0, old.chars().count(), &new).await;
+        }
+        self.line_ending = line_ending;
+        // Columns count chars before the line break, so they stay put.
+        self.restore_selections(before_selections.clone()).await;
+        self.is_dirty = true;
+
+        self.record_operation(UndoRecord::LineEnding {
+            edit: Box::new(UndoRecord::Insert {
+                edits: vec![ReplaceEdit {
+                    start_char_idx: 0,
+                    replaced_text: old,
+                }],
+                inserted_texts: vec![new],
+                before_cursors,
+                before_selections,
+                after_cursors: self.cursors.clone(),
+                after_selections: self.selections.clone(),
+                timestamp: self.clock.now(),
+            }),
+            before,
+            after: line_ending,
+        });
+        true
+    }
+
+    pub fn mark_clean(&mut self) {
+        self.is_dirty = false;
+    }
+
+    pub async fn line_count(&self) -> usize {
+        self.text_model.line_count().await
+    }
+
+    pub async fn get_line(&self, line_idx: usize) -> Option<String> {
+        self.text_model.get_line(line_idx).await
+    }
+
+    pub async fn get_line_length(&self, line_idx: usize) -> Option<usize> {
+        self.text_model
+            .get_line(line_idx)
+            .await
+            .map(|line| line.chars().count())
+    }
+
+    /// Replace a character range in the buffer with new text, returns new cursor index in chars.
+    pub async fn replace_range(
+        &mut self,
+        start_char_idx: usize,
+        len: usize,
+        new_text: &str,
+    ) -> usize {
+        if self.read_only {
+            return start_char_idx;
+        }
+        let replaced_text = self
+            .text_model
+            .get_text_range(start_char_idx, start_char_idx + len)
+            .await;
+        self.text_model.replace(start_char_idx, len, new_text).await;
+        self.is_dirty = true;
+        self.record_operation(UndoRecord::Insert {
+            edits: vec![ReplaceEdit {
+                start_char_idx,
+                replaced_text,
+            }],
+            inserted_texts: vec![new_text.to_string()],
+            before_cursors: self.cursors.clone(),
+            before_selections: self.selections.clone(),
+            after_cursors: self.cursors.clone(),
+            after_selections: self.selections.clone(),
+            timestamp: self.clock.now(),
+        });
+        // Return start + inserted length as a best-effort caret position.
+        start_char_idx + new_text.chars().count()
+    }
+
+    /// Replaces several char ranges of the current text as one undo step and one
+    /// version, e.g. edits a language server computed. Overlapping or out of range edits
+    /// change nothing. Selections keep their place in the surrounding text; text
+    /// inserted exactly at a caret goes after it.
+    pub async fn replace_ranges(&mut self, edits: &[(Range<usize>, String)]) {
+        if self.read_only {
+            return;
+        }
+        if edits.is_empty() {
+            return;
+        }
+        // Read before the edits; invalid ranges are clamped here and rejected below.
+        let len = self.text_model.len().await;
+        let mut replaced = Vec::with_capacity(edits.len());
+        for (range, _) in edits {
+            let end = range.end.min(len);
+            replaced.push(ReplaceEdit {
+                start_char_idx: range.start,
+                replaced_text: self
+                    .text_model
+                    .get_text_range(range.start.min(end), end)
+                    .await,
+            });
+        }
+        let mut anchors = Vec::with_capacity(self.selections.len());
+        for selection in self.selections.clone() {
+            let anchor = self.clamp_cursor(selection.anchor).await;
+            let active = self.clamp_cursor(selection.active).await;
+            anchors.push((
+                self.cursor_char_index(anchor).await,
+                self.cursor_char_index(active).await,
+            ));
+        }
+        let Ok(map) = self.text_model.apply_edits(edits.to_vec()).await else {
+            return;
+        };
+        self.is_dirty = true;
+        self.begin_transaction();
+        self.record_operation(UndoRecord::Insert {
+            edits: replaced,
+            inserted_texts: edits.iter().map(|(_, text)| text.clone()).collect(),
+            before_cursors: self.cursors.clone(),
+            before_selections: self.selections.clone(),
+            after_cursors: self.cursors.clone(),
+            after_selections: self.selections.clone(),
+            timestamp: self.clock.now(),
+        });
+        self.end_transaction();
+        let positions = anchors
+            .into_iter()
+            .map(|(anchor, active)| (map.map(anchor), map.map(active)));
+
+        let mut selections = Vec::with_capacity(positions.len());
+        for (anchor, active) in positions {
+            selections.push(Selection::new(
+                self.char_index_to_cursor(anchor).await,
+                self.char_index_to_cursor(active).await,
+            ));
+        }
+        self.set_selections(selections).await;
+        self.record_selections_after_edit();
+    }
+
+    /// Replaces the content with `text`, e.g. after the file changed on disk. Only the
+    /// lines that differ are rewritten, selections are carried over to where their lines
+    /// went, and the reload can be undone like an edit.
+    pub async fn reload(&mut self, text: &str) -> LineMap {
+        let old = self.text_model.get_text().await;
+        let map = LineMap::new(&old, text);
+        if map.is_identity() {
+            return map;
+        }
+        self.line_ending = LineEnding::detect(text);
+        self.indent_style = IndentStyle::detect(text);
+
+        let old_starts = line_starts(&old);
+        let new_starts = line_starts(text);
+        let new_chars: Vec<char> = text.chars().collect();
+        let mut edits = Vec::new();
+        let mut inserted_texts = Vec::new();
+        for (old_lines, new_lines) in map.changed_hunks() {
+            let start = old_starts[old_lines.start];
+            let end = old_starts[old_lines.end];
+            edits.push(ReplaceEdit {
+                start_char_idx: start,
+                replaced_text: self.text_model.get_text_range(start, end).await,
+            });
+            inserted_texts.push(
+                new_chars[new_starts[new_lines.start]..new_starts[new_lines.end]]
+                    .iter()
+                    .collect::<String>(),
+            );
+        }
+
+        let before_cursors = self.cursors.clone();
+        let before_selections = self.selections.clone();
+        self.begin_transaction();
+        for (edit, inserted) in edits.iter().zip(&inserted_texts).rev() {
+            let len = edit.replaced_text.chars().count();
+            if len == 0 {
+                self.text_model.insert(edit.start_char_idx, inserted).await;
+            } else if inserted.is_empty() {
+                self.text_model.remove(edit.start_char_idx, len).await;
+            } else {
+                self.text_model
+                    .replace(edit.start_char_idx, len, inserted)
+                    .await;
+            }
+        }
+
+        let mut selections = Vec::with_capacity(self.selections.len());
+        for selection in &self.selections {
+            let anchor = self.clamp_cursor(map.map_cursor(selection.anchor)).await;
+            let active = self.clamp_cursor(map.map_cursor(selection.active)).await;
+            selections.push(Selection::new(anchor, active));
+        }
+        self.set_selections(selections).await;
+        self.is_dirty = true;
 
-    pub async fn cursor_char_index(&self, cursor: Cursor) -> usize {
-        self.text_model.line_to_char(cursor.line).await + cursor.column
+        self.record_operation(UndoRecord::Insert {
+            edits,
+            inserted_texts,
+            before_cursors,
+            before_selections,
+            after_cursors: self.cursors.clone(),
+            after_selections: self.selections.clone(),
+            timestamp: self.clock.now(),
+        });
+        self.end_transaction();
+        map
     }
 
-    pub fn is_dirty(&self) -> bool {
-        self.is_dirty
+    pub(crate) async fn clamp_cursor(&self, cursor: Cursor) -> Cursor {
+        let last_line = self.text_model.line_count().await.saturating_sub(1);
+        let line = cursor.line.min(last_line);
+        let column = cursor.column.min(self.line_len_without_newline(line).await);
+        Cursor::new(line, column)
     }
 
-    pub fn mark_clean(&mut self) {
-        self.is_dirty = false;
+    /// Overwrite the entire buffer content
+    pub async fn set_text(&mut self, text: &str) {
+        self.text_model.set_text(text).await;
+        self.cursors = vec![Cursor::zero()];
+        self.selections = vec![Selection::single(Cursor::zero())];
+        self.is_dirty = true;
     }
 
-    pub async fn line_count(&self) -> usize {
-        self.text_model.line_count().await
+    /// Starts grouping edits into a single undo step, e.g. for format-on-save or applying
+    /// a patch. Transactions nest; only the outermost [`Buffer::end_transaction`] closes
+    /// the group.
+    pub fn begin_transaction(&mut self) {
+        self.transaction
+            .get_or_insert_with(Transaction::default)
+            .depth += 1;
     }
 
-    pub async fn get_line(&self, line_idx: usize) -> Option<String> {
-        self.text_model.get_line(line_idx).await
+    pub fn end_transaction(&mut self) {
+        let Some(transaction) = self.transaction.as_mut() else {
+            return;
+        };
+        transaction.depth -= 1;
+        if transaction.depth == 0 {
+            self.commit_transaction();
+        }
     }
 
-    pub async fn get_line_length(&self, line_idx: usize) -> Option<usize> {
-        self.text_model
-            .get_line(line_idx)
-            .await
-            .map(|line| line.chars().count())
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
     }
 
-    /// Replace a character range in the buffer with new text, returns new cursor index in chars.
-    pub async fn replace_range(
-        &mut self,
-        start_char_idx: usize,
-        len: usize,
-        new_text: &str,
-    ) -> usize {
-        self.text_model.replace(start_char_idx, len, new_text).await;
-        self.is_dirty = true;
-        // Return start + inserted length as a best-effort caret position.
-        start_char_idx + new_text.chars().count()
+    /// Makes redoing the latest edit restore the current selections, for edits that
+    /// place their carets after recording the text change.
+    fn record_selections_after_edit(&mut self) {
+        let last = match self.transaction.as_mut() {
+            Some(transaction) => transaction.records.last_mut(),
+            None => self.undo_stack.last_mut(),
+        };
+        if let Some(record) = last {
+            record.set_after(self.cursors.clone(), self.selections.clone());
+        }
     }
 
-    /// Overwrite the entire buffer content
-    pub async fn set_text(&mut self, text: &str) {
-        self.text_model.set_text(text).await;
-        self.cursors = vec![Cursor::zero()];
-        self.selections = vec![Selection::single(Cursor::zero())];
-        self.is_dirty = true;
+    fn commit_transaction(&mut self) {
+        let Some(transaction) = self.transaction.take() else {
+            return;
+        };
+        if !transaction.records.is_empty() {
+            self.push_undo_record_inner(UndoRecord::Group {
+                records: transaction.records,
+                timestamp: self.clock.now(),
+            });
+        }
     }
 
     pub async fn undo(&mut self) -> bool {
+        if self.read_only {
+            return false;
+        }
+        // An unfinished transaction is closed so that its edits are what gets undone.
+        self.commit_transaction();
         if let Some(record) = self.undo_stack.pop() {
             self.undo_stack_cost = self.undo_stack_cost.saturating_sub(record.cost());
             self.apply_undo(&record).await;
@@ -717,6 +2390,9 @@ impl Buffer {
     }
 
     pub async fn redo(&mut self) -> bool {
+        if self.read_only {
+            return false;
+        }
         if let Some(record) = self.redo_stack.pop() {
             self.apply_redo(&record).await;
             self.push_undo_record_inner(record);
@@ -727,6 +2403,18 @@ impl Buffer {
     }
 
     fn record_operation(&mut self, operation: UndoRecord) {
+        self.redo_stack.clear();
+        if let Some(transaction) = self.transaction.as_mut() {
+            let merged = transaction
+                .records
+                .last_mut()
+                .is_some_and(|last| last.try_merge(&operation));
+            if !merged {
+                transaction.records.push(operation);
+            }
+            return;
+        }
+
         let mut merged = false;
         if let Some(last) = self.undo_stack.last_mut() {
             if let Some(delta) = operation
@@ -751,7 +2439,14 @@ impl Buffer {
         } else {
             self.push_undo_record_inner(operation);
         }
-        self.redo_stack.clear();
+    }
+
+    /// The record the latest edit went into.
+    fn last_record_mut(&mut self) -> Option<&mut UndoRecord> {
+        match self.transaction.as_mut() {
+            Some(transaction) => transaction.records.last_mut(),
+            None => self.undo_stack.last_mut(),
+        }
     }
 
     fn push_undo_record_inner(&mut self, record: UndoRecord) {
@@ -784,7 +2479,9 @@ impl Buffer {
                     .cloned()
                     .zip(inserted_texts.iter().cloned())
                     .collect();
-                ordered.sort_by(|a, b| b.0.start_char_idx.cmp(&a.0.start_char_idx));
+                // Edits are stored in pre-edit positions; undoing front to back keeps the
+                // later ones at their recorded offsets.
+                ordered.sort_by_key(|(edit, _)| edit.start_char_idx);
                 for (edit, inserted) in ordered {
                     let inserted_len = inserted.chars().count();
                     if inserted_len > 0 {
@@ -809,7 +2506,7 @@ impl Buffer {
                 ..
             } => {
                 let mut ordered = edits.clone();
-                ordered.sort_by(|a, b| a.start_char_idx.cmp(&b.start_char_idx));
+                ordered.sort_by_key(|edit| edit.start_char_idx);
                 for edit in ordered {
                     self.text_model
                         .insert(edit.start_char_idx, &edit.deleted_text)
@@ -819,6 +2516,15 @@ impl Buffer {
                 self.selections = before_selections.clone();
                 self.is_dirty = true;
             }
+            UndoRecord::Group { records, .. } => {
+                for record in records.iter().rev() {
+                    Box::pin(self.apply_undo(record)).await;
+                }
+            }
+            UndoRecord::LineEnding { edit, before, .. } => {
+                Box::pin(self.apply_undo(edit)).await;
+                self.line_ending = *before;
+            }
         }
     }
 
@@ -836,8 +2542,9 @@ impl Buffer {
                     .cloned()
                     .zip(inserted_texts.iter().cloned())
                     .collect();
-                ordered.sort_by(|a, b| b.0.start_char_idx.cmp(&a.0.start_char_idx));
-                for (edit, inserted) in ordered {
+                // Back to front; text inserted at one place goes back in its original order.
+                ordered.sort_by_key(|(edit, _)| edit.start_char_idx);
+                for (edit, inserted) in ordered.into_iter().rev() {
                     if !edit.replaced_text.is_empty() {
                         let len = edit.replaced_text.chars().count();
                         self.text_model.remove(edit.start_char_idx, len).await;
@@ -858,8 +2565,40 @@ impl Buffer {
                 self.cursors = after_cursors.clone();
                 self.selections = after_selections.clone();
             }
+            UndoRecord::Group { records, .. } => {
+                for record in records {
+                    Box::pin(self.apply_redo(record)).await;
+                }
+            }
+            UndoRecord::LineEnding { edit, after, .. } => {
+                Box::pin(self.apply_redo(edit)).await;
+                self.line_ending = *after;
+            }
+        }
+    }
+}
+
+/// Char offset of each `\n`-separated line, plus the total length, matching how
+/// [`LineMap`] splits lines.
+fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut offset = 0;
+    for ch in text.chars() {
+        offset += 1;
+        if ch == '\n' {
+            starts.push(offset);
         }
     }
+    if starts.last() != Some(&offset) {
+        starts.push(offset);
+    }
+    starts
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -916,7 +2655,7 @@ mod tests {
             assert_eq!(text, "aXbcXd");
 
             assert_eq!(buffer.cursors[0], Cursor::new(0, 2));
-            assert_eq!(buffer.cursors[1], Cursor::new(0, 4));
+            assert_eq!(buffer.cursors[1], Cursor::new(0, 5));
             assert_eq!(buffer.cursors[2], untouched_cursor);
         });
     }
@@ -939,7 +2678,7 @@ mod tests {
             buffer.delete_backward().await;
 
             let text = buffer.get_text().await;
-            assert_eq!(text, "abf");
+            assert_eq!(text, "acf");
 
             assert_eq!(buffer.cursors[0], Cursor::new(0, 1));
             assert_eq!(buffer.cursors[1], Cursor::new(0, 2));
@@ -967,7 +2706,7 @@ mod tests {
             assert_eq!(text, "acef");
 
             assert_eq!(buffer.cursors[0], Cursor::new(0, 1));
-            assert_eq!(buffer.cursors[1], Cursor::new(0, 3));
+            assert_eq!(buffer.cursors[1], Cursor::new(0, 2));
         });
     }
 
@@ -1071,6 +2810,7 @@ mod tests {
     fn sequential_backspaces_coalesce() {
         run_async(async {
             let mut buffer = Buffer::from_text("abc");
+            buffer.set_cursor(Cursor::new(0, 3)).await;
             buffer.delete_backward().await;
             buffer.delete_backward().await;
             buffer.delete_backward().await;
@@ -1080,10 +2820,774 @@ mod tests {
             assert_eq!(buffer.get_text().await, "abc");
         });
     }
-}
 
-impl Default for Buffer {
-    fn default() -> Self {
-        Self::new()
+    #[test]
+    fn line_operations_apply_to_every_selection_and_undo() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("a\nb\nc\nd\ne");
+            buffer.selections = vec![
+                Selection::single(Cursor::new(1, 1)),
+                Selection::range(Cursor::new(3, 0), Cursor::new(4, 0)),
+            ];
+            buffer.cursors = vec![Cursor::new(1, 1), Cursor::new(4, 0)];
+
+            buffer.move_lines(LineDirection::Up).await;
+            assert_eq!(buffer.get_text().await, "b\na\nd\nc\ne");
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(0, 1), Cursor::new(3, 0)]
+            );
+
+            buffer.move_lines(LineDirection::Up).await;
+            assert_eq!(buffer.get_text().await, "b\na\nd\nc\ne");
+
+            buffer.move_lines(LineDirection::Down).await;
+            assert_eq!(buffer.get_text().await, "a\nb\nc\nd\ne");
+
+            buffer.duplicate_lines().await;
+            assert_eq!(buffer.get_text().await, "a\nb\nb\nc\nd\nd\ne");
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(2, 1), Cursor::new(6, 0)]
+            );
+
+            buffer.delete_lines().await;
+            assert_eq!(buffer.get_text().await, "a\nb\nc\nd\ne");
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(2, 1), Cursor::new(4, 0)]
+            );
+
+            assert!(buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "a\nb\nb\nc\nd\nd\ne");
+
+            buffer.set_cursor(Cursor::new(6, 0)).await;
+            buffer.delete_lines().await;
+            assert_eq!(buffer.get_text().await, "a\nb\nb\nc\nd\nd");
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(5, 0)]);
+        });
+    }
+
+    #[test]
+    fn line_operations_follow_every_line_break_of_the_rope() {
+        run_async(async {
+            // The rope also breaks lines on a lone `\r` and on U+2028.
+            let mut buffer = Buffer::from_text("a\u{2028}b\nc\rd");
+            assert_eq!(buffer.line_count().await, 4);
+
+            buffer.set_cursor(Cursor::new(1, 0)).await;
+            buffer.duplicate_lines().await;
+            assert_eq!(buffer.get_text().await, "a\u{2028}b\nb\nc\rd");
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(2, 0)]);
+
+            buffer.move_lines(LineDirection::Down).await;
+            assert_eq!(buffer.get_text().await, "a\u{2028}b\nc\nb\rd");
+            buffer.move_lines(LineDirection::Down).await;
+            assert_eq!(buffer.get_text().await, "a\u{2028}b\nc\nd\nb");
+
+            buffer.set_cursor(Cursor::new(0, 1)).await;
+            buffer.delete_lines().await;
+            assert_eq!(buffer.get_text().await, "b\nc\nd\nb");
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(0, 1)]);
+
+            let mut buffer = Buffer::from_text("a\rb\u{2028}");
+            buffer.set_cursor(Cursor::new(2, 0)).await;
+            buffer.delete_lines().await;
+            assert_eq!(buffer.get_text().await, "a\rb");
+        });
+    }
+
+    #[test]
+    fn typed_brackets_are_paired_skipped_and_matched() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("");
+            buffer.insert_char('f').await;
+            buffer.insert_char('(').await;
+            buffer.insert_char('[').await;
+            assert_eq!(buffer.get_text().await, "f([])");
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(0, 3)]);
+
+            buffer.insert_char(']').await;
+            buffer.insert_char(')').await;
+            assert_eq!(buffer.get_text().await, "f([])");
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(0, 5)]);
+            assert_eq!(
+                buffer.matching_bracket(Cursor::new(0, 5)).await,
+                Some((Cursor::new(0, 1), Cursor::new(0, 4)))
+            );
+            assert_eq!(buffer.matching_bracket(Cursor::new(0, 0)).await, None);
+
+            buffer.insert_char(' ').await;
+            buffer.insert_char('"').await;
+            assert_eq!(buffer.get_text().await, "f([]) \"\"");
+            buffer.insert_char('a').await;
+            buffer.insert_char('\'').await;
+            assert_eq!(buffer.get_text().await, "f([]) \"a'\"");
+
+            buffer.set_cursor(Cursor::new(0, 0)).await;
+            buffer.insert_char('(').await;
+            assert_eq!(buffer.get_text().await, "(f([]) \"a'\"");
+
+            assert!(buffer.undo().await);
+            assert!(buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "f([]) \"\"");
+        });
+    }
+
+    #[test]
+    fn cursors_can_be_added_by_line_occurrence_and_split() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("let a = a;\nlet b;\nlet a2 = a;");
+            buffer.set_cursor(Cursor::new(0, 4)).await;
+
+            assert!(buffer.add_next_occurrence().await);
+            assert_eq!(
+                buffer.get_selections(),
+                &[Selection::new(Cursor::new(0, 4), Cursor::new(0, 5))]
+            );
+            assert!(buffer.add_next_occurrence().await);
+            assert!(buffer.add_next_occurrence().await);
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(0, 5), Cursor::new(0, 9), Cursor::new(2, 5)]
+            );
+            buffer.insert_text_at_cursor("x").await;
+            assert_eq!(buffer.get_text().await, "let x = x;\nlet b;\nlet x2 = a;");
+
+            buffer.set_cursor(Cursor::new(1, 6)).await;
+            buffer.add_cursors_vertically(LineDirection::Down).await;
+            buffer.add_cursors_vertically(LineDirection::Up).await;
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(1, 6), Cursor::new(2, 6), Cursor::new(0, 6)]
+            );
+
+            buffer
+                .set_selection(Selection::new(Cursor::new(0, 4), Cursor::new(2, 0)))
+                .await;
+            buffer.split_selection_into_lines().await;
+            assert_eq!(
+                buffer.get_selections(),
+                &[
+                    Selection::new(Cursor::new(0, 4), Cursor::new(0, 10)),
+                    Selection::new(Cursor::new(1, 0), Cursor::new(1, 6)),
+                ]
+            );
+
+            // The only other `aa` overlaps the selection.
+            let mut buffer = Buffer::from_text("aaa");
+            buffer
+                .set_selection(Selection::new(Cursor::new(0, 1), Cursor::new(0, 3)))
+                .await;
+            assert!(!buffer.add_next_occurrence().await);
+        });
+    }
+
+    #[test]
+    fn transactions_undo_and_redo_as_one_step() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("fn main() {}\n");
+            buffer.set_cursor(Cursor::new(0, 11)).await;
+            buffer.insert_text_at_cursor("x").await;
+
+            buffer.begin_transaction();
+            buffer.replace_range(0, 2, "pub fn").await;
+            buffer.begin_transaction();
+            buffer.set_cursor(Cursor::new(1, 0)).await;
+            buffer.insert_text_at_cursor("// end\n").await;
+            buffer.end_transaction();
+            buffer.delete_backward().await;
+            assert!(buffer.in_transaction());
+            buffer.end_transaction();
+            assert!(!buffer.in_transaction());
+            assert_eq!(buffer.get_text().await, "pub fn main() {x}\n// end");
+
+            assert!(buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "fn main() {x}\n");
+            assert!(buffer.redo().await);
+            assert_eq!(buffer.get_text().await, "pub fn main() {x}\n// end");
+            assert!(buffer.undo().await);
+            assert!(buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "fn main() {}\n");
+        });
+    }
+
+    #[test]
+    fn crlf_is_kept_when_editing_and_converted_as_one_undo_step() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("a\r\nb\r\n");
+            assert_eq!(buffer.line_ending(), LineEnding::CrLf);
+
+            buffer.set_cursor(Cursor::new(0, 1)).await;
+            buffer.insert_line_break().await;
+            buffer.insert_text_at_cursor("x\ny").await;
+            assert_eq!(buffer.get_text().await, "a\r\nx\r\ny\r\nb\r\n");
+
+            buffer.set_cursor(Cursor::new(3, 0)).await;
+            buffer.delete_backward().await;
+            assert_eq!(buffer.get_text().await, "a\r\nx\r\nyb\r\n");
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(2, 1)]);
+
+            assert!(buffer.set_line_ending(LineEnding::Lf).await);
+            assert_eq!(buffer.get_text().await, "a\nx\nyb\n");
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(2, 1)]);
+            assert!(!buffer.set_line_ending(LineEnding::Lf).await);
+
+            assert!(buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "a\r\nx\r\nyb\r\n");
+            assert_eq!(buffer.line_ending(), LineEnding::CrLf);
+            assert!(buffer.redo().await);
+            assert_eq!(buffer.line_ending(), LineEnding::Lf);
+        });
+    }
+
+    #[test]
+    fn reload_keeps_selections_on_their_lines_and_can_be_undone() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("fn a() {}\nfn b() {}\nfn c() {}\n");
+            buffer
+                .set_selections(vec![
+                    Selection::new(Cursor::new(1, 3), Cursor::new(1, 4)),
+                    Selection::single(Cursor::new(2, 9)),
+                ])
+                .await;
+
+            let map = buffer
+                .reload("// header\nfn a() {}\nfn b() {}\nfn c2() {}\n")
+                .await;
+            assert_eq!(map.map_line(0), 1);
+            assert_eq!(
+                buffer.get_text().await,
+                "// header\nfn a() {}\nfn b() {}\nfn c2() {}\n"
+            );
+            assert_eq!(
+                buffer.get_selections(),
+                &[
+                    Selection::new(Cursor::new(2, 3), Cursor::new(2, 4)),
+                    Selection::single(Cursor::new(3, 0)),
+                ]
+            );
+
+            assert!(buffer
+                .reload("// header\nfn a() {}\nfn b() {}\nfn c2() {}\n")
+                .await
+                .is_identity());
+            assert!(buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "fn a() {}\nfn b() {}\nfn c() {}\n");
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(1, 4), Cursor::new(2, 9)]
+            );
+        });
+    }
+
+    #[test]
+    fn home_toggles_between_indent_and_line_start() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("fn a() {\n    let x = 1;\n}\n\tdone");
+            buffer.set_cursor(Cursor::new(1, 9)).await;
+            let presses = [
+                (CursorMovement::Home, Cursor::new(1, 4)),
+                (CursorMovement::Home, Cursor::new(1, 0)),
+                (CursorMovement::Home, Cursor::new(1, 4)),
+                (CursorMovement::PageDown, Cursor::new(3, 4)),
+                (CursorMovement::PageUp, Cursor::new(1, 4)),
+                (CursorMovement::PageUp, Cursor::new(0, 4)),
+                (CursorMovement::DocumentEnd, Cursor::new(3, 5)),
+                (CursorMovement::Home, Cursor::new(3, 1)),
+                (CursorMovement::DocumentStart, Cursor::zero()),
+            ];
+            for (movement, expected) in presses {
+                buffer.move_selections(movement, false, None, 2).await;
+                assert_eq!(buffer.get_cursors(), [expected], "{:?}", movement);
+            }
+        });
+    }
+
+    #[test]
+    fn navigates_back_and_forward_through_jumps() {
+        run_async(async {
+            let text: String = (0..50).map(|n| format!("line {}\n", n)).collect();
+            let mut buffer = Buffer::from_text(&text);
+            buffer.set_cursor(Cursor::new(30, 2)).await;
+            // Moving by a line is not a jump.
+            buffer
+                .move_selections(CursorMovement::Down, false, None, 1)
+                .await;
+            buffer.set_cursor(Cursor::new(5, 0)).await;
+
+            assert!(buffer.navigate_selections(false).await);
+            assert_eq!(buffer.get_cursors(), [Cursor::new(31, 2)]);
+            assert!(buffer.navigate_selections(false).await);
+            assert_eq!(buffer.get_cursors(), [Cursor::zero()]);
+            assert!(!buffer.navigate_selections(false).await);
+
+            // Places in text that is gone are moved back inside it.
+            buffer.set_text("short").await;
+            assert!(buffer.navigate_selections(true).await);
+            assert_eq!(buffer.get_cursors(), [Cursor::new(0, 2)]);
+            assert!(buffer.jumps().can_go_forward());
+        });
+    }
+
+    #[test]
+    fn duplicated_buffers_share_text_and_notify_each_other() {
+        run_async(async {
+            let mut first = Buffer::from_text("hello");
+            let second = first.duplicate();
+            let mut changes = second.watch_version();
+
+            first.set_cursor(Cursor::new(0, 5)).await;
+            first.insert_text_at_cursor("!").await;
+
+            assert_eq!(second.get_text().await, "hello!");
+            assert!(changes.has_changed().unwrap());
+            assert_eq!(*changes.borrow_and_update(), second.version());
+        });
+    }
+
+    #[test]
+    fn subscribers_receive_versioned_edits() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("ab\ncd");
+            let mut changes = buffer.subscribe();
+            let version = buffer.version();
+
+            buffer.set_cursor(Cursor::new(1, 1)).await;
+            buffer.insert_text_at_cursor("X").await;
+            buffer.delete_backward().await;
+
+            let insert = changes.recv().await.unwrap();
+            assert_eq!(insert.before_version, version);
+            assert_eq!(insert.after_version, version + 1);
+            assert_eq!(insert.start, Cursor::new(1, 1));
+            assert_eq!(insert.new_text(), "X");
+
+            let delete = changes.recv().await.unwrap();
+            assert_eq!(delete.before_version, insert.after_version);
+            assert_eq!(delete.start, Cursor::new(1, 1));
+            assert_eq!(delete.old_end, Cursor::new(1, 2));
+            assert_eq!(delete.old_text(), "X");
+            assert_eq!(delete.after_version, buffer.version());
+        });
+    }
+
+    #[test]
+    fn changes_carry_utf16_positions_for_non_ascii_lines() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("😀é\n");
+            let mut changes = buffer.subscribe();
+
+            buffer.set_cursor(Cursor::new(0, 2)).await;
+            buffer.insert_text_at_cursor("x").await;
+
+            let insert = changes.recv().await.unwrap();
+            assert_eq!(insert.start, Cursor::new(0, 2));
+            assert_eq!(insert.utf16_start, Cursor::new(0, 3));
+            assert_eq!(insert.utf16_old_end, Cursor::new(0, 3));
+        });
+    }
+
+    #[test]
+    fn marks_move_with_edits_and_are_shared_by_duplicates() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("fn a() {}\nfn b() {}\nfn c() {}\n");
+            let todo = MarkName::Named("todo".to_string());
+            buffer
+                .set_mark(MarkName::Number(1), Cursor::new(1, 0))
+                .await;
+            buffer.set_mark(todo.clone(), Cursor::new(2, 3)).await;
+            assert_eq!(buffer.free_mark_number(), Some(2));
+
+            buffer.set_cursor(Cursor::new(0, 0)).await;
+            buffer.insert_text_at_cursor("// header\n").await;
+            assert_eq!(
+                buffer.mark(&MarkName::Number(1)).await,
+                Some(Cursor::new(2, 0))
+            );
+
+            buffer.set_cursor(Cursor::new(1, 0)).await;
+            buffer.delete_lines().await;
+            assert_eq!(
+                buffer.marks().await,
+                vec![
+                    (MarkName::Number(1), Cursor::new(1, 0)),
+                    (todo.clone(), Cursor::new(2, 3)),
+                ]
+            );
+
+            let duplicate = buffer.duplicate();
+            assert_eq!(
+                duplicate.next_mark(Cursor::new(2, 3), true).await,
+                Some((MarkName::Number(1), Cursor::new(1, 0)))
+            );
+            assert_eq!(
+                duplicate.next_mark(Cursor::new(2, 0), false).await,
+                Some((MarkName::Number(1), Cursor::new(1, 0)))
+            );
+            assert!(buffer.remove_mark(&todo));
+            assert_eq!(duplicate.marks().await.len(), 1);
+        });
+    }
+
+    #[test]
+    fn replacing_ranges_keeps_carets_in_place() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("fn a() {\n    x\n    }");
+            buffer
+                .set_selections(vec![
+                    Selection::single(Cursor::new(1, 5)),
+                    Selection::single(Cursor::new(2, 5)),
+                ])
+                .await;
+            // Reindent the closing brace, insert at the first caret and append at the end.
+            buffer
+                .replace_ranges(&[
+                    (15..19, String::new()),
+                    (14..14, ";".to_string()),
+                    (20..20, "\n".to_string()),
+                ])
+                .await;
+
+            assert_eq!(buffer.get_text().await, "fn a() {\n    x;\n}\n");
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(1, 5), Cursor::new(2, 1)]
+            );
+            buffer.undo().await;
+            assert_eq!(buffer.get_text().await, "fn a() {\n    x\n    }");
+        });
+    }
+
+    #[test]
+    fn replacing_all_matches_inside_the_selection() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("a1 a2\na3 a4\n");
+            let regex = crate::SearchOptions {
+                regex: true,
+                ..Default::default()
+            };
+            let scope = [Cursor::new(0, 3)..Cursor::new(1, 2)];
+            let found = buffer.search_in(r"a(\d)", regex, &scope).await.unwrap();
+            assert_eq!(found.len(), 2);
+
+            let replaced = buffer
+                .replace_all(r"a(\d)", regex, "b$1", Some(&scope))
+                .await
+                .unwrap();
+            assert_eq!(replaced, 2);
+            assert_eq!(buffer.get_text().await, "a1 b2\nb3 a4\n");
+            buffer.undo().await;
+            assert_eq!(buffer.get_text().await, "a1 a2\na3 a4\n");
+
+            let mut buffer = Buffer::from_text("item Item ITEMS items_count\n");
+            let words = crate::SearchOptions {
+                whole_word: true,
+                preserve_case: true,
+                ..Default::default()
+            };
+            let replaced = buffer.replace_all("item", words, "entry", None).await;
+            assert_eq!(replaced.unwrap(), 2);
+            assert_eq!(buffer.get_text().await, "entry Entry ITEMS items_count\n");
+
+            // Whole words that start or end with punctuation only need a boundary
+            // beside their word chars.
+            let mut buffer = Buffer::from_text("foo() xfoo() foo()x a+b a+bc\n");
+            let words = crate::SearchOptions {
+                whole_word: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                buffer
+                    .replace_all("foo()", words, "bar()", None)
+                    .await
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                buffer.replace_all("a+b", words, "c", None).await.unwrap(),
+                1
+            );
+            assert_eq!(buffer.get_text().await, "bar() xfoo() bar()x c a+bc\n");
+            let regex_words = crate::SearchOptions {
+                regex: true,
+                ..words
+            };
+            let replaced = buffer.replace_all(r"o+\(\)", regex_words, "[]", None).await;
+            assert_eq!(replaced.unwrap(), 0);
+            let replaced = buffer
+                .replace_all(r"(\w+)\(\)", regex_words, "$1[]", None)
+                .await;
+            assert_eq!(replaced.unwrap(), 3);
+            assert_eq!(buffer.get_text().await, "bar[] xfoo[] bar[]x c a+bc\n");
+        });
+    }
+
+    #[test]
+    fn text_between_cursors_in_either_order() {
+        run_async(async {
+            let buffer = Buffer::from_text("first\nsecond\n");
+            assert_eq!(
+                buffer
+                    .get_text_range(Cursor::new(0, 3), Cursor::new(1, 2))
+                    .await,
+                "st\nse"
+            );
+            assert_eq!(
+                buffer
+                    .get_text_range(Cursor::new(1, 99), Cursor::new(1, 4))
+                    .await,
+                "nd"
+            );
+            let selection = Selection::new(Cursor::new(1, 6), Cursor::new(1, 0));
+            assert_eq!(buffer.get_text_in_selection(&selection).await, "second");
+            let caret = Selection::single(Cursor::new(0, 1));
+            assert_eq!(buffer.get_text_in_selection(&caret).await, "");
+        });
+    }
+
+    #[test]
+    fn line_comments_toggle_on_every_selected_line() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("fn a() {\n    x();\n\n  y();\n}\n");
+            buffer
+                .set_selection(Selection::range(Cursor::new(1, 2), Cursor::new(3, 1)))
+                .await;
+            assert!(buffer.toggle_line_comment("//").await);
+            assert_eq!(
+                buffer.get_text().await,
+                "fn a() {\n  //   x();\n\n  // y();\n}\n"
+            );
+
+            assert!(buffer.toggle_line_comment("//").await);
+            assert_eq!(buffer.get_text().await, "fn a() {\n    x();\n\n  y();\n}\n");
+            buffer.undo().await;
+            assert_eq!(
+                buffer.get_text().await,
+                "fn a() {\n  //   x();\n\n  // y();\n}\n"
+            );
+
+            buffer.set_cursor(Cursor::new(2, 0)).await;
+            assert!(!buffer.toggle_line_comment("//").await);
+        });
+    }
+
+    #[test]
+    fn line_breaks_follow_the_detected_indent() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("fn a() {\n  let x = ();\n}");
+            let style = buffer.indent_style().unwrap();
+            assert_eq!(style, IndentStyle::Spaces(2));
+
+            buffer.set_cursor(Cursor::new(1, 13)).await;
+            buffer.insert_line_break_and_indent(style).await;
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(2, 2)]);
+            assert_eq!(buffer.get_text().await, "fn a() {\n  let x = ();\n  \n}");
+            buffer.undo().await;
+
+            buffer.set_cursor(Cursor::new(1, 11)).await;
+            buffer.insert_line_break_and_indent(style).await;
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(2, 4)]);
+            assert_eq!(
+                buffer.get_text().await,
+                "fn a() {\n  let x = (\n    \n  );\n}"
+            );
+
+            buffer.set_cursor(Cursor::new(2, 4)).await;
+            buffer.insert_tab(style).await;
+            assert_eq!(buffer.get_cursors(), &[Cursor::new(2, 6)]);
+        });
+    }
+
+    #[test]
+    fn read_only_buffers_ignore_edits() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("one\n");
+            buffer.set_cursor(Cursor::new(0, 3)).await;
+            buffer.insert_text_at_cursor("!").await;
+            buffer.set_read_only(true);
+
+            buffer.insert_text_at_cursor("?").await;
+            buffer.delete_backward().await;
+            buffer.replace_ranges(&[(0..3, "two".to_string())]).await;
+            assert!(!buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "one!\n");
+
+            buffer.set_read_only(false);
+            assert!(buffer.undo().await);
+            assert_eq!(buffer.get_text().await, "one\n");
+        });
+    }
+
+    #[test]
+    fn selection_setters_clamp_into_the_text() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("one\r\ntwo three\n");
+            assert_eq!(Cursor::new(0, 9).clamp_to(&buffer).await, Cursor::new(0, 3));
+
+            buffer.set_cursor(Cursor::new(7, 40)).await;
+            assert_eq!(buffer.get_cursors(), [Cursor::new(2, 0)]);
+
+            buffer
+                .set_selection(Selection::new(Cursor::new(0, 8), Cursor::new(1, 99)))
+                .await;
+            assert_eq!(
+                buffer.get_selections(),
+                [Selection::new(Cursor::new(0, 3), Cursor::new(1, 9))]
+            );
+
+            // Carets that land on the same spot once clamped are merged.
+            buffer
+                .set_selections(vec![
+                    Selection::single(Cursor::new(1, 20)),
+                    Selection::single(Cursor::new(1, 30)),
+                ])
+                .await;
+            assert_eq!(buffer.get_cursors(), [Cursor::new(1, 9)]);
+            buffer.add_cursor(Cursor::new(0, 12)).await;
+            buffer.add_cursor(Cursor::new(1, 50)).await;
+            assert_eq!(buffer.get_cursors(), [Cursor::new(1, 9), Cursor::new(0, 3)]);
+            buffer.insert_text_at_cursor("!").await;
+            assert_eq!(buffer.get_text().await, "one!\r\ntwo three!\n");
+        });
+    }
+
+    #[test]
+    fn text_objects_under_the_cursor() {
+        run_async(async {
+            let buffer = Buffer::from_text("fn main() {\n    let foo_bar = 1;\n}\n\nlast");
+            assert_eq!(
+                buffer.word_at(Cursor::new(1, 11)).await,
+                Some(Cursor::new(1, 8)..Cursor::new(1, 15))
+            );
+            // A caret just after a word still touches it; whitespace doesn't.
+            assert_eq!(
+                buffer.word_at(Cursor::new(0, 7)).await,
+                Some(Cursor::new(0, 3)..Cursor::new(0, 7))
+            );
+            assert_eq!(buffer.word_at(Cursor::new(1, 2)).await, None);
+
+            assert_eq!(
+                buffer.line_range_at(Cursor::new(1, 3)).await,
+                Cursor::new(1, 0)..Cursor::new(2, 0)
+            );
+            assert_eq!(
+                buffer.line_range_at(Cursor::new(9, 0)).await,
+                Cursor::new(4, 0)..Cursor::new(4, 4)
+            );
+
+            assert_eq!(
+                buffer.paragraph_at(Cursor::new(1, 0)).await,
+                Some(Cursor::new(0, 0)..Cursor::new(3, 0))
+            );
+            assert_eq!(buffer.paragraph_at(Cursor::new(3, 0)).await, None);
+            assert_eq!(
+                buffer.paragraph_at(Cursor::new(4, 1)).await,
+                Some(Cursor::new(4, 0)..Cursor::new(4, 4))
+            );
+        });
+    }
+
+    #[test]
+    fn case_transforms_cover_selections_and_words_under_carets() {
+        run_async(async {
+            let mut buffer = Buffer::from_text(
+                "let fooBar = 1;
+http_server_name",
+            );
+            buffer
+                .set_selections(vec![
+                    Selection::single(Cursor::new(0, 5)),
+                    Selection::single(Cursor::new(0, 7)),
+                    Selection::range(Cursor::new(1, 0), Cursor::new(1, 16)),
+                ])
+                .await;
+            assert!(buffer.transform_case(CaseTransform::Snake).await);
+            assert_eq!(
+                buffer.get_text().await,
+                "let foo_bar = 1;
+http_server_name"
+            );
+            assert!(buffer.transform_case(CaseTransform::Pascal).await);
+            assert_eq!(
+                buffer.get_text().await,
+                "let FooBar = 1;
+HttpServerName"
+            );
+            assert_eq!(
+                buffer.get_selections()[2],
+                Selection::range(Cursor::new(1, 0), Cursor::new(1, 14))
+            );
+
+            buffer
+                .set_selection(Selection::single(Cursor::new(0, 14)))
+                .await;
+            assert!(!buffer.transform_case(CaseTransform::Upper).await);
+
+            buffer.undo().await;
+            assert_eq!(
+                buffer.get_text().await,
+                "let foo_bar = 1;
+http_server_name"
+            );
+            buffer.undo().await;
+            assert_eq!(
+                buffer.get_text().await,
+                "let fooBar = 1;
+http_server_name"
+            );
+        });
+    }
+
+    #[test]
+    fn argument_layout_is_one_undo_step() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("f(\"é\", b);");
+            buffer.set_cursor(Cursor::new(0, 8)).await;
+            let layout = crate::ArgumentLayout::Split;
+            assert!(
+                buffer
+                    .layout_arguments("rust", layout, IndentStyle::Spaces(2))
+                    .await
+            );
+            assert_eq!(buffer.get_text().await, "f(\n  \"é\",\n  b\n);");
+            assert!(
+                !buffer
+                    .layout_arguments("rust", layout, IndentStyle::Spaces(2))
+                    .await
+            );
+            buffer.undo().await;
+            assert_eq!(buffer.get_text().await, "f(\"é\", b);");
+        });
+    }
+
+    #[test]
+    fn paste_spreads_pieces_over_matching_selections() {
+        run_async(async {
+            let mut buffer = Buffer::from_text("one two\nthree");
+            buffer
+                .set_selections(vec![
+                    Selection::range(Cursor::new(0, 0), Cursor::new(0, 3)),
+                    Selection::range(Cursor::new(1, 0), Cursor::new(1, 5)),
+                ])
+                .await;
+            let cut = buffer.cut_selections().await;
+            assert_eq!(cut, ["one", "three"]);
+            assert_eq!(buffer.get_text().await, " two\n");
+
+            buffer.paste(&["1".to_string(), "3".to_string()]).await;
+            assert_eq!(buffer.get_text().await, "1 two\n3");
+            buffer.paste(&["x".to_string()]).await;
+            assert_eq!(buffer.get_text().await, "1x two\n3x");
+            assert_eq!(
+                buffer.get_cursors(),
+                &[Cursor::new(0, 2), Cursor::new(1, 2)]
+            );
+
+            buffer.undo().await;
+            buffer.undo().await;
+            buffer.undo().await;
+            assert_eq!(buffer.get_text().await, "one two\nthree");
+        });
     }
 }
@@ -0,0 +1,210 @@
+/// Case and identifier-style conversions for selected text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaseTransform {
+    Upper,
+    Lower,
+    Title,
+    Snake,
+    Camel,
+    Pascal,
+    Kebab,
+}
+
+impl CaseTransform {
+    pub const ALL: [CaseTransform; 7] = [
+        CaseTransform::Upper,
+        CaseTransform::Lower,
+        CaseTransform::Title,
+        CaseTransform::Snake,
+        CaseTransform::Camel,
+        CaseTransform::Pascal,
+        CaseTransform::Kebab,
+    ];
+
+    /// The name written in the style itself, for pickers.
+    pub fn label(self) -> &'static str {
+        match self {
+            CaseTransform::Upper => "UPPERCASE",
+            CaseTransform::Lower => "lowercase",
+            CaseTransform::Title => "Title Case",
+            CaseTransform::Snake => "snake_case",
+            CaseTransform::Camel => "camelCase",
+            CaseTransform::Pascal => "PascalCase",
+            CaseTransform::Kebab => "kebab-case",
+        }
+    }
+
+    /// Converts `text`. The identifier styles rewrite every identifier in it and keep
+    /// the text between identifiers, so `foo_bar(baz_qux)` becomes `fooBar(bazQux)`.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            CaseTransform::Upper => text.to_uppercase(),
+            CaseTransform::Lower => text.to_lowercase(),
+            CaseTransform::Title => title_case(text),
+            CaseTransform::Snake => map_identifiers(text, |words| join_lower(words, "_")),
+            CaseTransform::Kebab => map_identifiers(text, |words| join_lower(words, "-")),
+            CaseTransform::Camel => map_identifiers(text, |words| {
+                let mut out = words.first().map(|w| w.to_lowercase()).unwrap_or_default();
+                for word in words.iter().skip(1) {
+                    out.push_str(&capitalize(word));
+                }
+                out
+            }),
+            CaseTransform::Pascal => map_identifiers(text, |words| {
+                words.iter().map(|word| capitalize(word)).collect()
+            }),
+        }
+    }
+}
+
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut at_word_start = true;
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            at_word_start = true;
+            out.push(ch);
+        } else if at_word_start {
+            at_word_start = false;
+            out.extend(ch.to_uppercase());
+        } else {
+            out.extend(ch.to_lowercase());
+        }
+    }
+    out
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+fn join_lower(words: &[&str], separator: &str) -> String {
+    let words: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+    words.join(separator)
+}
+
+/// Rewrites each identifier in `text` from its words. An identifier is a run of
+/// letters, digits, `_` and `-` between letters or digits; leading and trailing
+/// underscores are kept, as in `__init__`.
+fn map_identifiers(text: &str, join: impl Fn(&[&str]) -> String) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let is_ident = |idx: usize| {
+        let ch = chars[idx];
+        ch.is_alphanumeric()
+            || ch == '_'
+            || (ch == '-'
+                && idx > 0
+                && chars[idx - 1].is_alphanumeric()
+                && chars
+                    .get(idx + 1)
+                    .is_some_and(|next| next.is_alphanumeric()))
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut idx = 0;
+    while idx < chars.len() {
+        if !is_ident(idx) {
+            out.push(chars[idx]);
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        while idx < chars.len() && is_ident(idx) {
+            idx += 1;
+        }
+        let ident: String = chars[start..idx].iter().collect();
+        let core = ident.trim_matches('_');
+        if core.is_empty() {
+            out.push_str(&ident);
+            continue;
+        }
+        let prefix = &ident[..ident.len() - ident.trim_start_matches('_').len()];
+        let suffix = &ident[ident.trim_end_matches('_').len()..];
+        out.push_str(prefix);
+        out.push_str(&join(&split_words(core)));
+        out.push_str(suffix);
+    }
+    out
+}
+
+/// Splits an identifier into words at `_`, `-` and case changes. A run of capitals
+/// is one word, so `HTTPServer` is `HTTP` and `Server`; digits stay with the word
+/// before them.
+fn split_words(ident: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for part in ident.split(['_', '-']).filter(|part| !part.is_empty()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (byte_idx, ch) = chars[i];
+            let prev = chars[i - 1].1;
+            let next_is_lower = chars
+                .get(i + 1)
+                .is_some_and(|(_, next)| next.is_lowercase());
+            let boundary = ch.is_uppercase()
+                && (prev.is_lowercase()
+                    || prev.is_numeric()
+                    || (prev.is_uppercase() && next_is_lower));
+            if boundary {
+                words.push(&part[start..byte_idx]);
+                start = byte_idx;
+            }
+        }
+        words.push(&part[start..]);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_identifier_styles() {
+        let cases = [
+            (
+                "parseHTTPResponse",
+                CaseTransform::Snake,
+                "parse_http_response",
+            ),
+            (
+                "parse_http_response",
+                CaseTransform::Camel,
+                "parseHttpResponse",
+            ),
+            (
+                "parse-http-response",
+                CaseTransform::Pascal,
+                "ParseHttpResponse",
+            ),
+            (
+                "ParseHttpResponse",
+                CaseTransform::Kebab,
+                "parse-http-response",
+            ),
+            ("utf8Decoder", CaseTransform::Snake, "utf8_decoder"),
+            ("__private_name__", CaseTransform::Camel, "__privateName__"),
+            (
+                "foo_bar(baz_qux, a - b)",
+                CaseTransform::Pascal,
+                "FooBar(BazQux, A - B)",
+            ),
+            ("hello wORLD", CaseTransform::Title, "Hello World"),
+            ("Straße", CaseTransform::Upper, "STRASSE"),
+        ];
+        for (input, transform, expected) in cases {
+            assert_eq!(
+                transform.apply(input),
+                expected,
+                "{input:?} to {transform:?}"
+            );
+        }
+    }
+}
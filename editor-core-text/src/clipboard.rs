@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+
+/// One copied or cut snippet. A copy from several selections keeps one piece per
+/// selection so that pasting with as many carets puts each piece back at its own caret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardEntry {
+    pieces: Vec<String>,
+}
+
+impl ClipboardEntry {
+    pub fn new(pieces: Vec<String>) -> Self {
+        Self { pieces }
+    }
+
+    pub fn pieces(&self) -> &[String] {
+        &self.pieces
+    }
+
+    /// The text put on the system clipboard: the pieces one per line.
+    pub fn text(&self) -> String {
+        self.pieces.join("\n")
+    }
+
+    /// First line of the text, shortened to `max_chars`, for pickers.
+    pub fn preview(&self, max_chars: usize) -> String {
+        let text = self.text();
+        let first = text.trim_start().lines().next().unwrap_or_default();
+        let mut preview: String = first.chars().take(max_chars).collect();
+        if first.chars().count() > max_chars || text.trim().lines().nth(1).is_some() {
+            preview.push('…');
+        }
+        preview
+    }
+}
+
+/// The last copied and cut snippets, newest first.
+///
+/// Copying a snippet that is already in the ring moves it to the front instead of
+/// storing it twice; the oldest entries fall off once `capacity` is reached.
+#[derive(Debug, Clone)]
+pub struct ClipboardRing {
+    entries: VecDeque<ClipboardEntry>,
+    capacity: usize,
+}
+
+impl ClipboardRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Records a snippet; empty snippets are ignored.
+    pub fn push(&mut self, pieces: Vec<String>) {
+        if pieces.iter().all(String::is_empty) {
+            return;
+        }
+        let entry = ClipboardEntry::new(pieces);
+        self.entries.retain(|existing| existing != &entry);
+        self.entries.push_front(entry);
+        self.entries.truncate(self.capacity);
+    }
+
+    pub fn latest(&self) -> Option<&ClipboardEntry> {
+        self.entries.front()
+    }
+
+    /// The newest entry whose text is `text`, e.g. to keep per-selection pieces when
+    /// the system clipboard still holds what this editor copied.
+    pub fn find_text(&self, text: &str) -> Option<&ClipboardEntry> {
+        self.entries.iter().find(|entry| entry.text() == text)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ClipboardEntry> {
+        self.entries.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ClipboardEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_newest_first_without_duplicates() {
+        let mut ring = ClipboardRing::new(3);
+        ring.push(pieces(&["a"]));
+        ring.push(pieces(&["b", "c"]));
+        ring.push(pieces(&[""]));
+        ring.push(pieces(&["d"]));
+        ring.push(pieces(&["a"]));
+        ring.push(pieces(&["e"]));
+
+        let texts: Vec<String> = ring.iter().map(ClipboardEntry::text).collect();
+        assert_eq!(texts, ["e", "a", "d"]);
+        assert!(ring.find_text("b\nc").is_none());
+        assert_eq!(
+            ring.find_text("d").map(|entry| entry.pieces().len()),
+            Some(1)
+        );
+
+        ring.push(pieces(&["fn main() {", "}"]));
+        assert_eq!(ring.latest().unwrap().preview(20), "fn main() {…");
+    }
+}
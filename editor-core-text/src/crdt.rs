@@ -0,0 +1,348 @@
+use crate::edit::EditKind;
+use serde::{Deserialize, Serialize};
+
+/// Identifies one copy of a shared text. Every peer in a session needs a different one.
+pub type ReplicaId = u32;
+
+/// Names one char of a shared text for its whole life. Ordered by Lamport time, then
+/// replica, which is the order concurrent inserts at the same place end up in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OpId {
+    pub counter: u64,
+    pub replica: ReplicaId,
+}
+
+/// One change to a shared text, as sent to the other peers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteOp {
+    /// `text` goes right after the char `origin`, or at the start for `None`. Its chars
+    /// get the ids `id`, `id + 1`, … in order.
+    Insert {
+        id: OpId,
+        origin: Option<OpId>,
+        text: String,
+    },
+    Delete {
+        ids: Vec<OpId>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    id: OpId,
+    ch: char,
+    deleted: bool,
+}
+
+/// A text that several peers edit at once, as a replicated growable array: every char
+/// keeps its id and deleted chars stay as tombstones, so operations apply in any order
+/// that respects causality and all peers end with the same text.
+///
+/// Peers must start from the same text. Operations that arrive before the ones they
+/// depend on are held back until those arrive. Lookups are linear in the length of the
+/// text including tombstones.
+#[derive(Debug, Clone)]
+pub struct TextCrdt {
+    replica: ReplicaId,
+    clock: u64,
+    items: Vec<Item>,
+    pending: Vec<RemoteOp>,
+}
+
+impl TextCrdt {
+    /// Starts from `text`. Its chars get the same ids on every replica.
+    pub fn new(replica: ReplicaId, text: &str) -> Self {
+        let items: Vec<Item> = text
+            .chars()
+            .enumerate()
+            .map(|(idx, ch)| Item {
+                id: OpId {
+                    counter: idx as u64 + 1,
+                    replica: 0,
+                },
+                ch,
+                deleted: false,
+            })
+            .collect();
+        Self {
+            replica,
+            clock: items.len() as u64,
+            items,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn replica(&self) -> ReplicaId {
+        self.replica
+    }
+
+    pub fn text(&self) -> String {
+        self.visible().map(|item| item.ch).collect()
+    }
+
+    /// Length of the text in chars.
+    pub fn len(&self) -> usize {
+        self.visible().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Operations received but waiting for ones they depend on.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Records an edit already made to the local text and returns the operations that
+    /// make it on the other peers.
+    pub fn local_edit(&mut self, edit: &EditKind) -> Vec<RemoteOp> {
+        let (char_idx, removed, inserted) = match edit {
+            EditKind::Insert { char_idx, text } => (*char_idx, 0, text.as_str()),
+            EditKind::Delete { char_idx, text } => (*char_idx, text.chars().count(), ""),
+            EditKind::Replace {
+                char_idx,
+                old_text,
+                new_text,
+            } => (*char_idx, old_text.chars().count(), new_text.as_str()),
+        };
+
+        let mut ops = Vec::new();
+        if removed > 0 {
+            let ids: Vec<OpId> = self
+                .visible_mut()
+                .skip(char_idx)
+                .take(removed)
+                .map(|item| {
+                    item.deleted = true;
+                    item.id
+                })
+                .collect();
+            ops.push(RemoteOp::Delete { ids });
+        }
+        if !inserted.is_empty() {
+            let origin = char_idx
+                .checked_sub(1)
+                .and_then(|idx| self.visible().nth(idx))
+                .map(|item| item.id);
+            let id = OpId {
+                counter: self.clock + 1,
+                replica: self.replica,
+            };
+            self.integrate_insert(id, origin, inserted);
+            ops.push(RemoteOp::Insert {
+                id,
+                origin,
+                text: inserted.to_string(),
+            });
+        }
+        ops
+    }
+
+    /// Applies an operation from another peer, and any held-back ones it unblocks.
+    /// Returns the edits to make to the local text, in the order to make them.
+    pub fn apply_remote(&mut self, op: RemoteOp) -> Vec<EditKind> {
+        let mut edits = Vec::new();
+        let mut next = Some(op);
+        while let Some(op) = next.take() {
+            if self.is_ready(&op) {
+                edits.extend(self.integrate(op));
+                // Anything held back may be ready now.
+                if let Some(idx) = self.pending.iter().position(|op| self.is_ready(op)) {
+                    next = Some(self.pending.remove(idx));
+                }
+            } else {
+                self.pending.push(op);
+            }
+        }
+        edits
+    }
+
+    fn visible(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter().filter(|item| !item.deleted)
+    }
+
+    fn visible_mut(&mut self) -> impl Iterator<Item = &mut Item> {
+        self.items.iter_mut().filter(|item| !item.deleted)
+    }
+
+    fn position(&self, id: OpId) -> Option<usize> {
+        self.items.iter().position(|item| item.id == id)
+    }
+
+    fn visible_index(&self, position: usize) -> usize {
+        self.items[..position]
+            .iter()
+            .filter(|item| !item.deleted)
+            .count()
+    }
+
+    fn is_ready(&self, op: &RemoteOp) -> bool {
+        match op {
+            RemoteOp::Insert { origin, .. } => origin.is_none_or(|id| self.position(id).is_some()),
+            RemoteOp::Delete { ids } => ids.iter().all(|id| self.position(*id).is_some()),
+        }
+    }
+
+    fn integrate(&mut self, op: RemoteOp) -> Vec<EditKind> {
+        match op {
+            RemoteOp::Insert { id, origin, text } => {
+                // Delivered twice: already in the text.
+                if self.position(id).is_some() {
+                    return Vec::new();
+                }
+                let start = self.integrate_insert(id, origin, &text);
+                vec![EditKind::Insert {
+                    char_idx: self.visible_index(start),
+                    text,
+                }]
+            }
+            RemoteOp::Delete { ids } => {
+                let mut positions: Vec<usize> = ids
+                    .into_iter()
+                    .filter_map(|id| self.position(id))
+                    .filter(|&position| !self.items[position].deleted)
+                    .collect();
+                positions.sort_unstable();
+                positions.dedup();
+
+                // From the end, so each edit's index is still right after the ones before.
+                let mut edits: Vec<EditKind> = Vec::new();
+                for position in positions.into_iter().rev() {
+                    let char_idx = self.visible_index(position);
+                    let ch = self.items[position].ch;
+                    self.items[position].deleted = true;
+                    match edits.last_mut() {
+                        Some(EditKind::Delete {
+                            char_idx: start,
+                            text,
+                        }) if *start == char_idx + 1 => {
+                            *start = char_idx;
+                            text.insert(0, ch);
+                        }
+                        _ => edits.push(EditKind::Delete {
+                            char_idx,
+                            text: ch.to_string(),
+                        }),
+                    }
+                }
+                edits
+            }
+        }
+    }
+
+    /// Places the chars of `text` and returns the position of the first one.
+    fn integrate_insert(&mut self, id: OpId, origin: Option<OpId>, text: &str) -> usize {
+        let mut origin = origin;
+        let mut start = None;
+        for (offset, ch) in text.chars().enumerate() {
+            let id = OpId {
+                counter: id.counter + offset as u64,
+                replica: id.replica,
+            };
+            let mut position = origin
+                .and_then(|origin| self.position(origin))
+                .map_or(0, |position| position + 1);
+            // Later inserts at the same place go first; chars inserted after those have
+            // even later ids, so this also skips past them.
+            while self.items.get(position).is_some_and(|item| item.id > id) {
+                position += 1;
+            }
+            self.items.insert(
+                position,
+                Item {
+                    id,
+                    ch,
+                    deleted: false,
+                },
+            );
+            self.clock = self.clock.max(id.counter);
+            start.get_or_insert(position);
+            origin = Some(id);
+        }
+        start.unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(char_idx: usize, text: &str) -> EditKind {
+        EditKind::Insert {
+            char_idx,
+            text: text.to_string(),
+        }
+    }
+
+    fn apply(text: &mut String, edits: &[EditKind]) {
+        for edit in edits {
+            let mut chars: Vec<char> = text.chars().collect();
+            match edit {
+                EditKind::Insert { char_idx, text } => {
+                    chars.splice(*char_idx..*char_idx, text.chars());
+                }
+                EditKind::Delete { char_idx, text } => {
+                    chars.drain(*char_idx..*char_idx + text.chars().count());
+                }
+                EditKind::Replace { .. } => unreachable!("remote edits are inserts or deletes"),
+            }
+            *text = chars.into_iter().collect();
+        }
+    }
+
+    #[test]
+    fn concurrent_edits_converge_and_wait_for_their_dependencies() {
+        let mut a = TextCrdt::new(1, "hello");
+        let mut b = TextCrdt::new(2, "hello");
+
+        // Both insert at the same place, and `a` deletes around it.
+        let a_ops = [
+            a.local_edit(&insert(5, " world")),
+            a.local_edit(&EditKind::Replace {
+                char_idx: 0,
+                old_text: "h".to_string(),
+                new_text: "H".to_string(),
+            }),
+        ]
+        .concat();
+        let b_ops = [
+            b.local_edit(&insert(5, "!")),
+            b.local_edit(&EditKind::Delete {
+                char_idx: 1,
+                text: "ell".to_string(),
+            }),
+        ]
+        .concat();
+        assert_eq!(a.text(), "Hello world");
+        assert_eq!(b.text(), "ho!");
+        let mut a_text = a.text();
+        let mut b_text = b.text();
+
+        for op in b_ops {
+            let edits = a.apply_remote(op);
+            apply(&mut a_text, &edits);
+        }
+        // `b` gets the ops in reverse; the insert after "H" waits for it.
+        for op in a_ops.into_iter().rev() {
+            let edits = b.apply_remote(op);
+            apply(&mut b_text, &edits);
+        }
+        // The later of two inserts at the same place goes first; ties go to the
+        // higher replica.
+        assert_eq!(a.text(), "Ho! world");
+        assert_eq!(b.text(), a.text());
+        assert_eq!(a.pending() + b.pending(), 0);
+        // The returned edits turn each local text into the merged one.
+        assert_eq!(a_text, a.text());
+        assert_eq!(b_text, b.text());
+
+        let held = a.local_edit(&insert(9, "?"));
+        let after = a.local_edit(&insert(10, "?"));
+        assert!(b.apply_remote(after[0].clone()).is_empty());
+        assert_eq!(b.pending(), 1);
+        assert_eq!(b.apply_remote(held[0].clone()).len(), 2);
+        assert_eq!(b.text(), "Ho! world??");
+        assert!(b.apply_remote(held[0].clone()).is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::buffer::Buffer;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,6 +15,12 @@ impl Cursor {
     pub fn zero() -> Self {
         Self { line: 0, column: 0 }
     }
+
+    /// The nearest position in `buffer`'s current text: lines past the end move to the
+    /// last line and columns past the end of a line to before its line break.
+    pub async fn clamp_to(self, buffer: &Buffer) -> Self {
+        buffer.clamp_cursor(self).await
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -0,0 +1,234 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+use unicode_width::UnicodeWidthStr;
+
+/// One field of a delimiter-separated file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableCell {
+    /// The value with quoting undone.
+    pub value: String,
+    /// Where the field is in the text, quotes included, in chars.
+    pub range: Range<usize>,
+}
+
+/// A CSV or TSV text split into rows and fields, RFC 4180 style: fields may be quoted,
+/// quotes inside them are doubled, and quoted fields may span lines. Rows may have
+/// different lengths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    pub delimiter: char,
+    pub rows: Vec<Vec<TableCell>>,
+}
+
+/// The delimiter for a file with extension `extension`, or `None` for files that are
+/// not tables. CSV files written with a decimal comma often use `;`, which the first
+/// line gives away.
+pub fn detect_delimiter(extension: &str, text: &str) -> Option<char> {
+    match extension.to_ascii_lowercase().as_str() {
+        "tsv" | "tab" => Some('\t'),
+        "psv" => Some('|'),
+        "csv" => {
+            let header = text.lines().next().unwrap_or_default();
+            let count = |delimiter| header.matches(delimiter).count();
+            Some(if count(';') > count(',') { ';' } else { ',' })
+        }
+        _ => None,
+    }
+}
+
+/// `value` as a field: quoted when it contains the delimiter, a quote or a line break.
+pub fn quote_field(value: &str, delimiter: char) -> String {
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Table {
+    pub fn parse(text: &str, delimiter: char) -> Self {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut chars = text.chars().enumerate().peekable();
+        let mut at_row_start = true;
+
+        while let Some(&(start, first)) = chars.peek() {
+            let mut value = String::new();
+            let mut end = start;
+            if first == '"' {
+                chars.next();
+                end += 1;
+                while let Some((idx, ch)) = chars.next() {
+                    end = idx + 1;
+                    if ch != '"' {
+                        value.push(ch);
+                    } else if chars.peek().is_some_and(|&(_, next)| next == '"') {
+                        chars.next();
+                        end += 1;
+                        value.push('"');
+                    } else {
+                        break;
+                    }
+                }
+            }
+            // Unquoted fields, and anything after a closing quote, run to the delimiter.
+            while let Some(&(idx, ch)) = chars.peek() {
+                if ch == delimiter || ch == '\n' || ch == '\r' {
+                    break;
+                }
+                value.push(ch);
+                end = idx + 1;
+                chars.next();
+            }
+            row.push(TableCell {
+                value,
+                range: start..end,
+            });
+            at_row_start = false;
+
+            match chars.next() {
+                // A delimiter at the very end leaves an empty last field.
+                Some((_, ch)) if ch == delimiter && chars.peek().is_none() => {
+                    row.push(TableCell {
+                        value: String::new(),
+                        range: end + 1..end + 1,
+                    });
+                }
+                Some((_, ch)) if ch == delimiter => {}
+                Some((_, '\r')) => {
+                    if chars.peek().is_some_and(|&(_, next)| next == '\n') {
+                        chars.next();
+                    }
+                    rows.push(std::mem::take(&mut row));
+                    at_row_start = true;
+                }
+                Some(_) => {
+                    rows.push(std::mem::take(&mut row));
+                    at_row_start = true;
+                }
+                None => {}
+            }
+        }
+        if !at_row_start {
+            rows.push(row);
+        }
+        Self { delimiter, rows }
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> Option<&TableCell> {
+        self.rows.get(row)?.get(column)
+    }
+
+    /// Display width of each column: its widest value, at most `max`. Line breaks in
+    /// a value count as spaces.
+    pub fn column_widths(&self, max: usize) -> Vec<usize> {
+        let mut widths = vec![0; self.column_count()];
+        for row in &self.rows {
+            for (column, cell) in row.iter().enumerate() {
+                let width = cell.value.replace(['\n', '\r'], " ").width();
+                widths[column] = widths[column].max(width.min(max));
+            }
+        }
+        widths
+    }
+
+    /// Indices of the rows after the header, sorted by `column`. Values that are all
+    /// numbers sort numerically and come before text; the sort is stable.
+    pub fn sorted_rows(&self, column: usize, ascending: bool) -> Vec<usize> {
+        let mut rows: Vec<usize> = (1..self.rows.len()).collect();
+        let key = |row: usize| {
+            self.cell(row, column)
+                .map(|cell| cell.value.trim())
+                .unwrap_or_default()
+        };
+        rows.sort_by(|&a, &b| {
+            let ordering = match (key(a).parse::<f64>(), key(b).parse::<f64>()) {
+                (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => key(a).cmp(key(b)),
+            };
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+        rows
+    }
+
+    /// The char range to replace and the text to put there to set a field to `value`.
+    /// A field past the end of a short row is added after the row's last field.
+    pub fn edit_for(
+        &self,
+        row: usize,
+        column: usize,
+        value: &str,
+    ) -> Option<(Range<usize>, String)> {
+        let cells = self.rows.get(row)?;
+        let quoted = quote_field(value, self.delimiter);
+        if let Some(cell) = cells.get(column) {
+            return Some((cell.range.clone(), quoted));
+        }
+        let end = cells.last()?.range.end;
+        let padding = self.delimiter.to_string().repeat(column + 1 - cells.len());
+        Some((end..end, format!("{}{}", padding, quoted)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(table: &Table) -> Vec<Vec<&str>> {
+        table
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.value.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn parses_quoted_fields_and_maps_edits_back_to_the_text() {
+        let text = "name,note,n\r\n\"Smith, J\",\"said \"\"hi\"\"\ntwice\",10\nLee,,9\nAli,x\n";
+        let table = Table::parse(text, ',');
+        assert_eq!(
+            values(&table),
+            [
+                vec!["name", "note", "n"],
+                vec!["Smith, J", "said \"hi\"\ntwice", "10"],
+                vec!["Lee", "", "9"],
+                vec!["Ali", "x"],
+            ]
+        );
+        let chars: Vec<char> = text.chars().collect();
+        let raw: String = chars[table.rows[1][0].range.clone()].iter().collect();
+        assert_eq!(raw, "\"Smith, J\"");
+        assert_eq!(table.column_widths(8), [8, 8, 2]);
+        assert_eq!(table.sorted_rows(2, true), [2, 1, 3]);
+        assert_eq!(table.sorted_rows(0, false), [1, 2, 3]);
+
+        let apply = |text: &str, row, column, value| {
+            let (range, replacement) = Table::parse(text, ',').edit_for(row, column, value)?;
+            let mut chars: Vec<char> = text.chars().collect();
+            chars.splice(range, replacement.chars());
+            Some(chars.into_iter().collect::<String>())
+        };
+        let text = apply(text, 2, 1, "a;b").unwrap();
+        assert!(text.contains("\nLee,a;b,9\n"));
+        // A short row gets the missing fields; values are quoted as needed.
+        let text = apply(&text, 3, 2, "7, 8").unwrap();
+        assert!(text.ends_with("\nAli,x,\"7, 8\"\n"));
+        assert_eq!(values(&Table::parse(&text, ','))[3], ["Ali", "x", "7, 8"]);
+        assert!(apply(&text, 9, 0, "").is_none());
+
+        assert_eq!(values(&Table::parse("a\tb\t", '\t')), [vec!["a", "b", ""]]);
+        assert_eq!(detect_delimiter("CSV", "a;b;c\n1,5;2;3"), Some(';'));
+        assert_eq!(detect_delimiter("tsv", ""), Some('\t'));
+        assert_eq!(detect_delimiter("rs", ""), None);
+    }
+}
@@ -0,0 +1,205 @@
+use similar::{DiffTag, TextDiff};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChangeKind {
+    Added,
+    Modified,
+    /// Lines removed between two lines of the new text.
+    Deleted,
+}
+
+/// A block of lines that differs between two texts: lines `old` of the old text were
+/// replaced by lines `new` of the new one. A deletion has an empty `new` range starting
+/// at the line that now follows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+    /// The replaced and replacing lines, each with its line break.
+    pub old_text: String,
+    pub new_text: String,
+}
+
+impl LineChange {
+    pub fn kind(&self) -> LineChangeKind {
+        if self.old.is_empty() {
+            LineChangeKind::Added
+        } else if self.new.is_empty() {
+            LineChangeKind::Deleted
+        } else {
+            LineChangeKind::Modified
+        }
+    }
+}
+
+/// Line-level diff (Myers) between two texts, e.g. the saved file and the buffer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineDiff {
+    changes: Vec<LineChange>,
+    new_len: usize,
+}
+
+impl LineDiff {
+    pub fn new(old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_lines(old, new);
+        let (old_lines, new_lines) = (diff.old_slices(), diff.new_slices());
+        let mut changes: Vec<LineChange> = Vec::new();
+        for op in diff.ops() {
+            let (tag, old, new) = op.as_tag_tuple();
+            if tag == DiffTag::Equal {
+                continue;
+            }
+            // A deletion next to an insertion is one modified block.
+            match changes.last_mut() {
+                Some(last) if last.old.end == old.start && last.new.end == new.start => {
+                    last.old.end = old.end;
+                    last.new.end = new.end;
+                }
+                _ => changes.push(LineChange {
+                    old,
+                    new,
+                    old_text: String::new(),
+                    new_text: String::new(),
+                }),
+            }
+        }
+        for change in &mut changes {
+            change.old_text = old_lines[change.old.clone()].concat();
+            change.new_text = new_lines[change.new.clone()].concat();
+        }
+        Self {
+            changes,
+            new_len: new_lines.len(),
+        }
+    }
+
+    pub fn changes(&self) -> &[LineChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The change to mark beside line `line` of the new text: lines of an added or
+    /// modified block, and the line right after deleted ones (the last line when the
+    /// deletion is at the end).
+    pub fn kind_at(&self, line: usize) -> Option<LineChangeKind> {
+        let idx = self
+            .changes
+            .partition_point(|change| self.marked_lines(change).end <= line);
+        let change = self.changes.get(idx)?;
+        self.marked_lines(change)
+            .contains(&line)
+            .then(|| change.kind())
+    }
+
+    fn marked_lines(&self, change: &LineChange) -> Range<usize> {
+        if !change.new.is_empty() {
+            return change.new.clone();
+        }
+        let line = if change.new.start == self.new_len {
+            self.new_len.saturating_sub(1)
+        } else {
+            change.new.start
+        };
+        line..line + 1
+    }
+}
+
+/// Byte ranges that differ between two versions of a line, word by word, so a small
+/// edit inside a modified line can be told apart from the text around it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordDiff {
+    pub old: Vec<Range<usize>>,
+    pub new: Vec<Range<usize>>,
+}
+
+impl WordDiff {
+    pub fn new(old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_words(old, new);
+        let (old_offsets, new_offsets) = (
+            slice_offsets(diff.old_slices()),
+            slice_offsets(diff.new_slices()),
+        );
+        let mut words = Self::default();
+        for op in diff.ops() {
+            let (tag, old, new) = op.as_tag_tuple();
+            if tag == DiffTag::Equal {
+                continue;
+            }
+            push_range(&mut words.old, old_offsets[old.start]..old_offsets[old.end]);
+            push_range(&mut words.new, new_offsets[new.start]..new_offsets[new.end]);
+        }
+        words
+    }
+}
+
+/// Byte offset of each slice, plus the end of the last one.
+fn slice_offsets(slices: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(slices.len() + 1);
+    let mut offset = 0;
+    offsets.push(offset);
+    for slice in slices {
+        offset += slice.len();
+        offsets.push(offset);
+    }
+    offsets
+}
+
+/// Adds `range` unless it is empty, merging it into the last one when they touch.
+fn push_range(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    if range.is_empty() {
+        return;
+    }
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_lines_into_added_modified_and_deleted_blocks() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nB\nc\nnew\nd\n";
+        let diff = LineDiff::new(old, new);
+
+        let kinds: Vec<_> = diff.changes().iter().map(LineChange::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                LineChangeKind::Modified,
+                LineChangeKind::Added,
+                LineChangeKind::Deleted
+            ]
+        );
+        assert_eq!(diff.changes()[0].old_text, "b\n");
+        assert_eq!(diff.changes()[0].new_text, "B\n");
+        assert_eq!(diff.changes()[2].new, 5..5);
+
+        assert_eq!(diff.kind_at(0), None);
+        assert_eq!(diff.kind_at(1), Some(LineChangeKind::Modified));
+        assert_eq!(diff.kind_at(3), Some(LineChangeKind::Added));
+        assert_eq!(diff.kind_at(2), None);
+        assert_eq!(diff.kind_at(4), Some(LineChangeKind::Deleted));
+        assert!(LineDiff::new(old, old).is_empty());
+    }
+
+    #[test]
+    fn word_diff_marks_only_the_changed_words() {
+        let words = WordDiff::new("let total = count + 1;", "let total = count * 2;");
+        assert_eq!(words.old, [18..19, 20..22]);
+        assert_eq!(words.new, [18..19, 20..22]);
+
+        let inserted = WordDiff::new("call(a)", "call(a) // é done");
+        assert!(inserted.old.is_empty());
+        assert_eq!(inserted.new.len(), 1);
+        assert_eq!(inserted.new[0], 7..18);
+        assert_eq!(WordDiff::new("same", "same"), WordDiff::default());
+    }
+}
@@ -0,0 +1,994 @@
+use crate::cursor::Cursor;
+use std::iter::Peekable;
+use std::str::Chars;
+use thiserror::Error;
+
+/// Where a node sits in its parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Object,
+    Array,
+    Scalar,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    /// `None` for the document root.
+    pub segment: Option<PathSegment>,
+    pub kind: NodeKind,
+    /// Scalars as written, quotes included. Empty for objects and arrays.
+    pub value: String,
+    pub parent: Option<usize>,
+    pub depth: usize,
+    /// Where the node starts: its key, or its value for array items and the root.
+    pub start: Cursor,
+    /// Last line of the node, its children included.
+    pub end_line: usize,
+    pub children: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message} at line {}, column {}", .line + 1, .column + 1)]
+pub struct TreeError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// The outline of a JSON, YAML or TOML document: every object, array and value with
+/// its place in the text, in document order with the root first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentTree {
+    pub nodes: Vec<TreeNode>,
+}
+
+impl DocumentTree {
+    /// The tree of `text` in `language`, or `None` for languages other than JSON, YAML
+    /// and TOML.
+    pub fn parse(language: &str, text: &str) -> Option<Result<Self, TreeError>> {
+        match language {
+            "json" | "jsonc" => Some(Self::parse_json(text)),
+            "yaml" => Some(Ok(Self::parse_yaml(text))),
+            "toml" => Some(Ok(Self::parse_toml(text))),
+            _ => None,
+        }
+    }
+
+    /// Parses JSON, allowing comments and trailing commas as in `tsconfig.json`.
+    pub fn parse_json(text: &str) -> Result<Self, TreeError> {
+        let mut parser = JsonParser {
+            chars: text.chars().peekable(),
+            line: 0,
+            column: 0,
+            nodes: Vec::new(),
+        };
+        parser.skip_trivia();
+        if parser.chars.peek().is_none() {
+            return Ok(Self::default());
+        }
+        parser.value(None, None, 0, None)?;
+        parser.skip_trivia();
+        if parser.chars.peek().is_some() {
+            return Err(parser.error("unexpected text after the document"));
+        }
+        Ok(Self {
+            nodes: parser.nodes,
+        })
+    }
+
+    /// Parses the block style of YAML that config files use: nested mappings and
+    /// sequences, with flow collections (`[a, b]`) and block scalars (`|`) as values.
+    /// Anything else is skipped rather than rejected, so the tree stays usable while
+    /// the file is being edited.
+    pub fn parse_yaml(text: &str) -> Self {
+        let mut parser = YamlParser {
+            nodes: vec![TreeNode {
+                segment: None,
+                kind: NodeKind::Scalar,
+                value: String::new(),
+                parent: None,
+                depth: 0,
+                start: Cursor::zero(),
+                end_line: 0,
+                children: 0,
+            }],
+            stack: vec![OpenNode {
+                owner_indent: -1,
+                child_indent: None,
+                node: 0,
+            }],
+            block_scalar: None,
+        };
+        for (line_idx, line) in text.lines().enumerate() {
+            let content = line.trim_start_matches(' ');
+            let indent = line.len() - content.len();
+            if let Some((owner, node)) = parser.block_scalar {
+                if content.trim().is_empty() || indent > owner {
+                    parser.nodes[node].end_line = line_idx;
+                    continue;
+                }
+                parser.block_scalar = None;
+            }
+            let content = content.trim_end();
+            if content.is_empty()
+                || content.starts_with('#')
+                || content.starts_with("---")
+                || content.starts_with("...")
+                || content.starts_with('%')
+            {
+                continue;
+            }
+            parser.close_before(indent, content);
+            parser.entry(line_idx, indent, content);
+        }
+
+        let mut nodes = parser.nodes;
+        for idx in (1..nodes.len()).rev() {
+            if let Some(parent) = nodes[idx].parent {
+                nodes[parent].end_line = nodes[parent].end_line.max(nodes[idx].end_line);
+            }
+        }
+        Self { nodes }
+    }
+
+    /// Parses TOML tables, arrays of tables and dotted keys. Arrays and inline tables are
+    /// kept as values, like YAML flow collections, and may span lines. Lines that don't
+    /// parse are skipped. A table reopened further down gets its new keys where they
+    /// are written, so nodes stay in document order but not always next to their parent.
+    pub fn parse_toml(text: &str) -> Self {
+        let mut parser = TomlParser {
+            nodes: vec![TreeNode {
+                segment: None,
+                kind: NodeKind::Object,
+                value: String::new(),
+                parent: None,
+                depth: 0,
+                start: Cursor::zero(),
+                end_line: 0,
+                children: 0,
+            }],
+        };
+        let mut table = 0;
+        // A value still open at the end of the previous line, and how far in it is.
+        let mut open: Option<(usize, TomlScan)> = None;
+        for (line_idx, line) in text.lines().enumerate() {
+            if let Some((node, mut scan)) = open.take() {
+                parser.nodes[node].end_line = line_idx;
+                scan.scan(line);
+                if !scan.is_closed() {
+                    open = Some((node, scan));
+                }
+                continue;
+            }
+            let content = line.trim_start();
+            let column = line.chars().count() - content.chars().count();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = content.strip_prefix('[') {
+                let array = header.starts_with('[');
+                let name = header.strip_prefix('[').unwrap_or(header);
+                let offset = column + content.chars().count() - name.chars().count();
+                let Some(keys) = split_dotted(name.split(']').next().unwrap_or_default()) else {
+                    continue;
+                };
+                if let Some(node) = parser.table(&keys, line_idx, offset, array) {
+                    table = node;
+                }
+            } else if let Some((keys, value)) = split_toml_key(content) {
+                let value = value.trim_start();
+                let mut parent = table;
+                for (key, key_offset) in &keys[..keys.len() - 1] {
+                    let start = Cursor::new(line_idx, column + key_offset);
+                    parent = parser.child(parent, key, start, NodeKind::Object);
+                }
+                let (key, key_offset) = &keys[keys.len() - 1];
+                let start = Cursor::new(line_idx, column + key_offset);
+                let node = parser.child(parent, key, start, NodeKind::Scalar);
+                let mut scan = TomlScan::default();
+                scan.scan(value);
+                parser.nodes[node].value = value[..scan.value_len].trim_end().to_string();
+                if !scan.is_closed() {
+                    open = Some((node, scan));
+                }
+            }
+        }
+
+        let mut nodes = parser.nodes;
+        for idx in (1..nodes.len()).rev() {
+            if let Some(parent) = nodes[idx].parent {
+                nodes[parent].end_line = nodes[parent].end_line.max(nodes[idx].end_line);
+            }
+        }
+        Self { nodes }
+    }
+
+    /// The path of node `idx` as a JSONPath, like `$.jobs.build.steps[0]`.
+    pub fn path(&self, idx: usize) -> String {
+        let mut segments = Vec::new();
+        let mut node = self.nodes.get(idx);
+        while let Some(current) = node {
+            segments.extend(&current.segment);
+            node = current.parent.map(|parent| &self.nodes[parent]);
+        }
+        let mut path = "$".to_string();
+        for segment in segments.into_iter().rev() {
+            match segment {
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+                PathSegment::Key(key) if is_identifier(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                PathSegment::Key(key) => path.push_str(&format!(
+                    "[{}]",
+                    serde_json::to_string(key).unwrap_or_default()
+                )),
+            }
+        }
+        path
+    }
+
+    /// The innermost node at `cursor`.
+    pub fn node_at(&self, cursor: Cursor) -> Option<usize> {
+        // Nodes come in document order, so the last one containing the cursor is the
+        // innermost.
+        self.nodes.iter().rposition(|node| {
+            (node.start.line, node.start.column) <= (cursor.line, cursor.column)
+                && cursor.line <= node.end_line
+        })
+    }
+
+    /// Whether node `idx` shows when the nodes `collapsed` rejects are folded.
+    pub fn is_visible(&self, idx: usize, mut collapsed: impl FnMut(usize) -> bool) -> bool {
+        let mut parent = self.nodes.get(idx).and_then(|node| node.parent);
+        while let Some(idx) = parent {
+            if collapsed(idx) {
+                return false;
+            }
+            parent = self.nodes[idx].parent;
+        }
+        true
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|ch| ch.is_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    nodes: Vec<TreeNode>,
+}
+
+impl JsonParser<'_> {
+    fn position(&self) -> Cursor {
+        Cursor::new(self.line, self.column)
+    }
+
+    fn error(&self, message: &str) -> TreeError {
+        TreeError {
+            line: self.line,
+            column: self.column,
+            message: message.to_string(),
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(&ch) = self.chars.peek() {
+            if ch.is_whitespace() {
+                self.bump();
+                continue;
+            }
+            let mut ahead = self.chars.clone();
+            ahead.next();
+            match (ch, ahead.next()) {
+                ('/', Some('/')) => {
+                    while self.chars.peek().is_some_and(|&ch| ch != '\n') {
+                        self.bump();
+                    }
+                }
+                ('/', Some('*')) => {
+                    self.bump();
+                    self.bump();
+                    let mut previous = ' ';
+                    while let Some(ch) = self.bump() {
+                        if previous == '*' && ch == '/' {
+                            break;
+                        }
+                        previous = ch;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Reads a string and returns it as written and decoded.
+    fn string(&mut self) -> Result<(String, String), TreeError> {
+        let mut raw = String::new();
+        let mut decoded = String::new();
+        if self.bump() != Some('"') {
+            return Err(self.error("expected a string"));
+        }
+        raw.push('"');
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => {
+                    let escaped = self
+                        .bump()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    raw.push('\\');
+                    raw.push(escaped);
+                    decoded.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                            raw.push_str(&hex);
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        other => other,
+                    });
+                }
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(ch) => {
+                    raw.push(ch);
+                    decoded.push(ch);
+                }
+            }
+        }
+        raw.push('"');
+        Ok((raw, decoded))
+    }
+
+    fn value(
+        &mut self,
+        segment: Option<PathSegment>,
+        parent: Option<usize>,
+        depth: usize,
+        start: Option<Cursor>,
+    ) -> Result<(), TreeError> {
+        self.skip_trivia();
+        let start = start.unwrap_or(self.position());
+        let idx = self.nodes.len();
+        self.nodes.push(TreeNode {
+            segment,
+            kind: NodeKind::Scalar,
+            value: String::new(),
+            parent,
+            depth,
+            start,
+            end_line: start.line,
+            children: 0,
+        });
+
+        match self.chars.peek() {
+            Some('{') => {
+                self.bump();
+                self.nodes[idx].kind = NodeKind::Object;
+                loop {
+                    self.skip_trivia();
+                    if self.chars.peek() == Some(&'}') {
+                        self.bump();
+                        break;
+                    }
+                    let key_start = self.position();
+                    let (_, key) = self.string()?;
+                    self.skip_trivia();
+                    if self.bump() != Some(':') {
+                        return Err(self.error("expected `:`"));
+                    }
+                    self.value(
+                        Some(PathSegment::Key(key)),
+                        Some(idx),
+                        depth + 1,
+                        Some(key_start),
+                    )?;
+                    self.nodes[idx].children += 1;
+                    self.skip_trivia();
+                    match self.bump() {
+                        Some(',') => {}
+                        Some('}') => break,
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some('[') => {
+                self.bump();
+                self.nodes[idx].kind = NodeKind::Array;
+                loop {
+                    self.skip_trivia();
+                    if self.chars.peek() == Some(&']') {
+                        self.bump();
+                        break;
+                    }
+                    let index = self.nodes[idx].children;
+                    self.value(Some(PathSegment::Index(index)), Some(idx), depth + 1, None)?;
+                    self.nodes[idx].children += 1;
+                    self.skip_trivia();
+                    match self.bump() {
+                        Some(',') => {}
+                        Some(']') => break,
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some('"') => self.nodes[idx].value = self.string()?.0,
+            Some(_) => {
+                let mut value = String::new();
+                while let Some(&ch) = self.chars.peek() {
+                    if ch.is_whitespace() || matches!(ch, ',' | ']' | '}' | '/') {
+                        break;
+                    }
+                    value.push(ch);
+                    self.bump();
+                }
+                if value.is_empty() {
+                    return Err(self.error("expected a value"));
+                }
+                self.nodes[idx].value = value;
+            }
+            None => return Err(self.error("unexpected end of the document")),
+        }
+        self.nodes[idx].end_line = self.line;
+        Ok(())
+    }
+}
+
+/// An object or array that takes entries from the lines below it.
+struct OpenNode {
+    /// Indent of the line with its key or dash; -1 for the root.
+    owner_indent: isize,
+    /// Indent its entries line up at, known from the first one.
+    child_indent: Option<usize>,
+    node: usize,
+}
+
+struct YamlParser {
+    nodes: Vec<TreeNode>,
+    stack: Vec<OpenNode>,
+    /// Indent of the key that started a block scalar, and its node.
+    block_scalar: Option<(usize, usize)>,
+}
+
+impl YamlParser {
+    /// Closes the open nodes that a line at `indent` ends.
+    fn close_before(&mut self, indent: usize, content: &str) {
+        while self.stack.len() > 1 {
+            let open = &self.stack[self.stack.len() - 1];
+            let keep = match open.child_indent {
+                Some(child_indent) => child_indent <= indent,
+                None => {
+                    indent as isize > open.owner_indent
+                        // `key:` followed by a sequence at the key's own indent.
+                        || (indent as isize == open.owner_indent
+                            && dash_item(content).is_some()
+                            && matches!(self.nodes[open.node].segment, Some(PathSegment::Key(_))))
+                }
+            };
+            if keep {
+                return;
+            }
+            self.stack.pop();
+        }
+    }
+
+    fn push(&mut self, segment: PathSegment, parent: usize, start: Cursor) -> usize {
+        let depth = self.nodes[parent].depth + 1;
+        self.nodes[parent].children += 1;
+        self.nodes.push(TreeNode {
+            segment: Some(segment),
+            kind: NodeKind::Scalar,
+            value: String::new(),
+            parent: Some(parent),
+            depth,
+            start,
+            end_line: start.line,
+            children: 0,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// A mapping entry or sequence item starting at `column`.
+    fn entry(&mut self, line: usize, column: usize, content: &str) {
+        let Some(open) = self.stack.last_mut() else {
+            return;
+        };
+        match open.child_indent {
+            None => open.child_indent = Some(column),
+            // More indented than the entries: a plain scalar running over lines.
+            Some(child_indent) if child_indent != column => return,
+            Some(_) => {}
+        }
+        let parent = open.node;
+        let start = Cursor::new(line, column);
+
+        if let Some(rest) = dash_item(content) {
+            if self.nodes[parent].children == 0 {
+                self.nodes[parent].kind = NodeKind::Array;
+            }
+            let index = self.nodes[parent].children;
+            let idx = self.push(PathSegment::Index(index), parent, start);
+            let value = rest.trim_start();
+            let offset = content.chars().count() - value.chars().count();
+            self.value(idx, line, column, column + offset, value, true);
+        } else if let Some((key, rest)) = split_key(content) {
+            if self.nodes[parent].children == 0 {
+                self.nodes[parent].kind = NodeKind::Object;
+            }
+            let idx = self.push(PathSegment::Key(key), parent, start);
+            let value = rest.trim_start();
+            let offset = content.chars().count() - value.chars().count();
+            self.value(idx, line, column, column + offset, value, false);
+        }
+    }
+
+    /// Fills in node `idx` from what follows its key or dash on the same line.
+    fn value(
+        &mut self,
+        idx: usize,
+        line: usize,
+        owner_indent: usize,
+        column: usize,
+        value: &str,
+        item: bool,
+    ) {
+        let value = strip_comment(value).trim_end();
+        if value.is_empty() {
+            self.stack.push(OpenNode {
+                owner_indent: owner_indent as isize,
+                child_indent: None,
+                node: idx,
+            });
+        } else if value.starts_with('|') || value.starts_with('>') {
+            self.nodes[idx].value = value.to_string();
+            self.block_scalar = Some((owner_indent, idx));
+        } else if item && (dash_item(value).is_some() || split_key(value).is_some()) {
+            // `- - a` or `- key: a`: the item's own entries line up after the dash.
+            self.stack.push(OpenNode {
+                owner_indent: owner_indent as isize,
+                child_indent: Some(column),
+                node: idx,
+            });
+            self.entry(line, column, value);
+        } else {
+            self.nodes[idx].value = value.to_string();
+        }
+    }
+}
+
+fn dash_item(content: &str) -> Option<&str> {
+    if content == "-" {
+        Some("")
+    } else {
+        content.strip_prefix("- ")
+    }
+}
+
+/// Splits `key: value` into the unquoted key and what follows the colon.
+fn split_key(content: &str) -> Option<(String, &str)> {
+    let is_separator = |rest: &str| rest.is_empty() || rest.starts_with([' ', '\t']);
+    if let Some(quote @ ('"' | '\'')) = content.chars().next() {
+        let mut key = String::new();
+        let mut chars = content.char_indices().skip(1).peekable();
+        while let Some((idx, ch)) = chars.next() {
+            if ch == '\\' && quote == '"' {
+                if let Some((_, escaped)) = chars.next() {
+                    key.push(escaped);
+                }
+            } else if ch == quote && quote == '\'' && chars.peek().is_some_and(|&(_, c)| c == '\'')
+            {
+                chars.next();
+                key.push('\'');
+            } else if ch == quote {
+                let rest = content[idx + 1..].trim_start().strip_prefix(':')?;
+                return is_separator(rest).then_some((key, rest));
+            } else {
+                key.push(ch);
+            }
+        }
+        return None;
+    }
+    if content.starts_with(['[', '{', '#', '&', '*', '!', '|', '>']) {
+        return None;
+    }
+    let mut search = 0;
+    while let Some(offset) = content[search..].find(':') {
+        let colon = search + offset;
+        let rest = &content[colon + 1..];
+        if is_separator(rest) {
+            let key = content[..colon].trim_end();
+            if key.contains(" #") {
+                return None;
+            }
+            return Some((key.to_string(), rest));
+        }
+        search = colon + 1;
+    }
+    None
+}
+
+/// `value` without a trailing ` # comment`, leaving `#` inside quotes alone.
+fn strip_comment(value: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (idx, ch) in value.char_indices() {
+        match (quote, ch) {
+            (None, '"' | '\'') if previous.is_whitespace() || idx == 0 => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '#') if previous.is_whitespace() => return &value[..idx],
+            _ => {}
+        }
+        previous = ch;
+    }
+    value
+}
+
+struct TomlParser {
+    nodes: Vec<TreeNode>,
+}
+
+impl TomlParser {
+    fn find(&self, parent: usize, key: &str) -> Option<usize> {
+        self.nodes.iter().rposition(|node| {
+            node.parent == Some(parent)
+                && matches!(&node.segment, Some(PathSegment::Key(name)) if name == key)
+        })
+    }
+
+    fn push(
+        &mut self,
+        parent: usize,
+        segment: PathSegment,
+        start: Cursor,
+        kind: NodeKind,
+    ) -> usize {
+        let depth = self.nodes[parent].depth + 1;
+        self.nodes[parent].children += 1;
+        self.nodes.push(TreeNode {
+            segment: Some(segment),
+            kind,
+            value: String::new(),
+            parent: Some(parent),
+            depth,
+            start,
+            end_line: start.line,
+            children: 0,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// The child `key` of `parent`, added as `kind` if it isn't there yet. An array of
+    /// tables stands for its last table.
+    fn child(&mut self, parent: usize, key: &str, start: Cursor, kind: NodeKind) -> usize {
+        match self.find(parent, key) {
+            Some(idx) if self.nodes[idx].kind == NodeKind::Array => self
+                .nodes
+                .iter()
+                .rposition(|node| node.parent == Some(idx))
+                .unwrap_or(idx),
+            Some(idx) => idx,
+            None => self.push(parent, PathSegment::Key(key.to_string()), start, kind),
+        }
+    }
+
+    /// Opens the table `[keys]`, or adds a table to the array `[[keys]]`.
+    fn table(
+        &mut self,
+        keys: &[(String, usize)],
+        line: usize,
+        column: usize,
+        array: bool,
+    ) -> Option<usize> {
+        let ((key, offset), parents) = keys.split_last()?;
+        let mut parent = 0;
+        for (name, name_offset) in parents {
+            let start = Cursor::new(line, column + name_offset);
+            parent = self.child(parent, name, start, NodeKind::Object);
+        }
+        let start = Cursor::new(line, column + offset);
+        if !array {
+            return Some(self.child(parent, key, start, NodeKind::Object));
+        }
+        let list = match self.find(parent, key) {
+            Some(idx) if self.nodes[idx].kind == NodeKind::Array => idx,
+            Some(_) => return None,
+            None => self.push(
+                parent,
+                PathSegment::Key(key.clone()),
+                start,
+                NodeKind::Array,
+            ),
+        };
+        let index = self.nodes[list].children;
+        Some(self.push(list, PathSegment::Index(index), start, NodeKind::Object))
+    }
+}
+
+/// How far a TOML value has been read: brackets still open and a multi-line string not
+/// yet closed, which carry the value over to the next line.
+#[derive(Debug, Default)]
+struct TomlScan {
+    depth: usize,
+    multiline: Option<char>,
+    /// Bytes of the last line read before its comment.
+    value_len: usize,
+}
+
+impl TomlScan {
+    fn is_closed(&self) -> bool {
+        self.depth == 0 && self.multiline.is_none()
+    }
+
+    fn scan(&mut self, line: &str) {
+        self.value_len = line.len();
+        let triple = |quote: char| if quote == '"' { "\"\"\"" } else { "'''" };
+        let mut chars = line.char_indices();
+        while let Some((idx, ch)) = chars.next() {
+            if let Some(quote) = self.multiline {
+                if ch == '\\' && quote == '"' {
+                    chars.next();
+                } else if line[idx..].starts_with(triple(quote)) {
+                    chars.nth(1);
+                    self.multiline = None;
+                }
+                continue;
+            }
+            match ch {
+                '"' | '\'' if line[idx..].starts_with(triple(ch)) => {
+                    chars.nth(1);
+                    self.multiline = Some(ch);
+                }
+                '"' | '\'' => {
+                    while let Some((_, next)) = chars.next() {
+                        if next == '\\' && ch == '"' {
+                            chars.next();
+                        } else if next == ch {
+                            break;
+                        }
+                    }
+                }
+                '[' | '{' => self.depth += 1,
+                ']' | '}' => self.depth = self.depth.saturating_sub(1),
+                '#' => {
+                    self.value_len = idx;
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Splits `key = value` into its dotted keys and what follows the `=`.
+fn split_toml_key(content: &str) -> Option<(Vec<(String, usize)>, &str)> {
+    let mut quote = None;
+    for (idx, ch) in content.char_indices() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '=') => return Some((split_dotted(&content[..idx])?, &content[idx + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits dotted TOML keys like `a."b.c".d`, each with its char offset into `text`.
+fn split_dotted(text: &str) -> Option<Vec<(String, usize)>> {
+    let mut keys = Vec::new();
+    let mut chars = text.chars().enumerate().peekable();
+    let skip_blanks = |chars: &mut Peekable<_>| {
+        while chars
+            .next_if(|&(_, ch): &(usize, char)| ch == ' ' || ch == '\t')
+            .is_some()
+        {}
+    };
+    loop {
+        skip_blanks(&mut chars);
+        let &(start, first) = chars.peek()?;
+        let mut key = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    (_, '\\') if first == '"' => key.push(chars.next()?.1),
+                    (_, ch) if ch == first => break,
+                    (_, ch) => key.push(ch),
+                }
+            }
+        } else {
+            while let Some((_, ch)) =
+                chars.next_if(|&(_, ch)| ch.is_alphanumeric() || ch == '_' || ch == '-')
+            {
+                key.push(ch);
+            }
+            if key.is_empty() {
+                return None;
+            }
+        }
+        keys.push((key, start));
+        skip_blanks(&mut chars);
+        match chars.next() {
+            None => return Some(keys),
+            Some((_, '.')) => {}
+            Some(_) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(tree: &DocumentTree) -> Vec<String> {
+        (0..tree.nodes.len())
+            .map(|idx| {
+                let node = &tree.nodes[idx];
+                format!("{} {} {}", tree.path(idx), node.start.line, node.end_line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_json_with_positions_and_paths() {
+        let text = "{\n  // build settings\n  \"name\": \"demo\",\n  \"a b\": [1, {\"x\": true}],\n  \"deps\": {\n    \"serde\": \"1\",\n  },\n}\n";
+        let tree = DocumentTree::parse("json", text).unwrap().unwrap();
+        assert_eq!(
+            outline(&tree),
+            [
+                "$ 0 7",
+                "$.name 2 2",
+                "$[\"a b\"] 3 3",
+                "$[\"a b\"][0] 3 3",
+                "$[\"a b\"][1] 3 3",
+                "$[\"a b\"][1].x 3 3",
+                "$.deps 4 6",
+                "$.deps.serde 5 5",
+            ]
+        );
+        assert_eq!(tree.nodes[1].value, "\"demo\"");
+        assert_eq!(tree.nodes[6].children, 1);
+        assert_eq!(tree.node_at(Cursor::new(3, 13)), Some(4));
+        assert_eq!(tree.node_at(Cursor::new(5, 20)), Some(7));
+        assert!(!tree.is_visible(7, |idx| idx == 6));
+
+        let error = DocumentTree::parse_json("{\n  \"a\" 1\n}").unwrap_err();
+        assert_eq!((error.line, error.message.as_str()), (1, "expected `:`"));
+        assert!(DocumentTree::parse("rust", "").is_none());
+    }
+
+    #[test]
+    fn parses_block_yaml() {
+        let text = "\
+# workflow
+name: CI # comment
+on: [push]
+jobs:
+  build:
+    steps:
+    - uses: actions/checkout@v4
+    - run: |
+        cargo test
+        cargo build
+      env:
+        \"RUST LOG\": debug
+    -
+      - nested
+list:
+- a
+- 'b: c'
+";
+        let tree = DocumentTree::parse_yaml(text);
+        assert_eq!(
+            outline(&tree),
+            [
+                "$ 0 16",
+                "$.name 1 1",
+                "$.on 2 2",
+                "$.jobs 3 13",
+                "$.jobs.build 4 13",
+                "$.jobs.build.steps 5 13",
+                "$.jobs.build.steps[0] 6 6",
+                "$.jobs.build.steps[0].uses 6 6",
+                "$.jobs.build.steps[1] 7 11",
+                "$.jobs.build.steps[1].run 7 9",
+                "$.jobs.build.steps[1].env 10 11",
+                "$.jobs.build.steps[1].env[\"RUST LOG\"] 11 11",
+                "$.jobs.build.steps[2] 12 13",
+                "$.jobs.build.steps[2][0] 13 13",
+                "$.list 14 16",
+                "$.list[0] 15 15",
+                "$.list[1] 16 16",
+            ]
+        );
+        assert_eq!(tree.nodes[1].value, "CI");
+        assert_eq!(tree.nodes[2].value, "[push]");
+        assert_eq!(tree.nodes[7].start, Cursor::new(6, 6));
+        assert_eq!(tree.nodes[16].value, "'b: c'");
+        assert_eq!(tree.nodes[14].kind, NodeKind::Array);
+        assert_eq!(tree.node_at(Cursor::new(9, 3)), Some(9));
+    }
+
+    #[test]
+    fn parses_toml_tables_and_dotted_keys() {
+        let text = "\
+name = \"demo\" # comment
+[package]
+edition = '2021'
+metadata.docs.\"all features\" = true
+[dependencies]
+serde = { version = \"1\", features = [\"derive\"] }
+tokio = [
+  \"full\", # ]
+]
+[[bin]]
+name = \"a\"
+[[bin]]
+name = \"b\"
+description = \"\"\"
+two [
+lines\"\"\"
+";
+        let tree = DocumentTree::parse("toml", text).unwrap().unwrap();
+        assert_eq!(
+            outline(&tree),
+            [
+                "$ 0 15",
+                "$.name 0 0",
+                "$.package 1 3",
+                "$.package.edition 2 2",
+                "$.package.metadata 3 3",
+                "$.package.metadata.docs 3 3",
+                "$.package.metadata.docs[\"all features\"] 3 3",
+                "$.dependencies 4 8",
+                "$.dependencies.serde 5 5",
+                "$.dependencies.tokio 6 8",
+                "$.bin 9 15",
+                "$.bin[0] 9 10",
+                "$.bin[0].name 10 10",
+                "$.bin[1] 11 15",
+                "$.bin[1].name 12 12",
+                "$.bin[1].description 13 15",
+            ]
+        );
+        assert_eq!(tree.nodes[1].value, "\"demo\"");
+        assert_eq!(tree.nodes[6].start, Cursor::new(3, 14));
+        assert_eq!(
+            tree.nodes[8].value,
+            "{ version = \"1\", features = [\"derive\"] }"
+        );
+        assert_eq!(tree.nodes[10].kind, NodeKind::Array);
+        assert_eq!(tree.nodes[2].start, Cursor::new(1, 1));
+    }
+}
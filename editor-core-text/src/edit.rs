@@ -1,3 +1,4 @@
+use crate::cursor::Cursor;
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 
@@ -62,33 +63,64 @@ impl Edit {
         }
     }
 
+    /// Where the edit starts, in chars.
+    pub fn char_idx(&self) -> usize {
+        match &self.kind {
+            EditKind::Insert { char_idx, .. }
+            | EditKind::Delete { char_idx, .. }
+            | EditKind::Replace { char_idx, .. } => *char_idx,
+        }
+    }
+
     pub fn description(&self) -> String {
+        // At most 10 chars; slicing bytes would split multi-byte chars.
+        fn short(text: &str) -> &str {
+            text.char_indices()
+                .nth(10)
+                .map_or(text, |(byte_idx, _)| &text[..byte_idx])
+        }
         match &self.kind {
-            EditKind::Insert { text, .. } => format!(
-                "Insert '{}'",
-                if text.len() > 10 { &text[..10] } else { text }
-            ),
-            EditKind::Delete { text, .. } => format!(
-                "Delete '{}'",
-                if text.len() > 10 { &text[..10] } else { text }
-            ),
+            EditKind::Insert { text, .. } => format!("Insert '{}'", short(text)),
+            EditKind::Delete { text, .. } => format!("Delete '{}'", short(text)),
             EditKind::Replace {
                 old_text, new_text, ..
-            } => {
-                format!(
-                    "Replace '{}' with '{}'",
-                    if old_text.len() > 10 {
-                        &old_text[..10]
-                    } else {
-                        old_text
-                    },
-                    if new_text.len() > 10 {
-                        &new_text[..10]
-                    } else {
-                        new_text
-                    }
-                )
-            }
+            } => format!("Replace '{}' with '{}'", short(old_text), short(new_text)),
+        }
+    }
+}
+
+/// An edit applied to a [`TextModel`](crate::TextModel), tagged with the model version
+/// before and after it.
+///
+/// `start` and `old_end` are positions in the text *before* the edit, so consumers can
+/// patch their own copy (view lines, LSP document, highlight cache) without re-reading
+/// the whole buffer.
+#[derive(Debug, Clone)]
+pub struct TextChange {
+    pub edit: Edit,
+    pub before_version: usize,
+    pub after_version: usize,
+    pub start: Cursor,
+    pub old_end: Cursor,
+    /// `start` and `old_end` with UTF-16 columns, as LSP positions count them.
+    pub utf16_start: Cursor,
+    pub utf16_old_end: Cursor,
+}
+
+impl TextChange {
+    pub fn old_text(&self) -> &str {
+        match &self.edit.kind {
+            EditKind::Insert { .. } => "",
+            EditKind::Delete { text, .. } => text,
+            EditKind::Replace { old_text, .. } => old_text,
+        }
+    }
+
+    pub fn new_text(&self) -> &str {
+        match &self.edit.kind {
+            EditKind::Insert { text, .. } => text,
+            EditKind::Delete { .. } => "",
+            EditKind::Replace { new_text, .. } => new_text,
         }
     }
 }
@@ -0,0 +1,450 @@
+use crate::edit::{Edit, EditKind};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use thiserror::Error;
+
+/// Every edit made to a text since recording started, with the text at that point, so
+/// the text can be rebuilt step by step from a bug report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditLog {
+    pub initial_text: String,
+    /// Model version when recording started; entries continue from here.
+    pub initial_version: usize,
+    pub entries: Vec<EditLogEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditLogEntry {
+    /// Model version after the edit.
+    pub version: usize,
+    pub edit: Edit,
+}
+
+/// First line of a journal file; the entries follow one per line.
+#[derive(Serialize, Deserialize)]
+struct JournalHeader {
+    initial_text: String,
+    initial_version: usize,
+}
+
+enum JournalWrite {
+    Entry(EditLogEntry),
+    /// Answered once everything before it is on disk.
+    Flush(mpsc::Sender<()>),
+}
+
+/// An [`EditLog`] written to a file as it is recorded, one JSON line per edit, so
+/// the edits survive a crash. Read it back with [`EditLog::from_journal`].
+///
+/// Entries are handed to a writer thread, so recording an edit never waits on the
+/// disk. The file is only created with the first edit; a text that is never edited
+/// is never written. Dropping the journal waits for the entries still queued.
+#[derive(Debug)]
+pub struct EditJournal {
+    path: PathBuf,
+    writes: Option<mpsc::Sender<JournalWrite>>,
+    writer: Option<JoinHandle<std::io::Result<()>>>,
+}
+
+impl EditJournal {
+    /// Starts a journal at `path` from `initial_text`. A file already there is
+    /// replaced when the first edit is written.
+    pub fn create(
+        path: &Path,
+        initial_text: &str,
+        initial_version: usize,
+    ) -> std::io::Result<Self> {
+        let mut header = serde_json::to_vec(&JournalHeader {
+            initial_text: initial_text.to_string(),
+            initial_version,
+        })?;
+        header.push(b'\n');
+        let (writes, queued) = mpsc::channel();
+        let file = path.to_path_buf();
+        let writer = std::thread::Builder::new()
+            .name("edit-journal".to_string())
+            .spawn(move || write_journal(&file, &header, queued))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writes: Some(writes),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queues `edit` for writing. Fails once the writer has stopped on an error.
+    pub fn append(&mut self, version: usize, edit: &Edit) -> std::io::Result<()> {
+        let entry = EditLogEntry {
+            version,
+            edit: edit.clone(),
+        };
+        self.send(JournalWrite::Entry(entry))
+    }
+
+    /// Waits until every edit appended so far is written to the file.
+    pub fn flush(&self) -> std::io::Result<()> {
+        let (done, flushed) = mpsc::channel();
+        self.send(JournalWrite::Flush(done))?;
+        flushed.recv().map_err(|_| writer_stopped())
+    }
+
+    fn send(&self, write: JournalWrite) -> std::io::Result<()> {
+        self.writes
+            .as_ref()
+            .and_then(|writes| writes.send(write).ok())
+            .ok_or_else(writer_stopped)
+    }
+}
+
+impl Drop for EditJournal {
+    fn drop(&mut self) {
+        drop(self.writes.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn writer_stopped() -> std::io::Error {
+    std::io::Error::other("Edit journal writer stopped")
+}
+
+/// Writes queued entries one line each, flushing whenever the queue runs dry, so a
+/// crash can at worst cut the last line short.
+fn write_journal(
+    path: &Path,
+    header: &[u8],
+    queued: mpsc::Receiver<JournalWrite>,
+) -> std::io::Result<()> {
+    let mut file: Option<BufWriter<File>> = None;
+    while let Ok(first) = queued.recv() {
+        let mut next = Some(first);
+        while let Some(write) = next {
+            match write {
+                JournalWrite::Entry(entry) => {
+                    let file = match &mut file {
+                        Some(file) => file,
+                        None => {
+                            let mut created = BufWriter::new(File::create(path)?);
+                            created.write_all(header)?;
+                            file.insert(created)
+                        }
+                    };
+                    let mut line = serde_json::to_vec(&entry)?;
+                    line.push(b'\n');
+                    file.write_all(&line)?;
+                }
+                JournalWrite::Flush(done) => {
+                    if let Some(file) = &mut file {
+                        file.flush()?;
+                    }
+                    let _ = done.send(());
+                }
+            }
+            next = queued.try_recv().ok();
+        }
+        if let Some(file) = &mut file {
+            file.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("Step {step}: expected version {expected}, the log has {found}; edits are missing")]
+    MissingEdits {
+        step: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Step {step}: edit at char {char_idx} is past the end of the text ({len} chars)")]
+    OutOfBounds {
+        step: usize,
+        char_idx: usize,
+        len: usize,
+    },
+    #[error("Step {step}: expected {expected:?} at char {char_idx}, found {found:?}")]
+    TextMismatch {
+        step: usize,
+        char_idx: usize,
+        expected: String,
+        found: String,
+    },
+    #[error("Invalid edit log: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+impl EditLog {
+    pub fn new(initial_text: &str, initial_version: usize) -> Self {
+        Self {
+            initial_text: initial_text.to_string(),
+            initial_version,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, version: usize, edit: &Edit) {
+        self.entries.push(EditLogEntry {
+            version,
+            edit: edit.clone(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Rough heap use: the initial text plus the text of every edit.
+    pub fn approximate_bytes(&self) -> usize {
+        let edits: usize = self
+            .entries
+            .iter()
+            .map(|entry| match &entry.edit.kind {
+                EditKind::Insert { text, .. } | EditKind::Delete { text, .. } => text.len(),
+                EditKind::Replace {
+                    old_text, new_text, ..
+                } => old_text.len() + new_text.len(),
+            })
+            .sum();
+        self.initial_text.len() + edits + self.entries.len() * std::mem::size_of::<EditLogEntry>()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Reads a journal written by [`EditJournal`]. A last line cut short by a crash is
+    /// dropped; anything else that doesn't parse is an error.
+    pub fn from_journal(text: &str) -> Result<Self, ReplayError> {
+        let mut lines = text.lines().filter(|line| !line.is_empty()).peekable();
+        let header: JournalHeader = serde_json::from_str(lines.next().unwrap_or_default())?;
+        let mut log = Self::new(&header.initial_text, header.initial_version);
+        while let Some(line) = lines.next() {
+            match serde_json::from_str(line) {
+                Ok(entry) => log.entries.push(entry),
+                Err(_) if lines.peek().is_none() && !text.ends_with('\n') => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(log)
+    }
+
+    /// The text after every edit that replays cleanly, and the error that stopped the
+    /// replay early, if any.
+    pub fn final_text(&self) -> (String, Option<ReplayError>) {
+        let mut replay = self.replay();
+        loop {
+            match replay.apply_next() {
+                Ok(Some(_)) => {}
+                Ok(None) => return (replay.text(), None),
+                Err(e) => return (replay.text(), Some(e)),
+            }
+        }
+    }
+
+    pub fn replay(&self) -> EditReplay<'_> {
+        EditReplay {
+            log: self,
+            rope: Rope::from_str(&self.initial_text),
+            step: 0,
+        }
+    }
+
+    /// The text after the first `step` edits.
+    pub fn text_at(&self, step: usize) -> Result<String, ReplayError> {
+        let mut replay = self.replay();
+        while replay.step() < step && replay.apply_next()?.is_some() {}
+        Ok(replay.text())
+    }
+}
+
+/// Applies the edits of an [`EditLog`] one at a time. Each edit is checked against the
+/// text it is applied to, so a log recorded from a corrupted buffer stops at the first
+/// edit that doesn't fit instead of producing garbage.
+#[derive(Debug, Clone)]
+pub struct EditReplay<'a> {
+    log: &'a EditLog,
+    rope: Rope,
+    step: usize,
+}
+
+impl<'a> EditReplay<'a> {
+    /// Number of edits applied so far.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn text(&self) -> String {
+        self.rope.to_string()
+    }
+
+    /// Applies the next edit and returns it, or `None` at the end of the log.
+    pub fn apply_next(&mut self) -> Result<Option<&'a EditLogEntry>, ReplayError> {
+        let Some(entry) = self.log.entries.get(self.step) else {
+            return Ok(None);
+        };
+        let step = self.step + 1;
+        let expected = self.log.initial_version + step;
+        if entry.version != expected {
+            return Err(ReplayError::MissingEdits {
+                step,
+                expected,
+                found: entry.version,
+            });
+        }
+
+        let (char_idx, old_text, new_text) = match &entry.edit.kind {
+            EditKind::Insert { char_idx, text } => (*char_idx, "", text.as_str()),
+            EditKind::Delete { char_idx, text } => (*char_idx, text.as_str(), ""),
+            EditKind::Replace {
+                char_idx,
+                old_text,
+                new_text,
+            } => (*char_idx, old_text.as_str(), new_text.as_str()),
+        };
+        let len = self.rope.len_chars();
+        let end = char_idx + old_text.chars().count();
+        if end > len {
+            return Err(ReplayError::OutOfBounds {
+                step,
+                char_idx,
+                len,
+            });
+        }
+        let found = self.rope.slice(char_idx..end).to_string();
+        if found != old_text {
+            return Err(ReplayError::TextMismatch {
+                step,
+                char_idx,
+                expected: old_text.to_string(),
+                found,
+            });
+        }
+
+        self.rope.remove(char_idx..end);
+        self.rope.insert(char_idx, new_text);
+        self.step = step;
+        Ok(Some(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "hello" at version 3, then " world" appended, "hello" replaced and " wor" deleted.
+    fn sample_log() -> EditLog {
+        let mut log = EditLog::new("hello", 3);
+        log.record(4, &Edit::new_insert(5, " world".to_string()));
+        log.record(
+            5,
+            &Edit::new_replace(0, "hello".to_string(), "bye".to_string()),
+        );
+        log.record(6, &Edit::new_delete(3, " wor".to_string()));
+        log
+    }
+
+    #[test]
+    fn replays_to_any_step_after_a_json_round_trip() {
+        let log = EditLog::from_json(&sample_log().to_json()).unwrap();
+        assert_eq!(log.text_at(0).unwrap(), "hello");
+        assert_eq!(log.text_at(2).unwrap(), "bye world");
+        assert_eq!(log.text_at(9).unwrap(), "byeld");
+    }
+
+    #[test]
+    fn descriptions_keep_multibyte_chars_whole() {
+        assert_eq!(
+            Edit::new_insert(0, "编辑日志的每一步都可以回放".to_string()).description(),
+            "Insert '编辑日志的每一步都可'"
+        );
+    }
+
+    #[test]
+    fn replay_stops_at_the_first_mismatch() {
+        let mut corrupt = sample_log();
+        corrupt.entries[2].edit = Edit::new_delete(2, "xyz".to_string());
+        let mut replay = corrupt.replay();
+        replay.apply_next().unwrap();
+        replay.apply_next().unwrap();
+        assert!(matches!(
+            replay.apply_next(),
+            Err(ReplayError::TextMismatch { step: 3, .. })
+        ));
+        assert_eq!(replay.text(), "bye world");
+    }
+
+    #[test]
+    fn gaps_in_the_versions_end_the_replay() {
+        let mut gapped = sample_log();
+        gapped.entries.remove(1);
+        assert!(matches!(
+            gapped.text_at(2),
+            Err(ReplayError::MissingEdits { step: 2, .. })
+        ));
+        let (text, error) = gapped.final_text();
+        assert_eq!(text, "hello world");
+        assert!(error.is_some());
+    }
+
+    /// What a journal of "a\nb" at version 7 holds after two edits.
+    fn written_journal(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("journal-{}-{}.jsonl", name, std::process::id()));
+        let mut journal = EditJournal::create(&path, "a\nb", 7).unwrap();
+        journal.flush().unwrap();
+        // Nothing is written for a text that is never edited.
+        assert!(!path.exists());
+        journal
+            .append(8, &Edit::new_insert(1, "1".to_string()))
+            .unwrap();
+        journal
+            .append(9, &Edit::new_delete(0, "a".to_string()))
+            .unwrap();
+        journal.flush().unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        drop(journal);
+        std::fs::remove_file(&path).unwrap();
+        written
+    }
+
+    #[test]
+    fn journals_read_back_as_edit_logs() {
+        let log = EditLog::from_journal(&written_journal("read")).unwrap();
+        assert_eq!((log.initial_version, log.len()), (7, 2));
+        assert_eq!(log.final_text().0, "1\nb");
+    }
+
+    #[test]
+    fn journals_lose_only_a_last_line_cut_short() {
+        let written = written_journal("cut");
+        // A crash in the middle of the last write loses only that edit.
+        let cut = &written[..written.len() - 10];
+        let log = EditLog::from_journal(cut).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log.final_text().0, "a1\nb");
+    }
+
+    #[test]
+    fn journals_with_a_garbled_line_are_rejected() {
+        let garbled = written_journal("garbled").replacen("\"version\":8", "\"version\":", 1);
+        assert!(EditLog::from_journal(&garbled).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use editor_infra::language::{LanguagePack, LANGUAGE_PACKS};
+use tree_sitter::{Language, Parser, Tree};
+
+/// The tree-sitter grammar of `language`, an LSP language id or file extension, as
+/// named by its [`LanguagePack`]. `None` for languages without a bundled grammar.
+pub fn grammar(language: &str) -> Option<Language> {
+    let language = language.trim().to_ascii_lowercase();
+    let pack = LanguagePack::for_id(&language).or_else(|| {
+        LANGUAGE_PACKS
+            .iter()
+            .find(|pack| pack.extensions.contains(&language.as_str()))
+    })?;
+    let grammar = match pack.grammar? {
+        "c" => tree_sitter_c::LANGUAGE,
+        "cpp" => tree_sitter_cpp::LANGUAGE,
+        "go" => tree_sitter_go::LANGUAGE,
+        "java" => tree_sitter_java::LANGUAGE,
+        "javascript" => tree_sitter_javascript::LANGUAGE,
+        "json" => tree_sitter_json::LANGUAGE,
+        "python" => tree_sitter_python::LANGUAGE,
+        "rust" => tree_sitter_rust::LANGUAGE,
+        "tsx" => tree_sitter_typescript::LANGUAGE_TSX,
+        "typescript" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        _ => return None,
+    };
+    Some(grammar.into())
+}
+
+/// Parses `text` as `language`. `None` without a grammar for it.
+pub fn parse(text: &str, language: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(&grammar(language)?).ok()?;
+    parser.parse(text, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_grammars_by_id_and_extension() {
+        for language in ["rust", "rs", "Python", "typescriptreact", "js", "json"] {
+            let tree = parse("x", language).unwrap();
+            assert_eq!(tree.root_node().start_byte(), 0, "{language}");
+        }
+        assert!(grammar("markdown").is_none());
+        assert!(grammar("plaintext").is_none());
+    }
+}
@@ -0,0 +1,137 @@
+/// How a file indents its lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndentStyle {
+    Tabs,
+    /// The number of spaces per level.
+    Spaces(usize),
+}
+
+impl IndentStyle {
+    pub fn from_settings(use_spaces: bool, tab_size: usize) -> Self {
+        if use_spaces {
+            IndentStyle::Spaces(tab_size.max(1))
+        } else {
+            IndentStyle::Tabs
+        }
+    }
+
+    /// The style for a file of `language` with no indentation to detect: the
+    /// language's own convention where it has one, otherwise the settings.
+    pub fn for_language(language: &str, use_spaces: bool, tab_size: usize) -> Self {
+        match editor_infra::LanguagePack::for_id(language) {
+            Some(pack) if pack.tab_indent => IndentStyle::Tabs,
+            Some(pack) => Self::from_settings(use_spaces, pack.indent_width.unwrap_or(tab_size)),
+            None => Self::from_settings(use_spaces, tab_size),
+        }
+    }
+
+    /// Guesses the style from the indentation of `text`: tabs when most indented lines
+    /// start with a tab, otherwise the most common step between the indents of
+    /// neighbouring lines. `None` when there is too little indentation to tell.
+    pub fn detect(text: &str) -> Option<Self> {
+        let mut tab_lines = 0usize;
+        let mut space_lines = 0usize;
+        // Occurrences of each step from 1 to 8 spaces.
+        let mut steps = [0usize; 9];
+        let mut previous = 0usize;
+
+        for line in text.lines() {
+            let content = line.trim_start_matches([' ', '\t']);
+            // Blank lines and the ` * ` of block comments say nothing about the style.
+            if content.is_empty() || content.starts_with('*') {
+                continue;
+            }
+            let indent = &line[..line.len() - content.len()];
+            if indent.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            if indent.contains('\t') {
+                continue;
+            }
+            let width = indent.len();
+            if width > 0 {
+                space_lines += 1;
+            }
+            let step = width.abs_diff(previous);
+            if (2..steps.len()).contains(&step) {
+                steps[step] += 1;
+            }
+            previous = width;
+        }
+
+        if tab_lines > space_lines {
+            return Some(IndentStyle::Tabs);
+        }
+        if space_lines == 0 {
+            return None;
+        }
+        // On a tie the narrower step wins, since closing several levels at once makes
+        // wide steps out of narrow indentation.
+        let (width, count) = steps
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, count)| **count)?;
+        (*count > 0).then_some(IndentStyle::Spaces(width))
+    }
+
+    /// The text inserted for one level.
+    pub fn unit(self) -> String {
+        match self {
+            IndentStyle::Tabs => "\t".to_string(),
+            IndentStyle::Spaces(width) => " ".repeat(width),
+        }
+    }
+
+    pub fn uses_spaces(self) -> bool {
+        matches!(self, IndentStyle::Spaces(_))
+    }
+
+    /// Columns per level; tabs are as wide as `tab_size`.
+    pub fn width(self, tab_size: usize) -> usize {
+        match self {
+            IndentStyle::Tabs => tab_size,
+            IndentStyle::Spaces(width) => width,
+        }
+    }
+
+    /// Short name for the status bar.
+    pub fn label(self) -> String {
+        match self {
+            IndentStyle::Tabs => "Tabs".to_string(),
+            IndentStyle::Spaces(width) => format!("Spaces: {}", width),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_tabs_and_space_widths() {
+        let four = "fn main() {\n    if x {\n        y();\n    }\n}\n";
+        assert_eq!(IndentStyle::detect(four), Some(IndentStyle::Spaces(4)));
+
+        let two = "a:\n  b:\n    c: 1\n  d: 2\n\n  e:\n    f: 3\n";
+        assert_eq!(IndentStyle::detect(two), Some(IndentStyle::Spaces(2)));
+
+        let tabs = "func main() {\n\tif x {\n\t\ty()\n\t}\n}\n/**\n * doc\n */\n";
+        assert_eq!(IndentStyle::detect(tabs), Some(IndentStyle::Tabs));
+
+        assert_eq!(IndentStyle::detect("one\ntwo\n"), None);
+        assert_eq!(IndentStyle::Spaces(2).unit(), "  ");
+        assert_eq!(IndentStyle::Tabs.width(8), 8);
+        assert_eq!(IndentStyle::from_settings(false, 4), IndentStyle::Tabs);
+        assert_eq!(IndentStyle::for_language("go", true, 4), IndentStyle::Tabs);
+        assert_eq!(
+            IndentStyle::for_language("yaml", true, 4),
+            IndentStyle::Spaces(2)
+        );
+        assert_eq!(
+            IndentStyle::for_language("plaintext", true, 3),
+            IndentStyle::Spaces(3)
+        );
+    }
+}
@@ -1,13 +1,53 @@
+pub mod arguments;
+pub mod brackets;
 pub mod buffer;
+pub mod case;
+pub mod clipboard;
+pub mod clock;
+pub mod crdt;
 pub mod cursor;
+pub mod delimited;
+pub mod diff;
+pub mod document_tree;
 pub mod edit;
+pub mod edit_log;
+pub mod grammar;
+pub mod indent;
+pub mod line_ending;
+pub mod line_map;
+pub mod marks;
+pub mod navigation;
 pub mod rope_ext;
+pub mod search;
 pub mod selection;
+pub mod snapshot;
+pub mod stats;
+pub mod syntax;
 pub mod text_model;
+pub mod wrap;
 
-pub use buffer::Buffer;
+pub use arguments::{ArgumentLayout, ArgumentList};
+pub use buffer::{Buffer, BufferMemory, LineDirection};
+pub use case::CaseTransform;
+pub use clipboard::{ClipboardEntry, ClipboardRing};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use crdt::{OpId, RemoteOp, ReplicaId, TextCrdt};
 pub use cursor::{Cursor, CursorMovement};
-pub use edit::{Edit, EditKind};
+pub use delimited::{detect_delimiter, quote_field, Table, TableCell};
+pub use diff::{LineChange, LineChangeKind, LineDiff, WordDiff};
+pub use document_tree::{DocumentTree, NodeKind, PathSegment, TreeError, TreeNode};
+pub use edit::{Edit, EditKind, TextChange};
+pub use edit_log::{EditJournal, EditLog, EditLogEntry, EditReplay, ReplayError};
+pub use indent::IndentStyle;
+pub use line_ending::LineEnding;
+pub use line_map::LineMap;
+pub use marks::{Anchor, Anchors, Bias, Mark, MarkName, Marks, TrackedRanges};
+pub use navigation::NavigationHistory;
 pub use rope_ext::RopeExt;
+pub use search::{SearchError, SearchMatch, SearchOptions, SearchQuery};
 pub use selection::Selection;
-pub use text_model::TextModel;
+pub use snapshot::BufferSnapshot;
+pub use stats::BufferStats;
+pub use syntax::{Highlighter, SyntaxSpan, TokenKind};
+pub use text_model::{EditError, EditMap, TextModel};
+pub use wrap::{VisualRow, WrapLayout};
@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+
+/// The line break a buffer writes, detected from the file it was opened from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The ending used by most lines of `text`; `Lf` when there are none or it is a tie.
+    pub fn detect(text: &str) -> Self {
+        let line_breaks = text.matches('\n').count();
+        let crlf = text.matches("\r\n").count();
+        if crlf * 2 > line_breaks {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// Short name for the status bar.
+    pub fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            LineEnding::Lf => LineEnding::CrLf,
+            LineEnding::CrLf => LineEnding::Lf,
+        }
+    }
+
+    /// Rewrites every line break in `text` to this ending. Borrows when nothing changes.
+    pub fn normalize(self, text: &str) -> Cow<'_, str> {
+        match self {
+            LineEnding::Lf if text.contains("\r\n") => Cow::Owned(text.replace("\r\n", "\n")),
+            LineEnding::CrLf if text.matches('\n').count() != text.matches("\r\n").count() => {
+                Cow::Owned(text.replace("\r\n", "\n").replace('\n', "\r\n"))
+            }
+            _ => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// Whether `ch` ends a line in the rope: besides `\n` and `\r`, ropey also breaks
+/// lines on vertical tab, form feed, NEL and the Unicode line and paragraph separators.
+pub fn is_line_break(ch: char) -> bool {
+    matches!(
+        ch,
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+/// `line` without the one line break it ends with, `\r\n` counting as one.
+pub fn strip_line_break(line: &str) -> &str {
+    if let Some(line) = line.strip_suffix("\r\n") {
+        return line;
+    }
+    match line.chars().next_back() {
+        Some(ch) if is_line_break(ch) => &line[..line.len() - ch.len_utf8()],
+        _ => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_majority_and_normalizes_mixed_text() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("no breaks"), LineEnding::Lf);
+
+        let mixed = "a\r\nb\nc";
+        assert_eq!(LineEnding::Lf.normalize(mixed), "a\nb\nc");
+        assert_eq!(LineEnding::CrLf.normalize(mixed), "a\r\nb\r\nc");
+        assert!(matches!(
+            LineEnding::CrLf.normalize("a\r\nb"),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(LineEnding::Lf.normalize("a\nb"), Cow::Borrowed(_)));
+
+        assert_eq!(strip_line_break("a\r\n"), "a");
+        assert_eq!(strip_line_break("a\n\n"), "a\n");
+        assert_eq!(strip_line_break("a\u{2028}"), "a");
+        assert_eq!(strip_line_break("a\r"), "a");
+        assert_eq!(strip_line_break("a"), "a");
+    }
+}
@@ -0,0 +1,104 @@
+use crate::cursor::Cursor;
+use similar::{DiffTag, TextDiff};
+use std::ops::Range;
+
+/// Maps line positions in one version of a text to another, using a line diff.
+///
+/// Unchanged lines keep their column; positions inside a rewritten block move to the
+/// start of its replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMap {
+    hunks: Vec<Hunk>,
+    old_len: usize,
+    new_len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hunk {
+    old: Range<usize>,
+    new: Range<usize>,
+    equal: bool,
+}
+
+impl LineMap {
+    pub fn new(old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_lines(old, new);
+        let hunks = diff
+            .ops()
+            .iter()
+            .map(|op| {
+                let (tag, old, new) = op.as_tag_tuple();
+                Hunk {
+                    old,
+                    new,
+                    equal: tag == DiffTag::Equal,
+                }
+            })
+            .collect();
+
+        Self {
+            hunks,
+            old_len: diff.old_slices().len(),
+            new_len: diff.new_slices().len(),
+        }
+    }
+
+    /// Whether nothing changed.
+    pub fn is_identity(&self) -> bool {
+        self.hunks.iter().all(|hunk| hunk.equal)
+    }
+
+    pub fn map_line(&self, line: usize) -> usize {
+        self.map_cursor(Cursor::new(line, 0)).line
+    }
+
+    /// Where `cursor` ends up in the new text. The column may need clamping to the line.
+    pub fn map_cursor(&self, cursor: Cursor) -> Cursor {
+        if cursor.line >= self.old_len {
+            return Cursor::new(self.new_len + (cursor.line - self.old_len), cursor.column);
+        }
+        let Some(hunk) = self
+            .hunks
+            .iter()
+            .find(|hunk| hunk.old.contains(&cursor.line))
+        else {
+            return cursor;
+        };
+        if hunk.equal {
+            Cursor::new(
+                hunk.new.start + (cursor.line - hunk.old.start),
+                cursor.column,
+            )
+        } else {
+            Cursor::new(hunk.new.start, 0)
+        }
+    }
+
+    /// Changed blocks as `(old lines, new lines)`, front to back.
+    pub fn changed_hunks(&self) -> impl Iterator<Item = (Range<usize>, Range<usize>)> + '_ {
+        self.hunks
+            .iter()
+            .filter(|hunk| !hunk.equal)
+            .map(|hunk| (hunk.old.clone(), hunk.new.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_unchanged_lines_across_insertions_and_deletions() {
+        let old = "a\nb\nc\nd\n";
+        let new = "header\na\nc\nD\n";
+        let map = LineMap::new(old, new);
+
+        assert!(!map.is_identity());
+        assert_eq!(map.map_cursor(Cursor::new(0, 1)), Cursor::new(1, 1));
+        assert_eq!(map.map_cursor(Cursor::new(1, 1)), Cursor::new(2, 0));
+        assert_eq!(map.map_cursor(Cursor::new(2, 1)), Cursor::new(2, 1));
+        assert_eq!(map.map_cursor(Cursor::new(3, 1)), Cursor::new(3, 0));
+        assert_eq!(map.map_line(4), 4);
+        assert!(LineMap::new(old, old).is_identity());
+    }
+}
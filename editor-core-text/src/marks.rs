@@ -0,0 +1,378 @@
+use crate::edit::EditKind;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Anchors are numbered across all texts, so one from another text is never mistaken
+/// for one of this text's.
+static NEXT_ANCHOR: AtomicU64 = AtomicU64::new(1);
+
+/// Name of a bookmark: a digit bound to a quick key, or a name chosen by the user.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MarkName {
+    Number(u8),
+    Named(String),
+}
+
+impl fmt::Display for MarkName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkName::Number(number) => write!(f, "{}", number),
+            MarkName::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mark {
+    pub name: MarkName,
+    pub char_idx: usize,
+}
+
+/// Bookmarks of one text, kept as char indices and moved along with every edit.
+///
+/// Text inserted at a mark pushes it forward, so a mark at the start of a line stays
+/// with that line when a line is inserted above it. A mark inside deleted text moves to
+/// where the deletion happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Marks {
+    /// Sorted by position, then by name.
+    marks: Vec<Mark>,
+}
+
+impl Marks {
+    /// Sets `name` at `char_idx`, moving it if it is already set.
+    pub fn set(&mut self, name: MarkName, char_idx: usize) {
+        self.marks.retain(|mark| mark.name != name);
+        self.marks.push(Mark { name, char_idx });
+        self.sort();
+    }
+
+    pub fn remove(&mut self, name: &MarkName) -> bool {
+        let len = self.marks.len();
+        self.marks.retain(|mark| &mark.name != name);
+        self.marks.len() != len
+    }
+
+    pub fn get(&self, name: &MarkName) -> Option<usize> {
+        self.marks
+            .iter()
+            .find(|mark| &mark.name == name)
+            .map(|mark| mark.char_idx)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Mark> {
+        self.marks.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// The lowest digit not yet in use, trying 1 to 9 before 0.
+    pub fn free_number(&self) -> Option<u8> {
+        (1..=9)
+            .chain([0])
+            .find(|number| self.get(&MarkName::Number(*number)).is_none())
+    }
+
+    /// The first mark after `char_idx` (or before it when going backwards), wrapping
+    /// around the text.
+    pub fn next(&self, char_idx: usize, forward: bool) -> Option<&Mark> {
+        if forward {
+            self.marks
+                .iter()
+                .find(|mark| mark.char_idx > char_idx)
+                .or_else(|| self.marks.first())
+        } else {
+            self.marks
+                .iter()
+                .rev()
+                .find(|mark| mark.char_idx < char_idx)
+                .or_else(|| self.marks.last())
+        }
+    }
+
+    /// Shifts the marks past an edit that has already been applied to the text.
+    pub fn apply(&mut self, edit: &EditKind) {
+        let span = edit_span(edit);
+        if self.marks.is_empty() || (span.1 == 0 && span.2 == 0) {
+            return;
+        }
+        for mark in &mut self.marks {
+            mark.char_idx = shift(mark.char_idx, span, true);
+        }
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        self.marks
+            .sort_by(|a, b| (a.char_idx, &a.name).cmp(&(b.char_idx, &b.name)));
+    }
+}
+
+/// Char ranges of one text that move with its edits, e.g. the code an AI conversation
+/// is about. Text inserted right at either end stays outside the range, so it only
+/// grows when edited inside; a range whose text is deleted collapses to where the
+/// deletion happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackedRanges {
+    next_id: u64,
+    ranges: Vec<(u64, Range<usize>)>,
+}
+
+impl TrackedRanges {
+    /// Starts tracking `range` and returns the id to look it up by.
+    pub fn track(&mut self, range: Range<usize>) -> u64 {
+        self.next_id += 1;
+        self.ranges.push((self.next_id, range));
+        self.next_id
+    }
+
+    pub fn get(&self, id: u64) -> Option<Range<usize>> {
+        self.ranges
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, range)| range.clone())
+    }
+
+    pub fn remove(&mut self, id: u64) -> bool {
+        let len = self.ranges.len();
+        self.ranges.retain(|(other, _)| *other != id);
+        self.ranges.len() != len
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Shifts the ranges past an edit that has already been applied to the text.
+    pub fn apply(&mut self, edit: &EditKind) {
+        let span = edit_span(edit);
+        for (_, range) in &mut self.ranges {
+            let start = shift(range.start, span, true);
+            range.end = shift(range.end, span, false).max(start);
+            range.start = start;
+        }
+    }
+}
+
+/// Which side of text inserted right at an [`Anchor`] the anchor ends up on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bias {
+    /// Stays before the inserted text, e.g. the end of a diagnostic's range.
+    Left,
+    /// Moves past the inserted text, like a bookmark at the start of a line.
+    Right,
+}
+
+/// A position in a text that moves with its edits, registered with
+/// [`crate::TextModel::update_anchors`]. Look its position up in [`Anchors`] each time
+/// it's needed; it stays valid until removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Anchor(u64);
+
+/// The anchors of one text, kept as char indices. An anchor inside deleted text moves
+/// to where the deletion happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Anchors {
+    anchors: Vec<(Anchor, usize, Bias)>,
+}
+
+impl Anchors {
+    pub fn create(&mut self, char_idx: usize, bias: Bias) -> Anchor {
+        let anchor = Anchor(NEXT_ANCHOR.fetch_add(1, Ordering::Relaxed));
+        self.anchors.push((anchor, char_idx, bias));
+        anchor
+    }
+
+    pub fn get(&self, anchor: Anchor) -> Option<usize> {
+        self.anchors
+            .iter()
+            .find(|(other, _, _)| *other == anchor)
+            .map(|(_, char_idx, _)| *char_idx)
+    }
+
+    pub fn remove(&mut self, anchor: Anchor) -> bool {
+        let len = self.anchors.len();
+        self.anchors.retain(|(other, _, _)| *other != anchor);
+        self.anchors.len() != len
+    }
+
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Shifts the anchors past an edit that has already been applied to the text.
+    pub fn apply(&mut self, edit: &EditKind) {
+        let span = edit_span(edit);
+        for (_, char_idx, bias) in &mut self.anchors {
+            *char_idx = shift(*char_idx, span, *bias == Bias::Right);
+        }
+    }
+}
+
+/// Where an edit starts, how many chars it removed and how many it inserted.
+fn edit_span(edit: &EditKind) -> (usize, usize, usize) {
+    match edit {
+        EditKind::Insert { char_idx, text } => (*char_idx, 0, text.chars().count()),
+        EditKind::Delete { char_idx, text } => (*char_idx, text.chars().count(), 0),
+        EditKind::Replace {
+            char_idx,
+            old_text,
+            new_text,
+        } => (
+            *char_idx,
+            old_text.chars().count(),
+            new_text.chars().count(),
+        ),
+    }
+}
+
+/// Moves `position` past an edit. A pure insertion exactly at `position` pushes it
+/// forward only if `push_at_insert`; a position inside removed text moves to its start.
+fn shift(
+    position: usize,
+    (start, removed, inserted): (usize, usize, usize),
+    push_at_insert: bool,
+) -> usize {
+    let pushed = removed > 0 || position > start || push_at_insert;
+    if position >= start + removed && pushed {
+        position - removed + inserted
+    } else if position > start {
+        start
+    } else {
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(marks: &Marks) -> Vec<(String, usize)> {
+        marks
+            .iter()
+            .map(|mark| (mark.name.to_string(), mark.char_idx))
+            .collect()
+    }
+
+    #[test]
+    fn marks_follow_edits_and_wrap_when_jumping() {
+        let mut marks = Marks::default();
+        marks.set(MarkName::Number(1), 10);
+        marks.set(MarkName::Named("todo".to_string()), 4);
+        marks.set(MarkName::Number(2), 20);
+        assert_eq!(marks.free_number(), Some(3));
+
+        marks.apply(&EditKind::Insert {
+            char_idx: 4,
+            text: "ab".to_string(),
+        });
+        assert_eq!(
+            positions(&marks),
+            vec![("todo".into(), 6), ("1".into(), 12), ("2".into(), 22)]
+        );
+
+        marks.apply(&EditKind::Delete {
+            char_idx: 8,
+            text: "abcdef".to_string(),
+        });
+        assert_eq!(
+            positions(&marks),
+            vec![("todo".into(), 6), ("1".into(), 8), ("2".into(), 16)]
+        );
+
+        marks.apply(&EditKind::Replace {
+            char_idx: 0,
+            old_text: "x".repeat(10),
+            new_text: "y".to_string(),
+        });
+        assert_eq!(
+            positions(&marks),
+            vec![("1".into(), 0), ("todo".into(), 0), ("2".into(), 7)]
+        );
+
+        assert_eq!(marks.next(0, true).unwrap().char_idx, 7);
+        assert_eq!(marks.next(7, true).unwrap().char_idx, 0);
+        assert_eq!(marks.next(0, false).unwrap().name, MarkName::Number(2));
+
+        marks.set(MarkName::Number(1), 3);
+        assert_eq!(marks.get(&MarkName::Number(1)), Some(3));
+        assert_eq!(marks.len(), 3);
+        assert!(marks.remove(&MarkName::Named("todo".to_string())));
+        assert!(!marks.remove(&MarkName::Named("todo".to_string())));
+    }
+
+    #[test]
+    fn tracked_ranges_only_grow_from_edits_inside_them() {
+        let mut ranges = TrackedRanges::default();
+        let id = ranges.track(10..20);
+        let insert = |char_idx: usize, text: &str| EditKind::Insert {
+            char_idx,
+            text: text.to_string(),
+        };
+
+        ranges.apply(&insert(10, "ab"));
+        ranges.apply(&insert(22, "cd"));
+        assert_eq!(ranges.get(id), Some(12..22));
+        ranges.apply(&insert(15, "xyz"));
+        assert_eq!(ranges.get(id), Some(12..25));
+
+        ranges.apply(&EditKind::Replace {
+            char_idx: 20,
+            old_text: "x".repeat(5),
+            new_text: "y".to_string(),
+        });
+        assert_eq!(ranges.get(id), Some(12..21));
+        ranges.apply(&EditKind::Delete {
+            char_idx: 5,
+            text: "z".repeat(30),
+        });
+        assert_eq!(ranges.get(id), Some(5..5));
+
+        assert!(ranges.remove(id));
+        assert!(ranges.get(id).is_none() && ranges.is_empty());
+    }
+
+    #[test]
+    fn anchors_keep_their_side_of_insertions() {
+        let mut anchors = Anchors::default();
+        let before = anchors.create(10, Bias::Left);
+        let after = anchors.create(10, Bias::Right);
+        let later = anchors.create(30, Bias::Left);
+
+        anchors.apply(&EditKind::Insert {
+            char_idx: 10,
+            text: "abc".to_string(),
+        });
+        assert_eq!(anchors.get(before), Some(10));
+        assert_eq!(anchors.get(after), Some(13));
+        assert_eq!(anchors.get(later), Some(33));
+
+        anchors.apply(&EditKind::Replace {
+            char_idx: 12,
+            old_text: "x".repeat(5),
+            new_text: "yy".to_string(),
+        });
+        assert_eq!(anchors.get(after), Some(12));
+        assert_eq!(anchors.get(later), Some(30));
+
+        assert!(anchors.remove(before));
+        assert!(anchors.get(before).is_none());
+        assert_eq!(anchors.len(), 2);
+        assert!(Anchors::default().get(after).is_none());
+    }
+}
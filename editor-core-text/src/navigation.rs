@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+
+const DEFAULT_CAPACITY: usize = 50;
+
+/// Places left by jumps, most recent last, to go back and forward through like a
+/// browser history. The oldest places are dropped past the capacity.
+#[derive(Debug, Clone)]
+pub struct NavigationHistory<T> {
+    back: VecDeque<T>,
+    forward: Vec<T>,
+    capacity: usize,
+}
+
+impl<T: PartialEq> NavigationHistory<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            back: VecDeque::new(),
+            forward: Vec::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Remembers `place` as left by a new jump. The places to go forward to are dropped.
+    pub fn push(&mut self, place: T) {
+        self.forward.clear();
+        if self.back.back() == Some(&place) {
+            return;
+        }
+        if self.back.len() == self.capacity {
+            self.back.pop_front();
+        }
+        self.back.push_back(place);
+    }
+
+    /// The place left before `current`, which becomes the next place forward.
+    pub fn back(&mut self, current: T) -> Option<T> {
+        let place = self.back.pop_back()?;
+        self.forward.push(current);
+        Some(place)
+    }
+
+    /// The place last gone back from, undoing [`NavigationHistory::back`].
+    pub fn forward(&mut self, current: T) -> Option<T> {
+        let place = self.forward.pop()?;
+        self.back.push_back(current);
+        Some(place)
+    }
+
+    /// Drops the places `keep` rejects, e.g. those in a closed file.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        self.back.retain(&mut keep);
+        self.forward.retain(keep);
+    }
+
+    /// Updates every place, e.g. after the file of some of them was renamed.
+    pub fn for_each_mut(&mut self, mut update: impl FnMut(&mut T)) {
+        self.back.iter_mut().for_each(&mut update);
+        self.forward.iter_mut().for_each(update);
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+}
+
+impl<T: PartialEq> Default for NavigationHistory<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goes_back_and_forward_and_drops_forward_places_on_a_new_jump() {
+        let mut history = NavigationHistory::new(3);
+        for place in [1, 2, 2, 3, 4] {
+            history.push(place);
+        }
+        // The oldest place is dropped; the repeated one is kept once.
+        assert_eq!(history.back(5), Some(4));
+        assert_eq!(history.back(4), Some(3));
+        assert_eq!(history.back(3), Some(2));
+        assert_eq!(history.back(2), None);
+        assert_eq!(history.forward(2), Some(3));
+        assert_eq!(history.forward(3), Some(4));
+
+        history.back(4);
+        history.push(9);
+        assert!(!history.can_go_forward());
+        history.retain(|&place| place != 9);
+        assert_eq!(history.back(7), Some(2));
+    }
+}
@@ -1,9 +1,56 @@
-use ropey::Rope;
+use crate::cursor::Cursor;
+use crate::line_ending::strip_line_break;
+use ropey::{Rope, RopeSlice};
+use std::borrow::Cow;
+use std::ops::Range;
 
 pub trait RopeExt {
     fn to_string(&self) -> String;
     fn get_line_length(&self, line_idx: usize) -> Option<usize>;
     fn get_line_content(&self, line_idx: usize) -> Option<String>;
+    /// The UTF-16 column of a char column, as used by LSP positions. Columns past the end
+    /// of the line are clamped to it.
+    fn char_to_utf16_col(&self, line_idx: usize, char_col: usize) -> usize;
+    /// The char column of a UTF-16 column. A column inside a surrogate pair maps to the
+    /// char containing it.
+    fn utf16_to_char_col(&self, line_idx: usize, utf16_col: usize) -> usize;
+    /// Byte offset of `cursor` in the whole text, clamped like [`Self::char_to_utf16_col`].
+    fn cursor_to_byte(&self, cursor: Cursor) -> usize;
+    fn byte_to_cursor(&self, byte_idx: usize) -> Cursor;
+
+    // Unlike the `Rope` methods of the same shape, the conversions below clamp
+    // out-of-range indices to the end of the text instead of panicking. A byte index
+    // inside a multi-byte char maps to that char.
+    fn byte_to_char_clamped(&self, byte_idx: usize) -> usize;
+    fn char_to_byte_clamped(&self, char_idx: usize) -> usize;
+    fn char_to_line_clamped(&self, char_idx: usize) -> usize;
+    fn line_to_char_clamped(&self, line_idx: usize) -> usize;
+    fn byte_to_line_clamped(&self, byte_idx: usize) -> usize;
+    fn line_to_byte_clamped(&self, line_idx: usize) -> usize;
+    fn char_range_to_byte_range(&self, range: Range<usize>) -> Range<usize>;
+    fn byte_range_to_char_range(&self, range: Range<usize>) -> Range<usize>;
+    /// The chars in `range`, borrowed when they sit in one chunk of the rope.
+    fn slice_cow(&self, range: Range<usize>) -> Cow<'_, str>;
+    /// The lines in `lines` without their line breaks, borrowed where possible.
+    fn lines_cow(&self, lines: Range<usize>) -> impl Iterator<Item = Cow<'_, str>>;
+}
+
+fn slice_to_cow(slice: RopeSlice<'_>) -> Cow<'_, str> {
+    match slice.as_str() {
+        Some(text) => Cow::Borrowed(text),
+        None => Cow::Owned(slice.to_string()),
+    }
+}
+
+fn trim_line_break(line: Cow<'_, str>) -> Cow<'_, str> {
+    let len = strip_line_break(&line).len();
+    match line {
+        Cow::Borrowed(text) => Cow::Borrowed(&text[..len]),
+        Cow::Owned(mut text) => {
+            text.truncate(len);
+            Cow::Owned(text)
+        }
+    }
 }
 
 impl RopeExt for Rope {
@@ -32,4 +79,133 @@ impl RopeExt for Rope {
             None
         }
     }
+
+    fn char_to_utf16_col(&self, line_idx: usize, char_col: usize) -> usize {
+        if line_idx >= self.len_lines() {
+            return 0;
+        }
+        let line = self.line(line_idx);
+        let char_col = char_col.min(line.len_chars());
+        line.char_to_utf16_cu(char_col)
+    }
+
+    fn utf16_to_char_col(&self, line_idx: usize, utf16_col: usize) -> usize {
+        if line_idx >= self.len_lines() {
+            return 0;
+        }
+        let line = self.line(line_idx);
+        let utf16_col = utf16_col.min(line.len_utf16_cu());
+        line.utf16_cu_to_char(utf16_col)
+    }
+
+    fn cursor_to_byte(&self, cursor: Cursor) -> usize {
+        if cursor.line >= self.len_lines() {
+            return self.len_bytes();
+        }
+        let line = self.line(cursor.line);
+        let column = cursor.column.min(line.len_chars());
+        self.line_to_byte(cursor.line) + line.char_to_byte(column)
+    }
+
+    fn byte_to_cursor(&self, byte_idx: usize) -> Cursor {
+        let char_idx = self.byte_to_char(byte_idx.min(self.len_bytes()));
+        let line = self.char_to_line(char_idx);
+        Cursor::new(line, char_idx - self.line_to_char(line))
+    }
+
+    fn byte_to_char_clamped(&self, byte_idx: usize) -> usize {
+        self.byte_to_char(byte_idx.min(self.len_bytes()))
+    }
+
+    fn char_to_byte_clamped(&self, char_idx: usize) -> usize {
+        self.char_to_byte(char_idx.min(self.len_chars()))
+    }
+
+    fn char_to_line_clamped(&self, char_idx: usize) -> usize {
+        self.char_to_line(char_idx.min(self.len_chars()))
+    }
+
+    fn line_to_char_clamped(&self, line_idx: usize) -> usize {
+        self.line_to_char(line_idx.min(self.len_lines()))
+    }
+
+    fn byte_to_line_clamped(&self, byte_idx: usize) -> usize {
+        self.byte_to_line(byte_idx.min(self.len_bytes()))
+    }
+
+    fn line_to_byte_clamped(&self, line_idx: usize) -> usize {
+        self.line_to_byte(line_idx.min(self.len_lines()))
+    }
+
+    fn char_range_to_byte_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.char_to_byte_clamped(range.start);
+        start..self.char_to_byte_clamped(range.end).max(start)
+    }
+
+    fn byte_range_to_char_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.byte_to_char_clamped(range.start);
+        start..self.byte_to_char_clamped(range.end).max(start)
+    }
+
+    fn slice_cow(&self, range: Range<usize>) -> Cow<'_, str> {
+        let end = range.end.min(self.len_chars());
+        slice_to_cow(self.slice(range.start.min(end)..end))
+    }
+
+    fn lines_cow(&self, lines: Range<usize>) -> impl Iterator<Item = Cow<'_, str>> {
+        let end = lines.end.min(self.len_lines());
+        (lines.start.min(end)..end).map(|line| trim_line_break(slice_to_cow(self.line(line))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_columns_between_chars_utf16_and_bytes() {
+        // "é" is one UTF-16 unit and two bytes, "😀" is two units and four bytes.
+        let rope = Rope::from_str("ab\né😀x\n");
+
+        assert_eq!(rope.char_to_utf16_col(1, 0), 0);
+        assert_eq!(rope.char_to_utf16_col(1, 2), 3);
+        assert_eq!(rope.char_to_utf16_col(1, 3), 4);
+        assert_eq!(rope.utf16_to_char_col(1, 3), 2);
+        assert_eq!(rope.utf16_to_char_col(1, 2), 1);
+        assert_eq!(rope.utf16_to_char_col(1, 99), 4);
+        assert_eq!(rope.char_to_utf16_col(9, 3), 0);
+
+        let cursor = Cursor::new(1, 2);
+        assert_eq!(rope.cursor_to_byte(cursor), 3 + 2 + 4);
+        assert_eq!(rope.byte_to_cursor(9), cursor);
+        assert_eq!(rope.cursor_to_byte(Cursor::new(0, 99)), 3);
+    }
+
+    #[test]
+    fn clamps_conversions_and_borrows_slices() {
+        let rope = Rope::from_str("ab\né😀x\r\nlast");
+
+        assert_eq!(rope.char_to_byte_clamped(4), 5);
+        // Byte 6 is inside "😀", which starts at char 4.
+        assert_eq!(rope.byte_to_char_clamped(6), 4);
+        assert_eq!(rope.byte_to_char_clamped(999), rope.len_chars());
+        assert_eq!(rope.char_to_line_clamped(999), 2);
+        assert_eq!(rope.line_to_char_clamped(999), rope.len_chars());
+        assert_eq!(rope.byte_to_line_clamped(4), 1);
+        assert_eq!(rope.line_to_byte_clamped(2), 3 + 2 + 4 + 1 + 2);
+        assert_eq!(rope.char_range_to_byte_range(3..5), 3..9);
+        assert_eq!(rope.byte_range_to_char_range(3..9), 3..5);
+
+        assert!(matches!(rope.slice_cow(3..6), Cow::Borrowed("é😀x")));
+        assert_eq!(rope.slice_cow(8..99), "last");
+        let lines: Vec<_> = rope.lines_cow(0..99).collect();
+        assert_eq!(lines, ["ab", "é😀x", "last"]);
+        assert!(lines.iter().all(|line| matches!(line, Cow::Borrowed(_))));
+
+        // Across chunk boundaries the slice has to be copied.
+        let long = "0123456789\n".repeat(2000);
+        let rope = Rope::from_str(&long);
+        assert_eq!(rope.slice_cow(0..long.len()), long);
+        assert_eq!(rope.lines_cow(1990..2000).count(), 10);
+    }
 }
@@ -0,0 +1,396 @@
+use crate::cursor::Cursor;
+use crate::line_ending::strip_line_break;
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use ropey::Rope;
+use std::borrow::Cow;
+use std::ops::Range;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+    /// Replacements follow the case of the text they replace, so `foo` → `bar` also
+    /// turns `Foo` into `Bar` and `FOO` into `BAR`.
+    pub preserve_case: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum SearchError {
+    #[error("Invalid search pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+}
+
+/// A match as char offsets into the document, plus the matching line/column positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub start: usize,
+    pub end: usize,
+    pub start_position: Cursor,
+    pub end_position: Cursor,
+}
+
+/// A compiled search, reusable across buffers and edits.
+///
+/// Queries that cannot match a line break are run line by line over the rope, so the
+/// document is never copied into one string. Patterns that can, like `\n`, `\s+` or
+/// `[^;]+`, search the whole text and may match across lines.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    regex: Regex,
+    spans_lines: bool,
+    whole_word: bool,
+    expands_captures: bool,
+    preserve_case: bool,
+}
+
+impl SearchQuery {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, SearchError> {
+        let pattern = if options.regex {
+            format!("(?:{})", query)
+        } else {
+            regex::escape(query)
+        };
+        // With `crlf`, `.` and `$` stop before a `\r\n` when the whole text is searched.
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .crlf(true)
+            .build()?;
+
+        // The pattern compiled above, so it parses; the fallback only guards against
+        // the two parsers disagreeing.
+        let spans_lines = regex_syntax::ParserBuilder::new()
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .crlf(true)
+            .build()
+            .parse(&pattern)
+            .map_or(true, |hir| can_match_line_break(&hir));
+        Ok(Self {
+            regex,
+            spans_lines,
+            whole_word: options.whole_word,
+            expands_captures: options.regex,
+            preserve_case: options.preserve_case,
+        })
+    }
+
+    pub fn find_all(&self, rope: &Rope) -> Vec<SearchMatch> {
+        if self.spans_lines {
+            return self.find_in_text(rope, &rope.to_string(), 0);
+        }
+
+        let mut matches = Vec::new();
+        let mut line_start = 0;
+        for line in rope.lines() {
+            let text: Cow<str> = line.into();
+            let content = strip_line_break(&text);
+            if !content.is_empty() {
+                matches.extend(self.find_in_text(rope, content, line_start));
+            }
+            line_start += line.len_chars();
+        }
+        matches
+    }
+
+    /// Matches lying wholly inside one of `scope`'s char ranges, e.g. the selections
+    /// for "replace in selection". Word boundaries and anchors still see the text
+    /// around the ranges.
+    pub fn find_in(&self, rope: &Rope, scope: &[Range<usize>]) -> Vec<SearchMatch> {
+        let mut matches = self.find_all(rope);
+        matches.retain(|found| {
+            scope
+                .iter()
+                .any(|range| range.start <= found.start && found.end <= range.end)
+        });
+        matches
+    }
+
+    /// The text to replace `matched` with. Regex queries expand `$1`/`${name}` from
+    /// the match's groups; otherwise `replacement` is used as is, apart from taking the
+    /// case of `matched` with [`SearchOptions::preserve_case`].
+    pub fn replacement<'a>(&self, matched: &str, replacement: &'a str) -> Cow<'a, str> {
+        let expanded = match self.regex.captures(matched) {
+            Some(captures) if self.expands_captures => {
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                Cow::Owned(expanded)
+            }
+            _ => Cow::Borrowed(replacement),
+        };
+        if self.preserve_case {
+            Cow::Owned(match_case(matched, &expanded))
+        } else {
+            expanded
+        }
+    }
+
+    fn find_in_text(&self, rope: &Rope, text: &str, char_offset: usize) -> Vec<SearchMatch> {
+        let mut matches = Vec::new();
+        // Walk forward once so byte -> char conversion stays linear in the text length.
+        let mut byte_cursor = 0;
+        let mut char_cursor = char_offset;
+        let mut at = 0;
+        while let Some(found) = self.regex.find_at(text, at) {
+            let skip = || {
+                found.start()
+                    + text[found.start()..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8)
+            };
+            if found.start() == found.end() {
+                at = skip();
+                continue;
+            }
+            // The next match may start inside a rejected one, e.g. `foo` in `xfoo foo`.
+            if self.whole_word && !is_whole_word(text, found.range()) {
+                at = skip();
+                continue;
+            }
+            at = found.end();
+            char_cursor += text[byte_cursor..found.start()].chars().count();
+            let start = char_cursor;
+            let end = start + found.as_str().chars().count();
+            byte_cursor = found.start();
+
+            matches.push(SearchMatch {
+                start,
+                end,
+                start_position: position(rope, start),
+                end_position: position(rope, end),
+            });
+        }
+        matches
+    }
+}
+
+/// Whether `range` of `text` starts and ends at a word boundary. Only a side where the
+/// match has a word char needs one, so whole-word `foo.` matches in `foo. bar` and `-x`
+/// in `a -x`, where a `\b` around the pattern never could.
+fn is_whole_word(text: &str, range: Range<usize>) -> bool {
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let matched = &text[range.clone()];
+    let starts_word = matched.chars().next().is_some_and(is_word);
+    let ends_word = matched.chars().next_back().is_some_and(is_word);
+    let word_before = text[..range.start].chars().next_back().is_some_and(is_word);
+    let word_after = text[range.end..].chars().next().is_some_and(is_word);
+    (!starts_word || !word_before) && (!ends_word || !word_after)
+}
+
+/// Whether `hir` can match a `\n` or `\r`. The rare Unicode line separators are left
+/// out, so that `.` doesn't send every pattern through the whole text.
+fn can_match_line_break(hir: &Hir) -> bool {
+    const BREAKS: [u8; 2] = [b'\n', b'\r'];
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => false,
+        HirKind::Literal(literal) => literal.0.iter().any(|byte| BREAKS.contains(byte)),
+        HirKind::Class(Class::Unicode(class)) => class.ranges().iter().any(|range| {
+            BREAKS
+                .iter()
+                .any(|&byte| (range.start()..=range.end()).contains(&char::from(byte)))
+        }),
+        HirKind::Class(Class::Bytes(class)) => class.ranges().iter().any(|range| {
+            BREAKS
+                .iter()
+                .any(|byte| (range.start()..=range.end()).contains(byte))
+        }),
+        HirKind::Repetition(repetition) => can_match_line_break(&repetition.sub),
+        HirKind::Capture(capture) => can_match_line_break(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(can_match_line_break),
+    }
+}
+
+/// `replacement` in the case of `matched` when that is all upper, all lower or
+/// capitalized; mixed case like `fooBar` leaves it as typed.
+fn match_case(matched: &str, replacement: &str) -> String {
+    let mut letters = matched.chars().filter(|ch| ch.is_alphabetic());
+    let Some(first) = letters.next() else {
+        return replacement.to_string();
+    };
+    let rest: Vec<char> = letters.collect();
+    if first.is_uppercase() && !rest.is_empty() && rest.iter().all(|ch| ch.is_uppercase()) {
+        replacement.to_uppercase()
+    } else if first.is_lowercase() && rest.iter().all(|ch| ch.is_lowercase()) {
+        replacement.to_lowercase()
+    } else if first.is_uppercase() && rest.iter().all(|ch| ch.is_lowercase()) {
+        let mut chars = replacement.chars();
+        chars
+            .next()
+            .map(|head| head.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        replacement.to_string()
+    }
+}
+
+fn position(rope: &Rope, char_idx: usize) -> Cursor {
+    let line = rope.char_to_line(char_idx);
+    Cursor::new(line, char_idx - rope.line_to_char(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starts(query: &str, options: SearchOptions, text: &str) -> Vec<(usize, usize)> {
+        let rope = Rope::from_str(text);
+        SearchQuery::new(query, options)
+            .unwrap()
+            .find_all(&rope)
+            .into_iter()
+            .map(|m| (m.start_position.line, m.start_position.column))
+            .collect()
+    }
+
+    #[test]
+    fn finds_literal_word_and_regex_matches() {
+        let text = "let foo = 1;\nFoo(foobar);\n";
+        assert_eq!(
+            starts("foo", SearchOptions::default(), text),
+            vec![(0, 4), (1, 0), (1, 4)]
+        );
+        let exact = SearchOptions {
+            case_sensitive: true,
+            whole_word: true,
+            ..Default::default()
+        };
+        assert_eq!(starts("foo", exact, text), vec![(0, 4)]);
+        let regex = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(starts(r"\d+", regex, text), vec![(0, 10)]);
+        assert_eq!(starts(r";\nfoo", regex, text), vec![(0, 11)]);
+        assert_eq!(starts("(", SearchOptions::default(), "a(b"), vec![(0, 1)]);
+        assert!(SearchQuery::new("(", regex).is_err());
+    }
+
+    #[test]
+    fn whole_word_needs_boundaries_only_beside_word_chars() {
+        let word = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            starts("foo.", word, "foo. bar foo.x xfoo."),
+            vec![(0, 0), (0, 9)]
+        );
+        assert_eq!(starts("-x", word, "a -x -xy b-x"), vec![(0, 2), (0, 10)]);
+        assert_eq!(
+            starts("foo()", word, "foo() afoo() foo()b"),
+            vec![(0, 0), (0, 13)]
+        );
+        // A rejected match doesn't hide one starting inside it.
+        assert_eq!(starts("aa", word, "aaa aa"), vec![(0, 4)]);
+        let regex_word = SearchOptions {
+            regex: true,
+            ..word
+        };
+        assert_eq!(starts(r"a\+b", regex_word, "a+b xa+b a+bc"), vec![(0, 0)]);
+        assert_eq!(
+            starts(r"\w+\(", regex_word, "f( g_h("),
+            vec![(0, 0), (0, 3)]
+        );
+    }
+
+    #[test]
+    fn patterns_that_can_match_a_line_break_span_lines() {
+        let regex = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        let multi_line = |query: &str| SearchQuery::new(query, regex).unwrap().spans_lines;
+        assert!(multi_line(r"\n"));
+        assert!(multi_line(r"\s+"));
+        assert!(multi_line(r"[^;]+"));
+        assert!(multi_line(r"\W"));
+        assert!(!multi_line(r"a.b"));
+        assert!(!multi_line(r"^\w+$"));
+        assert!(!multi_line(r"[ \t]+"));
+        assert!(
+            SearchQuery::new("a\nb", SearchOptions::default())
+                .unwrap()
+                .spans_lines
+        );
+
+        let text = "let a =\r\n    1;\r\nb;";
+        let rope = Rope::from_str(text);
+        let found = |query: &str| -> Vec<String> {
+            SearchQuery::new(query, regex)
+                .unwrap()
+                .find_all(&rope)
+                .iter()
+                .map(|m| rope.slice(m.start..m.end).to_string())
+                .collect()
+        };
+        assert_eq!(found(r"=\s+1"), ["=\r\n    1"]);
+        // `$` and `.` stop before a `\r\n` when the whole text is searched.
+        assert_eq!(found(r"[^;\s]+;$"), ["1;", "b;"]);
+        assert_eq!(found(r"\s.+;"), ["\n    1;", "\nb;"]);
+    }
+
+    #[test]
+    fn finds_only_inside_the_scope_and_expands_groups() {
+        let rope = Rope::from_str(
+            "foo foo
+foofoo foo
+",
+        );
+        let word = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new("foo", word).unwrap();
+        // The second line's range starts inside "foofoo", which stays no whole word.
+        let found: Vec<usize> = query
+            .find_in(&rope, &[2..7, 11..20])
+            .iter()
+            .map(|m| m.start)
+            .collect();
+        assert_eq!(found, vec![4, 15]);
+        assert!(query.find_in(&rope, &[]).is_empty());
+
+        let regex = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new(r"(\w)o+", regex).unwrap();
+        assert_eq!(query.replacement("foo", "${1}0"), "f0");
+        let literal = SearchQuery::new("foo", SearchOptions::default()).unwrap();
+        assert_eq!(literal.replacement("foo", "$1"), "$1");
+    }
+
+    #[test]
+    fn preserve_case_follows_the_replaced_text() {
+        let options = SearchOptions {
+            whole_word: true,
+            preserve_case: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new("foo", options).unwrap();
+        let rope = Rope::from_str("foo Foo FOO fOo food");
+        let replaced: Vec<String> = query
+            .find_all(&rope)
+            .iter()
+            .map(|m| {
+                let matched = rope.slice(m.start..m.end).to_string();
+                query.replacement(&matched, "bar").into_owned()
+            })
+            .collect();
+        assert_eq!(replaced, ["bar", "Bar", "BAR", "bar"]);
+        assert_eq!(query.replacement("Foo", "newName"), "NewName");
+        assert_eq!(query.replacement("F", "bar"), "Bar");
+
+        let regex = SearchOptions {
+            regex: true,
+            preserve_case: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new(r"get_(\w+)", regex).unwrap();
+        assert_eq!(query.replacement("GET_NAME", "set_$1"), "SET_NAME");
+    }
+}
@@ -58,6 +58,28 @@ impl Selection {
             && (cursor.line < end.line || (cursor.line == end.line && cursor.column <= end.column))
     }
 
+    /// Whether editing both selections at once would touch the same text. Ranges
+    /// that only share an end point don't overlap; a caret on a range's edge does.
+    pub fn overlaps(&self, other: &Selection) -> bool {
+        if self.is_collapsed() || other.is_collapsed() {
+            return self.contains(other.start()) || other.contains(self.start());
+        }
+        let key = |cursor: Cursor| (cursor.line, cursor.column);
+        key(self.start()) < key(other.end()) && key(other.start()) < key(self.end())
+    }
+
+    /// The smallest selection covering both, facing the same way as `self`.
+    pub fn merge(&self, other: &Selection) -> Self {
+        let key = |cursor: Cursor| (cursor.line, cursor.column);
+        let start = std::cmp::min_by_key(self.start(), other.start(), |c| key(*c));
+        let end = std::cmp::max_by_key(self.end(), other.end(), |c| key(*c));
+        if self.anchor == self.start() {
+            Self::new(start, end)
+        } else {
+            Self::new(end, start)
+        }
+    }
+
     pub fn expand_to_line(&self) -> Self {
         let start = Cursor::new(self.start().line, 0);
         let end = Cursor::new(self.end().line, usize::MAX); // Will be clamped to actual line length
@@ -0,0 +1,158 @@
+use crate::cursor::Cursor;
+use crate::rope_ext::RopeExt;
+use crate::search::{SearchError, SearchMatch, SearchOptions, SearchQuery};
+use crate::selection::Selection;
+use ropey::Rope;
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// The text and selections of a [`crate::Buffer`] at one version.
+///
+/// Taking one clones the rope, which only bumps a reference count, so background work
+/// can read the text after releasing the buffer lock. Edits made afterwards are not
+/// visible here.
+#[derive(Debug, Clone)]
+pub struct BufferSnapshot {
+    rope: Rope,
+    version: usize,
+    selections: Vec<Selection>,
+}
+
+impl BufferSnapshot {
+    pub(crate) fn new(rope: Rope, version: usize, selections: Vec<Selection>) -> Self {
+        Self {
+            rope,
+            version,
+            selections,
+        }
+    }
+
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
+    pub fn text(&self) -> String {
+        String::from(&self.rope)
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_chars() == 0
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    /// The line including its line break.
+    pub fn line(&self, line_idx: usize) -> Option<String> {
+        (line_idx < self.rope.len_lines()).then(|| self.rope.line(line_idx).to_string())
+    }
+
+    /// The lines in `lines` without their line breaks, clamped to the text. Lines are
+    /// only copied when they span chunks of the rope.
+    pub fn lines(&self, lines: Range<usize>) -> impl Iterator<Item = Cow<'_, str>> {
+        self.rope.lines_cow(lines)
+    }
+
+    /// Length of the line in chars, without its line break.
+    pub fn line_len(&self, line_idx: usize) -> Option<usize> {
+        let line = self.lines(line_idx..line_idx + 1).next()?;
+        Some(line.chars().count())
+    }
+
+    pub fn char_at(&self, char_idx: usize) -> Option<char> {
+        self.rope.get_char(char_idx)
+    }
+
+    /// Text in a char range, clamped to the end of the text.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        self.rope.slice_cow(range).into_owned()
+    }
+
+    /// Char index of `cursor`, clamped to its line and to the end of the text.
+    pub fn cursor_to_char(&self, cursor: Cursor) -> usize {
+        if cursor.line >= self.rope.len_lines() {
+            return self.rope.len_chars();
+        }
+        let column = cursor.column.min(self.line_len(cursor.line).unwrap_or(0));
+        self.rope.line_to_char(cursor.line) + column
+    }
+
+    pub fn char_to_cursor(&self, char_idx: usize) -> Cursor {
+        let char_idx = char_idx.min(self.rope.len_chars());
+        let line = self.rope.char_to_line(char_idx);
+        Cursor::new(line, char_idx - self.rope.line_to_char(line))
+    }
+
+    pub fn selections(&self) -> &[Selection] {
+        &self.selections
+    }
+
+    pub fn selected_text(&self, selection: &Selection) -> String {
+        self.slice(self.cursor_to_char(selection.start())..self.cursor_to_char(selection.end()))
+    }
+
+    pub fn search(
+        &self,
+        query: &str,
+        options: SearchOptions,
+    ) -> Result<Vec<SearchMatch>, SearchError> {
+        Ok(SearchQuery::new(query, options)?.find_all(&self.rope))
+    }
+
+    pub fn stats(&self) -> crate::BufferStats {
+        crate::BufferStats::of(&self.rope, &self.selections)
+    }
+
+    /// Like [`BufferSnapshot::search`], keeping only matches wholly inside one of `scope`.
+    pub fn search_in(
+        &self,
+        query: &str,
+        options: SearchOptions,
+        scope: &[Range<Cursor>],
+    ) -> Result<Vec<SearchMatch>, SearchError> {
+        let scope: Vec<Range<usize>> = scope
+            .iter()
+            .map(|range| self.cursor_to_char(range.start)..self.cursor_to_char(range.end))
+            .collect();
+        Ok(SearchQuery::new(query, options)?.find_in(&self.rope, &scope))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn snapshot_is_unaffected_by_later_edits() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut buffer = Buffer::from_text("let a = 1;\nlet b = 2;\n");
+            buffer
+                .set_selection(Selection::range(Cursor::new(1, 4), Cursor::new(1, 5)))
+                .await;
+            let snapshot = buffer.snapshot().await;
+
+            buffer.set_cursor(Cursor::new(0, 0)).await;
+            buffer.insert_text_at_cursor("// header\n").await;
+
+            assert_eq!(snapshot.text(), "let a = 1;\nlet b = 2;\n");
+            assert_eq!(snapshot.line_count(), 3);
+            assert_eq!(snapshot.line_len(1), Some(10));
+            assert_eq!(snapshot.selected_text(&snapshot.selections()[0]), "b");
+            assert_eq!(snapshot.char_to_cursor(15), Cursor::new(1, 4));
+            assert_eq!(snapshot.cursor_to_char(Cursor::new(0, 99)), 10);
+            assert!(snapshot.version() < buffer.version());
+            let matches = snapshot.search("let", SearchOptions::default()).unwrap();
+            assert_eq!(matches.len(), 2);
+        });
+    }
+}
@@ -0,0 +1,107 @@
+use crate::selection::Selection;
+use editor_infra::telemetry::TelemetryEvent;
+use ropey::Rope;
+use std::collections::HashMap;
+
+/// Counts describing a text and where its primary caret is, e.g. for the status bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BufferStats {
+    pub chars: usize,
+    /// Runs of non-whitespace chars.
+    pub words: usize,
+    pub lines: usize,
+    /// Chars covered by all selections together.
+    pub selected_chars: usize,
+    /// How far into the text the primary caret is, from 0 to 100.
+    pub cursor_percent: f32,
+}
+
+impl BufferStats {
+    /// The primary selection is the first one, as in [`crate::Buffer::get_selections`].
+    pub fn of(rope: &Rope, selections: &[Selection]) -> Self {
+        let mut words = 0;
+        let mut in_word = false;
+        for chunk in rope.chunks() {
+            for ch in chunk.chars() {
+                let word_char = !ch.is_whitespace();
+                if word_char && !in_word {
+                    words += 1;
+                }
+                in_word = word_char;
+            }
+        }
+
+        let chars = rope.len_chars();
+        let char_idx = |cursor: crate::Cursor| {
+            if cursor.line >= rope.len_lines() {
+                return chars;
+            }
+            let line = rope.line(cursor.line);
+            let mut line_len = line.len_chars();
+            while line_len > 0 && matches!(line.char(line_len - 1), '\n' | '\r') {
+                line_len -= 1;
+            }
+            rope.line_to_char(cursor.line) + cursor.column.min(line_len)
+        };
+        let selected_chars = selections
+            .iter()
+            .map(|selection| char_idx(selection.end()) - char_idx(selection.start()))
+            .sum();
+        let cursor_percent = match selections.first() {
+            Some(primary) if chars > 0 => char_idx(primary.active) as f32 * 100.0 / chars as f32,
+            _ => 0.0,
+        };
+
+        Self {
+            chars,
+            words,
+            lines: rope.len_lines(),
+            selected_chars,
+            cursor_percent,
+        }
+    }
+
+    /// The counts as a telemetry event. Only sizes are reported, never the text.
+    pub fn to_event(&self) -> TelemetryEvent {
+        TelemetryEvent {
+            name: "buffer_stats".to_string(),
+            properties: HashMap::new(),
+            metrics: HashMap::from([
+                ("chars".to_string(), self.chars as f64),
+                ("words".to_string(), self.words as f64),
+                ("lines".to_string(), self.lines as f64),
+                ("selected_chars".to_string(), self.selected_chars as f64),
+                ("cursor_percent".to_string(), self.cursor_percent as f64),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cursor;
+
+    #[test]
+    fn counts_words_lines_and_selected_chars() {
+        let rope = Rope::from_str("fn main() {\n    let x = 1;\n}\n");
+        let selections = [
+            Selection::single(Cursor::new(1, 4)),
+            Selection::new(Cursor::new(1, 8), Cursor::new(0, 3)),
+            Selection::new(Cursor::new(2, 0), Cursor::new(9, 0)),
+        ];
+        let stats = BufferStats::of(&rope, &selections);
+        assert_eq!(stats.chars, 29);
+        assert_eq!(stats.words, 8);
+        assert_eq!(stats.lines, 4);
+        assert_eq!(stats.selected_chars, 17 + 2);
+        assert_eq!(stats.cursor_percent, 16.0 * 100.0 / 29.0);
+
+        let empty = BufferStats::of(&Rope::new(), &[]);
+        assert_eq!(
+            (empty.words, empty.lines, empty.cursor_percent),
+            (0, 1, 0.0)
+        );
+        assert_eq!(stats.to_event().metrics["words"], 8.0);
+    }
+}
@@ -0,0 +1,405 @@
+use editor_infra::language::{LanguagePack, LANGUAGE_PACKS};
+use std::ops::Range;
+
+/// What a highlighted piece of code is; themes pick a color for each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Keyword,
+    /// Capitalized identifiers, which name types in most languages.
+    Type,
+    /// Identifiers directly followed by `(`.
+    Function,
+    String,
+    Number,
+    Comment,
+}
+
+/// A highlighted byte range of one line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxSpan {
+    pub range: Range<usize>,
+    pub kind: TokenKind,
+}
+
+/// Lexical highlighting shared by the editor and code shown elsewhere (AI answers,
+/// previews): comments, strings, numbers, keywords, types and calls. Only block
+/// comments carry over from one line to the next.
+#[derive(Debug, Clone, Copy)]
+pub struct Highlighter {
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    keywords: &'static [&'static str],
+    /// `'` starts a char literal, not a string, and may start a lifetime instead.
+    char_literals: bool,
+}
+
+impl Highlighter {
+    /// The highlighter for a language id such as `rust`, or an extension or fence tag
+    /// such as `rs`; unknown languages still get strings and numbers.
+    pub fn for_language(language: &str) -> Self {
+        let language = language.trim().to_ascii_lowercase();
+        let language = match language.as_str() {
+            "bash" | "sh" | "zsh" | "shell" => "shellscript",
+            "js" => "javascript",
+            "ts" => "typescript",
+            "c++" => "cpp",
+            other => other,
+        };
+        let pack = LanguagePack::for_id(language).or_else(|| {
+            LANGUAGE_PACKS
+                .iter()
+                .find(|pack| pack.extensions.contains(&language))
+        });
+        Self {
+            line_comment: pack.and_then(|pack| pack.line_comment),
+            block_comment: pack.and_then(|pack| pack.block_comment),
+            keywords: pack.map_or(&[], |pack| keywords(pack.id)),
+            char_literals: pack.is_some_and(|pack| pack.id == "rust"),
+        }
+    }
+
+    /// The spans of each line of `text`.
+    pub fn highlight(&self, text: &str) -> Vec<Vec<SyntaxSpan>> {
+        self.highlight_lines(text.lines())
+    }
+
+    pub fn highlight_lines<'a>(
+        &self,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<Vec<SyntaxSpan>> {
+        let mut in_block = false;
+        lines
+            .into_iter()
+            .map(|line| self.highlight_line(line, &mut in_block))
+            .collect()
+    }
+
+    fn highlight_line(&self, line: &str, in_block: &mut bool) -> Vec<SyntaxSpan> {
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        while pos < line.len() {
+            let rest = &line[pos..];
+            if *in_block {
+                let (_, close) = self.block_comment.expect("only set with block comments");
+                let end = match rest.find(close) {
+                    Some(at) => {
+                        *in_block = false;
+                        pos + at + close.len()
+                    }
+                    None => line.len(),
+                };
+                spans.push(span(pos..end, TokenKind::Comment));
+                pos = end;
+                continue;
+            }
+            if self
+                .line_comment
+                .is_some_and(|token| rest.starts_with(token))
+            {
+                spans.push(span(pos..line.len(), TokenKind::Comment));
+                break;
+            }
+            if let Some((open, _)) = self
+                .block_comment
+                .filter(|(open, _)| rest.starts_with(open))
+            {
+                // The close is looked for after the opening token.
+                *in_block = true;
+                let start = pos;
+                pos += open.len();
+                let (_, close) = self.block_comment.expect("checked above");
+                let end = match line[pos..].find(close) {
+                    Some(at) => {
+                        *in_block = false;
+                        pos + at + close.len()
+                    }
+                    None => line.len(),
+                };
+                spans.push(span(start..end, TokenKind::Comment));
+                pos = end;
+                continue;
+            }
+
+            let ch = rest.chars().next().expect("pos < len");
+            if ch == '"' || ch == '\'' || ch == '`' {
+                let end = string_end(line, pos, ch);
+                let literal = &line[pos..end];
+                let is_string = !(ch == '\'' && self.char_literals)
+                    || (literal.len() > 2
+                        && literal.ends_with('\'')
+                        && (literal.starts_with("'\\") || literal[1..].chars().count() == 2));
+                if is_string {
+                    spans.push(span(pos..end, TokenKind::String));
+                    pos = end;
+                    continue;
+                }
+            }
+            if ch.is_ascii_digit() {
+                let end = word_end(line, pos);
+                spans.push(span(pos..end, TokenKind::Number));
+                pos = end;
+                continue;
+            }
+            if ch.is_alphabetic() || ch == '_' {
+                let end = word_end(line, pos);
+                let word = &line[pos..end];
+                let kind = if self.keywords.contains(&word) {
+                    Some(TokenKind::Keyword)
+                } else if line[end..].starts_with('(') {
+                    Some(TokenKind::Function)
+                } else if ch.is_uppercase() {
+                    Some(TokenKind::Type)
+                } else {
+                    None
+                };
+                if let Some(kind) = kind {
+                    spans.push(span(pos..end, kind));
+                }
+                pos = end;
+                continue;
+            }
+            pos += ch.len_utf8();
+        }
+        spans
+    }
+}
+
+fn span(range: Range<usize>, kind: TokenKind) -> SyntaxSpan {
+    SyntaxSpan { range, kind }
+}
+
+/// The byte after the string opened by `quote` at `start`, or the end of the line
+/// when it isn't closed there. Backslashes escape the next char.
+fn string_end(line: &str, start: usize, quote: char) -> usize {
+    let mut escaped = false;
+    for (offset, ch) in line[start + 1..].char_indices() {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == quote {
+            return start + 1 + offset + ch.len_utf8();
+        }
+    }
+    line.len()
+}
+
+fn word_end(line: &str, start: usize) -> usize {
+    line[start..]
+        .char_indices()
+        .find(|(_, ch)| !(ch.is_alphanumeric() || *ch == '_'))
+        .map_or(line.len(), |(offset, _)| start + offset)
+}
+
+fn keywords(language: &str) -> &'static [&'static str] {
+    match language {
+        "rust" => &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        "python" => &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+        "javascript" | "typescript" | "javascriptreact" | "typescriptreact" => &[
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "export",
+            "extends",
+            "false",
+            "finally",
+            "for",
+            "from",
+            "function",
+            "if",
+            "import",
+            "in",
+            "instanceof",
+            "interface",
+            "let",
+            "new",
+            "null",
+            "of",
+            "return",
+            "static",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "type",
+            "typeof",
+            "undefined",
+            "var",
+            "void",
+            "while",
+            "yield",
+        ],
+        "go" => &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "fallthrough",
+            "false",
+            "for",
+            "func",
+            "go",
+            "goto",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "nil",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "true",
+            "type",
+            "var",
+        ],
+        "c" | "cpp" | "java" => &[
+            "break",
+            "case",
+            "catch",
+            "char",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "extends",
+            "false",
+            "final",
+            "float",
+            "for",
+            "if",
+            "implements",
+            "import",
+            "include",
+            "int",
+            "long",
+            "namespace",
+            "new",
+            "null",
+            "nullptr",
+            "package",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "short",
+            "sizeof",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typedef",
+            "union",
+            "unsigned",
+            "void",
+            "volatile",
+            "while",
+        ],
+        "shellscript" => &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "then", "until", "while",
+        ],
+        "json" | "toml" | "yaml" => &["false", "null", "true"],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(spans: &[SyntaxSpan], line: &str) -> Vec<(String, TokenKind)> {
+        spans
+            .iter()
+            .map(|span| (line[span.range.clone()].to_string(), span.kind))
+            .collect()
+    }
+
+    #[test]
+    fn highlights_tokens_and_block_comments_across_lines() {
+        let rust = Highlighter::for_language("rs");
+        let text =
+            "fn main() -> Option<u8> { let s = \"a \\\" b\"; // done\n/* open\nstill */ 42 'a' '_";
+        let lines: Vec<&str> = text.lines().collect();
+        let spans = rust.highlight(text);
+        assert_eq!(
+            kinds(&spans[0], lines[0]),
+            [
+                ("fn".to_string(), TokenKind::Keyword),
+                ("main".to_string(), TokenKind::Function),
+                ("Option".to_string(), TokenKind::Type),
+                ("let".to_string(), TokenKind::Keyword),
+                ("\"a \\\" b\"".to_string(), TokenKind::String),
+                ("// done".to_string(), TokenKind::Comment),
+            ]
+        );
+        assert_eq!(
+            kinds(&spans[1], lines[1]),
+            [("/* open".to_string(), TokenKind::Comment)]
+        );
+        assert_eq!(
+            kinds(&spans[2], lines[2]),
+            [
+                ("still */".to_string(), TokenKind::Comment),
+                ("42".to_string(), TokenKind::Number),
+                ("'a'".to_string(), TokenKind::String),
+            ]
+        );
+
+        let python = Highlighter::for_language("python");
+        let line = "def f(x): return None  # not // a comment";
+        assert_eq!(
+            kinds(&python.highlight(line)[0], line),
+            [
+                ("def".to_string(), TokenKind::Keyword),
+                ("f".to_string(), TokenKind::Function),
+                ("return".to_string(), TokenKind::Keyword),
+                ("None".to_string(), TokenKind::Keyword),
+                ("# not // a comment".to_string(), TokenKind::Comment),
+            ]
+        );
+
+        let unknown = Highlighter::for_language("brainfuck");
+        assert_eq!(
+            kinds(&unknown.highlight("if 'x' 1")[0], "if 'x' 1"),
+            [
+                ("'x'".to_string(), TokenKind::String),
+                ("1".to_string(), TokenKind::Number),
+            ]
+        );
+    }
+}
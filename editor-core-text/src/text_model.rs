@@ -1,27 +1,309 @@
+use crate::crdt::{RemoteOp, ReplicaId, TextCrdt};
+use crate::cursor::Cursor;
+use crate::edit::{Edit, EditKind, TextChange};
+use crate::edit_log::{EditJournal, EditLog};
+use crate::marks::{Anchors, Marks, TrackedRanges};
+use crate::rope_ext::RopeExt;
+use crate::search::{SearchMatch, SearchQuery};
 use ropey::Rope;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::sync::{broadcast, watch, RwLock};
+
+const CHANGE_CHANNEL_CAPACITY: usize = 1024;
 
 #[derive(Debug, Clone)]
 pub struct TextModel {
     rope: Arc<RwLock<Rope>>,
     version: Arc<AtomicUsize>,
+    versions: Arc<watch::Sender<usize>>,
+    changes: broadcast::Sender<TextChange>,
+    /// Shared like the text, so every view of the model sees the same bookmarks.
+    marks: Arc<Mutex<Marks>>,
+    tracked_ranges: Arc<Mutex<TrackedRanges>>,
+    anchors: Arc<Mutex<Anchors>>,
+    /// Set while edits are being recorded for a bug report.
+    edit_log: Arc<Mutex<Option<EditLog>>>,
+    /// Set while edits are written to a journal on disk.
+    journal: Arc<Mutex<Option<EditJournal>>>,
+    /// Set while the text is shared with other peers.
+    collaboration: Arc<Mutex<Option<Collaboration>>>,
+}
+
+/// The CRDT copy of a shared text and the local operations not sent yet.
+#[derive(Debug)]
+struct Collaboration {
+    crdt: TextCrdt,
+    outbox: Vec<RemoteOp>,
 }
 
 impl TextModel {
     pub fn new() -> Self {
-        Self {
-            rope: Arc::new(RwLock::new(Rope::new())),
-            version: Arc::new(AtomicUsize::new(0)),
-        }
+        Self::from_rope(Rope::new())
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(text: &str) -> Self {
+        Self::from_rope(Rope::from_str(text))
+    }
+
+    fn from_rope(rope: Rope) -> Self {
+        let (versions, _) = watch::channel(0);
+        let (changes, _) = broadcast::channel(CHANGE_CHANNEL_CAPACITY);
         Self {
-            rope: Arc::new(RwLock::new(Rope::from_str(text))),
+            rope: Arc::new(RwLock::new(rope)),
             version: Arc::new(AtomicUsize::new(0)),
+            versions: Arc::new(versions),
+            changes,
+            marks: Arc::new(Mutex::new(Marks::default())),
+            tracked_ranges: Arc::new(Mutex::new(TrackedRanges::default())),
+            anchors: Arc::new(Mutex::new(Anchors::default())),
+            edit_log: Arc::new(Mutex::new(None)),
+            journal: Arc::new(Mutex::new(None)),
+            collaboration: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Watch the model version. Every view sharing this model is woken after each edit.
+    pub fn watch_version(&self) -> watch::Receiver<usize> {
+        self.versions.subscribe()
+    }
+
+    /// Receive every edit as a [`TextChange`].
+    ///
+    /// A receiver that falls more than the channel capacity behind gets
+    /// `RecvError::Lagged` and should resynchronise from [`TextModel::get_text`].
+    pub fn subscribe(&self) -> broadcast::Receiver<TextChange> {
+        self.changes.subscribe()
+    }
+
+    /// Must be called with the rope write lock held so versions and changes stay ordered.
+    fn publish(&self, edit: Edit, range: EditRange) {
+        if let Some(collaboration) = self.lock_collaboration().as_mut() {
+            let ops = collaboration.crdt.local_edit(&edit.kind);
+            collaboration.outbox.extend(ops);
+        }
+        self.publish_change(edit, range);
+    }
+
+    /// Like [`Self::publish`], for edits that don't need sending to other peers.
+    fn publish_change(&self, edit: Edit, range: EditRange) {
+        self.update_marks(|marks| marks.apply(&edit.kind));
+        self.update_tracked_ranges(|ranges| ranges.apply(&edit.kind));
+        self.update_anchors(|anchors| anchors.apply(&edit.kind));
+        self.announce(edit, range);
+    }
+
+    /// Bumps the version and hands `edit` to the edit log, the journal and subscribers.
+    fn announce(&self, edit: Edit, range: EditRange) {
+        let after_version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+        self.versions.send_replace(after_version);
+        if let Some(log) = self.lock_edit_log().as_mut() {
+            log.record(after_version, &edit);
+        }
+        let mut journal = self.lock_journal();
+        // A journal that can't be written to stops; the edit itself goes ahead.
+        if journal
+            .as_mut()
+            .is_some_and(|journal| journal.append(after_version, &edit).is_err())
+        {
+            *journal = None;
+        }
+        drop(journal);
+        // No receivers is fine; nobody is listening for deltas.
+        let _ = self.changes.send(TextChange {
+            edit,
+            before_version: after_version - 1,
+            after_version,
+            start: range.start,
+            old_end: range.old_end,
+            utf16_start: range.utf16_start,
+            utf16_old_end: range.utf16_old_end,
+        });
+    }
+
+    /// Reads or changes the bookmarks. Positions are char indices into the current text.
+    pub fn update_marks<T>(&self, f: impl FnOnce(&mut Marks) -> T) -> T {
+        let mut marks = self.marks.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut marks)
+    }
+
+    /// Reads or changes the ranges tracked across edits, in char indices.
+    pub fn update_tracked_ranges<T>(&self, f: impl FnOnce(&mut TrackedRanges) -> T) -> T {
+        let mut ranges = self
+            .tracked_ranges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        f(&mut ranges)
+    }
+
+    /// Reads or changes the anchors, in char indices. Every view of the model shares
+    /// them, so diagnostics and suggestions anchored by one view move with edits made
+    /// in another.
+    pub fn update_anchors<T>(&self, f: impl FnOnce(&mut Anchors) -> T) -> T {
+        let mut anchors = self.anchors.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut anchors)
+    }
+
+    /// Bytes of text, and bytes the rope has allocated for it including spare room.
+    pub async fn memory_usage(&self) -> (usize, usize) {
+        let rope = self.rope.read().await;
+        (rope.len_bytes(), rope.capacity())
+    }
+
+    /// Releases the rope's spare room. The text and version are unchanged.
+    pub async fn shrink_to_fit(&self) {
+        self.rope.write().await.shrink_to_fit();
+    }
+
+    /// Starts recording every edit from the current text, replacing an earlier recording.
+    pub async fn start_edit_log(&self) {
+        let rope = self.rope.read().await;
+        *self.lock_edit_log() = Some(EditLog::new(&rope.to_string(), self.version()));
+    }
+
+    /// Records into `log`, or stops recording with `None`. The log must start from the
+    /// current text and version for it to replay.
+    pub fn set_edit_log(&self, log: Option<EditLog>) {
+        *self.lock_edit_log() = log;
+    }
+
+    /// A copy of the recording so far.
+    pub fn edit_log(&self) -> Option<EditLog> {
+        self.lock_edit_log().clone()
+    }
+
+    pub fn edit_log_bytes(&self) -> usize {
+        self.lock_edit_log()
+            .as_ref()
+            .map_or(0, EditLog::approximate_bytes)
+    }
+
+    /// Writes every edit to `journal`, or stops with `None`. The journal must start from
+    /// the current text and version for it to replay.
+    pub fn set_journal(&self, journal: Option<EditJournal>) {
+        *self.lock_journal() = journal;
+    }
+
+    /// Waits until the journal has written every edit so far.
+    pub fn flush_journal(&self) -> std::io::Result<()> {
+        self.lock_journal()
+            .as_ref()
+            .map_or(Ok(()), EditJournal::flush)
+    }
+
+    /// Where edits are being journaled, if they are.
+    pub fn journal_path(&self) -> Option<PathBuf> {
+        self.lock_journal()
+            .as_ref()
+            .map(|journal| journal.path().to_path_buf())
+    }
+
+    fn lock_journal(&self) -> std::sync::MutexGuard<'_, Option<EditJournal>> {
+        self.journal.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_edit_log(&self) -> std::sync::MutexGuard<'_, Option<EditLog>> {
+        self.edit_log.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts sharing the text as `replica`, replacing an earlier session. Every peer
+    /// must start from the same text; after that, local edits produce operations for
+    /// [`TextModel::take_local_ops`].
+    pub async fn start_collaboration(&self, replica: ReplicaId) {
+        let rope = self.rope.read().await;
+        *self.lock_collaboration() = Some(Collaboration {
+            crdt: TextCrdt::new(replica, &rope.to_string()),
+            outbox: Vec::new(),
+        });
+    }
+
+    pub fn stop_collaboration(&self) {
+        *self.lock_collaboration() = None;
+    }
+
+    pub fn is_collaborating(&self) -> bool {
+        self.lock_collaboration().is_some()
+    }
+
+    /// Operations for the local edits since the last call, to send to the other peers.
+    pub fn take_local_ops(&self) -> Vec<RemoteOp> {
+        self.lock_collaboration()
+            .as_mut()
+            .map(|collaboration| std::mem::take(&mut collaboration.outbox))
+            .unwrap_or_default()
+    }
+
+    /// Applies an operation from another peer and returns the edits it made, which are
+    /// published like local ones. Nothing changes while the operation waits for ones it
+    /// depends on, or when the text isn't shared.
+    pub async fn apply_remote_op(&self, op: RemoteOp) -> Vec<Edit> {
+        let mut rope = self.rope.write().await;
+        let kinds = match self.lock_collaboration().as_mut() {
+            Some(collaboration) => collaboration.crdt.apply_remote(op),
+            None => return Vec::new(),
+        };
+
+        let mut edits = Vec::new();
+        for kind in kinds {
+            let (char_idx, old_text, new_text) = match &kind {
+                EditKind::Insert { char_idx, text } => (*char_idx, "", text.as_str()),
+                EditKind::Delete { char_idx, text } => (*char_idx, text.as_str(), ""),
+                EditKind::Replace {
+                    char_idx,
+                    old_text,
+                    new_text,
+                } => (*char_idx, old_text.as_str(), new_text.as_str()),
+            };
+            let end = char_idx + old_text.chars().count();
+            let range = Self::edit_range(&rope, char_idx, end);
+            rope.remove(char_idx..end);
+            rope.insert(char_idx, new_text);
+            let edit = Edit {
+                kind,
+                timestamp: std::time::SystemTime::now(),
+            };
+            self.publish_change(edit.clone(), range);
+            edits.push(edit);
         }
+        edits
+    }
+
+    fn lock_collaboration(&self) -> std::sync::MutexGuard<'_, Option<Collaboration>> {
+        self.collaboration.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn position(rope: &Rope, char_idx: usize) -> Cursor {
+        let line = rope.char_to_line(char_idx);
+        Cursor::new(line, char_idx - rope.line_to_char(line))
+    }
+
+    fn utf16_position(rope: &Rope, cursor: Cursor) -> Cursor {
+        Cursor::new(
+            cursor.line,
+            rope.char_to_utf16_col(cursor.line, cursor.column),
+        )
+    }
+
+    /// Positions of `start..end` before the edit, read before the rope changes.
+    fn edit_range(rope: &Rope, start: usize, end: usize) -> EditRange {
+        let start = Self::position(rope, start);
+        let old_end = Self::position(rope, end);
+        EditRange {
+            start,
+            old_end,
+            utf16_start: Self::utf16_position(rope, start),
+            utf16_old_end: Self::utf16_position(rope, old_end),
+        }
+    }
+
+    /// The rope and its version, read together.
+    pub async fn snapshot(&self) -> (Rope, usize) {
+        let rope = self.rope.read().await;
+        (rope.clone(), self.version())
     }
 
     pub async fn get_text(&self) -> String {
@@ -43,8 +325,9 @@ impl TextModel {
         let mut rope = self.rope.write().await;
 
         if char_idx <= rope.len_chars() {
+            let range = Self::edit_range(&rope, char_idx, char_idx);
             rope.insert(char_idx, text);
-            self.version.fetch_add(1, Ordering::SeqCst);
+            self.publish(Edit::new_insert(char_idx, text.to_string()), range);
         }
     }
 
@@ -53,22 +336,93 @@ impl TextModel {
 
         if char_idx < rope.len_chars() {
             let end_idx = (char_idx + len).min(rope.len_chars());
+            let range = Self::edit_range(&rope, char_idx, end_idx);
+            let removed = rope.slice(char_idx..end_idx).to_string();
             rope.remove(char_idx..end_idx);
-            self.version.fetch_add(1, Ordering::SeqCst);
+            self.publish(Edit::new_delete(char_idx, removed), range);
         }
     }
 
     pub async fn replace(&self, char_idx: usize, len: usize, text: &str) {
         let mut rope = self.rope.write().await;
 
-        if char_idx < rope.len_chars() {
+        if char_idx <= rope.len_chars() {
             let end_idx = (char_idx + len).min(rope.len_chars());
+            let range = Self::edit_range(&rope, char_idx, end_idx);
+            let removed = rope.slice(char_idx..end_idx).to_string();
             rope.remove(char_idx..end_idx);
             rope.insert(char_idx, text);
-            self.version.fetch_add(1, Ordering::SeqCst);
+            self.publish(
+                Edit::new_replace(char_idx, removed, text.to_string()),
+                range,
+            );
         }
     }
 
+    /// Replaces several char ranges of the current text at once, e.g. every match of a
+    /// replace-all or the edits of a language server. Ranges are positions in the text
+    /// before any of the edits and must not overlap; text inserted at the same place goes
+    /// in in the given order. Nothing changes when a range is invalid.
+    ///
+    /// The version goes up once and subscribers get a single [`TextChange`] spanning
+    /// all the edits, while marks, tracked ranges and peers see each edit on its own.
+    pub async fn apply_edits(
+        &self,
+        edits: Vec<(Range<usize>, String)>,
+    ) -> Result<EditMap, EditError> {
+        let mut rope = self.rope.write().await;
+        let mut edits = edits;
+        edits.sort_by_key(|(range, _)| (range.start, range.end));
+        let len = rope.len_chars();
+        for (range, _) in &edits {
+            if range.start > range.end || range.end > len {
+                return Err(EditError::OutOfBounds {
+                    range: range.clone(),
+                    len,
+                });
+            }
+        }
+        for pair in edits.windows(2) {
+            if pair[0].0.end > pair[1].0.start {
+                return Err(EditError::Overlapping {
+                    first: pair[0].0.clone(),
+                    second: pair[1].0.clone(),
+                });
+            }
+        }
+        let (Some(first), Some(last)) = (edits.first(), edits.last()) else {
+            return Ok(EditMap::default());
+        };
+
+        let span = first.0.start..last.0.end;
+        let range = Self::edit_range(&rope, span.start, span.end);
+        let old_text = rope.slice(span.clone()).to_string();
+        for (range, text) in edits.iter().rev() {
+            let removed = rope.slice(range.clone()).to_string();
+            rope.remove(range.clone());
+            rope.insert(range.start, text);
+            let kind = Edit::new_replace(range.start, removed, text.clone()).kind;
+            if let Some(collaboration) = self.lock_collaboration().as_mut() {
+                let ops = collaboration.crdt.local_edit(&kind);
+                collaboration.outbox.extend(ops);
+            }
+            self.update_marks(|marks| marks.apply(&kind));
+            self.update_tracked_ranges(|ranges| ranges.apply(&kind));
+            self.update_anchors(|anchors| anchors.apply(&kind));
+        }
+
+        let map = EditMap {
+            edits: edits
+                .into_iter()
+                .map(|(range, text)| (range, text.chars().count()))
+                .collect(),
+        };
+        let new_end = map.new_ranges().last().map_or(span.end, |range| range.end);
+        let new_text = rope.slice(span.start..new_end).to_string();
+        self.announce(Edit::new_replace(span.start, old_text, new_text), range);
+        Ok(map)
+    }
+
     pub async fn get_char(&self, char_idx: usize) -> Option<char> {
         let rope = self.rope.read().await;
         rope.get_char(char_idx)
@@ -99,29 +453,221 @@ impl TextModel {
         rope.line_to_char(line_idx)
     }
 
+    /// See [`RopeExt::char_to_utf16_col`].
+    pub async fn char_to_utf16_col(&self, line_idx: usize, char_col: usize) -> usize {
+        let rope = self.rope.read().await;
+        rope.char_to_utf16_col(line_idx, char_col)
+    }
+
+    /// See [`RopeExt::utf16_to_char_col`].
+    pub async fn utf16_to_char_col(&self, line_idx: usize, utf16_col: usize) -> usize {
+        let rope = self.rope.read().await;
+        rope.utf16_to_char_col(line_idx, utf16_col)
+    }
+
+    pub async fn cursor_to_byte(&self, cursor: Cursor) -> usize {
+        let rope = self.rope.read().await;
+        rope.cursor_to_byte(cursor)
+    }
+
+    pub async fn byte_to_cursor(&self, byte_idx: usize) -> Cursor {
+        let rope = self.rope.read().await;
+        rope.byte_to_cursor(byte_idx)
+    }
+
+    /// See [`RopeExt::byte_to_char_clamped`].
+    pub async fn byte_to_char(&self, byte_idx: usize) -> usize {
+        let rope = self.rope.read().await;
+        rope.byte_to_char_clamped(byte_idx)
+    }
+
+    pub async fn char_to_byte(&self, char_idx: usize) -> usize {
+        let rope = self.rope.read().await;
+        rope.char_to_byte_clamped(char_idx)
+    }
+
+    pub async fn byte_to_line(&self, byte_idx: usize) -> usize {
+        let rope = self.rope.read().await;
+        rope.byte_to_line_clamped(byte_idx)
+    }
+
+    pub async fn line_to_byte(&self, line_idx: usize) -> usize {
+        let rope = self.rope.read().await;
+        rope.line_to_byte_clamped(line_idx)
+    }
+
+    pub async fn char_range_to_byte_range(&self, range: Range<usize>) -> Range<usize> {
+        let rope = self.rope.read().await;
+        rope.char_range_to_byte_range(range)
+    }
+
+    pub async fn byte_range_to_char_range(&self, range: Range<usize>) -> Range<usize> {
+        let rope = self.rope.read().await;
+        rope.byte_range_to_char_range(range)
+    }
+
+    /// Calls `f` with the index and text of each line in `lines`, without line breaks,
+    /// under one read lock and without allocating a `String` per line.
+    pub async fn for_each_line(&self, lines: Range<usize>, mut f: impl FnMut(usize, &str)) {
+        let rope = self.rope.read().await;
+        let start = lines.start;
+        for (offset, line) in rope.lines_cow(lines).enumerate() {
+            f(start + offset, &line);
+        }
+    }
+
     pub fn version(&self) -> usize {
         self.version.load(Ordering::SeqCst)
     }
 
     pub async fn set_text(&self, text: &str) {
         let mut rope = self.rope.write().await;
-        *rope = Rope::from_str(text);
-        self.version.fetch_add(1, Ordering::SeqCst);
+        let range = Self::edit_range(&rope, 0, rope.len_chars());
+        let old_text = String::from(std::mem::replace(&mut *rope, Rope::from_str(text)));
+        self.publish(Edit::new_replace(0, old_text, text.to_string()), range);
+    }
+
+    pub async fn search(&self, query: &SearchQuery) -> Vec<SearchMatch> {
+        let rope = self.rope.read().await;
+        query.find_all(&rope)
+    }
+
+    /// Matches wholly inside one of the `scope` char ranges.
+    pub async fn search_in(&self, query: &SearchQuery, scope: &[Range<usize>]) -> Vec<SearchMatch> {
+        let rope = self.rope.read().await;
+        query.find_in(&rope, scope)
+    }
+
+    /// Char index of the bracket matching the one at `char_idx`.
+    pub async fn matching_bracket(&self, char_idx: usize) -> Option<usize> {
+        let rope = self.rope.read().await;
+        crate::brackets::find_matching(&rope, char_idx)
     }
 
     pub async fn get_text_range(&self, start: usize, end: usize) -> String {
         let rope = self.rope.read().await;
-        let end = end.min(rope.len_chars());
-        if start >= end {
-            String::new()
-        } else {
-            rope.slice(start..end).to_string()
+        rope.slice_cow(start..end).into_owned()
+    }
+}
+
+/// Why [`TextModel::apply_edits`] rejected its edits.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EditError {
+    #[error("Edit range {range:?} is outside the text ({len} chars)")]
+    OutOfBounds { range: Range<usize>, len: usize },
+    #[error("Edit ranges {first:?} and {second:?} overlap")]
+    Overlapping {
+        first: Range<usize>,
+        second: Range<usize>,
+    },
+}
+
+/// Where the edits of [`TextModel::apply_edits`] went, to carry positions in the text
+/// before them over to the text after.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditMap {
+    /// Each replaced range, in document order, and how many chars went in its place.
+    edits: Vec<(Range<usize>, usize)>,
+}
+
+impl EditMap {
+    /// Where `char_idx` is after the edits. A position inside a replaced range keeps its
+    /// offset into the new text as far as that reaches; text inserted exactly at a
+    /// position goes after it.
+    pub fn map(&self, char_idx: usize) -> usize {
+        let mut delta = 0isize;
+        for (range, inserted) in &self.edits {
+            if range.end < char_idx || (range.end == char_idx && !range.is_empty()) {
+                delta += *inserted as isize - range.len() as isize;
+            } else if range.start < char_idx {
+                let start = range.start.saturating_add_signed(delta);
+                return start + (char_idx - range.start).min(*inserted);
+            } else {
+                break;
+            }
         }
+        char_idx.saturating_add_signed(delta)
+    }
+
+    /// The range each edit's new text occupies, in document order.
+    pub fn new_ranges(&self) -> Vec<Range<usize>> {
+        let mut delta = 0isize;
+        let mut ranges = Vec::with_capacity(self.edits.len());
+        for (range, inserted) in &self.edits {
+            let start = range.start.saturating_add_signed(delta);
+            ranges.push(start..start + inserted);
+            delta += *inserted as isize - range.len() as isize;
+        }
+        ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
     }
 }
 
+/// Where an edit applies in the text before it, in chars and in UTF-16 units.
+struct EditRange {
+    start: Cursor,
+    old_end: Cursor,
+    utf16_start: Cursor,
+    utf16_old_end: Cursor,
+}
+
 impl Default for TextModel {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marks::MarkName;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn applies_edits_at_once_and_maps_positions() {
+        Runtime::new().unwrap().block_on(async {
+            let model = TextModel::from_str("one two three\nfour");
+            let mut changes = model.subscribe();
+            model.update_marks(|marks| marks.set(MarkName::Number(1), 8));
+
+            let edits = vec![
+                (8..13, "3".to_string()),
+                (0..3, "1".to_string()),
+                (4..4, "and ".to_string()),
+            ];
+            let map = model.apply_edits(edits).await.unwrap();
+            assert_eq!(model.get_text().await, "1 and two 3\nfour");
+            assert_eq!(model.version(), 1);
+            assert_eq!(map.new_ranges(), [0..1, 2..6, 10..11]);
+            // Before the text inserted at 4, inside a shrunk range, and past every edit.
+            assert_eq!((map.map(4), map.map(12), map.map(14)), (2, 11, 12));
+            assert_eq!(
+                model.update_marks(|marks| marks.get(&MarkName::Number(1))),
+                Some(10)
+            );
+
+            let change = changes.try_recv().unwrap();
+            assert_eq!((change.before_version, change.after_version), (0, 1));
+            assert_eq!(change.old_text(), "one two three");
+            assert_eq!(change.new_text(), "1 and two 3");
+            assert!(changes.try_recv().is_err());
+
+            let overlapping = vec![(0..3, String::new()), (2..5, String::new())];
+            assert_eq!(
+                model.apply_edits(overlapping).await,
+                Err(EditError::Overlapping {
+                    first: 0..3,
+                    second: 2..5
+                })
+            );
+            assert!(model
+                .apply_edits(vec![(3..99, String::new())])
+                .await
+                .is_err());
+            assert_eq!(model.version(), 1);
+        });
+    }
+}
@@ -0,0 +1,236 @@
+use crate::cursor::Cursor;
+use unicode_width::UnicodeWidthChar;
+
+/// A slice of one buffer line shown on its own screen row, as a char column range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VisualRow {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl VisualRow {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Splits buffer lines into visual rows no wider than a wrap width, measured in
+/// terminal cells with tabs counted as `tab_size`.
+///
+/// Rows break after the last whitespace that fits and fall back to a hard break inside
+/// long words. A width of 0 disables wrapping, giving one row per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrapLayout {
+    rows: Vec<VisualRow>,
+    /// Index of the first row of each line.
+    line_starts: Vec<usize>,
+}
+
+impl WrapLayout {
+    pub fn new<'a>(
+        lines: impl IntoIterator<Item = &'a str>,
+        width: usize,
+        tab_size: usize,
+    ) -> Self {
+        let mut rows = Vec::new();
+        let mut line_starts = Vec::new();
+        for (line_idx, line) in lines.into_iter().enumerate() {
+            line_starts.push(rows.len());
+            let line = line.trim_end_matches(['\n', '\r']);
+            for (start, end) in wrap_line(line, width, tab_size) {
+                rows.push(VisualRow {
+                    line: line_idx,
+                    start,
+                    end,
+                });
+            }
+        }
+        Self { rows, line_starts }
+    }
+
+    pub fn rows(&self) -> &[VisualRow] {
+        &self.rows
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The rows a line occupies, front to back.
+    pub fn rows_of_line(&self, line: usize) -> &[VisualRow] {
+        let Some(&first) = self.line_starts.get(line) else {
+            return &[];
+        };
+        let last = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.rows.len());
+        &self.rows[first..last]
+    }
+
+    /// Index of the row showing `cursor`. A column on a wrap boundary belongs to the
+    /// row it starts, so the caret is drawn at the start of the next row.
+    pub fn row_of(&self, cursor: Cursor) -> usize {
+        let line = cursor.line.min(self.line_count().saturating_sub(1));
+        let Some(&first) = self.line_starts.get(line) else {
+            return 0;
+        };
+        let rows = self.rows_of_line(line);
+        let offset = rows
+            .iter()
+            .rposition(|row| row.start <= cursor.column)
+            .unwrap_or(0);
+        first + offset
+    }
+
+    /// Moves `cursor` by `delta` visual rows, keeping its offset into the row. The
+    /// cursor stays put when there is no row in that direction.
+    pub fn move_vertically(&self, cursor: Cursor, delta: isize) -> Cursor {
+        if self.rows.is_empty() {
+            return cursor;
+        }
+        let current = self.row_of(cursor);
+        let target = current
+            .saturating_add_signed(delta)
+            .min(self.rows.len() - 1);
+        if target == current {
+            return cursor;
+        }
+        let offset = cursor.column.saturating_sub(self.rows[current].start);
+        let row = self.rows[target];
+        let wraps = self
+            .rows
+            .get(target + 1)
+            .is_some_and(|next| next.line == row.line);
+        // The end of a wrapped row is the start of the next one.
+        let max = if wraps && !row.is_empty() {
+            row.end - 1
+        } else {
+            row.end
+        };
+        Cursor::new(row.line, (row.start + offset).min(max))
+    }
+}
+
+fn char_width(ch: char, tab_size: usize) -> usize {
+    if ch == '\t' {
+        tab_size
+    } else {
+        UnicodeWidthChar::width(ch).unwrap_or(1)
+    }
+}
+
+fn wrap_line(line: &str, width: usize, tab_size: usize) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    if width == 0 {
+        return vec![(0, chars.len())];
+    }
+    let widths: Vec<usize> = chars.iter().map(|ch| char_width(*ch, tab_size)).collect();
+
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut row_width = 0;
+    // Column just after the last whitespace on the current row.
+    let mut break_at = None;
+    for idx in 0..chars.len() {
+        // Whitespace may hang past the edge so rows never start with it.
+        if row_width + widths[idx] > width && idx > start && !chars[idx].is_whitespace() {
+            let end = break_at.unwrap_or(idx);
+            rows.push((start, end));
+            start = end;
+            row_width = widths[start..idx].iter().sum();
+            break_at = (start..idx)
+                .rev()
+                .find(|i| chars[*i].is_whitespace())
+                .map(|i| i + 1);
+        }
+        row_width += widths[idx];
+        if chars[idx].is_whitespace() {
+            break_at = Some(idx + 1);
+        }
+    }
+    rows.push((start, chars.len()));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(layout: &WrapLayout) -> Vec<(usize, usize, usize)> {
+        layout
+            .rows()
+            .iter()
+            .map(|row| (row.line, row.start, row.end))
+            .collect()
+    }
+
+    #[test]
+    fn wraps_at_whitespace_and_moves_by_visual_row() {
+        let lines = ["one two three four\n", "\n", "abcdefghij\n"];
+        let layout = WrapLayout::new(lines, 9, 4);
+        assert_eq!(
+            spans(&layout),
+            vec![
+                (0, 0, 8),
+                (0, 8, 14),
+                (0, 14, 18),
+                (1, 0, 0),
+                (2, 0, 9),
+                (2, 9, 10),
+            ]
+        );
+        assert_eq!(layout.rows_of_line(0).len(), 3);
+        assert_eq!(layout.row_of(Cursor::new(0, 8)), 1);
+        assert_eq!(layout.row_of(Cursor::new(0, 18)), 2);
+        assert_eq!(layout.row_of(Cursor::new(2, 9)), 5);
+
+        assert_eq!(
+            layout.move_vertically(Cursor::new(0, 2), 1),
+            Cursor::new(0, 10)
+        );
+        assert_eq!(
+            layout.move_vertically(Cursor::new(0, 7), 1),
+            Cursor::new(0, 13)
+        );
+        assert_eq!(
+            layout.move_vertically(Cursor::new(0, 17), -1),
+            Cursor::new(0, 11)
+        );
+        assert_eq!(
+            layout.move_vertically(Cursor::new(0, 17), 1),
+            Cursor::new(1, 0)
+        );
+        assert_eq!(
+            layout.move_vertically(Cursor::new(2, 3), -2),
+            Cursor::new(0, 17)
+        );
+        assert_eq!(
+            layout.move_vertically(Cursor::new(0, 3), -1),
+            Cursor::new(0, 3)
+        );
+
+        let long_word = WrapLayout::new(["aaaaaaa bb"], 4, 4);
+        assert_eq!(spans(&long_word), vec![(0, 0, 4), (0, 4, 8), (0, 8, 10)]);
+        assert_eq!(
+            long_word.move_vertically(Cursor::new(0, 9), -1),
+            Cursor::new(0, 5)
+        );
+
+        let tabs = WrapLayout::new(["\tx = 1"], 6, 4);
+        assert_eq!(spans(&tabs), vec![(0, 0, 3), (0, 3, 6)]);
+
+        let unwrapped = WrapLayout::new(lines, 0, 4);
+        assert_eq!(unwrapped.row_count(), 3);
+    }
+}
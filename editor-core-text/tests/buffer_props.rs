@@ -0,0 +1,298 @@
+//! Random sequences of edits, cursor changes, undo and redo against the invariants
+//! every `Buffer` operation must keep.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use editor_core_text::{
+    Buffer, Cursor, EditLog, IndentStyle, LineDirection, LineEnding, ManualClock, RemoteOp,
+    Selection, TextModel,
+};
+use proptest::prelude::*;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(String),
+    InsertChar(char),
+    LineBreak,
+    DeleteBackward,
+    DeleteForward,
+    DeleteLines,
+    DuplicateLines,
+    MoveLines(LineDirection),
+    Replace(usize, usize, String),
+    Select(Vec<((usize, usize), (usize, usize))>),
+    AddCursors(LineDirection),
+    AddNextOccurrence,
+    SplitIntoLines,
+    SetLineEnding(LineEnding),
+    Cut,
+    Paste(Vec<String>),
+    Undo,
+    Redo,
+    Wait(u64),
+}
+
+fn text() -> impl Strategy<Value = String> {
+    proptest::string::string_regex("[ab(){}\n\"]{0,4}").unwrap()
+}
+
+fn initial_text() -> impl Strategy<Value = String> {
+    proptest::string::string_regex("[ab(\n]{0,12}").unwrap()
+}
+
+fn position() -> impl Strategy<Value = (usize, usize)> {
+    (0usize..6, 0usize..8)
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => text().prop_map(Op::Insert),
+        4 => prop::sample::select(vec!['a', '(', ')', '{', '"', ' ']).prop_map(Op::InsertChar),
+        1 => Just(Op::LineBreak),
+        3 => Just(Op::DeleteBackward),
+        2 => Just(Op::DeleteForward),
+        1 => Just(Op::DeleteLines),
+        1 => Just(Op::DuplicateLines),
+        1 => prop_oneof![Just(LineDirection::Up), Just(LineDirection::Down)].prop_map(Op::MoveLines),
+        1 => (0usize..30, 0usize..5, text()).prop_map(|(start, len, text)| Op::Replace(start, len, text)),
+        3 => prop::collection::vec((position(), position()), 1..4).prop_map(Op::Select),
+        1 => prop_oneof![Just(LineDirection::Up), Just(LineDirection::Down)].prop_map(Op::AddCursors),
+        1 => Just(Op::AddNextOccurrence),
+        1 => Just(Op::SplitIntoLines),
+        1 => prop_oneof![Just(LineEnding::Lf), Just(LineEnding::CrLf)].prop_map(Op::SetLineEnding),
+        1 => Just(Op::Cut),
+        2 => prop::collection::vec(text(), 1..4).prop_map(Op::Paste),
+        2 => Just(Op::Undo),
+        1 => Just(Op::Redo),
+        2 => prop_oneof![Just(100u64), Just(1000)].prop_map(Op::Wait),
+    ]
+}
+
+async fn apply(buffer: &mut Buffer, clock: &ManualClock, op: Op) {
+    match op {
+        Op::Insert(text) => buffer.insert_text_at_cursor(&text).await,
+        Op::InsertChar(ch) => buffer.insert_char(ch).await,
+        Op::LineBreak => {
+            buffer
+                .insert_line_break_and_indent(IndentStyle::Spaces(2))
+                .await
+        }
+        Op::DeleteBackward => buffer.delete_backward().await,
+        Op::DeleteForward => buffer.delete_forward().await,
+        Op::DeleteLines => buffer.delete_lines().await,
+        Op::DuplicateLines => buffer.duplicate_lines().await,
+        Op::MoveLines(direction) => buffer.move_lines(direction).await,
+        Op::Replace(start, len, text) => {
+            let chars: Vec<char> = buffer.get_text().await.chars().collect();
+            // Callers never split a `\r\n`, so neither end lands inside one.
+            let outside_crlf = |idx: usize| {
+                let idx = idx.min(chars.len());
+                if idx > 0 && chars[idx - 1] == '\r' && chars.get(idx) == Some(&'\n') {
+                    idx - 1
+                } else {
+                    idx
+                }
+            };
+            let start = outside_crlf(start);
+            let end = outside_crlf(start + len).max(start);
+            buffer.replace_ranges(&[(start..end, text)]).await;
+        }
+        Op::Select(ranges) => {
+            let selections = ranges
+                .into_iter()
+                .map(|(anchor, active)| {
+                    Selection::new(
+                        Cursor::new(anchor.0, anchor.1),
+                        Cursor::new(active.0, active.1),
+                    )
+                })
+                .collect();
+            buffer.restore_selections(selections).await;
+        }
+        Op::AddCursors(direction) => buffer.add_cursors_vertically(direction).await,
+        Op::AddNextOccurrence => {
+            buffer.add_next_occurrence().await;
+        }
+        Op::SplitIntoLines => buffer.split_selection_into_lines().await,
+        Op::SetLineEnding(line_ending) => {
+            buffer.set_line_ending(line_ending).await;
+        }
+        Op::Cut => {
+            buffer.cut_selections().await;
+        }
+        Op::Paste(pieces) => buffer.paste(&pieces).await,
+        Op::Undo => {
+            buffer.undo().await;
+        }
+        Op::Redo => {
+            buffer.redo().await;
+        }
+        Op::Wait(ms) => clock.advance(Duration::from_millis(ms)),
+    }
+}
+
+/// Line lengths in chars, without the line break (`\r\n` or `\n`).
+fn line_lengths(text: &str) -> Vec<usize> {
+    text.split('\n')
+        .map(|line| line.trim_end_matches('\r').chars().count())
+        .collect()
+}
+
+async fn check_invariants(buffer: &Buffer) -> Result<(), TestCaseError> {
+    let text = buffer.get_text().await;
+    let snapshot = buffer.snapshot().await;
+    prop_assert_eq!(&snapshot.text(), &text);
+    prop_assert_eq!(snapshot.len_chars(), text.chars().count());
+
+    let lengths = line_lengths(&text);
+    prop_assert_eq!(buffer.line_count().await, lengths.len());
+    prop_assert_eq!(buffer.get_cursors().len(), buffer.get_selections().len());
+    for selection in buffer.get_selections() {
+        for cursor in [selection.anchor, selection.active] {
+            prop_assert!(
+                cursor.line < lengths.len() && cursor.column <= lengths[cursor.line],
+                "{:?} is outside {:?}",
+                cursor,
+                text
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run(
+    test: impl std::future::Future<Output = Result<(), TestCaseError>>,
+) -> Result<(), TestCaseError> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(test)
+}
+
+/// A step of two peers editing one shared text.
+#[derive(Debug, Clone)]
+enum PeerStep {
+    /// The peer replaces `len` chars at `start` (both taken modulo the text length).
+    Edit(bool, usize, usize, String),
+    /// The oldest operation from the peer that has not arrived yet arrives at the other.
+    Deliver(bool),
+}
+
+fn peer_step() -> impl Strategy<Value = PeerStep> {
+    prop_oneof![
+        3 => (any::<bool>(), 0usize..40, 0usize..4, text())
+            .prop_map(|(peer, start, len, text)| PeerStep::Edit(peer, start, len, text)),
+        2 => any::<bool>().prop_map(PeerStep::Deliver),
+    ]
+}
+
+fn buffer_with_clock(text: &str) -> (Buffer, Arc<ManualClock>) {
+    let clock = Arc::new(ManualClock::default());
+    let mut buffer = Buffer::from_text(text);
+    buffer.set_clock(clock.clone());
+    (buffer, clock)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn edits_keep_cursors_and_text_consistent(
+        initial in initial_text(),
+        ops in prop::collection::vec(op(), 1..40),
+    ) {
+        run(async {
+            let (mut buffer, clock) = buffer_with_clock(&initial);
+            for op in ops {
+                apply(&mut buffer, &clock, op).await;
+                check_invariants(&buffer).await?;
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip(
+        initial in initial_text(),
+        ops in prop::collection::vec(op(), 1..40),
+    ) {
+        run(async {
+            let (mut buffer, clock) = buffer_with_clock(&initial);
+            for op in ops {
+                apply(&mut buffer, &clock, op).await;
+            }
+            // Redo whatever the ops left undone so both stacks span the whole history.
+            while buffer.redo().await {}
+            let edited = buffer.get_text().await;
+
+            while buffer.undo().await {
+                check_invariants(&buffer).await?;
+            }
+            prop_assert_eq!(buffer.get_text().await, initial);
+            prop_assert!(!buffer.is_dirty());
+
+            while buffer.redo().await {
+                check_invariants(&buffer).await?;
+            }
+            prop_assert_eq!(buffer.get_text().await, edited);
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn shared_texts_converge_whatever_order_operations_arrive_in(
+        initial in initial_text(),
+        steps in prop::collection::vec(peer_step(), 1..40),
+    ) {
+        run(async {
+            let peers = [TextModel::from_str(&initial), TextModel::from_str(&initial)];
+            peers[0].start_collaboration(1).await;
+            peers[1].start_collaboration(2).await;
+            // Operations in flight from each peer, in the order it sent them.
+            let mut in_flight: [Vec<RemoteOp>; 2] = [Vec::new(), Vec::new()];
+
+            for step in steps {
+                match step {
+                    PeerStep::Edit(peer, start, len, text) => {
+                        let model = &peers[peer as usize];
+                        let start = start % (model.len().await + 1);
+                        model.replace(start, len, &text).await;
+                        in_flight[peer as usize].extend(model.take_local_ops());
+                    }
+                    PeerStep::Deliver(peer) => {
+                        if !in_flight[peer as usize].is_empty() {
+                            let op = in_flight[peer as usize].remove(0);
+                            peers[!peer as usize].apply_remote_op(op).await;
+                        }
+                    }
+                }
+            }
+            for peer in [0, 1] {
+                for op in std::mem::take(&mut in_flight[peer]) {
+                    peers[1 - peer].apply_remote_op(op).await;
+                }
+            }
+            prop_assert_eq!(peers[0].get_text().await, peers[1].get_text().await);
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn edit_log_replays_to_the_buffer_text(
+        initial in initial_text(),
+        ops in prop::collection::vec(op(), 1..40),
+    ) {
+        run(async {
+            let (mut buffer, clock) = buffer_with_clock(&initial);
+            buffer.text_model().start_edit_log().await;
+            for op in ops {
+                apply(&mut buffer, &clock, op).await;
+            }
+            let log = buffer.text_model().edit_log().unwrap();
+            let log = EditLog::from_json(&log.to_json()).unwrap();
+            prop_assert_eq!(log.text_at(log.len()).unwrap(), buffer.get_text().await);
+            Ok(())
+        })?;
+    }
+}
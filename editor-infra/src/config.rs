@@ -1,3 +1,4 @@
+use crate::language::LANGUAGE_PACKS;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -17,6 +18,251 @@ pub struct EditorConfig {
     pub auto_save: bool,
     pub font_size: f32,
     pub font_family: String,
+    /// 删除文件时跳过系统回收站
+    #[serde(default)]
+    pub delete_permanently: bool,
+    /// 遍历工作区时是否进入符号链接指向的目录
+    #[serde(default = "default_follow_symlinks")]
+    pub follow_symlinks: bool,
+    /// 文件树中始终隐藏的条目，gitignore 写法，相对工作区根目录，如 `dist/`、`*.log`
+    #[serde(default)]
+    pub file_tree_exclude: Vec<String>,
+    /// 文件树中以暗色显示被 .gitignore/.ignore 忽略或被排除的条目，而不是隐藏
+    #[serde(default)]
+    pub file_tree_show_ignored: bool,
+    /// 快速打开与代码索引最多收录的文件数，超出时显示提示
+    #[serde(default = "default_max_indexed_files")]
+    pub max_indexed_files: usize,
+    /// 输入括号和引号时自动补全配对字符
+    #[serde(default = "default_auto_close_brackets")]
+    pub auto_close_brackets: bool,
+    /// 保存前先格式化
+    #[serde(default)]
+    pub format_on_save: bool,
+    /// 输入 `}`、`;` 等语言服务器声明的字符后调整格式
+    #[serde(default)]
+    pub format_on_type: bool,
+    /// 移动或重命名文件时请语言服务器同步更新模块声明与导入
+    #[serde(default = "default_update_imports_on_move")]
+    pub update_imports_on_move: bool,
+    /// 剪贴板历史保留的条目数
+    #[serde(default = "default_clipboard_history_size")]
+    pub clipboard_history_size: usize,
+    /// 按行宽拆分或合并参数列表时允许的最大列数
+    #[serde(default = "default_max_line_length")]
+    pub max_line_length: usize,
+    /// 记录每个缓冲区的编辑日志，可导出后附在问题反馈里逐步回放
+    #[serde(default)]
+    pub record_edit_log: bool,
+    /// 把打开文件的每一步编辑写入磁盘上的日志，可回放上次保存以来的修改，崩溃后恢复未保存的内容。
+    /// 日志含有被编辑文件的内容，保存后重新开始，关闭文件时删除
+    #[serde(default)]
+    pub journal_edits: bool,
+    /// 运行笔记本单元格的 Python 解释器
+    #[serde(default = "default_notebook_python")]
+    pub notebook_python: String,
+    /// 缓冲区闲置多少分钟后释放其缓存（不影响内容和撤销历史），0 表示不释放
+    #[serde(default = "default_evict_idle_buffers_minutes")]
+    pub evict_idle_buffers_minutes: u64,
+    /// 每隔多少分钟把打开的标签页、光标和未保存的草稿写入会话，0 表示只在打开、关闭、保存时写入
+    #[serde(default = "default_checkpoint_interval_minutes")]
+    pub checkpoint_interval_minutes: u64,
+    /// 闲置多少分钟后在后台生成工作区健康报告（诊断、TODO、大文件、久未提交的分支），0 表示不生成
+    #[serde(default)]
+    pub health_report_idle_minutes: u64,
+    /// 超过多少天没有提交的分支在健康报告中列为陈旧
+    #[serde(default = "default_stale_branch_days")]
+    pub stale_branch_days: u64,
+    /// 性能模式关闭的功能；各工作区单独开关，开关状态记在工作区的会话里
+    #[serde(default)]
+    pub performance_mode: PerformanceModeConfig,
+    /// 语言服务器不提供格式化时使用的外部格式化命令
+    #[serde(default = "default_formatters")]
+    pub formatters: Vec<FormatterConfig>,
+    /// 可从外部工具列表运行的命令
+    #[serde(default)]
+    pub external_tools: Vec<ExternalToolConfig>,
+    /// 自建的代码托管平台；github.com 与 gitlab.com 不用配置
+    #[serde(default)]
+    pub forges: Vec<ForgeConfig>,
+    /// 注释和提交信息中按模式识别的问题编号，如 `JIRA-456`；`#123` 按 origin 所在的托管平台识别
+    #[serde(default)]
+    pub issue_trackers: Vec<IssueTrackerConfig>,
+}
+
+/// 性能模式：在超大仓库或性能较弱的机器上关闭耗资源的功能，并拉长请求的防抖间隔
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerformanceModeConfig {
+    /// 不建立代码索引，代码库搜索不可用
+    pub disable_ai_indexing: bool,
+    /// 不读取 Git Blame
+    pub disable_git_blame: bool,
+    /// 不做语法高亮
+    pub disable_syntax_highlighting: bool,
+    /// 语言服务器请求（补全、悬停、诊断）的防抖毫秒数
+    pub lsp_debounce_ms: u64,
+    /// 总是以性能模式打开的工作区
+    pub workspaces: Vec<PathBuf>,
+}
+
+impl Default for PerformanceModeConfig {
+    fn default() -> Self {
+        Self {
+            disable_ai_indexing: true,
+            disable_git_blame: true,
+            disable_syntax_highlighting: true,
+            lsp_debounce_ms: 600,
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+/// 外部格式化命令：从 stdin 读取源码，向 stdout 输出结果。参数中的 `{file}` 替换为文件路径
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatterConfig {
+    pub language: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 超时后终止命令，保留原文
+    #[serde(default = "default_formatter_timeout")]
+    pub timeout_seconds: u64,
+}
+
+/// 用户定义的外部工具。参数中的 `$FILE`、`$SELECTION`、`$WORKSPACE` 替换为当前文件路径、
+/// 选中的文本和工作区路径，选中的文本同时写入 stdin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalToolConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub output: ToolOutput,
+    /// 超时后终止命令
+    #[serde(default = "default_tool_timeout")]
+    pub timeout_seconds: u64,
+}
+
+/// 外部工具输出的去处
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolOutput {
+    /// 显示在输出面板
+    #[default]
+    Panel,
+    /// 替换选中的文本，没有选区时插入到光标处
+    ReplaceSelection,
+}
+
+/// 代码托管平台，按主机名匹配仓库的远程地址。没有配置令牌时读取
+/// `GITHUB_TOKEN` 或 `GITLAB_TOKEN` 环境变量
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForgeConfig {
+    pub host: String,
+    pub kind: ForgeKind,
+    #[serde(default)]
+    pub token: Option<String>,
+    /// REST API 的地址，留空时按平台的惯例从主机名推出
+    #[serde(default)]
+    pub api_url: Option<String>,
+}
+
+/// 问题跟踪系统。`pattern` 是正则表达式，`url` 中的 `$0` 替换为匹配到的编号，`$1` 等替换为分组
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueTrackerConfig {
+    pub pattern: String,
+    pub url: String,
+    /// 只在这个目录下的工作区中生效，留空时对所有工作区生效
+    #[serde(default)]
+    pub workspace: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForgeKind {
+    #[serde(rename = "github")]
+    GitHub,
+    #[serde(rename = "gitlab")]
+    GitLab,
+}
+
+fn default_follow_symlinks() -> bool {
+    true
+}
+
+fn default_max_indexed_files() -> usize {
+    10_000
+}
+
+fn default_auto_close_brackets() -> bool {
+    true
+}
+
+fn default_update_imports_on_move() -> bool {
+    true
+}
+
+fn default_notebook_python() -> String {
+    "python3".to_string()
+}
+
+fn default_max_line_length() -> usize {
+    100
+}
+
+fn default_clipboard_history_size() -> usize {
+    20
+}
+
+fn default_evict_idle_buffers_minutes() -> u64 {
+    30
+}
+
+fn default_checkpoint_interval_minutes() -> u64 {
+    2
+}
+
+fn default_stale_branch_days() -> u64 {
+    90
+}
+
+fn default_formatter_timeout() -> u64 {
+    10
+}
+
+fn default_tool_timeout() -> u64 {
+    30
+}
+
+fn default_formatters() -> Vec<FormatterConfig> {
+    LANGUAGE_PACKS
+        .iter()
+        .filter_map(|pack| {
+            let (command, args) = pack.formatter?;
+            Some(FormatterConfig {
+                language: pack.id.to_string(),
+                command: command.to_string(),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                timeout_seconds: default_formatter_timeout(),
+            })
+        })
+        .collect()
+}
+
+fn default_language_servers() -> Vec<LSPServerConfig> {
+    LANGUAGE_PACKS
+        .iter()
+        .filter_map(|pack| {
+            let (command, args) = pack.language_server?;
+            Some(LSPServerConfig {
+                language: pack.id.to_string(),
+                command: command.to_string(),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+            })
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -227,6 +473,42 @@ pub struct UIConfig {
     pub theme: String,
     pub show_line_numbers: bool,
     pub show_minimap: bool,
+    /// 在行尾显示该行第一条诊断信息
+    #[serde(default)]
+    pub inline_diagnostics: InlineDiagnosticsConfig,
+    /// 长行折行显示，不改变文件内容
+    #[serde(default)]
+    pub soft_wrap: bool,
+    /// 折行的最大列数；编辑区更窄时按编辑区宽度折行
+    #[serde(default = "default_wrap_column")]
+    pub wrap_column: usize,
+}
+
+fn default_wrap_column() -> usize {
+    100
+}
+
+/// 行内诊断，按严重级别分别开关；波浪下划线始终显示
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InlineDiagnosticsConfig {
+    pub enabled: bool,
+    pub errors: bool,
+    pub warnings: bool,
+    pub information: bool,
+    pub hints: bool,
+}
+
+impl Default for InlineDiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            errors: true,
+            warnings: true,
+            information: false,
+            hints: false,
+        }
+    }
 }
 
 // 运行时模型信息
@@ -454,6 +736,29 @@ impl Default for Config {
                 auto_save: false,
                 font_size: 14.0,
                 font_family: "Monaco".to_string(),
+                delete_permanently: false,
+                follow_symlinks: true,
+                file_tree_exclude: Vec::new(),
+                file_tree_show_ignored: false,
+                max_indexed_files: default_max_indexed_files(),
+                auto_close_brackets: default_auto_close_brackets(),
+                format_on_save: false,
+                format_on_type: false,
+                update_imports_on_move: default_update_imports_on_move(),
+                clipboard_history_size: default_clipboard_history_size(),
+                max_line_length: default_max_line_length(),
+                record_edit_log: false,
+                journal_edits: false,
+                notebook_python: default_notebook_python(),
+                evict_idle_buffers_minutes: default_evict_idle_buffers_minutes(),
+                checkpoint_interval_minutes: default_checkpoint_interval_minutes(),
+                health_report_idle_minutes: 0,
+                stale_branch_days: default_stale_branch_days(),
+                performance_mode: PerformanceModeConfig::default(),
+                formatters: default_formatters(),
+                external_tools: Vec::new(),
+                forges: Vec::new(),
+                issue_trackers: Vec::new(),
             },
             ai: AIConfig {
                 default_model: "gpt-5".to_string(),
@@ -466,23 +771,15 @@ impl Default for Config {
             },
             lsp: LSPConfig {
                 enabled: true,
-                servers: vec![
-                    LSPServerConfig {
-                        language: "rust".to_string(),
-                        command: "rust-analyzer".to_string(),
-                        args: vec![],
-                    },
-                    LSPServerConfig {
-                        language: "python".to_string(),
-                        command: "pylsp".to_string(),
-                        args: vec![],
-                    },
-                ],
+                servers: default_language_servers(),
             },
             ui: UIConfig {
                 theme: "dark".to_string(),
                 show_line_numbers: true,
                 show_minimap: true,
+                inline_diagnostics: InlineDiagnosticsConfig::default(),
+                soft_wrap: false,
+                wrap_column: default_wrap_column(),
             },
         }
     }
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+use url::Url;
+
+pub const SCHEME: &str = "fusang";
+
+/// `fusang://` 深链接，行列号从 1 开始
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeepLink {
+    /// `fusang://open?file=/path/to/file&line=42&column=7`
+    Open {
+        file: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+}
+
+impl DeepLink {
+    pub fn parse(uri: &str) -> anyhow::Result<Self> {
+        let url = Url::parse(uri.trim())?;
+        if url.scheme() != SCHEME {
+            anyhow::bail!("Not a {}:// link: {}", SCHEME, uri);
+        }
+
+        match url.host_str() {
+            Some("open") => {
+                let mut file = None;
+                let mut line = None;
+                let mut column = None;
+                for (key, value) in url.query_pairs() {
+                    match key.as_ref() {
+                        "file" => file = Some(PathBuf::from(value.as_ref())),
+                        "line" => line = value.parse::<usize>().ok().filter(|l| *l > 0),
+                        "column" | "col" => column = value.parse::<usize>().ok().filter(|c| *c > 0),
+                        _ => {}
+                    }
+                }
+                let file = file.ok_or_else(|| anyhow::anyhow!("Missing file in {}", uri))?;
+                Ok(DeepLink::Open { file, line, column })
+            }
+            other => anyhow::bail!("Unsupported {}:// action: {:?}", SCHEME, other),
+        }
+    }
+
+    pub fn to_uri(&self) -> String {
+        match self {
+            DeepLink::Open { file, line, column } => {
+                let mut url = Url::parse(&format!("{}://open", SCHEME)).expect("valid base URI");
+                {
+                    let mut query = url.query_pairs_mut();
+                    query.append_pair("file", &file.to_string_lossy());
+                    if let Some(line) = line {
+                        query.append_pair("line", &line.to_string());
+                    }
+                    if let Some(column) = column {
+                        query.append_pair("column", &column.to_string());
+                    }
+                }
+                url.to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_open_links_and_round_trips() {
+        let link = DeepLink::parse("fusang://open?file=/tmp/a%20b.rs&line=42&column=3").unwrap();
+        assert_eq!(
+            link,
+            DeepLink::Open {
+                file: PathBuf::from("/tmp/a b.rs"),
+                line: Some(42),
+                column: Some(3),
+            }
+        );
+        assert_eq!(DeepLink::parse(&link.to_uri()).unwrap(), link);
+
+        assert!(DeepLink::parse("https://open?file=/tmp/a.rs").is_err());
+        assert!(DeepLink::parse("fusang://open?line=1").is_err());
+    }
+}
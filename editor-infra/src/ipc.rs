@@ -0,0 +1,216 @@
+use crate::paths;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+/// 连接多久没有发来数据就断开
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 运行中实例写入监听端口和口令的位置
+pub fn port_file() -> PathBuf {
+    paths::data_dir().join("ipc-port")
+}
+
+/// 把消息交给已运行的实例；没有实例在监听时返回 false
+pub fn send_to_running_instance(message: &str) -> bool {
+    send_to(&port_file(), message)
+}
+
+fn send_to(port_file: &Path, message: &str) -> bool {
+    let Some((port, token)) = std::fs::read_to_string(port_file)
+        .ok()
+        .and_then(|text| parse_port_file(&text))
+    else {
+        return false;
+    };
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+        Ok(mut stream) => writeln!(stream, "{}\n{}", token, message.trim()).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// 端口文件第一行是端口，第二行是口令
+fn parse_port_file(text: &str) -> Option<(u16, String)> {
+    let mut lines = text.lines();
+    let port = lines.next()?.trim().parse().ok()?;
+    let token = lines.next()?.trim();
+    (!token.is_empty()).then(|| (port, token.to_string()))
+}
+
+/// 本机单实例 IPC：其他进程（命令行、URI 处理器）通过它把消息交给当前窗口。
+///
+/// Listens on a loopback port and publishes it in [`port_file`] with a random token,
+/// readable only by the current user. A connection must send the token as its first
+/// line, so other users on the machine can't inject links; each further line is one
+/// message, forwarded to the channel unchanged. Every connection is read on its own
+/// thread with a read timeout, so an idle one can't hold up the rest.
+pub struct IpcServer {
+    port: u16,
+    port_file: PathBuf,
+}
+
+impl IpcServer {
+    pub fn start(sender: UnboundedSender<String>) -> std::io::Result<Self> {
+        Self::start_at(port_file(), sender)
+    }
+
+    fn start_at(port_file: PathBuf, sender: UnboundedSender<String>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener.local_addr()?.port();
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        write_private(&port_file, &format!("{}\n{}\n", port, token))?;
+
+        std::thread::Builder::new()
+            .name("fusang-ipc".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if sender.is_closed() {
+                        return;
+                    }
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+                        continue;
+                    }
+                    let sender = sender.clone();
+                    let token = token.clone();
+                    let _ = std::thread::Builder::new()
+                        .name("fusang-ipc-connection".to_string())
+                        .spawn(move || forward_messages(stream, &token, &sender));
+                }
+            })?;
+
+        Ok(Self { port, port_file })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+/// 口令对上之后，把连接发来的每一行交给 `sender`
+fn forward_messages(stream: TcpStream, token: &str, sender: &UnboundedSender<String>) {
+    let mut lines = BufReader::new(stream).lines().map_while(Result::ok);
+    if lines.next().as_deref().map(str::trim) != Some(token) {
+        return;
+    }
+    for line in lines {
+        if !line.trim().is_empty() && sender.send(line).is_err() {
+            return;
+        }
+    }
+}
+
+/// 写入只有当前用户能读写的文件
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // 文件已存在时 mode 不生效
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        // 只删除自己写入的端口文件，避免误删新实例的
+        let ours = std::fs::read_to_string(&self.port_file)
+            .ok()
+            .and_then(|text| parse_port_file(&text))
+            .is_some_and(|(port, _)| port == self.port);
+        if ours {
+            let _ = std::fs::remove_file(&self.port_file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn temp_port_file() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("fusang-ipc-{}", uuid::Uuid::new_v4()))
+            .join("ipc-port")
+    }
+
+    #[test]
+    fn forwards_messages_sent_with_the_token() {
+        let port_file = temp_port_file();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let server = IpcServer::start_at(port_file.clone(), sender).unwrap();
+
+        assert!(send_to(&port_file, "fusang://open?file=/tmp/a.rs\n"));
+        assert_eq!(
+            receiver.blocking_recv().unwrap(),
+            "fusang://open?file=/tmp/a.rs"
+        );
+
+        drop(server);
+        assert!(!port_file.exists());
+        std::fs::remove_dir(port_file.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn ignores_connections_without_the_token() {
+        let port_file = temp_port_file();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let server = IpcServer::start_at(port_file.clone(), sender).unwrap();
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, server.port()));
+
+        let mut forged = TcpStream::connect(addr).unwrap();
+        writeln!(forged, "fusang://open?file=/etc/passwd").unwrap();
+        // A connection that never sends anything doesn't hold up the others.
+        let _idle = TcpStream::connect(addr).unwrap();
+
+        assert!(send_to(&port_file, "fusang://open?file=/tmp/b.rs"));
+        assert_eq!(
+            receiver.blocking_recv().unwrap(),
+            "fusang://open?file=/tmp/b.rs"
+        );
+        drop(forged);
+        drop(server);
+        assert!(receiver.try_recv().is_err());
+        std::fs::remove_dir_all(port_file.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn port_file_is_private_to_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let port_file = temp_port_file();
+        std::fs::create_dir_all(port_file.parent().unwrap()).unwrap();
+        std::fs::write(&port_file, "1\nstale\n").unwrap();
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let server = IpcServer::start_at(port_file.clone(), sender).unwrap();
+
+        let mode = std::fs::metadata(&port_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let (port, token) = parse_port_file(&std::fs::read_to_string(&port_file).unwrap()).unwrap();
+        assert_eq!(port, server.port());
+        assert_eq!(token.len(), 32);
+
+        drop(server);
+        std::fs::remove_dir_all(port_file.parent().unwrap()).unwrap();
+    }
+}
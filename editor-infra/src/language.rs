@@ -0,0 +1,280 @@
+use std::path::Path;
+
+/// 一门语言的内置知识：识别文件、注释、缩进、语言服务器、语法、格式化与测试文件约定。
+/// 支持新语言只需在 [`LANGUAGE_PACKS`] 中加一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LanguagePack {
+    /// LSP 语言标识
+    pub id: &'static str,
+    /// 文件扩展名，小写，不含点
+    pub extensions: &'static [&'static str],
+    /// 行注释前缀
+    pub line_comment: Option<&'static str>,
+    /// 块注释的开始与结束
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// 约定用 Tab 缩进，如 Go；文件没有可识别的缩进时不按配置使用空格
+    pub tab_indent: bool,
+    /// 约定的缩进宽度，文件没有可识别的缩进时代替配置的 tab_size
+    pub indent_width: Option<usize>,
+    /// 默认的语言服务器命令与参数
+    pub language_server: Option<(&'static str, &'static [&'static str])>,
+    /// 语法树使用的 tree-sitter 语法名
+    pub grammar: Option<&'static str>,
+    /// 默认的格式化命令与参数，`{file}` 替换为文件路径
+    pub formatter: Option<(&'static str, &'static [&'static str])>,
+    /// 测试文件名的模式，`*` 匹配任意字符
+    pub test_files: &'static [&'static str],
+    /// 其中的文件都算测试文件的目录名
+    pub test_dirs: &'static [&'static str],
+}
+
+const PRETTIER: (&str, &[&str]) = ("prettier", &["--stdin-filepath", "{file}"]);
+
+const C_LIKE: LanguagePack = LanguagePack {
+    id: "",
+    extensions: &[],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    tab_indent: false,
+    indent_width: None,
+    language_server: None,
+    grammar: None,
+    formatter: None,
+    test_files: &[],
+    test_dirs: &[],
+};
+
+const JS_TESTS: &[&str] = &["*.test.*", "*.spec.*"];
+
+/// 内置的语言，按识别文件时的优先顺序
+pub const LANGUAGE_PACKS: &[LanguagePack] = &[
+    LanguagePack {
+        id: "rust",
+        extensions: &["rs"],
+        language_server: Some(("rust-analyzer", &[])),
+        grammar: Some("rust"),
+        formatter: Some(("rustfmt", &["--edition", "2021"])),
+        test_dirs: &["tests", "benches"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "python",
+        extensions: &["py"],
+        line_comment: Some("#"),
+        block_comment: None,
+        indent_width: Some(4),
+        language_server: Some(("pylsp", &[])),
+        grammar: Some("python"),
+        formatter: Some(("black", &["--quiet", "-"])),
+        test_files: &["test_*.py", "*_test.py"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "javascript",
+        extensions: &["js", "mjs", "cjs"],
+        grammar: Some("javascript"),
+        formatter: Some(PRETTIER),
+        test_files: JS_TESTS,
+        test_dirs: &["__tests__"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "typescript",
+        extensions: &["ts"],
+        grammar: Some("typescript"),
+        formatter: Some(PRETTIER),
+        test_files: JS_TESTS,
+        test_dirs: &["__tests__"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "typescriptreact",
+        extensions: &["tsx"],
+        grammar: Some("tsx"),
+        formatter: Some(PRETTIER),
+        test_files: JS_TESTS,
+        test_dirs: &["__tests__"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "javascriptreact",
+        extensions: &["jsx"],
+        grammar: Some("javascript"),
+        formatter: Some(PRETTIER),
+        test_files: JS_TESTS,
+        test_dirs: &["__tests__"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "go",
+        extensions: &["go"],
+        tab_indent: true,
+        grammar: Some("go"),
+        test_files: &["*_test.go"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "c",
+        extensions: &["c", "h"],
+        grammar: Some("c"),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "cpp",
+        extensions: &["cpp", "cc", "hpp"],
+        grammar: Some("cpp"),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "java",
+        extensions: &["java"],
+        grammar: Some("java"),
+        test_files: &["*Test.java", "*Tests.java"],
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "json",
+        extensions: &["json"],
+        line_comment: None,
+        block_comment: None,
+        grammar: Some("json"),
+        formatter: Some(PRETTIER),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "toml",
+        extensions: &["toml"],
+        line_comment: Some("#"),
+        block_comment: None,
+        grammar: Some("toml"),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "yaml",
+        extensions: &["yaml", "yml"],
+        line_comment: Some("#"),
+        block_comment: None,
+        indent_width: Some(2),
+        grammar: Some("yaml"),
+        formatter: Some(PRETTIER),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "markdown",
+        extensions: &["md"],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        grammar: Some("markdown"),
+        formatter: Some(PRETTIER),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "html",
+        extensions: &["html"],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        grammar: Some("html"),
+        formatter: Some(PRETTIER),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "css",
+        extensions: &["css"],
+        line_comment: None,
+        grammar: Some("css"),
+        formatter: Some(PRETTIER),
+        ..C_LIKE
+    },
+    LanguagePack {
+        id: "shellscript",
+        extensions: &["sh"],
+        line_comment: Some("#"),
+        block_comment: None,
+        grammar: Some("bash"),
+        ..C_LIKE
+    },
+];
+
+impl LanguagePack {
+    pub fn for_id(id: &str) -> Option<&'static LanguagePack> {
+        LANGUAGE_PACKS.iter().find(|pack| pack.id == id)
+    }
+
+    /// 按扩展名识别文件的语言，不区分大小写
+    pub fn for_path(path: &Path) -> Option<&'static LanguagePack> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        LANGUAGE_PACKS
+            .iter()
+            .find(|pack| pack.extensions.contains(&extension.as_str()))
+    }
+
+    /// `path` 是否按这门语言的约定是测试文件
+    pub fn is_test_file(&self, path: &Path) -> bool {
+        let in_test_dir = path
+            .parent()
+            .into_iter()
+            .flat_map(|parent| parent.components())
+            .any(|component| {
+                self.test_dirs
+                    .iter()
+                    .any(|dir| component.as_os_str() == *dir)
+            });
+        let name = path.file_name().and_then(|name| name.to_str());
+        in_test_dir
+            || name.is_some_and(|name| {
+                self.test_files
+                    .iter()
+                    .any(|pattern| matches_pattern(name, pattern))
+            })
+    }
+}
+
+/// `name` 是否符合 `pattern`，其中的 `*` 匹配任意字符
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_are_found_by_path_and_know_their_test_files() {
+        let rust = LanguagePack::for_path(Path::new("src/Lib.RS")).unwrap();
+        assert_eq!(rust.id, "rust");
+        assert!(rust.is_test_file(Path::new("crate/tests/props.rs")));
+        assert!(!rust.is_test_file(Path::new("crate/src/tests.rs")));
+
+        let python = LanguagePack::for_id("python").unwrap();
+        assert!(python.is_test_file(Path::new("pkg/test_io.py")));
+        assert!(python.is_test_file(Path::new("pkg/io_test.py")));
+        assert!(!python.is_test_file(Path::new("pkg/testing.py")));
+
+        let typescript = LanguagePack::for_path(Path::new("app.ts")).unwrap();
+        assert!(typescript.is_test_file(Path::new("app.spec.ts")));
+        assert!(!typescript.is_test_file(Path::new("spec.ts")));
+        assert!(LanguagePack::for_path(Path::new("README")).is_none());
+
+        for (index, pack) in LANGUAGE_PACKS.iter().enumerate() {
+            assert!(!pack.id.is_empty());
+            assert!(LANGUAGE_PACKS[..index]
+                .iter()
+                .all(|other| other.id != pack.id));
+        }
+    }
+}
@@ -1,8 +1,23 @@
 pub mod config;
+pub mod deep_link;
+pub mod ipc;
+pub mod language;
 pub mod logging;
+pub mod paths;
+pub mod scheduler;
+pub mod session;
+pub mod settings_archive;
+pub mod startup;
 pub mod task_executor;
 pub mod telemetry;
 
 pub use config::Config;
+pub use deep_link::DeepLink;
+pub use language::{LanguagePack, LANGUAGE_PACKS};
 pub use logging::init_logging;
+pub use scheduler::{WorkflowScheduler, WorkflowStatus};
+pub use session::{Session, SessionSelection, SessionTab};
+pub use settings_archive::{ConflictResolution, SettingsArchive};
+pub use startup::StartupProfile;
 pub use task_executor::TaskExecutor;
+pub use telemetry::{Metric, MetricSummary, Metrics};
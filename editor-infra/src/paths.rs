@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+const APP_DIR: &str = "fusang";
+
+/// 本地数据目录（历史快照、会话等），找不到系统目录时退回临时目录
+pub fn data_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR)
+}
+
+/// 用户配置目录
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR)
+}
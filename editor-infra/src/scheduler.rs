@@ -0,0 +1,216 @@
+use crate::config::{Config, WorkflowConfig, WorkflowTrigger};
+use crate::task_executor::TaskExecutor;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+const TICK: Duration = Duration::from_secs(1);
+
+/// 定时 workflow 的运行状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowStatus {
+    pub name: String,
+    pub interval: Duration,
+    pub last_run: Option<SystemTime>,
+    pub next_run: SystemTime,
+    pub running: bool,
+    pub run_count: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug)]
+struct ScheduledWorkflow {
+    workflow: WorkflowConfig,
+    status: WorkflowStatus,
+}
+
+/// 按 `WorkflowTrigger::Timer` 的间隔触发已启用的 workflow。
+///
+/// A workflow that is still running when it comes due again is skipped for that tick
+/// rather than started twice.
+#[derive(Debug, Clone, Default)]
+pub struct WorkflowScheduler {
+    entries: Arc<Mutex<BTreeMap<String, ScheduledWorkflow>>>,
+}
+
+impl WorkflowScheduler {
+    pub fn new(config: &Config) -> Self {
+        Self::from_workflows(config.get_enabled_workflows(), SystemTime::now())
+    }
+
+    pub fn from_workflows<'a>(
+        workflows: impl IntoIterator<Item = &'a WorkflowConfig>,
+        now: SystemTime,
+    ) -> Self {
+        let mut entries = BTreeMap::new();
+        for workflow in workflows {
+            // 多个 Timer 触发器时取最短的间隔
+            let interval = workflow
+                .triggers
+                .iter()
+                .filter_map(|trigger| match trigger {
+                    WorkflowTrigger::Timer { interval_seconds } => {
+                        Some(Duration::from_secs((*interval_seconds).max(1)))
+                    }
+                    _ => None,
+                })
+                .min();
+            let Some(interval) = interval else {
+                continue;
+            };
+
+            entries.insert(
+                workflow.name.clone(),
+                ScheduledWorkflow {
+                    workflow: workflow.clone(),
+                    status: WorkflowStatus {
+                        name: workflow.name.clone(),
+                        interval,
+                        last_run: None,
+                        next_run: now + interval,
+                        running: false,
+                        run_count: 0,
+                        last_error: None,
+                    },
+                },
+            );
+        }
+
+        Self {
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<WorkflowStatus> {
+        let entries = self.entries.lock().unwrap();
+        entries.values().map(|entry| entry.status.clone()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+
+    /// 让 workflow 在下一个 tick 立即运行
+    pub fn run_now(&self, name: &str, now: SystemTime) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get_mut(name) {
+            Some(entry) => {
+                entry.status.next_run = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 取出到期的 workflow，标记为运行中并安排下一次运行时间
+    pub fn take_due(&self, now: SystemTime) -> Vec<WorkflowConfig> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut due = Vec::new();
+
+        for entry in entries.values_mut() {
+            if entry.status.running || entry.status.next_run > now {
+                continue;
+            }
+            entry.status.running = true;
+            entry.status.last_run = Some(now);
+            entry.status.next_run = now + entry.status.interval;
+            due.push(entry.workflow.clone());
+        }
+
+        due
+    }
+
+    pub fn complete(&self, name: &str, result: Result<(), String>) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(name) {
+            entry.status.running = false;
+            entry.status.run_count += 1;
+            entry.status.last_error = result.err();
+        }
+    }
+
+    /// 在 executor 上启动调度循环；对返回的 handle 调用 `abort` 停止调度
+    pub fn start<F, Fut>(&self, executor: &TaskExecutor, runner: F) -> JoinHandle<()>
+    where
+        F: Fn(WorkflowConfig) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let scheduler = self.clone();
+        let runner = Arc::new(runner);
+        let spawner = executor.clone();
+
+        executor.spawn(async move {
+            let mut ticker = tokio::time::interval(TICK);
+            loop {
+                ticker.tick().await;
+                for workflow in scheduler.take_due(SystemTime::now()) {
+                    let scheduler = scheduler.clone();
+                    let runner = runner.clone();
+                    spawner.spawn(async move {
+                        let name = workflow.name.clone();
+                        let result = runner(workflow).await;
+                        if let Err(e) = &result {
+                            tracing::warn!("Scheduled workflow {} failed: {}", name, e);
+                        }
+                        scheduler.complete(&name, result);
+                    });
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WorkflowConfig;
+
+    fn workflow(name: &str, triggers: Vec<WorkflowTrigger>) -> WorkflowConfig {
+        WorkflowConfig {
+            name: name.to_string(),
+            description: String::new(),
+            steps: Vec::new(),
+            triggers,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn fires_timer_workflows_once_per_interval() {
+        let start = SystemTime::UNIX_EPOCH;
+        let workflows = [
+            workflow(
+                "lint",
+                vec![WorkflowTrigger::Timer {
+                    interval_seconds: 60,
+                }],
+            ),
+            workflow("manual", vec![WorkflowTrigger::Manual]),
+        ];
+        let scheduler = WorkflowScheduler::from_workflows(&workflows, start);
+        assert_eq!(scheduler.statuses().len(), 1);
+
+        assert!(scheduler
+            .take_due(start + Duration::from_secs(30))
+            .is_empty());
+        let due = scheduler.take_due(start + Duration::from_secs(60));
+        assert_eq!(due.len(), 1);
+
+        // Still running: not started again even when overdue.
+        assert!(scheduler
+            .take_due(start + Duration::from_secs(200))
+            .is_empty());
+
+        scheduler.complete("lint", Err("boom".to_string()));
+        let status = &scheduler.statuses()[0];
+        assert_eq!(status.run_count, 1);
+        assert_eq!(status.last_error.as_deref(), Some("boom"));
+        assert_eq!(status.next_run, start + Duration::from_secs(120));
+        assert_eq!(
+            scheduler.take_due(start + Duration::from_secs(200)).len(),
+            1
+        );
+    }
+}
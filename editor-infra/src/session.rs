@@ -0,0 +1,154 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 跨启动保留的编辑器状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// 固定的标签页，按显示顺序
+    #[serde(default)]
+    pub pinned_tabs: Vec<PathBuf>,
+    /// 当前标签页
+    #[serde(default)]
+    pub current: Option<PathBuf>,
+    /// 打开的标签页，按显示顺序；定期写入，崩溃后据此恢复
+    #[serde(default)]
+    pub open_tabs: Vec<SessionTab>,
+    /// 这个工作区开着性能模式
+    #[serde(default)]
+    pub performance_mode: bool,
+}
+
+/// 一个打开的标签页
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTab {
+    /// 未命名缓冲区的路径只是占位，恢复时重新分配
+    pub path: PathBuf,
+    #[serde(default)]
+    pub untitled: bool,
+    #[serde(default)]
+    pub selections: Vec<SessionSelection>,
+    /// 编辑区顶部的行号
+    #[serde(default)]
+    pub scroll_line: usize,
+    /// 未保存的内容，没有修改时为空
+    #[serde(default)]
+    pub draft: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// 选区的两端，行列从 0 开始
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionSelection {
+    pub anchor_line: usize,
+    pub anchor_column: usize,
+    pub active_line: usize,
+    pub active_column: usize,
+}
+
+impl Session {
+    /// 早期版本所有工作区共用的会话文件
+    pub fn default_path() -> PathBuf {
+        paths::data_dir().join("session.toml")
+    }
+
+    /// 工作区 `root` 的会话文件，按路径的稳定哈希命名
+    pub fn workspace_path(root: &Path) -> PathBuf {
+        let hash = root
+            .to_string_lossy()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        paths::data_dir()
+            .join("sessions")
+            .join(format!("{:016x}.toml", hash))
+    }
+
+    /// 读取工作区的会话；工作区还没有会话时沿用早期版本的共用会话
+    pub fn load_workspace(root: &Path) -> anyhow::Result<Self> {
+        let path = Self::workspace_path(root);
+        if path.exists() {
+            Self::load(&path)
+        } else {
+            Self::load(&Self::default_path())
+        }
+    }
+
+    /// 文件不存在时返回空会话
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(toml::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 先写临时文件再改名，写到一半崩溃也不会损坏上一次的会话
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = path.with_extension("toml.tmp");
+        std::fs::write(&temp, toml::to_string_pretty(self)?)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_defaults_when_missing() {
+        let dir = std::env::temp_dir().join(format!("fusang-session-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("session.toml");
+        assert_eq!(Session::load(&path).unwrap(), Session::default());
+
+        let session = Session {
+            performance_mode: true,
+            pinned_tabs: vec![PathBuf::from("/work/src/main.rs")],
+            current: Some(PathBuf::from("untitled-1")),
+            open_tabs: vec![
+                SessionTab {
+                    path: PathBuf::from("/work/src/main.rs"),
+                    selections: vec![SessionSelection {
+                        anchor_line: 3,
+                        anchor_column: 1,
+                        active_line: 4,
+                        active_column: 0,
+                    }],
+                    scroll_line: 2,
+                    ..Default::default()
+                },
+                SessionTab {
+                    path: PathBuf::from("untitled-1"),
+                    untitled: true,
+                    draft: Some("草稿\n\"quoted\"\n".to_string()),
+                    language: Some("markdown".to_string()),
+                    ..Default::default()
+                },
+            ],
+        };
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session);
+        assert!(!path.with_extension("toml.tmp").exists());
+
+        // 旧版本只写了固定标签页
+        std::fs::write(&path, "pinned_tabs = [\"/work/a.rs\"]\n").unwrap();
+        let old = Session::load(&path).unwrap();
+        assert_eq!(old.pinned_tabs, [PathBuf::from("/work/a.rs")]);
+        assert!(old.open_tabs.is_empty() && old.current.is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let a = Session::workspace_path(Path::new("/work/a"));
+        assert_eq!(a, Session::workspace_path(Path::new("/work/a")));
+        assert_ne!(a, Session::workspace_path(Path::new("/work/b")));
+        assert_eq!(a.extension().unwrap(), "toml");
+    }
+}
@@ -0,0 +1,250 @@
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+const FORMAT_VERSION: u32 = 1;
+
+/// 随配置一起导出的子目录（快捷键、代码片段、主题、提示词模板）
+pub const SETTINGS_DIRS: &[&str] = &["keymaps", "snippets", "themes", "prompts"];
+
+/// 配置节名称，用作冲突项的 key
+pub const CONFIG_SECTIONS: &[&str] = &["editor", "ai", "lsp", "ui"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    KeepExisting,
+    UseImported,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub imported: Vec<String>,
+    pub kept: Vec<String>,
+}
+
+/// 单文件的配置归档，可在另一台机器上导入。
+///
+/// API keys are stripped on export; on import, existing keys are kept for providers
+/// whose imported entry has none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsArchive {
+    pub format_version: u32,
+    pub config: Config,
+    /// 相对于配置目录的路径（使用 `/` 分隔）到文件内容
+    pub files: BTreeMap<String, String>,
+}
+
+impl SettingsArchive {
+    pub fn export(config: &Config, config_dir: &Path) -> anyhow::Result<Self> {
+        let mut config = config.clone();
+        for provider in config.ai.providers.values_mut() {
+            provider.api_key = None;
+        }
+
+        let mut files = BTreeMap::new();
+        for dir in SETTINGS_DIRS {
+            collect_files(&config_dir.join(dir), config_dir, &mut files)?;
+        }
+
+        Ok(Self {
+            format_version: FORMAT_VERSION,
+            config,
+            files,
+        })
+    }
+
+    pub fn load_from_file(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let archive: SettingsArchive = toml::from_str(&content)?;
+        if archive.format_version > FORMAT_VERSION {
+            anyhow::bail!(
+                "Settings archive format {} is newer than supported ({})",
+                archive.format_version,
+                FORMAT_VERSION
+            );
+        }
+        for name in archive.files.keys() {
+            validate_relative(name)?;
+        }
+        Ok(archive)
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// 导入时会覆盖本地不同内容的项：配置节为 `settings.<section>`，文件为相对路径
+    pub fn conflicts(&self, config: &Config, config_dir: &Path) -> anyhow::Result<Vec<String>> {
+        let mut conflicts = Vec::new();
+
+        // 归档中不含 API key，比较前同样去掉本地的
+        let mut current = config.clone();
+        for provider in current.ai.providers.values_mut() {
+            provider.api_key = None;
+        }
+        let current = toml::Value::try_from(&current)?;
+        let imported = toml::Value::try_from(&self.config)?;
+        for section in CONFIG_SECTIONS {
+            if current.get(section) != imported.get(section) {
+                conflicts.push(format!("settings.{}", section));
+            }
+        }
+
+        for (name, content) in &self.files {
+            let path = config_dir.join(name);
+            if let Ok(existing) = std::fs::read_to_string(&path) {
+                if &existing != content {
+                    conflicts.push(name.clone());
+                }
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    /// 应用归档；未在 `resolutions` 中给出的冲突项保留本地版本
+    pub fn apply(
+        &self,
+        config: &mut Config,
+        config_dir: &Path,
+        resolutions: &HashMap<String, ConflictResolution>,
+    ) -> anyhow::Result<ImportSummary> {
+        let conflicts = self.conflicts(config, config_dir)?;
+        let use_imported = |key: &str| {
+            !conflicts.iter().any(|c| c == key)
+                || resolutions.get(key) == Some(&ConflictResolution::UseImported)
+        };
+        let mut summary = ImportSummary::default();
+
+        for section in CONFIG_SECTIONS {
+            let key = format!("settings.{}", section);
+            if !conflicts.contains(&key) {
+                continue;
+            }
+            if !use_imported(&key) {
+                summary.kept.push(key);
+                continue;
+            }
+            match *section {
+                "editor" => config.editor = self.config.editor.clone(),
+                "ai" => {
+                    let previous = std::mem::replace(&mut config.ai, self.config.ai.clone());
+                    for (name, provider) in config.ai.providers.iter_mut() {
+                        if provider.api_key.is_none() {
+                            provider.api_key =
+                                previous.providers.get(name).and_then(|p| p.api_key.clone());
+                        }
+                    }
+                }
+                "lsp" => config.lsp = self.config.lsp.clone(),
+                "ui" => config.ui = self.config.ui.clone(),
+                _ => {}
+            }
+            summary.imported.push(key);
+        }
+
+        for (name, content) in &self.files {
+            if !use_imported(name) {
+                summary.kept.push(name.clone());
+                continue;
+            }
+            let path = config_dir.join(validate_relative(name)?);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)?;
+            summary.imported.push(name.clone());
+        }
+
+        Ok(summary)
+    }
+}
+
+fn collect_files(
+    dir: &Path,
+    config_dir: &Path,
+    files: &mut BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, config_dir, files)?;
+        } else if let Ok(content) = std::fs::read_to_string(&path) {
+            let relative = path.strip_prefix(config_dir)?;
+            let name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.insert(name, content);
+        }
+    }
+
+    Ok(())
+}
+
+/// 拒绝绝对路径和 `..`，避免归档写到配置目录之外
+fn validate_relative(name: &str) -> anyhow::Result<PathBuf> {
+    let path = PathBuf::from(name);
+    let safe = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !safe || name.is_empty() {
+        anyhow::bail!("Invalid path in settings archive: {}", name);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_then_import_reports_conflicts_and_keeps_local_by_default() {
+        let root = std::env::temp_dir().join(format!("fusang-settings-{}", uuid::Uuid::new_v4()));
+        let source_dir = root.join("source");
+        let target_dir = root.join("target");
+        std::fs::create_dir_all(source_dir.join("snippets")).unwrap();
+        std::fs::create_dir_all(target_dir.join("snippets")).unwrap();
+        std::fs::write(source_dir.join("snippets/rust.json"), "imported").unwrap();
+        std::fs::write(target_dir.join("snippets/rust.json"), "local").unwrap();
+
+        let mut exported_config = Config::default();
+        exported_config.editor.tab_size = 2;
+        let archive_path = root.join("settings.toml");
+        SettingsArchive::export(&exported_config, &source_dir)
+            .unwrap()
+            .save_to_file(&archive_path)
+            .unwrap();
+
+        let archive = SettingsArchive::load_from_file(&archive_path).unwrap();
+        let mut config = Config::default();
+        let conflicts = archive.conflicts(&config, &target_dir).unwrap();
+        assert_eq!(conflicts, vec!["settings.editor", "snippets/rust.json"]);
+
+        let resolutions = HashMap::from([(
+            "settings.editor".to_string(),
+            ConflictResolution::UseImported,
+        )]);
+        let summary = archive
+            .apply(&mut config, &target_dir, &resolutions)
+            .unwrap();
+        assert_eq!(summary.kept, vec!["snippets/rust.json"]);
+        assert_eq!(config.editor.tab_size, 2);
+        assert_eq!(
+            std::fs::read_to_string(target_dir.join("snippets/rust.json")).unwrap(),
+            "local"
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Target time from process start to the first frame.
+pub const FIRST_FRAME_BUDGET: Duration = Duration::from_millis(200);
+
+static PROFILE: OnceLock<StartupProfile> = OnceLock::new();
+
+/// The startup profile of this process, started the first time it is asked for.
+pub fn profile() -> &'static StartupProfile {
+    PROFILE.get_or_init(StartupProfile::new)
+}
+
+/// How long each startup phase took, up to the first frame.
+///
+/// Phases are also `tracing` spans named `startup`, so a subscriber sees them nested
+/// in whatever else it records. With `--profile-startup` the breakdown is printed to
+/// stderr once the first frame is drawn.
+#[derive(Debug)]
+pub struct StartupProfile {
+    started: Instant,
+    phases: Mutex<Vec<(&'static str, Duration)>>,
+    first_frame: OnceLock<Duration>,
+    print: AtomicBool,
+}
+
+impl StartupProfile {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            phases: Mutex::new(Vec::new()),
+            first_frame: OnceLock::new(),
+            print: AtomicBool::new(false),
+        }
+    }
+
+    /// Print the breakdown when the first frame is drawn.
+    pub fn print_on_first_frame(&self, enabled: bool) {
+        self.print.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Runs `f` as the phase `name`.
+    pub fn phase<T>(&self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let span = tracing::info_span!("startup", phase = name);
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = f();
+        self.lock_phases().push((name, started.elapsed()));
+        result
+    }
+
+    /// Records the first frame. Only the first call counts; it returns the time since
+    /// startup and prints the breakdown if asked to.
+    pub fn first_frame(&self) -> Option<Duration> {
+        let elapsed = self.started.elapsed();
+        self.first_frame.set(elapsed).ok()?;
+        tracing::info!("First frame after {:.1} ms", elapsed.as_secs_f64() * 1000.0);
+        if self.print.load(Ordering::Relaxed) {
+            eprintln!("{}", self.report());
+        }
+        Some(elapsed)
+    }
+
+    pub fn phases(&self) -> Vec<(&'static str, Duration)> {
+        self.lock_phases().clone()
+    }
+
+    /// The phases in the order they ran, then the time to the first frame.
+    pub fn report(&self) -> String {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let phases = self.phases();
+        let width = phases
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(["first frame".len()])
+            .max()
+            .unwrap_or_default();
+
+        let mut report = String::from("Startup profile:\n");
+        for (name, duration) in &phases {
+            report.push_str(&format!("  {:<width$}  {:>7.1} ms\n", name, ms(*duration)));
+        }
+        match self.first_frame.get() {
+            Some(first_frame) => report.push_str(&format!(
+                "  {:<width$}  {:>7.1} ms (budget {} ms{})",
+                "first frame",
+                ms(*first_frame),
+                FIRST_FRAME_BUDGET.as_millis(),
+                if *first_frame > FIRST_FRAME_BUDGET {
+                    ", over budget"
+                } else {
+                    ""
+                }
+            )),
+            None => report.push_str("  no frame drawn yet"),
+        }
+        report
+    }
+
+    fn lock_phases(&self) -> std::sync::MutexGuard<'_, Vec<(&'static str, Duration)>> {
+        self.phases.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for StartupProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_phases_and_only_the_first_frame() {
+        let profile = StartupProfile::new();
+        assert_eq!(profile.phase("config", || 42), 42);
+        profile.phase("window", || {});
+        assert!(profile.report().ends_with("no frame drawn yet"));
+
+        assert!(profile.first_frame().is_some());
+        assert!(profile.first_frame().is_none());
+        let names: Vec<_> = profile.phases().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["config", "window"]);
+        let report = profile.report();
+        assert!(report.contains("  config  "));
+        assert!(report.contains("first frame"));
+    }
+}
@@ -13,7 +13,24 @@ pub struct TaskExecutor {
 
 impl TaskExecutor {
     pub fn new() -> Self {
-        let runtime = Arc::new(Runtime::new().expect("Failed to create Tokio runtime"));
+        Self::with_runtime(Runtime::new().expect("Failed to create Tokio runtime"))
+    }
+
+    /// 给一个子系统（如语言服务器、AI）单独开的后台服务：任务在名为 `fusang-{name}`
+    /// 的独立线程池上运行，结果经 [`JoinHandle`] 传回。子系统卡死或占满线程时，
+    /// 界面和其他子系统照常运行
+    pub fn service(name: &str, worker_threads: usize) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(worker_threads.max(1))
+            .thread_name(format!("fusang-{}", name))
+            .enable_all()
+            .build()
+            .expect("Failed to create Tokio runtime");
+        Self::with_runtime(runtime)
+    }
+
+    fn with_runtime(runtime: Runtime) -> Self {
+        let runtime = Arc::new(runtime);
 
         let (sender, mut receiver) = mpsc::unbounded_channel::<Box<dyn FnOnce() + Send>>();
 
@@ -64,3 +81,28 @@ impl Clone for TaskExecutor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_stuck_service_does_not_block_other_executors() {
+        let lsp = TaskExecutor::service("lsp", 1);
+        // 占住服务唯一的工作线程
+        let (release, stuck) = std::sync::mpsc::channel::<()>();
+        lsp.spawn(async move {
+            let _ = stuck.recv();
+        });
+
+        let shared = TaskExecutor::new();
+        let answer = shared.runtime.block_on(shared.spawn(async { 42 }));
+        assert_eq!(answer.unwrap(), 42);
+
+        release.send(()).unwrap();
+        let name = shared
+            .runtime
+            .block_on(lsp.spawn(async { std::thread::current().name().map(str::to_string) }));
+        assert_eq!(name.unwrap().as_deref(), Some("fusang-lsp"));
+    }
+}
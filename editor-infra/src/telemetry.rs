@@ -1,4 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Samples kept per metric; older ones are dropped.
+const MAX_SAMPLES: usize = 1000;
 
 #[derive(Debug, Clone)]
 pub struct TelemetryEvent {
@@ -10,6 +15,7 @@ pub struct TelemetryEvent {
 pub struct Telemetry {
     enabled: bool,
     session_id: String,
+    metrics: Metrics,
 }
 
 impl Telemetry {
@@ -17,9 +23,16 @@ impl Telemetry {
         Self {
             enabled,
             session_id: uuid::Uuid::new_v4().to_string(),
+            metrics: Metrics::default(),
         }
     }
 
+    /// Local performance metrics. They are recorded even when telemetry is disabled,
+    /// since they never leave the machine.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
     pub fn track_event(&self, event: TelemetryEvent) {
         if !self.enabled {
             return;
@@ -40,6 +53,8 @@ impl Telemetry {
             metrics: HashMap::from([("duration_ms".to_string(), duration_ms)]),
         };
         self.track_event(event);
+        self.metrics
+            .record_ms(Metric::EditorAction(action.to_string()), duration_ms);
     }
 
     pub fn track_ai_completion(&self, model: &str, tokens_used: usize) {
@@ -57,3 +72,155 @@ impl Default for Telemetry {
         Self::new(true)
     }
 }
+
+/// What a duration was measured for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Metric {
+    /// From a key press to the next frame.
+    KeystrokeToRender,
+    /// A language server request, by method.
+    LspRequest(String),
+    /// A chat completion, by model.
+    AiRequest(String),
+    EditorAction(String),
+}
+
+impl Metric {
+    pub fn label(&self) -> String {
+        match self {
+            Metric::KeystrokeToRender => "keystroke → render".to_string(),
+            Metric::LspRequest(method) => format!("lsp {}", method),
+            Metric::AiRequest(model) => format!("ai {}", model),
+            Metric::EditorAction(action) => action.clone(),
+        }
+    }
+}
+
+/// Percentiles of the recorded samples of one metric, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetricSummary {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl MetricSummary {
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        // Nearest rank.
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        Some(Self {
+            count: sorted.len(),
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+/// Recent durations per metric, kept in memory for the diagnostics view. Clones share
+/// the same samples.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    samples: Arc<Mutex<HashMap<Metric, VecDeque<f64>>>>,
+}
+
+impl Metrics {
+    pub fn record(&self, metric: Metric, duration: Duration) {
+        self.record_ms(metric, duration.as_secs_f64() * 1000.0);
+    }
+
+    pub fn record_ms(&self, metric: Metric, duration_ms: f64) {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let samples = samples.entry(metric).or_default();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(duration_ms);
+    }
+
+    /// Records how long `future` takes.
+    pub async fn measure<T>(
+        &self,
+        metric: Metric,
+        future: impl std::future::Future<Output = T>,
+    ) -> T {
+        let started = Instant::now();
+        let output = future.await;
+        self.record(metric, started.elapsed());
+        output
+    }
+
+    /// Every metric with samples, sorted.
+    pub fn metrics(&self) -> Vec<Metric> {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let mut metrics: Vec<Metric> = samples.keys().cloned().collect();
+        metrics.sort();
+        metrics
+    }
+
+    /// Samples in milliseconds, oldest first.
+    pub fn samples(&self, metric: &Metric) -> Vec<f64> {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples
+            .get(metric)
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn summary(&self, metric: &Metric) -> Option<MetricSummary> {
+        MetricSummary::from_samples(&self.samples(metric))
+    }
+
+    pub fn clear(&self) {
+        self.samples
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_recent_samples_with_percentiles() {
+        let metrics = Metrics::default();
+        for ms in 1..=100 {
+            metrics.record_ms(Metric::KeystrokeToRender, ms as f64);
+        }
+        metrics.record(
+            Metric::LspRequest("textDocument/hover".to_string()),
+            Duration::from_millis(40),
+        );
+
+        let summary = metrics.summary(&Metric::KeystrokeToRender).unwrap();
+        assert_eq!(summary.count, 100);
+        assert_eq!((summary.p50, summary.p90, summary.p99), (50.0, 90.0, 99.0));
+        assert_eq!(summary.max, 100.0);
+        assert_eq!(summary.mean, 50.5);
+        assert_eq!(metrics.metrics()[0], Metric::KeystrokeToRender);
+
+        for _ in 0..MAX_SAMPLES {
+            metrics.record_ms(Metric::KeystrokeToRender, 1.0);
+        }
+        assert_eq!(
+            metrics.summary(&Metric::KeystrokeToRender).unwrap().max,
+            1.0
+        );
+        assert!(MetricSummary::from_samples(&[]).is_none());
+    }
+}
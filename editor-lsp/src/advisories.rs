@@ -0,0 +1,676 @@
+use crate::cargo::{self, VersionRequirement};
+use crate::protocol::{CodeAction, Diagnostic, DiagnosticSeverity, Range, TextEdit, WorkspaceEdit};
+use editor_core_text::{Cursor, DocumentTree, NodeKind, PathSegment};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+
+const OSV_QUERY_API: &str = "https://api.osv.dev/v1/query";
+const USER_AGENT: &str = concat!("fusang/", env!("CARGO_PKG_VERSION"));
+const NPM_DEPENDENCY_TABLES: &[&str] = &[
+    "dependencies",
+    "devDependencies",
+    "optionalDependencies",
+    "peerDependencies",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ecosystem {
+    CratesIo,
+    Npm,
+}
+
+impl Ecosystem {
+    /// The ecosystem of the manifest at `path`, if it is a Cargo or npm manifest.
+    pub fn of_manifest(path: &Path) -> Option<Self> {
+        match path.file_name()?.to_str()? {
+            "Cargo.toml" => Some(Self::CratesIo),
+            "package.json" => Some(Self::Npm),
+            _ => None,
+        }
+    }
+
+    /// The name osv.dev knows the ecosystem by.
+    fn osv_name(self) -> &'static str {
+        match self {
+            Self::CratesIo => "crates.io",
+            Self::Npm => "npm",
+        }
+    }
+
+    fn registry_url(self, name: &str, version: &str) -> String {
+        match self {
+            Self::CratesIo => format!("https://crates.io/api/v1/crates/{}/{}", name, version),
+            Self::Npm => format!("https://registry.npmjs.org/{}/{}", name, version),
+        }
+    }
+}
+
+/// A dependency of a manifest and the version it is locked to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub ecosystem: Ecosystem,
+    pub name: String,
+    pub version: String,
+    /// The line of the manifest the dependency is declared on.
+    pub line: usize,
+    pub requirement: VersionRequirement,
+}
+
+/// A published vulnerability affecting a package version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advisory {
+    /// The RUSTSEC or GHSA id, or the osv.dev one.
+    pub id: String,
+    pub summary: String,
+    /// The lowest version past the affected one with the fix.
+    pub fixed: Option<String>,
+}
+
+impl Advisory {
+    pub fn url(&self) -> String {
+        format!("https://osv.dev/vulnerability/{}", self.id)
+    }
+}
+
+/// What the registries and osv.dev say about a locked package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageReport {
+    pub package: LockedPackage,
+    pub advisories: Vec<Advisory>,
+    /// Why the version was yanked from crates.io or deprecated on npm.
+    pub withdrawn: Option<String>,
+    pub license: Option<String>,
+}
+
+impl PackageReport {
+    /// The lowest version fixing every advisory.
+    pub fn fix_version(&self) -> Option<&str> {
+        self.advisories
+            .iter()
+            .filter_map(|advisory| advisory.fixed.as_deref())
+            .max_by(|a, b| compare_versions(a, b))
+    }
+
+    pub fn diagnostics(&self, rope: &Rope) -> Vec<Diagnostic> {
+        let line = self.package.line;
+        let text = cargo::line_text(rope, line);
+        let start = text.chars().take_while(|ch| ch.is_whitespace()).count();
+        let end = text.trim_end().chars().count().max(start);
+        let range = Range::from_cursors(rope, Cursor::new(line, start), Cursor::new(line, end));
+        let package = format!("{} {}", self.package.name, self.package.version);
+
+        let mut diagnostics = Vec::new();
+        for advisory in &self.advisories {
+            let fix = match &advisory.fixed {
+                Some(fixed) => format!(" Fixed in {}.", fixed),
+                None => " No fixed version yet.".to_string(),
+            };
+            diagnostics.push(Diagnostic {
+                range: range.clone(),
+                severity: Some(DiagnosticSeverity::Error),
+                code: Some(Value::String(advisory.id.clone())),
+                source: Some("osv.dev".to_string()),
+                message: format!(
+                    "{} is affected by {}: {}.{}\n{}",
+                    package,
+                    advisory.id,
+                    advisory.summary.trim_end_matches('.'),
+                    fix,
+                    advisory.url()
+                ),
+            });
+        }
+        if let Some(reason) = &self.withdrawn {
+            let (source, what) = match self.package.ecosystem {
+                Ecosystem::CratesIo => ("crates.io", "yanked"),
+                Ecosystem::Npm => ("npm", "deprecated"),
+            };
+            let reason = if reason.is_empty() {
+                String::new()
+            } else {
+                format!(": {}", reason)
+            };
+            diagnostics.push(Diagnostic {
+                range,
+                severity: Some(DiagnosticSeverity::Warning),
+                code: Some(Value::String(what.to_string())),
+                source: Some(source.to_string()),
+                message: format!("{} is {}{}", package, what, reason),
+            });
+        }
+        diagnostics
+    }
+
+    /// Raises the requirement to the version with every fix, so the lock file has to
+    /// move past the affected version too.
+    pub fn fix_action(&self, rope: &Rope, uri: &str) -> Option<CodeAction> {
+        let fixed = self.fix_version()?;
+        let requirement = &self.package.requirement;
+        let ids: Vec<&str> = self
+            .advisories
+            .iter()
+            .map(|advisory| advisory.id.as_str())
+            .collect();
+        Some(CodeAction {
+            title: format!(
+                "Update {} to {} (fixes {})",
+                self.package.name,
+                fixed,
+                ids.join(", ")
+            ),
+            kind: Some("quickfix".to_string()),
+            edit: Some(WorkspaceEdit {
+                changes: vec![(
+                    uri.to_string(),
+                    vec![TextEdit {
+                        range: Range::from_cursors(rope, requirement.start, requirement.end),
+                        new_text: cargo::updated_requirement(&requirement.text, fixed),
+                    }],
+                )],
+            }),
+            command: None,
+        })
+    }
+}
+
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    match (semver::Version::parse(a), semver::Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// The registry dependencies of the manifest at `path` with their locked versions,
+/// from `Cargo.lock` or `package-lock.json` in its directory or above. Without a lock
+/// file only exact requirements are known.
+pub fn locked_packages(path: &Path, rope: &Rope) -> Vec<LockedPackage> {
+    let Some(ecosystem) = Ecosystem::of_manifest(path) else {
+        return Vec::new();
+    };
+    let (declared, locked) = match ecosystem {
+        Ecosystem::CratesIo => {
+            let declared = cargo::dependencies(rope)
+                .into_iter()
+                .filter_map(|dependency| {
+                    Some((dependency.name, dependency.line, dependency.requirement?))
+                })
+                .collect();
+            let locked = find_upwards(path, "Cargo.lock")
+                .map(|text| cargo_lock_versions(&text))
+                .unwrap_or_default();
+            (declared, locked)
+        }
+        Ecosystem::Npm => {
+            let declared = npm_dependencies(rope);
+            let locked = find_upwards(path, "package-lock.json")
+                .map(|text| npm_lock_versions(&text))
+                .unwrap_or_default();
+            (declared, locked)
+        }
+    };
+
+    let mut packages = Vec::new();
+    for (name, line, requirement) in declared {
+        let candidates = locked.get(&name).map(Vec::as_slice).unwrap_or_default();
+        let version = candidates
+            .iter()
+            .filter(|version| cargo::allows(&requirement.text, version))
+            .max_by(|a, b| compare_versions(a, b))
+            .or(match candidates {
+                [only] => Some(only),
+                _ => None,
+            })
+            .cloned()
+            .or_else(|| exact_version(ecosystem, &requirement.text));
+        if let Some(version) = version {
+            packages.push(LockedPackage {
+                ecosystem,
+                name,
+                version,
+                line,
+                requirement,
+            });
+        }
+    }
+    packages
+}
+
+/// The version a requirement pins, e.g. `=1.2.3` for Cargo or `1.2.3` for npm.
+fn exact_version(ecosystem: Ecosystem, requirement: &str) -> Option<String> {
+    let version = match ecosystem {
+        Ecosystem::CratesIo => requirement.trim().strip_prefix('=')?,
+        Ecosystem::Npm => requirement.trim().trim_start_matches(['=', 'v']),
+    };
+    semver::Version::parse(version.trim())
+        .ok()
+        .map(|version| version.to_string())
+}
+
+/// The file `name` in the directory of `path` or the nearest one above it.
+fn find_upwards(path: &Path, name: &str) -> Option<String> {
+    let mut dir: Option<PathBuf> = path.parent().map(Path::to_path_buf);
+    while let Some(current) = dir {
+        if let Ok(text) = std::fs::read_to_string(current.join(name)) {
+            return Some(text);
+        }
+        dir = current.parent().map(Path::to_path_buf);
+    }
+    None
+}
+
+/// The versions of each package in a `Cargo.lock`; a package may be locked at several.
+fn cargo_lock_versions(text: &str) -> HashMap<String, Vec<String>> {
+    let tree = DocumentTree::parse_toml(text);
+    let mut versions: HashMap<String, Vec<String>> = HashMap::new();
+    for (idx, node) in tree.nodes.iter().enumerate() {
+        if node.kind != NodeKind::Object || !matches!(node.segment, Some(PathSegment::Index(_))) {
+            continue;
+        }
+        let field = |key: &str| {
+            tree.nodes
+                .iter()
+                .find(|child| {
+                    child.parent == Some(idx)
+                        && child.segment == Some(PathSegment::Key(key.to_string()))
+                })
+                .and_then(|child| cargo::unquote(&child.value))
+        };
+        if let (Some(name), Some(version)) = (field("name"), field("version")) {
+            versions.entry(name).or_default().push(version);
+        }
+    }
+    versions
+}
+
+/// The versions of the top-level packages in a `package-lock.json`, lockfile version
+/// 2 and 3 (`packages`) or 1 (`dependencies`).
+fn npm_lock_versions(text: &str) -> HashMap<String, Vec<String>> {
+    let Ok(lock) = serde_json::from_str::<Value>(text) else {
+        return HashMap::new();
+    };
+    let mut versions: HashMap<String, Vec<String>> = HashMap::new();
+    if let Some(packages) = lock["packages"].as_object() {
+        for (path, package) in packages {
+            let Some(name) = path.strip_prefix("node_modules/") else {
+                continue;
+            };
+            if let (false, Some(version)) =
+                (name.contains("/node_modules/"), package["version"].as_str())
+            {
+                versions
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(version.to_string());
+            }
+        }
+    } else if let Some(dependencies) = lock["dependencies"].as_object() {
+        for (name, package) in dependencies {
+            if let Some(version) = package["version"].as_str() {
+                versions
+                    .entry(name.clone())
+                    .or_default()
+                    .push(version.to_string());
+            }
+        }
+    }
+    versions
+}
+
+/// The dependencies a `package.json` declares, with their line and requirement.
+fn npm_dependencies(rope: &Rope) -> Vec<(String, usize, VersionRequirement)> {
+    let Ok(tree) = DocumentTree::parse_json(&rope.to_string()) else {
+        return Vec::new();
+    };
+    let mut dependencies = Vec::new();
+    for node in &tree.nodes {
+        let (Some(PathSegment::Key(name)), Some(parent)) = (&node.segment, node.parent) else {
+            continue;
+        };
+        let table = &tree.nodes[parent];
+        let in_table = table.parent == Some(0)
+            && matches!(&table.segment, Some(PathSegment::Key(key)) if NPM_DEPENDENCY_TABLES.contains(&key.as_str()));
+        if !in_table || node.kind != NodeKind::Scalar {
+            continue;
+        }
+        let Some(text) = serde_json::from_str::<String>(&node.value).ok() else {
+            continue;
+        };
+        let line = cargo::line_text(rope, node.start.line);
+        let column = cargo::value_column(&line, node.start.column, ':');
+        dependencies.push((
+            name.clone(),
+            node.start.line,
+            cargo::requirement_at(node.start.line, column, text),
+        ));
+    }
+    dependencies
+}
+
+/// Checks locked packages against osv.dev and their registry. Answers are kept for the
+/// session, as a published version's advisories rarely change while editing.
+#[derive(Debug, Clone)]
+pub struct AdvisoryChecker {
+    client: reqwest::Client,
+    cache: Arc<RwLock<HashMap<PackageKey, Lookup>>>,
+}
+
+/// Ecosystem, name and version.
+type PackageKey = (Ecosystem, String, String);
+
+#[derive(Debug, Clone)]
+struct Lookup {
+    advisories: Vec<Advisory>,
+    withdrawn: Option<String>,
+    license: Option<String>,
+}
+
+impl AdvisoryChecker {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(Duration::from_secs(15))
+                .build()
+                .unwrap_or_default(),
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Reports for the packages with something to know. Packages whose lookup failed,
+    /// e.g. while offline, are left out and looked up again next time.
+    pub async fn check(&self, packages: Vec<LockedPackage>) -> Vec<PackageReport> {
+        let mut lookups = JoinSet::new();
+        for package in &packages {
+            let key = (
+                package.ecosystem,
+                package.name.clone(),
+                package.version.clone(),
+            );
+            if self.cache.read().await.contains_key(&key) {
+                continue;
+            }
+            let client = self.client.clone();
+            lookups.spawn(async move {
+                let lookup = Self::look_up(&client, &key.0, &key.1, &key.2).await;
+                (key, lookup)
+            });
+        }
+        while let Some(Ok((key, lookup))) = lookups.join_next().await {
+            match lookup {
+                Ok(lookup) => {
+                    self.cache.write().await.insert(key, lookup);
+                }
+                Err(e) => log::debug!("Failed to check {} {}: {}", key.1, key.2, e),
+            }
+        }
+
+        let cache = self.cache.read().await;
+        packages
+            .into_iter()
+            .filter_map(|package| {
+                let key = (
+                    package.ecosystem,
+                    package.name.clone(),
+                    package.version.clone(),
+                );
+                let lookup = cache.get(&key)?.clone();
+                Some(PackageReport {
+                    package,
+                    advisories: lookup.advisories,
+                    withdrawn: lookup.withdrawn,
+                    license: lookup.license,
+                })
+            })
+            .collect()
+    }
+
+    async fn look_up(
+        client: &reqwest::Client,
+        ecosystem: &Ecosystem,
+        name: &str,
+        version: &str,
+    ) -> Result<Lookup, std::io::Error> {
+        let query = json!({
+            "package": { "name": name, "ecosystem": ecosystem.osv_name() },
+            "version": version,
+        });
+        let osv = Self::get_json(client.post(OSV_QUERY_API).json_body(&query)).await?;
+        let registry = Self::get_json(client.get(ecosystem.registry_url(name, version))).await?;
+        Ok(Lookup {
+            advisories: parse_osv_response(&osv, name, version),
+            withdrawn: match ecosystem {
+                Ecosystem::CratesIo => registry["version"]["yanked"]
+                    .as_bool()
+                    .filter(|yanked| *yanked)
+                    .map(|_| {
+                        registry["version"]["yank_message"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string()
+                    }),
+                Ecosystem::Npm => registry["deprecated"].as_str().map(String::from),
+            },
+            license: match ecosystem {
+                Ecosystem::CratesIo => registry["version"]["license"].as_str(),
+                Ecosystem::Npm => registry["license"].as_str(),
+            }
+            .map(String::from),
+        })
+    }
+
+    async fn get_json(request: reqwest::RequestBuilder) -> Result<Value, std::io::Error> {
+        let bytes = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(std::io::Error::other)?
+            .bytes()
+            .await
+            .map_err(std::io::Error::other)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+impl Default for AdvisoryChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends `value` as a JSON body, without the `json` feature of reqwest.
+trait JsonBody {
+    fn json_body(self, value: &Value) -> Self;
+}
+
+impl JsonBody for reqwest::RequestBuilder {
+    fn json_body(self, value: &Value) -> Self {
+        self.header("Content-Type", "application/json")
+            .body(value.to_string())
+    }
+}
+
+/// The advisories in an osv.dev query response, with the first fixed version past
+/// `version` for package `name`.
+fn parse_osv_response(response: &Value, name: &str, version: &str) -> Vec<Advisory> {
+    let vulns = response["vulns"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    vulns
+        .iter()
+        .filter_map(|vuln| {
+            let osv_id = vuln["id"].as_str()?;
+            // Prefer the id people know the advisory by.
+            let ids: Vec<&str> = std::iter::once(osv_id)
+                .chain(
+                    vuln["aliases"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Value::as_str),
+                )
+                .collect();
+            let id = ["RUSTSEC-", "GHSA-"]
+                .iter()
+                .find_map(|prefix| ids.iter().find(|id| id.starts_with(prefix)))
+                .copied()
+                .unwrap_or(osv_id);
+            let summary = vuln["summary"]
+                .as_str()
+                .or_else(|| {
+                    vuln["details"]
+                        .as_str()
+                        .and_then(|details| details.lines().next())
+                })
+                .unwrap_or("security advisory");
+            let fixed = vuln["affected"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|affected| affected["package"]["name"].as_str() == Some(name))
+                .flat_map(|affected| affected["ranges"].as_array().into_iter().flatten())
+                .flat_map(|range| range["events"].as_array().into_iter().flatten())
+                .filter_map(|event| event["fixed"].as_str())
+                .filter(|fixed| compare_versions(fixed, version).is_gt())
+                .min_by(|a, b| compare_versions(a, b))
+                .map(String::from);
+            Some(Advisory {
+                id: id.to_string(),
+                summary: summary.to_string(),
+                fixed,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fusang-advisories-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("app")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn locks_dependencies_to_the_versions_in_the_lock_file() {
+        let dir = temp_dir();
+        std::fs::write(
+            dir.join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"time\"\nversion = \"0.1.45\"\n\n[[package]]\nname = \"time\"\nversion = \"0.3.36\"\n\n[[package]]\nname = \"smallvec\"\nversion = \"1.6.0\"\n",
+        )
+        .unwrap();
+        let manifest = Rope::from_str(
+            "[dependencies]\ntime = \"0.3\"\nsmallvec = { version = \"1\" }\nregex = \"=1.5.4\"\nserde = \"1\"\n",
+        );
+        let packages = locked_packages(&dir.join("app/Cargo.toml"), &manifest);
+        let found: Vec<(&str, &str, usize)> = packages
+            .iter()
+            .map(|package| {
+                (
+                    package.name.as_str(),
+                    package.version.as_str(),
+                    package.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("time", "0.3.36", 1),
+                ("smallvec", "1.6.0", 2),
+                ("regex", "1.5.4", 3)
+            ]
+        );
+
+        std::fs::write(
+            dir.join("app/package-lock.json"),
+            r#"{"lockfileVersion": 3, "packages": {"": {}, "node_modules/lodash": {"version": "4.17.20"}, "node_modules/a/node_modules/lodash": {"version": "3.0.0"}}}"#,
+        )
+        .unwrap();
+        let manifest = Rope::from_str("{\n  \"dependencies\": {\n    \"lodash\": \"^4.17.0\",\n    \"left-pad\": \"1.3.0\"\n  }\n}");
+        let packages = locked_packages(&dir.join("app/package.json"), &manifest);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            (packages[0].version.as_str(), packages[0].line),
+            ("4.17.20", 2)
+        );
+        assert_eq!(packages[0].requirement.text, "^4.17.0");
+        assert_eq!(packages[0].requirement.start, Cursor::new(2, 15));
+        assert_eq!(packages[1].version, "1.3.0");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reports_advisories_with_the_fixed_version() {
+        let response = json!({ "vulns": [{
+            "id": "GHSA-xxxx",
+            "aliases": ["CVE-2020-1", "RUSTSEC-2020-0071"],
+            "summary": "Potential segfault in the time crate.",
+            "affected": [{
+                "package": { "name": "time", "ecosystem": "crates.io" },
+                "ranges": [{ "type": "SEMVER", "events": [
+                    { "introduced": "0" }, { "fixed": "0.2.23" },
+                    { "introduced": "0.3.0" }, { "fixed": "0.3.37" }
+                ]}]
+            }]
+        }]});
+        let advisories = parse_osv_response(&response, "time", "0.3.36");
+        assert_eq!(
+            advisories,
+            [Advisory {
+                id: "RUSTSEC-2020-0071".to_string(),
+                summary: "Potential segfault in the time crate.".to_string(),
+                fixed: Some("0.3.37".to_string()),
+            }]
+        );
+
+        let rope = Rope::from_str("[dependencies]\n  time = \"0.3\"\n");
+        let report = PackageReport {
+            package: LockedPackage {
+                ecosystem: Ecosystem::CratesIo,
+                name: "time".to_string(),
+                version: "0.3.36".to_string(),
+                line: 1,
+                requirement: cargo::requirement_at(1, 9, "0.3".to_string()),
+            },
+            advisories,
+            withdrawn: Some(String::new()),
+            license: Some("MIT OR Apache-2.0".to_string()),
+        };
+        let diagnostics = report.diagnostics(&rope);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "time 0.3.36 is affected by RUSTSEC-2020-0071: Potential segfault in the time crate. Fixed in 0.3.37.\nhttps://osv.dev/vulnerability/RUSTSEC-2020-0071"
+        );
+        assert_eq!(
+            (
+                diagnostics[0].range.start.character,
+                diagnostics[0].range.end.character
+            ),
+            (2, 14)
+        );
+        assert_eq!(diagnostics[1].message, "time 0.3.36 is yanked");
+
+        // The requirement allows the fix, but the lock file still has the old version.
+        let action = report.fix_action(&rope, "file:///w/Cargo.toml").unwrap();
+        assert_eq!(
+            action.title,
+            "Update time to 0.3.37 (fixes RUSTSEC-2020-0071)"
+        );
+        let edit = &action.edit.unwrap().changes[0].1[0];
+        assert_eq!(edit.new_text, "0.3.37");
+        assert_eq!(
+            (edit.range.start.character, edit.range.end.character),
+            (10, 13)
+        );
+    }
+}
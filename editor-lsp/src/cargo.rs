@@ -0,0 +1,573 @@
+use crate::protocol::{CodeAction, Hover, Range, TextEdit, WorkspaceEdit};
+use editor_core_text::{Cursor, DocumentTree, NodeKind, PathSegment};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+
+const CRATES_IO_API: &str = "https://crates.io/api/v1/crates";
+/// crates.io rejects requests without a user agent naming the client.
+const USER_AGENT: &str = concat!("fusang/", env!("CARGO_PKG_VERSION"));
+/// How long a lookup that failed, e.g. while offline, waits before being tried again.
+const RETRY_AFTER: Duration = Duration::from_secs(600);
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// A dependency declared in a Cargo manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoDependency {
+    /// The name of the crate on the registry, `package` for renamed dependencies.
+    pub name: String,
+    /// The line the dependency is declared on: its key, or its table header.
+    pub line: usize,
+    pub requirement: Option<VersionRequirement>,
+}
+
+/// The `version` of a dependency and where it is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRequirement {
+    pub text: String,
+    /// Start and end of the text between the quotes.
+    pub start: Cursor,
+    pub end: Cursor,
+}
+
+/// The newest version of a dependency, for showing after its line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateVersionHint {
+    pub line: usize,
+    pub latest: String,
+    /// The requirement doesn't allow the newest version.
+    pub outdated: bool,
+}
+
+/// The registry dependencies of a Cargo manifest: those in `[dependencies]`,
+/// `[dev-dependencies]` and `[build-dependencies]`, also under `[target.*]` and
+/// `[workspace]`. Path, git and workspace-inherited dependencies without a version are
+/// left out.
+pub fn dependencies(rope: &Rope) -> Vec<CargoDependency> {
+    let text = rope.to_string();
+    let tree = DocumentTree::parse_toml(&text);
+    let mut dependencies = Vec::new();
+    for (idx, node) in tree.nodes.iter().enumerate() {
+        let (Some(PathSegment::Key(key)), Some(parent)) = (&node.segment, node.parent) else {
+            continue;
+        };
+        if !is_dependency_table(&tree, parent) {
+            continue;
+        }
+        let mut name = key.clone();
+        let mut requirement = None;
+        match node.kind {
+            NodeKind::Scalar if node.value.starts_with('{') => {
+                let line = line_text(rope, node.start.line);
+                let offset = value_column(&line, node.start.column, '=');
+                if let Some(package) = inline_string(&node.value, "package") {
+                    name = package.1;
+                }
+                requirement = inline_string(&node.value, "version")
+                    .map(|(column, text)| requirement_at(node.start.line, offset + column, text));
+            }
+            NodeKind::Scalar => requirement = string_value(rope, node),
+            NodeKind::Object => {
+                for child in tree.nodes.iter().filter(|child| child.parent == Some(idx)) {
+                    match &child.segment {
+                        Some(PathSegment::Key(key)) if key == "version" => {
+                            requirement = string_value(rope, child)
+                        }
+                        Some(PathSegment::Key(key)) if key == "package" => {
+                            if let Some(package) = string_value(rope, child) {
+                                name = package.text;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            NodeKind::Array => continue,
+        }
+        if requirement.is_some() {
+            dependencies.push(CargoDependency {
+                name,
+                line: node.start.line,
+                requirement,
+            });
+        }
+    }
+    dependencies
+}
+
+fn is_dependency_table(tree: &DocumentTree, idx: usize) -> bool {
+    let mut path = Vec::new();
+    let mut node = tree.nodes.get(idx);
+    while let Some(current) = node {
+        match &current.segment {
+            Some(PathSegment::Key(key)) => path.push(key.as_str()),
+            Some(PathSegment::Index(_)) => return false,
+            None => {}
+        }
+        node = current.parent.map(|parent| &tree.nodes[parent]);
+    }
+    path.reverse();
+    match path.as_slice() {
+        [table] | ["workspace", table] | ["target", _, table] => DEPENDENCY_TABLES.contains(table),
+        _ => false,
+    }
+}
+
+pub(crate) fn line_text(rope: &Rope, line: usize) -> String {
+    rope.get_line(line)
+        .map(|line| line.chars().collect())
+        .unwrap_or_default()
+}
+
+/// The column of the value of the key written at `key_column`, after `separator`.
+pub(crate) fn value_column(line: &str, key_column: usize, separator: char) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    let mut column = key_column;
+    while column < chars.len() && chars[column] != separator {
+        column += 1;
+    }
+    column += 1;
+    while column < chars.len() && chars[column].is_whitespace() {
+        column += 1;
+    }
+    column
+}
+
+/// The value of `node` if it is a single-line string.
+fn string_value(rope: &Rope, node: &editor_core_text::TreeNode) -> Option<VersionRequirement> {
+    let text = unquote(&node.value)?;
+    let column = value_column(&line_text(rope, node.start.line), node.start.column, '=');
+    Some(requirement_at(node.start.line, column, text))
+}
+
+/// `text`, written in quotes starting at `column`.
+pub(crate) fn requirement_at(line: usize, column: usize, text: String) -> VersionRequirement {
+    VersionRequirement {
+        start: Cursor::new(line, column + 1),
+        end: Cursor::new(line, column + 1 + text.chars().count()),
+        text,
+    }
+}
+
+pub(crate) fn unquote(raw: &str) -> Option<String> {
+    let quote = raw.chars().next().filter(|ch| matches!(ch, '"' | '\''))?;
+    let inner = raw.strip_prefix(quote)?.strip_suffix(quote)?;
+    (!inner.contains(quote) && !inner.contains('\\')).then(|| inner.to_string())
+}
+
+/// The string `key` is set to in an inline table, with the column of its opening quote
+/// within `table`.
+fn inline_string(table: &str, key: &str) -> Option<(usize, String)> {
+    let chars: Vec<char> = table.chars().collect();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut entry_start = true;
+    for (column, &ch) in chars.iter().enumerate() {
+        if let Some(open) = quote {
+            if ch == open {
+                quote = None;
+            }
+            continue;
+        }
+        match ch {
+            '"' | '\'' => quote = Some(ch),
+            '{' | '[' => {
+                depth += 1;
+                entry_start = depth == 1;
+            }
+            '}' | ']' => depth -= 1,
+            ',' if depth == 1 => entry_start = true,
+            _ if ch.is_whitespace() => {}
+            _ if entry_start && depth == 1 => {
+                entry_start = false;
+                let rest: String = chars[column..].iter().collect();
+                let Some(after) = rest.strip_prefix(key) else {
+                    continue;
+                };
+                let Some(value) = after.trim_start().strip_prefix('=') else {
+                    continue;
+                };
+                let value = value.trim_start();
+                let value_column = chars.len() - value.chars().count();
+                let open = value.chars().next().filter(|ch| matches!(ch, '"' | '\''))?;
+                let body = &value[open.len_utf8()..];
+                let end = open.len_utf8() + body.find(open)? + open.len_utf8();
+                return Some((value_column, unquote(&value[..end])?));
+            }
+            _ => entry_start = false,
+        }
+    }
+    None
+}
+
+/// Whether `requirement` allows `version`. Requirements semver can't read count as
+/// allowing anything, so no update is offered for them.
+pub fn allows(requirement: &str, version: &str) -> bool {
+    match (
+        semver::VersionReq::parse(requirement),
+        semver::Version::parse(version),
+    ) {
+        (Ok(requirement), Ok(version)) => requirement.matches(&version),
+        _ => true,
+    }
+}
+
+/// The requirement to write to allow `latest`, keeping a leading `^`, `~` or `=`.
+pub(crate) fn updated_requirement(requirement: &str, latest: &str) -> String {
+    let operator: String = requirement
+        .chars()
+        .take_while(|ch| matches!(ch, '^' | '~' | '=') || ch.is_whitespace())
+        .collect();
+    format!("{}{}", operator.trim_end(), latest)
+}
+
+/// Where a crate's documentation is, for the version `requirement` allows.
+pub fn docs_url(name: &str, requirement: Option<&str>) -> String {
+    let version = requirement
+        .map(|requirement| requirement.trim_start_matches(['^', '=', ' ']))
+        .filter(|version| semver::VersionReq::parse(version).is_ok() && !version.is_empty())
+        .unwrap_or("latest");
+    format!("https://docs.rs/{}/{}", name, version)
+}
+
+#[derive(Debug, Clone)]
+enum Lookup {
+    Found(String),
+    Failed(Instant),
+}
+
+/// The newest versions of crates on crates.io, looked up once and kept for the session.
+#[derive(Debug, Clone)]
+pub struct CrateVersions {
+    client: reqwest::Client,
+    cache: Arc<RwLock<HashMap<String, Lookup>>>,
+}
+
+impl CrateVersions {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// The newest version already looked up for `name`.
+    pub async fn latest(&self, name: &str) -> Option<String> {
+        match self.cache.read().await.get(name) {
+            Some(Lookup::Found(version)) => Some(version.clone()),
+            _ => None,
+        }
+    }
+
+    /// Records the newest version of `name`, e.g. for a registry other than crates.io.
+    pub async fn insert(&self, name: &str, version: &str) {
+        self.cache
+            .write()
+            .await
+            .insert(name.to_string(), Lookup::Found(version.to_string()));
+    }
+
+    /// Looks up the crates not known yet, at the same time. Failed lookups are tried
+    /// again after a while.
+    pub async fn fetch(&self, names: impl IntoIterator<Item = String>) {
+        let missing: Vec<String> = {
+            let cache = self.cache.read().await;
+            names
+                .into_iter()
+                .filter(|name| match cache.get(name) {
+                    Some(Lookup::Found(_)) => false,
+                    Some(Lookup::Failed(at)) => at.elapsed() >= RETRY_AFTER,
+                    None => true,
+                })
+                .collect()
+        };
+        let mut lookups = JoinSet::new();
+        for name in missing {
+            let client = self.client.clone();
+            lookups.spawn(async move {
+                let latest = Self::fetch_latest(&client, &name).await;
+                (name, latest)
+            });
+        }
+        while let Some(Ok((name, latest))) = lookups.join_next().await {
+            let lookup = match latest {
+                Ok(version) => Lookup::Found(version),
+                Err(e) => {
+                    log::debug!("Failed to look up crate {}: {}", name, e);
+                    Lookup::Failed(Instant::now())
+                }
+            };
+            self.cache.write().await.insert(name, lookup);
+        }
+    }
+
+    async fn fetch_latest(client: &reqwest::Client, name: &str) -> Result<String, std::io::Error> {
+        let bytes = client
+            .get(format!("{}/{}", CRATES_IO_API, name))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(std::io::Error::other)?
+            .bytes()
+            .await
+            .map_err(std::io::Error::other)?;
+        let body: Value = serde_json::from_slice(&bytes)?;
+        let krate = &body["crate"];
+        krate["max_stable_version"]
+            .as_str()
+            .or_else(|| krate["max_version"].as_str())
+            .map(String::from)
+            .ok_or_else(|| std::io::Error::other("no version in the crates.io response"))
+    }
+
+    /// The newest version of each dependency that has been looked up.
+    pub async fn hints(&self, dependencies: &[CargoDependency]) -> Vec<CrateVersionHint> {
+        let mut hints = Vec::new();
+        for dependency in dependencies {
+            let (Some(requirement), Some(latest)) =
+                (&dependency.requirement, self.latest(&dependency.name).await)
+            else {
+                continue;
+            };
+            hints.push(CrateVersionHint {
+                line: dependency.line,
+                outdated: !allows(&requirement.text, &latest),
+                latest,
+            });
+        }
+        hints
+    }
+
+    /// Actions updating the dependencies declared on lines `range` covers to the newest
+    /// version, for those whose requirement doesn't allow it.
+    pub async fn update_actions(&self, rope: &Rope, uri: &str, range: &Range) -> Vec<CodeAction> {
+        let (first, last) = (range.start.line as usize, range.end.line as usize);
+        let mut actions = Vec::new();
+        for dependency in dependencies(rope) {
+            let Some(requirement) = &dependency.requirement else {
+                continue;
+            };
+            let lines = dependency.line.min(requirement.start.line)
+                ..=dependency.line.max(requirement.start.line);
+            if *lines.end() < first || *lines.start() > last {
+                continue;
+            }
+            let Some(latest) = self.latest(&dependency.name).await else {
+                continue;
+            };
+            if allows(&requirement.text, &latest) {
+                continue;
+            }
+            actions.push(CodeAction {
+                title: format!("Update {} to {}", dependency.name, latest),
+                kind: Some("quickfix".to_string()),
+                edit: Some(WorkspaceEdit {
+                    changes: vec![(
+                        uri.to_string(),
+                        vec![TextEdit {
+                            range: Range::from_cursors(rope, requirement.start, requirement.end),
+                            new_text: updated_requirement(&requirement.text, &latest),
+                        }],
+                    )],
+                }),
+                command: None,
+            });
+        }
+        actions
+    }
+
+    /// Links to the documentation and registry page of the dependency declared on
+    /// `line`, with its newest version if known.
+    pub async fn hover(&self, rope: &Rope, line: usize) -> Option<Hover> {
+        let dependency = dependencies(rope)
+            .into_iter()
+            .find(|dependency| dependency.line == line)?;
+        let requirement = dependency.requirement.as_ref().map(|r| r.text.as_str());
+        let mut sections = vec![format!(
+            "**{}** `{}`",
+            dependency.name,
+            requirement.unwrap_or("*")
+        )];
+        if let Some(latest) = self.latest(&dependency.name).await {
+            let note = match requirement {
+                Some(requirement) if !allows(requirement, &latest) => {
+                    " (not allowed by the requirement)"
+                }
+                _ => "",
+            };
+            sections.push(format!("Latest version: `{}`{}", latest, note));
+        }
+        sections.push(format!(
+            "[docs.rs]({}) · [crates.io](https://crates.io/crates/{})",
+            docs_url(&dependency.name, requirement),
+            dependency.name
+        ));
+        let end = line_text(rope, line).trim_end().chars().count();
+        Some(Hover {
+            contents: json!({ "kind": "markdown", "value": sections.join("\n\n") }),
+            range: Some(Range::from_cursors(
+                rope,
+                Cursor::new(line, 0),
+                Cursor::new(line, end),
+            )),
+        })
+    }
+}
+
+impl Default for CrateVersions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Position;
+    use tokio::runtime::Runtime;
+
+    const MANIFEST: &str = r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+tokio = "1"
+local = { path = "../local" }
+json = { package = "serde_json", version = "=1.0.100" }
+
+[dependencies.reqwest]
+version = "0.11"
+default-features = false
+
+[target.'cfg(unix)'.dev-dependencies]
+libc = "0.2"
+"#;
+
+    #[test]
+    fn reads_dependencies_and_where_their_versions_are() {
+        let rope = Rope::from_str(MANIFEST);
+        let found: Vec<(String, usize, String, (usize, usize))> = dependencies(&rope)
+            .into_iter()
+            .map(|dependency| {
+                let requirement = dependency.requirement.unwrap();
+                let line = line_text(&rope, requirement.start.line);
+                let written: String = line
+                    .chars()
+                    .skip(requirement.start.column)
+                    .take(requirement.end.column - requirement.start.column)
+                    .collect();
+                assert_eq!(written, requirement.text);
+                (
+                    dependency.name,
+                    dependency.line,
+                    requirement.text,
+                    (requirement.start.line, requirement.start.column),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("serde".into(), 5, "1.0".into(), (5, 21)),
+                ("tokio".into(), 6, "1".into(), (6, 9)),
+                ("serde_json".into(), 8, "=1.0.100".into(), (8, 44)),
+                ("reqwest".into(), 10, "0.11".into(), (11, 11)),
+                ("libc".into(), 15, "0.2".into(), (15, 8)),
+            ]
+        );
+    }
+
+    async fn known_versions() -> CrateVersions {
+        let versions = CrateVersions::new();
+        versions.insert("serde", "1.0.210").await;
+        versions.insert("tokio", "2.1.0").await;
+        versions.insert("serde_json", "1.0.128").await;
+        versions
+    }
+
+    #[test]
+    fn hints_flag_requirements_that_exclude_the_latest_version() {
+        Runtime::new().unwrap().block_on(async {
+            let versions = known_versions().await;
+            let rope = Rope::from_str(MANIFEST);
+            let hints = versions.hints(&dependencies(&rope)).await;
+            let hints: Vec<(usize, &str, bool)> = hints
+                .iter()
+                .map(|hint| (hint.line, hint.latest.as_str(), hint.outdated))
+                .collect();
+            assert_eq!(
+                hints,
+                [
+                    (5, "1.0.210", false),
+                    (6, "2.1.0", true),
+                    (8, "1.0.128", true)
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn offers_updates_keeping_the_requirement_operator() {
+        Runtime::new().unwrap().block_on(async {
+            let versions = known_versions().await;
+            let rope = Rope::from_str(MANIFEST);
+            let whole = Range {
+                start: Position {
+                    line: 0,
+                    character: 0,
+                },
+                end: Position {
+                    line: 20,
+                    character: 0,
+                },
+            };
+            let actions = versions
+                .update_actions(&rope, "file:///w/Cargo.toml", &whole)
+                .await;
+            let titles: Vec<&str> = actions.iter().map(|action| action.title.as_str()).collect();
+            assert_eq!(
+                titles,
+                ["Update tokio to 2.1.0", "Update serde_json to 1.0.128"]
+            );
+            let edit = &actions[1].edit.as_ref().unwrap().changes[0].1[0];
+            assert_eq!(edit.new_text, "=1.0.128");
+            assert_eq!(
+                (edit.range.start.character, edit.range.end.character),
+                (44, 52)
+            );
+        });
+    }
+
+    #[test]
+    fn hover_links_the_docs_of_the_required_version() {
+        Runtime::new().unwrap().block_on(async {
+            let versions = known_versions().await;
+            let rope = Rope::from_str(MANIFEST);
+            let hover = versions.hover(&rope, 6).await.unwrap();
+            let value = hover.contents["value"].as_str().unwrap();
+            assert!(value.contains("Latest version: `2.1.0` (not allowed"));
+            assert!(value.contains("(https://docs.rs/tokio/1)"));
+            assert!(versions.hover(&rope, 7).await.is_none());
+        });
+    }
+
+    #[test]
+    fn skips_inline_values_that_are_not_strings() {
+        assert_eq!(inline_string("{ version = ", "version"), None);
+        assert_eq!(inline_string("{ version = \"1.0", "version"), None);
+        assert_eq!(inline_string("{ version = “1.0” }", "version"), None);
+        assert_eq!(
+            inline_string("{ version = 'ü.0' }", "version"),
+            Some((12, "ü.0".to_string()))
+        );
+    }
+}
@@ -1,19 +1,53 @@
-use super::protocol::{CompletionItem, Hover, LspMessage, LspMethod, Position};
+use super::protocol::{
+    CodeAction, CompletionItem, FileChangeType, HierarchyDirection, HierarchyItem, Hover, Location,
+    LspCommand, LspMessage, LspMethod, LspNotification, Position, Range, TextEdit, WorkspaceEdit,
+};
+use super::server_log::ServerLog;
+use editor_infra::telemetry::{Metric, Metrics};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{ChildStdin as AsyncChildStdin, ChildStdout as AsyncChildStdout};
-use tokio::sync::Mutex;
+use tokio::process::{
+    ChildStderr as AsyncChildStderr, ChildStdin as AsyncChildStdin, ChildStdout as AsyncChildStdout,
+};
+use tokio::sync::{mpsc, Mutex};
+
+/// A request the server sent to the editor, answered with [`ServerRequest::respond`].
+#[derive(Debug, Clone)]
+pub struct ServerRequest {
+    pub id: u64,
+    pub method: LspMethod,
+    pub params: Value,
+    stdin: Arc<Mutex<AsyncChildStdin>>,
+}
+
+impl ServerRequest {
+    /// Replies without going through the client, which may be busy waiting on a request
+    /// of its own that triggered this one.
+    pub async fn respond(&self, result: Value) -> Result<(), std::io::Error> {
+        write_message(&self.stdin, &LspMessage::new_response(self.id, result)).await
+    }
+}
 
 #[derive(Debug)]
 pub struct LspClient {
     process: Option<Child>,
-    stdin: Option<AsyncChildStdin>,
+    stdin: Option<Arc<Mutex<AsyncChildStdin>>>,
     stdout: Option<BufReader<AsyncChildStdout>>,
     next_request_id: u64,
     pending_requests: Arc<Mutex<HashMap<u64, tokio::sync::oneshot::Sender<LspMessage>>>>,
+    log: Option<ServerLog>,
+    exit_signal: Option<tokio::sync::oneshot::Receiver<()>>,
+    server_requests: Option<mpsc::UnboundedReceiver<ServerRequest>>,
+    server_notifications: Option<mpsc::UnboundedReceiver<LspNotification>>,
+    /// Uris this server has been sent `didOpen` for.
+    open_documents: HashSet<String>,
+    /// What the server said it supports in its `initialize` response.
+    capabilities: Value,
+    /// Where request durations are recorded, if anywhere.
+    metrics: Option<Metrics>,
 }
 
 impl LspClient {
@@ -24,14 +58,113 @@ impl LspClient {
             stdout: None,
             next_request_id: 1,
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            log: None,
+            exit_signal: None,
+            server_requests: None,
+            server_notifications: None,
+            open_documents: HashSet::new(),
+            capabilities: Value::Null,
+            metrics: None,
+        }
+    }
+
+    pub fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = Some(metrics);
+    }
+
+    /// `documentFormattingProvider` may be `true` or an options object.
+    pub fn supports_formatting(&self) -> bool {
+        self.provides("documentFormattingProvider")
+    }
+
+    pub fn supports_range_formatting(&self) -> bool {
+        self.provides("documentRangeFormattingProvider")
+    }
+
+    fn provides(&self, capability: &str) -> bool {
+        match self.capabilities.get(capability) {
+            Some(Value::Bool(supported)) => *supported,
+            Some(Value::Object(_)) => true,
+            _ => false,
         }
     }
 
+    pub fn supports_completion_resolve(&self) -> bool {
+        self.capabilities
+            .get("completionProvider")
+            .and_then(|provider| provider.get("resolveProvider"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    /// Whether the server registered for `workspace/willRenameFiles` (`"willRename"`) or
+    /// `workspace/didRenameFiles` (`"didRename"`). The registration's file filters are not
+    /// checked; only files of the server's own language are reported to it.
+    pub fn supports_file_operation(&self, operation: &str) -> bool {
+        self.capabilities
+            .get("workspace")
+            .and_then(|workspace| workspace.get("fileOperations"))
+            .and_then(|operations| operations.get(operation))
+            .is_some_and(|registration| !registration.is_null())
+    }
+
+    /// Characters that trigger `textDocument/onTypeFormatting`; empty when the server
+    /// does not support it.
+    pub fn on_type_formatting_triggers(&self) -> Vec<String> {
+        let Some(provider) = self.capabilities.get("documentOnTypeFormattingProvider") else {
+            return Vec::new();
+        };
+        let first = provider
+            .get("firstTriggerCharacter")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let more = provider
+            .get("moreTriggerCharacter")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_str().map(str::to_string));
+        first.into_iter().chain(more).collect()
+    }
+
+    pub fn is_open(&self, uri: &str) -> bool {
+        self.open_documents.contains(uri)
+    }
+
+    /// Stderr output of the running server.
+    pub fn server_log(&self) -> Option<&ServerLog> {
+        self.log.as_ref()
+    }
+
+    /// Resolves once the server closes its stderr, which happens when the process exits.
+    pub fn take_exit_signal(&mut self) -> Option<tokio::sync::oneshot::Receiver<()>> {
+        self.exit_signal.take()
+    }
+
+    /// Requests from the server that need the editor's answer, such as `workspace/applyEdit`.
+    pub fn take_server_requests(&mut self) -> Option<mpsc::UnboundedReceiver<ServerRequest>> {
+        self.server_requests.take()
+    }
+
+    /// Notifications from the server, such as `textDocument/publishDiagnostics`.
+    pub fn take_server_notifications(
+        &mut self,
+    ) -> Option<mpsc::UnboundedReceiver<LspNotification>> {
+        self.server_notifications.take()
+    }
+
+    pub fn exit_status(&mut self) -> Option<std::process::ExitStatus> {
+        self.process
+            .as_mut()
+            .and_then(|process| process.try_wait().ok().flatten())
+    }
+
     pub async fn start_server(
         &mut self,
         command: &str,
         args: &[String],
     ) -> Result<(), std::io::Error> {
+        let log = ServerLog::for_server(command);
         let mut command = Command::new(command);
         command.args(args);
         command.stdin(Stdio::piped());
@@ -42,14 +175,33 @@ impl LspClient {
 
         let stdin = child.stdin.take().expect("Failed to open stdin");
         let stdout = child.stdout.take().expect("Failed to open stdout");
+        let stderr = child.stderr.take().expect("Failed to open stderr");
 
         let async_stdin = AsyncChildStdin::from_std(stdin)?;
         let async_stdout = AsyncChildStdout::from_std(stdout)?;
 
-        self.stdin = Some(async_stdin);
+        self.stdin = Some(Arc::new(Mutex::new(async_stdin)));
         self.stdout = Some(BufReader::new(async_stdout));
         self.process = Some(child);
 
+        // Drain stderr so a chatty server can't block on a full pipe
+        let (exit_tx, exit_rx) = tokio::sync::oneshot::channel();
+        let stderr_log = log.clone();
+        let mut stderr = BufReader::new(AsyncChildStderr::from_std(stderr)?);
+        tokio::spawn(async move {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match stderr.read_line(&mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => stderr_log.push(&line),
+                }
+            }
+            let _ = exit_tx.send(());
+        });
+        self.log = Some(log);
+        self.exit_signal = Some(exit_rx);
+
         // Start message processing loop
         self.start_message_processor().await;
 
@@ -64,15 +216,46 @@ impl LspClient {
                 "textDocument": {
                     "completion": {
                         "completionItem": {
-                            "snippetSupport": true
+                            "snippetSupport": true,
+                            "insertReplaceSupport": true,
+                            "resolveSupport": {
+                                "properties": ["documentation", "detail", "additionalTextEdits"]
+                            }
                         }
                     },
                     "hover": {
                         "contentFormat": ["markdown", "plaintext"]
+                    },
+                    "definition": {
+                        "linkSupport": true
+                    },
+                    "references": {},
+                    "formatting": {},
+                    "rangeFormatting": {},
+                    "onTypeFormatting": {},
+                    "callHierarchy": {},
+                    "typeHierarchy": {},
+                    "codeAction": {
+                        "codeActionLiteralSupport": {
+                            "codeActionKind": {
+                                "valueSet": ["", "quickfix", "refactor", "source"]
+                            }
+                        }
                     }
                 },
                 "workspace": {
-                    "configuration": true
+                    "applyEdit": true,
+                    "workspaceEdit": {
+                        "documentChanges": true
+                    },
+                    "configuration": true,
+                    "didChangeWatchedFiles": {
+                        "dynamicRegistration": false
+                    },
+                    "fileOperations": {
+                        "willRename": true,
+                        "didRename": true
+                    }
                 }
             },
             "trace": "off"
@@ -81,6 +264,7 @@ impl LspClient {
         let response = self
             .send_request(LspMethod::Initialize, initialize_params)
             .await?;
+        self.capabilities = response.get("capabilities").cloned().unwrap_or(Value::Null);
         Ok(response)
     }
 
@@ -92,6 +276,8 @@ impl LspClient {
         let request_id = self.next_request_id;
         self.next_request_id += 1;
 
+        let metric = Metric::LspRequest(method.as_str().to_string());
+        let started = std::time::Instant::now();
         let message = LspMessage::new_request(request_id, method, params);
         self.send_message(&message).await?;
 
@@ -101,26 +287,24 @@ impl LspClient {
             pending.insert(request_id, sender);
         }
 
-        match receiver.await {
+        let response = receiver.await;
+        if let Some(metrics) = &self.metrics {
+            metrics.record(metric, started.elapsed());
+        }
+        match response {
             Ok(response) => {
                 if let Some(result) = response.result {
                     Ok(result)
                 } else if let Some(error) = response.error {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("LSP error: {}", error.message),
-                    ))
+                    Err(std::io::Error::other(format!(
+                        "LSP error: {}",
+                        error.message
+                    )))
                 } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Invalid LSP response",
-                    ))
+                    Err(std::io::Error::other("Invalid LSP response"))
                 }
             }
-            Err(_) => Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Request timeout or channel closed",
-            )),
+            Err(_) => Err(std::io::Error::other("Request timeout or channel closed")),
         }
     }
 
@@ -134,13 +318,10 @@ impl LspClient {
     }
 
     async fn send_message(&mut self, message: &LspMessage) -> Result<(), std::io::Error> {
-        if let Some(stdin) = &mut self.stdin {
-            let json = serde_json::to_string(message)?;
-            let content = format!("Content-Length: {}\r\n\r\n{}", json.len(), json);
-            stdin.write_all(content.as_bytes()).await?;
-            stdin.flush().await?;
+        match &self.stdin {
+            Some(stdin) => write_message(stdin, message).await,
+            None => Ok(()),
         }
-        Ok(())
     }
 
     async fn start_message_processor(&mut self) {
@@ -148,7 +329,14 @@ impl LspClient {
             Some(stdout) => stdout,
             None => return,
         };
+        let Some(stdin) = self.stdin.clone() else {
+            return;
+        };
         let pending_requests = self.pending_requests.clone();
+        let (requests_tx, requests_rx) = mpsc::unbounded_channel();
+        self.server_requests = Some(requests_rx);
+        let (notifications_tx, notifications_rx) = mpsc::unbounded_channel();
+        self.server_notifications = Some(notifications_rx);
 
         tokio::spawn(async move {
             let mut reader = stdout;
@@ -176,8 +364,14 @@ impl LspClient {
                                             if let Ok(message) =
                                                 serde_json::from_str::<LspMessage>(&json_str)
                                             {
-                                                Self::handle_message(&message, &pending_requests)
-                                                    .await;
+                                                Self::handle_message(
+                                                    message,
+                                                    &pending_requests,
+                                                    &stdin,
+                                                    &requests_tx,
+                                                    &notifications_tx,
+                                                )
+                                                .await;
                                             }
                                         }
                                     }
@@ -192,16 +386,38 @@ impl LspClient {
     }
 
     async fn handle_message(
-        message: &LspMessage,
+        message: LspMessage,
         pending_requests: &Arc<Mutex<HashMap<u64, tokio::sync::oneshot::Sender<LspMessage>>>>,
+        stdin: &Arc<Mutex<AsyncChildStdin>>,
+        server_requests: &mpsc::UnboundedSender<ServerRequest>,
+        server_notifications: &mpsc::UnboundedSender<LspNotification>,
     ) {
+        if message.is_request() {
+            if let (Some(id), Some(method)) = (message.id, message.method) {
+                let _ = server_requests.send(ServerRequest {
+                    id,
+                    method,
+                    params: message.params.unwrap_or_default(),
+                    stdin: stdin.clone(),
+                });
+            }
+            return;
+        }
+        if message.is_notification() {
+            if let Some(method) = message.method {
+                let _ = server_notifications.send(LspNotification {
+                    method,
+                    params: message.params.unwrap_or_default(),
+                });
+            }
+            return;
+        }
         if let Some(id) = message.id {
             let mut pending = pending_requests.lock().await;
             if let Some(sender) = pending.remove(&id) {
-                let _ = sender.send(message.clone());
+                let _ = sender.send(message);
             }
         }
-        // Handle notifications (like diagnostics) here
     }
 
     pub async fn request_completion(
@@ -217,16 +433,22 @@ impl LspClient {
         let result = self
             .send_request(LspMethod::TextDocumentCompletion, params)
             .await?;
+        Ok(CompletionItem::list_from_value(&result))
+    }
 
-        if let Some(items) = result.get("items").and_then(|i| i.as_array()) {
-            let completions: Vec<CompletionItem> = items
-                .iter()
-                .filter_map(|item| serde_json::from_value(item.clone()).ok())
-                .collect();
-            Ok(completions)
-        } else {
-            Ok(Vec::new())
+    /// Fills in the parts of an item the server left out of the list, such as
+    /// `additionalTextEdits`. Returns the item unchanged when the server can't resolve.
+    pub async fn resolve_completion(
+        &mut self,
+        item: &CompletionItem,
+    ) -> Result<CompletionItem, std::io::Error> {
+        if !self.supports_completion_resolve() {
+            return Ok(item.clone());
         }
+        let result = self
+            .send_request(LspMethod::CompletionItemResolve, item.raw.clone())
+            .await?;
+        Ok(CompletionItem::from_value(&result).unwrap_or_else(|| item.clone()))
     }
 
     pub async fn request_hover(
@@ -242,8 +464,172 @@ impl LspClient {
         let result = self
             .send_request(LspMethod::TextDocumentHover, params)
             .await?;
+        serde_json::from_value(result).map_err(std::io::Error::other)
+    }
+
+    pub async fn request_definition(
+        &mut self,
+        uri: &str,
+        position: Position,
+    ) -> Result<Vec<Location>, std::io::Error> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": position
+        });
+
+        let result = self
+            .send_request(LspMethod::TextDocumentDefinition, params)
+            .await?;
+        Ok(Location::list_from_value(&result))
+    }
+
+    pub async fn request_references(
+        &mut self,
+        uri: &str,
+        position: Position,
+        include_declaration: bool,
+    ) -> Result<Vec<Location>, std::io::Error> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": position,
+            "context": { "includeDeclaration": include_declaration }
+        });
+
+        let result = self
+            .send_request(LspMethod::TextDocumentReferences, params)
+            .await?;
+        Ok(Location::list_from_value(&result))
+    }
+
+    /// Resolves the symbol at `position` to the items a hierarchy starts from.
+    pub async fn prepare_hierarchy(
+        &mut self,
+        direction: HierarchyDirection,
+        uri: &str,
+        position: Position,
+    ) -> Result<Vec<HierarchyItem>, std::io::Error> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": position
+        });
+
+        let result = self
+            .send_request(direction.prepare_method(), params)
+            .await?;
+        if result.is_null() {
+            return Ok(Vec::new());
+        }
+        serde_json::from_value(result).map_err(std::io::Error::other)
+    }
+
+    /// The callers, callees, supertypes or subtypes of `item`.
+    pub async fn request_hierarchy(
+        &mut self,
+        direction: HierarchyDirection,
+        item: &HierarchyItem,
+    ) -> Result<Vec<HierarchyItem>, std::io::Error> {
+        let params = serde_json::json!({ "item": item });
+        let result = self.send_request(direction.method(), params).await?;
+        Ok(direction.items_from_value(&result))
+    }
+
+    /// Quick fixes and refactorings for `range`.
+    pub async fn request_code_actions(
+        &mut self,
+        uri: &str,
+        range: Range,
+    ) -> Result<Vec<CodeAction>, std::io::Error> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "range": range,
+            "context": { "diagnostics": [] }
+        });
+
+        let result = self
+            .send_request(LspMethod::TextDocumentCodeAction, params)
+            .await?;
+        Ok(CodeAction::list_from_value(&result))
+    }
+
+    pub async fn request_formatting(
+        &mut self,
+        uri: &str,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<Vec<TextEdit>, std::io::Error> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "options": { "tabSize": tab_size, "insertSpaces": insert_spaces }
+        });
+
+        let result = self
+            .send_request(LspMethod::TextDocumentFormatting, params)
+            .await?;
+        if result.is_null() {
+            return Ok(Vec::new());
+        }
         serde_json::from_value(result)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Edits that format only `range` of the document.
+    pub async fn request_range_formatting(
+        &mut self,
+        uri: &str,
+        range: Range,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<Vec<TextEdit>, std::io::Error> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "range": range,
+            "options": { "tabSize": tab_size, "insertSpaces": insert_spaces }
+        });
+
+        let result = self
+            .send_request(LspMethod::TextDocumentRangeFormatting, params)
+            .await?;
+        if result.is_null() {
+            return Ok(Vec::new());
+        }
+        serde_json::from_value(result)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Edits for the character `ch` just typed before `position`.
+    pub async fn request_on_type_formatting(
+        &mut self,
+        uri: &str,
+        position: Position,
+        ch: &str,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<Vec<TextEdit>, std::io::Error> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": position,
+            "ch": ch,
+            "options": { "tabSize": tab_size, "insertSpaces": insert_spaces }
+        });
+
+        let result = self
+            .send_request(LspMethod::TextDocumentOnTypeFormatting, params)
+            .await?;
+        if result.is_null() {
+            return Ok(Vec::new());
+        }
+        serde_json::from_value(result)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Runs a code action's command. Any edits come back as `workspace/applyEdit` requests.
+    pub async fn execute_command(&mut self, command: &LspCommand) -> Result<Value, std::io::Error> {
+        let params = serde_json::json!({
+            "command": command.command,
+            "arguments": command.arguments.clone().unwrap_or_default()
+        });
+        self.send_request(LspMethod::WorkspaceExecuteCommand, params)
+            .await
     }
 
     pub async fn notify_did_open(
@@ -251,17 +637,35 @@ impl LspClient {
         uri: &str,
         text: &str,
         language_id: &str,
+        version: u64,
     ) -> Result<(), std::io::Error> {
         let params = serde_json::json!({
             "textDocument": {
                 "uri": uri,
                 "languageId": language_id,
-                "version": 1,
+                "version": version,
                 "text": text
             }
         });
 
         self.send_notification(LspMethod::TextDocumentDidOpen, params)
+            .await?;
+        self.open_documents.insert(uri.to_string());
+        Ok(())
+    }
+
+    /// Does nothing for documents this server wasn't sent `didOpen` for.
+    pub async fn notify_did_close(&mut self, uri: &str) -> Result<(), std::io::Error> {
+        if !self.open_documents.remove(uri) {
+            return Ok(());
+        }
+        let params = serde_json::json!({
+            "textDocument": {
+                "uri": uri
+            }
+        });
+
+        self.send_notification(LspMethod::TextDocumentDidClose, params)
             .await
     }
 
@@ -285,6 +689,98 @@ impl LspClient {
             .await
     }
 
+    /// Send `TextChange`s as incremental `contentChanges`, in the order they were applied.
+    pub async fn notify_did_change_incremental(
+        &mut self,
+        uri: &str,
+        changes: &[editor_core_text::TextChange],
+    ) -> Result<(), std::io::Error> {
+        let Some(last) = changes.last() else {
+            return Ok(());
+        };
+
+        let content_changes: Vec<Value> = changes
+            .iter()
+            .map(|change| {
+                serde_json::json!({
+                    "range": {
+                        "start": {
+                            "line": change.utf16_start.line,
+                            "character": change.utf16_start.column
+                        },
+                        "end": {
+                            "line": change.utf16_old_end.line,
+                            "character": change.utf16_old_end.column
+                        }
+                    },
+                    "text": change.new_text()
+                })
+            })
+            .collect();
+        let params = serde_json::json!({
+            "textDocument": {
+                "uri": uri,
+                "version": last.after_version
+            },
+            "contentChanges": content_changes
+        });
+
+        self.send_notification(LspMethod::TextDocumentDidChange, params)
+            .await
+    }
+
+    /// Report files changed on disk by something other than this client.
+    pub async fn notify_did_change_watched_files(
+        &mut self,
+        changes: &[(String, FileChangeType)],
+    ) -> Result<(), std::io::Error> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let events: Vec<Value> = changes
+            .iter()
+            .map(|(uri, kind)| serde_json::json!({ "uri": uri, "type": *kind as u8 }))
+            .collect();
+        let params = serde_json::json!({ "changes": events });
+
+        self.send_notification(LspMethod::WorkspaceDidChangeWatchedFiles, params)
+            .await
+    }
+
+    /// Asks the server for the edits a file rename needs, e.g. updated imports, before the
+    /// file is moved. Empty when the server has none or doesn't support it.
+    pub async fn request_will_rename_files(
+        &mut self,
+        old_uri: &str,
+        new_uri: &str,
+    ) -> Result<WorkspaceEdit, std::io::Error> {
+        if !self.supports_file_operation("willRename") {
+            return Ok(WorkspaceEdit::default());
+        }
+        let params = serde_json::json!({
+            "files": [{ "oldUri": old_uri, "newUri": new_uri }]
+        });
+        let result = self
+            .send_request(LspMethod::WorkspaceWillRenameFiles, params)
+            .await?;
+        Ok(WorkspaceEdit::from_value(&result))
+    }
+
+    pub async fn notify_did_rename_files(
+        &mut self,
+        old_uri: &str,
+        new_uri: &str,
+    ) -> Result<(), std::io::Error> {
+        if !self.supports_file_operation("didRename") {
+            return Ok(());
+        }
+        let params = serde_json::json!({
+            "files": [{ "oldUri": old_uri, "newUri": new_uri }]
+        });
+        self.send_notification(LspMethod::WorkspaceDidRenameFiles, params)
+            .await
+    }
+
     pub async fn shutdown(&mut self) -> Result<(), std::io::Error> {
         self.send_request(LspMethod::Shutdown, serde_json::Value::Null)
             .await?;
@@ -299,6 +795,23 @@ impl LspClient {
     }
 }
 
+async fn write_message(
+    stdin: &Mutex<AsyncChildStdin>,
+    message: &LspMessage,
+) -> Result<(), std::io::Error> {
+    let json = serde_json::to_string(message)?;
+    let content = format!("Content-Length: {}\r\n\r\n{}", json.len(), json);
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(content.as_bytes()).await?;
+    stdin.flush().await
+}
+
+impl Default for LspClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for LspClient {
     fn drop(&mut self) {
         if let Some(mut process) = self.process.take() {
@@ -0,0 +1,201 @@
+use editor_infra::config::LSPServerConfig;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// One way to get a missing language server onto the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallMethod {
+    /// A release binary, gzip-compressed when the URL ends in `.gz`.
+    Download { url: String },
+    /// A command the user can run themselves or have the editor run for them.
+    Command { program: String, args: Vec<String> },
+}
+
+impl InstallMethod {
+    pub fn label(&self) -> String {
+        match self {
+            InstallMethod::Download { url } => format!("Download {}", url),
+            InstallMethod::Command { program, args } => std::iter::once(program.as_str())
+                .chain(args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// A configured server whose binary could not be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingServer {
+    pub language: String,
+    pub command: String,
+    pub methods: Vec<InstallMethod>,
+}
+
+/// Where servers downloaded by the editor are kept.
+pub fn servers_dir() -> PathBuf {
+    editor_infra::paths::data_dir().join("lsp-servers")
+}
+
+/// Locates a server binary on `PATH`, falling back to servers the editor installed.
+pub fn resolve_command(command: &str) -> Option<PathBuf> {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    which::which(command).ok().or_else(|| {
+        let installed = servers_dir().join(binary_name(command));
+        installed.is_file().then_some(installed)
+    })
+}
+
+pub fn missing_servers(configs: &[LSPServerConfig]) -> Vec<MissingServer> {
+    configs
+        .iter()
+        .filter(|config| resolve_command(&config.command).is_none())
+        .map(|config| MissingServer {
+            language: config.language.clone(),
+            command: config.command.clone(),
+            methods: install_methods(&config.command),
+        })
+        .collect()
+}
+
+/// Known ways to install common servers. Unknown servers get no suggestions.
+pub fn install_methods(command: &str) -> Vec<InstallMethod> {
+    let command_method = |program: &str, args: &[&str]| InstallMethod::Command {
+        program: program.to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+    };
+
+    match command {
+        "rust-analyzer" => {
+            let mut methods = Vec::new();
+            if let Some(target) = rust_analyzer_target() {
+                methods.push(InstallMethod::Download {
+                    url: format!(
+                        "https://github.com/rust-lang/rust-analyzer/releases/latest/download/rust-analyzer-{}.gz",
+                        target
+                    ),
+                });
+            }
+            methods.push(command_method(
+                "rustup",
+                &["component", "add", "rust-analyzer"],
+            ));
+            methods
+        }
+        "pylsp" => vec![command_method(
+            "python3",
+            &["-m", "pip", "install", "--user", "python-lsp-server"],
+        )],
+        "typescript-language-server" => vec![command_method(
+            "npm",
+            &["install", "-g", "typescript-language-server", "typescript"],
+        )],
+        "gopls" => vec![command_method(
+            "go",
+            &["install", "golang.org/x/tools/gopls@latest"],
+        )],
+        _ => Vec::new(),
+    }
+}
+
+/// Installs `server` with `method` and returns the path to the usable binary.
+pub async fn install(
+    server: &MissingServer,
+    method: &InstallMethod,
+) -> Result<PathBuf, std::io::Error> {
+    match method {
+        InstallMethod::Download { url } => {
+            let bytes = reqwest::get(url)
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(std::io::Error::other)?
+                .bytes()
+                .await
+                .map_err(std::io::Error::other)?;
+
+            let binary = if url.ends_with(".gz") {
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(bytes.as_ref()).read_to_end(&mut decoded)?;
+                decoded
+            } else {
+                bytes.to_vec()
+            };
+
+            let dir = servers_dir();
+            std::fs::create_dir_all(&dir)?;
+            let target = dir.join(binary_name(&server.command));
+            std::fs::write(&target, binary)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o755))?;
+            }
+            Ok(target)
+        }
+        InstallMethod::Command { program, args } => {
+            let status = tokio::process::Command::new(program)
+                .args(args)
+                .status()
+                .await?;
+            if !status.success() {
+                return Err(std::io::Error::other(format!(
+                    "`{}` exited with {}",
+                    method.label(),
+                    status
+                )));
+            }
+            resolve_command(&server.command).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} was installed but is not on PATH", server.command),
+                )
+            })
+        }
+    }
+}
+
+fn binary_name(command: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.exe", command)
+    } else {
+        command.to_string()
+    }
+}
+
+fn rust_analyzer_target() -> Option<&'static str> {
+    match (std::env::consts::ARCH, std::env::consts::OS) {
+        ("x86_64", "linux") => Some("x86_64-unknown-linux-gnu"),
+        ("aarch64", "linux") => Some("aarch64-unknown-linux-gnu"),
+        ("x86_64", "macos") => Some("x86_64-apple-darwin"),
+        ("aarch64", "macos") => Some("aarch64-apple-darwin"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_missing_servers_with_install_suggestions() {
+        let config = |language: &str, command: &str| LSPServerConfig {
+            language: language.to_string(),
+            command: command.to_string(),
+            args: Vec::new(),
+        };
+        let missing = missing_servers(&[
+            config("shell", "sh"),
+            config("python", "pylsp-that-does-not-exist"),
+        ]);
+
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].language, "python");
+        assert!(missing[0].methods.is_empty());
+        assert_eq!(
+            install_methods("pylsp")[0].label(),
+            "python3 -m pip install --user python-lsp-server"
+        );
+    }
+}
@@ -1,7 +1,25 @@
+pub mod advisories;
+pub mod cargo;
 pub mod client;
+pub mod installer;
 pub mod protocol;
+pub mod request_gate;
+pub mod schema;
+pub mod server_log;
 pub mod server_manager;
+pub mod uri;
 
-pub use client::LspClient;
-pub use protocol::{LspMessage, LspNotification, LspRequest, LspResponse};
-pub use server_manager::LspServerManager;
+pub use advisories::{Advisory, AdvisoryChecker, Ecosystem, LockedPackage, PackageReport};
+pub use cargo::{CargoDependency, CrateVersionHint, CrateVersions};
+pub use client::{LspClient, ServerRequest};
+pub use installer::{InstallMethod, MissingServer};
+pub use protocol::{
+    CodeAction, CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, FileChangeType,
+    HierarchyDirection, HierarchyItem, Location, LspCommand, LspMessage, LspNotification,
+    LspRequest, LspResponse, Position, PublishDiagnostics, Range, TextEdit, WorkspaceEdit,
+};
+pub use request_gate::{GateTicket, RequestGate, RequestKind};
+pub use schema::{JsonSchema, SchemaDocument};
+pub use server_log::ServerLog;
+pub use server_manager::{ApplyEditRequest, LspServerManager, ServerCrash};
+pub use uri::{path_to_uri, untitled_uri, uri_to_path};
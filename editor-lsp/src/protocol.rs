@@ -1,3 +1,5 @@
+use editor_core_text::{Cursor, RopeExt};
+use ropey::Rope;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -8,14 +10,52 @@ pub enum LspMethod {
     Initialize,
     #[serde(rename = "textDocument/completion")]
     TextDocumentCompletion,
+    #[serde(rename = "completionItem/resolve")]
+    CompletionItemResolve,
     #[serde(rename = "textDocument/hover")]
     TextDocumentHover,
+    #[serde(rename = "textDocument/definition")]
+    TextDocumentDefinition,
+    #[serde(rename = "textDocument/references")]
+    TextDocumentReferences,
+    #[serde(rename = "textDocument/prepareCallHierarchy")]
+    TextDocumentPrepareCallHierarchy,
+    #[serde(rename = "callHierarchy/incomingCalls")]
+    CallHierarchyIncomingCalls,
+    #[serde(rename = "callHierarchy/outgoingCalls")]
+    CallHierarchyOutgoingCalls,
+    #[serde(rename = "textDocument/prepareTypeHierarchy")]
+    TextDocumentPrepareTypeHierarchy,
+    #[serde(rename = "typeHierarchy/supertypes")]
+    TypeHierarchySupertypes,
+    #[serde(rename = "typeHierarchy/subtypes")]
+    TypeHierarchySubtypes,
+    #[serde(rename = "textDocument/codeAction")]
+    TextDocumentCodeAction,
+    #[serde(rename = "textDocument/formatting")]
+    TextDocumentFormatting,
+    #[serde(rename = "textDocument/rangeFormatting")]
+    TextDocumentRangeFormatting,
+    #[serde(rename = "textDocument/onTypeFormatting")]
+    TextDocumentOnTypeFormatting,
+    #[serde(rename = "workspace/executeCommand")]
+    WorkspaceExecuteCommand,
+    #[serde(rename = "workspace/applyEdit")]
+    WorkspaceApplyEdit,
     #[serde(rename = "textDocument/didOpen")]
     TextDocumentDidOpen,
     #[serde(rename = "textDocument/didChange")]
     TextDocumentDidChange,
+    #[serde(rename = "textDocument/didClose")]
+    TextDocumentDidClose,
     #[serde(rename = "textDocument/publishDiagnostics")]
     TextDocumentPublishDiagnostics,
+    #[serde(rename = "workspace/didChangeWatchedFiles")]
+    WorkspaceDidChangeWatchedFiles,
+    #[serde(rename = "workspace/willRenameFiles")]
+    WorkspaceWillRenameFiles,
+    #[serde(rename = "workspace/didRenameFiles")]
+    WorkspaceDidRenameFiles,
     #[serde(rename = "shutdown")]
     Shutdown,
     #[serde(rename = "exit")]
@@ -28,10 +68,29 @@ impl LspMethod {
         match self {
             LspMethod::Initialize => "initialize",
             LspMethod::TextDocumentCompletion => "textDocument/completion",
+            LspMethod::CompletionItemResolve => "completionItem/resolve",
             LspMethod::TextDocumentHover => "textDocument/hover",
+            LspMethod::TextDocumentDefinition => "textDocument/definition",
+            LspMethod::TextDocumentReferences => "textDocument/references",
+            LspMethod::TextDocumentPrepareCallHierarchy => "textDocument/prepareCallHierarchy",
+            LspMethod::CallHierarchyIncomingCalls => "callHierarchy/incomingCalls",
+            LspMethod::CallHierarchyOutgoingCalls => "callHierarchy/outgoingCalls",
+            LspMethod::TextDocumentPrepareTypeHierarchy => "textDocument/prepareTypeHierarchy",
+            LspMethod::TypeHierarchySupertypes => "typeHierarchy/supertypes",
+            LspMethod::TypeHierarchySubtypes => "typeHierarchy/subtypes",
+            LspMethod::TextDocumentCodeAction => "textDocument/codeAction",
+            LspMethod::TextDocumentFormatting => "textDocument/formatting",
+            LspMethod::TextDocumentRangeFormatting => "textDocument/rangeFormatting",
+            LspMethod::TextDocumentOnTypeFormatting => "textDocument/onTypeFormatting",
+            LspMethod::WorkspaceExecuteCommand => "workspace/executeCommand",
+            LspMethod::WorkspaceApplyEdit => "workspace/applyEdit",
             LspMethod::TextDocumentDidOpen => "textDocument/didOpen",
             LspMethod::TextDocumentDidChange => "textDocument/didChange",
+            LspMethod::TextDocumentDidClose => "textDocument/didClose",
             LspMethod::TextDocumentPublishDiagnostics => "textDocument/publishDiagnostics",
+            LspMethod::WorkspaceDidChangeWatchedFiles => "workspace/didChangeWatchedFiles",
+            LspMethod::WorkspaceWillRenameFiles => "workspace/willRenameFiles",
+            LspMethod::WorkspaceDidRenameFiles => "workspace/didRenameFiles",
             LspMethod::Shutdown => "shutdown",
             LspMethod::Exit => "exit",
             LspMethod::Custom(s) => s,
@@ -91,26 +150,299 @@ pub struct LspNotification {
 }
 
 // LSP Protocol specific structures
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Position {
     pub line: u32,
     pub character: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Position {
+    /// `cursor` in `rope` with its column counted in UTF-16 code units, as servers expect.
+    pub fn from_cursor(rope: &Rope, cursor: Cursor) -> Self {
+        Self {
+            line: cursor.line as u32,
+            character: rope.char_to_utf16_col(cursor.line, cursor.column) as u32,
+        }
+    }
+
+    /// The char-based cursor for this position in `rope`.
+    pub fn to_cursor(&self, rope: &Rope) -> Cursor {
+        let line = self.line as usize;
+        Cursor::new(line, rope.utf16_to_char_col(line, self.character as usize))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Range {
     pub start: Position,
     pub end: Position,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Range {
+    pub fn from_cursors(rope: &Rope, start: Cursor, end: Cursor) -> Self {
+        Self {
+            start: Position::from_cursor(rope, start),
+            end: Position::from_cursor(rope, end),
+        }
+    }
+
+    pub fn to_cursors(&self, rope: &Rope) -> (Cursor, Cursor) {
+        (self.start.to_cursor(rope), self.end.to_cursor(rope))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Location {
     pub uri: String,
     pub range: Range,
 }
 
+impl Location {
+    /// Reads a definition or references result: `null`, a `Location`, or an array of
+    /// `Location` or `LocationLink`.
+    pub fn list_from_value(value: &Value) -> Vec<Location> {
+        let items = match value {
+            Value::Array(items) => items.iter().collect(),
+            Value::Null => Vec::new(),
+            item => vec![item],
+        };
+        items
+            .into_iter()
+            .filter_map(|item| {
+                if let Ok(location) = serde_json::from_value::<Location>(item.clone()) {
+                    return Some(location);
+                }
+                // LocationLink: point at the name rather than the whole item.
+                let uri = item.get("targetUri")?.as_str()?.to_string();
+                let range = item
+                    .get("targetSelectionRange")
+                    .or_else(|| item.get("targetRange"))?;
+                Some(Location {
+                    uri,
+                    range: serde_json::from_value(range.clone()).ok()?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    /// `text` with every edit applied. The edits must not overlap, as the protocol
+    /// requires; their ranges refer to the original text.
+    pub fn apply_all(text: &str, edits: &[TextEdit]) -> String {
+        let mut rope = Rope::from_str(text);
+        let char_idx = |rope: &Rope, position: &Position| {
+            if position.line as usize >= rope.len_lines() {
+                return rope.len_chars();
+            }
+            let cursor = position.to_cursor(rope);
+            (rope.line_to_char(cursor.line) + cursor.column).min(rope.len_chars())
+        };
+        let ranges: Vec<(usize, usize, &str)> = edits
+            .iter()
+            .map(|edit| {
+                let start = char_idx(&rope, &edit.range.start);
+                let end = char_idx(&rope, &edit.range.end).max(start);
+                (start, end, edit.new_text.as_str())
+            })
+            .collect();
+        // Back to front so earlier offsets stay valid. Inserts at the same position keep
+        // their listed order, so the later one goes in first.
+        let mut order: Vec<usize> = (0..ranges.len()).collect();
+        order.sort_by_key(|&idx| std::cmp::Reverse((ranges[idx].0, idx)));
+        for (start, end, new_text) in order.into_iter().map(|idx| ranges[idx]) {
+            rope.remove(start..end);
+            rope.insert(start, new_text);
+        }
+        String::from(rope)
+    }
+}
+
+/// Text edits to several documents, in the order the server listed them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceEdit {
+    pub changes: Vec<(String, Vec<TextEdit>)>,
+}
+
+impl WorkspaceEdit {
+    /// Reads either the `changes` map or `documentChanges`. Create, rename and delete
+    /// operations in `documentChanges` are skipped.
+    pub fn from_value(value: &Value) -> Self {
+        let mut changes = Vec::new();
+        if let Some(document_changes) = value.get("documentChanges").and_then(Value::as_array) {
+            for change in document_changes {
+                let Some(uri) = change
+                    .get("textDocument")
+                    .and_then(|document| document.get("uri"))
+                    .and_then(Value::as_str)
+                else {
+                    continue;
+                };
+                changes.push((uri.to_string(), Self::edits_from_value(change.get("edits"))));
+            }
+        } else if let Some(map) = value.get("changes").and_then(Value::as_object) {
+            for (uri, edits) in map {
+                changes.push((uri.clone(), Self::edits_from_value(Some(edits))));
+            }
+        }
+        changes.retain(|(_, edits)| !edits.is_empty());
+        Self { changes }
+    }
+
+    fn edits_from_value(value: Option<&Value>) -> Vec<TextEdit> {
+        value
+            .and_then(Value::as_array)
+            .map(|edits| {
+                edits
+                    .iter()
+                    .filter_map(|edit| serde_json::from_value(edit.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LspCommand {
+    pub title: String,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Vec<Value>>,
+}
+
+/// A quick fix or refactoring offered for a range. It carries an edit, a command that
+/// makes the server send the edit, or both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeAction {
+    pub title: String,
+    pub kind: Option<String>,
+    pub edit: Option<WorkspaceEdit>,
+    pub command: Option<LspCommand>,
+}
+
+impl CodeAction {
+    /// Reads a code action result, whose entries are either `CodeAction`s or bare `Command`s.
+    pub fn list_from_value(value: &Value) -> Vec<CodeAction> {
+        let Some(items) = value.as_array() else {
+            return Vec::new();
+        };
+        items
+            .iter()
+            .filter_map(|item| {
+                let title = item.get("title")?.as_str()?.to_string();
+                // A bare `Command` has a string `command`; a `CodeAction` nests one.
+                if item.get("command").is_some_and(Value::is_string) {
+                    return Some(CodeAction {
+                        title,
+                        kind: None,
+                        edit: None,
+                        command: serde_json::from_value(item.clone()).ok(),
+                    });
+                }
+                Some(CodeAction {
+                    title,
+                    kind: item.get("kind").and_then(Value::as_str).map(String::from),
+                    edit: item.get("edit").map(WorkspaceEdit::from_value),
+                    command: item
+                        .get("command")
+                        .and_then(|command| serde_json::from_value(command.clone()).ok()),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A `CallHierarchyItem` or `TypeHierarchyItem`. Both have the same shape and are sent
+/// back unchanged to ask for the next level.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HierarchyItem {
+    pub name: String,
+    pub kind: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<u32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: String,
+    pub range: Range,
+    pub selection_range: Range,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyDirection {
+    IncomingCalls,
+    OutgoingCalls,
+    Supertypes,
+    Subtypes,
+}
+
+impl HierarchyDirection {
+    pub fn is_call(self) -> bool {
+        matches!(
+            self,
+            HierarchyDirection::IncomingCalls | HierarchyDirection::OutgoingCalls
+        )
+    }
+
+    pub fn prepare_method(self) -> LspMethod {
+        if self.is_call() {
+            LspMethod::TextDocumentPrepareCallHierarchy
+        } else {
+            LspMethod::TextDocumentPrepareTypeHierarchy
+        }
+    }
+
+    pub fn method(self) -> LspMethod {
+        match self {
+            HierarchyDirection::IncomingCalls => LspMethod::CallHierarchyIncomingCalls,
+            HierarchyDirection::OutgoingCalls => LspMethod::CallHierarchyOutgoingCalls,
+            HierarchyDirection::Supertypes => LspMethod::TypeHierarchySupertypes,
+            HierarchyDirection::Subtypes => LspMethod::TypeHierarchySubtypes,
+        }
+    }
+
+    /// Reads one level of the hierarchy. Call results wrap each item in `from` or `to`.
+    pub fn items_from_value(self, value: &Value) -> Vec<HierarchyItem> {
+        let wrapper = match self {
+            HierarchyDirection::IncomingCalls => Some("from"),
+            HierarchyDirection::OutgoingCalls => Some("to"),
+            _ => None,
+        };
+        value
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        let item = match wrapper {
+                            Some(key) => item.get(key)?,
+                            None => item,
+                        };
+                        serde_json::from_value(item.clone()).ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
 // 新增诊断严重性枚举
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Sent as its number.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "u8", into = "u8")]
 pub enum DiagnosticSeverity {
     Error = 1,
     Warning = 2,
@@ -118,6 +450,34 @@ pub enum DiagnosticSeverity {
     Hint = 4,
 }
 
+impl TryFrom<u8> for DiagnosticSeverity {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, String> {
+        match value {
+            1 => Ok(DiagnosticSeverity::Error),
+            2 => Ok(DiagnosticSeverity::Warning),
+            3 => Ok(DiagnosticSeverity::Information),
+            4 => Ok(DiagnosticSeverity::Hint),
+            other => Err(format!("unknown diagnostic severity {}", other)),
+        }
+    }
+}
+
+impl From<DiagnosticSeverity> for u8 {
+    fn from(severity: DiagnosticSeverity) -> u8 {
+        severity as u8
+    }
+}
+
+/// Sent as its number in `workspace/didChangeWatchedFiles`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeType {
+    Created = 1,
+    Changed = 2,
+    Deleted = 3,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Diagnostic {
     pub range: Range,
@@ -127,8 +487,46 @@ pub struct Diagnostic {
     pub message: String,
 }
 
+impl Diagnostic {
+    /// Servers may leave the severity out; clients are expected to treat it as an error.
+    pub fn severity_or_error(&self) -> DiagnosticSeverity {
+        self.severity.unwrap_or(DiagnosticSeverity::Error)
+    }
+}
+
+/// The params of `textDocument/publishDiagnostics`.
+#[derive(Debug, Clone)]
+pub struct PublishDiagnostics {
+    pub uri: String,
+    pub version: Option<usize>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl PublishDiagnostics {
+    /// Diagnostics that fail to parse are dropped rather than the whole notification.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        Some(Self {
+            uri: value.get("uri")?.as_str()?.to_string(),
+            version: value
+                .get("version")
+                .and_then(Value::as_u64)
+                .map(|version| version as usize),
+            diagnostics: value
+                .get("diagnostics")
+                .and_then(Value::as_array)
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| serde_json::from_value(item.clone()).ok())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
 // 新增完成项类型枚举
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum CompletionItemKind {
     Text = 1,
     Method = 2,
@@ -157,12 +555,229 @@ pub enum CompletionItemKind {
     TypeParameter = 25,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl CompletionItemKind {
+    const ALL: [CompletionItemKind; 25] = [
+        CompletionItemKind::Text,
+        CompletionItemKind::Method,
+        CompletionItemKind::Function,
+        CompletionItemKind::Constructor,
+        CompletionItemKind::Field,
+        CompletionItemKind::Variable,
+        CompletionItemKind::Class,
+        CompletionItemKind::Interface,
+        CompletionItemKind::Module,
+        CompletionItemKind::Property,
+        CompletionItemKind::Unit,
+        CompletionItemKind::Value,
+        CompletionItemKind::Enum,
+        CompletionItemKind::Keyword,
+        CompletionItemKind::Snippet,
+        CompletionItemKind::Color,
+        CompletionItemKind::File,
+        CompletionItemKind::Reference,
+        CompletionItemKind::Folder,
+        CompletionItemKind::EnumMember,
+        CompletionItemKind::Constant,
+        CompletionItemKind::Struct,
+        CompletionItemKind::Event,
+        CompletionItemKind::Operator,
+        CompletionItemKind::TypeParameter,
+    ];
+
+    /// The kind the protocol sends as `number`.
+    pub fn from_number(number: u64) -> Option<Self> {
+        let index = usize::try_from(number).ok()?.checked_sub(1)?;
+        Self::ALL.get(index).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CompletionItem {
     pub label: String,
     pub kind: Option<CompletionItemKind>,
     pub detail: Option<String>,
     pub documentation: Option<String>,
+    pub sort_text: Option<String>,
+    pub filter_text: Option<String>,
+    pub insert_text: Option<String>,
+    /// `insertTextFormat` 2: `$1`, `${1:name}` and `$0` mark tab stops.
+    pub is_snippet: bool,
+    /// Replaces the completed word; an `InsertReplaceEdit` uses its insert range.
+    pub text_edit: Option<TextEdit>,
+    /// Edits elsewhere in the file, such as an import at the top.
+    pub additional_text_edits: Vec<TextEdit>,
+    /// The item as the server sent it, sent back for `completionItem/resolve`.
+    pub raw: Value,
+}
+
+impl CompletionItem {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let string = |key: &str| value.get(key).and_then(Value::as_str).map(String::from);
+        let text_edit = value.get("textEdit").and_then(|edit| {
+            let new_text = edit.get("newText")?.as_str()?.to_string();
+            let range = edit.get("range").or_else(|| edit.get("insert"))?;
+            Some(TextEdit {
+                range: serde_json::from_value(range.clone()).ok()?,
+                new_text,
+            })
+        });
+        Some(CompletionItem {
+            label: string("label")?,
+            kind: value
+                .get("kind")
+                .and_then(Value::as_u64)
+                .and_then(CompletionItemKind::from_number),
+            detail: string("detail"),
+            // Either a plain string or a `MarkupContent`.
+            documentation: value.get("documentation").and_then(|doc| {
+                doc.as_str()
+                    .or_else(|| doc.get("value").and_then(Value::as_str))
+                    .map(String::from)
+            }),
+            sort_text: string("sortText"),
+            filter_text: string("filterText"),
+            insert_text: string("insertText"),
+            is_snippet: value.get("insertTextFormat").and_then(Value::as_u64) == Some(2),
+            text_edit,
+            additional_text_edits: value
+                .get("additionalTextEdits")
+                .and_then(|edits| serde_json::from_value(edits.clone()).ok())
+                .unwrap_or_default(),
+            raw: value.clone(),
+        })
+    }
+
+    /// Reads a completion result: a `CompletionList` or a bare array of items.
+    pub fn list_from_value(value: &Value) -> Vec<CompletionItem> {
+        let items = value.get("items").unwrap_or(value);
+        items
+            .as_array()
+            .map(|items| items.iter().filter_map(Self::from_value).collect())
+            .unwrap_or_default()
+    }
+
+    /// The text matched against what the user typed.
+    pub fn filter_text(&self) -> &str {
+        self.filter_text.as_deref().unwrap_or(&self.label)
+    }
+
+    /// The edit that inserts the item. `word` is the range of the partly typed word, used
+    /// when the server sends no `textEdit`. Snippets are reduced to plain text; the
+    /// second value is where the caret goes, in chars from the start of the new text.
+    pub fn main_edit(&self, word: Range) -> (TextEdit, usize) {
+        let (range, text) = match &self.text_edit {
+            Some(edit) => (edit.range.clone(), edit.new_text.clone()),
+            None => (
+                word,
+                self.insert_text
+                    .clone()
+                    .unwrap_or_else(|| self.label.clone()),
+            ),
+        };
+        let (new_text, caret) = if self.is_snippet {
+            expand_snippet(&text)
+        } else {
+            let len = text.chars().count();
+            (text, len)
+        };
+        (TextEdit { range, new_text }, caret)
+    }
+}
+
+/// Replaces tab stops and placeholders with their default text. The caret goes to the
+/// first tab stop, or to `$0`, or to the end.
+fn expand_snippet(snippet: &str) -> (String, usize) {
+    let chars: Vec<char> = snippet.chars().collect();
+    let mut text = String::new();
+    let mut stops = Vec::new();
+    expand_snippet_part(&chars, 0, false, &mut text, &mut stops);
+    let len = text.chars().count();
+    let caret = stops
+        .iter()
+        .filter(|(number, _)| *number > 0)
+        .min_by_key(|(number, _)| *number)
+        .or_else(|| stops.iter().find(|(number, _)| *number == 0))
+        .map_or(len, |(_, at)| *at);
+    (text, caret)
+}
+
+/// Expands `chars[i..]` into `text` until an unmatched `}` when `nested`. Returns the
+/// index after the last char read.
+fn expand_snippet_part(
+    chars: &[char],
+    mut i: usize,
+    nested: bool,
+    text: &mut String,
+    stops: &mut Vec<(u32, usize)>,
+) -> usize {
+    let digits = |from: usize| {
+        let end = (from..chars.len())
+            .find(|&j| !chars[j].is_ascii_digit())
+            .unwrap_or(chars.len());
+        let number = chars[from..end]
+            .iter()
+            .collect::<String>()
+            .parse::<u32>()
+            .ok();
+        (number, end)
+    };
+    let name_end = |from: usize| {
+        (from..chars.len())
+            .find(|&j| !(chars[j].is_ascii_alphanumeric() || chars[j] == '_'))
+            .unwrap_or(chars.len())
+    };
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            '}' if nested => return i + 1,
+            '$' if chars.get(i + 1).is_some_and(char::is_ascii_digit) => {
+                let (number, end) = digits(i + 1);
+                stops.push((number.unwrap_or(0), text.chars().count()));
+                i = end;
+            }
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                let start = i + 2;
+                let (number, end) = digits(start);
+                let end = if number.is_some() {
+                    end
+                } else {
+                    name_end(start)
+                };
+                if let Some(number) = number {
+                    stops.push((number, text.chars().count()));
+                }
+                i = match chars.get(end) {
+                    Some(':') => expand_snippet_part(chars, end + 1, true, text, stops),
+                    Some('|') => {
+                        // A choice: keep the first option.
+                        let close = (end + 1..chars.len())
+                            .find(|&j| chars[j] == '|')
+                            .unwrap_or(chars.len());
+                        let option_end =
+                            (end + 1..close).find(|&j| chars[j] == ',').unwrap_or(close);
+                        text.extend(&chars[end + 1..option_end]);
+                        (close + 2).min(chars.len())
+                    }
+                    _ => (end + 1).min(chars.len()),
+                };
+            }
+            '$' if chars
+                .get(i + 1)
+                .is_some_and(|ch| ch.is_ascii_alphabetic() || *ch == '_') =>
+            {
+                // Variables such as `$TM_SELECTED_TEXT` are left empty.
+                i = name_end(i + 1);
+            }
+            ch => {
+                text.push(ch);
+                i += 1;
+            }
+        }
+    }
+    i
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -217,3 +832,266 @@ impl LspMessage {
         self.id.is_none() && self.method.is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_locations_and_location_links() {
+        let range = serde_json::json!({
+            "start": { "line": 3, "character": 4 },
+            "end": { "line": 3, "character": 8 }
+        });
+        let location = serde_json::json!({ "uri": "file:///a.rs", "range": range });
+        let link = serde_json::json!({
+            "targetUri": "file:///b.rs",
+            "targetRange": { "start": { "line": 0, "character": 0 }, "end": { "line": 9, "character": 1 } },
+            "targetSelectionRange": range
+        });
+
+        let expected_range: Range = serde_json::from_value(range).unwrap();
+        assert_eq!(
+            Location::list_from_value(&location),
+            vec![Location {
+                uri: "file:///a.rs".to_string(),
+                range: expected_range.clone(),
+            }]
+        );
+        let links = Location::list_from_value(&Value::Array(vec![link]));
+        assert_eq!(links[0].uri, "file:///b.rs");
+        assert_eq!(links[0].range, expected_range);
+        assert!(Location::list_from_value(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn reads_hierarchy_levels_and_round_trips_items() {
+        let item = serde_json::json!({
+            "name": "render",
+            "kind": 12,
+            "uri": "file:///a.rs",
+            "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 9, "character": 1 } },
+            "selectionRange": { "start": { "line": 1, "character": 3 }, "end": { "line": 1, "character": 9 } },
+            "data": { "id": 7 }
+        });
+        let incoming = serde_json::json!([{ "from": item.clone(), "fromRanges": [] }]);
+
+        let items = HierarchyDirection::IncomingCalls.items_from_value(&incoming);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "render");
+        assert_eq!(items[0].selection_range.start.character, 3);
+        assert_eq!(serde_json::to_value(&items[0]).unwrap(), item);
+
+        let subtypes = serde_json::json!([item]);
+        assert_eq!(
+            HierarchyDirection::Subtypes.items_from_value(&subtypes),
+            items
+        );
+        assert!(HierarchyDirection::OutgoingCalls
+            .items_from_value(&Value::Null)
+            .is_empty());
+    }
+
+    #[test]
+    fn applies_text_edits_back_to_front() {
+        let edit = |line, start, end, text: &str| TextEdit {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start,
+                },
+                end: Position {
+                    line,
+                    character: end,
+                },
+            },
+            new_text: text.to_string(),
+        };
+        let edits = vec![
+            edit(2, 1, 1, "\n"),
+            edit(2, 1, 1, "// end"),
+            edit(0, 2, 2, " "),
+            edit(1, 0, 4, "  "),
+            edit(1, 7, 9, "b"),
+        ];
+        assert_eq!(
+            TextEdit::apply_all("fn(){\n    \"😀\";\n}", &edits),
+            "fn (){\n  \"😀b\n}\n// end"
+        );
+    }
+
+    #[test]
+    fn converts_positions_through_utf16_columns() {
+        let rope = Rope::from_str("let s = \"😀\";\nx\n");
+        let after_emoji = Cursor::new(0, 10);
+
+        let position = Position::from_cursor(&rope, after_emoji);
+        assert_eq!(position.character, 11);
+        assert_eq!(position.to_cursor(&rope), after_emoji);
+
+        let range = Range::from_cursors(&rope, Cursor::new(0, 9), after_emoji);
+        assert_eq!((range.start.character, range.end.character), (9, 11));
+        assert_eq!(range.to_cursors(&rope), (Cursor::new(0, 9), after_emoji));
+    }
+
+    #[test]
+    fn reads_published_diagnostics_with_numeric_severity() {
+        let params = serde_json::json!({
+            "uri": "file:///a.rs",
+            "version": 4,
+            "diagnostics": [
+                {
+                    "range": { "start": { "line": 1, "character": 2 }, "end": { "line": 1, "character": 5 } },
+                    "severity": 2,
+                    "message": "unused variable"
+                },
+                {
+                    "range": { "start": { "line": 3, "character": 0 }, "end": { "line": 3, "character": 1 } },
+                    "message": "no severity"
+                },
+                { "message": "no range" }
+            ]
+        });
+
+        let published = PublishDiagnostics::from_value(&params).unwrap();
+        assert_eq!(published.uri, "file:///a.rs");
+        assert_eq!(published.version, Some(4));
+        assert_eq!(published.diagnostics.len(), 2);
+        assert_eq!(
+            published.diagnostics[0].severity,
+            Some(DiagnosticSeverity::Warning)
+        );
+        assert_eq!(
+            published.diagnostics[1].severity_or_error(),
+            DiagnosticSeverity::Error
+        );
+        assert_eq!(
+            serde_json::to_value(DiagnosticSeverity::Hint).unwrap(),
+            serde_json::json!(4)
+        );
+    }
+
+    #[test]
+    fn reads_workspace_edits_and_code_actions() {
+        let edit = serde_json::json!({
+            "range": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 7 } },
+            "newText": "renamed"
+        });
+        let document_changes = serde_json::json!({
+            "documentChanges": [
+                { "textDocument": { "uri": "file:///a.rs", "version": 3 }, "edits": [edit.clone()] },
+                { "kind": "create", "uri": "file:///new.rs" }
+            ]
+        });
+        let workspace_edit = WorkspaceEdit::from_value(&document_changes);
+        assert_eq!(workspace_edit.changes.len(), 1);
+        assert_eq!(workspace_edit.changes[0].0, "file:///a.rs");
+        assert_eq!(workspace_edit.changes[0].1[0].new_text, "renamed");
+        let changes = serde_json::json!({ "changes": { "file:///b.rs": [edit] } });
+        assert_eq!(
+            WorkspaceEdit::from_value(&changes).changes[0].0,
+            "file:///b.rs"
+        );
+
+        let actions = CodeAction::list_from_value(&serde_json::json!([
+            { "title": "Run fix", "command": "fix.run", "arguments": [1] },
+            { "title": "Rename", "kind": "refactor", "edit": changes }
+        ]));
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].command.as_ref().unwrap().command, "fix.run");
+        assert!(actions[0].edit.is_none());
+        assert_eq!(actions[1].kind.as_deref(), Some("refactor"));
+        assert!(!actions[1].edit.as_ref().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_completion_items_and_expands_snippets() {
+        let result = serde_json::json!({
+            "isIncomplete": false,
+            "items": [
+                {
+                    "label": "HashMap",
+                    "kind": 22,
+                    "documentation": { "kind": "markdown", "value": "A hash map." },
+                    "additionalTextEdits": [{
+                        "range": {
+                            "start": { "line": 0, "character": 0 },
+                            "end": { "line": 0, "character": 0 }
+                        },
+                        "newText": "use std::collections::HashMap;\n"
+                    }],
+                    "data": { "id": 7 }
+                },
+                {
+                    "label": "push",
+                    "kind": 2,
+                    "insertTextFormat": 2,
+                    "textEdit": {
+                        "newText": "push(${1:value})$0",
+                        "insert": {
+                            "start": { "line": 3, "character": 6 },
+                            "end": { "line": 3, "character": 8 }
+                        },
+                        "replace": {
+                            "start": { "line": 3, "character": 6 },
+                            "end": { "line": 3, "character": 10 }
+                        }
+                    }
+                },
+                { "kind": 1 }
+            ]
+        });
+        let items = CompletionItem::list_from_value(&result);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].kind, Some(CompletionItemKind::Struct));
+        assert_eq!(items[0].documentation.as_deref(), Some("A hash map."));
+        assert_eq!(items[0].additional_text_edits.len(), 1);
+        assert_eq!(items[0].raw["data"]["id"], 7);
+
+        let word = Range {
+            start: Position {
+                line: 1,
+                character: 0,
+            },
+            end: Position {
+                line: 1,
+                character: 4,
+            },
+        };
+        let (edit, caret) = items[0].main_edit(word.clone());
+        assert_eq!(
+            (edit.new_text.as_str(), edit.range, caret),
+            ("HashMap", word, 7)
+        );
+
+        let (edit, caret) = items[1].main_edit(Range {
+            start: Position {
+                line: 0,
+                character: 0,
+            },
+            end: Position {
+                line: 0,
+                character: 0,
+            },
+        });
+        assert_eq!(edit.new_text, "push(value)");
+        assert_eq!(
+            edit.range.end,
+            Position {
+                line: 3,
+                character: 8
+            }
+        );
+        assert_eq!(caret, 5);
+
+        assert_eq!(
+            expand_snippet("fn ${1:name}($2) {\n\t$0\n}"),
+            ("fn name() {\n\t\n}".to_string(), 3)
+        );
+        assert_eq!(
+            expand_snippet("${1|a,b|} \\$x $TM_FILENAME$0"),
+            ("a $x ".to_string(), 0)
+        );
+        assert_eq!(expand_snippet("done$0"), ("done".to_string(), 4));
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    Completion,
+    Hover,
+    Diagnostics,
+}
+
+/// Issued to a request that outlived the debounce delay, recording the version it ran against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GateTicket {
+    uri: String,
+    kind: RequestKind,
+    seq: u64,
+    version: usize,
+}
+
+impl GateTicket {
+    pub fn version(&self) -> usize {
+        self.version
+    }
+}
+
+/// Holds back requests while the document is being edited and throws away results
+/// that no longer match it.
+///
+/// Only the newest request of each kind per document survives; versions come from
+/// `TextModel::watch_version`.
+#[derive(Debug)]
+pub struct RequestGate {
+    /// Milliseconds, so the delay can change while requests are waiting.
+    delay_ms: AtomicU64,
+    next_seq: AtomicU64,
+    latest: Mutex<HashMap<(String, RequestKind), u64>>,
+}
+
+impl RequestGate {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay_ms: AtomicU64::new(delay.as_millis() as u64),
+            next_seq: AtomicU64::new(0),
+            latest: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms.load(Ordering::Relaxed))
+    }
+
+    /// Changes the delay for requests from now on, e.g. for a slower machine.
+    pub fn set_delay(&self, delay: Duration) {
+        self.delay_ms
+            .store(delay.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Waits until the document has gone `delay` without an edit. Returns `None` if a
+    /// newer request of the same kind arrived meanwhile or the document was closed.
+    pub async fn settle(
+        &self,
+        uri: &str,
+        kind: RequestKind,
+        versions: &mut watch::Receiver<usize>,
+    ) -> Option<GateTicket> {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        self.latest_seqs().insert((uri.to_string(), kind), seq);
+
+        versions.borrow_and_update();
+        let delay = self.delay();
+        loop {
+            match tokio::time::timeout(delay, versions.changed()).await {
+                Ok(Ok(())) if self.is_latest(uri, kind, seq) => continue,
+                Err(_) if self.is_latest(uri, kind, seq) => break,
+                _ => return None,
+            }
+        }
+
+        Some(GateTicket {
+            uri: uri.to_string(),
+            kind,
+            seq,
+            version: *versions.borrow(),
+        })
+    }
+
+    /// Whether a result produced for `ticket` still applies to the document at `current_version`.
+    pub fn accepts(&self, ticket: &GateTicket, current_version: usize) -> bool {
+        ticket.version == current_version && self.is_latest(&ticket.uri, ticket.kind, ticket.seq)
+    }
+
+    /// Debounces `request`, runs it and returns its result only if it is still current.
+    pub async fn run<T>(
+        &self,
+        uri: &str,
+        kind: RequestKind,
+        mut versions: watch::Receiver<usize>,
+        request: impl Future<Output = Result<T, std::io::Error>>,
+    ) -> Result<Option<T>, std::io::Error> {
+        let Some(ticket) = self.settle(uri, kind, &mut versions).await else {
+            return Ok(None);
+        };
+        let result = request.await?;
+        let current_version = *versions.borrow();
+        Ok(self.accepts(&ticket, current_version).then_some(result))
+    }
+
+    fn is_latest(&self, uri: &str, kind: RequestKind, seq: u64) -> bool {
+        self.latest_seqs().get(&(uri.to_string(), kind)) == Some(&seq)
+    }
+
+    fn latest_seqs(&self) -> std::sync::MutexGuard<'_, HashMap<(String, RequestKind), u64>> {
+        self.latest.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for RequestGate {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEBOUNCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn debounces_typing_and_drops_superseded_or_stale_results() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let gate = Arc::new(RequestGate::new(Duration::from_millis(40)));
+            let (versions, rx) = watch::channel(1usize);
+
+            let first = tokio::spawn({
+                let gate = gate.clone();
+                let rx = rx.clone();
+                async move {
+                    gate.run("file:///a.rs", RequestKind::Hover, rx, async {
+                        Ok("first")
+                    })
+                    .await
+                }
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let second = tokio::spawn({
+                let gate = gate.clone();
+                let rx = rx.clone();
+                async move {
+                    gate.run("file:///a.rs", RequestKind::Hover, rx, async {
+                        Ok("second")
+                    })
+                    .await
+                }
+            });
+            for version in 2..5 {
+                tokio::time::sleep(Duration::from_millis(15)).await;
+                versions.send(version).unwrap();
+            }
+
+            assert_eq!(first.await.unwrap().unwrap(), None);
+            assert_eq!(second.await.unwrap().unwrap(), Some("second"));
+
+            let mut rx = rx.clone();
+            let ticket = gate
+                .settle("file:///a.rs", RequestKind::Completion, &mut rx)
+                .await
+                .unwrap();
+            assert_eq!(ticket.version(), 4);
+            assert!(gate.accepts(&ticket, 4));
+            versions.send(5).unwrap();
+            assert!(!gate.accepts(&ticket, 5));
+
+            gate.set_delay(Duration::from_millis(600));
+            assert_eq!(gate.delay(), Duration::from_millis(600));
+        });
+    }
+}
@@ -0,0 +1,1418 @@
+use crate::protocol::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, Hover, Position, Range,
+    TextEdit,
+};
+use editor_core_text::{Cursor, DocumentTree, NodeKind, PathSegment, TextChange, TreeError};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+
+/// How many `$ref`s and nested `anyOf`s are followed, so cyclic schemas end.
+const MAX_SCHEMA_DEPTH: usize = 16;
+
+/// A bundled schema and the files it applies to. A pattern matches the last components
+/// of a path; `*` in a component matches any run of characters.
+struct SchemaAssociation {
+    patterns: &'static [&'static str],
+    source: &'static str,
+}
+
+/// Like the SchemaStore catalog editors use, for the config files Fusang knows.
+const ASSOCIATIONS: &[SchemaAssociation] = &[
+    SchemaAssociation {
+        patterns: &["package.json"],
+        source: include_str!("../schemas/package.json"),
+    },
+    SchemaAssociation {
+        patterns: &["Cargo.toml"],
+        source: include_str!("../schemas/cargo.json"),
+    },
+    SchemaAssociation {
+        patterns: &[".github/workflows/*.yml", ".github/workflows/*.yaml"],
+        source: include_str!("../schemas/github-workflow.json"),
+    },
+    SchemaAssociation {
+        patterns: &["fusang/config.toml"],
+        source: include_str!("../schemas/fusang-config.json"),
+    },
+];
+
+/// A JSON Schema (draft 7) for completion, hover and validation. `$ref`s within the
+/// schema, `anyOf`, `oneOf` and `allOf`, `type`, `enum`, `const`, `properties`,
+/// `additionalProperties`, `required` and `items` are understood; other keywords are
+/// ignored.
+#[derive(Debug)]
+pub struct JsonSchema {
+    root: Value,
+}
+
+impl JsonSchema {
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        Ok(Self {
+            root: serde_json::from_str(source)?,
+        })
+    }
+
+    /// The bundled schema for the file at `path`, if there is one.
+    pub fn for_path(path: &Path) -> Option<Arc<Self>> {
+        let components: Vec<String> = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let association = ASSOCIATIONS.iter().find(|association| {
+            association
+                .patterns
+                .iter()
+                .any(|pattern| matches_path(pattern, &components))
+        })?;
+        // The bundled schemas are checked by the tests.
+        Self::parse(association.source).ok().map(Arc::new)
+    }
+
+    pub fn title(&self) -> &str {
+        self.root["title"].as_str().unwrap_or("schema")
+    }
+
+    /// The schema `schema` stands for once its `$ref`s are followed.
+    fn resolve<'a>(&'a self, mut schema: &'a Value) -> &'a Value {
+        for _ in 0..MAX_SCHEMA_DEPTH {
+            let Some(target) = schema
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|reference| reference.strip_prefix('#'))
+                .and_then(|pointer| self.root.pointer(pointer))
+            else {
+                break;
+            };
+            schema = target;
+        }
+        schema
+    }
+
+    /// `schema`, what it refers to and the alternatives it combines, for looking up
+    /// keywords in any of them.
+    fn branches<'a>(&'a self, schema: &'a Value) -> Vec<&'a Value> {
+        let mut branches = Vec::new();
+        let mut pending = vec![(schema, 0)];
+        while let Some((schema, depth)) = pending.pop() {
+            if depth > MAX_SCHEMA_DEPTH {
+                continue;
+            }
+            branches.push(schema);
+            let resolved = self.resolve(schema);
+            if !std::ptr::eq(resolved, schema) {
+                branches.push(resolved);
+            }
+            for keyword in ["anyOf", "oneOf", "allOf"] {
+                if let Some(alternatives) = resolved.get(keyword).and_then(Value::as_array) {
+                    pending.extend(alternatives.iter().rev().map(|branch| (branch, depth + 1)));
+                }
+            }
+        }
+        branches
+    }
+
+    /// The schema of the value at `segment` inside a value described by `schema`.
+    fn child<'a>(&'a self, schema: &'a Value, segment: &PathSegment) -> Option<&'a Value> {
+        let branches = self.branches(schema);
+        match segment {
+            PathSegment::Key(key) => branches
+                .iter()
+                .find_map(|branch| branch.get("properties")?.get(key))
+                .or_else(|| {
+                    branches.iter().find_map(|branch| {
+                        branch
+                            .get("additionalProperties")
+                            .filter(|additional| additional.is_object())
+                    })
+                }),
+            PathSegment::Index(index) => {
+                branches
+                    .iter()
+                    .find_map(|branch| match branch.get("items")? {
+                        Value::Array(items) => items.get(*index),
+                        items => Some(items),
+                    })
+            }
+        }
+    }
+
+    fn at(&self, path: &[PathSegment]) -> Option<&Value> {
+        path.iter()
+            .try_fold(&self.root, |schema, segment| self.child(schema, segment))
+    }
+
+    /// The named properties of `schema` with their schemas, in the order written.
+    fn properties<'a>(&'a self, schema: &'a Value) -> Vec<(&'a str, &'a Value)> {
+        let mut properties: Vec<(&str, &Value)> = Vec::new();
+        for branch in self.branches(schema) {
+            for (key, property) in branch
+                .get("properties")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+            {
+                if !properties.iter().any(|(name, _)| name == key) {
+                    properties.push((key, property));
+                }
+            }
+        }
+        properties
+    }
+
+    fn description<'a>(&'a self, schema: &'a Value) -> Option<&'a str> {
+        self.branches(schema).into_iter().find_map(|branch| {
+            branch
+                .get("markdownDescription")
+                .or_else(|| branch.get("description"))
+                .and_then(Value::as_str)
+        })
+    }
+
+    fn types<'a>(&'a self, schema: &'a Value) -> Vec<&'a str> {
+        let mut types = Vec::new();
+        for branch in self.branches(schema) {
+            let names = match branch.get("type") {
+                Some(Value::String(name)) => vec![name.as_str()],
+                Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            for name in names {
+                if !types.contains(&name) {
+                    types.push(name);
+                }
+            }
+        }
+        types
+    }
+
+    fn allowed_values<'a>(&'a self, schema: &'a Value) -> Vec<&'a Value> {
+        let mut values = Vec::new();
+        for branch in self.branches(schema) {
+            let listed = branch.get("enum").and_then(Value::as_array);
+            for value in listed.into_iter().flatten().chain(branch.get("const")) {
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+            }
+        }
+        values
+    }
+
+    fn default_value<'a>(&'a self, schema: &'a Value) -> Option<&'a Value> {
+        self.branches(schema)
+            .into_iter()
+            .find_map(|branch| branch.get("default"))
+    }
+
+    /// Checks node `idx` of `document` against `schema`, adding what's wrong to `problems`.
+    fn validate(
+        &self,
+        document: &ParsedDocument,
+        idx: usize,
+        schema: &Value,
+        depth: usize,
+        problems: &mut Vec<Problem>,
+    ) {
+        if depth > MAX_SCHEMA_DEPTH {
+            return;
+        }
+        let schema = self.resolve(schema);
+        let node = &document.tree.nodes[idx];
+        let Some(keywords) = schema.as_object() else {
+            if *schema == Value::Bool(false) {
+                problems.push(Problem::new(node.start, "Value is not allowed here."));
+            }
+            return;
+        };
+
+        for branch in keywords
+            .get("allOf")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            self.validate(document, idx, branch, depth + 1, problems);
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            let Some(alternatives) = keywords.get(keyword).and_then(Value::as_array) else {
+                continue;
+            };
+            let mut closest: Option<Vec<Problem>> = None;
+            for branch in alternatives {
+                let mut found = Vec::new();
+                self.validate(document, idx, branch, depth + 1, &mut found);
+                if found.is_empty() {
+                    closest = None;
+                    break;
+                }
+                if closest
+                    .as_ref()
+                    .is_none_or(|closest| found.len() < closest.len())
+                {
+                    closest = Some(found);
+                }
+            }
+            problems.extend(closest.into_iter().flatten());
+        }
+
+        let (actual, value) = document.value(idx);
+        let expected = match keywords.get("type") {
+            Some(Value::String(name)) => vec![name.as_str()],
+            Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if let Some(actual) = actual {
+            let matches =
+                |name: &&str| *name == actual || (*name == "number" && actual == "integer");
+            if !expected.is_empty() && !expected.iter().any(matches) {
+                let expected: Vec<String> = expected
+                    .iter()
+                    .map(|name| format!("\"{}\"", name))
+                    .collect();
+                problems.push(Problem::new(
+                    node.start,
+                    format!("Incorrect type. Expected {}.", expected.join(" or ")),
+                ));
+                return;
+            }
+        }
+        if let Some(value) = value {
+            let listed = keywords.get("enum").and_then(Value::as_array);
+            let allowed: Vec<&Value> = listed
+                .into_iter()
+                .flatten()
+                .chain(keywords.get("const"))
+                .collect();
+            if !allowed.is_empty() && !allowed.contains(&&value) {
+                let allowed: Vec<String> = allowed.iter().map(|value| value.to_string()).collect();
+                problems.push(Problem::new(
+                    node.start,
+                    format!(
+                        "Value is not accepted. Valid values: {}.",
+                        allowed.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        match node.kind {
+            NodeKind::Object => {
+                let children = document.children(idx);
+                let required = keywords.get("required").and_then(Value::as_array);
+                for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                    if !children
+                        .iter()
+                        .any(|&child| document.key(child) == Some(name))
+                    {
+                        problems.push(Problem::new(
+                            node.start,
+                            format!("Missing property \"{}\".", name),
+                        ));
+                    }
+                }
+                let properties = keywords.get("properties");
+                let additional = keywords.get("additionalProperties");
+                for child in children {
+                    let Some(key) = document.key(child) else {
+                        continue;
+                    };
+                    match (
+                        properties.and_then(|properties| properties.get(key)),
+                        additional,
+                    ) {
+                        (Some(property), _) => {
+                            self.validate(document, child, property, depth + 1, problems)
+                        }
+                        (None, Some(Value::Bool(false))) => problems.push(Problem::new(
+                            document.tree.nodes[child].start,
+                            format!("Property {} is not allowed.", key),
+                        )),
+                        (None, Some(additional)) => {
+                            self.validate(document, child, additional, depth + 1, problems)
+                        }
+                        (None, None) => {}
+                    }
+                }
+            }
+            NodeKind::Array => {
+                for (index, child) in document.children(idx).into_iter().enumerate() {
+                    let items = match keywords.get("items") {
+                        Some(Value::Array(items)) => items.get(index),
+                        items => items,
+                    };
+                    if let Some(items) = items {
+                        self.validate(document, child, items, depth + 1, problems);
+                    }
+                }
+            }
+            NodeKind::Scalar => {}
+        }
+    }
+}
+
+/// Whether the last components of a path are `pattern`'s.
+fn matches_path(pattern: &str, components: &[String]) -> bool {
+    let parts: Vec<&str> = pattern.split('/').collect();
+    components.len() >= parts.len()
+        && parts
+            .iter()
+            .zip(&components[components.len() - parts.len()..])
+            .all(|(part, component)| match part.split_once('*') {
+                Some((prefix, suffix)) => {
+                    component.len() >= prefix.len() + suffix.len()
+                        && component.starts_with(prefix)
+                        && component.ends_with(suffix)
+                }
+                None => part == component,
+            })
+}
+
+/// Something wrong with a value, found by validation.
+#[derive(Debug)]
+struct Problem {
+    start: Cursor,
+    message: String,
+}
+
+impl Problem {
+    fn new(start: Cursor, message: impl Into<String>) -> Self {
+        Self {
+            start,
+            message: message.into(),
+        }
+    }
+}
+
+/// A document that parsed, with the language needed to read its scalars.
+struct ParsedDocument<'a> {
+    language: &'a str,
+    tree: &'a DocumentTree,
+}
+
+impl ParsedDocument<'_> {
+    fn children(&self, idx: usize) -> Vec<usize> {
+        (idx + 1..self.tree.nodes.len())
+            .filter(|&child| self.tree.nodes[child].parent == Some(idx))
+            .collect()
+    }
+
+    fn key(&self, idx: usize) -> Option<&str> {
+        match &self.tree.nodes[idx].segment {
+            Some(PathSegment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// The node with path `path`.
+    fn find(&self, path: &[PathSegment]) -> Option<usize> {
+        let mut idx = 0;
+        for segment in path {
+            idx = self
+                .children(idx)
+                .into_iter()
+                .find(|&child| self.tree.nodes[child].segment.as_ref() == Some(segment))?;
+        }
+        Some(idx)
+    }
+
+    /// The JSON type of node `idx` and, for scalars, its value. Either is `None` when the
+    /// text doesn't tell, e.g. for a YAML block scalar or a TOML date.
+    fn value(&self, idx: usize) -> (Option<&'static str>, Option<Value>) {
+        let node = &self.tree.nodes[idx];
+        match node.kind {
+            NodeKind::Object => return (Some("object"), None),
+            NodeKind::Array => return (Some("array"), None),
+            NodeKind::Scalar => {}
+        }
+        let raw = node.value.as_str();
+        if raw.starts_with('[') {
+            return (Some("array"), None);
+        }
+        if raw.starts_with('{') {
+            return (Some("object"), None);
+        }
+        let value = match self.language {
+            "yaml" => yaml_scalar(raw),
+            "toml" => toml_scalar(raw),
+            _ => serde_json::from_str(raw).ok(),
+        };
+        match value {
+            Some(value) => (Some(type_name(&value)), Some(value)),
+            None if raw.starts_with(['"', '\'', '|', '>']) => (Some("string"), None),
+            None => (None, None),
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn number(text: &str) -> Option<Value> {
+    if let Ok(integer) = text.parse::<i64>() {
+        return Some(integer.into());
+    }
+    let float = text.parse::<f64>().ok().filter(|float| float.is_finite())?;
+    serde_json::Number::from_f64(float).map(Value::Number)
+}
+
+/// A plain or quoted YAML scalar as JSON. Block scalars give `None`.
+fn yaml_scalar(raw: &str) -> Option<Value> {
+    match raw {
+        "" | "~" | "null" | "Null" | "NULL" => return Some(Value::Null),
+        "true" | "True" | "TRUE" => return Some(Value::Bool(true)),
+        "false" | "False" | "FALSE" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    if raw.starts_with('"') {
+        return serde_json::from_str(raw).ok();
+    }
+    if let Some(quoted) = raw.strip_prefix('\'') {
+        return Some(Value::String(quoted.strip_suffix('\'')?.replace("''", "'")));
+    }
+    if raw.starts_with(['|', '>']) {
+        return None;
+    }
+    Some(number(raw).unwrap_or_else(|| Value::String(raw.to_string())))
+}
+
+/// A single-line TOML string, boolean or number as JSON.
+fn toml_scalar(raw: &str) -> Option<Value> {
+    match raw {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    if raw.starts_with("\"\"\"") || raw.starts_with("'''") {
+        return None;
+    }
+    if raw.starts_with('"') {
+        return serde_json::from_str(raw).ok();
+    }
+    if let Some(literal) = raw.strip_prefix('\'') {
+        return Some(Value::String(literal.strip_suffix('\'')?.to_string()));
+    }
+    number(&raw.replace('_', ""))
+}
+
+/// What is being typed at the cursor, and where the typed text starts.
+#[derive(Debug, PartialEq)]
+enum Context {
+    /// A property of the object at `path`. `written` are keys already in the object
+    /// that the parsed tree may not know, as it fails to parse while a key is typed.
+    Key {
+        path: Vec<PathSegment>,
+        start: Cursor,
+        written: Vec<String>,
+    },
+    /// The value at `path`.
+    Value {
+        path: Vec<PathSegment>,
+        start: Cursor,
+    },
+    /// A TOML table header inside the table at `path`; `array` for `[[`.
+    TableHeader {
+        path: Vec<PathSegment>,
+        start: Cursor,
+        array: bool,
+    },
+}
+
+/// A config file with a bundled schema, kept in step with the editor's copy. Gives
+/// completion, hover and diagnostics from the schema, for when no language server does.
+#[derive(Debug)]
+pub struct SchemaDocument {
+    schema: Arc<JsonSchema>,
+    language: String,
+    rope: Rope,
+    tree: Result<DocumentTree, TreeError>,
+}
+
+impl SchemaDocument {
+    /// `None` when `language` isn't JSON, YAML or TOML.
+    pub fn new(schema: Arc<JsonSchema>, language: &str, text: &str) -> Option<Self> {
+        let tree = DocumentTree::parse(language, text)?;
+        Some(Self {
+            schema,
+            language: language.to_string(),
+            rope: Rope::from_str(text),
+            tree,
+        })
+    }
+
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.rope = Rope::from_str(text);
+        self.reparse();
+    }
+
+    /// Applies edits in the order they were made.
+    pub fn apply(&mut self, changes: &[TextChange]) {
+        for change in changes {
+            let offset = |cursor: Cursor| {
+                let line = cursor.line.min(self.rope.len_lines().saturating_sub(1));
+                (self.rope.line_to_char(line) + cursor.column).min(self.rope.len_chars())
+            };
+            let start = offset(change.start);
+            let end = offset(change.old_end).max(start);
+            self.rope.remove(start..end);
+            self.rope.insert(start, change.new_text());
+        }
+        self.reparse();
+    }
+
+    fn reparse(&mut self) {
+        let text = self.rope.to_string();
+        if let Some(tree) = DocumentTree::parse(&self.language, &text) {
+            self.tree = tree;
+        }
+    }
+
+    fn parsed(&self) -> Option<ParsedDocument<'_>> {
+        let tree = self.tree.as_ref().ok()?;
+        (!tree.nodes.is_empty()).then_some(ParsedDocument {
+            language: &self.language,
+            tree,
+        })
+    }
+
+    /// Syntax errors, and values the schema doesn't allow, as warnings.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let source = Some(self.schema.title().to_string());
+        if let Err(error) = &self.tree {
+            let start = self.clamp(Cursor::new(error.line, error.column));
+            return vec![Diagnostic {
+                range: Range::from_cursors(&self.rope, start, start),
+                severity: Some(DiagnosticSeverity::Error),
+                code: None,
+                source,
+                message: error.message.clone(),
+            }];
+        }
+        let Some(document) = self.parsed() else {
+            return Vec::new();
+        };
+        let mut problems = Vec::new();
+        self.schema
+            .validate(&document, 0, &self.schema.root, 0, &mut problems);
+        problems
+            .into_iter()
+            .map(|problem| Diagnostic {
+                range: Range::from_cursors(
+                    &self.rope,
+                    problem.start,
+                    self.line_end(problem.start.line),
+                ),
+                severity: Some(DiagnosticSeverity::Warning),
+                code: None,
+                source: source.clone(),
+                message: problem.message,
+            })
+            .collect()
+    }
+
+    /// The schema's description of the key or value at `position`.
+    pub fn hover(&self, position: Position) -> Option<Hover> {
+        let document = self.parsed()?;
+        let cursor = position.to_cursor(&self.rope);
+        let idx = document.tree.node_at(cursor)?;
+        let node = &document.tree.nodes[idx];
+        if node.start.line != cursor.line {
+            return None;
+        }
+        let path = path_of(document.tree, idx);
+        let schema = self.schema.at(&path)?;
+
+        let mut sections = Vec::new();
+        match &node.segment {
+            Some(PathSegment::Key(key)) => sections.push(format!("**{}**", key)),
+            _ => sections.push(format!("**{}**", self.schema.title())),
+        }
+        if let Some(description) = self.schema.description(schema) {
+            sections.push(description.to_string());
+        }
+        let types = self.schema.types(schema);
+        if !types.is_empty() {
+            sections.push(format!("Type: `{}`", types.join(" | ")));
+        }
+        let values = self.schema.allowed_values(schema);
+        if !values.is_empty() {
+            let values: Vec<String> = values.iter().map(|value| format!("`{}`", value)).collect();
+            sections.push(format!("Allowed values: {}", values.join(", ")));
+        }
+        if let Some(default) = self.schema.default_value(schema) {
+            sections.push(format!("Default: `{}`", default));
+        }
+        if sections.len() == 1 {
+            return None;
+        }
+        Some(Hover {
+            contents: json!({ "kind": "markdown", "value": sections.join("\n\n") }),
+            range: Some(Range::from_cursors(
+                &self.rope,
+                node.start,
+                self.line_end(node.start.line),
+            )),
+        })
+    }
+
+    /// Property names, table names or values the schema allows at `position`.
+    pub fn completions(&self, position: Position) -> Vec<CompletionItem> {
+        let cursor = position.to_cursor(&self.rope);
+        let Some(line) = self.rope.get_line(cursor.line) else {
+            return Vec::new();
+        };
+        let line: Vec<char> = line
+            .chars()
+            .take_while(|&ch| ch != '\n' && ch != '\r')
+            .collect();
+        let column = cursor.column.min(line.len());
+        let context = match self.language.as_str() {
+            "yaml" => self.yaml_context(cursor.line, &line[..column]),
+            "toml" => self.toml_context(cursor.line, &line[..column]),
+            _ => self.json_context(cursor),
+        };
+        let Some(context) = context else {
+            return Vec::new();
+        };
+
+        // The closing quote or brackets typed along with the opening ones go too.
+        let after = &line[column..];
+        let (start, items) = match context {
+            Context::Key {
+                path,
+                start,
+                written,
+            } => (start, self.key_completions(&path, start, written)),
+            Context::Value { path, start } => (start, self.value_completions(&path)),
+            Context::TableHeader { path, start, array } => {
+                (start, self.table_completions(&path, array))
+            }
+        };
+        let typed = &line[start.column.min(column)..column];
+        let closing = match (typed.first(), after.first()) {
+            (Some('"'), Some('"')) => 1,
+            (_, Some(']')) if self.language == "toml" => {
+                after.iter().take_while(|&&ch| ch == ']').count()
+            }
+            _ => 0,
+        };
+        let end = Cursor::new(cursor.line, column + closing);
+        items
+            .into_iter()
+            .map(
+                |(label, kind, detail, documentation, new_text)| CompletionItem {
+                    filter_text: Some(label.clone()),
+                    label,
+                    kind: Some(kind),
+                    detail,
+                    documentation,
+                    sort_text: None,
+                    insert_text: None,
+                    is_snippet: true,
+                    text_edit: Some(TextEdit {
+                        range: Range::from_cursors(&self.rope, start, end),
+                        new_text,
+                    }),
+                    additional_text_edits: Vec::new(),
+                    raw: Value::Null,
+                },
+            )
+            .collect()
+    }
+
+    fn key_completions(
+        &self,
+        path: &[PathSegment],
+        start: Cursor,
+        mut existing: Vec<String>,
+    ) -> Vec<CompletionEntry> {
+        let Some(schema) = self.schema.at(path) else {
+            return Vec::new();
+        };
+        if let Some(document) = self.parsed() {
+            if let Some(idx) = document.find(path) {
+                existing.extend(
+                    document
+                        .children(idx)
+                        .into_iter()
+                        .filter_map(|child| document.key(child).map(String::from)),
+                );
+            }
+        }
+        self.schema
+            .properties(schema)
+            .into_iter()
+            .filter(|(key, _)| !existing.iter().any(|name| name == key))
+            .map(|(key, property)| {
+                let types = self.schema.types(property);
+                let new_text = match self.language.as_str() {
+                    "yaml" => {
+                        let indent = " ".repeat(start.column + 2);
+                        match types.first() {
+                            Some(&"object") => format!("{}:\n{}$0", key, indent),
+                            Some(&"array") => format!("{}:\n{}- $0", key, indent),
+                            _ => format!("{}: $0", key),
+                        }
+                    }
+                    "toml" => format!("{} = {}", toml_key(key), value_skeleton(&types, true)),
+                    _ => format!(
+                        "{}: {}",
+                        Value::String(key.to_string()),
+                        value_skeleton(&types, false)
+                    ),
+                };
+                (
+                    key.to_string(),
+                    CompletionItemKind::Property,
+                    (!types.is_empty()).then(|| types.join(" | ")),
+                    self.schema.description(property).map(String::from),
+                    new_text,
+                )
+            })
+            .collect()
+    }
+
+    fn value_completions(&self, path: &[PathSegment]) -> Vec<CompletionEntry> {
+        let Some(schema) = self.schema.at(path) else {
+            return Vec::new();
+        };
+        let mut values: Vec<Value> = self
+            .schema
+            .allowed_values(schema)
+            .into_iter()
+            .cloned()
+            .collect();
+        if self.schema.types(schema).contains(&"boolean") {
+            values.extend([Value::Bool(true), Value::Bool(false)]);
+        }
+        let description = self.schema.description(schema).map(String::from);
+        values
+            .into_iter()
+            .map(|value| {
+                let label = match &value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                let new_text = match (&value, self.language.as_str()) {
+                    (Value::String(text), "yaml") if yaml_plain(text) => text.clone(),
+                    _ => value.to_string(),
+                };
+                (
+                    label,
+                    CompletionItemKind::EnumMember,
+                    None,
+                    description.clone(),
+                    new_text.replace('$', "\\$"),
+                )
+            })
+            .collect()
+    }
+
+    fn table_completions(&self, path: &[PathSegment], array: bool) -> Vec<CompletionEntry> {
+        let Some(schema) = self.schema.at(path) else {
+            return Vec::new();
+        };
+        let wanted = if array { "array" } else { "object" };
+        self.schema
+            .properties(schema)
+            .into_iter()
+            .filter(|(_, property)| self.schema.types(property).contains(&wanted))
+            .map(|(key, property)| {
+                let close = if array { "]]" } else { "]" };
+                (
+                    key.to_string(),
+                    CompletionItemKind::Module,
+                    None,
+                    self.schema.description(property).map(String::from),
+                    format!("{}{}", toml_key(key), close),
+                )
+            })
+            .collect()
+    }
+
+    /// Reads the JSON before `cursor` to find the object or array being typed in.
+    fn json_context(&self, cursor: Cursor) -> Option<Context> {
+        let end = self.rope.line_to_char(cursor.line) + cursor.column;
+        let mut frames: Vec<JsonFrame> = Vec::new();
+        let mut string_start: Option<usize> = None;
+        let mut last_string = String::new();
+        let mut chars = self.rope.chars().take(end).enumerate().peekable();
+        while let Some((idx, ch)) = chars.next() {
+            if string_start.is_some() {
+                match ch {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            last_string.push(escaped);
+                        }
+                    }
+                    '"' => string_start = None,
+                    '\n' => string_start = None,
+                    _ => last_string.push(ch),
+                }
+                continue;
+            }
+            match ch {
+                '"' => {
+                    string_start = Some(idx);
+                    last_string.clear();
+                }
+                '/' if chars.peek().is_some_and(|&(_, next)| next == '/') => {
+                    while chars.next_if(|&(_, next)| next != '\n').is_some() {}
+                }
+                '/' if chars.peek().is_some_and(|&(_, next)| next == '*') => {
+                    let mut previous = ' ';
+                    for (_, next) in chars.by_ref() {
+                        if previous == '*' && next == '/' {
+                            break;
+                        }
+                        previous = next;
+                    }
+                }
+                ':' => {
+                    if let Some(JsonFrame::Object {
+                        key,
+                        written,
+                        expect_key,
+                    }) = frames.last_mut()
+                    {
+                        let name = std::mem::take(&mut last_string);
+                        written.push(name.clone());
+                        *key = Some(name);
+                        *expect_key = false;
+                    }
+                }
+                ',' => match frames.last_mut() {
+                    Some(JsonFrame::Object {
+                        key, expect_key, ..
+                    }) => {
+                        *key = None;
+                        *expect_key = true;
+                    }
+                    Some(JsonFrame::Array { index }) => *index += 1,
+                    None => {}
+                },
+                '{' => frames.push(JsonFrame::Object {
+                    key: None,
+                    written: Vec::new(),
+                    expect_key: true,
+                }),
+                '[' => frames.push(JsonFrame::Array { index: 0 }),
+                '}' | ']' => {
+                    frames.pop();
+                }
+                _ => {}
+            }
+        }
+
+        let (last, parents) = frames.split_last()?;
+        let mut path: Vec<PathSegment> = parents.iter().filter_map(JsonFrame::segment).collect();
+        let start = match string_start {
+            Some(idx) => {
+                let line = self.rope.char_to_line(idx);
+                Cursor::new(line, idx - self.rope.line_to_char(line))
+            }
+            None => Cursor::new(cursor.line, word_start(&self.rope, cursor)),
+        };
+        match last {
+            JsonFrame::Object {
+                written,
+                expect_key: true,
+                ..
+            } => Some(Context::Key {
+                path,
+                start,
+                written: written.clone(),
+            }),
+            frame => {
+                path.push(frame.segment()?);
+                Some(Context::Value { path, start })
+            }
+        }
+    }
+
+    /// Uses indentation to find the mapping a YAML line belongs to.
+    fn yaml_context(&self, line_idx: usize, before: &[char]) -> Option<Context> {
+        let indent = before.iter().take_while(|&&ch| ch == ' ').count();
+        let mut column = indent;
+        let mut items = 0;
+        while before[column..].starts_with(&['-', ' ']) {
+            column += 2;
+            items += 1;
+        }
+        let rest: String = before[column..].iter().collect();
+        let owner_column = if items > 0 { indent } else { column };
+        let mut path = self.yaml_parent(line_idx, owner_column);
+        path.extend(std::iter::repeat_n(PathSegment::Index(0), items));
+
+        if let Some(colon) = rest.find(": ") {
+            let key = rest[..colon].trim().trim_matches(['"', '\'']).to_string();
+            let value = &rest[colon + 1..];
+            let typed = value.trim_start();
+            let start = before.len() - typed.chars().count();
+            path.push(PathSegment::Key(key));
+            return Some(Context::Value {
+                path,
+                start: Cursor::new(line_idx, start),
+            });
+        }
+        if rest
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '-' | '.'))
+        {
+            return Some(Context::Key {
+                path,
+                start: Cursor::new(line_idx, column),
+                written: Vec::new(),
+            });
+        }
+        None
+    }
+
+    /// The path of the entry a YAML line indented by `column` belongs to: the nearest
+    /// line above that is indented less.
+    fn yaml_parent(&self, line_idx: usize, column: usize) -> Vec<PathSegment> {
+        let Some(document) = self.parsed() else {
+            return Vec::new();
+        };
+        for above in (0..line_idx).rev() {
+            let line: String = self.rope.line(above).chars().collect();
+            let content = line.trim_start_matches(' ');
+            let trimmed = content.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let indent = line.len() - content.len();
+            let mut key_column = indent;
+            while line[key_column..].starts_with("- ") {
+                key_column += 2;
+            }
+            let owner = if key_column < column {
+                key_column
+            } else if indent < column {
+                indent
+            } else {
+                continue;
+            };
+            let start = Cursor::new(above, owner);
+            return match document
+                .tree
+                .nodes
+                .iter()
+                .rposition(|node| node.start == start)
+            {
+                Some(idx) => path_of(document.tree, idx),
+                None => Vec::new(),
+            };
+        }
+        Vec::new()
+    }
+
+    /// Uses the nearest table header above a TOML line to find its table.
+    fn toml_context(&self, line_idx: usize, before: &[char]) -> Option<Context> {
+        let text: String = before.iter().collect();
+        let content = text.trim_start();
+        let indent = before.len() - content.chars().count();
+        if let Some(header) = content.strip_prefix('[') {
+            let array = header.starts_with('[');
+            let name = header.strip_prefix('[').unwrap_or(header);
+            if name.contains(']') {
+                return None;
+            }
+            let (path, typed) = dotted_prefix(name)?;
+            let start = Cursor::new(line_idx, before.len() - typed.chars().count());
+            return Some(Context::TableHeader { path, start, array });
+        }
+
+        let mut path = self.toml_table(line_idx);
+        if let Some((keys, value)) = content.split_once('=') {
+            let (prefix, last) = dotted_prefix(keys.trim_end())?;
+            path.extend(prefix);
+            path.push(PathSegment::Key(last.to_string()));
+            let typed = value.trim_start();
+            let start = Cursor::new(line_idx, before.len() - typed.chars().count());
+            return Some(Context::Value { path, start });
+        }
+        let (prefix, typed) = dotted_prefix(content)?;
+        path.extend(prefix);
+        let start = Cursor::new(
+            line_idx,
+            indent + content.chars().count() - typed.chars().count(),
+        );
+        Some(Context::Key {
+            path,
+            start,
+            written: Vec::new(),
+        })
+    }
+
+    /// The table the TOML line `line_idx` is in, from the nearest header above it.
+    fn toml_table(&self, line_idx: usize) -> Vec<PathSegment> {
+        for above in (0..line_idx).rev() {
+            let line: String = self.rope.line(above).chars().collect();
+            let Some(header) = line.trim().strip_prefix('[') else {
+                continue;
+            };
+            let array = header.starts_with('[');
+            let name = header.strip_prefix('[').unwrap_or(header);
+            let Some((name, _)) = name.split_once(']') else {
+                continue;
+            };
+            let Some((mut path, last)) = dotted_prefix(name.trim()) else {
+                continue;
+            };
+            path.push(PathSegment::Key(last.to_string()));
+            if array {
+                path.push(PathSegment::Index(0));
+            }
+            return path;
+        }
+        Vec::new()
+    }
+
+    fn line_end(&self, line_idx: usize) -> Cursor {
+        let line = self
+            .rope
+            .line(line_idx.min(self.rope.len_lines().saturating_sub(1)));
+        let text: String = line.chars().collect();
+        Cursor::new(line_idx, text.trim_end().chars().count())
+    }
+
+    fn clamp(&self, cursor: Cursor) -> Cursor {
+        let line = cursor.line.min(self.rope.len_lines().saturating_sub(1));
+        Cursor::new(line, cursor.column.min(self.line_end(line).column))
+    }
+}
+
+/// Label, kind, detail, documentation and snippet of a completion.
+type CompletionEntry = (
+    String,
+    CompletionItemKind,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+/// An object or array the JSON before the cursor is inside.
+enum JsonFrame {
+    Object {
+        /// The key of the value being written.
+        key: Option<String>,
+        written: Vec<String>,
+        expect_key: bool,
+    },
+    Array {
+        index: usize,
+    },
+}
+
+impl JsonFrame {
+    fn segment(&self) -> Option<PathSegment> {
+        match self {
+            JsonFrame::Object { key, .. } => key.clone().map(PathSegment::Key),
+            JsonFrame::Array { index } => Some(PathSegment::Index(*index)),
+        }
+    }
+}
+
+fn path_of(tree: &DocumentTree, idx: usize) -> Vec<PathSegment> {
+    let mut path = Vec::new();
+    let mut node = tree.nodes.get(idx);
+    while let Some(current) = node {
+        path.extend(current.segment.clone());
+        node = current.parent.map(|parent| &tree.nodes[parent]);
+    }
+    path.reverse();
+    path
+}
+
+/// Where the word before `cursor` starts, as a column.
+fn word_start(rope: &Rope, cursor: Cursor) -> usize {
+    let line: Vec<char> = rope.line(cursor.line).chars().collect();
+    let column = cursor.column.min(line.len());
+    let word = line[..column]
+        .iter()
+        .rev()
+        .take_while(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '-'))
+        .count();
+    column - word
+}
+
+/// Splits dotted TOML keys being typed into the complete keys and the last, partial one.
+fn dotted_prefix(text: &str) -> Option<(Vec<PathSegment>, &str)> {
+    let mut keys: Vec<&str> = text.split('.').collect();
+    let last = keys.pop()?;
+    let mut path = Vec::new();
+    for key in keys {
+        let key = key.trim().trim_matches(['"', '\'']);
+        if key.is_empty() {
+            return None;
+        }
+        path.push(PathSegment::Key(key.to_string()));
+    }
+    let last = last.trim_start();
+    last.chars()
+        .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '-'))
+        .then_some((path, last))
+}
+
+/// `key`, quoted unless it's a bare TOML key.
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-'))
+    {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+/// Whether `text` reads back as the same string when written without quotes in YAML.
+fn yaml_plain(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with([
+            '-', '?', ':', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@',
+            '`', ' ',
+        ])
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.ends_with([':', ' '])
+        && matches!(yaml_scalar(text), Some(Value::String(_)))
+}
+
+/// A snippet for an empty value of one of `types`, with the caret inside.
+fn value_skeleton(types: &[&str], toml: bool) -> &'static str {
+    match types.first() {
+        Some(&"object") if toml => "{ $1 }",
+        Some(&"object") => "{$1}",
+        Some(&"array") => "[$1]",
+        Some(&"string") => "\"$1\"",
+        _ => "$1",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn document(path: &str, language: &str, text: &str) -> SchemaDocument {
+        let schema = JsonSchema::for_path(&PathBuf::from(path)).unwrap();
+        SchemaDocument::new(schema, language, text).unwrap()
+    }
+
+    fn at(text: &str) -> (String, Position) {
+        let offset = text.find('|').unwrap();
+        let text = text.replacen('|', "", 1);
+        let before = &text[..offset];
+        let line = before.matches('\n').count() as u32;
+        let character = before.rsplit('\n').next().unwrap().encode_utf16().count() as u32;
+        (text, Position { line, character })
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    fn messages(document: &SchemaDocument) -> Vec<String> {
+        document
+            .diagnostics()
+            .into_iter()
+            .map(|diagnostic| format!("{}: {}", diagnostic.range.start.line, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn bundled_schemas_parse_and_match_their_files() {
+        for association in ASSOCIATIONS {
+            assert!(JsonSchema::parse(association.source).is_ok());
+        }
+        let title = |path: &str| {
+            JsonSchema::for_path(Path::new(path)).map(|schema| schema.title().to_string())
+        };
+        assert_eq!(
+            title("/w/app/package.json").as_deref(),
+            Some("package.json")
+        );
+        assert_eq!(
+            title("/w/.github/workflows/ci.yml").as_deref(),
+            Some("GitHub workflow")
+        );
+        assert_eq!(
+            title("/home/u/.config/fusang/config.toml").as_deref(),
+            Some("Fusang 配置")
+        );
+        assert!(title("/w/.github/ci.yml").is_none());
+        assert!(title("/w/config.toml").is_none());
+    }
+
+    #[test]
+    fn completes_json_keys_and_values() {
+        let (text, position) = at("{\n  \"name\": \"demo\",\n  \"ty|\"\n}");
+        let document = document("/w/package.json", "json", &text);
+        let items = document.completions(position);
+        let item = items.iter().find(|item| item.label == "type").unwrap();
+        assert!(!labels(&items).contains(&"name"));
+        let edit = item.text_edit.as_ref().unwrap();
+        assert_eq!(edit.new_text, "\"type\": \"$1\"");
+        // From the opening quote to past the closing one.
+        assert_eq!(
+            (edit.range.start.character, edit.range.end.character),
+            (2, 6)
+        );
+
+        let (text, position) = at("{\n  \"type\": \"|\"\n}");
+        let document = super::tests::document("/w/package.json", "json", &text);
+        let items = document.completions(position);
+        assert_eq!(labels(&items), ["commonjs", "module"]);
+        assert_eq!(
+            items[0].text_edit.as_ref().unwrap().new_text,
+            "\"commonjs\""
+        );
+
+        let (text, position) = at("{\"scripts\": {\"b|");
+        let document = super::tests::document("/w/package.json", "json", &text);
+        assert!(labels(&document.completions(position)).contains(&"build"));
+    }
+
+    #[test]
+    fn completes_yaml_by_indentation() {
+        let (text, position) =
+            at("on: push\njobs:\n  build:\n    runs-on: ubuntu-latest\n    st|\n");
+        let document = document("/w/.github/workflows/ci.yml", "yaml", &text);
+        let items = document.completions(position);
+        let steps = items.iter().find(|item| item.label == "steps").unwrap();
+        assert_eq!(
+            steps.text_edit.as_ref().unwrap().new_text,
+            "steps:\n      - $0"
+        );
+        assert!(!labels(&items).contains(&"runs-on"));
+
+        let (text, position) = at("jobs:\n  build:\n    steps:\n      - name: x\n        sh|\n");
+        let document = super::tests::document("/w/.github/workflows/ci.yml", "yaml", &text);
+        assert!(labels(&document.completions(position)).contains(&"shell"));
+
+        let (text, position) = at("permissions:\n  contents: |\n");
+        let document = super::tests::document("/w/.github/workflows/ci.yml", "yaml", &text);
+        assert_eq!(
+            labels(&document.completions(position)),
+            ["read", "write", "none"]
+        );
+    }
+
+    #[test]
+    fn completes_toml_tables_keys_and_values() {
+        let (text, position) = at("[package]\nname = \"a\"\ned|\n");
+        let document = document("/w/Cargo.toml", "toml", &text);
+        let items = document.completions(position);
+        let edition = items.iter().find(|item| item.label == "edition").unwrap();
+        assert!(!labels(&items).contains(&"name"));
+        assert_eq!(
+            edition.text_edit.as_ref().unwrap().new_text,
+            "edition = \"$1\""
+        );
+
+        let (text, position) = at("[package]\nedition = \"|\"\n");
+        let document = super::tests::document("/w/Cargo.toml", "toml", &text);
+        assert_eq!(
+            labels(&document.completions(position)),
+            ["2015", "2018", "2021", "2024"]
+        );
+
+        let (text, position) = at("[dev-|]\n");
+        let document = super::tests::document("/w/Cargo.toml", "toml", &text);
+        let items = document.completions(position);
+        let item = items
+            .iter()
+            .find(|item| item.label == "dev-dependencies")
+            .unwrap();
+        let edit = item.text_edit.as_ref().unwrap();
+        assert_eq!(edit.new_text, "dev-dependencies]");
+        assert_eq!(
+            (edit.range.start.character, edit.range.end.character),
+            (1, 6)
+        );
+        assert!(!labels(&items).contains(&"bin"));
+    }
+
+    #[test]
+    fn validates_against_the_schema() {
+        let text = "[package]\nname = \"a\"\nedition = \"2020\"\nauthor = \"me\"\n\n[dependencies]\nserde = { version = \"1\" }\ntokio = 1\n";
+        assert_eq!(
+            messages(&document("/w/Cargo.toml", "toml", text)),
+            [
+                "2: Value is not accepted. Valid values: \"2015\", \"2018\", \"2021\", \"2024\".",
+                "3: Property author is not allowed.",
+                "7: Incorrect type. Expected \"string\".",
+            ]
+        );
+
+        let text = "name: CI\njobs:\n  build:\n    runs-on: ubuntu-latest\n    steps:\n      - uses: actions/checkout@v4\n        colour: red\n";
+        assert_eq!(
+            messages(&document("/w/.github/workflows/ci.yaml", "yaml", text)),
+            [
+                "0: Missing property \"on\".",
+                "6: Property colour is not allowed.",
+            ]
+        );
+
+        let document = document("/w/package.json", "json", "{\n  \"private\": \"yes\",\n}");
+        assert_eq!(
+            messages(&document),
+            ["1: Incorrect type. Expected \"boolean\"."]
+        );
+        let mut document = document;
+        document.set_text("{\n  \"private\": true\n");
+        assert_eq!(
+            document.diagnostics()[0].severity,
+            Some(DiagnosticSeverity::Error)
+        );
+    }
+
+    #[test]
+    fn the_default_config_matches_its_schema() {
+        let text = toml::to_string_pretty(&editor_infra::config::Config::default()).unwrap();
+        let document = document("/c/fusang/config.toml", "toml", &text);
+        assert_eq!(messages(&document), Vec::<String>::new());
+    }
+
+    #[test]
+    fn hover_shows_the_description() {
+        let (text, position) = at("[package]\ned|ition = \"2021\"\n");
+        let document = document("/w/Cargo.toml", "toml", &text);
+        let hover = document.hover(position).unwrap();
+        let value = hover.contents["value"].as_str().unwrap();
+        assert!(value.starts_with("**edition**\n\nThe Rust edition"));
+        assert!(value.contains("Allowed values: `\"2015\"`"));
+    }
+
+    #[test]
+    fn follows_edits() {
+        let mut document = document("/w/package.json", "json", "{\"private\": true}");
+        let model = editor_core_text::TextModel::from_str("{\"private\": true}");
+        let mut changes = model.subscribe();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            model.replace(12, 4, "1").await;
+        });
+        document.apply(&[changes.try_recv().unwrap()]);
+        assert_eq!(
+            messages(&document),
+            ["0: Incorrect type. Expected \"boolean\"."]
+        );
+    }
+}
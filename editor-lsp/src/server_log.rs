@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// The most recent stderr lines of one language server, mirrored to a log file.
+#[derive(Debug, Clone)]
+pub struct ServerLog {
+    path: PathBuf,
+    capacity: usize,
+    state: Arc<Mutex<LogState>>,
+}
+
+#[derive(Debug)]
+struct LogState {
+    lines: VecDeque<String>,
+    file: Option<std::fs::File>,
+}
+
+impl ServerLog {
+    /// Starts a fresh log at `path`. Lines are still kept in memory if the file can't be written.
+    pub fn new(path: impl Into<PathBuf>, capacity: usize) -> Self {
+        let path = path.into();
+        let file = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::File::create(&path))
+            .map_err(|e| log::warn!("Cannot write server log {}: {}", path.display(), e))
+            .ok();
+
+        Self {
+            path,
+            capacity: capacity.max(1),
+            state: Arc::new(Mutex::new(LogState {
+                lines: VecDeque::new(),
+                file,
+            })),
+        }
+    }
+
+    /// The log for `command` in [`ServerLog::logs_dir`].
+    pub fn for_server(command: &str) -> Self {
+        let name = Path::new(command)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| command.to_string());
+        Self::new(
+            Self::logs_dir().join(format!("{}.log", name)),
+            DEFAULT_CAPACITY,
+        )
+    }
+
+    pub fn logs_dir() -> PathBuf {
+        editor_infra::paths::data_dir().join("lsp-logs")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn push(&self, line: &str) {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = &mut state.file {
+            let _ = writeln!(file, "{}", line);
+        }
+        if state.lines.len() == self.capacity {
+            state.lines.pop_front();
+        }
+        state.lines.push_back(line.to_string());
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.lines.iter().cloned().collect()
+    }
+
+    /// The last `count` lines, oldest first.
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = state.lines.len().saturating_sub(count);
+        state.lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_recent_lines_and_mirrors_to_file() {
+        let path = std::env::temp_dir()
+            .join(format!("fusang-lsp-log-{}", uuid::Uuid::new_v4()))
+            .join("server.log");
+        let log = ServerLog::new(&path, 2);
+        log.push("starting\n");
+        log.push("warning: slow");
+        log.push("error: crashed\r\n");
+
+        assert_eq!(log.lines(), vec!["warning: slow", "error: crashed"]);
+        assert_eq!(log.tail(1), vec!["error: crashed"]);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "starting\nwarning: slow\nerror: crashed\n"
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,14 +1,84 @@
-use super::client::LspClient;
-use super::protocol::{Diagnostic, Position};
+use super::advisories::{self, AdvisoryChecker, PackageReport};
+use super::cargo::{self, CrateVersionHint, CrateVersions};
+use super::client::{LspClient, ServerRequest};
+use super::installer::resolve_command;
+use super::protocol::{
+    CodeAction, Diagnostic, FileChangeType, HierarchyDirection, HierarchyItem, Location,
+    LspCommand, LspMethod, LspNotification, Position, PublishDiagnostics, Range, TextEdit,
+    WorkspaceEdit,
+};
+use super::request_gate::RequestGate;
+use super::schema::{JsonSchema, SchemaDocument};
+use super::server_log::ServerLog;
+use super::uri::uri_to_path;
 use editor_infra::config::LSPServerConfig;
+use editor_infra::telemetry::Metrics;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+
+const CRASH_TAIL_LINES: usize = 10;
+/// How long an on-type request waits for the typed character to reach the server.
+const ON_TYPE_SYNC_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long a manifest has to stay unchanged before its dependencies are checked.
+const ADVISORY_CHECK_DELAY: Duration = Duration::from_secs(1);
+/// How long a file rename waits for the server's edits before going ahead without them.
+const WILL_RENAME_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A server that exited without being shut down.
+#[derive(Debug, Clone)]
+pub struct ServerCrash {
+    pub language: String,
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub log_path: PathBuf,
+    /// The last stderr lines before the exit, oldest first.
+    pub last_lines: Vec<String>,
+}
+
+/// A server asking to change files, e.g. after a code action command ran. The server
+/// waits until [`ApplyEditRequest::respond`] is called.
+#[derive(Debug, Clone)]
+pub struct ApplyEditRequest {
+    pub language: String,
+    pub label: Option<String>,
+    pub edit: WorkspaceEdit,
+    request: ServerRequest,
+}
+
+impl ApplyEditRequest {
+    pub async fn respond(&self, applied: bool) -> Result<(), std::io::Error> {
+        self.request
+            .respond(serde_json::json!({ "applied": applied }))
+            .await
+    }
+}
 
 #[derive(Debug)]
 pub struct LspServerManager {
     servers: Arc<RwLock<HashMap<String, Arc<Mutex<LspClient>>>>>,
     diagnostics: Arc<RwLock<HashMap<String, Vec<Diagnostic>>>>,
+    logs: Arc<RwLock<HashMap<String, ServerLog>>>,
+    crashes: broadcast::Sender<ServerCrash>,
+    apply_edits: broadcast::Sender<ApplyEditRequest>,
+    /// Uris whose diagnostics were replaced.
+    diagnostics_changed: broadcast::Sender<String>,
+    gate: RequestGate,
+    /// The latest version sent to the servers for each open document.
+    document_versions: Arc<RwLock<HashMap<String, usize>>>,
+    versions_sent: Arc<Notify>,
+    /// Open config files with a bundled schema, which answers for them while no server
+    /// for their language runs.
+    schema_documents: Arc<RwLock<HashMap<String, SchemaDocument>>>,
+    crate_versions: CrateVersions,
+    advisories: AdvisoryChecker,
+    /// The last advisory check of each open Cargo or npm manifest.
+    package_reports: Arc<RwLock<HashMap<String, Vec<PackageReport>>>>,
+    /// Bumped on every change of a manifest, so only the check after the last one runs.
+    advisory_checks: Arc<RwLock<HashMap<String, u64>>>,
+    metrics: Option<Metrics>,
 }
 
 impl LspServerManager {
@@ -16,29 +86,217 @@ impl LspServerManager {
         Self {
             servers: Arc::new(RwLock::new(HashMap::new())),
             diagnostics: Arc::new(RwLock::new(HashMap::new())),
+            logs: Arc::new(RwLock::new(HashMap::new())),
+            crashes: broadcast::channel(16).0,
+            apply_edits: broadcast::channel(16).0,
+            diagnostics_changed: broadcast::channel(64).0,
+            gate: RequestGate::default(),
+            document_versions: Arc::new(RwLock::new(HashMap::new())),
+            versions_sent: Arc::new(Notify::new()),
+            schema_documents: Arc::new(RwLock::new(HashMap::new())),
+            crate_versions: CrateVersions::new(),
+            advisories: AdvisoryChecker::new(),
+            package_reports: Arc::new(RwLock::new(HashMap::new())),
+            advisory_checks: Arc::new(RwLock::new(HashMap::new())),
+            metrics: None,
         }
     }
 
+    /// Record the duration of every request to the servers started from now on.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Shared by every caller so typing in one view also holds back requests from another.
+    pub fn request_gate(&self) -> &RequestGate {
+        &self.gate
+    }
+
+    pub fn subscribe_crashes(&self) -> broadcast::Receiver<ServerCrash> {
+        self.crashes.subscribe()
+    }
+
+    /// `workspace/applyEdit` requests from every server. Each must be answered once.
+    pub fn subscribe_apply_edits(&self) -> broadcast::Receiver<ApplyEditRequest> {
+        self.apply_edits.subscribe()
+    }
+
+    /// The uri of each document whose diagnostics changed; read them with
+    /// [`Self::get_diagnostics`].
+    pub fn subscribe_diagnostics(&self) -> broadcast::Receiver<String> {
+        self.diagnostics_changed.subscribe()
+    }
+
+    /// Stderr logs by language, including servers that have since exited.
+    pub async fn server_logs(&self) -> Vec<(String, ServerLog)> {
+        let logs = self.logs.read().await;
+        let mut logs: Vec<(String, ServerLog)> = logs
+            .iter()
+            .map(|(language, log)| (language.clone(), log.clone()))
+            .collect();
+        logs.sort_by(|a, b| a.0.cmp(&b.0));
+        logs
+    }
+
     pub async fn start_server_for_language(
         &self,
         config: &LSPServerConfig,
         workspace_root: &str,
     ) -> Result<(), std::io::Error> {
+        let command = resolve_command(&config.command).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} not found on PATH", config.command),
+            )
+        })?;
+
         let client = Arc::new(Mutex::new(LspClient::new()));
-        {
+        let exit_signal = {
             let mut client_guard = client.lock().await;
+            if let Some(metrics) = &self.metrics {
+                client_guard.set_metrics(metrics.clone());
+            }
             client_guard
-                .start_server(&config.command, &config.args)
+                .start_server(&command.to_string_lossy(), &config.args)
                 .await?;
+            if let Some(log) = client_guard.server_log() {
+                let mut logs = self.logs.write().await;
+                logs.insert(config.language.clone(), log.clone());
+            }
+            let exit_signal = client_guard.take_exit_signal();
+            if let Some(requests) = client_guard.take_server_requests() {
+                self.forward_server_requests(&config.language, requests);
+            }
+            if let Some(notifications) = client_guard.take_server_notifications() {
+                self.forward_server_notifications(notifications);
+            }
             client_guard.initialize(workspace_root).await?;
-        }
+            exit_signal
+        };
 
         let mut servers = self.servers.write().await;
-        servers.insert(config.language.clone(), client);
+        servers.insert(config.language.clone(), client.clone());
+        drop(servers);
+
+        if let Some(exit_signal) = exit_signal {
+            self.watch_for_crash(config, client, exit_signal);
+        }
 
         Ok(())
     }
 
+    fn forward_server_requests(
+        &self,
+        language: &str,
+        mut requests: tokio::sync::mpsc::UnboundedReceiver<ServerRequest>,
+    ) {
+        let apply_edits = self.apply_edits.clone();
+        let language = language.to_string();
+
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                if request.method != LspMethod::WorkspaceApplyEdit {
+                    continue;
+                }
+                let edit = ApplyEditRequest {
+                    language: language.clone(),
+                    label: request
+                        .params
+                        .get("label")
+                        .and_then(|label| label.as_str())
+                        .map(String::from),
+                    edit: WorkspaceEdit::from_value(
+                        request.params.get("edit").unwrap_or(&request.params),
+                    ),
+                    request,
+                };
+                // Nobody is listening to preview it, so it can't be applied.
+                if let Err(broadcast::error::SendError(edit)) = apply_edits.send(edit) {
+                    let _ = edit.respond(false).await;
+                }
+            }
+        });
+    }
+
+    fn forward_server_notifications(
+        &self,
+        mut notifications: tokio::sync::mpsc::UnboundedReceiver<LspNotification>,
+    ) {
+        let store = self.diagnostics_store();
+
+        tokio::spawn(async move {
+            while let Some(notification) = notifications.recv().await {
+                if notification.method != LspMethod::TextDocumentPublishDiagnostics {
+                    continue;
+                }
+                if let Some(published) = PublishDiagnostics::from_value(&notification.params) {
+                    store
+                        .publish(published.uri, published.version, published.diagnostics)
+                        .await;
+                }
+            }
+        });
+    }
+
+    fn diagnostics_store(&self) -> DiagnosticsStore {
+        DiagnosticsStore {
+            diagnostics: self.diagnostics.clone(),
+            document_versions: self.document_versions.clone(),
+            changed: self.diagnostics_changed.clone(),
+        }
+    }
+
+    fn watch_for_crash(
+        &self,
+        config: &LSPServerConfig,
+        client: Arc<Mutex<LspClient>>,
+        exit_signal: tokio::sync::oneshot::Receiver<()>,
+    ) {
+        let servers = self.servers.clone();
+        let logs = self.logs.clone();
+        let crashes = self.crashes.clone();
+        let language = config.language.clone();
+        let command = config.command.clone();
+
+        tokio::spawn(async move {
+            let _ = exit_signal.await;
+
+            // Servers removed by shutdown or replaced by a restart exited on purpose.
+            {
+                let mut servers = servers.write().await;
+                let current = servers
+                    .get(&language)
+                    .is_some_and(|running| Arc::ptr_eq(running, &client));
+                if !current {
+                    return;
+                }
+                servers.remove(&language);
+            }
+
+            // A request may still hold the client waiting on the dead server, so don't block.
+            let exit_code = client
+                .try_lock()
+                .ok()
+                .and_then(|mut client| client.exit_status())
+                .and_then(|status| status.code());
+            let log = logs.read().await.get(&language).cloned();
+            let crash = ServerCrash {
+                language,
+                command,
+                exit_code,
+                log_path: log
+                    .as_ref()
+                    .map(|log| log.path().to_path_buf())
+                    .unwrap_or_default(),
+                last_lines: log
+                    .map(|log| log.tail(CRASH_TAIL_LINES))
+                    .unwrap_or_default(),
+            };
+            let _ = crashes.send(crash);
+        });
+    }
+
     pub async fn get_server(&self, language: &str) -> Option<Arc<Mutex<LspClient>>> {
         let servers = self.servers.read().await;
         servers.get(language).cloned()
@@ -54,7 +312,24 @@ impl LspServerManager {
             let mut client = client.lock().await;
             client.request_completion(uri, position).await
         } else {
-            Ok(Vec::new())
+            let documents = self.schema_documents.read().await;
+            Ok(documents
+                .get(uri)
+                .map(|document| document.completions(position))
+                .unwrap_or_default())
+        }
+    }
+
+    /// Resolves `item` before it is inserted, so lazily computed edits such as
+    /// auto-imports are included.
+    pub async fn resolve_completion(
+        &self,
+        language: &str,
+        item: &super::protocol::CompletionItem,
+    ) -> Result<super::protocol::CompletionItem, std::io::Error> {
+        match self.get_server(language).await {
+            Some(client) => client.lock().await.resolve_completion(item).await,
+            None => Ok(item.clone()),
         }
     }
 
@@ -64,12 +339,322 @@ impl LspServerManager {
         uri: &str,
         position: Position,
     ) -> Result<Option<super::protocol::Hover>, std::io::Error> {
+        if let Some(hover) = self.dependency_hover(uri, position.line).await {
+            return Ok(Some(hover));
+        }
         if let Some(client) = self.get_server(language).await {
             let mut client = client.lock().await;
             client.request_hover(uri, position).await
         } else {
-            Ok(None)
+            let documents = self.schema_documents.read().await;
+            Ok(documents
+                .get(uri)
+                .and_then(|document| document.hover(position)))
+        }
+    }
+
+    pub async fn request_definition(
+        &self,
+        language: &str,
+        uri: &str,
+        position: Position,
+    ) -> Result<Vec<Location>, std::io::Error> {
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.request_definition(uri, position).await
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    pub async fn request_references(
+        &self,
+        language: &str,
+        uri: &str,
+        position: Position,
+    ) -> Result<Vec<Location>, std::io::Error> {
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.request_references(uri, position, true).await
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    pub async fn prepare_hierarchy(
+        &self,
+        language: &str,
+        direction: HierarchyDirection,
+        uri: &str,
+        position: Position,
+    ) -> Result<Vec<HierarchyItem>, std::io::Error> {
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.prepare_hierarchy(direction, uri, position).await
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    pub async fn request_hierarchy(
+        &self,
+        language: &str,
+        direction: HierarchyDirection,
+        item: &HierarchyItem,
+    ) -> Result<Vec<HierarchyItem>, std::io::Error> {
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.request_hierarchy(direction, item).await
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    pub async fn request_code_actions(
+        &self,
+        language: &str,
+        uri: &str,
+        range: Range,
+    ) -> Result<Vec<CodeAction>, std::io::Error> {
+        let mut actions = if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.request_code_actions(uri, range.clone()).await?
+        } else {
+            Vec::new()
+        };
+        if let Some(rope) = self.manifest_rope(uri).await {
+            let updates = self.crate_versions.update_actions(&rope, uri, &range).await;
+            actions.extend(updates);
+        }
+        if let Some(rope) = self.schema_rope(uri).await {
+            let (first, last) = (range.start.line as usize, range.end.line as usize);
+            let reports = self.package_reports.read().await;
+            actions.extend(
+                reports
+                    .get(uri)
+                    .into_iter()
+                    .flatten()
+                    .filter(|report| (first..=last).contains(&report.package.line))
+                    .filter_map(|report| report.fix_action(&rope, uri)),
+            );
+        }
+        Ok(actions)
+    }
+
+    /// The text of `uri` if it is an open Cargo manifest.
+    async fn manifest_rope(&self, uri: &str) -> Option<ropey::Rope> {
+        if !uri.ends_with("/Cargo.toml") {
+            return None;
+        }
+        self.schema_rope(uri).await
+    }
+
+    async fn schema_rope(&self, uri: &str) -> Option<ropey::Rope> {
+        let documents = self.schema_documents.read().await;
+        documents.get(uri).map(|document| document.rope().clone())
+    }
+
+    /// Links to the docs of the dependency on the hovered line of a Cargo manifest.
+    /// Rust servers don't look at manifests, so this comes first.
+    async fn dependency_hover(&self, uri: &str, line: u32) -> Option<super::protocol::Hover> {
+        let line = line as usize;
+        let report = {
+            let reports = self.package_reports.read().await;
+            reports
+                .get(uri)
+                .and_then(|reports| reports.iter().find(|report| report.package.line == line))
+                .cloned()
+        };
+        let hover = match self.manifest_rope(uri).await {
+            Some(rope) => self.crate_versions.hover(&rope, line).await,
+            None => None,
+        };
+        let Some(report) = report else {
+            return hover;
+        };
+        let mut sections = Vec::new();
+        if let Some(license) = &report.license {
+            sections.push(format!("License: `{}`", license));
+        }
+        for advisory in &report.advisories {
+            sections.push(format!(
+                "⚠ [{}]({}): {}",
+                advisory.id,
+                advisory.url(),
+                advisory.summary
+            ));
+        }
+        let extra = sections.join("\n\n");
+        match hover {
+            Some(mut hover) => {
+                if let Some(value) = hover.contents["value"].as_str() {
+                    hover.contents["value"] = format!("{}\n\n{}", value, extra).into();
+                }
+                Some(hover)
+            }
+            None => Some(super::protocol::Hover {
+                contents: serde_json::json!({
+                    "kind": "markdown",
+                    "value": format!("**{}** `{}`\n\n{}", report.package.name, report.package.version, extra),
+                }),
+                range: None,
+            }),
+        }
+    }
+
+    /// The newest version of each dependency of the Cargo manifest at `uri`, looking up
+    /// those not known yet on crates.io first. Empty for other files.
+    pub async fn crate_version_hints(&self, uri: &str) -> Vec<CrateVersionHint> {
+        let Some(rope) = self.manifest_rope(uri).await else {
+            return Vec::new();
+        };
+        let dependencies = cargo::dependencies(&rope);
+        self.crate_versions
+            .fetch(
+                dependencies
+                    .iter()
+                    .map(|dependency| dependency.name.clone()),
+            )
+            .await;
+        self.crate_versions.hints(&dependencies).await
+    }
+
+    /// `None` when no running server for `language` can format documents, so the caller
+    /// can fall back to an external formatter.
+    pub async fn format_document(
+        &self,
+        language: &str,
+        uri: &str,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<Option<Vec<TextEdit>>, std::io::Error> {
+        let Some(client) = self.get_server(language).await else {
+            return Ok(None);
+        };
+        let mut client = client.lock().await;
+        if !client.supports_formatting() {
+            return Ok(None);
+        }
+        client
+            .request_formatting(uri, tab_size, insert_spaces)
+            .await
+            .map(Some)
+    }
+
+    /// Like [`LspServerManager::format_document`], formatting only `range`.
+    pub async fn format_range(
+        &self,
+        language: &str,
+        uri: &str,
+        range: Range,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<Option<Vec<TextEdit>>, std::io::Error> {
+        let Some(client) = self.get_server(language).await else {
+            return Ok(None);
+        };
+        let mut client = client.lock().await;
+        if !client.supports_range_formatting() {
+            return Ok(None);
+        }
+        client
+            .request_range_formatting(uri, range, tab_size, insert_spaces)
+            .await
+            .map(Some)
+    }
+
+    /// Edits for `ch`, typed just before `position` in `version` of the document.
+    /// `None` when no running server formats on `ch`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn format_on_type(
+        &self,
+        language: &str,
+        uri: &str,
+        version: usize,
+        position: Position,
+        ch: &str,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<Option<Vec<TextEdit>>, std::io::Error> {
+        let Some(client) = self.get_server(language).await else {
+            return Ok(None);
+        };
+        if !client
+            .lock()
+            .await
+            .on_type_formatting_triggers()
+            .iter()
+            .any(|trigger| trigger == ch)
+        {
+            return Ok(None);
+        }
+        // The edit is sent from another task; asking before it arrives would format
+        // the text as it was before the character was typed.
+        if !self
+            .wait_for_version(uri, version, ON_TYPE_SYNC_TIMEOUT)
+            .await
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("{} was not synced with the language server", uri),
+            ));
+        }
+        let mut client = client.lock().await;
+        client
+            .request_on_type_formatting(uri, position, ch, tab_size, insert_spaces)
+            .await
+            .map(Some)
+    }
+
+    /// The edits the language server wants applied along with moving `old_uri` to
+    /// `new_uri`. A server that is missing, slow or failing doesn't block the rename; its
+    /// edits are just left out.
+    pub async fn will_rename_file(
+        &self,
+        language: &str,
+        old_uri: &str,
+        new_uri: &str,
+    ) -> Result<WorkspaceEdit, std::io::Error> {
+        let Some(client) = self.get_server(language).await else {
+            return Ok(WorkspaceEdit::default());
+        };
+        let request = async {
+            let mut client = client.lock().await;
+            client.request_will_rename_files(old_uri, new_uri).await
+        };
+        match tokio::time::timeout(WILL_RENAME_TIMEOUT, request).await {
+            Ok(result) => result,
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "workspace/willRenameFiles timed out",
+            )),
+        }
+    }
+
+    pub async fn notify_file_renamed(
+        &self,
+        language: &str,
+        old_uri: &str,
+        new_uri: &str,
+    ) -> Result<(), std::io::Error> {
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.notify_did_rename_files(old_uri, new_uri).await
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn execute_command(
+        &self,
+        language: &str,
+        command: &LspCommand,
+    ) -> Result<(), std::io::Error> {
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.execute_command(command).await?;
         }
+        Ok(())
     }
 
     pub async fn notify_file_opened(
@@ -77,10 +662,59 @@ impl LspServerManager {
         language: &str,
         uri: &str,
         text: &str,
+        version: usize,
     ) -> Result<(), std::io::Error> {
+        self.record_version(uri, version).await;
+        self.sync_schema_document(language, uri, Some(text), &[])
+            .await;
         if let Some(client) = self.get_server(language).await {
             let mut client = client.lock().await;
-            client.notify_did_open(uri, text, language).await
+            client
+                .notify_did_open(uri, text, language, version as u64)
+                .await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Tells the server the editor no longer has `uri` open, e.g. because the file was
+    /// renamed, and forgets the versions and schema diagnostics kept for it.
+    pub async fn notify_file_closed(
+        &self,
+        language: &str,
+        uri: &str,
+    ) -> Result<(), std::io::Error> {
+        self.document_versions.write().await.remove(uri);
+        self.schema_documents.write().await.remove(uri);
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.notify_did_close(uri).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sends the whole document: `didOpen` the first time a server sees it, a full
+    /// `didChange` afterwards. Used when a document is shown and after a server starts.
+    pub async fn sync_document(
+        &self,
+        language: &str,
+        uri: &str,
+        text: &str,
+        version: usize,
+    ) -> Result<(), std::io::Error> {
+        self.record_version(uri, version).await;
+        self.sync_schema_document(language, uri, Some(text), &[])
+            .await;
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            if client.is_open(uri) {
+                client.notify_did_change(uri, text, version as u64).await
+            } else {
+                client
+                    .notify_did_open(uri, text, language, version as u64)
+                    .await
+            }
         } else {
             Ok(())
         }
@@ -93,6 +727,9 @@ impl LspServerManager {
         text: &str,
         version: u64,
     ) -> Result<(), std::io::Error> {
+        self.record_version(uri, version as usize).await;
+        self.sync_schema_document(language, uri, Some(text), &[])
+            .await;
         if let Some(client) = self.get_server(language).await {
             let mut client = client.lock().await;
             client.notify_did_change(uri, text, version).await
@@ -101,14 +738,205 @@ impl LspServerManager {
         }
     }
 
+    pub async fn notify_file_edits(
+        &self,
+        language: &str,
+        uri: &str,
+        changes: &[editor_core_text::TextChange],
+    ) -> Result<(), std::io::Error> {
+        if let Some(last) = changes.last() {
+            self.record_version(uri, last.after_version).await;
+        }
+        self.sync_schema_document(language, uri, None, changes)
+            .await;
+        if let Some(client) = self.get_server(language).await {
+            let mut client = client.lock().await;
+            client.notify_did_change_incremental(uri, changes).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Brings the schema copy of a config file up to date with `text`, or else with
+    /// `changes`, and publishes its diagnostics while no server for `language` runs.
+    /// Files without a bundled schema are left alone.
+    async fn sync_schema_document(
+        &self,
+        language: &str,
+        uri: &str,
+        text: Option<&str>,
+        changes: &[editor_core_text::TextChange],
+    ) {
+        let diagnostics = {
+            let mut documents = self.schema_documents.write().await;
+            let document = match (documents.get_mut(uri), text) {
+                (Some(document), Some(text)) => {
+                    document.set_text(text);
+                    document
+                }
+                (Some(document), None) => {
+                    document.apply(changes);
+                    document
+                }
+                (None, Some(text)) => {
+                    let Some(document) = uri_to_path(uri)
+                        .and_then(|path| JsonSchema::for_path(&path))
+                        .and_then(|schema| SchemaDocument::new(schema, language, text))
+                    else {
+                        return;
+                    };
+                    documents.entry(uri.to_string()).or_insert(document)
+                }
+                (None, None) => return,
+            };
+            document.diagnostics()
+        };
+        if self.get_server(language).await.is_none() {
+            self.update_diagnostics(uri.to_string(), diagnostics).await;
+        }
+        self.schedule_advisory_check(uri).await;
+    }
+
+    /// Checks the dependencies of the manifest at `uri` once it has stopped changing,
+    /// in the background. Other files are left alone.
+    async fn schedule_advisory_check(&self, uri: &str) {
+        let Some(path) = uri_to_path(uri) else {
+            return;
+        };
+        if advisories::Ecosystem::of_manifest(&path).is_none() {
+            return;
+        }
+        let generation = {
+            let mut checks = self.advisory_checks.write().await;
+            let generation = checks.entry(uri.to_string()).or_default();
+            *generation += 1;
+            *generation
+        };
+        let uri = uri.to_string();
+        let checks = self.advisory_checks.clone();
+        let documents = self.schema_documents.clone();
+        let reports = self.package_reports.clone();
+        let checker = self.advisories.clone();
+        let changed = self.diagnostics_changed.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ADVISORY_CHECK_DELAY).await;
+            let is_latest = || async { checks.read().await.get(&uri) == Some(&generation) };
+            if !is_latest().await {
+                return;
+            }
+            let Some(rope) = documents
+                .read()
+                .await
+                .get(&uri)
+                .map(|document| document.rope().clone())
+            else {
+                return;
+            };
+            let packages = advisories::locked_packages(&path, &rope);
+            let found = checker.check(packages).await;
+            if is_latest().await {
+                reports.write().await.insert(uri.clone(), found);
+                let _ = changed.send(uri);
+            }
+        });
+    }
+
+    /// Diagnostics for the vulnerable and withdrawn dependencies of a manifest.
+    async fn advisory_diagnostics(&self, uri: &str) -> Vec<Diagnostic> {
+        let Some(rope) = self.schema_rope(uri).await else {
+            return Vec::new();
+        };
+        let reports = self.package_reports.read().await;
+        reports
+            .get(uri)
+            .into_iter()
+            .flatten()
+            .flat_map(|report| report.diagnostics(&rope))
+            .collect()
+    }
+
+    async fn record_version(&self, uri: &str, version: usize) {
+        let mut versions = self.document_versions.write().await;
+        versions.insert(uri.to_string(), version);
+        self.versions_sent.notify_waiters();
+    }
+
+    /// Waits until `version` of the document, or a later one, was sent to the servers.
+    /// Returns `false` if that did not happen within `timeout`.
+    pub async fn wait_for_version(&self, uri: &str, version: usize, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let notified = self.versions_sent.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let sent = self.document_versions.read().await.get(uri).copied();
+                if sent.is_some_and(|sent| sent >= version) {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    /// Stores diagnostics unless they were computed for an older version than the
+    /// servers have since been sent. Returns whether they were kept.
+    pub async fn publish_diagnostics(
+        &self,
+        uri: String,
+        version: Option<usize>,
+        diagnostics: Vec<Diagnostic>,
+    ) -> bool {
+        self.diagnostics_store()
+            .publish(uri, version, diagnostics)
+            .await
+    }
+
+    /// Watched files are workspace-wide, so every running server is told.
+    pub async fn notify_watched_files_changed(
+        &self,
+        changes: &[(String, FileChangeType)],
+    ) -> Result<(), std::io::Error> {
+        let clients: Vec<Arc<Mutex<LspClient>>> =
+            self.servers.read().await.values().cloned().collect();
+        let mut result = Ok(());
+        for client in clients {
+            let mut client = client.lock().await;
+            if let Err(e) = client.notify_did_change_watched_files(changes).await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
     pub async fn update_diagnostics(&self, uri: String, diagnostics: Vec<Diagnostic>) {
-        let mut current_diagnostics = self.diagnostics.write().await;
-        current_diagnostics.insert(uri, diagnostics);
+        self.diagnostics_store().update(uri, diagnostics).await;
     }
 
     pub async fn get_diagnostics(&self, uri: &str) -> Vec<Diagnostic> {
-        let current_diagnostics = self.diagnostics.read().await;
-        current_diagnostics.get(uri).cloned().unwrap_or_default()
+        let mut diagnostics = {
+            let current_diagnostics = self.diagnostics.read().await;
+            current_diagnostics.get(uri).cloned().unwrap_or_default()
+        };
+        diagnostics.extend(self.advisory_diagnostics(uri).await);
+        diagnostics
+    }
+
+    /// Diagnostics of every document that has any, sorted by uri.
+    pub async fn all_diagnostics(&self) -> Vec<(String, Vec<Diagnostic>)> {
+        let mut uris: Vec<String> = self.diagnostics.read().await.keys().cloned().collect();
+        uris.extend(self.package_reports.read().await.keys().cloned());
+        uris.sort();
+        uris.dedup();
+        let mut all = Vec::new();
+        for uri in uris {
+            let diagnostics = self.get_diagnostics(&uri).await;
+            if !diagnostics.is_empty() {
+                all.push((uri, diagnostics));
+            }
+        }
+        all
     }
 
     pub async fn shutdown_all(&self) -> Result<(), std::io::Error> {
@@ -126,3 +954,111 @@ impl Default for LspServerManager {
         Self::new()
     }
 }
+
+/// The diagnostics state shared with the notification forwarding tasks.
+struct DiagnosticsStore {
+    diagnostics: Arc<RwLock<HashMap<String, Vec<Diagnostic>>>>,
+    document_versions: Arc<RwLock<HashMap<String, usize>>>,
+    changed: broadcast::Sender<String>,
+}
+
+impl DiagnosticsStore {
+    async fn publish(
+        &self,
+        uri: String,
+        version: Option<usize>,
+        diagnostics: Vec<Diagnostic>,
+    ) -> bool {
+        if let Some(version) = version {
+            let versions = self.document_versions.read().await;
+            if versions.get(&uri).is_some_and(|latest| version < *latest) {
+                return false;
+            }
+        }
+        self.update(uri, diagnostics).await;
+        true
+    }
+
+    async fn update(&self, uri: String, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics
+            .write()
+            .await
+            .insert(uri.clone(), diagnostics);
+        let _ = self.changed.send(uri);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST_URI: &str = "file:///w/Cargo.toml";
+
+    /// Version hints for a manifest with `text`, every crate in it already looked up.
+    async fn hints_for(text: &str) -> Vec<(usize, String, bool)> {
+        let manager = LspServerManager::new();
+        for (name, latest) in [
+            ("serde", "1.0.210"),
+            ("tokio", "1.40.0"),
+            ("regex", "1.11.0"),
+            ("log", "0.4.22"),
+            ("quoted", "1.0.0"),
+        ] {
+            manager.crate_versions.insert(name, latest).await;
+        }
+        manager
+            .notify_file_opened("toml", MANIFEST_URI, text, 1)
+            .await
+            .unwrap();
+        manager
+            .crate_version_hints(MANIFEST_URI)
+            .await
+            .into_iter()
+            .map(|hint| (hint.line, hint.latest, hint.outdated))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn version_hints_skip_unterminated_strings_and_headers() {
+        let hints =
+            hints_for("[dependencies]\nserde = \"1.0\n[dependencies\ntokio = \"1\"\n").await;
+        assert_eq!(hints, [(3, "1.40.0".to_string(), false)]);
+    }
+
+    #[tokio::test]
+    async fn version_hints_survive_an_unclosed_inline_table() {
+        let manifest = "[package\nname = \"demo\"\n\n[dependencies]\nserde = { version = \"1.0\"\ntokio = 1\n= \"2\"\nbroken \"3\"\n";
+        let hints = hints_for(manifest).await;
+        assert_eq!(hints, [(4, "1.0.210".to_string(), false)]);
+    }
+
+    #[tokio::test]
+    async fn version_hints_never_call_unreadable_requirements_outdated() {
+        let manifest = "[dependencies]\nserde = { version = 1.0 }\nregex = { version = \"\" }\nlog = \"\"\n\"quoted\" = \"0.4\"\n";
+        let hints = hints_for(manifest).await;
+        assert_eq!(
+            hints,
+            [
+                (2, "1.11.0".to_string(), false),
+                (3, "0.4.22".to_string(), false),
+                (4, "1.0.0".to_string(), true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn version_hints_are_empty_without_dependency_tables() {
+        assert!(hints_for("").await.is_empty());
+        assert!(hints_for("[[dependencies]]\nserde = \"1\"\n")
+            .await
+            .is_empty());
+
+        let manager = LspServerManager::new();
+        let uri = "file:///w/package.json";
+        manager
+            .notify_file_opened("json", uri, "{\"dependencies\": {}}", 1)
+            .await
+            .unwrap();
+        assert!(manager.crate_version_hints(uri).await.is_empty());
+    }
+}
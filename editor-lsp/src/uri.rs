@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The `file://` URI a language server knows `path` by, percent-encoded so spaces, `#`,
+/// `%` and non-ASCII names survive. Relative paths are taken from the working directory.
+pub fn path_to_uri(path: &Path) -> String {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match Url::from_file_path(&absolute) {
+        Ok(url) => url.to_string(),
+        // Only reachable for paths `absolute` can't anchor, which no server could open.
+        Err(()) => format!("file://{}", absolute.display()),
+    }
+}
+
+/// The `untitled:` URI an unsaved buffer is opened under. `key` is the buffer's generated
+/// key, which stays the same when the buffer is renamed.
+pub fn untitled_uri(key: &Path) -> String {
+    format!("untitled:{}", key.display())
+}
+
+/// The path of a `file://` URI, decoded. `None` for other schemes, such as `untitled:`.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let url = Url::parse(uri).ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+    url.to_file_path().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_and_decodes_file_uris() {
+        let path = Path::new("/work/my project/#1/100%/日本.rs");
+        let uri = path_to_uri(path);
+        assert_eq!(
+            uri,
+            "file:///work/my%20project/%231/100%25/%E6%97%A5%E6%9C%AC.rs"
+        );
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+        assert_eq!(
+            uri_to_path("file:///a.rs").as_deref(),
+            Some(Path::new("/a.rs"))
+        );
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+        let untitled = untitled_uri(Path::new("untitled-1f0c"));
+        assert_eq!(untitled, "untitled:untitled-1f0c");
+        assert_eq!(uri_to_path(&untitled), None);
+        assert_eq!(uri_to_path("/not/a/uri"), None);
+
+        let relative = path_to_uri(Path::new("src/main.rs"));
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(uri_to_path(&relative), Some(cwd.join("src/main.rs")));
+    }
+}
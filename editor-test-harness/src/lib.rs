@@ -0,0 +1,252 @@
+//! Drives a `BufferManager` through the editor keymap without opening a window.
+//!
+//! Keystrokes are routed with the same [`keymap::route`] the view uses and the
+//! editing subset of the resulting commands is applied straight to the current
+//! buffer. Time only moves when a test calls [`Harness::advance`], so undo
+//! grouping is deterministic. Copy, cut and paste go through a clipboard ring of
+//! the harness's own instead of the system clipboard.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use editor_core_project::BufferManager;
+use editor_core_text::{Buffer, ClipboardRing, Cursor, IndentStyle, LineDirection, ManualClock};
+use editor_infra::Config;
+use editor_ui_gpui::keymap::{self, CursorAction, LineAction};
+use editor_ui_gpui::{KeyCommand, KeyContext};
+use gpui::Modifiers;
+use tokio::sync::Mutex;
+
+/// Rows PageUp and PageDown move, standing in for the height of a window.
+const PAGE_ROWS: usize = 20;
+
+pub struct Harness {
+    root: PathBuf,
+    buffers: BufferManager,
+    clock: Arc<ManualClock>,
+    context: KeyContext,
+    indent: IndentStyle,
+    auto_close: bool,
+    clipboard: ClipboardRing,
+}
+
+impl Harness {
+    /// A harness with the default config and an empty scratch directory.
+    pub fn new() -> Self {
+        let root = std::env::temp_dir().join(format!("fusang-harness-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).expect("create harness directory");
+        let clock = Arc::new(ManualClock::default());
+        let config = Config::default();
+        Self {
+            root,
+            buffers: BufferManager::new().with_clock(clock.clone()),
+            clock,
+            context: KeyContext::default(),
+            indent: IndentStyle::from_settings(config.editor.use_spaces, config.editor.tab_size),
+            auto_close: config.editor.auto_close_brackets,
+            clipboard: ClipboardRing::new(config.editor.clipboard_history_size),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn buffers(&self) -> &BufferManager {
+        &self.buffers
+    }
+
+    /// Override the UI state that changes what a key means.
+    pub fn set_context(&mut self, context: KeyContext) {
+        self.context = context;
+    }
+
+    pub fn write_file(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.root.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("create parent directory");
+        }
+        std::fs::write(&path, content).expect("write file");
+        path
+    }
+
+    pub fn read_file(&self, name: &str) -> String {
+        std::fs::read_to_string(self.root.join(name)).expect("read file")
+    }
+
+    /// Open `name` relative to the scratch directory and make it current. The
+    /// indent style follows the file, like the view does.
+    pub async fn open(&mut self, name: &str) {
+        let path = self.root.join(name);
+        self.buffers.open_file(&path).await.expect("open file");
+        if let Some(style) = self.buffer().await.lock().await.indent_style() {
+            self.indent = style;
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.clock.advance(duration);
+    }
+
+    /// What the view would do for `keystroke`, written like `cmd-shift-k`.
+    pub fn route(&self, keystroke: &str) -> Option<KeyCommand> {
+        let (key, modifiers) = parse_keystroke(keystroke);
+        keymap::route(key, &modifiers, self.context)
+    }
+
+    /// Route `keystroke` and apply it to the current buffer. Panics on commands
+    /// that need the window, such as popups and panels.
+    pub async fn press(&mut self, keystroke: &str) -> Option<KeyCommand> {
+        let command = self.route(keystroke)?;
+        self.run(command.clone()).await;
+        Some(command)
+    }
+
+    /// Press every char of `text` in turn.
+    pub async fn type_text(&mut self, text: &str) {
+        for ch in text.chars() {
+            let key = match ch {
+                '\n' => "Enter".to_string(),
+                '\t' => "Tab".to_string(),
+                ch => ch.to_string(),
+            };
+            self.press(&key).await;
+        }
+    }
+
+    pub fn clipboard(&self) -> &ClipboardRing {
+        &self.clipboard
+    }
+
+    pub async fn text(&self) -> String {
+        self.buffer().await.lock().await.get_text().await
+    }
+
+    pub async fn cursors(&self) -> Vec<Cursor> {
+        self.buffer().await.lock().await.get_cursors().to_vec()
+    }
+
+    async fn buffer(&self) -> Arc<Mutex<Buffer>> {
+        self.buffers
+            .get_current_buffer()
+            .await
+            .expect("no buffer is open")
+    }
+
+    async fn run(&mut self, command: KeyCommand) {
+        match command {
+            KeyCommand::Save => {
+                self.buffers.save_current_file().await.expect("save file");
+                return;
+            }
+            KeyCommand::Copy => {
+                let texts = self.buffer().await.lock().await.selected_texts().await;
+                self.clipboard.push(texts);
+                return;
+            }
+            KeyCommand::NavigateSelections { forward } => {
+                self.buffers.navigate(forward).await;
+                return;
+            }
+            _ => {}
+        }
+        if self.is_read_only().await {
+            return;
+        }
+
+        let handle = self.buffer().await;
+        let mut buffer = handle.lock().await;
+        match command {
+            KeyCommand::InsertText(text) => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some('\n'), None) => buffer.insert_line_break_and_indent(self.indent).await,
+                    (Some(ch), None) if self.auto_close => buffer.insert_char(ch).await,
+                    _ => buffer.insert_text_at_cursor(&text).await,
+                }
+            }
+            KeyCommand::DeleteBackward => buffer.delete_backward().await,
+            KeyCommand::Indent => buffer.insert_tab(self.indent).await,
+            KeyCommand::MoveCursor { movement, extend } => {
+                buffer
+                    .move_selections(movement, extend, None, PAGE_ROWS)
+                    .await
+            }
+            KeyCommand::EditCursors(action) => match action {
+                CursorAction::AddAbove => buffer.add_cursors_vertically(LineDirection::Up).await,
+                CursorAction::AddBelow => buffer.add_cursors_vertically(LineDirection::Down).await,
+                CursorAction::AddNextOccurrence => {
+                    buffer.add_next_occurrence().await;
+                }
+                CursorAction::SplitIntoLines => buffer.split_selection_into_lines().await,
+                CursorAction::Collapse => {
+                    if let Some(primary) = buffer.get_selections().first().copied() {
+                        buffer.set_selection(primary).await;
+                    }
+                }
+            },
+            KeyCommand::Cut => {
+                let texts = buffer.cut_selections().await;
+                self.clipboard.push(texts);
+            }
+            KeyCommand::Paste => {
+                if let Some(entry) = self.clipboard.latest() {
+                    buffer.paste(entry.pieces()).await;
+                }
+            }
+            KeyCommand::Undo => {
+                buffer.undo().await;
+            }
+            KeyCommand::Redo => {
+                buffer.redo().await;
+            }
+            KeyCommand::EditLines(LineAction::Delete) => buffer.delete_lines().await,
+            KeyCommand::EditLines(LineAction::Duplicate) => buffer.duplicate_lines().await,
+            KeyCommand::EditLines(LineAction::Move(direction)) => {
+                buffer.move_lines(direction).await
+            }
+            other => panic!("{other:?} needs a window and is not supported by the harness"),
+        }
+    }
+
+    async fn is_read_only(&self) -> bool {
+        match self.buffers.get_current_file_path().await {
+            Some(path) => self.buffers.is_read_only(&path).await,
+            None => false,
+        }
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Split `cmd-shift-k` into the key and its modifiers. A trailing `-` is the
+/// minus key itself, as in `cmd--`.
+pub fn parse_keystroke(keystroke: &str) -> (&str, Modifiers) {
+    let mut modifiers = Modifiers::default();
+    let mut rest = keystroke;
+    while let Some((prefix, tail)) = rest.split_once('-') {
+        if tail.is_empty() {
+            break;
+        }
+        match prefix {
+            "cmd" | "super" => modifiers.platform = true,
+            "ctrl" => modifiers.control = true,
+            "alt" => modifiers.alt = true,
+            "shift" => modifiers.shift = true,
+            _ => break,
+        }
+        rest = tail;
+    }
+    (rest, modifiers)
+}
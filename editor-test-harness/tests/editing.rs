@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use editor_core_project::{DiskChangeEvent, FileChange, FileChangeKind};
+use editor_core_text::Cursor;
+use editor_test_harness::Harness;
+
+#[tokio::test]
+async fn open_type_undo_save() {
+    let mut harness = Harness::new();
+    harness.write_file("main.rs", "fn main() {}\n");
+    harness.open("main.rs").await;
+
+    harness.press("End").await;
+    harness.type_text(" // one").await;
+    harness.advance(Duration::from_secs(2));
+    harness.type_text(" two").await;
+    assert_eq!(harness.text().await, "fn main() {} // one two\n");
+
+    harness.press("cmd-z").await;
+    assert_eq!(harness.text().await, "fn main() {} // one\n");
+    harness.press("cmd-y").await;
+    harness.press("cmd-z").await;
+
+    harness.press("cmd-s").await;
+    assert_eq!(harness.read_file("main.rs"), "fn main() {} // one\n");
+}
+
+#[tokio::test]
+async fn enter_keeps_the_file_indent() {
+    let mut harness = Harness::new();
+    harness.write_file("lib.rs", "fn f() {\n\tlet a = 1;\n}\n");
+    harness.open("lib.rs").await;
+
+    harness.press("Down").await;
+    harness.press("End").await;
+    harness.type_text("\nb").await;
+    assert_eq!(harness.text().await, "fn f() {\n\tlet a = 1;\n\tb\n}\n");
+    assert_eq!(harness.cursors().await, vec![Cursor::new(2, 2)]);
+}
+
+#[tokio::test]
+async fn line_commands_edit_the_current_line() {
+    let mut harness = Harness::new();
+    harness.write_file("notes.txt", "one\ntwo\n");
+    harness.open("notes.txt").await;
+
+    harness.press("alt-Down").await;
+    assert_eq!(harness.text().await, "two\none\n");
+    harness.press("cmd-shift-k").await;
+    assert_eq!(harness.text().await, "two\n");
+    assert_eq!(harness.cursors().await, vec![Cursor::new(1, 0)]);
+    harness.press("Backspace").await;
+    assert_eq!(harness.text().await, "two");
+    harness.press("cmd-z").await;
+    assert_eq!(harness.text().await, "two\n");
+}
+
+#[tokio::test]
+async fn locked_buffers_ignore_typing() {
+    let mut harness = Harness::new();
+    let path = harness.write_file("locked.txt", "keep\n");
+    harness.open("locked.txt").await;
+
+    harness.buffers().lock_editing(&path).await;
+    harness.type_text("x").await;
+    harness.press("cmd-z").await;
+    assert_eq!(harness.text().await, "keep\n");
+
+    harness.buffers().allow_editing(&path).await.unwrap();
+    harness.type_text("x").await;
+    assert_eq!(harness.text().await, "xkeep\n");
+}
+
+#[tokio::test]
+async fn cut_and_paste_keep_one_piece_per_cursor() {
+    let mut harness = Harness::new();
+    harness.write_file("list.txt", "a1\nb2\n");
+    harness.open("list.txt").await;
+
+    harness.press("cmd-alt-Down").await;
+    harness.press("shift-Right").await;
+    harness.press("cmd-x").await;
+    assert_eq!(harness.text().await, "1\n2\n");
+    assert_eq!(harness.clipboard().latest().unwrap().text(), "a\nb");
+
+    harness.press("End").await;
+    harness.press("cmd-v").await;
+    assert_eq!(harness.text().await, "1a\n2b\n");
+}
+
+#[tokio::test]
+async fn navigating_back_retraces_jumps_across_files() {
+    let mut harness = Harness::new();
+    let long: String = (0..40).map(|n| format!("{}\n", n)).collect();
+    let first = harness.write_file("first.txt", &long);
+    let second = harness.write_file("second.txt", "two\n");
+    harness.open("first.txt").await;
+    let buffer = harness.buffers().get_current_buffer().await.unwrap();
+    buffer.lock().await.set_cursor(Cursor::new(30, 1)).await;
+    harness.open("second.txt").await;
+    harness.press("End").await;
+
+    harness.press("ctrl--").await;
+    assert_eq!(
+        harness.buffers().get_current_file_path().await,
+        Some(first.clone())
+    );
+    assert_eq!(harness.cursors().await, vec![Cursor::new(30, 1)]);
+    harness.press("ctrl--").await;
+    assert_eq!(harness.cursors().await, vec![Cursor::zero()]);
+    // Nothing further back.
+    harness.press("ctrl--").await;
+    assert_eq!(harness.cursors().await, vec![Cursor::zero()]);
+
+    harness.press("ctrl-shift--").await;
+    assert_eq!(harness.cursors().await, vec![Cursor::new(30, 1)]);
+    harness.press("ctrl-shift--").await;
+    assert_eq!(
+        harness.buffers().get_current_file_path().await,
+        Some(second)
+    );
+    assert_eq!(harness.cursors().await, vec![Cursor::new(0, 3)]);
+}
+
+#[tokio::test]
+async fn disk_changes_reload_clean_buffers_and_flag_edited_ones() {
+    let mut harness = Harness::new();
+    let clean = harness.write_file("clean.txt", "one\n");
+    let edited = harness.write_file("edited.txt", "one\n");
+    harness.open("clean.txt").await;
+    harness.open("edited.txt").await;
+    harness.type_text("mine ").await;
+    // Saving writes the file too, which is no conflict.
+    harness.press("cmd-s").await;
+    harness.type_text("more ").await;
+
+    harness.write_file("clean.txt", "one\ntwo\n");
+    let changed = |path: &std::path::Path| FileChange {
+        path: path.to_path_buf(),
+        kind: FileChangeKind::Changed,
+    };
+    let buffers = harness.buffers();
+    let events = buffers.apply_file_changes(&[changed(&edited)]).await;
+    assert!(events.is_empty());
+
+    harness.write_file("edited.txt", "theirs\n");
+    let events = buffers
+        .apply_file_changes(&[changed(&clean), changed(&edited)])
+        .await;
+    assert!(matches!(&events[0], DiskChangeEvent::Reloaded { path, .. } if *path == clean));
+    assert_eq!(
+        events[1],
+        DiskChangeEvent::Conflicted {
+            path: edited.clone()
+        }
+    );
+    let text = |path| async move {
+        let buffer = buffers.get_buffer(path).await.unwrap();
+        let text = buffer.lock().await.get_text().await;
+        text
+    };
+    assert_eq!(text(&clean).await, "one\ntwo\n");
+    assert_eq!(text(&edited).await, "mine more one\n");
+    assert!(buffers.is_conflicted(&edited).await);
+
+    buffers.reload_from_disk(&edited).await.unwrap();
+    assert_eq!(text(&edited).await, "theirs\n");
+    assert!(!buffers.is_conflicted(&edited).await);
+}
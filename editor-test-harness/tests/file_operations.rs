@@ -0,0 +1,151 @@
+use editor_core_project::{DeleteMode, FileJournal, FileTree, FileTreeEvent, Workspace};
+use editor_test_harness::Harness;
+use std::path::Path;
+
+#[tokio::test]
+async fn renaming_a_directory_moves_its_open_buffers() {
+    let mut harness = Harness::new();
+    harness.write_file("src/a.rs", "fn a() {}\n");
+    harness.write_file("src/b.rs", "fn b() {}\n");
+    harness.write_file("notes.md", "notes\n");
+    let root = harness.root().to_path_buf();
+    harness.open("notes.md").await;
+    harness.open("src/a.rs").await;
+    harness.type_text("// edited\n").await;
+
+    let workspace = Workspace::single_root(&root).unwrap();
+    let mut tree = FileTree::new(root.clone()).unwrap();
+    let target = workspace
+        .rename(Path::new("src"), Path::new("lib/core"))
+        .unwrap();
+    assert_eq!(target, root.join("lib/core"));
+    assert!(workspace
+        .rename(Path::new("src"), Path::new("other"))
+        .is_err());
+    assert!(workspace
+        .rename(Path::new("notes.md"), Path::new("lib"))
+        .is_err());
+
+    // `lib` isn't in the tree yet, so the moved node is dropped until a rescan.
+    assert!(tree.move_node(&root.join("src"), &root.join("lib/core")));
+    assert!(tree.find_node(&root.join("src")).is_none());
+    assert!(tree.move_node(&root.join("notes.md"), &root.join("todo.md")));
+    assert!(tree.find_node(&root.join("todo.md")).unwrap().is_file());
+    assert!(!tree.move_node(&root.join("missing.md"), &root.join("found.md")));
+
+    let buffers = harness.buffers();
+    assert_eq!(
+        buffers.get_open_files().await,
+        [root.join("notes.md"), root.join("src/a.rs")]
+    );
+    let renamed = buffers
+        .rename_path(&root.join("src"), &root.join("lib/core"))
+        .await;
+    assert_eq!(
+        renamed,
+        [(root.join("src/a.rs"), root.join("lib/core/a.rs"))]
+    );
+    assert_eq!(
+        buffers.get_current_file_path().await,
+        Some(root.join("lib/core/a.rs"))
+    );
+    assert!(buffers.get_buffer(&root.join("src/a.rs")).await.is_none());
+    let buffer = buffers
+        .get_buffer(&root.join("lib/core/a.rs"))
+        .await
+        .unwrap();
+    assert_eq!(
+        buffer.lock().await.get_text().await,
+        "// edited\nfn a() {}\n"
+    );
+    assert!(buffer.lock().await.is_dirty());
+    // The tab keeps its place instead of sorting in as a newly opened file.
+    assert_eq!(
+        buffers.get_open_files().await,
+        [root.join("notes.md"), root.join("lib/core/a.rs")]
+    );
+    assert_eq!(
+        buffers
+            .unsaved_changes(&root.join("lib/core/a.rs"))
+            .await
+            .map(|diff| diff.changes().len()),
+        Some(1)
+    );
+
+    buffers.save_current_file().await.unwrap();
+    assert_eq!(harness.read_file("lib/core/a.rs"), "// edited\nfn a() {}\n");
+    assert!(buffers
+        .rename_path(&root.join("src"), &root.join("elsewhere"))
+        .await
+        .is_empty());
+}
+
+#[tokio::test]
+async fn creating_and_deleting_from_the_tree_keeps_buffers_in_step() {
+    let mut harness = Harness::new();
+    harness.write_file("src/a.rs", "fn a() {}\n");
+    harness.write_file("notes.md", "notes\n");
+    let root = harness.root().to_path_buf();
+    let mut tree = FileTree::new(root.clone()).unwrap();
+    let mut journal = FileJournal::new(root.join(".trash")).with_system_trash(false);
+
+    // Missing parents are created on disk and in the tree.
+    let event = tree
+        .create_file(&mut journal, &root.join("src/deep/b.rs"), "fn b() {}\n")
+        .unwrap();
+    assert_eq!(
+        event,
+        FileTreeEvent::Created {
+            path: root.join("src/deep/b.rs"),
+            is_dir: false,
+        }
+    );
+    assert_eq!(harness.read_file("src/deep/b.rs"), "fn b() {}\n");
+    assert!(tree
+        .find_node(&root.join("src/deep"))
+        .unwrap()
+        .is_directory());
+    assert!(tree
+        .find_node(&root.join("src/deep/b.rs"))
+        .unwrap()
+        .is_file());
+    assert!(tree
+        .create_file(&mut journal, &root.join("notes.md"), "")
+        .is_err());
+
+    let event = tree.create_dir(&mut journal, &root.join("docs")).unwrap();
+    assert_eq!(
+        event,
+        FileTreeEvent::Created {
+            path: root.join("docs"),
+            is_dir: true,
+        }
+    );
+    assert!(tree.find_node(&root.join("docs")).unwrap().is_directory());
+
+    harness.open("notes.md").await;
+    harness.open("src/a.rs").await;
+    harness.open("src/deep/b.rs").await;
+    let event = tree
+        .delete(&mut journal, &root.join("src"), DeleteMode::Trash)
+        .unwrap();
+    assert_eq!(
+        event,
+        FileTreeEvent::Deleted {
+            path: root.join("src"),
+            mode: DeleteMode::Trash,
+        }
+    );
+    assert!(!root.join("src").exists());
+    assert!(tree.find_node(&root.join("src")).is_none());
+
+    let buffers = harness.buffers();
+    let mut closed = buffers.close_files_under(&root.join("src")).await.unwrap();
+    closed.sort();
+    assert_eq!(closed, [root.join("src/a.rs"), root.join("src/deep/b.rs")]);
+    assert_eq!(buffers.get_open_files().await, [root.join("notes.md")]);
+
+    // Undoing the delete brings the directory back for a rescan to pick up.
+    journal.undo_last().unwrap();
+    assert_eq!(harness.read_file("src/a.rs"), "fn a() {}\n");
+}
@@ -0,0 +1,45 @@
+use editor_core_project::{BufferManager, EditJournals};
+use editor_test_harness::Harness;
+
+#[tokio::test]
+async fn journals_recover_unsaved_text_after_a_crash() {
+    let harness = Harness::new();
+    let path = harness.write_file("notes.txt", "one\n");
+    let journals = EditJournals::new(harness.root().join("journals"));
+
+    let session = BufferManager::new().with_edit_journals(journals.clone());
+    session.open_file(&path).await.unwrap();
+    let buffer = session.get_buffer(&path).await.unwrap();
+    buffer.lock().await.replace_range(4, 0, "two\n").await;
+    session.save_file(&path).await.unwrap();
+    // Saving starts the journal over; nothing is written until the next edit.
+    assert!(session.journal(&path).await.unwrap().is_none());
+    buffer.lock().await.replace_range(0, 3, "ONE").await;
+    let log = session.journal(&path).await.unwrap().unwrap();
+    assert_eq!(log.initial_text, "one\ntwo\n");
+    assert_eq!(log.final_text().0, "ONE\ntwo\n");
+    // The session ends without closing the file.
+    drop(buffer);
+    drop(session);
+
+    let restarted = BufferManager::new().with_edit_journals(journals.clone());
+    restarted.open_file(&path).await.unwrap();
+    assert_eq!(
+        journals.crashed_files().unwrap(),
+        std::slice::from_ref(&path)
+    );
+    assert!(restarted.recover_from_journal(&path).await.unwrap());
+    let buffer = restarted.get_buffer(&path).await.unwrap();
+    assert_eq!(buffer.lock().await.get_text().await, "ONE\ntwo\n");
+    assert!(buffer.lock().await.is_dirty());
+    assert!(journals.crashed_files().unwrap().is_empty());
+    assert!(!restarted.recover_from_journal(&path).await.unwrap());
+
+    // Closing a file ends its journal, so nothing is left to recover next time.
+    restarted.save_file(&path).await.unwrap();
+    restarted.close_file(&path).await.unwrap();
+    assert!(journals.read(&path).unwrap().is_none());
+    let clean = BufferManager::new().with_edit_journals(journals.clone());
+    clean.open_file(&path).await.unwrap();
+    assert!(journals.crashed(&path).unwrap().is_none());
+}
@@ -0,0 +1,223 @@
+use editor_core_text::{CursorMovement, LineDirection, MarkName};
+use editor_test_harness::{parse_keystroke, Harness};
+use editor_ui_gpui::keymap::{ArgumentAction, CursorAction, LineAction, QuickInputMode};
+use editor_ui_gpui::{KeyCommand, KeyContext};
+
+#[test]
+fn keystrokes_parse_modifiers() {
+    let (key, modifiers) = parse_keystroke("ctrl-alt-1");
+    assert_eq!(key, "1");
+    assert!(modifiers.control && modifiers.alt && !modifiers.platform);
+
+    let (key, modifiers) = parse_keystroke("cmd--");
+    assert_eq!(key, "-");
+    assert!(modifiers.platform);
+
+    assert_eq!(parse_keystroke("-").0, "-");
+}
+
+#[test]
+fn command_shortcuts() {
+    let harness = Harness::new();
+    assert_eq!(harness.route("cmd-s"), Some(KeyCommand::Save));
+    assert_eq!(harness.route("cmd-z"), Some(KeyCommand::Undo));
+    assert_eq!(
+        harness.route("cmd-alt-z"),
+        Some(KeyCommand::UndoFileOperation)
+    );
+    assert_eq!(harness.route("alt-z"), Some(KeyCommand::ToggleSoftWrap));
+    assert_eq!(harness.route("cmd-alt-r"), Some(KeyCommand::ReloadFromDisk));
+    assert_eq!(
+        harness.route("cmd-f"),
+        Some(KeyCommand::QuickInput(QuickInputMode::Find))
+    );
+    assert_eq!(
+        harness.route("cmd-alt-f"),
+        Some(KeyCommand::QuickInput(QuickInputMode::Replace))
+    );
+    assert_eq!(
+        harness.route("cmd-shift-k"),
+        Some(KeyCommand::EditLines(LineAction::Delete))
+    );
+    assert_eq!(harness.route("cmd-alt-k"), Some(KeyCommand::ToggleMetrics));
+    assert_eq!(
+        harness.route("cmd-alt-shift-k"),
+        Some(KeyCommand::ToggleMemory)
+    );
+    assert_eq!(
+        harness.route("cmd-alt-h"),
+        Some(KeyCommand::ToggleHealthReport)
+    );
+    assert_eq!(
+        harness.route("cmd-alt-shift-p"),
+        Some(KeyCommand::TogglePerformanceMode)
+    );
+    assert_eq!(harness.route("cmd-x"), Some(KeyCommand::Cut));
+    assert_eq!(harness.route("cmd-v"), Some(KeyCommand::Paste));
+    assert_eq!(
+        harness.route("cmd-shift-v"),
+        Some(KeyCommand::QuickInput(QuickInputMode::PickClipboard))
+    );
+    assert_eq!(harness.route("cmd-alt-j"), Some(KeyCommand::ExportEditLog));
+    assert_eq!(
+        harness.route("cmd-alt-shift-j"),
+        Some(KeyCommand::RecoverFromJournal)
+    );
+    assert_eq!(
+        harness.route("ctrl-shift--"),
+        Some(KeyCommand::NavigateSelections { forward: true })
+    );
+    assert_eq!(
+        harness.route("cmd-alt-shift-o"),
+        Some(KeyCommand::ToggleDocumentTree)
+    );
+    assert_eq!(
+        harness.route("cmd-alt-shift-t"),
+        Some(KeyCommand::ToggleTableMode)
+    );
+    assert_eq!(
+        harness.route("cmd-shift-j"),
+        Some(KeyCommand::QuickInput(QuickInputMode::ReplayEditLog))
+    );
+    assert_eq!(
+        harness.route("cmd-u"),
+        Some(KeyCommand::QuickInput(QuickInputMode::TransformCase))
+    );
+    assert_eq!(harness.route("cmd-shift-u"), Some(KeyCommand::ServerLogs));
+    assert_eq!(
+        harness.route("cmd-alt-i"),
+        Some(KeyCommand::QuickInput(QuickInputMode::InlineThread))
+    );
+    assert_eq!(harness.route("cmd-i"), Some(KeyCommand::Completions));
+    assert_eq!(
+        harness.route("cmd-shift-p"),
+        Some(KeyCommand::QuickInput(QuickInputMode::RunTool))
+    );
+    assert_eq!(
+        harness.route("cmd-shift-g"),
+        Some(KeyCommand::ToggleSourceControl)
+    );
+    assert_eq!(
+        harness.route("cmd-alt-g"),
+        Some(KeyCommand::QuickInput(QuickInputMode::GitCommand))
+    );
+    assert_eq!(harness.route("cmd-g"), Some(KeyCommand::FindNext));
+    assert_eq!(harness.route("cmd-alt-b"), Some(KeyCommand::ToggleBlame));
+    assert_eq!(
+        harness.route("cmd-alt-,"),
+        Some(KeyCommand::LayoutArguments(ArgumentAction::Split))
+    );
+    assert_eq!(
+        harness.route("cmd-alt-shift-,"),
+        Some(KeyCommand::LayoutArguments(ArgumentAction::Join))
+    );
+    assert_eq!(
+        harness.route("cmd-alt-."),
+        Some(KeyCommand::LayoutArguments(ArgumentAction::Wrap))
+    );
+    assert_eq!(harness.route("cmd-."), Some(KeyCommand::CodeActions));
+    assert_eq!(
+        harness.route("cmd-shift-f"),
+        Some(KeyCommand::QuickInput(QuickInputMode::SearchWorkspace))
+    );
+    assert_eq!(
+        harness.route("cmd-alt-o"),
+        Some(KeyCommand::QuickInput(QuickInputMode::RecentWorkspace))
+    );
+    assert_eq!(
+        harness.route("cmd-alt-n"),
+        Some(KeyCommand::QuickInput(QuickInputMode::NewFile))
+    );
+    assert_eq!(
+        harness.route("cmd-alt-shift-n"),
+        Some(KeyCommand::QuickInput(QuickInputMode::NewFolder))
+    );
+}
+
+#[test]
+fn marks_use_digits_with_control() {
+    let harness = Harness::new();
+    assert_eq!(
+        harness.route("ctrl-shift-3"),
+        Some(KeyCommand::SetMark(MarkName::Number(3)))
+    );
+    assert_eq!(
+        harness.route("ctrl-3"),
+        Some(KeyCommand::JumpToMark(MarkName::Number(3)))
+    );
+}
+
+#[test]
+fn arrows_move_or_edit_lines() {
+    let harness = Harness::new();
+    assert_eq!(
+        harness.route("shift-Left"),
+        Some(KeyCommand::MoveCursor {
+            movement: CursorMovement::Left,
+            extend: true,
+        })
+    );
+    assert_eq!(
+        harness.route("alt-Up"),
+        Some(KeyCommand::EditLines(LineAction::Move(LineDirection::Up)))
+    );
+    assert_eq!(
+        harness.route("alt-shift-Down"),
+        Some(KeyCommand::EditLines(LineAction::Duplicate))
+    );
+    assert_eq!(
+        harness.route("cmd-alt-Down"),
+        Some(KeyCommand::EditCursors(CursorAction::AddBelow))
+    );
+    assert_eq!(
+        harness.route("cmd-shift-End"),
+        Some(KeyCommand::MoveCursor {
+            movement: CursorMovement::DocumentEnd,
+            extend: true,
+        })
+    );
+    assert_eq!(
+        harness.route("PageDown"),
+        Some(KeyCommand::MoveCursor {
+            movement: CursorMovement::PageDown,
+            extend: false,
+        })
+    );
+}
+
+#[test]
+fn context_changes_escape_and_enter() {
+    let mut harness = Harness::new();
+    assert_eq!(harness.route("Escape"), None);
+    assert_eq!(
+        harness.route("Enter"),
+        Some(KeyCommand::InsertText("\n".to_string()))
+    );
+
+    harness.set_context(KeyContext {
+        multiple_cursors: true,
+        ..KeyContext::default()
+    });
+    assert_eq!(
+        harness.route("Escape"),
+        Some(KeyCommand::EditCursors(CursorAction::Collapse))
+    );
+
+    harness.set_context(KeyContext {
+        peek_open: true,
+        multiple_cursors: true,
+        ..KeyContext::default()
+    });
+    assert_eq!(harness.route("Escape"), Some(KeyCommand::ClosePeek));
+    assert_eq!(harness.route("Enter"), Some(KeyCommand::OpenPeekLocation));
+}
+
+#[test]
+fn modified_letters_are_not_typed() {
+    let harness = Harness::new();
+    assert_eq!(harness.route("ctrl-q"), None);
+    assert_eq!(
+        harness.route("q"),
+        Some(KeyCommand::InsertText("q".to_string()))
+    );
+}
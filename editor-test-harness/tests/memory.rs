@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use editor_test_harness::Harness;
+
+#[tokio::test]
+async fn idle_clean_buffers_drop_caches_but_keep_text() {
+    let mut harness = Harness::new();
+    harness.write_file("a.txt", "alpha\n");
+    harness.write_file("b.txt", "beta\n");
+    harness.write_file("c.txt", "gamma\n");
+    let a = harness.root().join("a.txt");
+    let c = harness.root().join("c.txt");
+
+    harness.open("a.txt").await;
+    harness.type_text("1").await;
+    harness.press("cmd-s").await;
+    harness.open("c.txt").await;
+    harness.type_text("2").await;
+    harness.open("b.txt").await;
+
+    let report = harness.buffers().memory_report().await;
+    assert_eq!(report.len(), 3);
+    let a_report = report.iter().find(|entry| entry.path == a).unwrap();
+    assert_eq!(a_report.memory.text_bytes, "1alpha\n".len());
+    assert_eq!(a_report.saved_text_bytes, "1alpha\n".len());
+
+    harness.advance(Duration::from_secs(20 * 60));
+    let buffers = harness.buffers();
+    assert!(buffers
+        .evict_idle_caches(Duration::from_secs(30 * 60))
+        .await
+        .is_empty());
+
+    // `b` is current and `c` has unsaved edits, so only `a` is evicted.
+    harness.advance(Duration::from_secs(20 * 60));
+    let buffers = harness.buffers();
+    assert_eq!(
+        buffers
+            .evict_idle_caches(Duration::from_secs(30 * 60))
+            .await,
+        vec![a.clone()]
+    );
+    assert!(buffers
+        .evict_idle_caches(Duration::from_secs(30 * 60))
+        .await
+        .is_empty());
+
+    let report = buffers.memory_report().await;
+    let a_report = report.iter().find(|entry| entry.path == a).unwrap();
+    assert!(a_report.evicted);
+    assert_eq!(a_report.saved_text_bytes, 0);
+    assert_eq!(a_report.idle, Duration::from_secs(40 * 60));
+    let c_report = report.iter().find(|entry| entry.path == c).unwrap();
+    assert!(!c_report.evicted && c_report.saved_text_bytes > 0);
+
+    // Unsaved changes are diffed against the file again once `a` is edited.
+    harness.buffers().set_current_buffer(&a).await.unwrap();
+    assert_eq!(harness.text().await, "1alpha\n");
+    harness.press("End").await;
+    harness.type_text("!").await;
+    let changes = harness.buffers().unsaved_changes(&a).await.unwrap();
+    assert_eq!(changes.changes().len(), 1);
+}
@@ -0,0 +1,80 @@
+use editor_core_project::ClosedTab;
+use editor_core_text::Cursor;
+use editor_infra::{Session, SessionTab};
+use editor_test_harness::Harness;
+use std::collections::HashMap;
+
+#[tokio::test]
+async fn checkpointed_tabs_and_drafts_survive_a_restart() {
+    let mut harness = Harness::new();
+    harness.write_file("a.txt", "alpha\n");
+    harness.write_file("b.txt", "beta\ngamma\n");
+    let a = harness.root().join("a.txt");
+    let b = harness.root().join("b.txt");
+
+    harness.open("a.txt").await;
+    harness.type_text("1").await;
+    harness.open("b.txt").await;
+    harness.press("Down").await;
+    let untitled = harness.buffers().create_new_buffer().await;
+    harness.type_text("note").await;
+    harness
+        .buffers()
+        .open_remote("https://example.com/x", "fetched")
+        .await;
+    harness.buffers().set_current_buffer(&b).await.unwrap();
+
+    let session = Session {
+        performance_mode: false,
+        pinned_tabs: Vec::new(),
+        current: harness.buffers().get_current_file_path().await,
+        open_tabs: harness
+            .buffers()
+            .open_tab_states(&HashMap::from([(a.clone(), 3), (b.clone(), 7)]))
+            .await
+            .iter()
+            .map(SessionTab::from)
+            .collect(),
+    };
+    let path = harness.root().join("session.toml");
+    session.save(&path).unwrap();
+    let session = Session::load(&path).unwrap();
+
+    // Remote content is read-only and not part of the checkpoint; tabs the view
+    // doesn't know a scroll position for start at the top.
+    let paths: Vec<_> = session
+        .open_tabs
+        .iter()
+        .map(|tab| tab.path.clone())
+        .collect();
+    assert_eq!(paths.len(), 3);
+    assert!(paths.contains(&a) && paths.contains(&b) && paths.contains(&untitled));
+    let b_tab = session.open_tabs.iter().find(|tab| tab.path == b).unwrap();
+    assert_eq!((b_tab.scroll_line, b_tab.draft.as_ref()), (7, None));
+    let a_tab = session.open_tabs.iter().find(|tab| tab.path == a).unwrap();
+    assert_eq!(a_tab.scroll_line, 3);
+    let untitled_tab = session.open_tabs.iter().find(|tab| tab.untitled).unwrap();
+    assert_eq!(untitled_tab.scroll_line, 0);
+
+    let restarted = Harness::new();
+    for tab in session.open_tabs {
+        let untitled_tab = tab.untitled;
+        let restored = restarted
+            .buffers()
+            .restore_tab(ClosedTab::from(tab))
+            .await
+            .unwrap();
+        let text = restarted.text().await;
+        if untitled_tab {
+            assert_ne!(restored.path, untitled);
+            assert!(restarted.buffers().is_untitled(&restored.path).await);
+            assert_eq!(text, "note");
+        } else if restored.path == a {
+            assert_eq!(text, "1alpha\n");
+        } else {
+            assert_eq!(text, "beta\ngamma\n");
+            assert_eq!(restarted.cursors().await, [Cursor::new(1, 0)]);
+        }
+    }
+    assert_eq!(restarted.buffers().get_unsaved_files().await.len(), 2);
+}
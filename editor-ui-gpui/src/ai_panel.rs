@@ -1,26 +1,50 @@
-use editor_ai::models::{AIContext, AIMessage, AIRole};
-use editor_core_text::Buffer;
+use crate::theme::SyntaxTheme;
+use editor_ai::models::{AIContext, AIMessage, AIRole, MessageBlock};
+use editor_ai::{ChangeStatus, Citation, CodeIndex, WorkingSet};
+use editor_core_text::{Buffer, BufferSnapshot};
 use gpui::{div, prelude::*, px, rgb, Context, Window};
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// AI 面板的两个标签页：自由对话，或以修改为中心的 Composer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AIPanelTab {
+    #[default]
+    Chat,
+    Composer,
+}
+
 #[derive(Debug, Clone)]
 pub struct AIPanel {
+    tab: AIPanelTab,
+    /// Composer 提出的修改，由编辑器视图渲染和应用
+    working_set: WorkingSet,
     messages: Vec<AIMessage>,
     current_model: String,
     is_loading: bool,
     ai_engine: Arc<editor_ai::AIEngine>,
     buffer_context: Option<AIContext>,
+    citations: Vec<Citation>,
+    /// 代码块的配色，与编辑区相同
+    syntax_theme: SyntaxTheme,
 }
 
 impl AIPanel {
-    pub fn new(_cx: &mut Context<'_, Self>, ai_engine: Arc<editor_ai::AIEngine>) -> Self {
+    pub fn new(
+        _cx: &mut Context<'_, Self>,
+        ai_engine: Arc<editor_ai::AIEngine>,
+        syntax_theme: SyntaxTheme,
+    ) -> Self {
         Self {
+            tab: AIPanelTab::default(),
+            working_set: WorkingSet::default(),
             messages: Vec::new(),
             current_model: "gpt-3.5-turbo".to_string(),
             is_loading: false,
             ai_engine,
             buffer_context: None,
+            citations: Vec::new(),
+            syntax_theme,
         }
     }
 
@@ -33,6 +57,15 @@ impl AIPanel {
         AIContext::from_buffer(buffer, file_path, language.to_string()).await
     }
 
+    /// 从缓冲区快照构建 AI 上下文，不占用缓冲区锁
+    pub fn build_context_from_snapshot(
+        snapshot: &BufferSnapshot,
+        file_path: Option<PathBuf>,
+        language: &str,
+    ) -> anyhow::Result<AIContext> {
+        AIContext::from_snapshot(snapshot, file_path, language.to_string())
+    }
+
     /// 设置当前缓冲区上下文
     pub fn set_buffer_context(&mut self, context: AIContext) {
         self.buffer_context = Some(context);
@@ -88,6 +121,84 @@ impl AIPanel {
         Ok(())
     }
 
+    /// 在工作区索引中搜索代码，回答附带可点击的引用
+    pub async fn ask_codebase(&mut self, query: String, index: &CodeIndex) -> anyhow::Result<()> {
+        if self.is_loading {
+            return Ok(());
+        }
+
+        self.is_loading = true;
+        self.messages.push(AIMessage {
+            role: AIRole::User,
+            content: query.clone(),
+        });
+
+        let result = self
+            .ai_engine
+            .answer_code_query(&query, index, Some(&self.current_model))
+            .await;
+        self.is_loading = false;
+        let answer = result.map_err(|e| anyhow::anyhow!("AI engine error: {}", e))?;
+
+        self.messages.push(AIMessage {
+            role: AIRole::Assistant,
+            content: answer.answer,
+        });
+        self.citations = answer.citations;
+        Ok(())
+    }
+
+    /// 按指令对 `files`（路径, 当前内容）提出修改，已接受的修改保留在工作集中
+    pub async fn compose(
+        &mut self,
+        instruction: String,
+        files: Vec<(String, String)>,
+    ) -> anyhow::Result<()> {
+        if self.is_loading {
+            return Ok(());
+        }
+
+        self.is_loading = true;
+        let result = self
+            .ai_engine
+            .propose_edits(
+                &instruction,
+                &files,
+                &self.working_set,
+                Some(&self.current_model),
+            )
+            .await;
+        self.is_loading = false;
+        let reply = result.map_err(|e| anyhow::anyhow!("AI engine error: {}", e))?;
+        self.working_set.apply_reply(instruction, reply);
+        Ok(())
+    }
+
+    pub fn set_syntax_theme(&mut self, theme: SyntaxTheme) {
+        self.syntax_theme = theme;
+    }
+
+    pub fn tab(&self) -> AIPanelTab {
+        self.tab
+    }
+
+    pub fn set_tab(&mut self, tab: AIPanelTab) {
+        self.tab = tab;
+    }
+
+    pub fn working_set(&self) -> &WorkingSet {
+        &self.working_set
+    }
+
+    pub fn working_set_mut(&mut self) -> &mut WorkingSet {
+        &mut self.working_set
+    }
+
+    /// 获取最近一次代码搜索的引用
+    pub fn citations(&self) -> &[Citation] {
+        &self.citations
+    }
+
     /// 使用当前缓冲区上下文发送消息
     pub async fn send_message_with_context(&mut self, message: String) -> anyhow::Result<()> {
         if self.buffer_context.is_none() {
@@ -99,6 +210,7 @@ impl AIPanel {
     /// 清除对话历史
     pub fn clear_messages(&mut self) {
         self.messages.clear();
+        self.citations.clear();
     }
 
     /// 获取消息列表
@@ -175,7 +287,7 @@ impl AIPanel {
 }
 
 impl Render for AIPanel {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<'_, Self>) -> impl IntoElement {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<'_, Self>) -> impl IntoElement {
         let mut layout = div()
             .flex()
             .flex_col()
@@ -191,6 +303,7 @@ impl Render for AIPanel {
                 .items_center()
                 .justify_between()
                 .child(div().text_color(rgb(0x8fd8ff)).child("AI Copilot"))
+                .child(self.render_tabs(cx))
                 .child(
                     div()
                         .px_2()
@@ -206,6 +319,10 @@ impl Render for AIPanel {
                 ),
         );
 
+        if self.tab == AIPanelTab::Composer {
+            return layout.child(self.render_composer_summary());
+        }
+
         if let Some(summary) = self.context_summary() {
             layout = layout.child(
                 div()
@@ -254,12 +371,7 @@ impl Render for AIPanel {
                                 .text_color(role_color)
                                 .child(format!("{:?}", message.role)),
                         )
-                        .child(
-                            div()
-                                .mt_1()
-                                .text_color(rgb(0xd9e8ff))
-                                .child(message.content.clone()),
-                        ),
+                        .child(self.render_message_body(message)),
                 );
             }
         }
@@ -268,6 +380,111 @@ impl Render for AIPanel {
     }
 }
 
+impl AIPanel {
+    /// 正文按段落显示，代码块用编辑区的语法高亮
+    fn render_message_body(&self, message: &AIMessage) -> impl IntoElement {
+        let mut body = div()
+            .mt_1()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .text_color(rgb(0xd9e8ff));
+        for block in message.blocks() {
+            body = match block {
+                MessageBlock::Text(text) => body.child(div().child(text)),
+                MessageBlock::Code { language, code } => {
+                    let mut lines = div()
+                        .flex()
+                        .flex_col()
+                        .p_2()
+                        .rounded(px(4.0))
+                        .bg(rgb(0x0b1627))
+                        .border_1()
+                        .border_color(rgb(0x1a2d4a))
+                        .text_xs()
+                        .font_family("monospace")
+                        .whitespace_nowrap();
+                    for line in self.syntax_theme.highlight_code(&language, &code) {
+                        lines = lines.child(line);
+                    }
+                    body.child(lines)
+                }
+            };
+        }
+        body
+    }
+
+    fn render_tabs(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let tab = |id: &'static str, label: &'static str, value: AIPanelTab| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .rounded(px(4.0))
+                .text_xs()
+                .cursor_pointer()
+                .bg(if self.tab == value {
+                    rgb(0x1a4d8f)
+                } else {
+                    rgb(0x0f2038)
+                })
+                .child(label)
+                .on_click(cx.listener(move |panel: &mut AIPanel, _, _, cx| {
+                    panel.set_tab(value);
+                    cx.notify();
+                }))
+        };
+        div()
+            .flex()
+            .gap_1()
+            .child(tab("ai-tab-chat", "对话", AIPanelTab::Chat))
+            .child(tab("ai-tab-composer", "Composer", AIPanelTab::Composer))
+    }
+
+    /// Composer 标签页的说明和历次指令；修改列表由编辑器视图渲染
+    fn render_composer_summary(&self) -> impl IntoElement {
+        let set = &self.working_set;
+        let mut summary = div()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .p_2()
+            .rounded(px(8.0))
+            .bg(rgb(0x0f2038))
+            .border_1()
+            .border_color(rgb(0x1a2d4a));
+
+        if set.is_empty() {
+            return summary.child(
+                div()
+                    .text_color(rgb(0x7ea6d6))
+                    .child("描述要做的修改，回车发送。提出的修改按文件列出，可逐处接受或撤回，再继续追加指令。"),
+            );
+        }
+        for (idx, instruction) in set.instructions.iter().enumerate() {
+            summary = summary.child(div().text_xs().text_color(rgb(0xb3f7a4)).child(format!(
+                "{}. {}",
+                idx + 1,
+                instruction
+            )));
+        }
+        if !set.summary.is_empty() {
+            summary = summary.child(
+                div()
+                    .mt_1()
+                    .text_color(rgb(0xd9e8ff))
+                    .child(set.summary.clone()),
+            );
+        }
+        summary.child(div().text_xs().text_color(rgb(0x7ea6d6)).child(format!(
+            "{} 处待处理，{} 处已接受，{} 处已撤回",
+            set.count(&ChangeStatus::Proposed),
+            set.count(&ChangeStatus::Accepted),
+            set.count(&ChangeStatus::Reverted)
+        )))
+    }
+}
+
 // 便捷方法扩展
 impl AIPanel {
     /// 快速设置缓冲区上下文
@@ -0,0 +1,292 @@
+//! Composer 审阅：逐个文件查看本轮改动，为改动块写评论并交给下一轮修改
+
+use crate::editor_view::EditorView;
+use editor_ai::composer::REVISE_INSTRUCTION;
+use editor_ai::ChangeStatus;
+use editor_core_project::diff_lines;
+use gpui::{div, prelude::*, px, rgb, Context, InteractiveElement, StatefulInteractiveElement};
+
+/// Composer 修改的审阅界面
+#[derive(Default)]
+pub(crate) struct ComposerReview {
+    /// 所选文件在工作集文件列表中的位置
+    file: usize,
+    /// 正在编辑评论的修改
+    pub(crate) editing: Option<usize>,
+}
+
+impl EditorView {
+    pub(crate) fn composer_status_label(status: &ChangeStatus) -> (String, gpui::Rgba) {
+        match status {
+            ChangeStatus::Proposed => ("待处理".to_string(), rgb(0xffe4a6)),
+            ChangeStatus::Accepted => ("已接受".to_string(), rgb(0xb3f7a4)),
+            ChangeStatus::Reverted => ("已撤回".to_string(), rgb(0x7ea6d6)),
+            ChangeStatus::Failed(e) => (format!("失败: {}", e), rgb(0xff8a8a)),
+        }
+    }
+
+    /// 打开 Composer 修改的审阅界面
+    pub(crate) fn open_composer_review(&mut self, cx: &mut Context<'_, Self>) {
+        let has_changes = self
+            .ai_panel
+            .as_ref()
+            .is_some_and(|panel| !panel.read(cx).working_set().changes.is_empty());
+        if !has_changes {
+            self.set_status("没有需要审阅的修改");
+        } else {
+            self.composer_review = Some(ComposerReview::default());
+            self.set_status("逐处接受或拒绝修改并写下评论，提交后 Composer 按评论修订");
+        }
+        cx.notify();
+    }
+
+    /// 把审阅意见（拒绝的修改和评论）发给 Composer，开始下一轮修订
+    fn submit_composer_review(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(ai_panel) = &self.ai_panel else {
+            return;
+        };
+        if ai_panel.read(cx).working_set().review_feedback().is_empty() {
+            self.set_status("还没有评论或拒绝的修改");
+            cx.notify();
+            return;
+        }
+        self.composer_review = None;
+        self.send_composer_instruction(REVISE_INSTRUCTION.to_string(), cx);
+    }
+
+    /// 在审阅界面中编辑评论时的按键
+    pub(crate) fn edit_review_comment(
+        &mut self,
+        change: usize,
+        key: &str,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some(ai_panel) = self.ai_panel.clone() else {
+            return;
+        };
+        match key {
+            "Escape" | "Enter" => {
+                if let Some(review) = &mut self.composer_review {
+                    review.editing = None;
+                }
+            }
+            _ => ai_panel.update(cx, |panel, cx| {
+                if let Some(change) = panel.working_set_mut().changes.get_mut(change) {
+                    if key == "Backspace" {
+                        change.comment.pop();
+                    } else if key.chars().count() == 1 {
+                        change.comment.push_str(key);
+                    }
+                }
+                cx.notify();
+            }),
+        }
+        cx.notify();
+    }
+
+    /// 审阅界面：左侧是文件列表，右侧是所选文件的每处修改的差异、接受/拒绝按钮和评论
+    pub(crate) fn render_composer_review(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let (Some(review), Some(ai_panel)) = (&self.composer_review, &self.ai_panel) else {
+            return div();
+        };
+        let set = ai_panel.read(cx).working_set().clone();
+        let files = set.files();
+        let selected = review.file.min(files.len().saturating_sub(1));
+        let button = |id: (&'static str, usize), label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .rounded(px(4.0))
+                .bg(rgb(0x1a4d8f))
+                .cursor_pointer()
+                .text_xs()
+                .child(label)
+        };
+
+        let mut file_list = div()
+            .id("composer-review-files")
+            .w(px(220.0))
+            .flex()
+            .flex_col()
+            .gap_1()
+            .overflow_y_scroll()
+            .border_r_1()
+            .border_color(rgb(0x2a2a2a))
+            .pr_2();
+        for (file_idx, file) in files.iter().enumerate() {
+            let pending = set
+                .changes_in(file)
+                .filter(|(_, change)| change.status == ChangeStatus::Proposed)
+                .count();
+            file_list = file_list.child(
+                div()
+                    .id(("composer-review-file", file_idx))
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .cursor_pointer()
+                    .text_sm()
+                    .when(file_idx == selected, |row| row.bg(rgb(0x1f2f45)))
+                    .text_color(if pending > 0 {
+                        rgb(0xffe4a6)
+                    } else {
+                        rgb(0xbbbbbb)
+                    })
+                    .child(if pending > 0 {
+                        format!("{} ({})", file, pending)
+                    } else {
+                        file.to_string()
+                    })
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        if let Some(review) = &mut view.composer_review {
+                            review.file = file_idx;
+                            review.editing = None;
+                        }
+                        cx.notify();
+                    })),
+            );
+        }
+
+        let mut hunks = div()
+            .id("composer-review-hunks")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .pl_3()
+            .overflow_y_scroll();
+        let file = files.get(selected).copied().unwrap_or_default();
+        for (idx, change) in set.changes_in(file) {
+            let (label, color) = Self::composer_status_label(&change.status);
+            let lines = diff_lines(&change.patch.old_code, &change.patch.new_code);
+            let diff = div()
+                .flex()
+                .flex_col()
+                .text_xs()
+                .children(Self::diff_rows(&lines, 0xb3f7a4, 0xff8a8a));
+
+            let mut actions = div().flex().gap_1();
+            if change.status != ChangeStatus::Accepted {
+                actions = actions.child(button(("composer-review-accept", idx), "接受").on_click(
+                    cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.resolve_composer_changes(vec![idx], true, cx)
+                    }),
+                ));
+            }
+            if change.status != ChangeStatus::Reverted {
+                actions = actions.child(button(("composer-review-reject", idx), "拒绝").on_click(
+                    cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.resolve_composer_changes(vec![idx], false, cx)
+                    }),
+                ));
+            }
+
+            let editing = review.editing == Some(idx);
+            let comment = if editing {
+                format!("{}▏", change.comment)
+            } else if change.comment.is_empty() {
+                "添加评论…".to_string()
+            } else {
+                change.comment.clone()
+            };
+            hunks = hunks.child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap_1()
+                    .p_2()
+                    .rounded(px(6.0))
+                    .bg(rgb(0x12223a))
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .justify_between()
+                            .child(div().text_xs().text_color(color).child(label))
+                            .child(actions),
+                    )
+                    .child(diff)
+                    .child(
+                        div()
+                            .id(("composer-review-comment", idx))
+                            .px_2()
+                            .py_1()
+                            .rounded(px(4.0))
+                            .border_1()
+                            .border_color(if editing {
+                                rgb(0x4c8dff)
+                            } else {
+                                rgb(0x2a3a50)
+                            })
+                            .cursor_text()
+                            .text_xs()
+                            .text_color(if change.comment.is_empty() && !editing {
+                                rgb(0x666666)
+                            } else {
+                                rgb(0xdddddd)
+                            })
+                            .child(comment)
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                if let Some(review) = &mut view.composer_review {
+                                    review.editing = Some(idx);
+                                }
+                                cx.notify();
+                            })),
+                    ),
+            );
+        }
+
+        div().absolute().inset_0().child(
+            div()
+                .w(px(900.0))
+                .h(px(560.0))
+                .p_4()
+                .flex()
+                .flex_col()
+                .rounded(px(10.0))
+                .bg(rgb(0x121212))
+                .border_1()
+                .border_color(rgb(0x2a2a2a))
+                .shadow_lg()
+                .mx_auto()
+                .mt(px(80.0))
+                .child(
+                    div()
+                        .flex()
+                        .items_center()
+                        .justify_between()
+                        .pb_2()
+                        .child(div().text_color(rgb(0xffffff)).child(format!(
+                            "审阅修改 · 第 {} 轮 · {} 处待处理",
+                            set.instructions.len(),
+                            set.count(&ChangeStatus::Proposed)
+                        )))
+                        .child(
+                            div()
+                                .flex()
+                                .gap_2()
+                                .child(button(("composer-review-submit", 0), "提交审阅").on_click(
+                                    cx.listener(|view: &mut EditorView, _, _, cx| {
+                                        view.submit_composer_review(cx)
+                                    }),
+                                ))
+                                .child(button(("composer-review-close", 0), "关闭").on_click(
+                                    cx.listener(|view: &mut EditorView, _, _, cx| {
+                                        view.composer_review = None;
+                                        cx.notify();
+                                    }),
+                                )),
+                        ),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .flex()
+                        .min_h(px(0.0))
+                        .child(file_list)
+                        .child(hunks),
+                ),
+        )
+    }
+}
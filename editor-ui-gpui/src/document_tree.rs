@@ -0,0 +1,229 @@
+//! 结构视图：以可折叠的树显示 JSON/YAML 文档，复制节点路径并与光标同步
+
+use crate::editor_view::EditorView;
+use editor_core_text::{DocumentTree, NodeKind, PathSegment};
+use gpui::{div, prelude::*, px, rgb, Context, InteractiveElement, StatefulInteractiveElement};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// 结构视图最多显示的节点数
+const DOCUMENT_TREE_ROWS: usize = 2000;
+
+/// JSON/YAML/TOML 文件的结构视图，跟随当前文件
+pub(crate) struct DocumentTreePanel {
+    pub(crate) path: PathBuf,
+    tree: DocumentTree,
+    /// 最近一次解析失败的原因；树保留上一次解析成功的结果
+    error: Option<String>,
+    /// 解析时缓冲区的版本
+    pub(crate) version: usize,
+    /// 折叠的节点，按路径记录，重新解析后仍然有效
+    collapsed: HashSet<String>,
+}
+
+impl EditorView {
+    /// 打开或关闭当前 JSON/YAML/TOML 文件的结构视图
+    pub fn toggle_document_tree(&mut self, cx: &mut Context<'_, Self>) {
+        if self.document_tree.take().is_some() {
+            cx.notify();
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let language = self.current_language.clone().unwrap_or_default();
+        if DocumentTree::parse(&language, "").is_none() {
+            self.set_status("只有 JSON、YAML 和 TOML 文件有结构视图");
+            cx.notify();
+            return;
+        }
+        // 版本不同，刷新时就会解析
+        self.document_tree = Some(DocumentTreePanel {
+            path,
+            tree: DocumentTree::default(),
+            error: None,
+            version: usize::MAX,
+            collapsed: HashSet::new(),
+        });
+        self.refresh_buffer_view(cx);
+    }
+
+    /// 用当前文件的文本重新解析结构视图；切换到不是 JSON/YAML/TOML 的文件时关闭视图
+    pub(crate) fn update_document_tree(&mut self, text: &str, version: usize) {
+        let language = self.current_language.clone().unwrap_or_default();
+        let (Some(panel), Some(path)) = (&mut self.document_tree, &self.current_file_path) else {
+            return;
+        };
+        let Some(result) = DocumentTree::parse(&language, text) else {
+            self.document_tree = None;
+            return;
+        };
+        if &panel.path != path {
+            panel.path = path.clone();
+            panel.tree = DocumentTree::default();
+            panel.collapsed.clear();
+        }
+        panel.version = version;
+        match result {
+            Ok(tree) => {
+                panel.tree = tree;
+                panel.error = None;
+            }
+            Err(e) => panel.error = Some(e.to_string()),
+        }
+    }
+
+    fn copy_document_path(&mut self, idx: usize, cx: &mut Context<'_, Self>) {
+        let Some(panel) = &self.document_tree else {
+            return;
+        };
+        let path = panel.tree.path(idx);
+        cx.write_to_clipboard(gpui::ClipboardItem::new_string(path.clone()));
+        self.set_status(format!("已复制路径 {}", path));
+        cx.notify();
+    }
+
+    /// 结构视图：点击节点跳到文本中的位置，光标所在的节点高亮显示
+    pub(crate) fn render_document_tree(
+        &self,
+        panel: &DocumentTreePanel,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let tree = &panel.tree;
+        let collapsed: HashSet<usize> = (0..tree.nodes.len())
+            .filter(|&idx| panel.collapsed.contains(&tree.path(idx)))
+            .collect();
+        let is_visible = |idx: usize| tree.is_visible(idx, |idx| collapsed.contains(&idx));
+        // 光标所在的节点，折叠时高亮最近的可见祖先
+        let mut current = self
+            .selection
+            .and_then(|selection| tree.node_at(selection.active));
+        while let Some(idx) = current.filter(|&idx| !is_visible(idx)) {
+            current = tree.nodes[idx].parent;
+        }
+
+        let mut list = div()
+            .id("document-tree-list")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .overflow_scroll();
+        if let Some(error) = &panel.error {
+            list = list.child(
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_xs()
+                    .text_color(rgb(0xf48771))
+                    .child(error.clone()),
+            );
+        }
+        let visible = (0..tree.nodes.len()).filter(|&idx| is_visible(idx));
+        for idx in visible.take(DOCUMENT_TREE_ROWS) {
+            let node = &tree.nodes[idx];
+            let label = match &node.segment {
+                Some(PathSegment::Key(key)) => key.clone(),
+                Some(PathSegment::Index(index)) => format!("[{}]", index),
+                None => "(根)".to_string(),
+            };
+            let value = match node.kind {
+                NodeKind::Object => format!("{{{}}}", node.children),
+                NodeKind::Array => format!("[{}]", node.children),
+                NodeKind::Scalar => {
+                    let mut value: String = node.value.chars().take(40).collect();
+                    if value.len() < node.value.len() {
+                        value.push('…');
+                    }
+                    value
+                }
+            };
+            let arrow = match (node.children, collapsed.contains(&idx)) {
+                (0, _) => " ",
+                (_, true) => "▸",
+                (_, false) => "▾",
+            };
+            let start = node.start;
+            let file = panel.path.clone();
+
+            list = list.child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_1()
+                    .py_px()
+                    .pr_2()
+                    .pl(px(12.0 + node.depth as f32 * 14.0))
+                    .text_sm()
+                    .bg(if current == Some(idx) {
+                        rgb(0x264f78)
+                    } else {
+                        rgb(0x141414)
+                    })
+                    .child(
+                        div()
+                            .id(("document-tree-toggle", idx))
+                            .w(px(14.0))
+                            .cursor_pointer()
+                            .text_color(rgb(0x888888))
+                            .child(arrow)
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                if let Some(panel) = &mut view.document_tree {
+                                    let path = panel.tree.path(idx);
+                                    if !panel.collapsed.remove(&path) {
+                                        panel.collapsed.insert(path);
+                                    }
+                                }
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        div()
+                            .id(("document-tree-node", idx))
+                            .flex_1()
+                            .flex()
+                            .gap_2()
+                            .overflow_hidden()
+                            .whitespace_nowrap()
+                            .cursor_pointer()
+                            .child(div().text_color(rgb(0x9cdcfe)).child(label))
+                            .child(div().text_color(rgb(0x999999)).child(value))
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.open_file_at(&file, start.line, start.column, cx)
+                            })),
+                    )
+                    .child(
+                        div()
+                            .id(("document-tree-copy", idx))
+                            .px_1()
+                            .rounded(px(4.0))
+                            .cursor_pointer()
+                            .text_xs()
+                            .text_color(rgb(0x777777))
+                            .child("复制路径")
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.copy_document_path(idx, cx)
+                            })),
+                    ),
+            );
+        }
+
+        div()
+            .w(px(320.0))
+            .flex()
+            .flex_col()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_color(rgb(0x9ad1ff))
+                    .text_sm()
+                    .child("结构"),
+            )
+            .child(list)
+    }
+}
@@ -0,0 +1,466 @@
+//! 多文件修改预览：语言服务器的 applyEdit、代码操作和 AI 补丁按文件和块勾选后再应用
+
+use crate::editor_view::EditorView;
+use crate::keymap::QuickInputMode;
+use editor_core_project::{BufferManager, EditPreview};
+use editor_lsp::{uri_to_path, ApplyEditRequest, CodeAction, WorkspaceEdit};
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+
+/// 等待用户确认的多文件修改
+pub(crate) struct PendingPreview {
+    preview: EditPreview,
+    /// 来自语言服务器的 applyEdit 请求，应用或取消后回复
+    request: Option<ApplyEditRequest>,
+}
+
+impl EditorView {
+    /// 把语言服务器的修改转换为预览，UTF-16 列按各文件当前内容换算为字符列
+    pub(crate) async fn preview_from_workspace_edit(
+        buffer_manager: &BufferManager,
+        title: String,
+        edit: &WorkspaceEdit,
+    ) -> EditPreview {
+        let mut preview = EditPreview::new(title);
+        for (uri, edits) in &edit.changes {
+            let Some(path) = uri_to_path(uri) else {
+                continue;
+            };
+            let path = path.as_path();
+            let snapshot = buffer_manager.file_snapshot(path).await.ok();
+            for edit in edits {
+                let (start, end) = match &snapshot {
+                    Some(snapshot) => edit.range.to_cursors(snapshot.rope()),
+                    None => (
+                        Self::lsp_cursor(&edit.range.start),
+                        Self::lsp_cursor(&edit.range.end),
+                    ),
+                };
+                preview.push(path, start, end, edit.new_text.clone());
+            }
+        }
+        preview
+    }
+
+    /// 读取被替换的原文后弹出预览；已有预览时新的请求直接拒绝
+    pub(crate) fn show_edit_preview(
+        &mut self,
+        mut preview: EditPreview,
+        request: Option<ApplyEditRequest>,
+        cx: &mut Context<'_, Self>,
+    ) {
+        if self.edit_preview.is_some() || preview.is_empty() {
+            if let Some(request) = request {
+                self.lsp_executor.clone().spawn(async move {
+                    let _ = request.respond(false).await;
+                });
+            }
+            if preview.is_empty() {
+                self.set_status("没有需要应用的修改");
+                cx.notify();
+            }
+            return;
+        }
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let result = buffer_manager.load_preview_text(&mut preview).await;
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) => {
+                            view.set_status(format!(
+                                "{}：{} 个文件 {} 处修改，回车应用，Esc 取消",
+                                preview.title,
+                                preview.files.len(),
+                                preview.hunk_count()
+                            ));
+                            view.edit_preview = Some(PendingPreview { preview, request });
+                        }
+                        Err(e) => {
+                            view.set_status(format!("无法预览修改: {}", e));
+                            if let Some(request) = request {
+                                view.lsp_executor.clone().spawn(async move {
+                                    let _ = request.respond(false).await;
+                                });
+                            }
+                        }
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn toggle_preview_file(&mut self, file: usize, cx: &mut Context<'_, Self>) {
+        if let Some(pending) = self.edit_preview.as_mut() {
+            pending.preview.toggle_file(file);
+            cx.notify();
+        }
+    }
+
+    fn toggle_preview_hunk(&mut self, file: usize, hunk: usize, cx: &mut Context<'_, Self>) {
+        if let Some(pending) = self.edit_preview.as_mut() {
+            pending.preview.toggle_hunk(file, hunk);
+            cx.notify();
+        }
+    }
+
+    /// 应用预览中勾选的修改，每个文件一步撤销
+    pub(crate) fn apply_edit_preview(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(PendingPreview { preview, request }) = self.edit_preview.take() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+        let executor = self.lsp_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let result = buffer_manager.apply_preview(&preview).await;
+                if let Some(request) = request {
+                    let applied = result.as_ref().is_ok_and(|files| !files.is_empty());
+                    executor.spawn(async move {
+                        let _ = request.respond(applied).await;
+                    });
+                }
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(files) => view.set_status(format!(
+                            "已应用 {} 处修改（{} 个文件）",
+                            preview.accepted_count(),
+                            files.len()
+                        )),
+                        Err(e) => view.set_status(format!("应用修改失败: {}", e)),
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn cancel_edit_preview(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(pending) = self.edit_preview.take() else {
+            return;
+        };
+        if let Some(request) = pending.request {
+            self.lsp_executor.clone().spawn(async move {
+                let _ = request.respond(false).await;
+            });
+        }
+        self.set_status("已取消修改");
+        cx.notify();
+    }
+
+    /// 语言服务器要求修改文件时（如执行代码操作后）先弹出预览
+    pub(crate) fn watch_apply_edits(&mut self, cx: &mut Context<'_, Self>) {
+        if self.apply_edit_watch.is_some() {
+            return;
+        }
+        let mut requests = self.lsp_manager.subscribe_apply_edits();
+        let buffer_manager = self.buffer_manager.clone();
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                loop {
+                    match requests.recv().await {
+                        Ok(request) => {
+                            let title = request
+                                .label
+                                .clone()
+                                .unwrap_or_else(|| format!("{} 语言服务器修改", request.language));
+                            let preview = Self::preview_from_workspace_edit(
+                                &buffer_manager,
+                                title,
+                                &request.edit,
+                            )
+                            .await;
+                            this.update(&mut app, |view, cx| {
+                                view.show_edit_preview(preview, Some(request), cx);
+                            })?;
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                anyhow::Ok(())
+            }
+        });
+        self.apply_edit_watch = Some(task);
+    }
+
+    /// 查询光标或选区处可用的代码操作
+    pub(crate) fn show_code_actions(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let Some(selection) = self.selection else {
+            return;
+        };
+        let language = self.current_file_language();
+        let uri = self.open_file_uri(&path);
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let snapshot = buffer_manager.file_snapshot(&path).await?;
+                let range = editor_lsp::Range::from_cursors(
+                    snapshot.rope(),
+                    selection.start(),
+                    selection.end(),
+                );
+                let request = executor.spawn(async move {
+                    manager.request_code_actions(&language, &uri, range).await
+                });
+                let result = request.await.map_err(std::io::Error::other).and_then(|r| r);
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(actions) if actions.is_empty() => view.set_status("没有可用的代码操作"),
+                        Ok(actions) => {
+                            view.code_actions = actions;
+                            view.begin_quick_input(QuickInputMode::PickCodeAction, cx);
+                        }
+                        Err(e) => view.set_status(format!("查询代码操作失败: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn filtered_code_actions(&self, filter: &str) -> Vec<CodeAction> {
+        let filter = filter.to_lowercase();
+        self.code_actions
+            .iter()
+            .filter(|action| action.title.to_lowercase().contains(&filter))
+            .cloned()
+            .collect()
+    }
+
+    /// 代码操作自带的修改先预览；命令交给语言服务器执行，其修改通过 applyEdit 再次预览
+    pub(crate) fn run_code_action(&mut self, action: CodeAction, cx: &mut Context<'_, Self>) {
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        self.code_actions.clear();
+
+        if let Some(edit) = action.edit.clone().filter(|edit| !edit.is_empty()) {
+            let buffer_manager = self.buffer_manager.clone();
+            let title = action.title.clone();
+
+            cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+                async move {
+                    let preview =
+                        Self::preview_from_workspace_edit(&buffer_manager, title, &edit).await;
+                    this.update(&mut app, |view, cx| {
+                        view.show_edit_preview(preview, None, cx)
+                    })?;
+                    anyhow::Ok(())
+                }
+            })
+            .detach();
+        }
+        if let Some(command) = action.command {
+            let language = self.current_file_language();
+            let manager = self.lsp_manager.clone();
+            let executor = self.lsp_executor.clone();
+
+            cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+                async move {
+                    let request = executor
+                        .spawn(async move { manager.execute_command(&language, &command).await });
+                    let result = request.await.map_err(std::io::Error::other).and_then(|r| r);
+                    if let Err(e) = result {
+                        this.update(&mut app, |view, cx| {
+                            view.set_status(format!("执行代码操作失败: {}", e));
+                            cx.notify();
+                        })?;
+                    }
+                    anyhow::Ok(())
+                }
+            })
+            .detach();
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn render_code_actions(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::PickCodeAction {
+            return list;
+        }
+
+        for (idx, action) in self
+            .filtered_code_actions(self.quick_open_input.trim())
+            .into_iter()
+            .enumerate()
+        {
+            let kind = action.kind.clone().unwrap_or_default();
+            list = list.child(
+                div()
+                    .id(("code-action", idx as u64))
+                    .flex()
+                    .justify_between()
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(action.title.clone())
+                    .child(div().text_xs().text_color(rgb(0x777777)).child(kind))
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.run_code_action(action.clone(), cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    /// 多文件修改预览：按文件和修改块勾选要应用的内容
+    pub(crate) fn render_edit_preview(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let Some(pending) = &self.edit_preview else {
+            return div();
+        };
+        let preview = &pending.preview;
+        let root = std::env::current_dir().unwrap_or_default();
+        let check = |accepted: bool| if accepted { "☑" } else { "☐" };
+
+        let mut rows = div()
+            .id("edit-preview-list")
+            .mt_2()
+            .max_h(px(360.0))
+            .overflow_scroll()
+            .flex()
+            .flex_col()
+            .gap_1();
+        for (file_idx, file) in preview.files.iter().enumerate() {
+            let label = file
+                .path
+                .strip_prefix(&root)
+                .unwrap_or(&file.path)
+                .display()
+                .to_string();
+            rows = rows.child(
+                div()
+                    .id(("edit-preview-file", file_idx as u64))
+                    .flex()
+                    .gap_2()
+                    .text_sm()
+                    .text_color(rgb(0xffffff))
+                    .cursor_pointer()
+                    .child(check(file.all_accepted()))
+                    .child(label)
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.toggle_preview_file(file_idx, cx)
+                    })),
+            );
+            for (hunk_idx, hunk) in file.hunks.iter().enumerate() {
+                let id = (file_idx * 10_000 + hunk_idx) as u64;
+                let diff = Self::block_diff(&hunk.old_text, &hunk.new_text);
+                let body = div()
+                    .flex()
+                    .flex_col()
+                    .text_xs()
+                    .children(Self::diff_rows(&diff, 0x81c784, 0xe57373));
+                rows = rows.child(
+                    div()
+                        .id(("edit-preview-hunk", id))
+                        .flex()
+                        .gap_2()
+                        .pl_4()
+                        .cursor_pointer()
+                        .child(div().text_sm().child(check(hunk.accepted)))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(0x777777))
+                                .child(format!("L{}", hunk.start.line + 1)),
+                        )
+                        .child(body)
+                        .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                            view.toggle_preview_hunk(file_idx, hunk_idx, cx)
+                        })),
+                );
+            }
+        }
+
+        div().absolute().inset_0().child(
+            div()
+                .w(px(640.0))
+                .p_4()
+                .rounded(px(10.0))
+                .bg(rgb(0x121212))
+                .border_1()
+                .border_color(rgb(0x2a2a2a))
+                .shadow_lg()
+                .mx_auto()
+                .mt(px(100.0))
+                .child(div().text_color(rgb(0xffffff)).child(preview.title.clone()))
+                .child(
+                    div()
+                        .mt_1()
+                        .text_sm()
+                        .text_color(rgb(0x888888))
+                        .child(format!(
+                            "已勾选 {}/{} 处修改，点击切换文件或修改块",
+                            preview.accepted_count(),
+                            preview.hunk_count()
+                        )),
+                )
+                .child(rows)
+                .child(
+                    div()
+                        .flex()
+                        .gap_2()
+                        .mt_3()
+                        .child(
+                            div()
+                                .id("edit-preview-apply")
+                                .px_3()
+                                .py_1()
+                                .rounded(px(6.0))
+                                .bg(rgb(0x2e7d32))
+                                .cursor_pointer()
+                                .child("Apply")
+                                .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                    view.apply_edit_preview(cx)
+                                })),
+                        )
+                        .child(
+                            div()
+                                .id("edit-preview-cancel")
+                                .px_3()
+                                .py_1()
+                                .rounded(px(6.0))
+                                .bg(rgb(0x3a3a3a))
+                                .cursor_pointer()
+                                .child("Cancel")
+                                .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                    view.cancel_edit_preview(cx)
+                                })),
+                        ),
+                ),
+        )
+    }
+}
@@ -0,0 +1,365 @@
+//! 编辑日志：导出当前缓冲区的编辑记录，逐步回放编辑日志或编辑日记，并从日记恢复
+
+use crate::editor_view::EditorView;
+use editor_core_text::EditLog;
+use futures::FutureExt;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 正在逐步回放的编辑日志
+pub(crate) struct EditLogReplay {
+    log: EditLog,
+    /// 显示回放结果的只读缓冲区
+    path: PathBuf,
+    /// 已应用的编辑数
+    step: usize,
+    /// 回放到某一步失败的原因，通常说明日志记录时缓冲区已经损坏
+    error: Option<String>,
+}
+
+impl EditorView {
+    /// 导出当前缓冲区的编辑日志；还没有记录时从当前内容开始记录
+    pub fn export_edit_log(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let message = match buffer_manager.edit_log(&path).await {
+                    Some(log) => {
+                        let name = path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_else(|| "buffer".to_string());
+                        let secs = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|d| d.as_secs())
+                            .unwrap_or_default();
+                        let dir = editor_infra::paths::data_dir().join("edit-logs");
+                        let target = dir.join(format!("{}-{}.json", name, secs));
+                        match std::fs::create_dir_all(&dir)
+                            .and_then(|_| std::fs::write(&target, log.to_json()))
+                        {
+                            Ok(()) => format!("已导出 {} 步编辑到 {}", log.len(), target.display()),
+                            Err(e) => format!("导出编辑日志失败: {}", e),
+                        }
+                    }
+                    None if buffer_manager.start_edit_log(&path).await => {
+                        "已开始记录当前缓冲区的编辑，复现问题后再按 Cmd+Alt+J 导出".to_string()
+                    }
+                    None => return anyhow::Ok(()),
+                };
+                this.update(&mut app, |view, cx| {
+                    view.set_status(message);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 在只读缓冲区中打开编辑日志，从记录开始时的内容逐步回放
+    pub fn replay_edit_log(&mut self, file: PathBuf, cx: &mut Context<'_, Self>) {
+        let log = match std::fs::read_to_string(&file) {
+            Ok(json) => EditLog::from_json(&json).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let log = match log {
+            Ok(log) => log,
+            Err(e) => {
+                self.set_status(format!("无法读取编辑日志 {}: {}", file.display(), e));
+                cx.notify();
+                return;
+            }
+        };
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.start_edit_replay(name, log, cx);
+    }
+
+    /// 回放当前文件上次保存以来的编辑日志，查看文件是怎样一步步改成现在这样的
+    pub fn replay_journal(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        if self.buffer_manager.edit_journals().is_none() {
+            self.set_status("编辑日志未开启（editor.journal_edits）");
+            cx.notify();
+            return;
+        }
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let log = buffer_manager.journal(&path).await;
+                this.update(&mut app, |view, cx| match log {
+                    Ok(Some(log)) => {
+                        let name = path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        view.start_edit_replay(name, log, cx);
+                    }
+                    Ok(None) => {
+                        view.set_status("当前文件上次保存以来没有编辑");
+                        cx.notify();
+                    }
+                    Err(e) => {
+                        view.set_status(format!("无法读取编辑日志: {}", e));
+                        cx.notify();
+                    }
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 用上次崩溃前的编辑日志恢复当前文件未保存的内容，可以撤销
+    pub fn recover_from_journal(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let message = match buffer_manager.recover_from_journal(&path).await {
+                    Ok(true) => "已恢复上次退出前未保存的编辑，可撤销".to_string(),
+                    Ok(false) => "当前文件没有需要恢复的编辑".to_string(),
+                    Err(e) => format!("恢复失败: {}", e),
+                };
+                this.update(&mut app, |view, cx| {
+                    view.set_status(message);
+                    if view.current_file_path.as_ref() == Some(&path) {
+                        view.refresh_buffer_view(cx);
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn start_edit_replay(&mut self, name: String, log: EditLog, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let path = buffer_manager
+                    .open_remote(&format!("replay://{}", name), &log.initial_text)
+                    .await;
+                this.update(&mut app, |view, cx| {
+                    view.set_status(format!("回放 {}：共 {} 步", name, log.len()));
+                    view.current_file_path = Some(path.clone());
+                    view.edit_replay = Some(EditLogReplay {
+                        log,
+                        path,
+                        step: 0,
+                        error: None,
+                    });
+                    view.refresh_buffer_view(cx);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 把回放缓冲区重建为前 `step` 步编辑之后的内容，光标停在最后一步编辑处
+    fn replay_edit_log_to(&mut self, step: usize, cx: &mut Context<'_, Self>) {
+        let Some(replay) = self.edit_replay.as_mut() else {
+            return;
+        };
+        let mut replayer = replay.log.replay();
+        let mut last_edit = None;
+        let mut error = None;
+        while replayer.step() < step {
+            match replayer.apply_next() {
+                Ok(Some(entry)) => last_edit = Some(entry.edit.clone()),
+                Ok(None) => break,
+                Err(e) => {
+                    error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+        let text = replayer.text();
+        replay.step = replayer.step();
+        replay.error = error.clone();
+        let message = match (&error, &last_edit) {
+            (Some(e), _) => format!("回放停止：{}", e),
+            (None, Some(edit)) => format!(
+                "第 {}/{} 步：{}",
+                replay.step,
+                replay.log.len(),
+                edit.description()
+            ),
+            (None, None) => "回放到记录开始时的内容".to_string(),
+        };
+        let path = replay.path.clone();
+        self.set_status(message);
+
+        let replayed = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                buffer.set_text(&text).await;
+                buffer.mark_clean();
+                if let Some(edit) = &last_edit {
+                    let model = buffer.text_model();
+                    let char_idx = edit.char_idx().min(model.len().await);
+                    let line = model.char_to_line(char_idx).await;
+                    let column = char_idx - model.line_to_char(line).await;
+                    buffer
+                        .set_cursor(editor_core_text::Cursor::new(line, column))
+                        .await;
+                }
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                replayed.await;
+                this.update(&mut app, |view, cx| {
+                    if view.current_file_path.as_ref() == Some(&path) {
+                        view.refresh_buffer_view(cx);
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn render_edit_replay(
+        &self,
+        replay: &EditLogReplay,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        // 只列出当前步骤附近的编辑，长日志也能快速渲染
+        const CONTEXT_STEPS: usize = 20;
+
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x3a3a3a))
+                .cursor_pointer()
+                .child(label)
+        };
+        let step = replay.step;
+        let total = replay.log.len();
+        let mut panel = div()
+            .id("edit-replay-panel")
+            .w(px(360.0))
+            .flex()
+            .flex_col()
+            .overflow_scroll()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .child(
+                        div()
+                            .text_color(rgb(0x9ad1ff))
+                            .child(format!("Edit Replay {}/{}", step, total)),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .gap_1()
+                            .text_xs()
+                            .child(button("replay-start", "开头").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| view.replay_edit_log_to(0, cx),
+                            )))
+                            .child(button("replay-back", "上一步").on_click(cx.listener(
+                                move |view: &mut EditorView, _, _, cx| {
+                                    view.replay_edit_log_to(step.saturating_sub(1), cx)
+                                },
+                            )))
+                            .child(button("replay-next", "下一步").on_click(cx.listener(
+                                move |view: &mut EditorView, _, _, cx| {
+                                    view.replay_edit_log_to(step + 1, cx)
+                                },
+                            )))
+                            .child(button("replay-end", "末尾").on_click(cx.listener(
+                                move |view: &mut EditorView, _, _, cx| {
+                                    view.replay_edit_log_to(total, cx)
+                                },
+                            )))
+                            .child(button("replay-close", "关闭").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| {
+                                    view.edit_replay = None;
+                                    cx.notify();
+                                },
+                            ))),
+                    ),
+            );
+
+        if let Some(error) = &replay.error {
+            panel = panel.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_sm()
+                    .text_color(rgb(0xe57373))
+                    .child(error.clone()),
+            );
+        }
+
+        let first = step.saturating_sub(CONTEXT_STEPS);
+        let entries = replay.log.entries.iter().enumerate();
+        for (index, entry) in entries.skip(first).take(CONTEXT_STEPS * 2 + 1) {
+            // 第 index + 1 步；点击回放到这一步之后
+            let target = index + 1;
+            let color = if target == step {
+                rgb(0xffffff)
+            } else if target < step {
+                rgb(0x888888)
+            } else {
+                rgb(0x555555)
+            };
+            panel = panel.child(
+                div()
+                    .id(("replay-step", index as u64))
+                    .px_3()
+                    .py_1()
+                    .text_xs()
+                    .text_color(color)
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(format!("{:>4}  {}", target, entry.edit.description()))
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.replay_edit_log_to(target, cx)
+                    })),
+            );
+        }
+
+        panel
+    }
+}
@@ -1,72 +1,572 @@
-use crate::AIPanel;
-use editor_core_project::BufferManager;
-use editor_core_text::CursorMovement;
-use editor_infra::config::Config;
+use crate::composer_review::ComposerReview;
+use crate::document_tree::DocumentTreePanel;
+use crate::edit_preview::PendingPreview;
+use crate::edit_replay::EditLogReplay;
+use crate::hierarchy::HierarchyPanel;
+use crate::inline_thread::InlineThread;
+use crate::keymap::{
+    self, ArgumentAction, CursorAction, KeyCommand, KeyContext, LineAction, QuickInputMode,
+    TabClose,
+};
+use crate::notebook::NotebookSession;
+use crate::peek::{PeekView, PEEK_HEIGHT};
+use crate::problems::ProblemsPanel;
+use crate::pull_requests::PullRequestReview;
+use crate::settings_archive::PendingImport;
+use crate::source_control::{GitAction, SourceControl};
+use crate::table_mode::TableMode;
+use crate::tasks::{Refresh, RefreshTasks};
+use crate::{AIPanel, AIPanelTab, SyntaxTheme};
+use editor_ai::models::AIRole;
+use editor_ai::{AIPatch, ChangeStatus, CodeIndex};
+use editor_core_project::{
+    changed_words, edit_preview, is_remote_url, language_from_path, AgentEditEvent, BlameLine,
+    BufferManager, BufferMemoryReport, ClosedTab, DeleteMode, DiffLine, DiskChangeEvent,
+    EditJournals, EditPreview, ExternalFormatter, FileChangeKind, FileInfo, FileJournal,
+    FileOperation, FileReference, FileStatus, FileTree, FileTreeEvent, FileTreeNode,
+    FileTreeOptions, FileWatcher, ForgeClient, ForgeRepository, GitRepository, GitStatus,
+    HealthReport, IssueLinker, IssueReference, LocalHistory, ProjectSearchResults, PullRequest,
+    RecentEntry, RecentHistory, RemoteFetcher, Snapshot, ToolRun, WalkOptions, WalkSummary,
+    Workspace,
+};
+use editor_core_text::{
+    Anchor, ArgumentLayout, Bias, BufferStats, CaseTransform, ClipboardEntry, ClipboardRing,
+    CursorMovement, Highlighter, IndentStyle, LineChange, LineChangeKind, LineDiff, LineDirection,
+    LineEnding, LineMap, MarkName, SearchMatch, SearchOptions, SyntaxSpan, Table, TextModel,
+    WrapLayout,
+};
+use editor_infra::config::{Config, FormatterConfig, PerformanceModeConfig};
+use editor_infra::{
+    DeepLink, LanguagePack, Metric, Metrics, Session, SessionTab, TaskExecutor, WorkflowScheduler,
+};
+use editor_lsp::request_gate::DEFAULT_DEBOUNCE;
+use editor_lsp::{path_to_uri, untitled_uri};
+use editor_lsp::{
+    CodeAction, CompletionItem, CrateVersionHint, Diagnostic, DiagnosticSeverity, FileChangeType,
+    InstallMethod, LspServerManager, MissingServer, Position, ServerCrash, ServerLog,
+    WorkspaceEdit,
+};
+use futures::FutureExt;
 use gpui::{
-    div, prelude::*, px, rgb, AppContext, AsyncApp, Context, Entity, HighlightStyle,
+    div, prelude::*, px, rgb, App, AppContext, AsyncApp, Context, Entity, HighlightStyle,
     InteractiveElement, KeystrokeEvent, MouseButton, MouseDownEvent, MouseMoveEvent, MouseUpEvent,
-    Pixels, Point, StatefulInteractiveElement, StyledText, WeakEntity, Window,
+    Pixels, Point, StatefulInteractiveElement, StyledText, Task, UnderlineStyle, WeakEntity,
+    Window,
 };
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use unicode_width::UnicodeWidthChar;
 
-pub struct EditorView {
+const MAX_INDEXED_FILE_BYTES: u64 = 256 * 1024;
+const WORKSPACE_SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+pub(crate) const SKIPPED_DIRS: [&str; 2] = ["target", "node_modules"];
+const QUICK_OPEN_RESULTS: usize = 12;
+/// 欢迎页列出的最近工作区和文件各多少个
+const WELCOME_RECENT_ENTRIES: usize = 5;
+/// 跳转到 AI 引用的代码后，被引用的行高亮多久
+const LINE_FLASH_DURATION: Duration = Duration::from_millis(900);
+/// 逐行 Blame 在行号前占的列数：提交号、作者和一个空格
+const BLAME_GUTTER_COLUMNS: usize = 7 + 1 + 14 + 1;
+
+/// 诊断范围两端的锚点，连同锚点所在的文本模型
+type DiagnosticAnchors = (Arc<TextModel>, Vec<(Anchor, Anchor)>);
+
+/// 格式化一个文件所需的状态，可移入后台任务
+struct FormatJob {
     buffer_manager: BufferManager,
-    config: Config,
-    current_file_path: Option<PathBuf>,
-    open_files: Vec<PathBuf>,
-    lines: Vec<String>,
-    line_prefix_widths: Vec<Vec<f32>>,
+    lsp_manager: Arc<LspServerManager>,
+    executor: TaskExecutor,
+    formatters: Vec<FormatterConfig>,
+    path: PathBuf,
+    language: String,
+    tab_size: usize,
+    insert_spaces: bool,
+    /// 只格式化这个选区
     selection: Option<editor_core_text::Selection>,
-    is_dirty: bool,
+}
+
+impl FormatJob {
+    /// 优先使用语言服务器，没有时回退到配置的外部命令；返回使用的格式化工具，
+    /// 都没有时返回 None。格式化期间内容有修改则放弃结果
+    async fn run(self) -> anyhow::Result<Option<String>> {
+        let Some(handle) = self.buffer_manager.get_buffer(&self.path).await else {
+            return Ok(None);
+        };
+        let (snapshot, selected) = {
+            let buffer = handle.lock().await;
+            let selected = match &self.selection {
+                Some(selection) => Some(buffer.get_text_in_selection(selection).await),
+                None => None,
+            };
+            (buffer.snapshot().await, selected)
+        };
+        let text = snapshot.text();
+        let range = self.selection.map(|selection| {
+            editor_lsp::Range::from_cursors(snapshot.rope(), selection.start(), selection.end())
+        });
+
+        let from_server = {
+            let manager = self.lsp_manager.clone();
+            let language = self.language.clone();
+            let uri = EditorView::document_uri(&self.buffer_manager, &self.path).await;
+            let (tab_size, insert_spaces) = (self.tab_size, self.insert_spaces);
+            self.executor
+                .spawn(async move {
+                    match range {
+                        Some(range) => {
+                            manager
+                                .format_range(&language, &uri, range, tab_size, insert_spaces)
+                                .await
+                        }
+                        None => {
+                            manager
+                                .format_document(&language, &uri, tab_size, insert_spaces)
+                                .await
+                        }
+                    }
+                })
+                .await??
+        };
+        let (formatted, tool) = match from_server {
+            Some(edits) => (
+                editor_lsp::TextEdit::apply_all(&text, &edits),
+                format!("{} 语言服务器", self.language),
+            ),
+            None => {
+                let Some(formatter) =
+                    ExternalFormatter::for_language(&self.formatters, &self.language)
+                else {
+                    return Ok(None);
+                };
+                let tool = formatter.command.clone();
+                let path = self.path.clone();
+                let input = selected.clone().unwrap_or_else(|| text.clone());
+                // tokio::process 需要 tokio 运行时
+                let formatted = self
+                    .executor
+                    .spawn(async move { formatter.format(&input, &path).await })
+                    .await??;
+                match (self.selection, selected) {
+                    // 只把格式化后的选区文本放回原处；选区没有以换行结尾时不多出换行
+                    (Some(selection), Some(selected)) => {
+                        let formatted = if selected.ends_with('\n') {
+                            formatted.as_str()
+                        } else {
+                            formatted.trim_end_matches(['\n', '\r'])
+                        };
+                        let start = snapshot.cursor_to_char(selection.start());
+                        let end = snapshot.cursor_to_char(selection.end());
+                        let mut rope = snapshot.rope().clone();
+                        rope.remove(start..end);
+                        rope.insert(start, formatted);
+                        (rope.to_string(), tool)
+                    }
+                    _ => (formatted, tool),
+                }
+            }
+        };
+
+        let applied = self.buffer_manager.submit_edit(&self.path, move |buffer| {
+            async move {
+                if buffer.version() != snapshot.version() {
+                    anyhow::bail!("格式化期间内容已修改，结果已丢弃");
+                }
+                let formatted = buffer.line_ending().normalize(&formatted).into_owned();
+                if formatted != text {
+                    buffer.reload(&formatted).await;
+                }
+                Ok(())
+            }
+            .boxed()
+        });
+        match applied.await {
+            Some(result) => result.map(|()| Some(tool)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// 查找范围：开始查找时的选区，随编辑移动，查找和替换只在其中进行
+struct SearchScope {
+    model: Arc<TextModel>,
+    range_ids: Vec<u64>,
+    /// 上一次查找时各选区的位置，用于绘制
+    ranges: Vec<std::ops::Range<editor_core_text::Cursor>>,
+}
+
+/// 短暂高亮的几行，跳转后提示引用的位置
+struct LineFlash {
+    path: PathBuf,
+    lines: std::ops::Range<usize>,
+}
+
+pub struct EditorView {
+    pub(crate) buffer_manager: BufferManager,
+    pub(crate) config: Config,
+    pub(crate) current_file_path: Option<PathBuf>,
+    open_files: Vec<PathBuf>,
+    pub(crate) display_names: HashMap<PathBuf, String>,
+    pub(crate) current_language: Option<String>,
+    current_read_only: bool,
+    current_write_protected: bool,
+    current_file_info: Option<FileInfo>,
+    current_line_ending: LineEnding,
+    /// 从当前文件内容推断的缩进方式，推断不出时用配置
+    current_indent: Option<IndentStyle>,
+    read_only_files: HashSet<PathBuf>,
+    pinned_files: HashSet<PathBuf>,
+    /// 磁盘上已被修改、又有未保存修改的文件
+    conflicted_files: HashSet<PathBuf>,
+    /// 还没有保存过的缓冲区，在语言服务器中以 untitled: URI 打开
+    untitled_files: HashSet<PathBuf>,
+    pub(crate) lines: Vec<String>,
+    pub(crate) line_prefix_widths: Vec<Vec<f32>>,
+    pub(crate) selection: Option<editor_core_text::Selection>,
+    /// 多光标编辑时的全部选区
+    pub(crate) selections: Vec<editor_core_text::Selection>,
+    /// 光标处括号与其配对括号的位置
+    bracket_match: Option<(editor_core_text::Cursor, editor_core_text::Cursor)>,
+    pub(crate) is_dirty: bool,
     status_message: String,
     show_ai_panel: bool,
-    ai_panel: Option<Entity<AIPanel>>,
-    ai_engine: Arc<editor_ai::AIEngine>,
-    quick_open_active: bool,
-    quick_open_input: String,
+    pub(crate) ai_panel: Option<Entity<AIPanel>>,
+    pub(crate) ai_engine: Arc<editor_ai::AIEngine>,
+    pub(crate) code_index: Option<Arc<CodeIndex>>,
+    pub(crate) quick_open_active: bool,
+    pub(crate) quick_open_input: String,
+    pub(crate) quick_input_mode: QuickInputMode,
     ai_prompt_input: String,
     ai_input_focused: bool,
-    scroll_handle: gpui::ScrollHandle,
+    pub(crate) scroll_handle: gpui::ScrollHandle,
     dragging_selection: bool,
+    rendered_version: usize,
+    watched_path: Option<PathBuf>,
+    buffer_watch: Option<Task<anyhow::Result<()>>>,
+    follow_agent_edits: bool,
+    agent_touched_files: Vec<(PathBuf, usize)>,
+    agent_run_finished: bool,
+    agent_watch: Option<Task<anyhow::Result<()>>>,
+    pub(crate) show_local_history: bool,
+    pub(crate) history_snapshots: Vec<Snapshot>,
+    pub(crate) history_diff: Option<(usize, Vec<DiffLine>)>,
+    pub(crate) pending_import: Option<PendingImport>,
+    pub(crate) task_executor: TaskExecutor,
+    refresh_tasks: RefreshTasks,
+    /// 语言服务器和 AI 各自在独立的后台线程池上运行，卡住时不影响界面渲染和彼此
+    pub(crate) lsp_executor: TaskExecutor,
+    pub(crate) ai_executor: TaskExecutor,
+    pub(crate) workflow_scheduler: WorkflowScheduler,
+    pub(crate) scheduler_handle: Option<tokio::task::JoinHandle<()>>,
+    pub(crate) show_workflows: bool,
+    pub(crate) workflows_ticker: Option<Task<anyhow::Result<()>>>,
+    /// 当前工作区所在的 Git 仓库，打开源代码管理面板或 Git 命令时读取
+    pub(crate) source_control: Option<SourceControl>,
+    pub(crate) show_source_control: bool,
+    /// 工作区的分支和改动的文件，文件变化时刷新
+    git_status: Option<GitStatus>,
+    /// 当前文件在 HEAD 中的内容，None 表示 HEAD 中没有这个文件
+    git_base: Option<(PathBuf, Option<String>)>,
+    /// 当前缓冲区相对 HEAD 改动的行
+    git_line_changes: LineDiff,
+    /// 当前文件已保存内容的逐行 Blame，改动未保存时行号对不上，不显示
+    blame: Option<(PathBuf, Vec<BlameLine>)>,
+    /// 在行号前显示每行最后修改它的提交
+    show_blame_gutter: bool,
+    /// 等待再执行一次确认的 Git 命令
+    pub(crate) pending_git_action: Option<GitAction>,
+    /// origin 所在的托管平台，第一次列出 PR/MR 时连接
+    pub(crate) forge: Option<ForgeClient>,
+    pub(crate) pull_requests: Vec<PullRequest>,
+    pub(crate) pull_request_review: Option<PullRequestReview>,
+    /// 注释中问题编号的链接，工作区变化时按配置和 origin 重新生成
+    issue_linker: Option<IssueLinker>,
+    /// 鼠标下的问题编号及其所在行
+    hovered_issue: Option<(usize, IssueReference)>,
+    /// 按编号缓存的问题标题，读取中或失败时是提示
+    issue_titles: HashMap<u64, String>,
+    /// 本地性能指标，只在诊断面板中展示，不会上传
+    pub(crate) metrics: Metrics,
+    /// 尚未渲染的第一个按键的时间
+    keystroke_started: Option<Instant>,
+    pub(crate) show_metrics: bool,
+    pub(crate) metrics_ticker: Option<Task<anyhow::Result<()>>>,
+    /// 内存诊断面板的数据，面板关闭时为 None
+    pub(crate) memory_report: Option<Vec<BufferMemoryReport>>,
+    pub(crate) cache_eviction: Option<Task<anyhow::Result<()>>>,
+    /// 最近一次在编辑区按键或点击的时间，据此判断是否闲置
+    pub(crate) last_input: Instant,
+    /// 最近一次生成的工作区健康报告
+    pub(crate) health_report: Option<HealthReport>,
+    pub(crate) show_health_report: bool,
+    /// 正在生成的健康报告和开始生成的时间
+    pub(crate) health_scan: Option<Task<anyhow::Result<()>>>,
+    pub(crate) health_scanned_at: Option<Instant>,
+    pub(crate) health_idle_watch: Option<Task<anyhow::Result<()>>>,
+    /// 会话检查点：定时写入的任务和正在进行的写入
+    session_checkpoints: Option<Task<anyhow::Result<()>>>,
+    session_checkpoint: Option<Task<()>>,
+    /// 启动时的会话恢复完成前不写检查点，免得覆盖还没读完的会话
+    session_restored: bool,
+    /// 各标签页切走时的滚动位置，切回和写入会话时使用
+    tab_scroll_lines: HashMap<PathBuf, usize>,
+    /// 当前工作区开着性能模式，关闭的功能见 `editor.performance_mode`
+    performance_mode: bool,
+    /// 最近打开的文件和工作区，快速打开和欢迎页列出
+    pub(crate) recent: RecentHistory,
+    /// 第一帧画出后才启动文件树扫描、文件监视等后台任务
+    first_frame_drawn: bool,
+    /// 语言服务器在第一次打开有对应配置的文件时才启动
+    language_servers_checked: bool,
+    remote_fetcher: RemoteFetcher,
+    deep_link_watch: Option<Task<anyhow::Result<()>>>,
+    reference_candidates: Vec<PathBuf>,
+    reference_position: (usize, usize),
+    file_journal: FileJournal,
+    pub(crate) search_options: SearchOptions,
+    /// 工作区查找的文本和结果，结果面板关闭时为 None
+    pub(crate) workspace_search: Option<(String, ProjectSearchResults)>,
+    pub(crate) workspace_search_task: Option<Task<anyhow::Result<()>>>,
+    search_matches: Vec<SearchMatch>,
+    /// 最近一次查找的内容，编辑后按它刷新匹配
+    search_query: Option<String>,
+    search_scope: Option<SearchScope>,
+    /// 当前缓冲区的字数、行数与选区统计，显示在状态栏
+    buffer_stats: Option<BufferStats>,
+    file_tree: Option<FileTree>,
+    /// 在文件树中右键点开操作行的节点
+    tree_menu: Option<PathBuf>,
+    pub(crate) workspace_files: Vec<PathBuf>,
+    workspace_scan: Option<Task<anyhow::Result<()>>>,
+    scan_banner: Option<String>,
+    missing_servers: Vec<MissingServer>,
+    installing_server: bool,
+    pub(crate) lsp_manager: Arc<LspServerManager>,
+    lsp_crash_watch: Option<Task<anyhow::Result<()>>>,
+    server_crash: Option<ServerCrash>,
+    /// 最近一次输出到面板的外部工具名称与结果
+    pub(crate) tool_output: Option<(String, ToolRun)>,
+    pub(crate) apply_edit_watch: Option<Task<anyhow::Result<()>>>,
+    pub(crate) edit_preview: Option<PendingPreview>,
+    pub(crate) code_actions: Vec<CodeAction>,
+    completions: Vec<CompletionItem>,
+    /// 复制和剪切过的文本，最新的在前
+    pub(crate) clipboard: ClipboardRing,
+    pub(crate) edit_replay: Option<EditLogReplay>,
+    /// 正在补全的词：词首和请求补全时的光标
+    completion_word: Option<(editor_core_text::Cursor, editor_core_text::Cursor)>,
+    /// 当前文件的诊断，按语言服务器发布的顺序；列已换算为字符
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    /// 与 diagnostics 一一对应，输入时诊断随之移动；保存文本模型以便换掉时释放锚点
+    diagnostic_anchors: Option<DiagnosticAnchors>,
+    /// Cargo.toml 中各依赖的最新版本，显示在依赖所在行的末尾
+    crate_hints: Vec<CrateVersionHint>,
+    diagnostics_watch: Option<Task<anyhow::Result<()>>>,
+    pub(crate) problems: Option<ProblemsPanel>,
+    /// 当前缓冲区的书签，按位置排序
+    bookmarks: Vec<(MarkName, editor_core_text::Cursor)>,
+    /// 当前缓冲区相对磁盘文件的改动，用于行号旁的标记
+    line_changes: LineDiff,
+    /// 未保存修改的审阅面板：各文件的改动块
+    unsaved_review: Option<Vec<(PathBuf, Vec<LineChange>)>>,
+    pub(crate) composer_review: Option<ComposerReview>,
+    pub(crate) peek: Option<PeekView>,
+    pub(crate) hierarchy: Option<HierarchyPanel>,
+    /// 当前打开文件中锚定在代码范围上的对话
+    pub(crate) inline_threads: Vec<InlineThread>,
+    /// 点击对话的“回复”后，下一次提问发给这个对话
+    pub(crate) thread_reply_target: Option<u64>,
+    /// 轮询工作区文件变化并转发给语言服务器
+    file_watch: Option<Task<anyhow::Result<()>>>,
+    pub(crate) notebook: Option<NotebookSession>,
+    pub(crate) table_mode: Option<TableMode>,
+    pub(crate) document_tree: Option<DocumentTreePanel>,
+    line_flash: Option<LineFlash>,
+    /// 到时清除 `line_flash`，新的高亮替换它时取消
+    line_flash_timer: Option<Task<anyhow::Result<()>>>,
+    /// `ui.theme` 的语法配色，AI 面板的代码块也用它
+    pub(crate) syntax_theme: SyntaxTheme,
+    /// 当前缓冲区每行的语法高亮，随行内容一起更新
+    syntax_spans: Vec<Vec<SyntaxSpan>>,
 }
 
 impl EditorView {
     pub fn new(_cx: &mut Context<'_, Self>) -> Self {
         let config = Config::default();
-        let ai_engine = Arc::new(editor_ai::AIEngine::new(config.ai.clone()));
+        let syntax_theme = SyntaxTheme::named(&config.ui.theme).unwrap_or_default();
+        let metrics = Metrics::default();
+        let ai_engine =
+            Arc::new(editor_ai::AIEngine::new(config.ai.clone()).with_metrics(metrics.clone()));
+        let workflow_scheduler = WorkflowScheduler::new(&config);
+        let clipboard = ClipboardRing::new(config.editor.clipboard_history_size);
+        let mut buffer_manager = BufferManager::new()
+            .with_local_history(LocalHistory::new(LocalHistory::default_location()))
+            .with_edit_log(config.editor.record_edit_log);
+        if config.editor.journal_edits {
+            buffer_manager = buffer_manager
+                .with_edit_journals(EditJournals::new(EditJournals::default_location()));
+        }
 
         Self {
-            buffer_manager: BufferManager::new(),
+            buffer_manager,
             config,
             current_file_path: None,
             open_files: Vec::new(),
+            display_names: HashMap::new(),
+            current_language: None,
+            current_read_only: false,
+            current_write_protected: false,
+            current_file_info: None,
+            current_line_ending: LineEnding::default(),
+            current_indent: None,
+            read_only_files: HashSet::new(),
+            pinned_files: HashSet::new(),
+            conflicted_files: HashSet::new(),
+            untitled_files: HashSet::new(),
             lines: Vec::new(),
             line_prefix_widths: Vec::new(),
+            bracket_match: None,
             selection: None,
+            selections: Vec::new(),
             is_dirty: false,
             status_message: "Bootstrapping workspace…".to_string(),
             show_ai_panel: false,
             ai_panel: None,
             ai_engine,
+            code_index: None,
             quick_open_active: false,
             quick_open_input: String::new(),
+            quick_input_mode: QuickInputMode::OpenPath,
             ai_prompt_input: String::new(),
             ai_input_focused: false,
             scroll_handle: gpui::ScrollHandle::new(),
             dragging_selection: false,
+            rendered_version: 0,
+            watched_path: None,
+            buffer_watch: None,
+            follow_agent_edits: false,
+            agent_touched_files: Vec::new(),
+            agent_run_finished: false,
+            agent_watch: None,
+            show_local_history: false,
+            history_snapshots: Vec::new(),
+            history_diff: None,
+            pending_import: None,
+            task_executor: TaskExecutor::new(),
+            refresh_tasks: RefreshTasks::default(),
+            lsp_executor: TaskExecutor::service("lsp", 2),
+            ai_executor: TaskExecutor::service("ai", 2),
+            workflow_scheduler,
+            scheduler_handle: None,
+            show_workflows: false,
+            workflows_ticker: None,
+            source_control: None,
+            show_source_control: false,
+            git_status: None,
+            git_base: None,
+            git_line_changes: LineDiff::default(),
+            blame: None,
+            show_blame_gutter: false,
+            pending_git_action: None,
+            forge: None,
+            pull_requests: Vec::new(),
+            pull_request_review: None,
+            issue_linker: None,
+            hovered_issue: None,
+            issue_titles: HashMap::new(),
+            metrics: metrics.clone(),
+            keystroke_started: None,
+            show_metrics: false,
+            metrics_ticker: None,
+            memory_report: None,
+            cache_eviction: None,
+            last_input: Instant::now(),
+            health_report: None,
+            show_health_report: false,
+            health_scan: None,
+            health_scanned_at: None,
+            health_idle_watch: None,
+            session_checkpoints: None,
+            session_checkpoint: None,
+            session_restored: false,
+            tab_scroll_lines: HashMap::new(),
+            performance_mode: false,
+            recent: RecentHistory::default(),
+            first_frame_drawn: false,
+            language_servers_checked: false,
+            remote_fetcher: RemoteFetcher::new(RemoteFetcher::default_location()),
+            deep_link_watch: None,
+            reference_candidates: Vec::new(),
+            reference_position: (0, 0),
+            file_journal: FileJournal::new(FileJournal::default_location()),
+            search_options: SearchOptions::default(),
+            workspace_search: None,
+            workspace_search_task: None,
+            search_matches: Vec::new(),
+            search_query: None,
+            search_scope: None,
+            buffer_stats: None,
+            file_tree: None,
+            tree_menu: None,
+            workspace_files: Vec::new(),
+            workspace_scan: None,
+            scan_banner: None,
+            missing_servers: Vec::new(),
+            installing_server: false,
+            lsp_manager: Arc::new(LspServerManager::new().with_metrics(metrics)),
+            lsp_crash_watch: None,
+            inline_threads: Vec::new(),
+            notebook: None,
+            table_mode: None,
+            document_tree: None,
+            line_flash: None,
+            line_flash_timer: None,
+            syntax_theme,
+            syntax_spans: Vec::new(),
+            thread_reply_target: None,
+            file_watch: None,
+            server_crash: None,
+            tool_output: None,
+            apply_edit_watch: None,
+            edit_preview: None,
+            code_actions: Vec::new(),
+            completions: Vec::new(),
+            clipboard,
+            edit_replay: None,
+            completion_word: None,
+            diagnostics: Vec::new(),
+            diagnostic_anchors: None,
+            crate_hints: Vec::new(),
+            diagnostics_watch: None,
+            problems: None,
+            bookmarks: Vec::new(),
+            line_changes: LineDiff::default(),
+            unsaved_review: None,
+            composer_review: None,
+            peek: None,
+            hierarchy: None,
         }
     }
 
-    /// 启动时加载 README.md 或创建新的缓冲区，并写入欢迎文案
+    /// 启动时恢复工作区上次会话的标签页、光标、滚动位置和草稿；没有可恢复的标签页时加载 README.md 或创建新的缓冲区，
+    /// 并写入欢迎文案。其余后台任务等第一帧画出后再启动，见 [`EditorView::start_background_services`]
     pub fn initialize(&mut self, cx: &mut Context<'_, Self>) {
+        self.watch_agent_edits(cx);
         let buffer_manager = self.buffer_manager.clone();
-        let welcome = Self::welcome_text();
         let tab_size = self.config.editor.tab_size;
-        let repo_readme = std::env::current_dir()
-            .ok()
+        let workspace_root = std::env::current_dir().ok();
+        let configured_performance_mode = workspace_root.as_ref().is_some_and(|root| {
+            self.config
+                .editor
+                .performance_mode
+                .workspaces
+                .iter()
+                .any(|dir| root.starts_with(dir))
+        });
+        let repo_readme = workspace_root
+            .clone()
             .map(|mut dir| {
                 dir.push("README.md");
                 dir
@@ -76,7 +576,55 @@ impl EditorView {
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
             async move {
-                let target_path = if let Some(path) = repo_readme {
+                let session = match &workspace_root {
+                    Some(root) => Session::load_workspace(root),
+                    None => Session::load(&Session::default_path()),
+                }
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to load session: {}", e);
+                    Session::default()
+                });
+                let mut recent = RecentHistory::load(&RecentHistory::default_location())
+                    .unwrap_or_else(|e| {
+                        log::warn!("Failed to load recent files: {}", e);
+                        RecentHistory::default()
+                    });
+                if let Some(root) = &workspace_root {
+                    recent.record_workspace(root);
+                }
+                let welcome = Self::welcome_text(&recent);
+                let performance_mode = session.performance_mode || configured_performance_mode;
+                let mut restored = None;
+                let mut scroll_lines = HashMap::new();
+                for tab in session.open_tabs {
+                    if !tab.untitled && !tab.path.exists() {
+                        continue;
+                    }
+                    let saved_path = tab.path.clone();
+                    match buffer_manager.restore_tab(ClosedTab::from(tab)).await {
+                        Ok(tab) => {
+                            scroll_lines.insert(tab.path.clone(), tab.scroll_line);
+                            if restored.is_none() || session.current.as_ref() == Some(&saved_path) {
+                                restored = Some((tab.path, tab.scroll_line));
+                            }
+                        }
+                        Err(e) => log::warn!("Failed to restore {}: {}", saved_path.display(), e),
+                    }
+                }
+                for path in session.pinned_tabs.iter().filter(|path| path.exists()) {
+                    if buffer_manager.get_buffer(path).await.is_some()
+                        || buffer_manager.open_file(path).await.is_ok()
+                    {
+                        let _ = buffer_manager.set_pinned(path, true).await;
+                    }
+                }
+
+                let mut scroll_line = 0;
+                let target_path = if let Some((path, line)) = restored {
+                    let _ = buffer_manager.set_current_buffer(&path).await;
+                    scroll_line = line;
+                    path
+                } else if let Some(path) = repo_readme {
                     match buffer_manager.open_file(&path).await {
                         Ok(_) => path,
                         Err(_) => buffer_manager.create_new_buffer().await,
@@ -85,12 +633,16 @@ impl EditorView {
                     buffer_manager.create_new_buffer().await
                 };
 
-                if let Some(buffer_handle) = buffer_manager.get_buffer(&target_path).await {
-                    let mut buffer = buffer_handle.lock().await;
-                    if buffer.get_text().await.is_empty() {
-                        buffer.insert_text_at_cursor(&welcome).await;
-                    }
-                }
+                buffer_manager
+                    .submit_edit(&target_path, move |buffer| {
+                        async move {
+                            if buffer.get_text().await.is_empty() {
+                                buffer.insert_text_at_cursor(&welcome).await;
+                            }
+                        }
+                        .boxed()
+                    })
+                    .await;
 
                 let _snapshot =
                     if let Some(buffer_handle) = buffer_manager.get_buffer(&target_path).await {
@@ -101,7 +653,7 @@ impl EditorView {
                     };
 
                 let open_files = buffer_manager.get_open_files().await;
-                let (lines, selection, is_dirty, widths) =
+                let (lines, selections, is_dirty, widths) =
                     Self::snapshot_buffer(&buffer_manager, tab_size)
                         .await
                         .unwrap_or_default();
@@ -111,9 +663,19 @@ impl EditorView {
                     view.open_files = open_files;
                     view.lines = lines;
                     view.line_prefix_widths = widths;
-                    view.selection = selection;
+                    view.set_selections(selections);
                     view.is_dirty = is_dirty;
+                    view.scroll_to_top_line(scroll_line);
+                    view.tab_scroll_lines = scroll_lines;
+                    view.recent = recent;
+                    view.save_recent(cx);
+                    view.session_restored = true;
+                    if performance_mode {
+                        view.performance_mode = true;
+                        view.apply_performance_mode(cx);
+                    }
                     view.status_message = "Workspace ready".to_string();
+                    view.refresh_buffer_view(cx);
                     cx.notify();
                 });
 
@@ -128,187 +690,4882 @@ impl EditorView {
         tab_size: usize,
     ) -> Option<(
         Vec<String>,
-        Option<editor_core_text::Selection>,
+        Vec<editor_core_text::Selection>,
         bool,
         Vec<Vec<f32>>,
     )> {
         let handle = buffer_manager.get_current_buffer().await?;
-        let buffer = handle.lock().await;
-        let line_count = buffer.line_count().await;
+        let (snapshot, is_dirty) = {
+            let buffer = handle.lock().await;
+            (buffer.snapshot().await, buffer.is_dirty())
+        };
+        let line_count = snapshot.line_count();
         let mut lines = Vec::with_capacity(line_count);
         let mut widths = Vec::with_capacity(line_count);
         for i in 0..line_count {
-            if let Some(line) = buffer.get_line(i).await {
-                let mut prefix = Vec::with_capacity(line.chars().count());
-                let mut acc = 0.0f32;
-                for ch in line.chars() {
-                    let w_units = if ch == '\t' {
-                        tab_size as f32
-                    } else {
-                        UnicodeWidthChar::width(ch).unwrap_or(1) as f32
-                    };
-                    acc += w_units;
-                    prefix.push(acc);
-                }
+            if let Some(line) = snapshot.line(i) {
+                widths.push(Self::prefix_widths(&line, tab_size));
                 lines.push(line);
-                widths.push(prefix);
             }
         }
-        let selection = buffer.get_selections().first().cloned();
-        let is_dirty = buffer.is_dirty();
-        Some((lines, selection, is_dirty, widths))
+        Some((lines, snapshot.selections().to_vec(), is_dirty, widths))
+    }
+
+    fn prefix_widths(line: &str, tab_size: usize) -> Vec<f32> {
+        let mut prefix = Vec::with_capacity(line.chars().count());
+        let mut acc = 0.0f32;
+        for ch in line.chars() {
+            let w_units = if ch == '\t' {
+                tab_size as f32
+            } else {
+                UnicodeWidthChar::width(ch).unwrap_or(1) as f32
+            };
+            acc += w_units;
+            prefix.push(acc);
+        }
+        prefix
     }
 
-    fn welcome_text() -> String {
-        [
+    /// 欢迎页：快捷键说明和最近的工作区、文件
+    fn welcome_text(recent: &RecentHistory) -> String {
+        let mut lines: Vec<String> = [
             "// Fusang · Cursor-inspired shell",
             "// - 左侧切换打开的文件",
             "// - 编辑区直接键入，内容写入 BufferManager",
             "// - Cmd+S 保存，Cmd+Z/Y 撤销/重做，Ctrl+Space 切换 AI 面板",
-            "",
         ]
-        .join("\n")
+        .map(String::from)
+        .to_vec();
+        // 第一个是刚打开的当前工作区
+        let workspaces: Vec<&RecentEntry> = recent
+            .workspaces()
+            .iter()
+            .skip(1)
+            .take(WELCOME_RECENT_ENTRIES)
+            .collect();
+        if !workspaces.is_empty() {
+            lines.push("//".to_string());
+            lines.push("// 最近的工作区（Cmd+Alt+O 切换）".to_string());
+            for entry in workspaces {
+                lines.push(format!("// - {}", entry.path.display()));
+            }
+        }
+        let files: Vec<&RecentEntry> = recent.files().iter().take(WELCOME_RECENT_ENTRIES).collect();
+        if !files.is_empty() {
+            lines.push("//".to_string());
+            lines.push("// 最近的文件".to_string());
+            for entry in files {
+                lines.push(format!(
+                    "// - {} · {}",
+                    entry.path.display(),
+                    Self::format_age(entry.opened_at)
+                ));
+            }
+        }
+        lines.push(String::new());
+        lines.join("\n")
     }
 
-    fn set_status(&mut self, message: impl Into<String>) {
-        self.status_message = message.into();
+    /// 在后台写入最近打开的文件和工作区
+    pub(crate) fn save_recent(&self, cx: &mut Context<'_, Self>) {
+        let recent = self.recent.clone();
+        cx.background_executor()
+            .spawn(async move {
+                if let Err(e) = recent.save(&RecentHistory::default_location()) {
+                    log::warn!("Failed to save recent files: {}", e);
+                }
+            })
+            .detach();
     }
 
-    fn refresh_buffer_view(&mut self, cx: &mut Context<'_, Self>) {
-        let buffer_manager = self.buffer_manager.clone();
-        let tab_size = self.config.editor.tab_size;
+    /// 最近的工作区中路径包含 `filter` 的，当前工作区除外
+    fn filtered_recent_workspaces(&self, filter: &str) -> Vec<PathBuf> {
+        let filter = filter.to_lowercase();
+        let current = std::env::current_dir().ok();
+        self.recent
+            .workspaces()
+            .iter()
+            .map(|entry| entry.path.clone())
+            .filter(|path| Some(path) != current.as_ref())
+            .filter(|path| path.to_string_lossy().to_lowercase().contains(&filter))
+            .collect()
+    }
 
-        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
-            let mut app = cx.clone();
+    fn open_recent_workspace(&mut self, path: PathBuf, cx: &mut Context<'_, Self>) {
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        if !path.is_dir() {
+            self.recent.remove(&path);
+            self.save_recent(cx);
+            self.set_status(format!("{} 已不存在", path.display()));
+            return;
+        }
+        self.switch_worktree(&path, cx);
+    }
+
+    fn render_recent_workspaces(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::RecentWorkspace {
+            return list;
+        }
+
+        for (idx, path) in self
+            .filtered_recent_workspaces(self.quick_open_input.trim())
+            .into_iter()
+            .take(QUICK_OPEN_RESULTS)
+            .enumerate()
+        {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string());
+            list = list.child(
+                div()
+                    .id(("recent-workspace", idx as u64))
+                    .flex()
+                    .justify_between()
+                    .gap_2()
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(name)
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x777777))
+                            .child(path.display().to_string()),
+                    )
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.open_recent_workspace(path.clone(), cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    pub(crate) fn set_status(&mut self, message: impl Into<String>) {
+        self.status_message = message.into();
+    }
 
+    /// 在后台跑一轮 `kind` 刷新。同一种刷新进行中时只记下请求，那一轮结束后用
+    /// `rerun` 按最新状态再跑一轮
+    fn spawn_refresh<F, Fut>(
+        &mut self,
+        kind: Refresh,
+        rerun: fn(&mut Self, &mut Context<'_, Self>),
+        cx: &mut Context<'_, Self>,
+        refresh: F,
+    ) where
+        F: FnOnce(WeakEntity<EditorView>, AsyncApp) -> Fut + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + 'static,
+    {
+        let Some(version) = self.refresh_tasks.begin(kind) else {
+            return;
+        };
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
             async move {
+                let result = refresh(this.clone(), app.clone()).await;
+                this.update(&mut app, |view, cx| {
+                    if view.refresh_tasks.finish(kind, version) {
+                        rerun(view, cx);
+                    }
+                })?;
+                result
+            }
+        });
+        self.refresh_tasks.track(kind, task);
+    }
+
+    pub(crate) fn refresh_buffer_view(&mut self, cx: &mut Context<'_, Self>) {
+        self.refresh_buffer_stats(cx);
+        let buffer_manager = self.buffer_manager.clone();
+        let tab_size = self.config.editor.tab_size;
+        let threads: Vec<(u64, PathBuf)> = self
+            .inline_threads
+            .iter()
+            .map(|thread| (thread.range_id, thread.path.clone()))
+            .collect();
+        let table = self
+            .table_mode
+            .as_ref()
+            .map(|table| (table.path.clone(), table.version));
+        let tree = self
+            .document_tree
+            .as_ref()
+            .map(|panel| (panel.path.clone(), panel.version));
+        let highlight = !self
+            .performance_mode()
+            .is_some_and(|mode| mode.disable_syntax_highlighting);
+
+        self.spawn_refresh(
+            Refresh::BufferView,
+            Self::refresh_buffer_view,
+            cx,
+            move |this: WeakEntity<EditorView>, mut app: AsyncApp| async move {
                 let open_files = buffer_manager.get_open_files().await;
                 let current_path = buffer_manager.get_current_file_path().await;
-                let (lines, selection, is_dirty, widths) =
+                let (lines, selections, is_dirty, widths) =
                     Self::snapshot_buffer(&buffer_manager, tab_size)
                         .await
                         .unwrap_or_default();
 
+                let mut display_names = HashMap::new();
+                let mut read_only_files = HashSet::new();
+                let mut pinned_files = HashSet::new();
+                let mut conflicted_files = HashSet::new();
+                let mut untitled_files = HashSet::new();
+                for path in &open_files {
+                    display_names.insert(path.clone(), buffer_manager.display_name(path).await);
+                    if buffer_manager.is_read_only(path).await {
+                        read_only_files.insert(path.clone());
+                    }
+                    if buffer_manager.is_pinned(path).await {
+                        pinned_files.insert(path.clone());
+                    }
+                    if buffer_manager.is_conflicted(path).await {
+                        conflicted_files.insert(path.clone());
+                    }
+                    if buffer_manager.is_untitled(path).await {
+                        untitled_files.insert(path.clone());
+                    }
+                }
+                let current_language = match &current_path {
+                    Some(path) => Some(buffer_manager.language(path).await),
+                    None => None,
+                };
+                let highlighter = highlight
+                    .then(|| Highlighter::for_language(current_language.as_deref().unwrap_or("")));
+                let (lines, syntax_spans) = app
+                    .background_executor()
+                    .spawn(async move {
+                        let spans = highlighter
+                            .map(|highlighter| {
+                                highlighter.highlight_lines(lines.iter().map(String::as_str))
+                            })
+                            .unwrap_or_default();
+                        (lines, spans)
+                    })
+                    .await;
+                let read_only = match &current_path {
+                    Some(path) => buffer_manager.is_read_only(path).await,
+                    None => false,
+                };
+                let write_protected = match &current_path {
+                    Some(path) => buffer_manager.is_write_protected(path).await,
+                    None => false,
+                };
+                let file_info = current_path
+                    .as_ref()
+                    .and_then(|path| FileInfo::read(path).ok());
+                let (version, line_ending, indent, bookmarks) =
+                    match buffer_manager.get_current_buffer().await {
+                        Some(handle) => {
+                            let buffer = handle.lock().await;
+                            (
+                                buffer.version(),
+                                buffer.line_ending(),
+                                buffer.indent_style(),
+                                buffer.marks().await,
+                            )
+                        }
+                        None => (0, LineEnding::default(), None, Vec::new()),
+                    };
+                // 缓冲区变了（编辑单元格、撤销、重新加载）就重新解析表格和结构视图；
+                // 结构视图还跟着切换到的文件
+                let table_stale = table.as_ref().is_some_and(|(path, parsed)| {
+                    current_path.as_ref() == Some(path) && *parsed != version
+                });
+                let tree_stale = tree.as_ref().is_some_and(|(path, parsed)| {
+                    current_path.as_ref() != Some(path) || *parsed != version
+                });
+                let text = match &current_path {
+                    Some(path) if table_stale || tree_stale => {
+                        match buffer_manager.get_buffer(path).await {
+                            Some(handle) => Some(handle.lock().await.get_text().await),
+                            None => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let mut thread_lines = HashMap::new();
+                for (range_id, path) in threads {
+                    if Some(&path) != current_path.as_ref() {
+                        continue;
+                    }
+                    if let Some(handle) = buffer_manager.get_buffer(&path).await {
+                        if let Some(range) = handle.lock().await.tracked_range(range_id).await {
+                            thread_lines.insert(range_id, Self::thread_line_span(&range));
+                        }
+                    }
+                }
+
                 let _ = this.update(&mut app, |view, cx| {
+                    // 打开、关闭、切换、固定标签页或保存后写一次检查点
+                    let layout_changed = view.open_files != open_files
+                        || view.pinned_files != pinned_files
+                        || view.current_file_path != current_path
+                        || view.is_dirty != is_dirty;
                     view.open_files = open_files.clone();
+                    view.display_names = display_names;
+                    view.current_language = current_language;
+                    view.ensure_language_servers(cx);
+                    view.current_read_only = read_only;
+                    view.current_write_protected = write_protected;
+                    view.current_file_info = file_info;
+                    view.current_line_ending = line_ending;
+                    view.current_indent = indent;
+                    view.bookmarks = bookmarks;
+                    // 文件关闭后跟踪的范围随缓冲区一起消失，对话也不再有意义
+                    view.inline_threads
+                        .retain(|thread| open_files.contains(&thread.path));
+                    for thread in &mut view.inline_threads {
+                        if let Some(lines) = thread_lines.remove(&thread.range_id) {
+                            thread.lines = lines;
+                        }
+                    }
+                    view.read_only_files = read_only_files;
+                    view.pinned_files = pinned_files;
+                    view.conflicted_files = conflicted_files;
+                    view.untitled_files = untitled_files;
+                    view.rendered_version = version;
+                    if view.watched_path != current_path {
+                        if let Some(path) = current_path.as_ref().filter(|path| path.is_file()) {
+                            view.recent.record_file(path);
+                            view.save_recent(cx);
+                        }
+                        view.watch_current_buffer(cx);
+                        view.line_changes = LineDiff::default();
+                        view.git_line_changes = LineDiff::default();
+                        view.diagnostics.clear();
+                        view.replace_diagnostic_anchors(None);
+                        view.clear_search();
+                        view.crate_hints.clear();
+                        view.refresh_diagnostics(cx);
+                        view.refresh_blame(cx);
+                    }
+                    if view
+                        .peek
+                        .as_ref()
+                        .is_some_and(|peek| Some(&peek.source) != current_path.as_ref())
+                    {
+                        view.peek = None;
+                    }
+                    if view
+                        .table_mode
+                        .as_ref()
+                        .is_some_and(|table| Some(&table.path) != current_path.as_ref())
+                    {
+                        view.table_mode = None;
+                    }
+                    if let (Some(table), Some(text)) = (
+                        view.table_mode
+                            .as_mut()
+                            .filter(|table| table.version != version),
+                        &text,
+                    ) {
+                        table.table = Table::parse(text, table.table.delimiter);
+                        table.version = version;
+                        table.selected.0 = table
+                            .selected
+                            .0
+                            .min(table.table.rows.len().saturating_sub(1));
+                    }
                     view.current_file_path = current_path.clone();
+                    if let Some(text) = &text {
+                        view.update_document_tree(text, version);
+                    }
                     view.line_prefix_widths = widths;
                     view.lines = lines;
-                    view.selection = selection;
+                    view.syntax_spans = syntax_spans;
+                    view.set_selections(selections);
                     view.is_dirty = is_dirty;
+                    view.refresh_bracket_match(cx);
+                    view.refresh_line_changes(cx);
+                    if layout_changed {
+                        view.checkpoint_session(cx);
+                    }
                     cx.notify();
                 });
 
                 anyhow::Ok(())
-            }
-        })
-        .detach();
+            },
+        );
     }
 
-    /// 打开文件
-    pub fn open_file(&mut self, file_path: &Path, cx: &mut Context<'_, Self>) {
+    /// 在后台重新比较当前缓冲区与磁盘文件，以及与 HEAD 中的版本
+    fn refresh_line_changes(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            self.line_changes = LineDiff::default();
+            self.git_line_changes = LineDiff::default();
+            return;
+        };
         let buffer_manager = self.buffer_manager.clone();
-        let path = file_path.to_path_buf();
-
-        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
-            let mut app = cx.clone();
-            let path_for_io = path.clone();
-
-            async move {
-                match buffer_manager.open_file(&path_for_io).await {
-                    Ok(_) => {
-                        let path_clone = path_for_io.clone();
-                        let _ = this.update(&mut app, |view, cx| {
-                            view.current_file_path = Some(path_clone);
-                            view.set_status("文件已打开");
-                            view.refresh_buffer_view(cx);
-                            cx.notify();
-                        });
+        let executor = self.task_executor.clone();
+        let repository = self
+            .git_status
+            .as_ref()
+            .and_then(|_| std::env::current_dir().ok())
+            .and_then(|dir| GitRepository::discover(&dir));
+        // 被忽略的文件也不在 HEAD 中，只有未跟踪和新增的文件整个算作新增
+        let new_file = self
+            .git_status
+            .as_ref()
+            .and_then(|status| status.file(&path))
+            .is_some_and(|status| matches!(status, FileStatus::Added | FileStatus::Untracked));
+        let base = self
+            .git_base
+            .clone()
+            .filter(|(base_path, _)| *base_path == path)
+            .map(|(_, text)| text);
+
+        self.spawn_refresh(
+            Refresh::LineChanges,
+            Self::refresh_line_changes,
+            cx,
+            move |this: WeakEntity<EditorView>, mut app: AsyncApp| async move {
+                let diff_path = path.clone();
+                let git_path = path.clone();
+                let git_buffers = buffer_manager.clone();
+                let diff = executor
+                    .spawn(async move { buffer_manager.unsaved_changes(&diff_path).await })
+                    .await
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default();
+                // tokio::process 需要 tokio 运行时
+                let git = executor
+                    .spawn(async move {
+                        let repository = repository?;
+                        let base = match base {
+                            Some(base) => base,
+                            None => repository.head_text(&git_path).await.ok()?,
+                        };
+                        if base.is_none() && !new_file {
+                            return Some((base, LineDiff::default()));
+                        }
+                        let handle = git_buffers.get_buffer(&git_path).await?;
+                        let text = handle.lock().await.get_text().await;
+                        let diff = LineDiff::new(
+                            &LineEnding::Lf.normalize(base.as_deref().unwrap_or_default()),
+                            &LineEnding::Lf.normalize(&text),
+                        );
+                        Some((base, diff))
+                    })
+                    .await
+                    .ok()
+                    .flatten();
+                this.update(&mut app, |view, cx| {
+                    if view.current_file_path.as_ref() == Some(&path) {
+                        view.line_changes = diff;
+                        match git {
+                            Some((base, git_diff)) => {
+                                view.git_base = Some((path, base));
+                                view.git_line_changes = git_diff;
+                            }
+                            None => view.git_line_changes = LineDiff::default(),
+                        }
+                        cx.notify();
                     }
-                    Err(e) => log::error!("Failed to open file {}: {}", path_for_io.display(), e),
-                }
-
+                })?;
                 anyhow::Ok(())
-            }
-        })
-        .detach();
+            },
+        );
     }
 
-    /// 插入文本
-    pub fn insert_text(&mut self, text: &str, cx: &mut Context<'_, Self>) {
+    /// 在后台重新统计当前缓冲区，大文件也不卡住界面
+    fn refresh_buffer_stats(&mut self, cx: &mut Context<'_, Self>) {
         let buffer_manager = self.buffer_manager.clone();
-        let text = text.to_string();
-
-        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
-            let mut app = cx.clone();
-
-            async move {
-                if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
-                    let mut buffer = buffer_handle.lock().await;
-                    buffer.insert_text_at_cursor(&text).await;
-                    let _ = this.update(&mut app, |view, cx| {
-                        view.set_status("已输入文本");
-                        view.refresh_buffer_view(cx);
-                        view.is_dirty = true;
+        let executor = self.task_executor.clone();
+        let path = self.current_file_path.clone();
+
+        self.spawn_refresh(
+            Refresh::BufferStats,
+            Self::refresh_buffer_stats,
+            cx,
+            move |this: WeakEntity<EditorView>, mut app: AsyncApp| async move {
+                let stats = executor
+                    .spawn(async move {
+                        let handle = buffer_manager.get_current_buffer().await?;
+                        let snapshot = handle.lock().await.snapshot().await;
+                        Some(snapshot.stats())
+                    })
+                    .await
+                    .ok()
+                    .flatten();
+                this.update(&mut app, |view, cx| {
+                    if view.current_file_path == path {
+                        view.buffer_stats = stats;
                         cx.notify();
-                    });
-                }
-
+                    }
+                })?;
                 anyhow::Ok(())
-            }
-        })
-        .detach();
+            },
+        );
     }
 
-    /// 删除文本
-    pub fn delete_text(&mut self, cx: &mut Context<'_, Self>) {
+    /// 打开未保存修改的审阅面板，列出每个文件与磁盘不同的行
+    pub fn review_unsaved_changes(&mut self, cx: &mut Context<'_, Self>) {
+        if self.unsaved_review.take().is_some() {
+            cx.notify();
+            return;
+        }
         let buffer_manager = self.buffer_manager.clone();
+        let executor = self.task_executor.clone();
 
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
-
             async move {
-                if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
-                    let mut buffer = buffer_handle.lock().await;
-                    buffer.delete_backward().await;
-                    let _ = this.update(&mut app, |view, cx| {
-                        view.set_status("删除字符");
-                        view.refresh_buffer_view(cx);
-                        view.is_dirty = true;
-                        cx.notify();
-                    });
-                }
-
+                let files = executor
+                    .spawn(async move {
+                        let mut files = Vec::new();
+                        for path in buffer_manager.get_unsaved_files().await {
+                            if let Some(diff) = buffer_manager.unsaved_changes(&path).await {
+                                if !diff.is_empty() {
+                                    files.push((path, diff.changes().to_vec()));
+                                }
+                            }
+                        }
+                        files
+                    })
+                    .await
+                    .unwrap_or_default();
+                this.update(&mut app, |view, cx| {
+                    if files.is_empty() {
+                        view.set_status("没有未保存的修改");
+                    } else {
+                        let count: usize = files.iter().map(|(_, changes)| changes.len()).sum();
+                        view.set_status(format!(
+                            "{} 个文件有 {} 处未保存的修改，点击跳转，Esc 关闭",
+                            files.len(),
+                            count
+                        ));
+                        view.unsaved_review = Some(files);
+                    }
+                    cx.notify();
+                })?;
                 anyhow::Ok(())
             }
         })
         .detach();
     }
 
-    /// 移动光标（占位）
-    pub fn move_cursor(&mut self, _movement: CursorMovement, cx: &mut Context<'_, Self>) {
-        log::info!("Move cursor placeholder");
-        cx.notify();
+    fn line_change_color(kind: LineChangeKind) -> u32 {
+        match kind {
+            LineChangeKind::Added => 0x4caf50,
+            LineChangeKind::Modified => 0x42a5f5,
+            LineChangeKind::Deleted => 0xe57373,
+        }
     }
 
-    /// 保存当前文件
-    pub fn save_current_file(&mut self, cx: &mut Context<'_, Self>) {
-        let buffer_manager = self.buffer_manager.clone();
-
-        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
-            let mut app = cx.clone();
+    /// 差异的各行，成对的删除行与新增行中改动的词加深底色，行内的小改动一眼可见
+    pub(crate) fn diff_rows(diff: &[DiffLine], added: u32, removed: u32) -> Vec<gpui::Div> {
+        diff.iter()
+            .zip(changed_words(diff))
+            .map(|(line, words)| {
+                let (sign, text, color, emphasis) = match line {
+                    DiffLine::Context(text) => (' ', text, 0x888888, 0),
+                    DiffLine::Added(text) => ('+', text, added, 0x1f5130),
+                    DiffLine::Removed(text) => ('-', text, removed, 0x5e2626),
+                };
+                // 前面的符号和空格各占一个字节
+                let highlights: Vec<_> = words
+                    .into_iter()
+                    .map(|range| {
+                        let style = HighlightStyle {
+                            background_color: Some(rgb(emphasis).into()),
+                            ..Default::default()
+                        };
+                        (range.start + 2..range.end + 2, style)
+                    })
+                    .collect();
+                let mut styled = StyledText::new(format!("{} {}", sign, text));
+                if !highlights.is_empty() {
+                    styled = styled.with_highlights(highlights);
+                }
+                div()
+                    .whitespace_nowrap()
+                    .text_color(rgb(color))
+                    .child(styled)
+            })
+            .collect()
+    }
+
+    /// 一块被替换的文本：先列出原来的行，再列出替换后的行
+    pub(crate) fn block_diff(old_text: &str, new_text: &str) -> Vec<DiffLine> {
+        old_text
+            .lines()
+            .map(|line| DiffLine::Removed(line.to_string()))
+            .chain(
+                new_text
+                    .lines()
+                    .map(|line| DiffLine::Added(line.to_string())),
+            )
+            .collect()
+    }
+
+    fn render_unsaved_review(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let Some(files) = &self.unsaved_review else {
+            return div();
+        };
+        let root = std::env::current_dir().unwrap_or_default();
+
+        let mut rows = div()
+            .id("unsaved-review-list")
+            .mt_2()
+            .max_h(px(420.0))
+            .overflow_scroll()
+            .flex()
+            .flex_col()
+            .gap_1();
+        for (file_idx, (path, changes)) in files.iter().enumerate() {
+            let label = path
+                .strip_prefix(&root)
+                .unwrap_or(path)
+                .display()
+                .to_string();
+            rows = rows.child(
+                div()
+                    .mt_1()
+                    .text_sm()
+                    .text_color(rgb(0xffffff))
+                    .child(label),
+            );
+            for (change_idx, change) in changes.iter().enumerate() {
+                let id = (file_idx * 10_000 + change_idx) as u64;
+                let diff = Self::block_diff(&change.old_text, &change.new_text);
+                let body = div()
+                    .flex()
+                    .flex_col()
+                    .text_xs()
+                    .children(Self::diff_rows(&diff, 0x81c784, 0xe57373));
+                let (path, line) = (path.clone(), change.new.start);
+                rows = rows.child(
+                    div()
+                        .id(("unsaved-review-change", id))
+                        .flex()
+                        .gap_2()
+                        .pl_4()
+                        .rounded(px(4.0))
+                        .cursor_pointer()
+                        .hover(|style| style.bg(rgb(0x1f1f1f)))
+                        .child(
+                            div()
+                                .w(px(3.0))
+                                .bg(rgb(Self::line_change_color(change.kind()))),
+                        )
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(0x777777))
+                                .child(format!("L{}", line + 1)),
+                        )
+                        .child(body)
+                        .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                            view.unsaved_review = None;
+                            view.open_file_at(&path, line, 0, cx);
+                        })),
+                );
+            }
+        }
+
+        div().absolute().inset_0().child(
+            div()
+                .w(px(640.0))
+                .p_4()
+                .rounded(px(10.0))
+                .bg(rgb(0x121212))
+                .border_1()
+                .border_color(rgb(0x2a2a2a))
+                .shadow_lg()
+                .mx_auto()
+                .mt(px(100.0))
+                .child(div().text_color(rgb(0xffffff)).child("未保存的修改"))
+                .child(rows),
+        )
+    }
+
+    /// 重新查询光标处的括号配对
+    fn refresh_bracket_match(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+
+        self.spawn_refresh(
+            Refresh::BracketMatch,
+            Self::refresh_bracket_match,
+            cx,
+            move |this: WeakEntity<EditorView>, mut app: AsyncApp| async move {
+                let bracket_match = match buffer_manager.get_current_buffer().await {
+                    Some(handle) => {
+                        let buffer = handle.lock().await;
+                        match buffer.get_cursors().first().copied() {
+                            Some(cursor) => buffer.matching_bracket(cursor).await,
+                            None => None,
+                        }
+                    }
+                    None => None,
+                };
+
+                this.update(&mut app, |view, cx| {
+                    if view.bracket_match != bracket_match {
+                        view.bracket_match = bracket_match;
+                        cx.notify();
+                    }
+                })?;
+
+                anyhow::Ok(())
+            },
+        );
+    }
+
+    /// 订阅当前缓冲区的文本模型，按增量修改更新行缓存；其他视图的修改也会触发重绘
+    fn watch_current_buffer(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let lsp_enabled = self.config.lsp.enabled;
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let path = buffer_manager.get_current_file_path().await;
+                let Some(handle) = buffer_manager.get_current_buffer().await else {
+                    return anyhow::Ok(());
+                };
+                let mut changes = handle.lock().await.subscribe();
+                let document_sync = match &path {
+                    Some(path) if lsp_enabled => {
+                        let language = buffer_manager.language(path).await;
+                        let uri = Self::document_uri(&buffer_manager, path).await;
+                        Some(Self::sync_document(
+                            &executor,
+                            manager,
+                            handle.clone(),
+                            language,
+                            uri,
+                        ))
+                    }
+                    _ => None,
+                };
+                this.update(&mut app, |view, _| view.watched_path = path)?;
+
+                loop {
+                    match changes.recv().await {
+                        Ok(change) => {
+                            if let Some(sync) = &document_sync {
+                                let _ = sync.send(Some(change.clone()));
+                            }
+                            let anchors = this.update(&mut app, |view, cx| {
+                                if !view.apply_text_change(&change) {
+                                    view.refresh_buffer_view(cx);
+                                } else {
+                                    view.refresh_line_changes(cx);
+                                    view.refresh_buffer_stats(cx);
+                                }
+                                view.refresh_search(cx);
+                                cx.notify();
+                                view.diagnostic_anchors
+                                    .as_ref()
+                                    .map(|(_, anchors)| anchors.clone())
+                            })?;
+                            if let Some(anchors) = anchors.filter(|anchors| !anchors.is_empty()) {
+                                let mut ranges = Vec::with_capacity(anchors.len());
+                                {
+                                    let buffer = handle.lock().await;
+                                    for (start, end) in &anchors {
+                                        let start = buffer.anchor_cursor(*start).await;
+                                        let end = buffer.anchor_cursor(*end).await;
+                                        ranges.push(start.zip(end));
+                                    }
+                                }
+                                this.update(&mut app, |view, cx| {
+                                    view.move_diagnostics(&anchors, ranges);
+                                    cx.notify();
+                                })?;
+                            }
+                        }
+                        // 落后太多时整体重新读取
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                            if let Some(sync) = &document_sync {
+                                let _ = sync.send(None);
+                            }
+                            this.update(&mut app, |view, cx| view.refresh_buffer_view(cx))?
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+
+                anyhow::Ok(())
+            }
+        });
+        self.buffer_watch = Some(task);
+    }
+
+    /// 文档在语言服务器中的 URI；untitled 缓冲区没有文件，使用 untitled: 方案
+    async fn document_uri(buffer_manager: &BufferManager, path: &Path) -> String {
+        if buffer_manager.is_untitled(path).await {
+            untitled_uri(path)
+        } else {
+            path_to_uri(path)
+        }
+    }
+
+    /// 已打开文件在语言服务器中的 URI，见 [`Self::document_uri`]
+    pub(crate) fn open_file_uri(&self, path: &Path) -> String {
+        if self.untitled_files.contains(path) {
+            untitled_uri(path)
+        } else {
+            path_to_uri(path)
+        }
+    }
+
+    /// 把文档完整发给语言服务器，之后按顺序转发增量修改；收到 None 或修改不连续时
+    /// 重新完整同步。发送端随缓冲区订阅一起释放后结束
+    fn sync_document(
+        executor: &TaskExecutor,
+        manager: Arc<LspServerManager>,
+        handle: Arc<tokio::sync::Mutex<editor_core_text::Buffer>>,
+        language: String,
+        uri: String,
+    ) -> tokio::sync::mpsc::UnboundedSender<Option<editor_core_text::TextChange>> {
+        let (sender, mut receiver) =
+            tokio::sync::mpsc::unbounded_channel::<Option<editor_core_text::TextChange>>();
+
+        executor.spawn(async move {
+            let mut synced_version = None;
+            loop {
+                let synced = match synced_version {
+                    Some(version) => version,
+                    None => {
+                        let (text, version) = {
+                            let buffer = handle.lock().await;
+                            (buffer.get_text().await, buffer.version())
+                        };
+                        if let Err(e) = manager.sync_document(&language, &uri, &text, version).await
+                        {
+                            log::warn!("Failed to sync {} with language server: {}", uri, e);
+                        }
+                        synced_version = Some(version);
+                        version
+                    }
+                };
+                match receiver.recv().await {
+                    // 完整同步时已包含的修改
+                    Some(Some(change)) if change.after_version <= synced => {}
+                    Some(Some(change)) if change.before_version == synced => {
+                        if let Err(e) = manager
+                            .notify_file_edits(&language, &uri, std::slice::from_ref(&change))
+                            .await
+                        {
+                            log::warn!("Failed to send edits of {}: {}", uri, e);
+                        }
+                        synced_version = Some(change.after_version);
+                    }
+                    Some(_) => synced_version = None,
+                    None => break,
+                }
+            }
+        });
+        sender
+    }
+
+    /// 重新读取当前文件的诊断
+    fn refresh_diagnostics(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let Some(path) = buffer_manager.get_current_file_path().await else {
+                    return anyhow::Ok(());
+                };
+                let uri = Self::document_uri(&buffer_manager, &path).await;
+                let mut diagnostics = executor
+                    .spawn(async move { manager.get_diagnostics(&uri).await })
+                    .await?;
+                if let Ok(snapshot) = buffer_manager.file_snapshot(&path).await {
+                    for diagnostic in &mut diagnostics {
+                        let (start, end) = diagnostic.range.to_cursors(snapshot.rope());
+                        diagnostic.range = Self::char_range(start, end);
+                    }
+                }
+                // 在诊断两端放锚点：在开头输入时诊断后移，紧接结尾输入时不扩大
+                let anchors = match buffer_manager.get_buffer(&path).await {
+                    Some(handle) => {
+                        let buffer = handle.lock().await;
+                        let mut anchors = Vec::with_capacity(diagnostics.len());
+                        for diagnostic in &diagnostics {
+                            let (start, end) = Self::diagnostic_cursors(diagnostic);
+                            anchors.push((
+                                buffer.create_anchor(start, Bias::Right).await,
+                                buffer.create_anchor(end, Bias::Left).await,
+                            ));
+                        }
+                        Some((buffer.text_model(), anchors))
+                    }
+                    None => None,
+                };
+
+                this.update(&mut app, |view, cx| {
+                    if view.current_file_path.as_ref() == Some(&path)
+                        || view.watched_path.as_ref() == Some(&path)
+                    {
+                        view.diagnostics = diagnostics;
+                        view.replace_diagnostic_anchors(anchors);
+                        if path.file_name().is_some_and(|name| name == "Cargo.toml") {
+                            view.refresh_crate_hints(path.clone(), cx);
+                        }
+                        cx.notify();
+                    } else if let Some((model, anchors)) = anchors {
+                        Self::release_anchors(&model, &anchors);
+                    }
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 查询 Cargo.toml 依赖的最新版本；未查过的先到 crates.io 查询，离线时不显示
+    fn refresh_crate_hints(&mut self, path: PathBuf, cx: &mut Context<'_, Self>) {
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let uri = path_to_uri(&path);
+                let hints = executor
+                    .spawn(async move { manager.crate_version_hints(&uri).await })
+                    .await?;
+                this.update(&mut app, |view, cx| {
+                    if view.current_file_path.as_ref() == Some(&path) {
+                        view.crate_hints = hints;
+                        cx.notify();
+                    }
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn diagnostic_cursors(
+        diagnostic: &Diagnostic,
+    ) -> (editor_core_text::Cursor, editor_core_text::Cursor) {
+        let cursor = |position: &Position| {
+            editor_core_text::Cursor::new(position.line as usize, position.character as usize)
+        };
+        (
+            cursor(&diagnostic.range.start),
+            cursor(&diagnostic.range.end),
+        )
+    }
+
+    fn release_anchors(model: &TextModel, anchors: &[(Anchor, Anchor)]) {
+        model.update_anchors(|registered| {
+            for (start, end) in anchors {
+                registered.remove(*start);
+                registered.remove(*end);
+            }
+        });
+    }
+
+    fn replace_diagnostic_anchors(&mut self, anchors: Option<DiagnosticAnchors>) {
+        if let Some((model, old)) = std::mem::replace(&mut self.diagnostic_anchors, anchors) {
+            Self::release_anchors(&model, &old);
+        }
+    }
+
+    /// 把诊断移到锚点的新位置；锚点在读取期间被换掉时不动
+    fn move_diagnostics(
+        &mut self,
+        anchors: &[(Anchor, Anchor)],
+        ranges: Vec<Option<(editor_core_text::Cursor, editor_core_text::Cursor)>>,
+    ) {
+        let current = self.diagnostic_anchors.as_ref().map(|(_, current)| current);
+        if current.is_none_or(|current| current.as_slice() != anchors) {
+            return;
+        }
+        for (diagnostic, range) in self.diagnostics.iter_mut().zip(ranges) {
+            if let Some((start, end)) = range {
+                let end = if (end.line, end.column) < (start.line, start.column) {
+                    start
+                } else {
+                    end
+                };
+                diagnostic.range = Self::char_range(start, end);
+            }
+        }
+    }
+
+    /// 语言服务器发布当前文件的诊断时刷新波浪线与行内信息
+    fn watch_diagnostics(&mut self, cx: &mut Context<'_, Self>) {
+        if self.diagnostics_watch.is_some() {
+            return;
+        }
+        let mut published = self.lsp_manager.subscribe_diagnostics();
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                loop {
+                    match published.recv().await {
+                        Ok(uri) => this.update(&mut app, |view, cx| {
+                            let current = view
+                                .current_file_path
+                                .as_ref()
+                                .map(|path| view.open_file_uri(path));
+                            if current.as_deref() == Some(uri.as_str()) {
+                                view.refresh_diagnostics(cx);
+                            }
+                            if view.problems.is_some() {
+                                view.reload_problems(cx);
+                            }
+                        })?,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                            this.update(&mut app, |view, cx| view.refresh_diagnostics(cx))?
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                anyhow::Ok(())
+            }
+        });
+        self.diagnostics_watch = Some(task);
+    }
+
+    /// 把一次修改直接应用到已渲染的行上；版本对不上或无法安全拆行时返回 false
+    fn apply_text_change(&mut self, change: &editor_core_text::TextChange) -> bool {
+        if change.after_version <= self.rendered_version {
+            return true;
+        }
+        if change.before_version != self.rendered_version
+            || change.old_end.line >= self.lines.len()
+            || self.lines.len() != self.line_prefix_widths.len()
+        {
+            return false;
+        }
+
+        let first = &self.lines[change.start.line];
+        let last = &self.lines[change.old_end.line];
+        let prefix: String = first.chars().take(change.start.column).collect();
+        let suffix: String = last.chars().skip(change.old_end.column).collect();
+        let combined = format!("{}{}{}", prefix, change.new_text(), suffix);
+        // ropey 还会把 \r 和 Unicode 行分隔符当作换行，这些情况交给完整刷新
+        if combined.chars().any(|c| {
+            matches!(
+                c,
+                '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+            )
+        }) {
+            return false;
+        }
+
+        let is_last_line = change.old_end.line + 1 == self.lines.len();
+        let mut new_lines: Vec<String> = combined.split_inclusive('\n').map(String::from).collect();
+        if is_last_line && (combined.is_empty() || combined.ends_with('\n')) {
+            new_lines.push(String::new());
+        }
+
+        let tab_size = self.config.editor.tab_size;
+        let new_widths: Vec<Vec<f32>> = new_lines
+            .iter()
+            .map(|line| Self::prefix_widths(line, tab_size))
+            .collect();
+        let range = change.start.line..=change.old_end.line;
+        self.lines.splice(range.clone(), new_lines);
+        self.line_prefix_widths.splice(range, new_widths);
+        self.rendered_version = change.after_version;
+        true
+    }
+
+    /// 监听 AI agent / workflow 在后台对文件的修改
+    fn watch_agent_edits(&mut self, cx: &mut Context<'_, Self>) {
+        let mut events = self.buffer_manager.subscribe_agent_edits();
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                loop {
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    };
+                    this.update(&mut app, |view, cx| view.handle_agent_edit(event, cx))?;
+                }
+
+                anyhow::Ok(())
+            }
+        });
+        self.agent_watch = Some(task);
+    }
+
+    fn handle_agent_edit(&mut self, event: AgentEditEvent, cx: &mut Context<'_, Self>) {
+        match event {
+            AgentEditEvent::Edited { agent, path, line } => {
+                if self.agent_run_finished {
+                    self.agent_touched_files.clear();
+                    self.agent_run_finished = false;
+                }
+                match self
+                    .agent_touched_files
+                    .iter_mut()
+                    .find(|(p, _)| *p == path)
+                {
+                    Some(entry) => entry.1 = line,
+                    None => self.agent_touched_files.push((path.clone(), line)),
+                }
+
+                if self.follow_agent_edits {
+                    self.open_file_at(&path, line, 0, cx);
+                } else {
+                    self.refresh_buffer_view(cx);
+                }
+                self.set_status(format!(
+                    "{} 正在修改 {}:{}",
+                    agent,
+                    path.display(),
+                    line + 1
+                ));
+            }
+            AgentEditEvent::Finished { agent } => {
+                self.agent_run_finished = true;
+                self.set_status(format!(
+                    "{} 完成，共修改 {} 个文件",
+                    agent,
+                    self.agent_touched_files.len()
+                ));
+            }
+        }
+        cx.notify();
+    }
+
+    /// 切换是否跟随 AI agent 的修改
+    pub fn toggle_follow_agent_edits(&mut self, cx: &mut Context<'_, Self>) {
+        self.follow_agent_edits = !self.follow_agent_edits;
+        self.set_status(if self.follow_agent_edits {
+            "跟随 AI 修改：开"
+        } else {
+            "跟随 AI 修改：关"
+        });
+        cx.notify();
+    }
+
+    /// 将 AI 生成的补丁应用到对应文件的缓冲区（不写回磁盘），应用前先预览
+    pub fn apply_ai_patch(&mut self, patch: AIPatch, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        let path = Self::resolve_patch_path(&patch.file_path);
+
+        // 没有原始代码的补丁指向新文件：直接创建，便于撤销
+        if patch.old_code.trim().is_empty() && !path.exists() {
+            self.create_file(path, patch.new_code, cx);
+            return;
+        }
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let range = buffer_manager
+                    .file_snapshot(&path)
+                    .await
+                    .map(|snapshot| edit_preview::locate(&snapshot.text(), &patch.old_code));
+                this.update(&mut app, |view, cx| match range {
+                    Ok(Some((start, end))) => {
+                        let mut preview =
+                            EditPreview::new(format!("AI 补丁: {}", patch.description))
+                                .with_agent("AI");
+                        preview.push(&path, start, end, patch.new_code.clone());
+                        view.show_edit_preview(preview, None, cx);
+                    }
+                    Ok(None) => {
+                        view.set_status(format!("无法应用补丁 {}: 未找到原始代码", path.display()));
+                        cx.notify();
+                    }
+                    Err(e) => {
+                        view.set_status(format!("无法应用补丁 {}: {}", path.display(), e));
+                        cx.notify();
+                    }
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// AI 补丁中的相对路径相对于工作目录
+    fn resolve_patch_path(file_path: &str) -> PathBuf {
+        let path = PathBuf::from(file_path);
+        match std::env::current_dir() {
+            Ok(cwd) if path.is_relative() => cwd.join(path),
+            _ => path,
+        }
+    }
+
+    /// 无法读取文件内容时，直接把 UTF-16 列当作字符列
+    pub(crate) fn lsp_cursor(position: &Position) -> editor_core_text::Cursor {
+        editor_core_text::Cursor::new(position.line as usize, position.character as usize)
+    }
+
+    fn char_range(
+        start: editor_core_text::Cursor,
+        end: editor_core_text::Cursor,
+    ) -> editor_lsp::Range {
+        let position = |cursor: editor_core_text::Cursor| Position {
+            line: cursor.line as u32,
+            character: cursor.column as u32,
+        };
+        editor_lsp::Range {
+            start: position(start),
+            end: position(end),
+        }
+    }
+
+    /// 请求光标处的补全，已输入的词作为初始筛选
+    fn show_completions(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let Some(cursor) = self.selection.map(|selection| selection.active) else {
+            return;
+        };
+        let line: Vec<char> = self
+            .lines
+            .get(cursor.line)
+            .map(|line| line.chars().collect())
+            .unwrap_or_default();
+        let column = cursor.column.min(line.len());
+        let word_start = (0..column)
+            .rev()
+            .take_while(|&idx| line[idx].is_alphanumeric() || line[idx] == '_')
+            .last()
+            .unwrap_or(column);
+        let prefix: String = line[word_start..column].iter().collect();
+        let word = (
+            editor_core_text::Cursor::new(cursor.line, word_start),
+            editor_core_text::Cursor::new(cursor.line, column),
+        );
+        let language = self.current_file_language();
+        let uri = self.open_file_uri(&path);
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let snapshot = buffer_manager.file_snapshot(&path).await?;
+                let position = Position::from_cursor(snapshot.rope(), word.1);
+                let request = executor.spawn(async move {
+                    manager.request_completion(&language, &uri, position).await
+                });
+                let result = request.await.map_err(std::io::Error::other).and_then(|r| r);
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(items) if items.is_empty() => view.set_status("没有补全项"),
+                        Ok(mut items) => {
+                            items.sort_by(|a, b| {
+                                let key = |item: &CompletionItem| {
+                                    item.sort_text.clone().unwrap_or_else(|| item.label.clone())
+                                };
+                                key(a).cmp(&key(b))
+                            });
+                            view.completions = items;
+                            view.completion_word = Some(word);
+                            view.begin_quick_input(QuickInputMode::PickCompletion, cx);
+                            view.quick_open_input = prefix;
+                        }
+                        Err(e) => view.set_status(format!("请求补全失败: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn filtered_completions(&self, filter: &str) -> Vec<CompletionItem> {
+        let filter = filter.to_lowercase();
+        self.completions
+            .iter()
+            .filter(|item| item.filter_text().to_lowercase().contains(&filter))
+            .cloned()
+            .collect()
+    }
+
+    /// 插入前先向语言服务器补全细节，连同 additionalTextEdits（如自动导入）作为一步撤销应用
+    fn accept_completion(&mut self, item: CompletionItem, cx: &mut Context<'_, Self>) {
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        self.completions.clear();
+        let (Some(path), Some((word_start, word_end))) =
+            (self.current_file_path.clone(), self.completion_word.take())
+        else {
+            return;
+        };
+        let language = self.current_file_language();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let label = item.label.clone();
+                let request = executor
+                    .spawn(async move { manager.resolve_completion(&language, &item).await });
+                let item = match request.await.map_err(std::io::Error::other).and_then(|r| r) {
+                    Ok(item) => item,
+                    Err(e) => {
+                        this.update(&mut app, |view, cx| {
+                            view.set_status(format!("补全 {} 失败: {}", label, e));
+                            cx.notify();
+                        })?;
+                        return anyhow::Ok(());
+                    }
+                };
+                let applied = buffer_manager.submit_edit(&path, move |buffer| {
+                    async move {
+                        let snapshot = buffer.snapshot().await;
+                        let line_ending = buffer.line_ending();
+                        let char_range = |edit: &editor_lsp::TextEdit| {
+                            let (start, end) = edit.range.to_cursors(snapshot.rope());
+                            let start = snapshot.cursor_to_char(start);
+                            (
+                                start..snapshot.cursor_to_char(end).max(start),
+                                line_ending.normalize(&edit.new_text).into_owned(),
+                            )
+                        };
+                        let word =
+                            editor_lsp::Range::from_cursors(snapshot.rope(), word_start, word_end);
+                        let (main, caret) = item.main_edit(word);
+                        let main = char_range(&main);
+                        let additional: Vec<_> = item
+                            .additional_text_edits
+                            .iter()
+                            .map(char_range)
+                            // 与主修改重叠的无法一起应用
+                            .filter(|(range, _)| {
+                                range.end <= main.0.start || range.start >= main.0.end
+                            })
+                            .collect();
+                        // 主修改前的附加修改会让插入位置整体后移
+                        let shift: isize = additional
+                            .iter()
+                            .filter(|(range, _)| range.end <= main.0.start)
+                            .map(|(range, text)| {
+                                text.chars().count() as isize - range.len() as isize
+                            })
+                            .sum();
+                        let caret = main.0.start.saturating_add_signed(shift) + caret;
+
+                        let imports = additional.len();
+                        let mut edits = additional;
+                        edits.push(main);
+                        buffer.replace_ranges(&edits).await;
+                        let cursor = buffer.snapshot().await.char_to_cursor(caret);
+                        buffer.set_cursor(cursor).await;
+                        (buffer.get_selections().to_vec(), imports)
+                    }
+                    .boxed()
+                });
+                let Some((selections, imports)) = applied.await else {
+                    return anyhow::Ok(());
+                };
+
+                this.update(&mut app, |view, cx| {
+                    view.set_selections(selections);
+                    view.is_dirty = true;
+                    view.set_status(if imports > 0 {
+                        format!("已插入 {}，并添加 {} 处附加修改", label, imports)
+                    } else {
+                        format!("已插入 {}", label)
+                    });
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn render_completions(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::PickCompletion {
+            return list;
+        }
+
+        for (idx, item) in self
+            .filtered_completions(self.quick_open_input.trim())
+            .into_iter()
+            .take(50)
+            .enumerate()
+        {
+            let detail = item
+                .detail
+                .clone()
+                .or_else(|| item.kind.map(|kind| format!("{:?}", kind)))
+                .unwrap_or_default();
+            list = list.child(
+                div()
+                    .id(("completion", idx as u64))
+                    .flex()
+                    .justify_between()
+                    .gap_3()
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(item.label.clone())
+                    .child(div().text_xs().text_color(rgb(0x777777)).child(detail))
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.accept_completion(item.clone(), cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    fn file_info_label(info: &FileInfo) -> String {
+        let mut label = format!("{} • {}", info.size_label(), info.permissions);
+        if let Some(modified) = info.modified {
+            label.push_str(&format!(" • 修改于 {}", Self::format_age(modified)));
+        }
+        if !info.writable {
+            label.push_str(" • 不可写");
+        }
+        label
+    }
+
+    fn buffer_stats_label(stats: &BufferStats) -> String {
+        let mut label = format!(
+            "{} 词 · {} 字符 · {} 行",
+            stats.words, stats.chars, stats.lines
+        );
+        if stats.selected_chars > 0 {
+            label.push_str(&format!(" · 已选 {}", stats.selected_chars));
+        }
+        label.push_str(&format!(" · {:.0}%", stats.cursor_percent));
+        label
+    }
+
+    /// 不影响第一帧的后台任务：工作流调度、缓存释放、文件树扫描和文件监视
+    fn start_background_services(&mut self, cx: &mut Context<'_, Self>) {
+        self.start_workflow_scheduler();
+        self.start_cache_eviction(cx);
+        self.start_session_checkpoints(cx);
+        self.start_health_reports(cx);
+        self.reload_file_tree(cx);
+        self.load_issue_linker(cx);
+        self.refresh_git_status(cx);
+        if let Ok(root) = std::env::current_dir() {
+            self.watch_workspace_files(root, cx);
+        }
+    }
+
+    /// 第一次打开有语言服务器配置的文件时才检查并启动语言服务器
+    fn ensure_language_servers(&mut self, cx: &mut Context<'_, Self>) {
+        if self.language_servers_checked || !self.config.lsp.enabled {
+            return;
+        }
+        let Some(language) = &self.current_language else {
+            return;
+        };
+        if !self
+            .config
+            .lsp
+            .servers
+            .iter()
+            .any(|server| &server.language == language)
+        {
+            return;
+        }
+        self.language_servers_checked = true;
+        self.check_language_servers(cx);
+    }
+
+    /// 按配置的间隔定时写会话检查点，崩溃时最多丢失这段时间内的布局和草稿
+    fn start_session_checkpoints(&mut self, cx: &mut Context<'_, Self>) {
+        let minutes = self.config.editor.checkpoint_interval_minutes;
+        if minutes == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(minutes * 60);
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                loop {
+                    app.background_executor().timer(interval).await;
+                    this.update(&mut app, |view, cx| view.checkpoint_session(cx))?;
+                }
+            }
+        });
+        self.session_checkpoints = Some(task);
+    }
+
+    /// 性能模式开着时它的配置
+    fn performance_mode(&self) -> Option<&PerformanceModeConfig> {
+        self.performance_mode
+            .then_some(&self.config.editor.performance_mode)
+    }
+
+    /// 开关当前工作区的性能模式，状态随会话保存
+    pub fn toggle_performance_mode(&mut self, cx: &mut Context<'_, Self>) {
+        self.performance_mode = !self.performance_mode;
+        self.apply_performance_mode(cx);
+        self.checkpoint_session(cx);
+        self.set_status(if self.performance_mode {
+            "已开启性能模式"
+        } else {
+            "已关闭性能模式"
+        });
+        cx.notify();
+    }
+
+    /// 按性能模式调整防抖间隔，释放或重新计算受影响的数据
+    fn apply_performance_mode(&mut self, cx: &mut Context<'_, Self>) {
+        let mode = self.performance_mode().cloned();
+        let debounce = mode.as_ref().map_or(DEFAULT_DEBOUNCE, |mode| {
+            Duration::from_millis(mode.lsp_debounce_ms)
+        });
+        self.lsp_manager.request_gate().set_delay(debounce);
+        if mode.is_some_and(|mode| mode.disable_ai_indexing) {
+            self.code_index = None;
+        }
+        self.refresh_blame(cx);
+        self.refresh_buffer_view(cx);
+    }
+
+    /// 把打开的标签页、光标、当前标签页的滚动位置和未保存的草稿写入会话。
+    /// 新的检查点取代还没写完的上一个
+    fn checkpoint_session(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.session_restored {
+            return;
+        }
+        let buffer_manager = self.buffer_manager.clone();
+        let mut scroll_lines = self.tab_scroll_lines.clone();
+        let performance_mode = self.performance_mode;
+        if let Some(path) = &self.current_file_path {
+            scroll_lines.insert(path.clone(), self.top_visible_line());
+        }
+        let session_path = std::env::current_dir()
+            .map(|root| Session::workspace_path(&root))
+            .unwrap_or_else(|_| Session::default_path());
+
+        let task = cx.spawn(move |_, _: &mut AsyncApp| async move {
+            let session = Session {
+                performance_mode,
+                pinned_tabs: buffer_manager.pinned_files().await,
+                current: buffer_manager.get_current_file_path().await,
+                open_tabs: buffer_manager
+                    .open_tab_states(&scroll_lines)
+                    .await
+                    .iter()
+                    .map(SessionTab::from)
+                    .collect(),
+            };
+            if let Err(e) = session.save(&session_path) {
+                log::warn!("Failed to save session: {}", e);
+            }
+        });
+        self.session_checkpoint = Some(task);
+    }
+
+    /// 接收来自 IPC / 系统 URI 处理器的 `fusang://` 链接
+    pub fn listen_for_deep_links(
+        &mut self,
+        mut links: tokio::sync::mpsc::UnboundedReceiver<String>,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                while let Some(uri) = links.recv().await {
+                    this.update(&mut app, |view, cx| view.handle_deep_link(&uri, cx))?;
+                }
+
+                anyhow::Ok(())
+            }
+        });
+        self.deep_link_watch = Some(task);
+    }
+
+    /// 处理深链接并将窗口切到前台
+    pub fn handle_deep_link(&mut self, uri: &str, cx: &mut Context<'_, Self>) {
+        match DeepLink::parse(uri) {
+            Ok(DeepLink::Open { file, line, column }) => {
+                cx.activate(true);
+                self.open_file_at(
+                    &file,
+                    line.unwrap_or(1).saturating_sub(1),
+                    column.unwrap_or(1).saturating_sub(1),
+                    cx,
+                );
+            }
+            Err(e) => {
+                self.set_status(format!("无法处理链接 {}: {}", uri, e));
+                cx.notify();
+            }
+        }
+    }
+
+    /// 打开文件；笔记本以单元格视图打开
+    pub fn open_file(&mut self, file_path: &Path, cx: &mut Context<'_, Self>) {
+        if file_path.extension().is_some_and(|ext| ext == "ipynb") {
+            self.open_notebook(file_path.to_path_buf(), cx);
+        } else if self
+            .notebook
+            .as_ref()
+            .is_some_and(NotebookSession::is_dirty)
+        {
+            self.set_status("笔记本有未保存的修改，先保存或关闭笔记本");
+            cx.notify();
+        } else {
+            self.notebook = None;
+            self.open_text_file(file_path, cx);
+        }
+    }
+
+    pub(crate) fn open_text_file(&mut self, file_path: &Path, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        let path = file_path.to_path_buf();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            let path_for_io = path.clone();
+
+            async move {
+                match buffer_manager.open_file(&path_for_io).await {
+                    Ok(_) => {
+                        let path_clone = path_for_io.clone();
+                        let crashed = buffer_manager
+                            .edit_journals()
+                            .and_then(|journals| journals.crashed(&path_for_io).ok().flatten());
+                        let _ = this.update(&mut app, |view, cx| {
+                            view.current_file_path = Some(path_clone);
+                            match crashed {
+                                Some(log) => view.set_status(format!(
+                                    "文件已打开；上次退出前有 {} 步编辑没有保存，按 Cmd+Alt+Shift+J 恢复",
+                                    log.len()
+                                )),
+                                None if Self::is_table_file(&view.current_file_path) => view
+                                    .set_status("文件已打开；按 Cmd+Alt+Shift+T 以表格查看"),
+                                None => view.set_status("文件已打开"),
+                            }
+                            view.refresh_buffer_view(cx);
+                            cx.notify();
+                        });
+                    }
+                    Err(e) => log::error!("Failed to open file {}: {}", path_for_io.display(), e),
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 只读缓冲区拒绝编辑，返回是否可写
+    pub(crate) fn ensure_writable(&mut self, cx: &mut Context<'_, Self>) -> bool {
+        if self.current_read_only {
+            if self.current_write_protected {
+                self.set_status("文件不可写，按 Cmd+Alt+E 仍然编辑");
+            } else if self
+                .current_file_path
+                .as_ref()
+                .is_some_and(|path| path.exists())
+            {
+                self.set_status("缓冲区已锁定，按 Cmd+Alt+E 解锁");
+            } else {
+                self.set_status("只读缓冲区，无法编辑");
+            }
+            cx.notify();
+            return false;
+        }
+        true
+    }
+
+    /// 锁定当前缓冲区，或解除锁定和不可写文件的只读限制（保存时仍可能因权限失败）
+    pub fn toggle_read_only(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+        let unlock = self.current_read_only;
+        let write_protected = self.current_write_protected;
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = if unlock {
+                    buffer_manager.allow_editing(&path).await
+                } else {
+                    buffer_manager.lock_editing(&path).await;
+                    Ok(())
+                };
+                this.update(&mut app, |view, cx| {
+                    match &result {
+                        Ok(()) if !unlock => view.set_status("缓冲区已锁定为只读"),
+                        Ok(()) if write_protected => {
+                            view.set_status("已允许编辑不可写文件，保存可能失败")
+                        }
+                        Ok(()) => view.set_status("缓冲区已解锁"),
+                        Err(e) => view.set_status(format!("无法解除只读: {}", e)),
+                    }
+                    if result.is_ok() {
+                        view.refresh_buffer_view(cx);
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 插入文本
+    pub fn insert_text(&mut self, text: &str, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let text = text.to_string();
+        let auto_close = self.config.editor.auto_close_brackets;
+        let format_on_type = self.config.editor.format_on_type;
+        let indent = self.indent_style();
+
+        let typed = text.clone();
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let mut chars = typed.chars();
+                match (chars.next(), chars.next()) {
+                    (Some('\n'), None) => buffer.insert_line_break_and_indent(indent).await,
+                    (Some(ch), None) if auto_close => buffer.insert_char(ch).await,
+                    _ => buffer.insert_text_at_cursor(&typed).await,
+                }
+                (buffer.get_selections().to_vec(), buffer.version())
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some((selections, version)) = applied.await {
+                    let _ = this.update(&mut app, |view, cx| {
+                        view.set_status("已输入文本");
+                        // 行内容由 watch_current_buffer 增量更新
+                        let cursor = selections.last().map(|selection| selection.active);
+                        view.set_selections(selections);
+                        view.is_dirty = true;
+                        view.refresh_bracket_match(cx);
+                        if let Some(cursor) = cursor.filter(|_| format_on_type) {
+                            if text.chars().count() == 1 {
+                                view.format_on_type(text, version, cursor, cx);
+                            }
+                        }
+                        cx.notify();
+                    });
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 删除文本
+    pub fn delete_text(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, |buffer| {
+            async move {
+                buffer.delete_backward().await;
+                buffer.get_selections().to_vec()
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some(selections) = applied.await {
+                    let _ = this.update(&mut app, |view, cx| {
+                        view.set_status("删除字符");
+                        view.set_selections(selections);
+                        view.is_dirty = true;
+                        view.refresh_bracket_match(cx);
+                        cx.notify();
+                    });
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 移动光标（占位）
+    pub fn move_cursor(&mut self, _movement: CursorMovement, cx: &mut Context<'_, Self>) {
+        log::info!("Move cursor placeholder");
+        cx.notify();
+    }
+
+    /// 保存当前文件
+    pub fn save_current_file(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let buffer_manager = self.buffer_manager.clone();
+        let format_job = self
+            .config
+            .editor
+            .format_on_save
+            .then(|| self.format_job())
+            .flatten();
+        // 先等已提交的编辑全部写入，保存的是按键时看到的内容
+        let flushed = self
+            .current_file_path
+            .as_ref()
+            .map(|path| self.buffer_manager.edit_queue(path).flushed());
+        let indexing = self.code_index.is_some();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some(flushed) = flushed {
+                    flushed.await;
+                }
+                // 格式化失败不影响保存
+                let format_error = match format_job {
+                    Some(job) => job.run().await.err(),
+                    None => None,
+                };
+                match buffer_manager.save_current_file().await {
+                    Ok(_) => {
+                        let saved = match (
+                            buffer_manager.get_current_file_path().await,
+                            buffer_manager.get_current_buffer().await,
+                        ) {
+                            (Some(path), Some(handle)) if indexing => {
+                                let text = handle.lock().await.get_text().await;
+                                std::path::absolute(&path).ok().map(|path| (path, text))
+                            }
+                            _ => None,
+                        };
+                        let _ = this.update(&mut app, |view, cx| {
+                            if let Some((path, text)) = saved {
+                                let small = text.len() as u64 <= MAX_INDEXED_FILE_BYTES;
+                                view.update_code_index(vec![(path, small.then_some(text))]);
+                            }
+                            match &format_error {
+                                Some(e) => view.set_status(format!("已保存，格式化失败: {}", e)),
+                                None => view.set_status("保存成功"),
+                            }
+                            view.refresh_buffer_view(cx);
+                            view.is_dirty = false;
+                            cx.notify();
+                        });
+                    }
+                    Err(e) => log::error!("Failed to save file: {}", e),
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 获取当前文件路径
+    pub fn current_file_path(&self) -> Option<&PathBuf> {
+        self.current_file_path.as_ref()
+    }
+
+    /// 获取当前文件名称
+    pub fn current_file_name(&self) -> Option<String> {
+        let path = self.current_file_path.as_ref()?;
+        self.display_names
+            .get(path)
+            .cloned()
+            .or_else(|| path.file_name().map(|n| n.to_string_lossy().to_string()))
+    }
+
+    /// 获取文件语言，优先使用手动指定的语言
+    pub fn current_file_language(&self) -> String {
+        if let Some(language) = &self.current_language {
+            return language.clone();
+        }
+        self.current_file_path
+            .as_deref()
+            .map(language_from_path)
+            .unwrap_or("plaintext")
+            .to_string()
+    }
+
+    /// 重命名当前 untitled 缓冲区（仅修改显示名称）
+    pub fn rename_current_buffer(&mut self, name: String, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let result = buffer_manager.rename_untitled(&path, &name).await;
+                let _ = this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(_) => view.set_status(format!("缓冲区已重命名为 {}", name.trim())),
+                        Err(e) => view.set_status(format!("无法重命名: {}", e)),
+                    }
+                    view.refresh_buffer_view(cx);
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 为当前缓冲区指定语言，影响高亮、LSP 与 AI 上下文
+    pub fn set_current_language(&mut self, language: String, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let lsp_enabled = self.config.lsp.enabled;
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let previous = buffer_manager.language(&path).await;
+                let result = buffer_manager.set_language(&path, &language).await;
+                let language = buffer_manager.language(&path).await;
+                // 语言服务器按 languageId 处理文档：先按旧语言关闭，再由同步按新语言重新打开
+                let reopen = lsp_enabled && language != previous;
+                if reopen {
+                    let uri = Self::document_uri(&buffer_manager, &path).await;
+                    executor
+                        .spawn(async move {
+                            if let Err(e) = manager.notify_file_closed(&previous, &uri).await {
+                                log::warn!("didClose failed: {}", e);
+                            }
+                        })
+                        .await?;
+                }
+                let _ = this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(_) => {
+                            view.current_language = Some(language.clone());
+                            view.set_status(format!("语言已设置为 {}", language));
+                            view.set_ai_context(cx);
+                        }
+                        Err(e) => view.set_status(format!("无法设置语言: {}", e)),
+                    }
+                    if reopen {
+                        view.ensure_language_servers(cx);
+                        view.watch_current_buffer(cx);
+                    }
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 打开快速输入框
+    pub(crate) fn begin_quick_input(&mut self, mode: QuickInputMode, cx: &mut Context<'_, Self>) {
+        self.quick_open_active = true;
+        self.quick_input_mode = mode;
+        self.quick_open_input.clear();
+        if mode == QuickInputMode::OpenPath {
+            self.scan_workspace_files(cx);
+        }
+        if mode == QuickInputMode::GitCommand {
+            self.load_source_control(cx);
+        }
+        if mode == QuickInputMode::PickPullRequest {
+            self.load_pull_requests(cx);
+        }
+        self.status_message = match mode {
+            QuickInputMode::OpenPath => "输入路径或文件名后回车打开，Esc 取消",
+            QuickInputMode::RenameBuffer => "输入新名称后回车确认，Esc 取消",
+            QuickInputMode::SetLanguage => "输入语言（如 rust、python）后回车确认，Esc 取消",
+            QuickInputMode::ExportSettings => "输入导出路径（留空使用默认位置），回车确认",
+            QuickInputMode::ImportSettings => "输入配置归档路径后回车导入，Esc 取消",
+            QuickInputMode::PickReference => "输入关键字筛选，回车打开第一个匹配",
+            QuickInputMode::PickCodeAction => "输入关键字筛选，回车执行第一个匹配",
+            QuickInputMode::NewFile => "输入新文件路径后回车创建，Esc 取消",
+            QuickInputMode::NewFolder => "输入新文件夹路径后回车创建，Esc 取消",
+            QuickInputMode::MoveFile => "输入新路径后回车移动当前文件，Esc 取消",
+            QuickInputMode::Find => {
+                "输入查找内容后回车，Cmd+G 跳到下一个，Alt+S 只在选区内查找，留空回车清除查找"
+            }
+            QuickInputMode::Replace => "输入替换文本后回车，替换全部查找结果",
+            QuickInputMode::SetMark => "输入书签名后回车，单个数字为编号书签",
+            QuickInputMode::GotoMark => "输入书签名后回车跳转",
+            QuickInputMode::PickCompletion => "输入筛选补全项，回车插入第一个",
+            QuickInputMode::PickClipboard => "输入筛选剪贴板历史，回车粘贴第一个",
+            QuickInputMode::ReplayEditLog => {
+                "输入编辑日志路径后回车回放，留空回放当前文件本次会话的编辑，Esc 取消"
+            }
+            QuickInputMode::TransformCase => "输入筛选大小写格式，回车转换选区或光标处的词",
+            QuickInputMode::InlineThread => "输入问题后回车，有选区时就选中的行开始新对话",
+            QuickInputMode::RunTool if self.config.editor.external_tools.is_empty() => {
+                "还没有外部工具，可在配置的 editor.external_tools 中添加"
+            }
+            QuickInputMode::RunTool => "输入筛选外部工具，回车运行第一个",
+            QuickInputMode::GitCommand => "输入筛选 Git 命令，回车执行第一个",
+            QuickInputMode::AddWorktree => {
+                "输入分支名后回车，在仓库旁新建工作树；也可以在分支名后加上路径"
+            }
+            QuickInputMode::PickPullRequest => "正在读取打开的 PR/MR…",
+            QuickInputMode::SearchWorkspace => {
+                "输入查找内容后回车，在整个工作区中查找；Alt+C 区分大小写，Alt+W 全词，Alt+R 正则"
+            }
+            QuickInputMode::RecentWorkspace if self.filtered_recent_workspaces("").is_empty() => {
+                "还没有其他最近打开的工作区"
+            }
+            QuickInputMode::RecentWorkspace => "输入筛选最近的工作区，回车切换到第一个",
+            QuickInputMode::CommentPullRequest => "输入评论后回车发表，Esc 取消",
+        }
+        .to_string();
+        if mode == QuickInputMode::GotoMark && !self.bookmarks.is_empty() {
+            let marks: Vec<String> = self
+                .bookmarks
+                .iter()
+                .map(|(name, cursor)| format!("{} 行 {}", name, cursor.line + 1))
+                .collect();
+            self.status_message = format!("书签：{}", marks.join(" · "));
+        }
+        cx.notify();
+    }
+
+    fn submit_quick_input(&mut self, cx: &mut Context<'_, Self>) {
+        if self.quick_input_mode == QuickInputMode::OpenPath {
+            self.open_quick_input_path(cx);
+            return;
+        }
+
+        let raw_input = std::mem::take(&mut self.quick_open_input);
+        let input = raw_input.trim().to_string();
+        self.quick_open_active = false;
+        match self.quick_input_mode {
+            QuickInputMode::OpenPath => {}
+            QuickInputMode::RenameBuffer if !input.is_empty() => {
+                self.rename_current_buffer(input, cx)
+            }
+            QuickInputMode::SetLanguage if !input.is_empty() => {
+                self.set_current_language(input, cx)
+            }
+            QuickInputMode::ExportSettings => self.export_settings(input, cx),
+            QuickInputMode::ImportSettings if !input.is_empty() => self.import_settings(input, cx),
+            QuickInputMode::PickReference => {
+                if let Some(path) = self.filtered_reference_candidates(&input).first() {
+                    let path = path.clone();
+                    self.open_reference_candidate(path, cx);
+                }
+            }
+            QuickInputMode::PickCodeAction => {
+                if let Some(action) = self.filtered_code_actions(&input).first() {
+                    let action = action.clone();
+                    self.run_code_action(action, cx);
+                }
+            }
+            QuickInputMode::NewFile if !input.is_empty() => {
+                self.create_file(Self::resolve_input_path(&input), String::new(), cx)
+            }
+            QuickInputMode::NewFolder if !input.is_empty() => {
+                self.create_folder(Self::resolve_input_path(&input), cx)
+            }
+            QuickInputMode::MoveFile if !input.is_empty() => {
+                self.move_current_file(Self::resolve_input_path(&input), cx)
+            }
+            QuickInputMode::Find if !input.is_empty() => self.find_text(&input, cx),
+            QuickInputMode::Find => {
+                self.clear_search();
+                self.set_status("已清除查找");
+            }
+            QuickInputMode::Replace => match self.search_query.clone() {
+                Some(query) => self.replace_text(&query, &raw_input, cx),
+                None => self.set_status("先用 Cmd+F 查找要替换的内容"),
+            },
+            QuickInputMode::SetMark if !input.is_empty() => {
+                self.set_mark(keymap::mark_name(&input), cx)
+            }
+            QuickInputMode::GotoMark if !input.is_empty() => {
+                self.jump_to_mark(keymap::mark_name(&input), cx)
+            }
+            QuickInputMode::PickCompletion => {
+                if let Some(item) = self.filtered_completions(&input).first() {
+                    let item = item.clone();
+                    self.accept_completion(item, cx);
+                }
+            }
+            QuickInputMode::PickClipboard => {
+                if let Some((index, _)) = self.filtered_clipboard(&input).first() {
+                    self.paste_from_history(*index, cx);
+                }
+            }
+            QuickInputMode::ReplayEditLog if input.is_empty() => self.replay_journal(cx),
+            QuickInputMode::ReplayEditLog => {
+                self.replay_edit_log(Self::resolve_input_path(&input), cx)
+            }
+            QuickInputMode::TransformCase => {
+                if let Some(transform) = Self::filtered_case_transforms(&input).first() {
+                    self.transform_case(*transform, cx);
+                }
+            }
+            QuickInputMode::InlineThread if !input.is_empty() => self.ask_inline_thread(input, cx),
+            QuickInputMode::RunTool => {
+                if let Some(tool) = self.filtered_tools(&input).into_iter().next() {
+                    self.run_external_tool(tool, cx);
+                }
+            }
+            QuickInputMode::GitCommand => {
+                if let Some(action) = self.filtered_git_actions(&input).into_iter().next() {
+                    self.run_git_action(action, cx);
+                }
+            }
+            QuickInputMode::AddWorktree if !input.is_empty() => self.add_worktree(&input, cx),
+            QuickInputMode::SearchWorkspace if !input.is_empty() => {
+                self.search_workspace(raw_input, cx)
+            }
+            QuickInputMode::RecentWorkspace => {
+                if let Some(path) = self.filtered_recent_workspaces(&input).into_iter().next() {
+                    self.open_recent_workspace(path, cx);
+                }
+            }
+            QuickInputMode::PickPullRequest => {
+                if let Some(request) = self.filtered_pull_requests(&input).into_iter().next() {
+                    self.open_pull_request(request, cx);
+                }
+            }
+            QuickInputMode::CommentPullRequest if !input.is_empty() => {
+                self.comment_on_pull_request(raw_input, cx)
+            }
+            _ => {}
+        }
+        cx.notify();
+    }
+
+    /// 打开文本中的 `path:line:column` 引用；引用有多个候选文件时弹出选择框
+    pub fn open_file_reference(&mut self, text: &str, cx: &mut Context<'_, Self>) -> bool {
+        let Some(reference) =
+            FileReference::parse(text).or_else(|| FileReference::find_all(text).into_iter().next())
+        else {
+            return false;
+        };
+        let Ok(root) = std::env::current_dir() else {
+            return false;
+        };
+
+        let follow_symlinks = self.config.editor.follow_symlinks;
+        self.set_status(format!("正在查找 {}", reference.label()));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let path_text = reference.path.clone();
+                let candidates = app
+                    .background_executor()
+                    .spawn(async move {
+                        Workspace::single_root(&root)
+                            .map(|workspace| {
+                                workspace
+                                    .with_follow_symlinks(follow_symlinks)
+                                    .resolve_reference(&path_text)
+                            })
+                            .unwrap_or_default()
+                    })
+                    .await;
+
+                let _ = this.update(&mut app, |view, cx| {
+                    let line = reference.line.unwrap_or(1).saturating_sub(1);
+                    let column = reference.column.unwrap_or(1).saturating_sub(1);
+                    match candidates.as_slice() {
+                        [] => view.set_status(format!("未找到文件 {}", reference.path)),
+                        [path] => view.open_file_at(path, line, column, cx),
+                        _ => {
+                            view.reference_candidates = candidates;
+                            view.reference_position = (line, column);
+                            view.begin_quick_input(QuickInputMode::PickReference, cx);
+                        }
+                    }
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+        true
+    }
+
+    fn filtered_reference_candidates(&self, filter: &str) -> Vec<PathBuf> {
+        let filter = filter.to_lowercase();
+        self.reference_candidates
+            .iter()
+            .filter(|path| path.to_string_lossy().to_lowercase().contains(&filter))
+            .cloned()
+            .collect()
+    }
+
+    fn open_reference_candidate(&mut self, path: PathBuf, cx: &mut Context<'_, Self>) {
+        let (line, column) = self.reference_position;
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        self.reference_candidates.clear();
+        self.open_file_at(&path, line, column, cx);
+        cx.notify();
+    }
+
+    /// 切换 AI 面板显示
+    pub fn toggle_ai_panel(&mut self, cx: &mut Context<'_, Self>) {
+        self.show_ai_panel = !self.show_ai_panel;
+
+        if self.show_ai_panel && self.ai_panel.is_none() {
+            let ai_engine = self.ai_engine.clone();
+            let theme = self.syntax_theme;
+            self.ai_panel = Some(cx.new(|cx| AIPanel::new(cx, ai_engine, theme)));
+            self.set_ai_context(cx);
+        }
+
+        cx.notify();
+    }
+
+    /// 设置 AI 面板上下文
+    pub fn set_ai_context(&mut self, cx: &mut Context<'_, Self>) {
+        if let Some(ai_panel) = &self.ai_panel {
+            let buffer_manager = self.buffer_manager.clone();
+            let file_path = self.current_file_path.clone();
+            let language = self.current_file_language();
+            let ai_panel = ai_panel.clone();
+
+            cx.spawn(move |_this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+
+                async move {
+                    if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
+                        let snapshot = buffer_handle.lock().await.snapshot().await;
+                        if let Ok(context) =
+                            AIPanel::build_context_from_snapshot(&snapshot, file_path, &language)
+                        {
+                            let _ = ai_panel.update(&mut app, move |panel, _| {
+                                panel.set_buffer_context(context);
+                            });
+                        }
+                    }
+
+                    anyhow::Ok(())
+                }
+            })
+            .detach();
+        }
+    }
+
+    /// 向 AI 发送消息
+    pub fn send_ai_message(&mut self, message: String, cx: &mut Context<'_, Self>) {
+        if let Some(ai_panel) = &self.ai_panel {
+            let ai_panel = ai_panel.clone();
+            let ai_executor = self.ai_executor.clone();
+
+            cx.spawn(move |_this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+
+                async move {
+                    if let Ok(mut panel_state) = ai_panel.update(&mut app, |panel, _| panel.clone())
+                    {
+                        // 如果这里将来报 E0282，就按 AIPanel 定义补 turbofish：
+                        // panel_state.send_message::<AIPanelMessage>(message).await
+                        let (state, result) = ai_executor
+                            .spawn(async move {
+                                let result = panel_state.send_message(message).await;
+                                (panel_state, result)
+                            })
+                            .await?;
+                        panel_state = state;
+                        if let Err(e) = result {
+                            log::error!("Failed to send AI message: {}", e);
+                        }
+
+                        let _ = ai_panel.update(&mut app, |panel, _| {
+                            *panel = panel_state;
+                        });
+                    }
+
+                    anyhow::Ok(())
+                }
+            })
+            .detach();
+        }
+    }
+
+    fn ai_tab(&self, cx: &App) -> AIPanelTab {
+        self.ai_panel
+            .as_ref()
+            .map_or(AIPanelTab::Chat, |panel| panel.read(cx).tab())
+    }
+
+    /// 向 Composer 发送修改指令，附上工作集中各文件和当前文件的内容
+    pub(crate) fn send_composer_instruction(
+        &mut self,
+        instruction: String,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some(ai_panel) = self.ai_panel.clone() else {
+            return;
+        };
+        let ai_executor = self.ai_executor.clone();
+        let buffer_manager = self.buffer_manager.clone();
+        let mut paths: Vec<PathBuf> = ai_panel
+            .read(cx)
+            .working_set()
+            .files()
+            .into_iter()
+            .map(Self::resolve_patch_path)
+            .collect();
+        if let Some(current) = self.current_file_path.clone().filter(|path| path.exists()) {
+            if !paths.contains(&current) {
+                paths.insert(0, current);
+            }
+        }
+        let cwd = std::env::current_dir().unwrap_or_default();
+        self.set_status("Composer 正在生成修改…");
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let mut files = Vec::new();
+                for path in paths {
+                    // 还不存在的文件（Composer 提议新建的）没有内容可附
+                    if let Ok(snapshot) = buffer_manager.file_snapshot(&path).await {
+                        let display = path.strip_prefix(&cwd).unwrap_or(&path);
+                        files.push((display.display().to_string(), snapshot.text()));
+                    }
+                }
+
+                let mut result = Ok(());
+                if let Ok(mut panel_state) = ai_panel.update(&mut app, |panel, _| panel.clone()) {
+                    let (state, composed) = ai_executor
+                        .spawn(async move {
+                            let result = panel_state.compose(instruction, files).await;
+                            (panel_state, result)
+                        })
+                        .await?;
+                    panel_state = state;
+                    result = composed;
+                    let _ = ai_panel.update(&mut app, |panel, cx| {
+                        *panel = panel_state;
+                        cx.notify();
+                    });
+                }
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) => {
+                            let proposed = ai_panel
+                                .read(cx)
+                                .working_set()
+                                .count(&ChangeStatus::Proposed);
+                            view.set_status(format!("Composer 提出了 {} 处修改", proposed));
+                            // 有新的提议时直接进入审阅
+                            if proposed > 0 {
+                                view.composer_review = Some(ComposerReview::default());
+                            }
+                        }
+                        Err(e) => view.set_status(format!("Composer 失败: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 接受或撤回 Composer 的修改。接受时应用到缓冲区（不写回磁盘），撤回已接受的修改时
+    /// 把新代码换回原来的代码；每处修改一步撤销
+    pub(crate) fn resolve_composer_changes(
+        &mut self,
+        indices: Vec<usize>,
+        accept: bool,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some(ai_panel) = self.ai_panel.clone() else {
+            return;
+        };
+        let changes: Vec<_> = {
+            let set = ai_panel.read(cx).working_set();
+            indices
+                .into_iter()
+                .filter_map(|idx| set.changes.get(idx).cloned().map(|change| (idx, change)))
+                .collect()
+        };
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let mut statuses = Vec::new();
+                let mut created = Vec::new();
+                for (idx, change) in changes {
+                    let patch = change.patch;
+                    let path = Self::resolve_patch_path(&patch.file_path);
+                    let status = match (accept, change.status) {
+                        (true, ChangeStatus::Accepted) | (false, ChangeStatus::Reverted) => {
+                            continue
+                        }
+                        (true, _) if patch.old_code.is_empty() && !path.exists() => {
+                            created.push((path, patch.new_code));
+                            ChangeStatus::Accepted
+                        }
+                        (true, _) => {
+                            match Self::replace_patch_text(
+                                &buffer_manager,
+                                &path,
+                                &patch.old_code,
+                                &patch.new_code,
+                            )
+                            .await
+                            {
+                                Ok(()) => ChangeStatus::Accepted,
+                                Err(e) => ChangeStatus::Failed(e),
+                            }
+                        }
+                        (false, ChangeStatus::Accepted) => {
+                            match Self::replace_patch_text(
+                                &buffer_manager,
+                                &path,
+                                &patch.new_code,
+                                &patch.old_code,
+                            )
+                            .await
+                            {
+                                Ok(()) => ChangeStatus::Reverted,
+                                Err(e) => ChangeStatus::Failed(e),
+                            }
+                        }
+                        (false, _) => ChangeStatus::Reverted,
+                    };
+                    statuses.push((idx, status));
+                }
+
+                let failed = statuses
+                    .iter()
+                    .filter(|(_, status)| matches!(status, ChangeStatus::Failed(_)))
+                    .count();
+                let resolved = statuses.len() - failed;
+                ai_panel.update(&mut app, |panel, cx| {
+                    for (idx, status) in statuses {
+                        if let Some(change) = panel.working_set_mut().changes.get_mut(idx) {
+                            change.status = status;
+                        }
+                    }
+                    cx.notify();
+                })?;
+                this.update(&mut app, |view, cx| {
+                    for (path, content) in created {
+                        view.create_file(path, content, cx);
+                    }
+                    let action = if accept { "接受" } else { "撤回" };
+                    view.set_status(if failed == 0 {
+                        format!("已{} {} 处修改", action, resolved)
+                    } else {
+                        format!("已{} {} 处修改，{} 处失败", action, resolved, failed)
+                    });
+                    view.refresh_buffer_view(cx);
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 在 `path` 中把 `find` 第一次出现的位置替换为 `replace`
+    async fn replace_patch_text(
+        buffer_manager: &BufferManager,
+        path: &Path,
+        find: &str,
+        replace: &str,
+    ) -> Result<(), String> {
+        if find.is_empty() {
+            return Err("没有可定位的代码".to_string());
+        }
+        let snapshot = buffer_manager
+            .file_snapshot(path)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some((start, end)) = edit_preview::locate(&snapshot.text(), find) else {
+            return Err("未找到原始代码".to_string());
+        };
+        let mut preview = EditPreview::new("Composer").with_agent("Composer");
+        preview.push(path, start, end, replace);
+        buffer_manager
+            .load_preview_text(&mut preview)
+            .await
+            .map_err(|e| e.to_string())?;
+        buffer_manager
+            .apply_preview(&preview)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// 自然语言搜索代码库，首次使用时在后台构建索引
+    pub fn search_codebase(&mut self, query: String, cx: &mut Context<'_, Self>) {
+        let Some(ai_panel) = self.ai_panel.clone() else {
+            return;
+        };
+        if self.code_index.is_none()
+            && self
+                .performance_mode()
+                .is_some_and(|mode| mode.disable_ai_indexing)
+        {
+            self.set_status("性能模式下不建立代码索引，无法搜索代码库");
+            cx.notify();
+            return;
+        }
+        let ai_executor = self.ai_executor.clone();
+        let cached_index = self.code_index.clone();
+        let root = std::env::current_dir().ok();
+        let options = self.walk_options();
+        let max_files = self.config.editor.max_indexed_files;
+        self.set_status("正在搜索代码…");
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let index = match cached_index {
+                    Some(index) => index,
+                    None => {
+                        let Some(root) = root else {
+                            return anyhow::Ok(());
+                        };
+                        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+                        let build = app.background_executor().spawn(async move {
+                            Self::build_code_index(root, options, progress_tx)
+                        });
+                        while let Some(indexed) = progress_rx.recv().await {
+                            let _ = this.update(&mut app, |view, cx| {
+                                view.set_status(format!("正在索引代码… {} 个文件", indexed));
+                                cx.notify();
+                            });
+                        }
+
+                        let (index, summary) = build.await;
+                        let index = Arc::new(index);
+                        let cached = index.clone();
+                        let _ = this.update(&mut app, |view, cx| {
+                            view.code_index = Some(cached);
+                            if let Some(banner) = Self::scan_banner_text(&summary, max_files) {
+                                view.scan_banner = Some(banner);
+                            }
+                            cx.notify();
+                        });
+                        index
+                    }
+                };
+
+                if let Ok(mut panel_state) = ai_panel.update(&mut app, |panel, _| panel.clone()) {
+                    let (state, result) = ai_executor
+                        .spawn(async move {
+                            let result = panel_state.ask_codebase(query, &index).await;
+                            (panel_state, result)
+                        })
+                        .await?;
+                    panel_state = state;
+                    if let Err(e) = result {
+                        log::error!("Failed to search codebase: {}", e);
+                    }
+
+                    let _ = ai_panel.update(&mut app, |panel, _| {
+                        *panel = panel_state;
+                    });
+                }
+
+                let _ = this.update(&mut app, |view, cx| {
+                    view.set_status("代码搜索完成");
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 读取需要重新索引的文件；已删除、过大或不是文本的文件内容为 None，从索引中移除
+    fn read_indexed_files(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<String>)> {
+        paths
+            .into_iter()
+            .map(|path| {
+                let small = std::fs::metadata(&path)
+                    .is_ok_and(|meta| meta.is_file() && meta.len() <= MAX_INDEXED_FILE_BYTES);
+                let content = small.then(|| std::fs::read_to_string(&path).ok()).flatten();
+                (path, content)
+            })
+            .collect()
+    }
+
+    /// 文件保存或在磁盘上变化后更新代码索引；还没有建立索引时什么也不做。
+    /// 正在进行的搜索仍使用更新前的索引
+    fn update_code_index(&mut self, files: Vec<(PathBuf, Option<String>)>) {
+        let Some(index) = self.code_index.as_mut() else {
+            return;
+        };
+        let index = Arc::make_mut(index);
+        for (path, content) in files {
+            if !index.root().is_some_and(|root| path.starts_with(root)) {
+                continue;
+            }
+            match content {
+                Some(content) => index.add_file(&path, &content),
+                None => index.remove_file(&path),
+            }
+        }
+    }
+
+    /// 构建代码索引，每处理一批文件就通过 `progress` 报告已处理数量
+    fn build_code_index(
+        root: PathBuf,
+        options: WalkOptions,
+        progress: tokio::sync::mpsc::UnboundedSender<usize>,
+    ) -> (CodeIndex, WalkSummary) {
+        let mut index = CodeIndex::new(Some(root.clone()));
+        let Ok(workspace) = Workspace::single_root(&root) else {
+            return (index, WalkSummary::default());
+        };
+
+        let mut processed = 0;
+        let summary = workspace.walk_files(&options, |batch| {
+            processed += batch.len();
+            for path in batch {
+                let too_large = std::fs::metadata(&path)
+                    .map(|meta| meta.len() > MAX_INDEXED_FILE_BYTES)
+                    .unwrap_or(true);
+                if too_large {
+                    continue;
+                }
+                if let Ok(content) = std::fs::read_to_string(&path) {
+                    index.add_file(&path, &content);
+                }
+            }
+            let _ = progress.send(processed);
+            true
+        });
+
+        (index, summary)
+    }
+
+    pub(crate) fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            max_files: Some(self.config.editor.max_indexed_files),
+            timeout: Some(WORKSPACE_SCAN_TIMEOUT),
+            skip_hidden: true,
+            skip_dirs: SKIPPED_DIRS.iter().map(|dir| dir.to_string()).collect(),
+        }
+    }
+
+    fn scan_banner_text(summary: &WalkSummary, max_files: usize) -> Option<String> {
+        if summary.limit_reached {
+            Some(format!(
+                "工作区文件超过 {} 个，仅收录了前 {} 个；可在设置中调整 editor.max_indexed_files",
+                max_files, summary.files
+            ))
+        } else if summary.timed_out {
+            Some(format!("扫描工作区超时，仅收录了 {} 个文件", summary.files))
+        } else {
+            None
+        }
+    }
+
+    /// 在后台扫描工作区文件，结果分批推送给快速打开；超出上限或超时时显示提示条
+    fn scan_workspace_files(&mut self, cx: &mut Context<'_, Self>) {
+        if self.workspace_scan.is_some() {
+            return;
+        }
+        let Ok(root) = std::env::current_dir() else {
+            return;
+        };
+        let workspace = match Workspace::single_root(&root) {
+            Ok(workspace) => workspace.with_follow_symlinks(self.config.editor.follow_symlinks),
+            Err(e) => {
+                log::error!("Failed to scan workspace: {}", e);
+                return;
+            }
+        };
+        let options = self.walk_options();
+        let max_files = self.config.editor.max_indexed_files;
+        self.workspace_files.clear();
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let (batch_tx, mut batch_rx) = tokio::sync::mpsc::unbounded_channel();
+                // 重新扫描时接收端被丢弃，遍历随之停止
+                let walk = app.background_executor().spawn(async move {
+                    workspace.walk_files(&options, |batch| batch_tx.send(batch).is_ok())
+                });
+                while let Some(batch) = batch_rx.recv().await {
+                    this.update(&mut app, |view, cx| {
+                        view.workspace_files.extend(batch);
+                        view.set_status(format!(
+                            "正在扫描工作区… {} 个文件",
+                            view.workspace_files.len()
+                        ));
+                        cx.notify();
+                    })?;
+                }
+
+                let summary = walk.await;
+                this.update(&mut app, |view, cx| {
+                    view.set_status(format!("工作区共 {} 个文件", summary.files));
+                    view.scan_banner = Self::scan_banner_text(&summary, max_files);
+                    cx.notify();
+                })?;
+
+                anyhow::Ok(())
+            }
+        });
+        self.workspace_scan = Some(task);
+    }
+
+    /// 按相对路径筛选工作区文件，最近打开过的排在前面；没有输入时列出最近打开的文件
+    fn quick_open_matches(&self, filter: &str) -> Vec<PathBuf> {
+        let filter = filter.to_lowercase();
+        let root = std::env::current_dir().unwrap_or_default();
+        let recent: Vec<&Path> = self.recent.files_in(&root).collect();
+        if filter.is_empty() {
+            return recent
+                .into_iter()
+                .take(QUICK_OPEN_RESULTS)
+                .map(Path::to_path_buf)
+                .collect();
+        }
+        let matches = |path: &&Path| {
+            path.strip_prefix(&root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_lowercase()
+                .contains(&filter)
+        };
+        let mut seen = HashSet::new();
+        recent
+            .iter()
+            .copied()
+            .filter(matches)
+            .chain(
+                self.workspace_files
+                    .iter()
+                    .map(PathBuf::as_path)
+                    .filter(matches),
+            )
+            .filter(|path| seen.insert(*path))
+            .take(QUICK_OPEN_RESULTS)
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    fn render_quick_open_matches(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::OpenPath {
+            return list;
+        }
+
+        let root = std::env::current_dir().unwrap_or_default();
+        for (idx, path) in self
+            .quick_open_matches(self.quick_open_input.trim())
+            .into_iter()
+            .enumerate()
+        {
+            let label = path
+                .strip_prefix(&root)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            list = list.child(
+                div()
+                    .id(("quick-open-match", idx as u64))
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(label)
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.quick_open_active = false;
+                        view.quick_open_input.clear();
+                        view.open_file(&path, cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    /// 检查配置的语言服务器是否已安装，缺失时提示安装方式，其余的直接启动
+    fn check_language_servers(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.config.lsp.enabled {
+            return;
+        }
+        let servers = self.config.lsp.servers.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let configs = servers.clone();
+                let missing = app
+                    .background_executor()
+                    .spawn(async move { editor_lsp::installer::missing_servers(&configs) })
+                    .await;
+
+                this.update(&mut app, |view, cx| {
+                    let available = servers
+                        .into_iter()
+                        .filter(|config| !missing.iter().any(|m| m.language == config.language))
+                        .collect();
+                    view.start_language_servers(available, cx);
+                    if !missing.is_empty() {
+                        let names: Vec<&str> = missing.iter().map(|s| s.command.as_str()).collect();
+                        view.set_status(format!("未找到语言服务器: {}", names.join(", ")));
+                        view.missing_servers = missing;
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn start_language_servers(
+        &mut self,
+        servers: Vec<editor_infra::config::LSPServerConfig>,
+        cx: &mut Context<'_, Self>,
+    ) {
+        if servers.is_empty() {
+            return;
+        }
+        self.watch_server_crashes(cx);
+        self.watch_apply_edits(cx);
+        self.watch_diagnostics(cx);
+        let Ok(root) = std::env::current_dir() else {
+            return;
+        };
+        let root_uri = path_to_uri(&root);
+        let manager = self.lsp_manager.clone();
+
+        // 语言服务器客户端依赖 tokio 运行时
+        let started = self.lsp_executor.spawn(async move {
+            for config in servers {
+                if let Err(e) = manager.start_server_for_language(&config, &root_uri).await {
+                    log::warn!("Failed to start {}: {}", config.command, e);
+                }
+            }
+        });
+
+        // 服务器启动前打开的文件需要重新同步
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                started.await?;
+                this.update(&mut app, |view, cx| view.watch_current_buffer(cx))?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 工作区文件在编辑器外被创建、修改或删除时发送 workspace/didChangeWatchedFiles，
+    /// 例如其他工具改动 Cargo.toml 后 rust-analyzer 能及时重新加载；
+    /// 已打开且未修改的文件按差异重新加载，保留光标与滚动位置
+    fn watch_workspace_files(&mut self, root: PathBuf, cx: &mut Context<'_, Self>) {
+        if self.file_watch.is_some() {
+            return;
+        }
+        let Ok(workspace) = Workspace::single_root(&root)
+            .map(|ws| ws.with_follow_symlinks(self.config.editor.follow_symlinks))
+        else {
+            return;
+        };
+        let options = self.walk_options();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+
+        let buffer_manager = self.buffer_manager.clone();
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                // 递归添加监听要遍历整个目录树，放到后台线程
+                let watched = app
+                    .background_executor()
+                    .spawn(async move { FileWatcher::new(workspace, options) })
+                    .await;
+                let mut watcher = match watched {
+                    Ok(watcher) => watcher,
+                    Err(e) => {
+                        log::warn!("Failed to watch {}: {}", root.display(), e);
+                        return anyhow::Ok(());
+                    }
+                };
+                while let Some(changes) = watcher.changes().await {
+                    let current_path = buffer_manager.get_current_file_path().await;
+                    let events = buffer_manager.apply_file_changes(&changes).await;
+                    let indexing = this.update(&mut app, |view, cx| {
+                        if !events.is_empty() {
+                            view.show_disk_changes(&events, current_path.as_ref());
+                            view.refresh_buffer_view(cx);
+                        }
+                        view.refresh_git_status(cx);
+                        view.code_index.is_some()
+                    })?;
+                    if indexing {
+                        let paths = changes.iter().map(|change| change.path.clone()).collect();
+                        let files = app
+                            .background_executor()
+                            .spawn(async move { Self::read_indexed_files(paths) })
+                            .await;
+                        this.update(&mut app, |view, _| view.update_code_index(files))?;
+                    }
+
+                    let events: Vec<(String, FileChangeType)> = changes
+                        .into_iter()
+                        .map(|change| {
+                            let kind = match change.kind {
+                                FileChangeKind::Created => FileChangeType::Created,
+                                FileChangeKind::Changed => FileChangeType::Changed,
+                                FileChangeKind::Deleted => FileChangeType::Deleted,
+                            };
+                            (path_to_uri(&change.path), kind)
+                        })
+                        .collect();
+                    let manager = manager.clone();
+                    executor.spawn(async move {
+                        if let Err(e) = manager.notify_watched_files_changed(&events).await {
+                            log::warn!("Failed to send didChangeWatchedFiles: {}", e);
+                        }
+                    });
+                }
+                anyhow::Ok(())
+            }
+        });
+        self.file_watch = Some(task);
+    }
+
+    /// 在状态栏说明打开的文件在磁盘上发生了什么；冲突比重新加载更需要注意，优先显示
+    fn show_disk_changes(&mut self, events: &[DiskChangeEvent], current_path: Option<&PathBuf>) {
+        let name = |path: &Path| {
+            path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string())
+        };
+        let mut reloaded = Vec::new();
+        let mut conflicted = Vec::new();
+        let mut deleted = Vec::new();
+        for event in events {
+            match event {
+                DiskChangeEvent::Reloaded { path, map } => {
+                    if Some(path) == current_path {
+                        self.keep_scroll_position(map);
+                    }
+                    reloaded.push(name(path));
+                }
+                DiskChangeEvent::Conflicted { path } => conflicted.push(name(path)),
+                DiskChangeEvent::Deleted { path } => deleted.push(name(path)),
+            }
+        }
+        if !conflicted.is_empty() {
+            self.set_status(format!(
+                "磁盘上的 {} 已被修改，与未保存的修改冲突：保存则保留当前内容，Cmd+Alt+R 改用磁盘内容",
+                conflicted.join(", ")
+            ));
+        } else if !deleted.is_empty() {
+            self.set_status(format!("已在磁盘上删除: {}", deleted.join(", ")));
+        } else if !reloaded.is_empty() {
+            self.set_status(format!("已从磁盘重新加载: {}", reloaded.join(", ")));
+        }
+    }
+
+    /// 放弃未保存的修改，改用磁盘上的内容，可撤销
+    fn reload_from_disk(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = buffer_manager.reload_from_disk(&path).await;
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(map) => {
+                            if let Some(map) = &map {
+                                view.keep_scroll_position(map);
+                            }
+                            view.set_status("已改用磁盘上的内容");
+                        }
+                        Err(e) => view.set_status(format!("重新加载失败: {}", e)),
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 内容重新加载后，让原来顶部的行仍停在顶部
+    fn keep_scroll_position(&mut self, map: &LineMap) {
+        let line_height = self.line_height();
+        let offset = self.scroll_handle.offset();
+        let top_line = self.top_visible_line();
+        let shift = map.map_line(top_line) as f32 - top_line as f32;
+        self.scroll_handle
+            .set_offset(gpui::point(offset.x, offset.y - px(shift * line_height)));
+    }
+
+    /// 编辑区顶部的行号
+    fn top_visible_line(&self) -> usize {
+        let row =
+            (-f32::from(self.scroll_handle.offset().y) / self.line_height()).max(0.0) as usize;
+        match self.wrap_layout() {
+            Some(wrap) => wrap
+                .rows()
+                .get(row)
+                .map_or(self.lines.len(), |row| row.line),
+            None => row,
+        }
+    }
+
+    /// 滚动到让 `line` 停在编辑区顶部
+    fn scroll_to_top_line(&mut self, line: usize) {
+        let offset = self.scroll_handle.offset();
+        let row = match self.wrap_layout() {
+            Some(wrap) => wrap.row_of(editor_core_text::Cursor::new(line, 0)),
+            None => line,
+        };
+        let y = px(-(row as f32) * self.line_height());
+        self.scroll_handle.set_offset(gpui::point(offset.x, y));
+    }
+
+    /// 语言服务器异常退出时显示最后几行错误输出
+    fn watch_server_crashes(&mut self, cx: &mut Context<'_, Self>) {
+        if self.lsp_crash_watch.is_some() {
+            return;
+        }
+        let mut crashes = self.lsp_manager.subscribe_crashes();
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                loop {
+                    match crashes.recv().await {
+                        Ok(crash) => {
+                            this.update(&mut app, |view, cx| {
+                                view.set_status(format!(
+                                    "{} 异常退出: {}",
+                                    crash.command,
+                                    crash.last_lines.last().cloned().unwrap_or_default()
+                                ));
+                                view.server_crash = Some(crash);
+                                cx.notify();
+                            })?;
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                anyhow::Ok(())
+            }
+        });
+        self.lsp_crash_watch = Some(task);
+    }
+
+    /// 打开语言服务器日志，有多个时弹出选择框
+    pub fn open_server_logs(&mut self, cx: &mut Context<'_, Self>) {
+        let mut logs: Vec<PathBuf> = std::fs::read_dir(ServerLog::logs_dir())
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        logs.sort();
+
+        match logs.len() {
+            0 => {
+                self.set_status("暂无语言服务器日志");
+                cx.notify();
+            }
+            // 日志从末尾开始看
+            1 => self.open_file_at(&logs[0], usize::MAX, 0, cx),
+            _ => {
+                self.reference_candidates = logs;
+                self.reference_position = (usize::MAX, 0);
+                self.begin_quick_input(QuickInputMode::PickReference, cx);
+            }
+        }
+    }
+
+    fn render_server_crash_banner(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let banner = div().id("server-crash-banner");
+        let Some(crash) = &self.server_crash else {
+            return banner;
+        };
+
+        let title = match crash.exit_code {
+            Some(code) => format!(
+                "{} 语言服务器 {} 异常退出（退出码 {}）",
+                crash.language, crash.command, code
+            ),
+            None => format!("{} 语言服务器 {} 异常退出", crash.language, crash.command),
+        };
+        let log_path = crash.log_path.clone();
+
+        let mut output = div()
+            .mt_1()
+            .flex()
+            .flex_col()
+            .text_xs()
+            .text_color(rgb(0xe0a0a0));
+        for line in &crash.last_lines {
+            output = output.child(line.clone());
+        }
+
+        banner
+            .px_3()
+            .py_1()
+            .bg(rgb(0x3a1414))
+            .text_sm()
+            .text_color(rgb(0xff9a9a))
+            .flex()
+            .flex_col()
+            .child(
+                div().flex().justify_between().child(title).child(
+                    div()
+                        .flex()
+                        .gap_2()
+                        .child(
+                            div()
+                                .id("server-crash-logs")
+                                .px_2()
+                                .rounded(px(4.0))
+                                .bg(rgb(0x5a2020))
+                                .cursor_pointer()
+                                .child("查看日志")
+                                .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                    view.server_crash = None;
+                                    view.open_file_at(&log_path, usize::MAX, 0, cx);
+                                })),
+                        )
+                        .child(
+                            div()
+                                .id("server-crash-dismiss")
+                                .px_2()
+                                .cursor_pointer()
+                                .child("✕")
+                                .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                    view.server_crash = None;
+                                    cx.notify();
+                                })),
+                        ),
+                ),
+            )
+            .child(output)
+    }
+
+    /// 用指定方式安装第一个缺失的语言服务器
+    fn install_language_server(&mut self, method: InstallMethod, cx: &mut Context<'_, Self>) {
+        let Some(server) = self.missing_servers.first().cloned() else {
+            return;
+        };
+        if self.installing_server {
+            return;
+        }
+        self.installing_server = true;
+        self.set_status(format!("正在安装 {}…", server.command));
+        cx.notify();
+
+        let executor = self.task_executor.clone();
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                // 下载依赖 tokio 运行时
+                let target = server.clone();
+                let result = executor
+                    .spawn(async move { editor_lsp::installer::install(&target, &method).await })
+                    .await;
+
+                this.update(&mut app, |view, cx| {
+                    view.installing_server = false;
+                    match result {
+                        Ok(Ok(path)) => {
+                            view.missing_servers.retain(|s| s.command != server.command);
+                            view.set_status(format!(
+                                "已安装 {}: {}",
+                                server.command,
+                                path.display()
+                            ));
+                        }
+                        Ok(Err(e)) => {
+                            view.set_status(format!("安装 {} 失败: {}", server.command, e))
+                        }
+                        Err(e) => log::error!("Language server install task failed: {}", e),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn render_lsp_install_banner(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let banner = div().id("lsp-install-banner");
+        let Some(server) = self.missing_servers.first() else {
+            return banner;
+        };
+
+        let mut actions = div().flex().gap_2();
+        for (idx, method) in server.methods.iter().enumerate() {
+            let label = match method {
+                InstallMethod::Download { .. } => "下载安装".to_string(),
+                InstallMethod::Command { .. } => format!("运行 {}", method.label()),
+            };
+            let method = method.clone();
+            actions = actions.child(
+                div()
+                    .id(("lsp-install", idx as u64))
+                    .px_2()
+                    .rounded(px(4.0))
+                    .bg(rgb(0x1a4d8f))
+                    .cursor_pointer()
+                    .child(label)
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.install_language_server(method.clone(), cx);
+                    })),
+            );
+        }
+        actions = actions.child(
+            div()
+                .id("lsp-install-dismiss")
+                .px_2()
+                .cursor_pointer()
+                .child("忽略")
+                .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                    if !view.missing_servers.is_empty() {
+                        view.missing_servers.remove(0);
+                    }
+                    cx.notify();
+                })),
+        );
+
+        let message = if self.installing_server {
+            format!("正在安装 {}…", server.command)
+        } else if server.methods.is_empty() {
+            format!(
+                "未找到 {} 语言服务器 {}，请手动安装后重启",
+                server.language, server.command
+            )
+        } else {
+            format!(
+                "未找到 {} 语言服务器 {}，可手动运行: {}",
+                server.language,
+                server.command,
+                server.methods.last().map(|m| m.label()).unwrap_or_default()
+            )
+        };
+
+        banner
+            .px_3()
+            .py_1()
+            .bg(rgb(0x10283a))
+            .text_sm()
+            .text_color(rgb(0x9ad1ff))
+            .flex()
+            .justify_between()
+            .child(message)
+            .child(actions)
+    }
+
+    fn render_scan_banner(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let banner = div().id("scan-banner");
+        let Some(text) = self.scan_banner.clone() else {
+            return banner;
+        };
+
+        banner
+            .px_3()
+            .py_1()
+            .bg(rgb(0x3a2f10))
+            .text_sm()
+            .text_color(rgb(0xf0c36a))
+            .flex()
+            .justify_between()
+            .child(text)
+            .child(
+                div()
+                    .id("scan-banner-close")
+                    .cursor_pointer()
+                    .child("✕")
+                    .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                        view.scan_banner = None;
+                        cx.notify();
+                    })),
+            )
+    }
+
+    /// 打开文件并将光标定位到指定位置（行列从 0 开始）
+    /// 单个数字为编号书签，其余为命名书签
+    /// 在光标处设置书签，同名书签移到这里
+    pub fn set_mark(&mut self, name: MarkName, cx: &mut Context<'_, Self>) {
+        let cursor = self
+            .selection
+            .map(|selection| selection.active)
+            .unwrap_or(editor_core_text::Cursor::zero());
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let mark = name.clone();
+        let applied = self
+            .buffer_manager
+            .submit_edit(&path, move |buffer| buffer.set_mark(mark, cursor).boxed());
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                if applied.await.is_none() {
+                    return anyhow::Ok(());
+                }
+                this.update(&mut app, |view, cx| {
+                    view.set_status(format!("已设置书签 {}（行 {}）", name, cursor.line + 1));
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 光标所在行有书签时全部移除，否则在光标处加一个编号书签
+    pub fn toggle_bookmark(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(cursor) = self.selection.map(|selection| selection.active) else {
+            return;
+        };
+        let on_line: Vec<MarkName> = self
+            .bookmarks
+            .iter()
+            .filter(|(_, position)| position.line == cursor.line)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                if !on_line.is_empty() {
+                    for name in &on_line {
+                        buffer.remove_mark(name);
+                    }
+                    format!("已移除行 {} 的书签", cursor.line + 1)
+                } else if let Some(number) = buffer.free_mark_number() {
+                    buffer.set_mark(MarkName::Number(number), cursor).await;
+                    format!("已设置书签 {}（行 {}）", number, cursor.line + 1)
+                } else {
+                    "编号书签已用完，请先移除或使用命名书签".to_string()
+                }
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let Some(status) = applied.await else {
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    view.set_status(status);
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub fn jump_to_mark(&mut self, name: MarkName, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let mark = name.clone();
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let target = buffer.mark(&mark).await;
+                if let Some(cursor) = target {
+                    buffer.set_cursor(cursor).await;
+                }
+                target
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let Some(target) = applied.await else {
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    match target {
+                        Some(cursor) => view.set_status(format!(
+                            "跳转到书签 {}（行 {}）",
+                            name,
+                            cursor.line + 1
+                        )),
+                        None => view.set_status(format!("没有书签 {}", name)),
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 回到上一次跳转（点击远处、搜索、转到定义等）前的位置，或在回退后重新前进。
+    /// 当前文件中没有可去的位置时，切换到之前离开的文件
+    pub fn navigate_selection_history(&mut self, forward: bool, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let path = buffer_manager.navigate(forward).await;
+                this.update(&mut app, |view, cx| {
+                    match path {
+                        Some(path) => view.current_file_path = Some(path),
+                        None if forward => view.set_status("没有可以前进到的位置"),
+                        None => view.set_status("没有可以返回的位置"),
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 跳到下一个或上一个书签，到头后回绕
+    pub fn jump_to_next_mark(&mut self, forward: bool, cx: &mut Context<'_, Self>) {
+        let cursor = self
+            .selection
+            .map(|selection| selection.active)
+            .unwrap_or(editor_core_text::Cursor::zero());
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let target = buffer.next_mark(cursor, forward).await;
+                if let Some((_, position)) = &target {
+                    buffer.set_cursor(*position).await;
+                }
+                target
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let Some(target) = applied.await else {
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    match target {
+                        Some((name, position)) => view.set_status(format!(
+                            "跳转到书签 {}（行 {}）",
+                            name,
+                            position.line + 1
+                        )),
+                        None => view.set_status("没有书签"),
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub fn open_file_at(
+        &mut self,
+        file_path: &Path,
+        line: usize,
+        column: usize,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let buffer_manager = self.buffer_manager.clone();
+        let path = file_path.to_path_buf();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let result = if buffer_manager.get_buffer(&path).await.is_some() {
+                    buffer_manager.set_current_buffer(&path).await
+                } else {
+                    buffer_manager.open_file(&path).await
+                };
+
+                match result {
+                    Ok(_) => {
+                        buffer_manager
+                            .submit_edit(&path, move |buffer| {
+                                buffer
+                                    .set_cursor(editor_core_text::Cursor::new(line, column))
+                                    .boxed()
+                            })
+                            .await;
+
+                        let _ = this.update(&mut app, |view, cx| {
+                            view.current_file_path = Some(path.clone());
+                            view.set_status(format!("跳转到 {}:{}", path.display(), line + 1));
+                            view.refresh_buffer_view(cx);
+                            cx.notify();
+                        });
+                    }
+                    Err(e) => log::error!("Failed to open file {}: {}", path.display(), e),
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 打开文件并选中 `lines`（从 0 开始，不含结尾），被选中的行短暂高亮
+    pub fn open_file_range(
+        &mut self,
+        file_path: &Path,
+        lines: std::ops::Range<usize>,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let buffer_manager = self.buffer_manager.clone();
+        let path = file_path.to_path_buf();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = if buffer_manager.get_buffer(&path).await.is_some() {
+                    buffer_manager.set_current_buffer(&path).await
+                } else {
+                    buffer_manager.open_file(&path).await
+                };
+                if let Err(e) = result {
+                    this.update(&mut app, |view, cx| {
+                        view.set_status(format!("无法打开 {}: {}", path.display(), e));
+                        cx.notify();
+                    })?;
+                    return anyhow::Ok(());
+                }
+                let selected = lines.clone();
+                buffer_manager
+                    .submit_edit(&path, move |buffer| {
+                        async move {
+                            // 索引可能比文件旧，引用的行不一定还在
+                            let start = editor_core_text::Cursor::new(selected.start, 0)
+                                .clamp_to(buffer)
+                                .await;
+                            let end = editor_core_text::Cursor::new(selected.end, 0)
+                                .clamp_to(buffer)
+                                .await;
+                            // 光标停在开头，长的范围也从第一行看起
+                            buffer
+                                .set_selection(editor_core_text::Selection::new(end, start))
+                                .await;
+                        }
+                        .boxed()
+                    })
+                    .await;
+
+                this.update(&mut app, |view, cx| {
+                    view.current_file_path = Some(path.clone());
+                    view.scroll_to_top_line(lines.start.saturating_sub(3));
+                    view.set_status(format!(
+                        "跳转到 {}:{}-{}",
+                        path.display(),
+                        lines.start + 1,
+                        lines.end
+                    ));
+                    view.flash_lines(path, lines, cx);
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn flash_lines(
+        &mut self,
+        path: PathBuf,
+        lines: std::ops::Range<usize>,
+        cx: &mut Context<'_, Self>,
+    ) {
+        self.line_flash = Some(LineFlash { path, lines });
+        self.line_flash_timer = Some(cx.spawn(
+            move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+                async move {
+                    app.background_executor().timer(LINE_FLASH_DURATION).await;
+                    this.update(&mut app, |view, cx| {
+                        view.line_flash = None;
+                        cx.notify();
+                    })?;
+                    anyhow::Ok(())
+                }
+            },
+        ));
+    }
+
+    /// 请求代码解释
+    pub fn request_code_explanation(&mut self, cx: &mut Context<'_, Self>) {
+        self.set_ai_context(cx);
+        self.send_ai_message("请解释这段代码的功能和工作原理。".to_string(), cx);
+    }
+
+    /// 让 AI 解释主光标处的符号，AI 面板未打开时先打开
+    pub fn explain_symbol_at_cursor(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.show_ai_panel {
+            self.toggle_ai_panel(cx);
+        }
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let mut symbol = None;
+                if let Some(handle) = buffer_manager.get_current_buffer().await {
+                    let buffer = handle.lock().await;
+                    if let Some(&cursor) = buffer.get_cursors().first() {
+                        if let (Some(range), Some(line)) = (
+                            buffer.word_at(cursor).await,
+                            buffer.get_line(cursor.line).await,
+                        ) {
+                            let word: String = line
+                                .chars()
+                                .skip(range.start.column)
+                                .take(range.end.column - range.start.column)
+                                .collect();
+                            symbol = Some((word, cursor.line));
+                        }
+                    }
+                }
+                this.update(&mut app, |view, cx| match symbol {
+                    Some((symbol, line)) => {
+                        view.set_ai_context(cx);
+                        view.send_ai_message(
+                            format!(
+                                "请解释第 {} 行的符号 `{}` 在当前代码中的含义和作用。",
+                                line + 1,
+                                symbol
+                            ),
+                            cx,
+                        );
+                    }
+                    None => {
+                        view.set_status("光标处没有符号");
+                        cx.notify();
+                    }
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 请求代码改进
+    pub fn request_code_improvements(&mut self, cx: &mut Context<'_, Self>) {
+        self.set_ai_context(cx);
+        self.send_ai_message("请分析这段代码并提供改进建议。".to_string(), cx);
+    }
+
+    /// 复制选中文本
+    pub fn copy_selection(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
+                    let texts = buffer_handle.lock().await.selected_texts().await;
+                    this.update(&mut app, |view, cx| {
+                        view.remember_copied(texts, "已复制", cx);
+                        cx.notify();
+                    })?;
+                }
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 剪切选中的文本，多个选区一步撤销
+    pub fn cut_selection(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, |buffer| {
+            async move {
+                let texts = buffer.cut_selections().await;
+                (texts, buffer.get_selections().to_vec(), buffer.is_dirty())
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                if let Some((texts, selections, is_dirty)) = applied.await {
+                    this.update(&mut app, |view, cx| {
+                        view.remember_copied(texts, "已剪切", cx);
+                        view.set_selections(selections);
+                        view.is_dirty = is_dirty;
+                        cx.notify();
+                    })?;
+                }
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 转换所有选区（或光标处的词）的大小写或命名风格
+    pub fn transform_case(&mut self, transform: CaseTransform, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let changed = buffer.transform_case(transform).await;
+                (changed, buffer.get_selections().to_vec(), buffer.is_dirty())
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                if let Some((changed, selections, is_dirty)) = applied.await {
+                    this.update(&mut app, |view, cx| {
+                        if changed {
+                            view.set_status(format!("已转换为 {}", transform.label()));
+                        } else {
+                            view.set_status("没有需要转换的文本");
+                        }
+                        view.set_selections(selections);
+                        view.is_dirty = is_dirty;
+                        cx.notify();
+                    })?;
+                }
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn filtered_case_transforms(filter: &str) -> Vec<CaseTransform> {
+        let filter = filter.to_lowercase();
+        CaseTransform::ALL
+            .into_iter()
+            .filter(|transform| transform.label().to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    fn render_case_transforms(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::TransformCase {
+            return list;
+        }
+
+        for (index, transform) in Self::filtered_case_transforms(self.quick_open_input.trim())
+            .into_iter()
+            .enumerate()
+        {
+            list = list.child(
+                div()
+                    .id(("case-transform", index as u64))
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(transform.label())
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.quick_open_active = false;
+                        view.quick_open_input.clear();
+                        view.transform_case(transform, cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    /// 在后台读取工作区的分支和改动的文件，文件树和状态栏据此标记
+    pub(crate) fn refresh_git_status(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(repository) = std::env::current_dir()
+            .ok()
+            .and_then(|dir| GitRepository::discover(&dir))
+        else {
+            self.git_status = None;
+            return;
+        };
+        let executor = self.task_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                // tokio::process 需要 tokio 运行时
+                let status = executor
+                    .spawn(async move { repository.status().await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+                this.update(&mut app, |view, cx| {
+                    view.git_status = match status {
+                        Ok(status) => Some(status),
+                        Err(e) => {
+                            log::warn!("Failed to read git status: {}", e);
+                            None
+                        }
+                    };
+                    // 提交或切换分支后 HEAD 中的版本可能变了
+                    view.git_base = None;
+                    view.refresh_line_changes(cx);
+                    view.refresh_blame(cx);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 在后台读取当前文件的 Blame，文件切换和 Git 状态刷新时调用
+    fn refresh_blame(&mut self, cx: &mut Context<'_, Self>) {
+        if self
+            .performance_mode()
+            .is_some_and(|mode| mode.disable_git_blame)
+        {
+            self.blame = None;
+            return;
+        }
+        let repository = self
+            .git_status
+            .as_ref()
+            .and_then(|_| std::env::current_dir().ok())
+            .and_then(|dir| GitRepository::discover(&dir));
+        let (Some(repository), Some(path)) = (repository, self.current_file_path.clone()) else {
+            self.blame = None;
+            return;
+        };
+        // 未跟踪的文件没有 Blame
+        if self
+            .git_status
+            .as_ref()
+            .and_then(|status| status.file(&path))
+            .is_some_and(|status| matches!(status, FileStatus::Untracked | FileStatus::Added))
+        {
+            self.blame = None;
+            return;
+        }
+        let executor = self.task_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let blame_path = path.clone();
+                // tokio::process 需要 tokio 运行时
+                let blame = executor
+                    .spawn(async move { repository.blame(&blame_path).await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+                this.update(&mut app, |view, cx| {
+                    if view.current_file_path.as_ref() != Some(&path) {
+                        return;
+                    }
+                    view.blame = match blame {
+                        Ok(lines) => Some((path, lines)),
+                        Err(e) => {
+                            log::debug!("No blame for {}: {}", path.display(), e);
+                            None
+                        }
+                    };
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 当前缓冲区可用的 Blame：有未保存的改动时行号与已保存的内容对不上
+    fn current_blame(&self) -> Option<&[BlameLine]> {
+        self.blame
+            .as_ref()
+            .filter(|(path, _)| self.current_file_path.as_ref() == Some(path))
+            .filter(|_| self.line_changes.is_empty())
+            .map(|(_, lines)| lines.as_slice())
+    }
+
+    /// 开关行号前的逐行 Blame
+    pub fn toggle_blame_gutter(&mut self, cx: &mut Context<'_, Self>) {
+        self.show_blame_gutter = !self.show_blame_gutter;
+        self.set_status(match (self.show_blame_gutter, self.blame.is_some()) {
+            (true, true) => "已显示逐行 Blame",
+            (true, false) => "已显示逐行 Blame（当前文件没有 Git 记录）",
+            (false, _) => "已隐藏逐行 Blame",
+        });
+        cx.notify();
+    }
+
+    /// Blame 栏中一行的标签，同一提交连续的行只在第一行显示
+    fn blame_label(blame: &[BlameLine], idx: usize) -> String {
+        let Some(line) = blame.get(idx) else {
+            return String::new();
+        };
+        if idx > 0 && blame[idx - 1].commit == line.commit {
+            return String::new();
+        }
+        if !line.is_committed() {
+            return "未提交".to_string();
+        }
+        let author: String = line.author.chars().take(14).collect();
+        format!("{} {}", line.short_commit(), author)
+    }
+
+    /// 文件树中 Git 状态的标记和颜色
+    fn git_status_marker(status: FileStatus) -> (&'static str, u32) {
+        match status {
+            FileStatus::Modified => ("M", 0xd7ba7d),
+            FileStatus::Added => ("A", 0x81c784),
+            FileStatus::Deleted => ("D", 0xe57373),
+            FileStatus::Renamed => ("R", 0x81c784),
+            FileStatus::Untracked => ("U", 0x81c784),
+            FileStatus::Conflicted => ("!", 0xe57373),
+        }
+    }
+
+    /// 把复制或剪切的文本放进系统剪贴板和剪贴板历史
+    fn remember_copied(&mut self, texts: Vec<String>, status: &str, cx: &mut Context<'_, Self>) {
+        if texts.iter().all(String::is_empty) {
+            self.set_status("没有选中的文本");
+            return;
+        }
+        let entry = ClipboardEntry::new(texts);
+        cx.write_to_clipboard(gpui::ClipboardItem::new_string(entry.text()));
+        self.set_status(format!("{}: {}", status, entry.preview(40)));
+        self.clipboard.push(entry.pieces().to_vec());
+    }
+
+    /// 粘贴系统剪贴板的内容。内容是本编辑器多选区复制的时，按选区分别粘贴
+    pub fn paste_text(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) else {
+            self.set_status("剪贴板为空");
+            cx.notify();
+            return;
+        };
+        let pieces = self
+            .clipboard
+            .find_text(&text)
+            .map(|entry| entry.pieces().to_vec())
+            .unwrap_or_else(|| vec![text]);
+        self.clipboard.push(pieces.clone());
+        self.paste_pieces(pieces, cx);
+    }
+
+    /// 粘贴剪贴板历史中的第 `index` 条，并把它放回系统剪贴板
+    fn paste_from_history(&mut self, index: usize, cx: &mut Context<'_, Self>) {
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        let Some(entry) = self.clipboard.get(index).cloned() else {
+            return;
+        };
+        cx.write_to_clipboard(gpui::ClipboardItem::new_string(entry.text()));
+        self.clipboard.push(entry.pieces().to_vec());
+        self.paste_pieces(entry.pieces().to_vec(), cx);
+    }
+
+    fn paste_pieces(&mut self, pieces: Vec<String>, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                buffer.paste(&pieces).await;
+                buffer.get_selections().to_vec()
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                if let Some(selections) = applied.await {
+                    this.update(&mut app, |view, cx| {
+                        view.set_status("已粘贴");
+                        view.set_selections(selections);
+                        view.is_dirty = true;
+                        view.refresh_bracket_match(cx);
+                        cx.notify();
+                    })?;
+                }
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 按筛选词过滤剪贴板历史，保留原始序号
+    fn filtered_clipboard(&self, filter: &str) -> Vec<(usize, ClipboardEntry)> {
+        let filter = filter.to_lowercase();
+        self.clipboard
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.text().to_lowercase().contains(&filter))
+            .map(|(index, entry)| (index, entry.clone()))
+            .collect()
+    }
+
+    fn render_clipboard_history(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::PickClipboard {
+            return list;
+        }
+
+        for (index, entry) in self.filtered_clipboard(self.quick_open_input.trim()) {
+            let pieces = entry.pieces().len();
+            list = list.child(
+                div()
+                    .id(("clipboard-entry", index as u64))
+                    .flex()
+                    .justify_between()
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(entry.preview(60))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x777777))
+                            .child(if pieces > 1 {
+                                format!("{} 个选区", pieces)
+                            } else {
+                                format!("{} 字符", entry.text().chars().count())
+                            }),
+                    )
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.paste_from_history(index, cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    /// 撤销操作
+    pub fn undo(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, |buffer| {
+            async move { buffer.undo().await.then(|| buffer.is_dirty()) }.boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some(Some(is_dirty)) = applied.await {
+                    let _ = this.update(&mut app, |view, cx| {
+                        view.set_status("撤销");
+                        view.refresh_buffer_view(cx);
+                        view.is_dirty = is_dirty;
+                        cx.notify();
+                    });
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 把整个缓冲区的换行符转换为 `line_ending`，可一步撤销
+    pub fn convert_line_ending(&mut self, line_ending: LineEnding, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            buffer.set_line_ending(line_ending).boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let Some(changed) = applied.await else {
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    if changed {
+                        view.set_status(format!("换行符已转换为 {}", line_ending.label()));
+                    } else {
+                        view.set_status(format!("换行符已是 {}", line_ending.label()));
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 开关行内诊断，波浪线不受影响
+    pub fn toggle_inline_diagnostics(&mut self, cx: &mut Context<'_, Self>) {
+        let inline = &mut self.config.ui.inline_diagnostics;
+        inline.enabled = !inline.enabled;
+        let enabled = inline.enabled;
+        self.set_status(if enabled {
+            "已开启行内诊断"
+        } else {
+            "已关闭行内诊断"
+        });
+        cx.notify();
+    }
+
+    /// 开关长行折行显示
+    pub fn toggle_soft_wrap(&mut self, cx: &mut Context<'_, Self>) {
+        let ui = &mut self.config.ui;
+        ui.soft_wrap = !ui.soft_wrap;
+        let enabled = ui.soft_wrap;
+        self.set_status(if enabled {
+            "已开启自动折行"
+        } else {
+            "已关闭自动折行"
+        });
+        cx.notify();
+    }
+
+    /// 开关某一严重级别的行内诊断
+    pub fn toggle_inline_severity(
+        &mut self,
+        severity: DiagnosticSeverity,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let inline = &mut self.config.ui.inline_diagnostics;
+        let shown = match severity {
+            DiagnosticSeverity::Error => &mut inline.errors,
+            DiagnosticSeverity::Warning => &mut inline.warnings,
+            DiagnosticSeverity::Information => &mut inline.information,
+            DiagnosticSeverity::Hint => &mut inline.hints,
+        };
+        *shown = !*shown;
+        let message = format!(
+            "行内诊断{}{}",
+            if *shown { "显示" } else { "隐藏" },
+            Self::severity_label(severity)
+        );
+        // 调整级别时一并打开行内诊断，否则看不到效果
+        inline.enabled = true;
+        self.set_status(message);
+        cx.notify();
+    }
+
+    pub(crate) fn severity_label(severity: DiagnosticSeverity) -> &'static str {
+        match severity {
+            DiagnosticSeverity::Error => "错误",
+            DiagnosticSeverity::Warning => "警告",
+            DiagnosticSeverity::Information => "信息",
+            DiagnosticSeverity::Hint => "提示",
+        }
+    }
+
+    /// 重做操作
+    pub fn redo(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, |buffer| {
+            async move { buffer.redo().await.then(|| buffer.is_dirty()) }.boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some(Some(is_dirty)) = applied.await {
+                    let _ = this.update(&mut app, |view, cx| {
+                        view.set_status("重做");
+                        view.refresh_buffer_view(cx);
+                        view.is_dirty = is_dirty;
+                        cx.notify();
+                    });
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 在当前缓冲区（有查找范围时只在范围内）查找，并选中光标之后的第一个匹配
+    pub fn find_text(&mut self, query: &str, cx: &mut Context<'_, Self>) {
+        self.search_query = Some(query.to_string());
+        self.run_search(query.to_string(), true, cx);
+    }
+
+    /// 重新查找；`select` 时选中光标之后的第一个匹配，否则只刷新高亮
+    fn run_search(&mut self, query: String, select: bool, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        let options = self.search_options;
+        let range_ids = self
+            .search_scope
+            .as_ref()
+            .map(|scope| scope.range_ids.clone());
+
+        let search = move |this: WeakEntity<EditorView>, mut app: AsyncApp| async move {
+            let Some(handle) = buffer_manager.get_current_buffer().await else {
+                return anyhow::Ok(());
+            };
+            let (snapshot, scope) = {
+                let buffer = handle.lock().await;
+                let mut scope = Vec::new();
+                for id in range_ids.iter().flatten() {
+                    scope.extend(buffer.tracked_range(*id).await);
+                }
+                (buffer.snapshot().await, range_ids.map(|_| scope))
+            };
+            let result = match &scope {
+                Some(scope) => snapshot.search_in(&query, options, scope),
+                None => snapshot.search(&query, options),
+            };
+
+            this.update(&mut app, |view, cx| {
+                if view.search_query.as_ref() != Some(&query) {
+                    return;
+                }
+                if let (Some(current), Some(ranges)) = (&mut view.search_scope, scope) {
+                    current.ranges = ranges;
+                }
+                match result {
+                    Ok(matches) if !select => view.search_matches = matches,
+                    Ok(matches) if matches.is_empty() => {
+                        view.search_matches.clear();
+                        let place = if view.search_scope.is_some() {
+                            "选区内"
+                        } else {
+                            ""
+                        };
+                        view.set_status(format!("{}未找到 \"{}\"", place, query));
+                    }
+                    Ok(matches) => {
+                        view.search_matches = matches;
+                        let cursor = view
+                            .selection
+                            .map(|sel| sel.start())
+                            .unwrap_or(editor_core_text::Cursor::zero());
+                        let index = view
+                            .search_matches
+                            .iter()
+                            .position(|m| {
+                                (m.start_position.line, m.start_position.column)
+                                    >= (cursor.line, cursor.column)
+                            })
+                            .unwrap_or(0);
+                        view.select_search_match(index, cx);
+                    }
+                    Err(_) if !select => view.search_matches.clear(),
+                    Err(e) => {
+                        view.search_matches.clear();
+                        view.set_status(e.to_string());
+                    }
+                }
+                cx.notify();
+            })?;
+
+            anyhow::Ok(())
+        };
+        if select {
+            cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                search(this, cx.clone())
+            })
+            .detach();
+        } else {
+            self.spawn_refresh(Refresh::Search, Self::refresh_search, cx, search);
+        }
+    }
+
+    /// 编辑后按最近一次查找的内容刷新匹配
+    fn refresh_search(&mut self, cx: &mut Context<'_, Self>) {
+        if let Some(query) = self.search_query.clone() {
+            self.run_search(query, false, cx);
+        }
+    }
+
+    /// 跳到下一个查找结果
+    pub fn find_next(&mut self, cx: &mut Context<'_, Self>) {
+        if self.search_matches.is_empty() {
+            self.begin_quick_input(QuickInputMode::Find, cx);
+            return;
+        }
+        // 选中当前匹配后，active 位于匹配末尾
+        let cursor = self
+            .selection
+            .map(|sel| sel.active)
+            .unwrap_or(editor_core_text::Cursor::zero());
+        let index = self
+            .search_matches
+            .iter()
+            .position(|m| {
+                (m.start_position.line, m.start_position.column) >= (cursor.line, cursor.column)
+            })
+            .unwrap_or(0);
+        self.select_search_match(index, cx);
+    }
+
+    fn select_search_match(&mut self, index: usize, cx: &mut Context<'_, Self>) {
+        let Some(found) = self.search_matches.get(index).copied() else {
+            return;
+        };
+        let total = self.search_matches.len();
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let selected = self.buffer_manager.submit_edit(&path, move |buffer| {
+            buffer
+                .set_selection(editor_core_text::Selection::new(
+                    found.start_position,
+                    found.end_position,
+                ))
+                .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                selected.await;
+                this.update(&mut app, |view, cx| {
+                    view.set_status(format!("第 {}/{} 个匹配", index + 1, total));
+                    view.refresh_buffer_view(cx);
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn toggle_search_option(&mut self, option: &str, cx: &mut Context<'_, Self>) {
+        let options = &mut self.search_options;
+        match option {
+            "c" => options.case_sensitive = !options.case_sensitive,
+            "w" => options.whole_word = !options.whole_word,
+            "r" => options.regex = !options.regex,
+            "p" => options.preserve_case = !options.preserve_case,
+            "s" => return self.toggle_search_scope(cx),
+            _ => return,
+        }
+        let options = self.search_options;
+        self.set_status(format!(
+            "查找选项: 区分大小写 {} · 全词 {} · 正则 {} · 替换保留大小写 {}",
+            if options.case_sensitive { "开" } else { "关" },
+            if options.whole_word { "开" } else { "关" },
+            if options.regex { "开" } else { "关" },
+            if options.preserve_case { "开" } else { "关" },
+        ));
+        cx.notify();
+    }
+
+    /// 在选区内查找与在整个文件中查找之间切换；选区随编辑移动，直到清除查找
+    fn toggle_search_scope(&mut self, cx: &mut Context<'_, Self>) {
+        if let Some(scope) = self.search_scope.take() {
+            Self::release_search_scope(&scope);
+            self.set_status("查找范围：整个文件");
+            cx.notify();
+            return;
+        }
+        let ranges: Vec<_> = self
+            .selections
+            .iter()
+            .filter(|selection| !selection.is_collapsed())
+            .map(|selection| selection.start()..selection.end())
+            .collect();
+        if ranges.is_empty() {
+            self.set_status("没有选中文本，无法限定查找范围");
+            cx.notify();
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let tracked = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let mut range_ids = Vec::with_capacity(ranges.len());
+                for range in &ranges {
+                    range_ids.push(buffer.track_range(range.start, range.end).await);
+                }
+                SearchScope {
+                    model: buffer.text_model(),
+                    range_ids,
+                    ranges,
+                }
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let Some(scope) = tracked.await else {
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    view.set_status(format!("查找范围：{} 个选区", scope.ranges.len()));
+                    if let Some(old) = view.search_scope.replace(scope) {
+                        Self::release_search_scope(&old);
+                    }
+                    view.refresh_search(cx);
+                    cx.notify();
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn release_search_scope(scope: &SearchScope) {
+        scope.model.update_tracked_ranges(|ranges| {
+            for id in &scope.range_ids {
+                ranges.remove(*id);
+            }
+        });
+    }
+
+    /// 清除查找结果和查找范围
+    fn clear_search(&mut self) {
+        self.search_query = None;
+        self.search_matches.clear();
+        if let Some(scope) = self.search_scope.take() {
+            Self::release_search_scope(&scope);
+        }
+    }
+
+    /// 把 `query` 的全部匹配（有查找范围时只在范围内）替换为 `replacement`，可一次撤销
+    pub fn replace_text(&mut self, query: &str, replacement: &str, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let query = query.to_string();
+        let replacement = replacement.to_string();
+        let options = self.search_options;
+        let range_ids = self
+            .search_scope
+            .as_ref()
+            .map(|scope| scope.range_ids.clone());
+
+        let searched = query.clone();
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let mut scope = Vec::new();
+                for id in range_ids.iter().flatten() {
+                    scope.extend(buffer.tracked_range(*id).await);
+                }
+                let scope = range_ids.as_ref().map(|_| scope.as_slice());
+                buffer
+                    .replace_all(&searched, options, &replacement, scope)
+                    .await
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let Some(result) = applied.await else {
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(0) => view.set_status(format!("没有可替换的 \"{}\"", query)),
+                        Ok(count) => view.set_status(format!("已替换 {} 处", count)),
+                        Err(e) => view.set_status(e.to_string()),
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn format_job(&self) -> Option<FormatJob> {
+        Some(FormatJob {
+            buffer_manager: self.buffer_manager.clone(),
+            lsp_manager: self.lsp_manager.clone(),
+            executor: self.lsp_executor.clone(),
+            formatters: self.config.editor.formatters.clone(),
+            path: self.current_file_path.clone()?,
+            language: self.current_file_language(),
+            tab_size: self.indent_style().width(self.config.editor.tab_size),
+            insert_spaces: self.indent_style().uses_spaces(),
+            selection: None,
+        })
+    }
+
+    /// 当前文件的缩进方式：优先沿用文件已有的风格，否则按配置
+    fn indent_style(&self) -> IndentStyle {
+        self.current_indent.unwrap_or_else(|| {
+            IndentStyle::for_language(
+                &self.current_file_language(),
+                self.config.editor.use_spaces,
+                self.config.editor.tab_size,
+            )
+        })
+    }
+
+    /// 输入语言服务器声明的触发字符后按返回的修改调整格式，光标留在原处。
+    /// 期间继续输入则放弃结果
+    fn format_on_type(
+        &mut self,
+        ch: String,
+        version: usize,
+        cursor: editor_core_text::Cursor,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let language = self.current_file_language();
+        let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let indent = self.indent_style();
+        let (tab_size, insert_spaces) = (
+            indent.width(self.config.editor.tab_size),
+            indent.uses_spaces(),
+        );
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let Some(handle) = buffer_manager.get_buffer(&path).await else {
+                    return anyhow::Ok(());
+                };
+                let snapshot = handle.lock().await.snapshot().await;
+                if snapshot.version() != version {
+                    return anyhow::Ok(());
+                }
+                let position = Position::from_cursor(snapshot.rope(), cursor);
+                let uri = Self::document_uri(&buffer_manager, &path).await;
+                let result = executor
+                    .spawn(async move {
+                        manager
+                            .format_on_type(
+                                &language,
+                                &uri,
+                                version,
+                                position,
+                                &ch,
+                                tab_size,
+                                insert_spaces,
+                            )
+                            .await
+                    })
+                    .await
+                    .map_err(std::io::Error::other)
+                    .and_then(|r| r);
+                let edits = match result {
+                    Ok(Some(edits)) if !edits.is_empty() => edits,
+                    Ok(_) => return anyhow::Ok(()),
+                    Err(e) => {
+                        log::warn!("On-type formatting of {} failed: {}", path.display(), e);
+                        return anyhow::Ok(());
+                    }
+                };
+
+                let applied = buffer_manager.submit_edit(&path, move |buffer| {
+                    async move {
+                        if buffer.version() != version {
+                            return None;
+                        }
+                        let line_ending = buffer.line_ending();
+                        let ranges: Vec<(std::ops::Range<usize>, String)> = edits
+                            .iter()
+                            .map(|edit| {
+                                let (start, end) = edit.range.to_cursors(snapshot.rope());
+                                let start = snapshot.cursor_to_char(start);
+                                let end = snapshot.cursor_to_char(end).max(start);
+                                (
+                                    start..end,
+                                    line_ending.normalize(&edit.new_text).into_owned(),
+                                )
+                            })
+                            .collect();
+                        buffer.replace_ranges(&ranges).await;
+                        Some(buffer.get_selections().to_vec())
+                    }
+                    .boxed()
+                });
+                let Some(selections) = applied.await.flatten() else {
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    view.set_selections(selections);
+                    view.is_dirty = true;
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 格式化当前文件，有选中文本时只格式化主选区，作为一步撤销
+    pub fn format_code(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(mut job) = self.format_job() else {
+            return;
+        };
+        job.selection = self.selection.filter(|selection| !selection.is_collapsed());
+        let target = if job.selection.is_some() {
+            "选区"
+        } else {
+            ""
+        };
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let language = job.language.clone();
+                let result = job.run().await;
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(Some(tool)) => {
+                            view.set_status(format!("已格式化{}（{}）", target, tool))
+                        }
+                        Ok(None) => view.set_status(format!("{} 没有可用的格式化工具", language)),
+                        Err(e) => view.set_status(format!("格式化失败: {}", e)),
+                    }
+                    view.refresh_buffer_view(cx);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 按当前语言的行注释切换选中各行的注释
+    pub fn toggle_comment(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let language = self.current_file_language();
+        let Some(token) = LanguagePack::for_id(&language).and_then(|pack| pack.line_comment) else {
+            self.set_status(format!("{} 没有行注释", language));
+            cx.notify();
+            return;
+        };
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            buffer.toggle_line_comment(token).boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some(toggled) = applied.await {
+                    this.update(&mut app, |view, cx| {
+                        if toggled {
+                            view.set_status("切换注释");
+                            view.refresh_buffer_view(cx);
+                            view.is_dirty = true;
+                        }
+                    })?;
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 缩进代码
+    pub fn indent_code(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let indent = self.indent_style();
+
+        let applied = self
+            .buffer_manager
+            .submit_edit(&path, move |buffer| buffer.insert_tab(indent).boxed());
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
 
             async move {
-                match buffer_manager.save_current_file().await {
-                    Ok(_) => {
-                        let _ = this.update(&mut app, |view, cx| {
-                            view.set_status("保存成功");
-                            view.refresh_buffer_view(cx);
-                            view.is_dirty = false;
-                            cx.notify();
-                        });
+                if applied.await.is_some() {
+                    let _ = this.update(&mut app, |view, cx| {
+                        view.set_status("缩进");
+                        view.refresh_buffer_view(cx);
+                        view.is_dirty = true;
+                        cx.notify();
+                    });
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 对所有光标所在的行执行删除、复制或移动
+    fn edit_lines(&mut self, action: LineAction, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let status = match action {
+                    LineAction::Delete => {
+                        buffer.delete_lines().await;
+                        "删除行"
                     }
-                    Err(e) => log::error!("Failed to save file: {}", e),
+                    LineAction::Duplicate => {
+                        buffer.duplicate_lines().await;
+                        "复制行"
+                    }
+                    LineAction::Move(direction) => {
+                        buffer.move_lines(direction).await;
+                        "移动行"
+                    }
+                };
+                (status, buffer.get_selections().to_vec(), buffer.is_dirty())
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some((status, selections, is_dirty)) = applied.await {
+                    let _ = this.update(&mut app, |view, cx| {
+                        view.set_status(status);
+                        view.set_selections(selections);
+                        view.is_dirty = is_dirty;
+                        view.refresh_bracket_match(cx);
+                        cx.notify();
+                    });
                 }
 
                 anyhow::Ok(())
@@ -317,276 +5574,848 @@ impl EditorView {
         .detach();
     }
 
-    /// 获取当前文件路径
-    pub fn current_file_path(&self) -> Option<&PathBuf> {
-        self.current_file_path.as_ref()
+    /// 拆分或合并光标所在的参数列表，在 tree-sitter 语法树上识别，不依赖语言服务器
+    fn layout_arguments(&mut self, action: ArgumentAction, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let layout = match action {
+            ArgumentAction::Split => ArgumentLayout::Split,
+            ArgumentAction::Join => ArgumentLayout::Joined,
+            ArgumentAction::Wrap => ArgumentLayout::Fit(self.config.editor.max_line_length),
+        };
+        let language = self.current_file_language();
+        let style = self.indent_style();
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let changed = buffer.layout_arguments(&language, layout, style).await;
+                (changed, buffer.get_selections().to_vec(), buffer.is_dirty())
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                if let Some((changed, selections, is_dirty)) = applied.await {
+                    this.update(&mut app, |view, cx| {
+                        if !changed {
+                            view.set_status("光标不在可调整的参数列表中");
+                        }
+                        view.set_selections(selections);
+                        view.is_dirty = is_dirty;
+                        view.refresh_bracket_match(cx);
+                        cx.notify();
+                    })?;
+                }
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
     }
 
-    /// 获取当前文件名称
-    pub fn current_file_name(&self) -> Option<String> {
-        self.current_file_path
-            .as_ref()
-            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+    /// 取消缩进代码（占位）
+    pub fn unindent_code(&mut self, cx: &mut Context<'_, Self>) {
+        log::info!("Unindent code placeholder");
+        cx.notify();
     }
 
-    /// 获取文件语言
-    pub fn current_file_language(&self) -> String {
-        self.current_file_path
-            .as_ref()
-            .and_then(|p| p.extension().map(|e| e.to_string_lossy().to_string()))
-            .unwrap_or_else(|| "text".to_string())
+    /// 创建一个新的临时缓冲区
+    pub fn new_buffer(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        let tab_size = self.config.editor.tab_size;
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let path = buffer_manager.create_new_buffer().await;
+                let (lines, selections, is_dirty, widths) =
+                    EditorView::snapshot_buffer(&buffer_manager, tab_size)
+                        .await
+                        .unwrap_or_default();
+                let _text = if let Some(handle) = buffer_manager.get_buffer(&path).await {
+                    let buffer = handle.lock().await;
+                    buffer.get_text().await
+                } else {
+                    String::new()
+                };
+
+                let open_files = buffer_manager.get_open_files().await;
+
+                let _ = this.update(&mut app, |view, cx| {
+                    view.current_file_path = Some(path.clone());
+                    view.open_files = open_files;
+                    view.lines = lines;
+                    view.line_prefix_widths = widths;
+                    view.set_selections(selections);
+                    view.is_dirty = is_dirty;
+                    view.status_message = "新建 untitled 缓冲区".to_string();
+                    view.refresh_buffer_view(cx);
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
     }
 
-    /// 切换 AI 面板显示
-    pub fn toggle_ai_panel(&mut self, cx: &mut Context<'_, Self>) {
-        self.show_ai_panel = !self.show_ai_panel;
+    /// 在后台重新扫描工作区目录树
+    pub fn reload_file_tree(&mut self, cx: &mut Context<'_, Self>) {
+        let Ok(root) = std::env::current_dir() else {
+            return;
+        };
+        let options = FileTreeOptions {
+            follow_symlinks: self.config.editor.follow_symlinks,
+            respect_ignore_files: true,
+            exclude: self.config.editor.file_tree_exclude.clone(),
+            show_ignored: self.config.editor.file_tree_show_ignored,
+        };
+        // 文件列表已变化，下次快速打开时重新扫描
+        self.workspace_scan = None;
 
-        if self.show_ai_panel && self.ai_panel.is_none() {
-            let ai_engine = self.ai_engine.clone();
-            self.ai_panel = Some(cx.new(|cx| AIPanel::new(cx, ai_engine)));
-            self.set_ai_context(cx);
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let tree = app
+                    .background_executor()
+                    .spawn(async move { FileTree::with_options(root, options) })
+                    .await;
+
+                this.update(&mut app, |view, cx| {
+                    match tree {
+                        Ok(tree) => view.file_tree = Some(tree),
+                        Err(e) => log::error!("Failed to scan workspace: {}", e),
+                    }
+                    cx.notify();
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn toggle_tree_directory(&mut self, path: &Path, cx: &mut Context<'_, Self>) {
+        let Some(tree) = self.file_tree.as_mut() else {
+            return;
+        };
+        // 被忽略的目录展开时才读取，且只读一层
+        if tree
+            .find_node(path)
+            .is_some_and(|node| node.is_ignored() && !node.is_expanded())
+        {
+            if let Err(e) = tree.load_children(path) {
+                self.set_status(format!("无法读取 {}: {}", path.display(), e));
+            }
+        }
+        if let Some(node) = self
+            .file_tree
+            .as_mut()
+            .and_then(|tree| tree.find_node_mut(path))
+        {
+            let expanded = node.is_expanded();
+            node.set_expanded(!expanded);
+            cx.notify();
+        }
+    }
+
+    fn render_file_tree(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div()
+            .id("file-tree")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .overflow_y_scroll();
+        let Some(tree) = &self.file_tree else {
+            return list;
+        };
+
+        // 跳过根目录本身
+        for (idx, (depth, node)) in tree.visible_entries().into_iter().enumerate().skip(1) {
+            let path = node.path().to_path_buf();
+            let is_directory = node.is_directory();
+            let mut label = if is_directory {
+                format!(
+                    "{} {}",
+                    if node.is_expanded() { "▾" } else { "▸" },
+                    node.name()
+                )
+            } else {
+                node.name().to_string()
+            };
+            // 符号链接显示指向的目标
+            if let Some(target) = node.link_target() {
+                label.push_str(&format!(" → {}", target.display()));
+            }
+            let git_color = match &self.git_status {
+                Some(status) if is_directory => status.has_changes_in(&path).then(|| {
+                    label.push_str(" •");
+                    0xd7ba7d
+                }),
+                Some(status) => status.file(&path).map(|status| {
+                    let (marker, color) = Self::git_status_marker(status);
+                    label.push_str(&format!("  {}", marker));
+                    color
+                }),
+                None => None,
+            };
+
+            let info_path = path.clone();
+            let menu_path = path.clone();
+            let show_menu = self.tree_menu.as_ref() == Some(&path);
+            list = list.child(
+                div()
+                    .id(("tree-node", idx as u64))
+                    .tooltip(move |_, cx| {
+                        let text = match FileInfo::read(&info_path) {
+                            Ok(info) => Self::file_info_label(&info),
+                            Err(e) => e.to_string(),
+                        };
+                        cx.new(|_| InfoTooltip { text }).into()
+                    })
+                    .pl(px(12.0 * depth as f32))
+                    .pr_2()
+                    .py(px(2.0))
+                    .text_xs()
+                    .text_color(if node.is_ignored() {
+                        rgb(0x5c5c5c)
+                    } else if node.is_symlink() {
+                        rgb(0x7fb8ff)
+                    } else if let Some(color) = git_color {
+                        rgb(color)
+                    } else if is_directory {
+                        rgb(0xd0d0d0)
+                    } else {
+                        rgb(0xaaaaaa)
+                    })
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(label)
+                    .on_mouse_down(
+                        MouseButton::Right,
+                        cx.listener(move |view: &mut EditorView, _: &MouseDownEvent, _, cx| {
+                            view.tree_menu = Some(menu_path.clone());
+                            cx.stop_propagation();
+                            cx.notify();
+                        }),
+                    )
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.tree_menu = None;
+                        if is_directory {
+                            view.toggle_tree_directory(&path, cx);
+                        } else {
+                            view.open_file(&path, cx);
+                        }
+                    })),
+            );
+            if show_menu {
+                list = list.child(self.render_tree_menu(node, depth, cx));
+            }
         }
 
+        list
+    }
+
+    /// 文件树节点右键后显示在其下方的操作：新建文件、新建文件夹、删除
+    fn render_tree_menu(
+        &self,
+        node: &FileTreeNode,
+        depth: usize,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        // 新建在所选目录中，选中的是文件时新建在它旁边
+        let dir = if node.is_directory() {
+            node.path().to_path_buf()
+        } else {
+            node.path()
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default()
+        };
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x3a3a3a))
+                .text_xs()
+                .cursor_pointer()
+                .child(label)
+        };
+        let folder_dir = dir.clone();
+        let path = node.path().to_path_buf();
+
+        div()
+            .flex()
+            .gap_1()
+            .pl(px(12.0 * depth as f32))
+            .pr_2()
+            .py(px(2.0))
+            .child(
+                button("tree-menu-new-file", "新建文件…").on_click(cx.listener(
+                    move |view: &mut EditorView, _, _, cx| {
+                        view.begin_create_in(QuickInputMode::NewFile, &dir, cx);
+                    },
+                )),
+            )
+            .child(
+                button("tree-menu-new-folder", "新建文件夹…").on_click(cx.listener(
+                    move |view: &mut EditorView, _, _, cx| {
+                        view.begin_create_in(QuickInputMode::NewFolder, &folder_dir, cx);
+                    },
+                )),
+            )
+            .child(button("tree-menu-delete", "删除").on_click(cx.listener(
+                move |view: &mut EditorView, _, _, cx| {
+                    view.tree_menu = None;
+                    view.delete_path(path.clone(), cx);
+                },
+            )))
+            .child(button("tree-menu-cancel", "取消").on_click(cx.listener(
+                |view: &mut EditorView, _, _, cx| {
+                    view.tree_menu = None;
+                    cx.notify();
+                },
+            )))
+    }
+
+    /// 打开新建文件或文件夹的输入框，预填相对工作区的 `dir`，只需再输入名称
+    fn begin_create_in(&mut self, mode: QuickInputMode, dir: &Path, cx: &mut Context<'_, Self>) {
+        self.tree_menu = None;
+        self.begin_quick_input(mode, cx);
+        if let Some(relative) = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| dir.strip_prefix(cwd).ok().map(Path::to_path_buf))
+            .filter(|relative| !relative.as_os_str().is_empty())
+        {
+            self.quick_open_input = format!("{}/", relative.display());
+        }
         cx.notify();
     }
 
-    /// 设置 AI 面板上下文
-    pub fn set_ai_context(&mut self, cx: &mut Context<'_, Self>) {
-        if let Some(ai_panel) = &self.ai_panel {
-            let buffer_manager = self.buffer_manager.clone();
-            let file_path = self.current_file_path.clone();
-            let language = self.current_file_language();
-            let ai_panel = ai_panel.clone();
+    /// 新建文件并打开，记录到文件操作日志
+    pub fn create_file(&mut self, path: PathBuf, content: String, cx: &mut Context<'_, Self>) {
+        let created =
+            match self.file_tree.as_mut() {
+                Some(tree) => tree.create_file(&mut self.file_journal, &path, &content),
+                None => self.file_journal.create_file(&path, &content).map(|()| {
+                    FileTreeEvent::Created {
+                        path: path.clone(),
+                        is_dir: false,
+                    }
+                }),
+            };
+        match created {
+            Ok(event) => self.apply_file_tree_event(event, cx),
+            Err(e) => {
+                self.set_status(format!("无法创建 {}: {}", path.display(), e));
+                cx.notify();
+            }
+        }
+    }
 
-            cx.spawn(move |_this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
-                let mut app = cx.clone();
+    /// 新建文件夹，记录到文件操作日志
+    pub fn create_folder(&mut self, path: PathBuf, cx: &mut Context<'_, Self>) {
+        let created = match self.file_tree.as_mut() {
+            Some(tree) => tree.create_dir(&mut self.file_journal, &path),
+            None => self
+                .file_journal
+                .create_dir(&path)
+                .map(|()| FileTreeEvent::Created {
+                    path: path.clone(),
+                    is_dir: true,
+                }),
+        };
+        match created {
+            Ok(event) => self.apply_file_tree_event(event, cx),
+            Err(e) => {
+                self.set_status(format!("无法创建 {}: {}", path.display(), e));
+                cx.notify();
+            }
+        }
+    }
 
-                async move {
-                    if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
-                        let buffer = buffer_handle.lock().await;
-                        if let Ok(context) =
-                            AIPanel::build_context_from_buffer(&buffer, file_path, &language).await
-                        {
-                            let _ = ai_panel.update(&mut app, move |panel, _| {
-                                panel.set_buffer_context(context);
-                            });
-                        }
+    /// 文件树上的新建、删除完成后，让缓冲区、语言服务器和界面跟上磁盘的变化
+    fn apply_file_tree_event(&mut self, event: FileTreeEvent, cx: &mut Context<'_, Self>) {
+        // 快速打开的文件列表已过时，下次打开时重新扫描
+        self.workspace_scan = None;
+        match event {
+            FileTreeEvent::Created {
+                path,
+                is_dir: false,
+            } => self.open_file(&path, cx),
+            FileTreeEvent::Created { path, is_dir: true } => {
+                self.set_status(format!("已创建文件夹: {}", path.display()));
+                cx.notify();
+            }
+            FileTreeEvent::Deleted { path, mode } => {
+                self.set_status(match mode {
+                    DeleteMode::Trash => {
+                        format!("已移到回收站: {}（Cmd+Alt+Z 撤销）", path.display())
                     }
+                    DeleteMode::Permanent => format!("已永久删除: {}", path.display()),
+                });
+                self.reopen_moved_file(path, None, cx);
+            }
+        }
+    }
 
-                    anyhow::Ok(())
+    /// 移动（重命名）当前文件，已打开的缓冲区连同未保存的修改和撤销记录切换到新路径
+    pub fn move_current_file(&mut self, target: PathBuf, cx: &mut Context<'_, Self>) {
+        let Some(source) = self.current_file_path.clone() else {
+            return;
+        };
+
+        if !self.config.editor.update_imports_on_move {
+            match self.file_journal.rename(&source, &target) {
+                Ok(()) => self.reopen_moved_file(source, Some(target), cx),
+                Err(e) => {
+                    self.set_status(format!("无法移动 {}: {}", source.display(), e));
+                    cx.notify();
                 }
-            })
-            .detach();
+            }
+            return;
         }
-    }
 
-    /// 向 AI 发送消息
-    pub fn send_ai_message(&mut self, message: String, cx: &mut Context<'_, Self>) {
-        if let Some(ai_panel) = &self.ai_panel {
-            let ai_panel = ai_panel.clone();
+        let language = self.current_file_language();
+        let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let old_uri = path_to_uri(&source);
+        let new_uri = path_to_uri(&target);
+        self.set_status(format!("正在移动 {}…", source.display()));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                // 先取得语言服务器的修改，此时文件还在原路径
+                let request = {
+                    let (manager, language) = (manager.clone(), language.clone());
+                    let (old_uri, new_uri) = (old_uri.clone(), new_uri.clone());
+                    async move {
+                        manager
+                            .will_rename_file(&language, &old_uri, &new_uri)
+                            .await
+                    }
+                };
+                let edit = executor
+                    .spawn(request)
+                    .await
+                    .map_err(std::io::Error::other)
+                    .and_then(|result| result)
+                    .unwrap_or_else(|e| {
+                        log::warn!("willRenameFiles failed: {}", e);
+                        WorkspaceEdit::default()
+                    });
 
-            cx.spawn(move |_this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
-                let mut app = cx.clone();
+                let moved = this.update(&mut app, |view, cx| {
+                    let result = view.file_journal.rename(&source, &target);
+                    if let Err(e) = &result {
+                        view.set_status(format!("无法移动 {}: {}", source.display(), e));
+                        cx.notify();
+                    }
+                    result.is_ok()
+                })?;
+                if !moved {
+                    return anyhow::Ok(());
+                }
 
-                async move {
-                    if let Ok(mut panel_state) = ai_panel.update(&mut app, |panel, _| panel.clone())
-                    {
-                        // 如果这里将来报 E0282，就按 AIPanel 定义补 turbofish：
-                        // panel_state.send_message::<AIPanelMessage>(message).await
-                        if let Err(e) = panel_state.send_message(message).await {
-                            log::error!("Failed to send AI message: {}", e);
-                        }
+                let renamed = buffer_manager.rename_path(&source, &target).await;
+                Self::reopen_documents(&buffer_manager, &manager, &executor, renamed).await;
 
-                        let _ = ai_panel.update(&mut app, |panel, _| {
-                            *panel = panel_state;
-                        });
+                // 修改里指向原路径的部分落到移动后的文件上
+                let mut edit = edit;
+                for (uri, _) in &mut edit.changes {
+                    if *uri == old_uri {
+                        *uri = new_uri.clone();
                     }
-
-                    anyhow::Ok(())
                 }
-            })
-            .detach();
-        }
-    }
+                let mut preview = Self::preview_from_workspace_edit(
+                    &buffer_manager,
+                    format!("移动 {}", source.display()),
+                    &edit,
+                )
+                .await;
+                let applied = if preview.is_empty() {
+                    Ok(Vec::new())
+                } else {
+                    match buffer_manager.load_preview_text(&mut preview).await {
+                        Ok(()) => buffer_manager.apply_preview(&preview).await,
+                        Err(e) => Err(e),
+                    }
+                };
 
-    /// 请求代码解释
-    pub fn request_code_explanation(&mut self, cx: &mut Context<'_, Self>) {
-        self.set_ai_context(cx);
-        self.send_ai_message("请解释这段代码的功能和工作原理。".to_string(), cx);
-    }
+                executor.spawn(async move {
+                    if let Err(e) = manager
+                        .notify_file_renamed(&language, &old_uri, &new_uri)
+                        .await
+                    {
+                        log::warn!("didRenameFiles failed: {}", e);
+                    }
+                });
 
-    /// 请求代码改进
-    pub fn request_code_improvements(&mut self, cx: &mut Context<'_, Self>) {
-        self.set_ai_context(cx);
-        self.send_ai_message("请分析这段代码并提供改进建议。".to_string(), cx);
+                this.update(&mut app, |view, cx| {
+                    match applied {
+                        Ok(files) if files.is_empty() => {
+                            view.set_status(format!("已移动到 {}", target.display()))
+                        }
+                        Ok(files) => view.set_status(format!(
+                            "已移动到 {}，并更新了 {} 个文件中的引用（未保存）",
+                            target.display(),
+                            files.len()
+                        )),
+                        Err(e) => view.set_status(format!(
+                            "已移动到 {}，但无法更新引用: {}",
+                            target.display(),
+                            e
+                        )),
+                    }
+                    view.remap_moved_paths(&source, &target);
+                    view.refresh_buffer_view(cx);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
     }
 
-    /// 复制选中文本
-    pub fn copy_selection(&mut self, cx: &mut Context<'_, Self>) {
+    /// 关闭当前标签页，记住光标与滚动位置以便重新打开
+    pub fn close_current_tab(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
         let buffer_manager = self.buffer_manager.clone();
+        let scroll_line = self.top_visible_line();
+        let name = self
+            .display_names
+            .get(&path)
+            .cloned()
+            .unwrap_or_else(|| path.display().to_string());
 
-        cx.spawn(
-            move |_this: WeakEntity<EditorView>, _cx: &mut AsyncApp| async move {
-                if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
-                    let buffer = buffer_handle.lock().await;
-                    let selections = buffer.get_selections();
-                    if let Some(selection) = selections.first() {
-                        if !selection.is_collapsed() {
-                            log::info!("Copy selection: {:?}", selection);
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = buffer_manager.close_tab(&path, scroll_line).await;
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) => {
+                            view.tab_scroll_lines.remove(&path);
+                            view.scroll_to_top_line(0);
+                            view.set_status(format!("已关闭 {}（Cmd+Shift+T 重新打开）", name));
                         }
+                        Err(e) => view.set_status(format!("关闭失败: {}", e)),
                     }
-                }
+                    view.refresh_buffer_view(cx);
+                })?;
                 anyhow::Ok(())
-            },
-        )
+            }
+        })
         .detach();
     }
 
-    /// 粘贴文本
-    pub fn paste_text(&mut self, cx: &mut Context<'_, Self>) {
-        log::info!("Paste text placeholder");
-        cx.notify();
-    }
-
-    /// 撤销操作
-    pub fn undo(&mut self, cx: &mut Context<'_, Self>) {
+    /// 固定或取消固定当前标签页，并写入会话
+    pub fn toggle_pin_current_tab(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
         let buffer_manager = self.buffer_manager.clone();
+        let pinned = !self.pinned_files.contains(&path);
 
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
-
             async move {
-                if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
-                    let mut buffer = buffer_handle.lock().await;
-                    if buffer.undo().await {
-                        let _ = this.update(&mut app, |view, cx| {
-                            view.set_status("撤销");
-                            view.refresh_buffer_view(cx);
-                            view.is_dirty = buffer.is_dirty();
-                            cx.notify();
-                        });
+                let result = buffer_manager.set_pinned(&path, pinned).await;
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) if pinned => view.set_status("已固定标签页"),
+                        Ok(()) => view.set_status("已取消固定标签页"),
+                        Err(e) => view.set_status(format!("无法固定标签页: {}", e)),
                     }
-                }
-
+                    view.refresh_buffer_view(cx);
+                })?;
                 anyhow::Ok(())
             }
         })
         .detach();
     }
 
-    /// 重做操作
-    pub fn redo(&mut self, cx: &mut Context<'_, Self>) {
+    /// 关闭其他或全部未固定的标签页，固定的标签页保持打开
+    fn close_tabs(&mut self, mode: TabClose, cx: &mut Context<'_, Self>) {
         let buffer_manager = self.buffer_manager.clone();
+        let current = self.current_file_path.clone();
 
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
-
             async move {
-                if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
-                    let mut buffer = buffer_handle.lock().await;
-                    if buffer.redo().await {
-                        let _ = this.update(&mut app, |view, cx| {
-                            view.set_status("重做");
-                            view.refresh_buffer_view(cx);
-                            view.is_dirty = buffer.is_dirty();
-                            cx.notify();
-                        });
+                let result = match (mode, &current) {
+                    (TabClose::Others, Some(current)) => buffer_manager.close_others(current).await,
+                    _ => buffer_manager.close_all().await,
+                };
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(closed) => view.set_status(format!("已关闭 {} 个标签页", closed.len())),
+                        Err(e) => view.set_status(format!("关闭失败: {}", e)),
                     }
-                }
-
+                    view.refresh_buffer_view(cx);
+                })?;
                 anyhow::Ok(())
             }
         })
         .detach();
     }
 
-    /// 查找文本（占位）
-    pub fn find_text(&mut self, query: &str, cx: &mut Context<'_, Self>) {
-        log::info!("Find text: {}", query);
-        cx.notify();
+    /// 固定标签页只显示名称开头的几个字符
+    fn pinned_tab_label(name: &str) -> String {
+        let short: String = name.chars().take(3).collect();
+        format!("📌{}", short)
     }
 
-    /// 替换文本（占位）
-    pub fn replace_text(&mut self, query: &str, replacement: &str, cx: &mut Context<'_, Self>) {
-        log::info!("Replace '{}' with '{}'", query, replacement);
-        cx.notify();
+    /// 按关闭顺序倒序重新打开标签页，未命名缓冲区从草稿恢复
+    pub fn reopen_closed_tab(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = buffer_manager.reopen_closed_tab().await;
+                let name = match &result {
+                    Ok(Some(tab)) => Some(buffer_manager.display_name(&tab.path).await),
+                    _ => None,
+                };
+                this.update(&mut app, |view, cx| {
+                    match (result, name) {
+                        (Ok(Some(tab)), Some(name)) => {
+                            view.scroll_to_top_line(tab.scroll_line);
+                            view.set_status(format!("已重新打开 {}", name));
+                        }
+                        (Ok(_), _) => view.set_status("没有最近关闭的标签页"),
+                        (Err(e), _) => view.set_status(format!("重新打开失败: {}", e)),
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
     }
 
-    /// 格式化代码（占位）
-    pub fn format_code(&mut self, cx: &mut Context<'_, Self>) {
-        log::info!("Format code placeholder");
-        cx.notify();
+    /// 删除当前文件；默认移到系统回收站并可撤销，配置 `delete_permanently` 后直接删除
+    pub fn delete_current_file(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        if !path.exists() {
+            self.set_status("当前缓冲区没有对应的磁盘文件");
+            cx.notify();
+            return;
+        }
+        self.delete_path(path, cx);
     }
 
-    /// 切换注释（占位）
-    pub fn toggle_comment(&mut self, cx: &mut Context<'_, Self>) {
-        log::info!("Toggle comment placeholder");
+    /// 删除文件或文件夹，同 [`EditorView::delete_current_file`]，其下已打开的缓冲区一并关闭
+    pub fn delete_path(&mut self, path: PathBuf, cx: &mut Context<'_, Self>) {
+        let mode = DeleteMode::from_config(self.config.editor.delete_permanently);
+        let deleted = match self.file_tree.as_mut() {
+            Some(tree) => tree.delete(&mut self.file_journal, &path, mode),
+            None => self
+                .file_journal
+                .delete(&path, mode)
+                .map(|mode| FileTreeEvent::Deleted {
+                    path: path.clone(),
+                    mode,
+                }),
+        };
+        match deleted {
+            Ok(event) => self.apply_file_tree_event(event, cx),
+            Err(e) => {
+                self.set_status(format!("无法删除 {}: {}", path.display(), e));
+                cx.notify();
+            }
+        }
+    }
+
+    /// 撤销最近一次文件操作（新建、移动、删除）
+    pub fn undo_file_operation(&mut self, cx: &mut Context<'_, Self>) {
+        match self.file_journal.undo_last() {
+            Ok(Some(operation)) => {
+                let (closed, restored) = match &operation {
+                    FileOperation::Created { path } => (Some(path.clone()), None),
+                    FileOperation::Renamed { from, to } => (Some(to.clone()), Some(from.clone())),
+                    FileOperation::Deleted { path, .. } => (None, Some(path.clone())),
+                };
+                self.set_status(format!("已撤销: {}", operation.description()));
+                self.reload_file_tree(cx);
+                match closed {
+                    Some(closed) => self.reopen_moved_file(closed, restored, cx),
+                    None => {
+                        if let Some(restored) = restored {
+                            self.open_file(&restored, cx);
+                        }
+                    }
+                }
+            }
+            Ok(None) => self.set_status("没有可撤销的文件操作"),
+            Err(e) => self.set_status(format!("撤销文件操作失败: {}", e)),
+        }
         cx.notify();
     }
 
-    /// 缩进代码
-    pub fn indent_code(&mut self, cx: &mut Context<'_, Self>) {
+    /// 把 `old_path` 及其下已打开的缓冲区移到 `new_path`，没有新路径时关闭它们
+    fn reopen_moved_file(
+        &mut self,
+        old_path: PathBuf,
+        new_path: Option<PathBuf>,
+        cx: &mut Context<'_, Self>,
+    ) {
         let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
 
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
 
             async move {
-                if let Some(buffer_handle) = buffer_manager.get_current_buffer().await {
-                    let mut buffer = buffer_handle.lock().await;
-                    buffer.insert_tab(4).await; // TODO: use config.tab_size
-                    let _ = this.update(&mut app, |view, cx| {
-                        view.set_status("缩进");
-                        view.refresh_buffer_view(cx);
-                        view.is_dirty = true;
-                        cx.notify();
-                    });
+                match &new_path {
+                    Some(new_path) => {
+                        let renamed = buffer_manager.rename_path(&old_path, new_path).await;
+                        Self::reopen_documents(&buffer_manager, &manager, &executor, renamed).await;
+                    }
+                    None => {
+                        // 语言要在关闭前取，关闭后缓冲区就不在了
+                        let mut languages = Vec::new();
+                        for path in buffer_manager.get_open_files().await {
+                            if path.starts_with(&old_path) {
+                                languages.push((buffer_manager.language(&path).await, path));
+                            }
+                        }
+                        buffer_manager.close_files_under(&old_path).await?;
+                        for (language, path) in languages {
+                            let manager = manager.clone();
+                            let uri = path_to_uri(&path);
+                            executor.spawn(async move {
+                                if let Err(e) = manager.notify_file_closed(&language, &uri).await {
+                                    log::warn!("didClose failed: {}", e);
+                                }
+                            });
+                        }
+                    }
                 }
 
+                this.update(&mut app, |view, cx| {
+                    if let Some(new_path) = &new_path {
+                        view.remap_moved_paths(&old_path, new_path);
+                    }
+                    view.refresh_buffer_view(cx);
+                    cx.notify();
+                })?;
+
                 anyhow::Ok(())
             }
         })
         .detach();
     }
 
-    /// 取消缩进代码（占位）
-    pub fn unindent_code(&mut self, cx: &mut Context<'_, Self>) {
-        log::info!("Unindent code placeholder");
-        cx.notify();
+    /// 让语言服务器关闭移动前路径的文档，并以新路径重新打开
+    async fn reopen_documents(
+        buffer_manager: &BufferManager,
+        manager: &Arc<LspServerManager>,
+        executor: &TaskExecutor,
+        renamed: Vec<(PathBuf, PathBuf)>,
+    ) {
+        for (old_path, new_path) in renamed {
+            let Some(handle) = buffer_manager.get_buffer(&new_path).await else {
+                continue;
+            };
+            let language = buffer_manager.language(&new_path).await;
+            let (text, version) = {
+                let buffer = handle.lock().await;
+                (buffer.get_text().await, buffer.version())
+            };
+            let manager = manager.clone();
+            let old_uri = path_to_uri(&old_path);
+            let new_uri = path_to_uri(&new_path);
+            executor.spawn(async move {
+                if let Err(e) = manager.notify_file_closed(&language, &old_uri).await {
+                    log::warn!("didClose failed: {}", e);
+                }
+                // 当前缓冲区的同步可能已先打开了新路径，这时只发送全文修改
+                if let Err(e) = manager
+                    .sync_document(&language, &new_uri, &text, version)
+                    .await
+                {
+                    log::warn!("didOpen failed: {}", e);
+                }
+            });
+        }
     }
 
-    /// 创建一个新的临时缓冲区
-    pub fn new_buffer(&mut self, cx: &mut Context<'_, Self>) {
+    /// 文件或目录移动后，把文件树、当前文件和各标签页的滚动位置换到新路径
+    fn remap_moved_paths(&mut self, from: &Path, to: &Path) {
+        let moved = |path: &Path| {
+            let rest = path.strip_prefix(from).ok()?;
+            Some(if rest.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(rest)
+            })
+        };
+        if let Some(tree) = self.file_tree.as_mut() {
+            tree.move_node(from, to);
+        }
+        if let Some(path) = self.current_file_path.as_deref().and_then(moved) {
+            self.current_file_path = Some(path);
+        }
+        self.tab_scroll_lines = std::mem::take(&mut self.tab_scroll_lines)
+            .into_iter()
+            .map(|(path, line)| (moved(&path).unwrap_or(path), line))
+            .collect();
+    }
+
+    /// 以只读方式打开远程 HTTP(S) 文件
+    pub fn open_remote_url(&mut self, url: String, cx: &mut Context<'_, Self>) {
         let buffer_manager = self.buffer_manager.clone();
-        let tab_size = self.config.editor.tab_size;
+        let fetcher = self.remote_fetcher.clone();
+        let executor = self.task_executor.clone();
+        self.set_status(format!("正在获取 {}…", url));
+        cx.notify();
 
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
-            async move {
-                let path = buffer_manager.create_new_buffer().await;
-                let (lines, selection, is_dirty, widths) =
-                    EditorView::snapshot_buffer(&buffer_manager, tab_size)
-                        .await
-                        .unwrap_or_default();
-                let _text = if let Some(handle) = buffer_manager.get_buffer(&path).await {
-                    let buffer = handle.lock().await;
-                    buffer.get_text().await
-                } else {
-                    String::new()
-                };
-
-                let open_files = buffer_manager.get_open_files().await;
 
-                let _ = this.update(&mut app, |view, cx| {
-                    view.current_file_path = Some(path.clone());
-                    view.open_files = open_files;
-                    view.lines = lines;
-                    view.line_prefix_widths = widths;
-                    view.selection = selection;
-                    view.is_dirty = is_dirty;
-                    view.status_message = "新建 untitled 缓冲区".to_string();
-                    cx.notify();
-                });
+            async move {
+                // reqwest 需要 tokio 运行时
+                let fetch_url = url.clone();
+                let result = executor
+                    .spawn(async move { fetcher.fetch(&fetch_url).await })
+                    .await;
+
+                match result {
+                    Ok(Ok(content)) => {
+                        let path = buffer_manager.open_remote(&url, &content).await;
+                        let _ = this.update(&mut app, |view, cx| {
+                            view.current_file_path = Some(path);
+                            view.set_status(format!("只读打开 {}", url));
+                            view.refresh_buffer_view(cx);
+                            cx.notify();
+                        });
+                    }
+                    Ok(Err(e)) => {
+                        let _ = this.update(&mut app, |view, cx| {
+                            view.set_status(format!("无法获取 {}: {}", url, e));
+                            cx.notify();
+                        });
+                    }
+                    Err(e) => log::error!("Remote fetch task failed: {}", e),
+                }
 
                 anyhow::Ok(())
             }
@@ -603,6 +6432,27 @@ impl EditorView {
             return;
         }
 
+        if is_remote_url(&path_text) {
+            self.quick_open_active = false;
+            self.quick_open_input.clear();
+            self.open_remote_url(path_text, cx);
+            return;
+        }
+
+        if !Self::resolve_input_path(&path_text).exists() {
+            self.quick_open_active = false;
+            self.quick_open_input.clear();
+            if let Some(path) = self.quick_open_matches(&path_text).into_iter().next() {
+                self.open_file(&path, cx);
+                return;
+            }
+            if self.open_file_reference(&path_text, cx) {
+                return;
+            }
+            self.quick_open_active = true;
+            self.quick_open_input = path_text.clone();
+        }
+
         let buffer_manager = self.buffer_manager.clone();
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
@@ -660,8 +6510,12 @@ impl EditorView {
             return;
         }
         let msg = self.ai_prompt_input.trim().to_string();
-        self.set_ai_context(cx);
-        self.send_ai_message(msg, cx);
+        if self.ai_tab(cx) == AIPanelTab::Composer {
+            self.send_composer_instruction(msg, cx);
+        } else {
+            self.set_ai_context(cx);
+            self.send_ai_message(msg, cx);
+        }
         self.ai_prompt_input.clear();
         cx.notify();
     }
@@ -674,25 +6528,32 @@ impl EditorView {
         extend: bool,
         cx: &mut Context<'_, Self>,
     ) {
-        let buffer_manager = self.buffer_manager.clone();
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
 
+        let moved = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let current = buffer.get_selections().first().cloned();
+                let anchor = current
+                    .as_ref()
+                    .map(|s| s.anchor)
+                    .unwrap_or(editor_core_text::Cursor::zero());
+                let new_cursor = editor_core_text::Cursor::new(line, column);
+                if extend {
+                    buffer
+                        .set_selection(editor_core_text::Selection::new(anchor, new_cursor))
+                        .await;
+                } else {
+                    buffer.set_cursor(new_cursor).await;
+                }
+            }
+            .boxed()
+        });
         cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
             let mut app = cx.clone();
             async move {
-                if let Some(handle) = buffer_manager.get_current_buffer().await {
-                    let mut buffer = handle.lock().await;
-                    let current = buffer.get_selections().first().cloned();
-                    let anchor = current
-                        .as_ref()
-                        .map(|s| s.anchor)
-                        .unwrap_or(editor_core_text::Cursor::zero());
-                    let new_cursor = editor_core_text::Cursor::new(line, column);
-                    if extend {
-                        buffer.set_selection(editor_core_text::Selection::new(anchor, new_cursor));
-                    } else {
-                        buffer.set_cursor(new_cursor);
-                    }
-                }
+                moved.await;
 
                 let _ = this.update(&mut app, |view, cx| {
                     view.set_status("移动光标");
@@ -706,79 +6567,31 @@ impl EditorView {
         .detach();
     }
 
-    /// 根据方向移动光标
+    /// 根据方向移动光标，多光标时每个光标各自移动
     fn move_cursor_by(
         &mut self,
         movement: CursorMovement,
         extend: bool,
         cx: &mut Context<'_, Self>,
     ) {
-        let buffer_manager = self.buffer_manager.clone();
-        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
-            let mut app = cx.clone();
-            async move {
-                if let Some(handle) = buffer_manager.get_current_buffer().await {
-                    let mut buffer = handle.lock().await;
-                    let current = buffer.get_selections().first().cloned().unwrap_or(
-                        editor_core_text::Selection::single(editor_core_text::Cursor::zero()),
-                    );
-                    let mut cursor = current.active;
-                    let line_count = buffer.line_count().await;
-
-                    match movement {
-                        CursorMovement::Left => {
-                            if cursor.column > 0 {
-                                cursor.column -= 1;
-                            } else if cursor.line > 0 {
-                                cursor.line -= 1;
-                                cursor.column =
-                                    buffer.get_line_length(cursor.line).await.unwrap_or(0);
-                            }
-                        }
-                        CursorMovement::Right => {
-                            let len = buffer.get_line_length(cursor.line).await.unwrap_or(0);
-                            if cursor.column < len {
-                                cursor.column += 1;
-                            } else if cursor.line + 1 < line_count {
-                                cursor.line += 1;
-                                cursor.column = 0;
-                            } else {
-                                cursor.column = len;
-                            }
-                        }
-                        CursorMovement::Up => {
-                            if cursor.line > 0 {
-                                cursor.line -= 1;
-                                let len = buffer.get_line_length(cursor.line).await.unwrap_or(0);
-                                cursor.column = cursor.column.min(len);
-                            }
-                        }
-                        CursorMovement::Down => {
-                            let next_line = cursor.line + 1;
-                            if next_line < line_count {
-                                cursor.line = next_line;
-                                let len = buffer.get_line_length(cursor.line).await.unwrap_or(0);
-                                cursor.column = cursor.column.min(len);
-                            }
-                        }
-                        CursorMovement::LineStart | CursorMovement::Home => {
-                            cursor.column = 0;
-                        }
-                        CursorMovement::LineEnd | CursorMovement::End => {
-                            cursor.column = buffer.get_line_length(cursor.line).await.unwrap_or(0);
-                        }
-                        _ => {}
-                    }
-
-                    if extend {
-                        buffer.set_selection(editor_core_text::Selection::new(
-                            current.anchor,
-                            cursor,
-                        ));
-                    } else {
-                        buffer.set_cursor(cursor);
-                    }
-                }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        // 折行时上下移动按可视行
+        let wrap = self.wrap_layout();
+        let page_rows = self.page_rows();
+        let moved = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                buffer
+                    .move_selections(movement, extend, wrap.as_ref(), page_rows)
+                    .await
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                moved.await;
 
                 let _ = this.update(&mut app, |view, cx| {
                     view.set_status("移动光标");
@@ -792,15 +6605,68 @@ impl EditorView {
         .detach();
     }
 
-    /// 将点击位置转换为列号，基于大致字符宽度
-    fn hit_test_column(&self, line_idx: usize, mouse_x: gpui::Pixels) -> usize {
+    /// 多光标命令：上下添加光标、选中下一个相同内容、按行拆分选区、收起多余光标
+    fn edit_cursors(&mut self, action: CursorAction, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                let found = match action {
+                    CursorAction::AddAbove => {
+                        buffer.add_cursors_vertically(LineDirection::Up).await;
+                        true
+                    }
+                    CursorAction::AddBelow => {
+                        buffer.add_cursors_vertically(LineDirection::Down).await;
+                        true
+                    }
+                    CursorAction::AddNextOccurrence => buffer.add_next_occurrence().await,
+                    CursorAction::SplitIntoLines => {
+                        buffer.split_selection_into_lines().await;
+                        true
+                    }
+                    CursorAction::Collapse => {
+                        if let Some(primary) = buffer.get_selections().first().copied() {
+                            buffer.set_selection(primary).await;
+                        }
+                        true
+                    }
+                };
+                (found, buffer.get_selections().len())
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let Some((found, count)) = applied.await else {
+                    return anyhow::Ok(());
+                };
+
+                this.update(&mut app, |view, cx| {
+                    if !found {
+                        view.set_status("没有更多匹配");
+                    } else if count > 1 {
+                        view.set_status(format!("{} 个光标", count));
+                    }
+                    view.refresh_buffer_view(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 将点击位置转换为列号，基于大致字符宽度；`start` 为折行后该可视行的起始列
+    fn hit_test_column(&self, line_idx: usize, start: usize, mouse_x: gpui::Pixels) -> usize {
         let char_w = self.char_width();
         let pos_x: f32 = mouse_x.into();
         let scroll_x: f32 = self.scroll_handle.offset().x.into();
         let gutter = self.gutter_width();
         let base_x = gutter + self.code_left_padding();
         if pos_x + scroll_x <= base_x {
-            return 0;
+            return start;
         }
 
         let Some(line) = self.lines.get(line_idx) else {
@@ -809,7 +6675,7 @@ impl EditorView {
 
         let target_units = (pos_x + scroll_x - base_x) / char_w;
         let mut acc = 0.0f32;
-        for (idx, ch) in line.chars().enumerate() {
+        for (idx, ch) in line.chars().enumerate().skip(start) {
             let w_units = if ch == '\t' {
                 self.config.editor.tab_size as f32
             } else {
@@ -841,11 +6707,19 @@ impl EditorView {
         }
     }
 
-    fn line_height(&self) -> f32 {
+    pub(crate) fn line_height(&self) -> f32 {
         (self.config.editor.font_size.max(12.0)) * 1.6
     }
 
-    fn char_width(&self) -> f32 {
+    /// 翻页移动的行数：编辑区能完整显示的行数减一，留一行上下文
+    fn page_rows(&self) -> usize {
+        let height = f32::from(self.scroll_handle.bounds().size.height);
+        ((height / self.line_height()).floor() as usize)
+            .saturating_sub(1)
+            .max(1)
+    }
+
+    pub(crate) fn char_width(&self) -> f32 {
         (self.config.editor.font_size.max(8.0)) * 0.6
     }
 
@@ -853,15 +6727,41 @@ impl EditorView {
         ((self.lines.len().max(1) as f32).log10().floor() as usize) + 1
     }
 
-    fn gutter_width(&self) -> f32 {
-        self.char_width() * self.line_number_digits() as f32 + 12.0
+    pub(crate) fn gutter_width(&self) -> f32 {
+        let mut columns = self.line_number_digits();
+        if self.show_blame_gutter && self.current_blame().is_some() {
+            columns += BLAME_GUTTER_COLUMNS;
+        }
+        self.char_width() * columns as f32 + 12.0
+    }
+
+    /// 开启折行时的可视行排布，折行宽度取配置列数与编辑区宽度中较小者
+    fn wrap_layout(&self) -> Option<WrapLayout> {
+        if !self.config.ui.soft_wrap {
+            return None;
+        }
+        let text_width = f32::from(self.scroll_handle.bounds().size.width)
+            - self.gutter_width()
+            - self.code_left_padding()
+            - self.code_area_padding() * 2.0;
+        let visible_columns = (text_width / self.char_width()).floor().max(0.0) as usize;
+        let mut columns = self.config.ui.wrap_column.max(1);
+        if visible_columns > 0 {
+            // 编辑区尚未布局时宽度为 0，此时只按配置列数折行
+            columns = columns.min(visible_columns.max(20));
+        }
+        Some(WrapLayout::new(
+            self.lines.iter().map(String::as_str),
+            columns,
+            self.config.editor.tab_size,
+        ))
     }
 
     fn code_left_padding(&self) -> f32 {
         14.0
     }
 
-    fn code_area_padding(&self) -> f32 {
+    pub(crate) fn code_area_padding(&self) -> f32 {
         12.0
     }
 
@@ -872,15 +6772,23 @@ impl EditorView {
             .unwrap_or_else(|| line.len())
     }
 
-    fn selection_range_for_line(&self, line_idx: usize, line_len: usize) -> Option<(usize, usize)> {
-        let selection = self.selection?;
-        if selection.is_collapsed() {
-            return None;
-        }
-
-        let start = selection.start();
-        let end = selection.end();
+    fn selection_ranges_for_line(&self, line_idx: usize, line_len: usize) -> Vec<(usize, usize)> {
+        self.selections
+            .iter()
+            .filter(|selection| !selection.is_collapsed())
+            .filter_map(|selection| {
+                Self::columns_on_line(selection.start(), selection.end(), line_idx, line_len)
+            })
+            .collect()
+    }
 
+    /// 从 `start` 到 `end` 的范围在第 `line_idx` 行上覆盖的列
+    fn columns_on_line(
+        start: editor_core_text::Cursor,
+        end: editor_core_text::Cursor,
+        line_idx: usize,
+        line_len: usize,
+    ) -> Option<(usize, usize)> {
         if start.line == end.line && start.line == line_idx {
             Some((start.column.min(line_len), end.column.min(line_len)))
         } else if line_idx == start.line {
@@ -894,19 +6802,646 @@ impl EditorView {
         }
     }
 
+    /// 查找范围在一行上覆盖的列
+    fn search_scope_for_line(&self, line_idx: usize, line_len: usize) -> Vec<(usize, usize)> {
+        self.search_scope
+            .iter()
+            .flat_map(|scope| &scope.ranges)
+            .filter_map(|range| Self::columns_on_line(range.start, range.end, line_idx, line_len))
+            .collect()
+    }
+
+    /// 查找结果在一行上覆盖的列
+    fn search_matches_for_line(&self, line_idx: usize, line_len: usize) -> Vec<(usize, usize)> {
+        // 匹配按位置排序，且互不重叠
+        let first = self
+            .search_matches
+            .partition_point(|found| found.end_position.line < line_idx);
+        self.search_matches[first..]
+            .iter()
+            .take_while(|found| found.start_position.line <= line_idx)
+            .filter_map(|found| {
+                Self::columns_on_line(found.start_position, found.end_position, line_idx, line_len)
+            })
+            .collect()
+    }
+
+    /// 诊断波浪线的颜色与行内信息的弱化颜色
+    pub(crate) fn diagnostic_colors(severity: DiagnosticSeverity) -> (u32, u32) {
+        match severity {
+            DiagnosticSeverity::Error => (0xf14c4c, 0x9c5656),
+            DiagnosticSeverity::Warning => (0xcca700, 0x8c7d3e),
+            DiagnosticSeverity::Information => (0x3794ff, 0x56779c),
+            DiagnosticSeverity::Hint => (0x8a8a8a, 0x5f5f5f),
+        }
+    }
+
+    /// 诊断在某行上覆盖的列；空范围至少标出一个字符
+    pub(crate) fn diagnostic_columns(
+        diagnostic: &Diagnostic,
+        line_idx: usize,
+        line_len: usize,
+    ) -> Option<(usize, usize)> {
+        let start = &diagnostic.range.start;
+        let end = &diagnostic.range.end;
+        if line_idx < start.line as usize || line_idx > end.line as usize {
+            return None;
+        }
+        // 跨行诊断结束在下一行行首时，不标记下一行
+        if line_idx == end.line as usize && line_idx != start.line as usize && end.character == 0 {
+            return None;
+        }
+        let start_col = if line_idx == start.line as usize {
+            (start.character as usize).min(line_len)
+        } else {
+            0
+        };
+        let end_col = if line_idx == end.line as usize {
+            (end.character as usize).min(line_len)
+        } else {
+            line_len
+        };
+        if end_col > start_col {
+            Some((start_col, end_col))
+        } else if line_len > 0 {
+            let col = start_col.min(line_len - 1);
+            Some((col, col + 1))
+        } else {
+            None
+        }
+    }
+
+    fn shows_inline_diagnostic(&self, severity: DiagnosticSeverity) -> bool {
+        let inline = &self.config.ui.inline_diagnostics;
+        inline.enabled
+            && match severity {
+                DiagnosticSeverity::Error => inline.errors,
+                DiagnosticSeverity::Warning => inline.warnings,
+                DiagnosticSeverity::Information => inline.information,
+                DiagnosticSeverity::Hint => inline.hints,
+            }
+    }
+
+    /// 行尾显示的诊断：从该行开始、且其严重级别已开启的第一条
+    fn inline_diagnostic(&self, line_idx: usize) -> Option<&Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.range.start.line as usize == line_idx)
+            .filter(|diagnostic| self.shows_inline_diagnostic(diagnostic.severity_or_error()))
+            .min_by_key(|diagnostic| diagnostic.range.start.character)
+    }
+
+    /// 添加高亮，覆盖已有高亮中与之重叠的部分，保证区间互不重叠
+    fn push_highlight(
+        highlights: &mut Vec<(std::ops::Range<usize>, HighlightStyle)>,
+        range: std::ops::Range<usize>,
+        style: HighlightStyle,
+    ) {
+        let mut kept = Vec::with_capacity(highlights.len() + 1);
+        for (existing, existing_style) in highlights.drain(..) {
+            if existing.end <= range.start || existing.start >= range.end {
+                kept.push((existing, existing_style));
+                continue;
+            }
+            if existing.start < range.start {
+                kept.push((existing.start..range.start, existing_style));
+            }
+            if existing.end > range.end {
+                kept.push((range.end..existing.end, existing_style));
+            }
+        }
+        kept.push((range, style));
+        *highlights = kept;
+    }
+
+    /// 同步缓冲区的全部选区，第一个作为主选区
+    pub(crate) fn set_selections(&mut self, selections: Vec<editor_core_text::Selection>) {
+        self.selection = selections.first().copied();
+        self.selections = selections;
+    }
+
     #[allow(dead_code)]
     fn current_cursor(&self) -> Option<editor_core_text::Cursor> {
         self.selection.map(|sel| sel.active)
     }
 
+    fn render_reference_candidates(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::PickReference {
+            return list;
+        }
+
+        let root = std::env::current_dir().unwrap_or_default();
+        for (idx, path) in self
+            .filtered_reference_candidates(self.quick_open_input.trim())
+            .into_iter()
+            .enumerate()
+        {
+            let label = path
+                .strip_prefix(&root)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            list = list.child(
+                div()
+                    .id(("reference-candidate", idx as u64))
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(label)
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.open_reference_candidate(path.clone(), cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    /// 渲染 AI 回答中的引用，点击后跳转到对应文件位置
+    fn render_citations(
+        &self,
+        ai_panel: &Entity<AIPanel>,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let citations = ai_panel.read(cx).citations().to_vec();
+        let references = ai_panel
+            .read(cx)
+            .messages()
+            .iter()
+            .rev()
+            .find(|message| message.role == AIRole::Assistant)
+            .map(|message| FileReference::find_all(&message.content))
+            .unwrap_or_default();
+        let mut chips = div().flex().flex_wrap().gap_1().px_3();
+
+        for (idx, citation) in citations.into_iter().enumerate() {
+            let label = citation.label();
+            chips = chips.child(
+                div()
+                    .id(("citation", idx as u64))
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .bg(rgb(0x132c4d))
+                    .text_xs()
+                    .text_color(rgb(0x8fd8ff))
+                    .cursor_pointer()
+                    .child(label)
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        // 引用的行号从 1 开始，两端都包含
+                        let start = citation.start_line.saturating_sub(1);
+                        view.open_file_range(
+                            &citation.path,
+                            start..citation.end_line.max(start + 1),
+                            cx,
+                        );
+                    })),
+            );
+        }
+
+        // 回答正文里出现的 path:line 引用
+        for (idx, reference) in references.into_iter().enumerate() {
+            let label = reference.label();
+            chips = chips.child(
+                div()
+                    .id(("ai-reference", idx as u64))
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .bg(rgb(0x1d2b3a))
+                    .text_xs()
+                    .text_color(rgb(0xc7d9ee))
+                    .cursor_pointer()
+                    .child(label.clone())
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.open_file_reference(&label, cx);
+                    })),
+            );
+        }
+
+        chips
+    }
+
+    /// Composer 的工作集：按文件列出提出的修改和状态，可逐处或全部接受、撤回
+    fn render_composer(
+        &self,
+        ai_panel: &Entity<AIPanel>,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let set = ai_panel.read(cx).working_set().clone();
+        let button = |id: (&'static str, u64), label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .rounded(px(4.0))
+                .bg(rgb(0x1a4d8f))
+                .cursor_pointer()
+                .text_xs()
+                .child(label)
+        };
+        let preview = |code: &str, sign: char| {
+            let mut lines: Vec<String> = code
+                .lines()
+                .take(4)
+                .map(|line| format!("{} {}", sign, line))
+                .collect();
+            if code.lines().count() > 4 {
+                lines.push(format!("{} …", sign));
+            }
+            lines.join("\n")
+        };
+
+        let mut list = div()
+            .id("composer-changes")
+            .flex()
+            .flex_col()
+            .gap_2()
+            .px_3()
+            .max_h(px(420.0))
+            .overflow_y_scroll();
+
+        for (file_idx, file) in set.files().into_iter().enumerate() {
+            let path = Self::resolve_patch_path(file);
+            list = list.child(
+                div()
+                    .id(("composer-file", file_idx as u64))
+                    .text_sm()
+                    .text_color(rgb(0x8fd8ff))
+                    .cursor_pointer()
+                    .child(file.to_string())
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        if path.exists() {
+                            view.open_file(&path, cx);
+                        }
+                    })),
+            );
+            for (idx, change) in set.changes_in(file) {
+                let (label, color) = Self::composer_status_label(&change.status);
+                let mut actions = div().flex().gap_1();
+                if change.status != ChangeStatus::Accepted {
+                    actions =
+                        actions.child(button(("composer-accept", idx as u64), "接受").on_click(
+                            cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.resolve_composer_changes(vec![idx], true, cx)
+                            }),
+                        ));
+                }
+                if change.status != ChangeStatus::Reverted {
+                    actions =
+                        actions.child(button(("composer-revert", idx as u64), "撤回").on_click(
+                            cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.resolve_composer_changes(vec![idx], false, cx)
+                            }),
+                        ));
+                }
+                let mut hunk = div()
+                    .flex()
+                    .flex_col()
+                    .gap_1()
+                    .p_2()
+                    .rounded(px(6.0))
+                    .bg(rgb(0x12223a))
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .justify_between()
+                            .child(div().text_xs().text_color(color).child(label))
+                            .child(actions),
+                    );
+                if !change.patch.old_code.is_empty() {
+                    hunk = hunk.child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0xff8a8a))
+                            .child(preview(&change.patch.old_code, '-')),
+                    );
+                }
+                list = list.child(
+                    hunk.child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0xb3f7a4))
+                            .child(preview(&change.patch.new_code, '+')),
+                    ),
+                );
+            }
+        }
+
+        if set.changes.is_empty() {
+            return list;
+        }
+        let all: Vec<usize> = (0..set.changes.len()).collect();
+        let revert_all = all.clone();
+        list.child(
+            div()
+                .flex()
+                .gap_2()
+                .child(
+                    button(("composer-accept-all", 0), "全部接受").on_click(cx.listener(
+                        move |view: &mut EditorView, _, _, cx| {
+                            view.resolve_composer_changes(all.clone(), true, cx)
+                        },
+                    )),
+                )
+                .child(
+                    button(("composer-revert-all", 0), "全部撤回").on_click(cx.listener(
+                        move |view: &mut EditorView, _, _, cx| {
+                            view.resolve_composer_changes(revert_all.clone(), false, cx)
+                        },
+                    )),
+                )
+                .child(button(("composer-review", 0), "审阅").on_click(
+                    cx.listener(|view: &mut EditorView, _, _, cx| view.open_composer_review(cx)),
+                ))
+                .child(button(("composer-clear", 0), "清空").on_click(cx.listener(
+                    |view: &mut EditorView, _, _, cx| {
+                        if let Some(ai_panel) = &view.ai_panel {
+                            ai_panel.update(cx, |panel, cx| {
+                                panel.working_set_mut().clear();
+                                cx.notify();
+                            });
+                        }
+                    },
+                ))),
+        )
+    }
+
+    /// 按住 Cmd/Ctrl 点击时，打开光标下的文件引用
+    fn open_reference_at_point(
+        &mut self,
+        position: Point<Pixels>,
+        cx: &mut Context<'_, Self>,
+    ) -> bool {
+        let Some((line_idx, column)) = self.position_from_point(position) else {
+            return false;
+        };
+        match FileReference::at(&self.lines[line_idx], column) {
+            Some(reference) => self.open_file_reference(&reference.label(), cx),
+            None => false,
+        }
+    }
+
+    /// 按住 Cmd/Ctrl 点击注释中的问题编号时，在浏览器中打开
+    fn open_issue_at_point(&mut self, position: Point<Pixels>, cx: &mut Context<'_, Self>) -> bool {
+        let Some((_, reference)) = self.issue_at_point(position) else {
+            return false;
+        };
+        cx.open_url(&reference.url);
+        self.set_status(format!("已打开 {}", reference.url));
+        true
+    }
+
+    /// 鼠标停在问题编号上时在行尾显示标题，托管平台上的标题第一次用到时读取
+    fn hover_issue_at_point(&mut self, position: Point<Pixels>, cx: &mut Context<'_, Self>) {
+        let hovered = self.issue_at_point(position);
+        if hovered == self.hovered_issue {
+            return;
+        }
+        let number = hovered.as_ref().and_then(|(_, reference)| reference.number);
+        self.hovered_issue = hovered;
+        cx.notify();
+
+        let Some(number) = number.filter(|number| !self.issue_titles.contains_key(number)) else {
+            return;
+        };
+        let Some(forge) = self
+            .issue_linker
+            .as_ref()
+            .and_then(IssueLinker::forge)
+            .cloned()
+        else {
+            return;
+        };
+        let client = match ForgeClient::new(forge) {
+            Ok(client) => client,
+            Err(e) => {
+                self.issue_titles.insert(number, e.to_string());
+                return;
+            }
+        };
+        // 读取期间不再重复请求
+        self.issue_titles.insert(number, "读取标题…".to_string());
+        let executor = self.task_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                // reqwest 需要 tokio 运行时
+                let title = executor
+                    .spawn(async move { client.issue_title(number).await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+                this.update(&mut app, |view, cx| {
+                    let title = match title {
+                        Ok(title) => title,
+                        Err(e) => format!("无法读取标题: {}", e),
+                    };
+                    view.issue_titles.insert(number, title);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 鼠标下注释中的问题编号
+    fn issue_at_point(&self, position: Point<Pixels>) -> Option<(usize, IssueReference)> {
+        let linker = self.issue_linker.as_ref()?;
+        let (line, column) = self.position_from_point(position)?;
+        let comment_start = self.comment_starts(&self.lines[..=line])[line]?;
+        linker
+            .at(&self.lines[line], column)
+            .filter(|reference| reference.columns.start >= comment_start)
+            .map(|reference| (line, reference))
+    }
+
+    /// 每行注释开始的列，不在注释中的行为 None。提交信息中除了 `#` 开头的行都算
+    fn comment_starts(&self, lines: &[String]) -> Vec<Option<usize>> {
+        let commit_message = self
+            .current_file_path
+            .as_deref()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                matches!(
+                    name,
+                    "COMMIT_EDITMSG" | "MERGE_MSG" | "TAG_EDITMSG" | "SQUASH_MSG"
+                )
+            });
+        if commit_message {
+            return lines
+                .iter()
+                .map(|line| (!line.starts_with('#')).then_some(0))
+                .collect();
+        }
+
+        let pack = LanguagePack::for_id(&self.current_file_language());
+        let line_comment = pack.and_then(|pack| pack.line_comment);
+        let block_comment = pack.and_then(|pack| pack.block_comment);
+        let mut in_block = false;
+        lines
+            .iter()
+            .map(|line| {
+                let mut start = in_block.then_some(0);
+                let mut pos = 0;
+                loop {
+                    if in_block {
+                        let close = block_comment.and_then(|(_, close)| {
+                            line[pos..].find(close).map(|at| pos + at + close.len())
+                        });
+                        match close {
+                            Some(end) => {
+                                in_block = false;
+                                pos = end;
+                            }
+                            None => break,
+                        }
+                        continue;
+                    }
+                    let line_at = line_comment
+                        .and_then(|token| line[pos..].find(token))
+                        .map(|at| pos + at);
+                    let block_at = block_comment.and_then(|(open, _)| {
+                        line[pos..]
+                            .find(open)
+                            .map(|at| (pos + at, pos + at + open.len()))
+                    });
+                    match (line_at, block_at) {
+                        (Some(at), block) if block.is_none_or(|(open, _)| at <= open) => {
+                            start.get_or_insert(at);
+                            break;
+                        }
+                        (_, Some((open, end))) => {
+                            start.get_or_insert(open);
+                            in_block = true;
+                            pos = end;
+                        }
+                        _ => break,
+                    }
+                }
+                start.map(|byte| line[..byte].chars().count())
+            })
+            .collect()
+    }
+
+    /// 按配置和 origin 的地址生成注释中问题编号的链接
+    pub(crate) fn load_issue_linker(&mut self, cx: &mut Context<'_, Self>) {
+        let Ok(root) = std::env::current_dir() else {
+            return;
+        };
+        let trackers = self.config.editor.issue_trackers.clone();
+        let forges = self.config.editor.forges.clone();
+        let repository = GitRepository::discover(&root);
+        let executor = self.task_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let url = match repository {
+                    // tokio::process 需要 tokio 运行时
+                    Some(repository) => executor
+                        .spawn(async move { repository.remote_url("origin").await })
+                        .await
+                        .ok()
+                        .and_then(|result| result.ok()),
+                    None => None,
+                };
+                let forge = url.and_then(|url| ForgeRepository::from_remote(&url, &forges));
+                let linker = IssueLinker::new(&trackers, &root, forge);
+
+                this.update(&mut app, |view, cx| {
+                    view.issue_linker = match linker {
+                        Ok(linker) => (!linker.is_empty()).then_some(linker),
+                        Err(e) => {
+                            view.set_status(format!("问题跟踪的模式无效: {}", e));
+                            None
+                        }
+                    };
+                    view.hovered_issue = None;
+                    view.issue_titles.clear();
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
     fn update_cursor_from_point(
         &mut self,
         position: Point<Pixels>,
         extend: bool,
         cx: &mut Context<'_, Self>,
     ) {
-        if self.lines.is_empty() || self.quick_open_active {
+        let Some((line_idx, column)) = self.position_from_point(position) else {
+            return;
+        };
+        self.set_status("移动光标");
+        self.set_cursor_position(line_idx, column, extend, cx);
+    }
+
+    /// 双击选中单词，三击选中整行，四击选中段落
+    fn select_text_object_at_point(
+        &mut self,
+        position: Point<Pixels>,
+        click_count: usize,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some((line, column)) = self.position_from_point(position) else {
+            return;
+        };
+        let Some(path) = self.current_file_path.clone() else {
             return;
+        };
+
+        let selected = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move {
+                // 点击位置来自上一次渲染，文本可能已经变短
+                let cursor = editor_core_text::Cursor::new(line, column)
+                    .clamp_to(buffer)
+                    .await;
+                let range = match click_count {
+                    2 => buffer.word_at(cursor).await,
+                    3 => Some(buffer.line_range_at(cursor).await),
+                    _ => buffer.paragraph_at(cursor).await,
+                };
+                match range {
+                    Some(range) => {
+                        buffer
+                            .set_selection(editor_core_text::Selection::range(
+                                range.start,
+                                range.end,
+                            ))
+                            .await
+                    }
+                    None => buffer.set_cursor(cursor).await,
+                }
+            }
+            .boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                selected.await;
+                this.update(&mut app, |view, cx| view.refresh_buffer_view(cx))?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn position_from_point(&self, position: Point<Pixels>) -> Option<(usize, usize)> {
+        if self.lines.is_empty() || self.quick_open_active {
+            return None;
         }
 
         let bounds = self.scroll_handle.bounds();
@@ -925,24 +7460,77 @@ impl EditorView {
         if local_y < 0.0 {
             local_y = 0.0;
         }
+        let wrap = self.wrap_layout();
+        if let Some(peek) = &self.peek {
+            let anchor_rows = match &wrap {
+                Some(wrap) => {
+                    wrap.row_of(editor_core_text::Cursor::new(peek.anchor_line, usize::MAX)) + 1
+                }
+                None => peek.anchor_line + 1,
+            };
+            let peek_top = anchor_rows as f32 * self.line_height();
+            if local_y >= peek_top + PEEK_HEIGHT {
+                local_y -= PEEK_HEIGHT;
+            } else if local_y >= peek_top {
+                return None;
+            }
+        }
 
-        let mut line_idx = (local_y / self.line_height()).floor() as usize;
-        line_idx = line_idx.min(self.lines.len().saturating_sub(1));
+        let row_idx = (local_y / self.line_height()).floor() as usize;
+        if let Some(wrap) = &wrap {
+            let row_idx = row_idx.min(wrap.row_count().saturating_sub(1));
+            let row = *wrap.rows().get(row_idx)?;
+            let column = self.hit_test_column(row.line, row.start, Pixels::from(local_x));
+            // 点在折行处右侧时停在本行末字符前，否则光标会显示到下一可视行
+            let wraps = wrap
+                .rows()
+                .get(row_idx + 1)
+                .is_some_and(|next| next.line == row.line);
+            let last = if wraps && !row.is_empty() {
+                row.end - 1
+            } else {
+                row.end
+            };
+            return Some((row.line, column.min(last.max(row.start))));
+        }
 
-        let column = self.hit_test_column(line_idx, Pixels::from(local_x));
-        self.set_status("移动光标");
-        self.set_cursor_position(line_idx, column, extend, cx);
+        let line_idx = row_idx.min(self.lines.len().saturating_sub(1));
+        let column = self.hit_test_column(line_idx, 0, Pixels::from(local_x));
+        Some((line_idx, column))
     }
 }
 
 impl Render for EditorView {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<'_, Self>) -> impl IntoElement {
-        let file_name = self
+        // 没有引起重绘的按键会等到下一次无关的重绘，太久的不计入
+        if let Some(elapsed) = self
+            .keystroke_started
+            .take()
+            .map(|started| started.elapsed())
+            .filter(|elapsed| *elapsed < Duration::from_secs(1))
+        {
+            self.metrics.record(Metric::KeystrokeToRender, elapsed);
+        }
+        if !self.first_frame_drawn {
+            self.first_frame_drawn = true;
+            editor_infra::startup::profile().first_frame();
+            cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+                async move {
+                    this.update(&mut app, |view, cx| view.start_background_services(cx))?;
+                    anyhow::Ok(())
+                }
+            })
+            .detach();
+        }
+        let mut file_name = self
             .current_file_name()
             .unwrap_or_else(|| "Untitled".to_string());
+        if self.current_read_only {
+            file_name.push_str(" [只读]");
+        }
         let language = self.current_file_language();
         let ai_panel_open = self.show_ai_panel;
-        let cursor = self.selection.map(|sel| sel.active);
         let gutter_width = self.gutter_width();
         let line_digits = self.line_number_digits();
 
@@ -952,11 +7540,90 @@ impl Render for EditorView {
             cx.listener(|view: &mut EditorView, _, _, cx| view.toggle_ai_panel(cx));
         let new_file_listener = cx.listener(|view: &mut EditorView, _, _, cx| view.new_buffer(cx));
         let quick_open_listener = cx.listener(|view: &mut EditorView, _, _, cx| {
-            view.quick_open_active = true;
-            view.quick_open_input.clear();
-            view.status_message = "输入路径后回车打开，Esc 取消".to_string();
-            cx.notify();
+            view.begin_quick_input(QuickInputMode::OpenPath, cx);
         });
+        let (quick_input_title, quick_input_hint) = match self.quick_input_mode {
+            QuickInputMode::OpenPath => (
+                "Quick Open",
+                "输入相对路径或 https:// 链接，Enter 打开，Esc 取消；最近打开的文件排在前面",
+            ),
+            QuickInputMode::RenameBuffer => ("Rename Buffer", "输入新名称，Enter 确认，Esc 取消"),
+            QuickInputMode::SetLanguage => ("Set Language", "输入语言标识，Enter 确认，Esc 取消"),
+            QuickInputMode::ExportSettings => (
+                "Export Settings",
+                "输入导出路径，留空导出到配置目录，Enter 确认",
+            ),
+            QuickInputMode::ImportSettings => ("Import Settings", "输入配置归档路径，Enter 导入"),
+            QuickInputMode::PickReference => (
+                "Pick File",
+                "多个文件匹配该引用，输入筛选或点击选择，Enter 打开第一个",
+            ),
+            QuickInputMode::PickCodeAction => (
+                "Code Actions",
+                "输入筛选或点击选择，Enter 执行第一个，修改会先预览",
+            ),
+            QuickInputMode::NewFile => ("New File", "输入文件路径，Enter 创建，Esc 取消"),
+            QuickInputMode::NewFolder => ("New Folder", "输入文件夹路径，Enter 创建，Esc 取消"),
+            QuickInputMode::MoveFile => ("Move File", "输入目标路径，Enter 移动，Esc 取消"),
+            QuickInputMode::Find => (
+                "Find",
+                "Alt+C 区分大小写 · Alt+W 全词 · Alt+R 正则 · Alt+P 替换保留大小写 · Alt+S 仅选区",
+            ),
+            QuickInputMode::Replace => (
+                "Replace",
+                "替换最近一次查找的全部匹配，有查找范围时只替换范围内的，正则可用 $1 引用分组",
+            ),
+            QuickInputMode::SetMark => ("Set Bookmark", "输入名称或 0-9，Enter 设置，Esc 取消"),
+            QuickInputMode::GotoMark => ("Go to Bookmark", "输入名称或编号，Enter 跳转，Esc 取消"),
+            QuickInputMode::PickCompletion => (
+                "Completions",
+                "输入筛选或点击选择，Enter 插入第一个，需要时一并添加导入",
+            ),
+            QuickInputMode::PickClipboard => (
+                "Clipboard History",
+                "输入筛选或点击选择，Enter 粘贴第一个，多选区内容按选区粘贴",
+            ),
+            QuickInputMode::ReplayEditLog => (
+                "Replay Edit Log",
+                "输入导出的编辑日志路径，留空为当前文件本次会话的编辑，Enter 在只读缓冲区中逐步回放",
+            ),
+            QuickInputMode::TransformCase => (
+                "Transform Case",
+                "输入筛选或点击选择，没有选中文本时转换光标处的词，可一次撤销",
+            ),
+            QuickInputMode::InlineThread => (
+                "Ask About Code",
+                "在光标所在的对话中追问，有选区时就选中的行开始新对话，Enter 发送",
+            ),
+            QuickInputMode::RunTool => (
+                "External Tools",
+                "输入筛选或点击选择，Enter 运行第一个，选中的文本同时写入工具的 stdin",
+            ),
+            QuickInputMode::GitCommand => (
+                "Git",
+                "输入筛选或点击选择，Enter 执行第一个；丢弃 stash 或工作区有冲突时需要再执行一次确认",
+            ),
+            QuickInputMode::AddWorktree => (
+                "Add Worktree",
+                "输入分支名，不存在时从当前提交新建；默认放在仓库旁的「仓库名-分支名」目录",
+            ),
+            QuickInputMode::RecentWorkspace => (
+                "Recent Workspaces",
+                "输入筛选或点击选择，Enter 切换到第一个；当前工作区不列出",
+            ),
+            QuickInputMode::SearchWorkspace => (
+                "Search Workspace",
+                "在工作区的全部文本文件中查找，跳过隐藏文件与 .gitignore 忽略的文件；结果显示在右侧面板，点击跳转",
+            ),
+            QuickInputMode::PickPullRequest => (
+                "Pull Requests",
+                "origin 上打开的 PR/MR，输入筛选或点击选择，Enter 查看第一个的改动；令牌来自配置的 editor.forges 或 GITHUB_TOKEN/GITLAB_TOKEN",
+            ),
+            QuickInputMode::CommentPullRequest => (
+                "Comment",
+                "评论发表在 PR/MR 的对话中，Enter 发表",
+            ),
+        };
 
         let mut sidebar = div()
             .w(px(200.0))
@@ -977,6 +7644,15 @@ impl Render for EditorView {
                 .child("Workspace"),
         );
 
+        let mut pinned_row = div()
+            .flex()
+            .flex_wrap()
+            .gap_1()
+            .px_2()
+            .py_1()
+            .border_b_1()
+            .border_color(rgb(0x2a2a2a));
+        let mut unpinned_tabs = Vec::new();
         for (idx, path) in self.open_files.iter().enumerate() {
             let is_active = self
                 .current_file_path
@@ -984,15 +7660,26 @@ impl Render for EditorView {
                 .map(|p| p == path)
                 .unwrap_or(false);
 
-            let display = path
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| path.display().to_string());
+            let mut display = self.display_names.get(path).cloned().unwrap_or_else(|| {
+                path.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.display().to_string())
+            });
+            if self.read_only_files.contains(path) {
+                display.push_str(" 🔒");
+            }
+            if self.conflicted_files.contains(path) {
+                display.push_str(" ⚠");
+            }
 
             let path_clone = path.clone();
             let click_handler = cx.listener(move |view: &mut EditorView, _, _, cx| {
                 let buffer_manager = view.buffer_manager.clone();
                 let path = path_clone.clone();
+                if let Some(current) = view.current_file_path.clone() {
+                    let line = view.top_visible_line();
+                    view.tab_scroll_lines.insert(current, line);
+                }
                 cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
                     let mut app = cx.clone();
                     async move {
@@ -1004,6 +7691,8 @@ impl Render for EditorView {
 
                         let _ = this.update(&mut app, |view, cx| {
                             view.current_file_path = Some(path.clone());
+                            let line = view.tab_scroll_lines.get(&path).copied();
+                            view.scroll_to_top_line(line.unwrap_or_default());
                             view.set_status("切换文件");
                             view.refresh_buffer_view(cx);
                             cx.notify();
@@ -1015,7 +7704,32 @@ impl Render for EditorView {
                 .detach();
             });
 
-            sidebar = sidebar.child(
+            if self.pinned_files.contains(path) {
+                pinned_row = pinned_row.child(
+                    div()
+                        .id(("pinned-tab", idx as u64))
+                        .px_2()
+                        .py_1()
+                        .text_xs()
+                        .rounded(px(4.0))
+                        .bg(if is_active {
+                            rgb(0x2a3a52)
+                        } else {
+                            rgb(0x1f1f1f)
+                        })
+                        .text_color(if is_active {
+                            rgb(0xffffff)
+                        } else {
+                            rgb(0xbbbbbb)
+                        })
+                        .cursor_pointer()
+                        .child(Self::pinned_tab_label(&display))
+                        .on_click(click_handler),
+                );
+                continue;
+            }
+
+            unpinned_tabs.push(
                 div()
                     .id(("sidebar", idx as u64))
                     .px_3()
@@ -1037,6 +7751,56 @@ impl Render for EditorView {
                     .on_click(click_handler),
             );
         }
+        if !self.pinned_files.is_empty() {
+            sidebar = sidebar.child(pinned_row);
+        }
+        sidebar = sidebar.children(unpinned_tabs);
+
+        if !self.agent_touched_files.is_empty() {
+            sidebar = sidebar.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .mt_2()
+                    .border_t_1()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_color(rgb(0x9ad1ff))
+                    .text_sm()
+                    .child(if self.agent_run_finished {
+                        format!("AI 修改的文件 ({})", self.agent_touched_files.len())
+                    } else {
+                        "AI 正在修改…".to_string()
+                    }),
+            );
+
+            for (idx, (path, line)) in self.agent_touched_files.iter().enumerate() {
+                let display = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.display().to_string());
+                let path = path.clone();
+                let line = *line;
+
+                sidebar = sidebar.child(
+                    div()
+                        .id(("agent-edit", idx as u64))
+                        .px_3()
+                        .py_1()
+                        .text_sm()
+                        .text_color(rgb(0xbbbbbb))
+                        .cursor_pointer()
+                        .child(format!("{}:{}", display, line + 1))
+                        .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                            view.open_file_at(&path, line, 0, cx);
+                        })),
+                );
+            }
+        }
+
+        let follow_listener =
+            cx.listener(|view: &mut EditorView, _, _, cx| view.toggle_follow_agent_edits(cx));
+        let follow_agent_edits = self.follow_agent_edits;
 
         let mut layout = div()
             .flex()
@@ -1107,6 +7871,21 @@ impl Render for EditorView {
                                 .child("Save")
                                 .on_click(save_listener),
                         )
+                        .child(
+                            div()
+                                .id("follow-toggle")
+                                .px_3()
+                                .py_1()
+                                .rounded(px(6.0))
+                                .bg(if follow_agent_edits {
+                                    rgb(0x1a4d8f)
+                                } else {
+                                    rgb(0x3a3a3a)
+                                })
+                                .cursor_pointer()
+                                .child("Follow AI")
+                                .on_click(follow_listener),
+                        )
                         .child(
                             div()
                                 .id("ai-toggle")
@@ -1132,6 +7911,20 @@ impl Render for EditorView {
 
         let mut content_area = div().flex().flex_1();
 
+        sidebar = sidebar
+            .child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .mt_2()
+                    .border_t_1()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_color(rgb(0x9ad1ff))
+                    .text_sm()
+                    .child("Explorer"),
+            )
+            .child(self.render_file_tree(cx));
         content_area = content_area.child(sidebar);
 
         let editor_area = div()
@@ -1155,6 +7948,7 @@ impl Render for EditorView {
                             .gap_2()
                             .child("Cmd+S 保存")
                             .child("Cmd+Z/Y 撤销/重做")
+                            .child("Cmd+Shift+H 本地历史")
                             .child("Ctrl+Space 切换 AI"),
                     ),
             )
@@ -1174,6 +7968,24 @@ impl Render for EditorView {
                         MouseButton::Left,
                         cx.listener(
                             |view: &mut EditorView, event: &MouseDownEvent, window, cx| {
+                                view.last_input = Instant::now();
+                                if event.modifiers.secondary()
+                                    && (view.open_issue_at_point(event.position, cx)
+                                        || view.open_reference_at_point(event.position, cx))
+                                {
+                                    return;
+                                }
+                                if view.toggle_inline_thread_at_point(event.position, cx) {
+                                    return;
+                                }
+                                if event.click_count > 1 {
+                                    view.select_text_object_at_point(
+                                        event.position,
+                                        event.click_count,
+                                        cx,
+                                    );
+                                    return;
+                                }
                                 view.dragging_selection = true;
                                 view.update_cursor_from_point(
                                     event.position,
@@ -1189,6 +8001,8 @@ impl Render for EditorView {
                             if view.dragging_selection && event.dragging() {
                                 view.update_cursor_from_point(event.position, true, cx);
                                 view.autoscroll_on_drag(event.position.y);
+                            } else if !event.dragging() {
+                                view.hover_issue_at_point(event.position, cx);
                             }
                         },
                     ))
@@ -1208,96 +8022,412 @@ impl Render for EditorView {
                                 .child("空缓冲区，开始输入试试…")
                         } else {
                             let mut code_lines = div().flex().flex_col().gap_0();
+                            let wrap = self.wrap_layout();
+                            let comment_starts = self
+                                .issue_linker
+                                .as_ref()
+                                .map(|_| self.comment_starts(&self.lines));
+                            let blame = self.current_blame();
+                            let blame_gutter = blame.filter(|_| self.show_blame_gutter);
 
                             for (idx, line) in self.lines.iter().enumerate() {
-                                let is_active_line = cursor.map(|c| c.line == idx).unwrap_or(false);
                                 let line_len = line.chars().count();
-                                let selection_range = self.selection_range_for_line(idx, line_len);
-                                let caret_col = cursor.filter(|c| c.line == idx).map(|c| c.column);
+                                let caret_cols: Vec<usize> = self
+                                    .selections
+                                    .iter()
+                                    .filter(|sel| sel.active.line == idx)
+                                    .map(|sel| sel.active.column)
+                                    .collect();
+                                let is_active_line = !caret_cols.is_empty();
+                                let is_flashing = self.line_flash.as_ref().is_some_and(|flash| {
+                                    flash.lines.contains(&idx)
+                                        && self.current_file_path.as_ref() == Some(&flash.path)
+                                });
+                                let has_bookmark =
+                                    self.bookmarks.iter().any(|(_, cursor)| cursor.line == idx);
+                                let has_thread = self.inline_threads.iter().any(|thread| {
+                                    thread.lines.start == idx
+                                        && self.current_file_path.as_ref() == Some(&thread.path)
+                                });
+                                let line_change = self.line_changes.kind_at(idx);
+                                let git_change = self.git_line_changes.kind_at(idx);
+
+                                let mut highlights = self
+                                    .syntax_spans
+                                    .get(idx)
+                                    .map(|spans| self.syntax_theme.line_highlights(spans))
+                                    .unwrap_or_default();
+
+                                let issues = self
+                                    .issue_linker
+                                    .as_ref()
+                                    .zip(comment_starts.as_ref().and_then(|starts| starts[idx]));
+                                if let Some((linker, comment_start)) = issues {
+                                    for reference in linker.find_all(line) {
+                                        if reference.columns.start < comment_start {
+                                            continue;
+                                        }
+                                        let start = Self::byte_index_for_column(
+                                            line,
+                                            reference.columns.start,
+                                        );
+                                        let end = Self::byte_index_for_column(
+                                            line,
+                                            reference.columns.end,
+                                        );
+                                        let style = HighlightStyle {
+                                            color: Some(rgb(0x7fb8ff).into()),
+                                            underline: Some(UnderlineStyle {
+                                                thickness: px(1.0),
+                                                color: Some(rgb(0x7fb8ff).into()),
+                                                wavy: false,
+                                            }),
+                                            ..Default::default()
+                                        };
+                                        Self::push_highlight(&mut highlights, start..end, style);
+                                    }
+                                }
 
-                                let mut highlights = Vec::new();
+                                // 先画轻的，重叠时严重的覆盖在上面
+                                let visible_len =
+                                    line.trim_end_matches(['\n', '\r']).chars().count();
+                                let mut line_diagnostics: Vec<(&Diagnostic, (usize, usize))> = self
+                                    .diagnostics
+                                    .iter()
+                                    .filter_map(|diagnostic| {
+                                        Self::diagnostic_columns(diagnostic, idx, visible_len)
+                                            .map(|columns| (diagnostic, columns))
+                                    })
+                                    .collect();
+                                line_diagnostics.sort_by_key(|(diagnostic, _)| {
+                                    std::cmp::Reverse(diagnostic.severity_or_error() as u8)
+                                });
+                                for (diagnostic, (start_col, end_col)) in line_diagnostics {
+                                    let (color, _) =
+                                        Self::diagnostic_colors(diagnostic.severity_or_error());
+                                    let start = Self::byte_index_for_column(line, start_col);
+                                    let end = Self::byte_index_for_column(line, end_col);
+                                    let style = HighlightStyle {
+                                        underline: Some(UnderlineStyle {
+                                            thickness: px(1.0),
+                                            color: Some(rgb(color).into()),
+                                            wavy: true,
+                                        }),
+                                        ..Default::default()
+                                    };
+                                    Self::push_highlight(&mut highlights, start..end, style);
+                                }
 
-                                if let Some((start_col, end_col)) = selection_range {
+                                let search_colors = self
+                                    .search_scope_for_line(idx, line_len)
+                                    .into_iter()
+                                    .map(|cols| (cols, 0x1d2b22))
+                                    .chain(
+                                        self.search_matches_for_line(idx, line_len)
+                                            .into_iter()
+                                            .map(|cols| (cols, 0x5c4a1a)),
+                                    );
+                                for ((start_col, end_col), color) in search_colors {
                                     let start = Self::byte_index_for_column(line, start_col);
                                     let end = Self::byte_index_for_column(line, end_col);
                                     if end > start {
-                                        let mut style = HighlightStyle::default();
-                                        style.background_color = Some(rgb(0x24334e).into());
-                                        highlights.push((start..end, style));
+                                        let style = HighlightStyle {
+                                            background_color: Some(rgb(color).into()),
+                                            ..Default::default()
+                                        };
+                                        Self::push_highlight(&mut highlights, start..end, style);
                                     }
                                 }
 
-                                let caret_at_eol = caret_col.map_or(false, |col| col >= line_len);
-                                if let Some(col) = caret_col {
-                                    if col < line_len {
-                                        let start = Self::byte_index_for_column(line, col);
-                                        let end = Self::byte_index_for_column(
-                                            line,
-                                            (col + 1).min(line_len),
-                                        );
-                                        if end >= start {
-                                            let mut style = HighlightStyle::default();
-                                            style.background_color = Some(rgb(0x4c8dff).into());
-                                            highlights.push((start..end, style));
-                                        }
+                                for (start_col, end_col) in
+                                    self.selection_ranges_for_line(idx, line_len)
+                                {
+                                    let start = Self::byte_index_for_column(line, start_col);
+                                    let end = Self::byte_index_for_column(line, end_col);
+                                    if end > start {
+                                        let style = HighlightStyle {
+                                            background_color: Some(rgb(0x24334e).into()),
+                                            ..Default::default()
+                                        };
+                                        Self::push_highlight(&mut highlights, start..end, style);
                                     }
                                 }
 
-                                let mut text = StyledText::new(line.clone());
-                                if !highlights.is_empty() {
-                                    text = text.with_highlights(highlights);
+                                let caret_at_eol = caret_cols.iter().any(|col| *col >= line_len);
+                                for col in caret_cols.iter().copied().filter(|col| *col < line_len)
+                                {
+                                    let start = Self::byte_index_for_column(line, col);
+                                    let end = Self::byte_index_for_column(line, col + 1);
+                                    let style = HighlightStyle {
+                                        background_color: Some(rgb(0x4c8dff).into()),
+                                        ..Default::default()
+                                    };
+                                    Self::push_highlight(&mut highlights, start..end, style);
+                                }
+
+                                let brackets =
+                                    self.bracket_match.iter().flat_map(|(a, b)| [*a, *b]);
+                                for bracket in
+                                    brackets.filter(|c| c.line == idx && c.column < line_len)
+                                {
+                                    let start = Self::byte_index_for_column(line, bracket.column);
+                                    let end = Self::byte_index_for_column(line, bracket.column + 1);
+                                    // 与选区或光标重叠时保持原有高亮
+                                    if highlights
+                                        .iter()
+                                        .all(|(range, _)| range.end <= start || range.start >= end)
+                                    {
+                                        let style = HighlightStyle {
+                                            background_color: Some(rgb(0x3b4f6b).into()),
+                                            underline: Some(UnderlineStyle {
+                                                thickness: px(1.0),
+                                                color: Some(rgb(0x8fb4ff).into()),
+                                                wavy: false,
+                                            }),
+                                            ..Default::default()
+                                        };
+                                        highlights.push((start..end, style));
+                                    }
                                 }
+                                highlights.sort_by_key(|(range, _)| range.start);
+
+                                // 每个可视行一段字节区间，最后一段带上换行符
+                                let segments: Vec<std::ops::Range<usize>> = match &wrap {
+                                    Some(wrap) => {
+                                        let rows = wrap.rows_of_line(idx);
+                                        rows.iter()
+                                            .enumerate()
+                                            .map(|(row_idx, row)| {
+                                                let start =
+                                                    Self::byte_index_for_column(line, row.start);
+                                                let end = if row_idx + 1 == rows.len() {
+                                                    line.len()
+                                                } else {
+                                                    Self::byte_index_for_column(line, row.end)
+                                                };
+                                                start..end
+                                            })
+                                            .collect()
+                                    }
+                                    None => std::iter::once(0..line.len()).collect(),
+                                };
+                                let segment_count = segments.len();
+
+                                for (segment_idx, segment) in segments.into_iter().enumerate() {
+                                    let first_segment = segment_idx == 0;
+                                    let last_segment = segment_idx + 1 == segment_count;
+                                    let segment_highlights: Vec<_> = highlights
+                                        .iter()
+                                        .filter(|(range, _)| {
+                                            range.start < segment.end && range.end > segment.start
+                                        })
+                                        .map(|(range, style)| {
+                                            let start =
+                                                range.start.max(segment.start) - segment.start;
+                                            let end = range.end.min(segment.end) - segment.start;
+                                            (start..end, *style)
+                                        })
+                                        .collect();
+                                    let mut text =
+                                        StyledText::new(line[segment.clone()].to_string());
+                                    if !segment_highlights.is_empty() {
+                                        text = text.with_highlights(segment_highlights);
+                                    }
 
-                                let mut line_row = div()
-                                    .id(("line", idx as u64))
-                                    .flex()
-                                    .items_start()
-                                    .gap_3()
-                                    .px_2()
-                                    .py_1()
-                                    .bg(if is_active_line {
-                                        rgb(0x121820)
+                                    let row_id = if first_segment {
+                                        ("line", idx as u64)
                                     } else {
-                                        rgb(0x111111)
-                                    });
+                                        ("wrapped-line", ((idx as u64) << 16) | segment_idx as u64)
+                                    };
+                                    let mut line_row = div()
+                                        .id(row_id)
+                                        .flex()
+                                        .items_start()
+                                        .gap_3()
+                                        .px_2()
+                                        .py_1()
+                                        .bg(if is_flashing {
+                                            rgb(0x3a3520)
+                                        } else if is_active_line {
+                                            rgb(0x121820)
+                                        } else {
+                                            rgb(0x111111)
+                                        });
 
-                                line_row = line_row.child(
-                                    div()
-                                        .w(px(gutter_width))
-                                        .text_right()
-                                        .text_color(if is_active_line {
-                                            rgb(0x8ecbff)
+                                    line_row = line_row.child(
+                                        div()
+                                            .w(px(gutter_width))
+                                            .text_right()
+                                            .rounded(px(3.0))
+                                            .when(has_thread && first_segment, |gutter| {
+                                                gutter.bg(rgb(0x16283f))
+                                            })
+                                            .when(has_bookmark && first_segment, |gutter| {
+                                                gutter.bg(rgb(0x3a2f12))
+                                            })
+                                            .when_some(line_change, |gutter, kind| {
+                                                gutter.border_l_2().border_color(rgb(
+                                                    Self::line_change_color(kind),
+                                                ))
+                                            })
+                                            // 左侧是未保存的改动，右侧是相对 HEAD 的改动
+                                            .when_some(git_change, |gutter, kind| {
+                                                gutter.relative().child(
+                                                    div()
+                                                        .absolute()
+                                                        .top_0()
+                                                        .bottom_0()
+                                                        .right(px(-7.0))
+                                                        .w(px(2.0))
+                                                        .bg(rgb(Self::line_change_color(kind))),
+                                                )
+                                            })
+                                            .text_color(if has_bookmark {
+                                                rgb(0xf0b35a)
+                                            } else if has_thread {
+                                                rgb(0x9ad1ff)
+                                            } else if is_active_line {
+                                                rgb(0x8ecbff)
+                                            } else {
+                                                rgb(0x5a5a5a)
+                                            })
+                                            .text_sm()
+                                            .child(match blame_gutter {
+                                                Some(blame) if first_segment => format!(
+                                                    "{:<blame_width$}{:width$}",
+                                                    Self::blame_label(blame, idx),
+                                                    idx + 1,
+                                                    blame_width = BLAME_GUTTER_COLUMNS,
+                                                    width = line_digits
+                                                ),
+                                                None if first_segment => format!(
+                                                    "{:width$}",
+                                                    idx + 1,
+                                                    width = line_digits
+                                                ),
+                                                _ => String::new(),
+                                            }),
+                                    );
+
+                                    let mut code_text = div()
+                                        .flex()
+                                        .items_start()
+                                        .gap_0()
+                                        .whitespace_nowrap()
+                                        .text_color(rgb(0xffffff))
+                                        .child(text);
+
+                                    if caret_at_eol && last_segment {
+                                        code_text = code_text.child(
+                                            div()
+                                                .w(px(2.0))
+                                                .h(px(self.line_height() * 0.9))
+                                                .bg(rgb(0x4c8dff)),
+                                        );
+                                    }
+
+                                    if line_len == 0 && caret_at_eol {
+                                        code_text = code_text
+                                            .child(div().text_color(rgb(0x333333)).child(" "));
+                                    }
+
+                                    if let Some(diagnostic) =
+                                        self.inline_diagnostic(idx).filter(|_| last_segment)
+                                    {
+                                        let (_, muted) =
+                                            Self::diagnostic_colors(diagnostic.severity_or_error());
+                                        let message =
+                                            diagnostic.message.lines().next().unwrap_or_default();
+                                        code_text = code_text.child(
+                                            div()
+                                                .ml_6()
+                                                .text_sm()
+                                                .text_color(rgb(muted))
+                                                .child(message.to_string()),
+                                        );
+                                    }
+
+                                    if let Some(hint) = self
+                                        .crate_hints
+                                        .iter()
+                                        .find(|hint| hint.line == idx)
+                                        .filter(|_| last_segment)
+                                    {
+                                        let (label, color) = if hint.outdated {
+                                            (format!("最新 {}", hint.latest), 0xd7ba7d)
                                         } else {
-                                            rgb(0x5a5a5a)
+                                            (format!("✓ {}", hint.latest), 0x6a9955)
+                                        };
+                                        code_text = code_text.child(
+                                            div()
+                                                .ml_6()
+                                                .text_sm()
+                                                .text_color(rgb(color))
+                                                .child(label),
+                                        );
+                                    }
+
+                                    if let Some(line) = blame
+                                        .filter(|_| {
+                                            is_active_line
+                                                && last_segment
+                                                && !self.show_blame_gutter
+                                                && self.selections.len() == 1
                                         })
-                                        .text_sm()
-                                        .child(format!("{:width$}", idx + 1, width = line_digits)),
-                                );
+                                        .and_then(|blame| blame.get(idx))
+                                    {
+                                        let label = if line.is_committed() {
+                                            format!(
+                                                "{}，{} • {}",
+                                                line.author,
+                                                Self::format_age(line.time),
+                                                line.summary
+                                            )
+                                        } else {
+                                            "未提交的改动".to_string()
+                                        };
+                                        code_text = code_text.child(
+                                            div()
+                                                .ml_6()
+                                                .text_sm()
+                                                .text_color(rgb(0x5f6b7a))
+                                                .child(label),
+                                        );
+                                    }
 
-                                let mut code_text = div()
-                                    .flex()
-                                    .items_start()
-                                    .gap_0()
-                                    .whitespace_nowrap()
-                                    .text_color(rgb(0xffffff))
-                                    .child(text);
+                                    if let Some((_, reference)) = self
+                                        .hovered_issue
+                                        .as_ref()
+                                        .filter(|(line, _)| *line == idx && last_segment)
+                                    {
+                                        let label = match reference
+                                            .number
+                                            .and_then(|number| self.issue_titles.get(&number))
+                                        {
+                                            Some(title) => format!("{} {}", reference.id, title),
+                                            None => format!(
+                                                "{} → {}（Cmd/Ctrl+点击打开）",
+                                                reference.id, reference.url
+                                            ),
+                                        };
+                                        code_text = code_text.child(
+                                            div()
+                                                .ml_6()
+                                                .text_sm()
+                                                .text_color(rgb(0x7fb8ff))
+                                                .child(label),
+                                        );
+                                    }
 
-                                if caret_at_eol {
-                                    code_text = code_text.child(
-                                        div()
-                                            .w(px(2.0))
-                                            .h(px(self.line_height() * 0.9))
-                                            .bg(rgb(0x4c8dff)),
-                                    );
+                                    line_row = line_row.child(code_text);
+                                    code_lines = code_lines.child(line_row);
                                 }
 
-                                if line_len == 0 && caret_at_eol {
-                                    code_text =
-                                        code_text.child(div().text_color(rgb(0x333333)).child(" "));
+                                if let Some(peek) =
+                                    self.peek.as_ref().filter(|peek| peek.anchor_line == idx)
+                                {
+                                    code_lines = code_lines.child(self.render_peek(peek, cx));
                                 }
-
-                                line_row = line_row.child(code_text);
-                                code_lines = code_lines.child(line_row);
                             }
 
                             code_lines
@@ -1305,10 +8435,67 @@ impl Render for EditorView {
                     }),
             );
 
-        content_area = content_area.child(editor_area);
+        content_area = match (&self.notebook, &self.table_mode) {
+            (Some(session), _) => content_area.child(self.render_notebook(session, cx)),
+            (None, Some(table)) => content_area.child(self.render_table(table, cx)),
+            (None, None) => content_area.child(editor_area),
+        };
+
+        if self.show_local_history {
+            content_area = content_area.child(self.render_local_history(cx));
+        }
+
+        if let Some(panel) = &self.document_tree {
+            content_area = content_area.child(self.render_document_tree(panel, cx));
+        }
+
+        if self.show_workflows {
+            content_area = content_area.child(self.render_workflows(cx));
+        }
+
+        if self.show_source_control {
+            content_area = content_area.child(self.render_source_control(cx));
+        }
+
+        if self.show_metrics {
+            content_area = content_area.child(self.render_metrics(cx));
+        }
+
+        if self
+            .inline_threads
+            .iter()
+            .any(|thread| self.current_file_path.as_ref() == Some(&thread.path))
+        {
+            content_area = content_area.child(self.render_inline_threads(cx));
+        }
+
+        if let Some(report) = &self.memory_report {
+            content_area = content_area.child(self.render_memory(report, cx));
+        }
+
+        if self.show_health_report {
+            content_area = content_area.child(self.render_health_report(cx));
+        }
+
+        if let Some(replay) = &self.edit_replay {
+            content_area = content_area.child(self.render_edit_replay(replay, cx));
+        }
+
+        if let Some(hierarchy) = &self.hierarchy {
+            content_area = content_area.child(self.render_hierarchy(hierarchy, cx));
+        }
+
+        if let Some(problems) = &self.problems {
+            content_area = content_area.child(self.render_problems(problems, cx));
+        }
+
+        if let Some((query, results)) = &self.workspace_search {
+            content_area = content_area.child(self.render_workspace_search(query, results, cx));
+        }
 
         if self.show_ai_panel {
             if let Some(ai_panel) = &self.ai_panel {
+                let composing = self.ai_tab(cx) == AIPanelTab::Composer;
                 content_area = content_area.child(
                     div()
                         .w(px(380.0))
@@ -1318,6 +8505,12 @@ impl Render for EditorView {
                         .border_l_1()
                         .border_color(rgb(0x1a2d4a))
                         .child(ai_panel.clone())
+                        .when(composing, |panel| {
+                            panel.child(self.render_composer(ai_panel, cx))
+                        })
+                        .when(!composing, |panel| {
+                            panel.child(self.render_citations(ai_panel, cx))
+                        })
                         .child(
                             div()
                                 .border_t_1()
@@ -1326,7 +8519,13 @@ impl Render for EditorView {
                                 .flex()
                                 .flex_col()
                                 .gap_2()
-                                .child(div().text_color(rgb(0x9ecbff)).text_sm().child("Ask AI"))
+                                .child(div().text_color(rgb(0x9ecbff)).text_sm().child(
+                                    if composing {
+                                        "Composer 指令"
+                                    } else {
+                                        "Ask AI"
+                                    },
+                                ))
                                 .child(
                                     div()
                                         .id("ai-input")
@@ -1341,9 +8540,11 @@ impl Render for EditorView {
                                         .p_2()
                                         .cursor_text()
                                         .child(if self.ai_prompt_input.is_empty() {
-                                            div()
-                                                .text_color(rgb(0x5f7a9c))
-                                                .child("输入问题，回车发送，Esc 退出")
+                                            div().text_color(rgb(0x5f7a9c)).child(if composing {
+                                                "描述要做的修改，回车发送，Esc 退出"
+                                            } else {
+                                                "输入问题，回车发送，Esc 退出"
+                                            })
                                         } else {
                                             div()
                                                 .text_color(rgb(0xd9e8ff))
@@ -1376,6 +8577,22 @@ impl Render for EditorView {
                                                     },
                                                 )),
                                         )
+                                        .child(
+                                            div()
+                                                .id("ai-explain-symbol")
+                                                .px_2()
+                                                .py_1()
+                                                .rounded(px(4.0))
+                                                .bg(rgb(0x1a4d8f))
+                                                .cursor_pointer()
+                                                .text_sm()
+                                                .child("解释符号")
+                                                .on_click(cx.listener(
+                                                    |view: &mut EditorView, _, _, cx| {
+                                                        view.explain_symbol_at_cursor(cx)
+                                                    },
+                                                )),
+                                        )
                                         .child(
                                             div()
                                                 .id("ai-improve")
@@ -1391,6 +8608,27 @@ impl Render for EditorView {
                                                         view.request_code_improvements(cx)
                                                     },
                                                 )),
+                                        )
+                                        .child(
+                                            div()
+                                                .id("ai-search-code")
+                                                .px_2()
+                                                .py_1()
+                                                .rounded(px(4.0))
+                                                .bg(rgb(0x1a4d8f))
+                                                .cursor_pointer()
+                                                .text_sm()
+                                                .child("搜索代码")
+                                                .on_click(cx.listener(
+                                                    |view: &mut EditorView, _, _, cx| {
+                                                        let query =
+                                                            view.ai_prompt_input.trim().to_string();
+                                                        if !query.is_empty() {
+                                                            view.ai_prompt_input.clear();
+                                                            view.search_codebase(query, cx);
+                                                        }
+                                                    },
+                                                )),
                                         ),
                                 ),
                         ),
@@ -1400,6 +8638,10 @@ impl Render for EditorView {
 
         layout
             .child(content_area)
+            .child(self.render_scan_banner(cx))
+            .child(self.render_lsp_install_banner(cx))
+            .child(self.render_server_crash_banner(cx))
+            .child(self.render_tool_output(cx))
             .child(
                 div()
                     .h(px(28.0))
@@ -1414,7 +8656,39 @@ impl Render for EditorView {
                     .text_color(rgb(0x888888))
                     .child(self.status_message.clone())
                     .child(format!(
-                        "{} • UTC {}",
+                        "{}{}{}{}{}{}{} • UTC {}",
+                        if self.performance_mode {
+                            "⚡ 性能模式 • "
+                        } else {
+                            ""
+                        },
+                        self.git_status
+                            .as_ref()
+                            .and_then(|status| status.branch.as_ref())
+                            .map(|branch| format!("⎇ {} • ", branch))
+                            .unwrap_or_default(),
+                        self.buffer_stats
+                            .as_ref()
+                            .map(|stats| format!("{} • ", Self::buffer_stats_label(stats)))
+                            .unwrap_or_default(),
+                        self.current_file_info
+                            .as_ref()
+                            .map(|info| format!("{} • ", Self::file_info_label(info)))
+                            .unwrap_or_default(),
+                        if self.current_file_path.is_some() {
+                            format!(
+                                "{} • {} • ",
+                                self.indent_style().label(),
+                                self.current_line_ending.label()
+                            )
+                        } else {
+                            String::new()
+                        },
+                        if self.current_read_only {
+                            "🔒 只读 • "
+                        } else {
+                            ""
+                        },
                         if self.is_dirty {
                             "● 未保存"
                         } else {
@@ -1444,7 +8718,7 @@ impl Render for EditorView {
                                 .shadow_lg()
                                 .mx_auto()
                                 .mt(px(120.0))
-                                .child(div().text_color(rgb(0xffffff)).child("Quick Open"))
+                                .child(div().text_color(rgb(0xffffff)).child(quick_input_title))
                                 .child(
                                     div()
                                         .mt_2()
@@ -1461,13 +8735,28 @@ impl Render for EditorView {
                                         .mt_2()
                                         .text_sm()
                                         .text_color(rgb(0x888888))
-                                        .child("输入相对路径，Enter 打开，Esc 取消"),
-                                ),
+                                        .child(quick_input_hint),
+                                )
+                                .child(self.render_reference_candidates(cx))
+                                .child(self.render_code_actions(cx))
+                                .child(self.render_completions(cx))
+                                .child(self.render_clipboard_history(cx))
+                                .child(self.render_case_transforms(cx))
+                                .child(self.render_external_tools(cx))
+                                .child(self.render_git_actions(cx))
+                                .child(self.render_pull_requests(cx))
+                                .child(self.render_quick_open_matches(cx))
+                                .child(self.render_recent_workspaces(cx)),
                         )
                 } else {
                     div()
                 }
             })
+            .child(self.render_import_conflicts(cx))
+            .child(self.render_edit_preview(cx))
+            .child(self.render_unsaved_review(cx))
+            .child(self.render_pull_request_review(cx))
+            .child(self.render_composer_review(cx))
     }
 }
 
@@ -1476,6 +8765,67 @@ impl EditorView {
         let key = event.keystroke.key.as_str();
         let modifiers = &event.keystroke.modifiers;
         let command = modifiers.platform;
+        self.keystroke_started.get_or_insert_with(Instant::now);
+        self.last_input = Instant::now();
+
+        if self.edit_preview.is_some() {
+            match key {
+                "Escape" => self.cancel_edit_preview(cx),
+                "Enter" => self.apply_edit_preview(cx),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(review) = &self.composer_review {
+            match review.editing {
+                Some(change) => self.edit_review_comment(change, key, cx),
+                None if key == "Escape" => {
+                    self.composer_review = None;
+                    cx.notify();
+                }
+                None => {}
+            }
+            return;
+        }
+
+        if !self.quick_open_active && self.handle_notebook_key(key, modifiers.shift, command, cx) {
+            return;
+        }
+
+        if self.table_mode.is_some()
+            && !self.quick_open_active
+            && !command
+            && !modifiers.alt
+            && !modifiers.control
+        {
+            return self.handle_table_key(key, cx);
+        }
+
+        if self.unsaved_review.is_some() && key == "Escape" {
+            self.unsaved_review = None;
+            cx.notify();
+            return;
+        }
+
+        if self.pull_request_review.is_some() && key == "Escape" {
+            self.pull_request_review = None;
+            cx.notify();
+            return;
+        }
+
+        if self.pending_import.is_some() {
+            match key {
+                "Escape" => {
+                    self.pending_import = None;
+                    self.set_status("已取消导入配置");
+                    cx.notify();
+                }
+                "Enter" => self.apply_pending_import(cx),
+                _ => {}
+            }
+            return;
+        }
 
         // 快速打开模式下，按键只影响输入框
         if self.quick_open_active {
@@ -1485,11 +8835,28 @@ impl EditorView {
                     self.quick_open_input.clear();
                     cx.notify();
                 }
-                "Enter" => self.open_quick_input_path(cx),
+                "Enter" => self.submit_quick_input(cx),
                 "Backspace" => {
                     self.quick_open_input.pop();
                     cx.notify();
                 }
+                "c" | "w" | "r" | "p" | "s"
+                    if modifiers.alt && self.quick_input_mode == QuickInputMode::Find =>
+                {
+                    self.toggle_search_option(key, cx)
+                }
+                "c" | "w" | "r"
+                    if modifiers.alt
+                        && self.quick_input_mode == QuickInputMode::SearchWorkspace =>
+                {
+                    self.toggle_search_option(key, cx)
+                }
+                "v" if command => {
+                    if let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) {
+                        self.quick_open_input.push_str(text.trim());
+                    }
+                    cx.notify();
+                }
                 _ if event.keystroke.key.len() == 1 => {
                     self.quick_open_input.push_str(&event.keystroke.key);
                     cx.notify();
@@ -1519,48 +8886,110 @@ impl EditorView {
             return;
         }
 
-        match key {
-            "s" if command => self.save_current_file(cx),
-            "o" if command => {
-                self.quick_open_active = true;
-                self.quick_open_input.clear();
-                cx.notify();
+        let context = KeyContext {
+            peek_open: self.peek.is_some(),
+            ai_panel_open: self.show_ai_panel,
+            multiple_cursors: self.selections.len() > 1,
+        };
+        if let Some(command) = keymap::route(key, modifiers, context) {
+            self.run_key_command(command, cx);
+        }
+    }
+
+    /// 执行按键翻译出的命令
+    fn run_key_command(&mut self, command: KeyCommand, cx: &mut Context<'_, Self>) {
+        match command {
+            KeyCommand::Save => self.save_current_file(cx),
+            KeyCommand::QuickInput(mode) => self.begin_quick_input(mode, cx),
+            KeyCommand::ToggleLocalHistory => self.toggle_local_history(cx),
+            KeyCommand::ToggleWorkflows => self.toggle_workflows_panel(cx),
+            KeyCommand::ToggleSourceControl => self.toggle_source_control(cx),
+            KeyCommand::ShowHierarchy(direction) => self.show_hierarchy(direction, cx),
+            KeyCommand::Peek(kind) => self.peek_at_cursor(kind, cx),
+            KeyCommand::CyclePeek { backward } => self.cycle_peek(backward, cx),
+            KeyCommand::OpenPeekLocation => self.open_peek_location(cx),
+            KeyCommand::ClosePeek => self.close_peek(cx),
+            KeyCommand::JumpToDiagnostic { forward } => self.jump_to_diagnostic(forward, cx),
+            KeyCommand::ToggleBookmark => self.toggle_bookmark(cx),
+            KeyCommand::JumpToNextMark { forward } => self.jump_to_next_mark(forward, cx),
+            KeyCommand::SetMark(name) => self.set_mark(name, cx),
+            KeyCommand::JumpToMark(name) => self.jump_to_mark(name, cx),
+            KeyCommand::NavigateSelections { forward } => {
+                self.navigate_selection_history(forward, cx)
+            }
+            KeyCommand::ToggleProblems => self.toggle_problems(cx),
+            KeyCommand::CopyDiagnostic => self.copy_diagnostic_at_cursor(cx),
+            KeyCommand::ReopenClosedTab => self.reopen_closed_tab(cx),
+            KeyCommand::CloseTab => self.close_current_tab(cx),
+            KeyCommand::CloseTabs(scope) => self.close_tabs(scope, cx),
+            KeyCommand::TogglePin => self.toggle_pin_current_tab(cx),
+            KeyCommand::ToggleLineEnding => {
+                self.convert_line_ending(self.current_line_ending.toggled(), cx)
             }
-            "n" if command => self.new_buffer(cx),
-            "p" if command && self.show_ai_panel => {
+            KeyCommand::ToggleInlineDiagnostics => self.toggle_inline_diagnostics(cx),
+            KeyCommand::ToggleBlame => self.toggle_blame_gutter(cx),
+            KeyCommand::ToggleInlineSeverity(severity) => self.toggle_inline_severity(severity, cx),
+            KeyCommand::DeleteFile => self.delete_current_file(cx),
+            KeyCommand::UndoFileOperation => self.undo_file_operation(cx),
+            KeyCommand::ToggleReadOnly => self.toggle_read_only(cx),
+            KeyCommand::ReloadFromDisk => self.reload_from_disk(cx),
+            KeyCommand::NewBuffer => self.new_buffer(cx),
+            KeyCommand::FocusAiInput => {
                 self.ai_input_focused = true;
                 cx.notify();
             }
-            "z" if command => self.undo(cx),
-            "y" if command => self.redo(cx),
-            "f" if command => log::info!("Open find dialog"),
-            "c" if command => self.copy_selection(cx),
-            "v" if command => self.paste_text(cx),
-            "/" if command => self.toggle_comment(cx),
-            "]" if command => self.indent_code(cx),
-            "[" if command => self.unindent_code(cx),
-            " " if modifiers.control => self.toggle_ai_panel(cx),
-            "ArrowLeft" | "Left" => self.move_cursor_by(CursorMovement::Left, modifiers.shift, cx),
-            "ArrowRight" | "Right" => {
-                self.move_cursor_by(CursorMovement::Right, modifiers.shift, cx)
-            }
-            "ArrowUp" | "Up" => self.move_cursor_by(CursorMovement::Up, modifiers.shift, cx),
-            "ArrowDown" | "Down" => self.move_cursor_by(CursorMovement::Down, modifiers.shift, cx),
-            "Home" => self.move_cursor_by(CursorMovement::Home, modifiers.shift, cx),
-            "End" => self.move_cursor_by(CursorMovement::End, modifiers.shift, cx),
-            _ => {
-                if !modifiers.modified() {
-                    match key {
-                        "Backspace" => self.delete_text(cx),
-                        "Enter" => self.insert_text("\n", cx),
-                        "Tab" => self.indent_code(cx),
-                        _ if event.keystroke.key.len() == 1 => {
-                            self.insert_text(&event.keystroke.key, cx);
-                        }
-                        _ => {}
-                    }
-                }
+            KeyCommand::ToggleAiPanel => self.toggle_ai_panel(cx),
+            KeyCommand::Undo => self.undo(cx),
+            KeyCommand::Redo => self.redo(cx),
+            KeyCommand::ToggleSoftWrap => self.toggle_soft_wrap(cx),
+            KeyCommand::Format => self.format_code(cx),
+            KeyCommand::FindNext => self.find_next(cx),
+            KeyCommand::Copy => self.copy_selection(cx),
+            KeyCommand::Cut => self.cut_selection(cx),
+            KeyCommand::Paste => self.paste_text(cx),
+            KeyCommand::CodeActions => self.show_code_actions(cx),
+            KeyCommand::Completions => self.show_completions(cx),
+            KeyCommand::ToggleComment => self.toggle_comment(cx),
+            KeyCommand::Indent => self.indent_code(cx),
+            KeyCommand::Unindent => self.unindent_code(cx),
+            KeyCommand::EditLines(action) => self.edit_lines(action, cx),
+            KeyCommand::LayoutArguments(action) => self.layout_arguments(action, cx),
+            KeyCommand::EditCursors(action) => self.edit_cursors(action, cx),
+            KeyCommand::ToggleMetrics => self.toggle_metrics_panel(cx),
+            KeyCommand::ToggleMemory => self.toggle_memory_panel(cx),
+            KeyCommand::TogglePerformanceMode => self.toggle_performance_mode(cx),
+            KeyCommand::ToggleHealthReport => self.toggle_health_report(cx),
+            KeyCommand::ServerLogs => self.open_server_logs(cx),
+            KeyCommand::ReviewUnsaved => self.review_unsaved_changes(cx),
+            KeyCommand::ExportEditLog => self.export_edit_log(cx),
+            KeyCommand::RecoverFromJournal => self.recover_from_journal(cx),
+            KeyCommand::ToggleTableMode => self.toggle_table_mode(cx),
+            KeyCommand::ToggleDocumentTree => self.toggle_document_tree(cx),
+            KeyCommand::MoveCursor { movement, extend } => {
+                self.move_cursor_by(movement, extend, cx)
             }
+            KeyCommand::DeleteBackward => self.delete_text(cx),
+            KeyCommand::InsertText(text) => self.insert_text(&text, cx),
         }
     }
 }
+
+/// 文件树悬停提示
+struct InfoTooltip {
+    text: String,
+}
+
+impl Render for InfoTooltip {
+    fn render(&mut self, _window: &mut Window, _cx: &mut Context<'_, Self>) -> impl IntoElement {
+        div()
+            .px_2()
+            .py_1()
+            .bg(rgb(0x1f1f1f))
+            .border_1()
+            .border_color(rgb(0x333333))
+            .rounded(px(4.0))
+            .text_xs()
+            .text_color(rgb(0xcccccc))
+            .child(self.text.clone())
+    }
+}
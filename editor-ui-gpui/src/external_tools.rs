@@ -0,0 +1,248 @@
+//! 外部工具：按名称筛选配置的外部命令，运行后把输出显示在面板中或替换选中内容
+
+use crate::editor_view::EditorView;
+use crate::keymap::QuickInputMode;
+use editor_core_project::{ExternalTool, ToolContext};
+use editor_infra::config::ToolOutput;
+use futures::FutureExt;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+
+impl EditorView {
+    pub(crate) fn filtered_tools(&self, filter: &str) -> Vec<ExternalTool> {
+        let filter = filter.to_lowercase();
+        self.config
+            .editor
+            .external_tools
+            .iter()
+            .filter(|tool| tool.name.to_lowercase().contains(&filter))
+            .map(ExternalTool::from_config)
+            .collect()
+    }
+
+    /// 运行外部工具，按配置把输出显示在面板或替换当前选区
+    pub fn run_external_tool(&mut self, tool: ExternalTool, cx: &mut Context<'_, Self>) {
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        let replace = tool.output == ToolOutput::ReplaceSelection;
+        if replace && !self.ensure_writable(cx) {
+            return;
+        }
+        let buffer_manager = self.buffer_manager.clone();
+        let executor = self.task_executor.clone();
+        self.set_status(format!("正在运行 {}…", tool.name));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let handle = buffer_manager.get_current_buffer().await;
+                let (selection, selected, version) = match &handle {
+                    Some(handle) => {
+                        let buffer = handle.lock().await;
+                        let selection = buffer.get_selections().first().copied();
+                        let selected = match &selection {
+                            Some(selection) => buffer.get_text_in_selection(selection).await,
+                            None => String::new(),
+                        };
+                        (selection, selected, buffer.version())
+                    }
+                    None => (None, String::new(), 0),
+                };
+                let current = buffer_manager.get_current_file_path().await;
+                let file = match current.clone() {
+                    Some(path) if !buffer_manager.is_untitled(&path).await => Some(path),
+                    _ => None,
+                };
+                let context = ToolContext {
+                    file,
+                    selection: selected.clone(),
+                    workspace: std::env::current_dir().unwrap_or_default(),
+                };
+                let runner = tool.clone();
+                // tokio::process 需要 tokio 运行时
+                let result = executor
+                    .spawn(async move { runner.run(&context).await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+
+                let run = match result {
+                    Ok(run) => run,
+                    Err(e) => {
+                        this.update(&mut app, |view, cx| {
+                            view.set_status(format!("{} 运行失败: {}", tool.name, e));
+                            cx.notify();
+                        })?;
+                        return anyhow::Ok(());
+                    }
+                };
+
+                let (Some(path), Some(selection), true, true) =
+                    (current, selection, replace, run.success)
+                else {
+                    this.update(&mut app, |view, cx| {
+                        if replace && run.success {
+                            view.set_status("没有打开的文件，输出显示在面板中");
+                        } else if run.success {
+                            view.set_status(format!("{} 已完成", tool.name));
+                        } else {
+                            view.set_status(format!("{} 运行失败", tool.name));
+                        }
+                        view.tool_output = Some((tool.name.clone(), run));
+                        cx.notify();
+                    })?;
+                    return anyhow::Ok(());
+                };
+
+                // 选区没有以换行结尾时，不把工具输出末尾的换行带进去
+                let output = if selected.is_empty() || selected.ends_with('\n') {
+                    run.stdout.clone()
+                } else {
+                    run.stdout.trim_end_matches(['\n', '\r']).to_string()
+                };
+                let applied = buffer_manager.submit_edit(&path, move |buffer| {
+                    async move {
+                        if buffer.version() != version {
+                            return None;
+                        }
+                        let snapshot = buffer.snapshot().await;
+                        let start = snapshot.cursor_to_char(selection.start());
+                        let end = snapshot.cursor_to_char(selection.end());
+                        let output = buffer.line_ending().normalize(&output).into_owned();
+                        buffer.replace_ranges(&[(start..end, output)]).await;
+                        Some((buffer.get_selections().to_vec(), buffer.is_dirty()))
+                    }
+                    .boxed()
+                });
+                let Some((selections, is_dirty)) = applied.await.flatten() else {
+                    this.update(&mut app, |view, cx| {
+                        view.set_status(format!("{} 运行期间内容已修改，结果已丢弃", tool.name));
+                        cx.notify();
+                    })?;
+                    return anyhow::Ok(());
+                };
+                this.update(&mut app, |view, cx| {
+                    view.set_status(format!("已用 {} 的输出替换选区", tool.name));
+                    view.set_selections(selections);
+                    view.is_dirty = is_dirty;
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn render_external_tools(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::RunTool {
+            return list;
+        }
+
+        for (index, tool) in self
+            .filtered_tools(self.quick_open_input.trim())
+            .into_iter()
+            .enumerate()
+        {
+            let target = match tool.output {
+                ToolOutput::Panel => "输出到面板",
+                ToolOutput::ReplaceSelection => "替换选区",
+            };
+            let command = std::iter::once(tool.command.as_str())
+                .chain(tool.args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" ");
+            list = list.child(
+                div()
+                    .id(("external-tool", index as u64))
+                    .flex()
+                    .justify_between()
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(tool.name.clone())
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x777777))
+                            .child(format!("{} · {}", command, target)),
+                    )
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.run_external_tool(tool.clone(), cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    pub(crate) fn render_tool_output(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let panel = div().id("tool-output");
+        let Some((name, run)) = &self.tool_output else {
+            return panel;
+        };
+
+        let mut output = div()
+            .id("tool-output-lines")
+            .mt_1()
+            .flex()
+            .flex_col()
+            .max_h(px(200.0))
+            .overflow_y_scroll()
+            .text_xs()
+            .font_family("monospace");
+        for line in run.stdout.lines() {
+            output = output.child(div().text_color(rgb(0xd0d0d0)).child(line.to_string()));
+        }
+        for line in run.stderr.lines() {
+            output = output.child(div().text_color(rgb(0xe0a0a0)).child(line.to_string()));
+        }
+        if run.stdout.trim().is_empty() && run.stderr.trim().is_empty() {
+            output = output.child(div().text_color(rgb(0x777777)).child("（没有输出）"));
+        }
+
+        panel
+            .px_3()
+            .py_1()
+            .bg(rgb(0x141414))
+            .border_t_1()
+            .border_color(rgb(0x2a2a2a))
+            .text_sm()
+            .flex()
+            .flex_col()
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .text_color(if run.success {
+                        rgb(0x9ad1ff)
+                    } else {
+                        rgb(0xff9a9a)
+                    })
+                    .child(if run.success {
+                        format!("{} 输出", name)
+                    } else {
+                        format!("{} 输出（运行失败）", name)
+                    })
+                    .child(
+                        div()
+                            .id("tool-output-dismiss")
+                            .px_2()
+                            .cursor_pointer()
+                            .child("✕")
+                            .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                view.tool_output = None;
+                                cx.notify();
+                            })),
+                    ),
+            )
+            .child(output)
+    }
+}
@@ -0,0 +1,286 @@
+//! 工作区健康报告：空闲时在后台扫描工作区，汇总问题并在面板中显示
+
+use crate::editor_view::EditorView;
+use editor_core_project::{GitRepository, HealthOptions, HealthReport, Workspace};
+use editor_lsp::uri_to_path;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+impl EditorView {
+    /// 闲置到配置的分钟数后在后台生成工作区健康报告；之后没有操作就不再重复生成
+    pub(crate) fn start_health_reports(&mut self, cx: &mut Context<'_, Self>) {
+        let minutes = self.config.editor.health_report_idle_minutes;
+        if minutes == 0 {
+            return;
+        }
+        let idle = Duration::from_secs(minutes * 60);
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                loop {
+                    app.background_executor()
+                        .timer(Duration::from_secs(60))
+                        .await;
+                    this.update(&mut app, |view, cx| {
+                        let unchanged = view
+                            .health_scanned_at
+                            .is_some_and(|at| at > view.last_input);
+                        if view.last_input.elapsed() >= idle && !unchanged {
+                            view.refresh_health_report(cx);
+                        }
+                    })?;
+                }
+            }
+        });
+        self.health_idle_watch = Some(task);
+    }
+
+    /// 在后台汇总诊断、TODO、大文件和陈旧的分支。遍历工作区的方式与代码索引相同
+    fn refresh_health_report(&mut self, cx: &mut Context<'_, Self>) {
+        if self.health_scan.is_some() {
+            return;
+        }
+        let Ok(root) = std::env::current_dir() else {
+            return;
+        };
+        let manager = self.lsp_manager.clone();
+        let lsp_executor = self.lsp_executor.clone();
+        let executor = self.task_executor.clone();
+        let repository = GitRepository::discover(&root);
+        let walk = self.walk_options();
+        let options = HealthOptions {
+            stale_branch_age: Duration::from_secs(
+                self.config.editor.stale_branch_days * 24 * 60 * 60,
+            ),
+            ..Default::default()
+        };
+        self.health_scanned_at = Some(Instant::now());
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let all = lsp_executor
+                    .spawn(async move { manager.all_diagnostics().await })
+                    .await
+                    .unwrap_or_default();
+                let mut diagnostics: HashMap<PathBuf, usize> = HashMap::new();
+                for (uri, file_diagnostics) in all {
+                    if let Some(path) = uri_to_path(&uri) {
+                        *diagnostics.entry(path).or_default() += file_diagnostics.len();
+                    }
+                }
+                let branches = match repository {
+                    // tokio::process 需要 tokio 运行时
+                    Some(repository) => executor
+                        .spawn(async move { repository.branches().await })
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|result| result.map_err(anyhow::Error::from))
+                        .unwrap_or_else(|e| {
+                            log::warn!("Failed to list branches: {}", e);
+                            Vec::new()
+                        }),
+                    None => Vec::new(),
+                };
+                let report = app
+                    .background_executor()
+                    .spawn(async move {
+                        let workspace = Workspace::single_root(&root).ok()?;
+                        Some(HealthReport::build(
+                            &workspace,
+                            &walk,
+                            &options,
+                            diagnostics,
+                            branches,
+                        ))
+                    })
+                    .await;
+                this.update(&mut app, |view, cx| {
+                    view.health_scan = None;
+                    if report.is_some() {
+                        view.health_report = report;
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        });
+        self.health_scan = Some(task);
+        cx.notify();
+    }
+
+    /// 打开或关闭工作区健康面板；还没有报告时立即生成一份
+    pub fn toggle_health_report(&mut self, cx: &mut Context<'_, Self>) {
+        self.show_health_report = !self.show_health_report;
+        if self.show_health_report && self.health_report.is_none() {
+            self.refresh_health_report(cx);
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn render_health_report(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x3a3a3a))
+                .cursor_pointer()
+                .child(label)
+        };
+        let section = |title: String| {
+            div()
+                .px_3()
+                .pt_3()
+                .pb_1()
+                .text_xs()
+                .text_color(rgb(0x9ad1ff))
+                .child(title)
+        };
+        let file_row = |id: (&'static str, usize), path: PathBuf, detail: String| {
+            let label = std::env::current_dir()
+                .ok()
+                .and_then(|root| path.strip_prefix(root).ok().map(Path::to_path_buf))
+                .unwrap_or_else(|| path.clone())
+                .display()
+                .to_string();
+            div()
+                .id(id)
+                .flex()
+                .justify_between()
+                .gap_2()
+                .px_3()
+                .py_1()
+                .text_sm()
+                .cursor_pointer()
+                .hover(|row| row.bg(rgb(0x1f1f1f)))
+                .child(div().text_color(rgb(0xffffff)).child(label))
+                .child(div().text_xs().text_color(rgb(0x888888)).child(detail))
+                .on_click(
+                    cx.listener(move |view: &mut EditorView, _, _, cx| view.open_file(&path, cx)),
+                )
+        };
+
+        let title = match (&self.health_report, self.health_scan.is_some()) {
+            (_, true) => "工作区健康 · 正在分析…".to_string(),
+            (Some(report), false) => {
+                format!("工作区健康 · {}", Self::format_age(report.generated_at))
+            }
+            (None, false) => "工作区健康".to_string(),
+        };
+        let mut panel = div()
+            .id("health-panel")
+            .w(px(360.0))
+            .flex()
+            .flex_col()
+            .overflow_scroll()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .child(div().text_color(rgb(0x9ad1ff)).child(title))
+                    .child(
+                        div()
+                            .flex()
+                            .gap_1()
+                            .text_xs()
+                            .child(button("health-refresh", "刷新").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| view.refresh_health_report(cx),
+                            )))
+                            .child(button("health-close", "关闭").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| {
+                                    view.show_health_report = false;
+                                    cx.notify();
+                                },
+                            ))),
+                    ),
+            );
+        let Some(report) = &self.health_report else {
+            return panel;
+        };
+        if !report.walk.is_complete() {
+            panel = panel.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_xs()
+                    .text_color(rgb(0xd7ba7d))
+                    .child(format!(
+                        "只分析了前 {} 个文件，可在设置中调整 editor.max_indexed_files",
+                        report.walk.files
+                    )),
+            );
+        }
+
+        panel = panel.child(section(format!(
+            "诊断最多的文件（共 {} 条）",
+            report.diagnostics_total
+        )));
+        for (idx, file) in report.diagnostics.iter().enumerate() {
+            panel = panel.child(file_row(
+                ("health-diagnostics", idx),
+                file.path.clone(),
+                format!("{} 条", file.count),
+            ));
+        }
+        panel = panel.child(section(format!(
+            "TODO 最多的文件（共 {} 处）",
+            report.todos_total
+        )));
+        for (idx, file) in report.todos.iter().enumerate() {
+            panel = panel.child(file_row(
+                ("health-todos", idx),
+                file.path.clone(),
+                format!("{} 处", file.count),
+            ));
+        }
+        panel = panel.child(section("大文件".to_string()));
+        for (idx, (path, size)) in report.large_files.iter().enumerate() {
+            panel = panel.child(file_row(
+                ("health-large", idx),
+                path.clone(),
+                Self::format_bytes(*size as usize),
+            ));
+        }
+        panel = panel.child(section(format!(
+            "超过 {} 天没有提交的分支",
+            self.config.editor.stale_branch_days
+        )));
+        for branch in &report.stale_branches {
+            panel = panel.child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .px_3()
+                    .py_1()
+                    .text_sm()
+                    .child(
+                        div()
+                            .text_color(rgb(0xffffff))
+                            .child(format!("⎇ {}", branch.name)),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x888888))
+                            .child(Self::format_age(branch.last_commit)),
+                    ),
+            );
+        }
+        panel
+    }
+}
@@ -0,0 +1,337 @@
+//! 调用层次与类型层次：由语言服务器提供，逐级展开、切换方向并跳转到定义
+
+use crate::editor_view::EditorView;
+use editor_lsp::{uri_to_path, HierarchyDirection, HierarchyItem, Position};
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+
+/// 层级面板中的一个节点，子节点在展开时才向语言服务器请求
+struct HierarchyNode {
+    id: u64,
+    item: HierarchyItem,
+    depth: usize,
+    expanded: bool,
+    loading: bool,
+}
+
+/// 光标处符号的调用层级或类型层级
+pub(crate) struct HierarchyPanel {
+    direction: HierarchyDirection,
+    language: String,
+    nodes: Vec<HierarchyNode>,
+    next_id: u64,
+}
+
+impl HierarchyPanel {
+    fn push_nodes(&mut self, at: usize, items: Vec<HierarchyItem>, depth: usize) {
+        let nodes: Vec<HierarchyNode> = items
+            .into_iter()
+            .map(|item| {
+                self.next_id += 1;
+                HierarchyNode {
+                    id: self.next_id,
+                    item,
+                    depth,
+                    expanded: false,
+                    loading: false,
+                }
+            })
+            .collect();
+        self.nodes.splice(at..at, nodes);
+    }
+
+    fn title(&self) -> &'static str {
+        match self.direction {
+            HierarchyDirection::IncomingCalls => "Call Hierarchy · 调用方",
+            HierarchyDirection::OutgoingCalls => "Call Hierarchy · 被调用",
+            HierarchyDirection::Supertypes => "Type Hierarchy · 父类型",
+            HierarchyDirection::Subtypes => "Type Hierarchy · 子类型",
+        }
+    }
+}
+
+impl EditorView {
+    /// 打开光标处符号的调用层级或类型层级，并展开第一层
+    pub(crate) fn show_hierarchy(
+        &mut self,
+        direction: HierarchyDirection,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let Some(cursor) = self.selection.map(|selection| selection.active) else {
+            return;
+        };
+        let language = self.current_file_language();
+        let uri = self.open_file_uri(&path);
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let snapshot = buffer_manager.file_snapshot(&path).await?;
+                let position = Position::from_cursor(snapshot.rope(), cursor);
+                let request_language = language.clone();
+                let result = executor
+                    .spawn(async move {
+                        manager
+                            .prepare_hierarchy(&request_language, direction, &uri, position)
+                            .await
+                    })
+                    .await
+                    .map_err(std::io::Error::other)
+                    .and_then(|r| r);
+
+                this.update(&mut app, |view, cx| {
+                    let roots = match result {
+                        Ok(roots) if roots.is_empty() => {
+                            view.set_status("光标处没有可展开层级的符号");
+                            return;
+                        }
+                        Ok(roots) => roots,
+                        Err(e) => {
+                            view.set_status(format!("查询层级失败: {}", e));
+                            return;
+                        }
+                    };
+                    let mut panel = HierarchyPanel {
+                        direction,
+                        language,
+                        nodes: Vec::new(),
+                        next_id: 0,
+                    };
+                    panel.push_nodes(0, roots, 0);
+                    let first = panel.nodes[0].id;
+                    view.hierarchy = Some(panel);
+                    view.toggle_hierarchy_node(first, cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 展开时请求下一层，折叠时移除该节点下的所有子节点
+    fn toggle_hierarchy_node(&mut self, id: u64, cx: &mut Context<'_, Self>) {
+        let Some(panel) = self.hierarchy.as_mut() else {
+            return;
+        };
+        let Some(idx) = panel.nodes.iter().position(|node| node.id == id) else {
+            return;
+        };
+        let depth = panel.nodes[idx].depth;
+        if panel.nodes[idx].expanded {
+            let end = panel.nodes[idx + 1..]
+                .iter()
+                .position(|node| node.depth <= depth)
+                .map_or(panel.nodes.len(), |offset| idx + 1 + offset);
+            panel.nodes.drain(idx + 1..end);
+            panel.nodes[idx].expanded = false;
+            cx.notify();
+            return;
+        }
+        if panel.nodes[idx].loading {
+            return;
+        }
+        panel.nodes[idx].loading = true;
+        let item = panel.nodes[idx].item.clone();
+        let direction = panel.direction;
+        let language = panel.language.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = executor
+                    .spawn(
+                        async move { manager.request_hierarchy(&language, direction, &item).await },
+                    )
+                    .await
+                    .map_err(std::io::Error::other)
+                    .and_then(|r| r);
+
+                this.update(&mut app, |view, cx| {
+                    // 等待期间面板可能被关闭或切换了方向
+                    let Some(panel) = view
+                        .hierarchy
+                        .as_mut()
+                        .filter(|panel| panel.direction == direction)
+                    else {
+                        return;
+                    };
+                    let Some(idx) = panel.nodes.iter().position(|node| node.id == id) else {
+                        return;
+                    };
+                    panel.nodes[idx].loading = false;
+                    match result {
+                        Ok(children) => {
+                            panel.nodes[idx].expanded = true;
+                            panel.push_nodes(idx + 1, children, depth + 1);
+                        }
+                        Err(e) => view.set_status(format!("查询层级失败: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 在调用方/被调用、父类型/子类型之间切换，保留根节点
+    fn flip_hierarchy_direction(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(panel) = self.hierarchy.as_mut() else {
+            return;
+        };
+        panel.direction = match panel.direction {
+            HierarchyDirection::IncomingCalls => HierarchyDirection::OutgoingCalls,
+            HierarchyDirection::OutgoingCalls => HierarchyDirection::IncomingCalls,
+            HierarchyDirection::Supertypes => HierarchyDirection::Subtypes,
+            HierarchyDirection::Subtypes => HierarchyDirection::Supertypes,
+        };
+        panel.nodes.retain(|node| node.depth == 0);
+        for node in &mut panel.nodes {
+            node.expanded = false;
+            node.loading = false;
+        }
+        if let Some(first) = panel.nodes.first().map(|node| node.id) {
+            self.toggle_hierarchy_node(first, cx);
+        }
+        cx.notify();
+    }
+
+    fn open_hierarchy_item(&mut self, item: &HierarchyItem, cx: &mut Context<'_, Self>) {
+        let Some(path) = uri_to_path(&item.uri) else {
+            return;
+        };
+        let start = &item.selection_range.start;
+        self.open_file_at(&path, start.line as usize, start.character as usize, cx);
+    }
+
+    pub(crate) fn render_hierarchy(
+        &self,
+        panel: &HierarchyPanel,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let mut list = div()
+            .id("hierarchy-list")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .overflow_scroll();
+
+        for node in &panel.nodes {
+            let id = node.id;
+            let item = node.item.clone();
+            let arrow = if node.loading {
+                "…"
+            } else if node.expanded {
+                "▾"
+            } else {
+                "▸"
+            };
+            let file = item
+                .uri
+                .rsplit('/')
+                .next()
+                .unwrap_or(item.uri.as_str())
+                .to_string();
+
+            list = list.child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_1()
+                    .py_1()
+                    .pr_3()
+                    .pl(px(12.0 + node.depth as f32 * 14.0))
+                    .text_sm()
+                    .child(
+                        div()
+                            .id(("hierarchy-toggle", id))
+                            .w(px(14.0))
+                            .cursor_pointer()
+                            .text_color(rgb(0x888888))
+                            .child(arrow)
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.toggle_hierarchy_node(id, cx)
+                            })),
+                    )
+                    .child(
+                        div()
+                            .id(("hierarchy-item", id))
+                            .flex()
+                            .gap_2()
+                            .cursor_pointer()
+                            .whitespace_nowrap()
+                            .child(div().text_color(rgb(0xdddddd)).child(item.name.clone()))
+                            .child(div().text_xs().text_color(rgb(0x777777)).child(format!(
+                                "{}:{}",
+                                file,
+                                item.selection_range.start.line + 1
+                            )))
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.open_hierarchy_item(&item, cx)
+                            })),
+                    ),
+            );
+        }
+
+        div()
+            .w(px(320.0))
+            .flex()
+            .flex_col()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .child(div().text_color(rgb(0x9ad1ff)).child(panel.title()))
+                    .child(
+                        div()
+                            .flex()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .id("hierarchy-flip")
+                                    .px_2()
+                                    .rounded(px(4.0))
+                                    .bg(rgb(0x3a3a3a))
+                                    .cursor_pointer()
+                                    .child("⇄")
+                                    .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                        view.flip_hierarchy_direction(cx)
+                                    })),
+                            )
+                            .child(
+                                div()
+                                    .id("hierarchy-close")
+                                    .px_2()
+                                    .cursor_pointer()
+                                    .text_color(rgb(0xcccccc))
+                                    .child("×")
+                                    .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                        view.hierarchy = None;
+                                        cx.notify();
+                                    })),
+                            ),
+                    ),
+            )
+            .child(list)
+    }
+}
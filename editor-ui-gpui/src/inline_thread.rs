@@ -0,0 +1,493 @@
+//! 锚定在代码范围上的 AI 对话：行号栏中的标记、对话窗口和限定在范围内的改写
+
+use crate::editor_view::EditorView;
+use crate::keymap::QuickInputMode;
+use editor_ai::models::{AIMessage, AIRole};
+use editor_ai::ThreadContext;
+use editor_core_project::{BufferManager, EditPreview};
+use futures::FutureExt;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, Pixels, Point,
+    StatefulInteractiveElement, WeakEntity,
+};
+use std::path::{Path, PathBuf};
+
+/// 锚定在一段代码上的 AI 对话。范围由缓冲区跟踪，随编辑移动
+pub(crate) struct InlineThread {
+    /// 缓冲区中跟踪的范围
+    pub(crate) range_id: u64,
+    pub(crate) path: PathBuf,
+    /// 范围当前所在的行，刷新视图时更新
+    pub(crate) lines: std::ops::Range<usize>,
+    messages: Vec<AIMessage>,
+    collapsed: bool,
+    pending: bool,
+}
+
+impl EditorView {
+    /// 跟踪范围覆盖的行；范围止于下一行行首时不含那一行
+    pub(crate) fn thread_line_span(
+        range: &std::ops::Range<editor_core_text::Cursor>,
+    ) -> std::ops::Range<usize> {
+        let end = if range.end.column == 0 && range.end.line > range.start.line {
+            range.end.line
+        } else {
+            range.end.line + 1
+        };
+        range.start.line..end
+    }
+
+    /// 对话范围的当前内容，以及范围本身
+    async fn inline_thread_context(
+        buffer_manager: &BufferManager,
+        path: &Path,
+        range_id: u64,
+    ) -> Option<(ThreadContext, std::ops::Range<editor_core_text::Cursor>)> {
+        let handle = buffer_manager.get_buffer(path).await?;
+        let buffer = handle.lock().await;
+        let range = buffer.tracked_range(range_id).await?;
+        let snapshot = buffer.snapshot().await;
+        let code = snapshot
+            .slice(snapshot.cursor_to_char(range.start)..snapshot.cursor_to_char(range.end));
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let context = ThreadContext {
+            file_path: path
+                .strip_prefix(&cwd)
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+            language: buffer_manager.language(path).await,
+            start_line: range.start.line + 1,
+            code: code.trim_end_matches(['\n', '\r']).to_string(),
+        };
+        Some((context, range))
+    }
+
+    /// 向代码范围上的对话提问：点过“回复”的对话优先，其次是光标所在行的对话；
+    /// 有选区或光标处没有对话时，就选中的行（或光标所在行）开始新对话
+    pub(crate) fn ask_inline_thread(&mut self, question: String, cx: &mut Context<'_, Self>) {
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let Some(selection) = self.selections.first().copied() else {
+            return;
+        };
+        let target = self.thread_reply_target.take().or_else(|| {
+            if !selection.is_collapsed() {
+                return None;
+            }
+            let line = selection.active.line;
+            self.inline_threads
+                .iter()
+                .filter(|thread| thread.path == path && thread.lines.contains(&line))
+                .min_by_key(|thread| thread.collapsed)
+                .map(|thread| thread.range_id)
+        });
+        let buffer_manager = self.buffer_manager.clone();
+        let ai_engine = self.ai_engine.clone();
+        let ai_executor = self.ai_executor.clone();
+        self.set_status("AI 正在回复…");
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let range_id = match target {
+                    Some(range_id) => Some(range_id),
+                    None => {
+                        buffer_manager
+                            .submit_edit(&path, move |buffer| {
+                                async move {
+                                    let (start, end) = (selection.start(), selection.end());
+                                    let last = if end.column == 0 && end.line > start.line {
+                                        end.line - 1
+                                    } else {
+                                        end.line
+                                    };
+                                    let end = buffer
+                                        .line_range_at(editor_core_text::Cursor::new(last, 0))
+                                        .await
+                                        .end;
+                                    let start = editor_core_text::Cursor::new(start.line, 0);
+                                    buffer.track_range(start, end).await
+                                }
+                                .boxed()
+                            })
+                            .await
+                    }
+                };
+                let context = match range_id {
+                    Some(range_id) => {
+                        Self::inline_thread_context(&buffer_manager, &path, range_id).await
+                    }
+                    None => None,
+                };
+                let (Some(range_id), Some((context, range))) = (range_id, context) else {
+                    this.update(&mut app, |view, cx| {
+                        view.set_status("对话的代码范围已不存在");
+                        cx.notify();
+                    })?;
+                    return anyhow::Ok(());
+                };
+
+                let history = this.update(&mut app, |view, cx| {
+                    if !view
+                        .inline_threads
+                        .iter()
+                        .any(|thread| thread.range_id == range_id)
+                    {
+                        view.inline_threads.push(InlineThread {
+                            range_id,
+                            path: path.clone(),
+                            lines: Self::thread_line_span(&range),
+                            messages: Vec::new(),
+                            collapsed: false,
+                            pending: false,
+                        });
+                    }
+                    let thread = view
+                        .inline_threads
+                        .iter_mut()
+                        .find(|thread| thread.range_id == range_id)?;
+                    let history = thread.messages.clone();
+                    thread.messages.push(AIMessage {
+                        role: AIRole::User,
+                        content: question.clone(),
+                    });
+                    thread.collapsed = false;
+                    thread.pending = true;
+                    cx.notify();
+                    Some(history)
+                })?;
+                let Some(history) = history else {
+                    return anyhow::Ok(());
+                };
+
+                let reply = ai_executor
+                    .spawn(async move {
+                        ai_engine
+                            .reply_in_thread(&context, &history, &question, None)
+                            .await
+                    })
+                    .await?;
+                this.update(&mut app, |view, cx| {
+                    let Some(thread) = view
+                        .inline_threads
+                        .iter_mut()
+                        .find(|thread| thread.range_id == range_id)
+                    else {
+                        return;
+                    };
+                    thread.pending = false;
+                    match reply {
+                        Ok(content) => {
+                            thread.messages.push(AIMessage {
+                                role: AIRole::Assistant,
+                                content,
+                            });
+                            view.set_status("AI 已回复");
+                        }
+                        Err(e) => view.set_status(format!("AI 回复失败: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 按对话改写对话的代码范围，改写结果只替换这段范围，应用前先预览
+    fn rewrite_inline_thread(&mut self, range_id: u64, cx: &mut Context<'_, Self>) {
+        let Some(thread) = self
+            .inline_threads
+            .iter_mut()
+            .find(|thread| thread.range_id == range_id)
+        else {
+            return;
+        };
+        thread.pending = true;
+        let path = thread.path.clone();
+        let history = thread.messages.clone();
+        let buffer_manager = self.buffer_manager.clone();
+        let ai_engine = self.ai_engine.clone();
+        let ai_executor = self.ai_executor.clone();
+        self.set_status("AI 正在生成补丁…");
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let mut result = Err("对话的代码范围已不存在".to_string());
+                if let Some((context, _)) =
+                    Self::inline_thread_context(&buffer_manager, &path, range_id).await
+                {
+                    let thread_context = context.clone();
+                    result = ai_executor
+                        .spawn(async move {
+                            ai_engine
+                                .rewrite_thread_range(
+                                    &thread_context,
+                                    &history,
+                                    "Apply what we discussed to this code.",
+                                    None,
+                                )
+                                .await
+                                .map_err(|e| e.to_string())
+                        })
+                        .await?;
+                    // 生成期间范围可能被编辑过，只在原文没变时给出补丁
+                    if let Ok(new_code) = &result {
+                        result = match Self::inline_thread_context(&buffer_manager, &path, range_id)
+                            .await
+                        {
+                            Some((current, range)) if current.code == context.code => {
+                                let mut preview = EditPreview::new(format!(
+                                    "对话补丁: 第 {}-{} 行",
+                                    range.start.line + 1,
+                                    Self::thread_line_span(&range).end
+                                ))
+                                .with_agent("AI");
+                                // 范围含结尾的换行，改写的代码不含
+                                let line_break =
+                                    if range.end.column == 0 && range.end.line > range.start.line {
+                                        "\n"
+                                    } else {
+                                        ""
+                                    };
+                                preview.push(
+                                    &path,
+                                    range.start,
+                                    range.end,
+                                    format!("{}{}", new_code, line_break),
+                                );
+                                this.update(&mut app, |view, cx| {
+                                    view.show_edit_preview(preview, None, cx)
+                                })?;
+                                Ok(String::new())
+                            }
+                            _ => Err("代码在生成补丁时被修改，请重试".to_string()),
+                        };
+                    }
+                }
+
+                this.update(&mut app, |view, cx| {
+                    if let Some(thread) = view
+                        .inline_threads
+                        .iter_mut()
+                        .find(|thread| thread.range_id == range_id)
+                    {
+                        thread.pending = false;
+                    }
+                    if let Err(e) = result {
+                        view.set_status(format!("无法生成补丁: {}", e));
+                    }
+                    cx.notify();
+                })?;
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 结束对话，不再跟踪它的代码范围
+    fn close_inline_thread(&mut self, range_id: u64, cx: &mut Context<'_, Self>) {
+        let Some(index) = self
+            .inline_threads
+            .iter()
+            .position(|thread| thread.range_id == range_id)
+        else {
+            return;
+        };
+        let thread = self.inline_threads.remove(index);
+        if self.thread_reply_target == Some(range_id) {
+            self.thread_reply_target = None;
+        }
+        let untracked = self
+            .buffer_manager
+            .submit_edit(&thread.path, move |buffer| {
+                async move { buffer.untrack_range(thread.range_id) }.boxed()
+            });
+        self.task_executor.clone().spawn(async move {
+            untracked.await;
+        });
+        cx.notify();
+    }
+
+    /// 当前文件的内联对话，显示在编辑区右侧；每个对话可折叠，行号栏中有对应的标记
+    pub(crate) fn render_inline_threads(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let button = |id: (&'static str, usize), label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x3a3a3a))
+                .cursor_pointer()
+                .child(label)
+        };
+        let mut panel = div()
+            .id("inline-threads")
+            .w(px(360.0))
+            .flex()
+            .flex_col()
+            .overflow_scroll()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .text_color(rgb(0x9ad1ff))
+                    .child("代码对话"),
+            );
+
+        let threads = self
+            .inline_threads
+            .iter()
+            .filter(|thread| self.current_file_path.as_ref() == Some(&thread.path));
+        for thread in threads {
+            let range_id = thread.range_id;
+            let id = range_id as usize;
+            let topic = thread
+                .messages
+                .first()
+                .map(|message| message.content.lines().next().unwrap_or_default())
+                .unwrap_or_default();
+            let mut entry = div()
+                .flex()
+                .flex_col()
+                .px_3()
+                .py_2()
+                .border_b_1()
+                .border_color(rgb(0x1f1f1f))
+                .child(
+                    div()
+                        .id(("inline-thread-toggle", id))
+                        .flex()
+                        .gap_2()
+                        .cursor_pointer()
+                        .text_sm()
+                        .child(div().text_color(rgb(0x9ad1ff)).child(format!(
+                            "{} 第 {}-{} 行",
+                            if thread.collapsed { "▸" } else { "▾" },
+                            thread.lines.start + 1,
+                            thread.lines.end.max(thread.lines.start + 1)
+                        )))
+                        .child(
+                            div()
+                                .flex_1()
+                                .overflow_hidden()
+                                .whitespace_nowrap()
+                                .text_color(rgb(0x888888))
+                                .child(topic.to_string()),
+                        )
+                        .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                            if let Some(thread) = view
+                                .inline_threads
+                                .iter_mut()
+                                .find(|thread| thread.range_id == range_id)
+                            {
+                                thread.collapsed = !thread.collapsed;
+                            }
+                            cx.notify();
+                        })),
+                );
+
+            if !thread.collapsed {
+                for message in &thread.messages {
+                    let (speaker, color) = match message.role {
+                        AIRole::User => ("你", rgb(0xf0b35a)),
+                        _ => ("AI", rgb(0x9ad1ff)),
+                    };
+                    entry = entry.child(
+                        div()
+                            .mt_1()
+                            .flex()
+                            .flex_col()
+                            .text_xs()
+                            .child(div().text_color(color).child(speaker))
+                            .child(
+                                div()
+                                    .text_color(rgb(0xdddddd))
+                                    .child(message.content.clone()),
+                            ),
+                    );
+                }
+                if thread.pending {
+                    entry = entry.child(
+                        div()
+                            .mt_1()
+                            .text_xs()
+                            .text_color(rgb(0x666666))
+                            .child("思考中…"),
+                    );
+                }
+                entry =
+                    entry.child(
+                        div()
+                            .mt_2()
+                            .flex()
+                            .gap_1()
+                            .text_xs()
+                            .child(button(("inline-thread-reply", id), "回复").on_click(
+                                cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                    view.begin_quick_input(QuickInputMode::InlineThread, cx);
+                                    view.thread_reply_target = Some(range_id);
+                                }),
+                            ))
+                            .when(!thread.pending && !thread.messages.is_empty(), |row| {
+                                row.child(button(("inline-thread-patch", id), "生成补丁").on_click(
+                                    cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                        view.rewrite_inline_thread(range_id, cx)
+                                    }),
+                                ))
+                            })
+                            .child(button(("inline-thread-close", id), "关闭").on_click(
+                                cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                    view.close_inline_thread(range_id, cx)
+                                }),
+                            )),
+                    );
+            }
+            panel = panel.child(entry);
+        }
+
+        panel
+    }
+
+    /// 点击行号栏中对话的标记时展开或折叠对话
+    pub(crate) fn toggle_inline_thread_at_point(
+        &mut self,
+        position: Point<Pixels>,
+        cx: &mut Context<'_, Self>,
+    ) -> bool {
+        let bounds = self.scroll_handle.bounds();
+        let x = f32::from(position.x) - f32::from(bounds.left()) - self.code_area_padding()
+            + f32::from(self.scroll_handle.offset().x);
+        if x > self.gutter_width() {
+            return false;
+        }
+        let Some((line, _)) = self.position_from_point(position) else {
+            return false;
+        };
+        let current = self.current_file_path.clone();
+        let Some(thread) = self
+            .inline_threads
+            .iter_mut()
+            .find(|thread| thread.lines.start == line && current.as_ref() == Some(&thread.path))
+        else {
+            return false;
+        };
+        thread.collapsed = !thread.collapsed;
+        cx.notify();
+        true
+    }
+}
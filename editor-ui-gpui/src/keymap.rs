@@ -0,0 +1,321 @@
+//! 编辑区的按键绑定。按键先翻译成 [`KeyCommand`] 再执行，这一步不依赖窗口，
+//! 可以在无界面的测试里直接验证
+
+use editor_core_text::{CursorMovement, LineDirection, MarkName};
+use editor_lsp::{DiagnosticSeverity, HierarchyDirection};
+use gpui::Modifiers;
+
+/// 多光标命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorAction {
+    AddAbove,
+    AddBelow,
+    AddNextOccurrence,
+    SplitIntoLines,
+    Collapse,
+}
+
+/// 行内预览的内容来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeekKind {
+    Definition,
+    References,
+}
+
+impl PeekKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            PeekKind::Definition => "定义",
+            PeekKind::References => "引用",
+        }
+    }
+}
+
+/// 批量关闭标签页的范围，固定的标签页不受影响
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabClose {
+    Others,
+    All,
+}
+
+/// 快速输入框的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuickInputMode {
+    OpenPath,
+    RenameBuffer,
+    SetLanguage,
+    ExportSettings,
+    ImportSettings,
+    PickReference,
+    PickCodeAction,
+    NewFile,
+    NewFolder,
+    MoveFile,
+    Find,
+    Replace,
+    SetMark,
+    GotoMark,
+    PickCompletion,
+    PickClipboard,
+    ReplayEditLog,
+    TransformCase,
+    InlineThread,
+    RunTool,
+    GitCommand,
+    AddWorktree,
+    PickPullRequest,
+    CommentPullRequest,
+    SearchWorkspace,
+    RecentWorkspace,
+}
+
+/// 按行编辑的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineAction {
+    Delete,
+    Duplicate,
+    Move(LineDirection),
+}
+
+/// 光标所在参数列表的排版方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentAction {
+    /// 每个参数一行
+    Split,
+    /// 合并到一行
+    Join,
+    /// 超出 `editor.max_line_length` 时拆分，否则合并
+    Wrap,
+}
+
+/// 会改变按键含义的界面状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyContext {
+    pub peek_open: bool,
+    pub ai_panel_open: bool,
+    pub multiple_cursors: bool,
+}
+
+/// 编辑区按键对应的命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyCommand {
+    Save,
+    QuickInput(QuickInputMode),
+    ToggleLocalHistory,
+    ToggleWorkflows,
+    ToggleSourceControl,
+    ShowHierarchy(HierarchyDirection),
+    Peek(PeekKind),
+    CyclePeek {
+        backward: bool,
+    },
+    OpenPeekLocation,
+    ClosePeek,
+    JumpToDiagnostic {
+        forward: bool,
+    },
+    ToggleBookmark,
+    JumpToNextMark {
+        forward: bool,
+    },
+    SetMark(MarkName),
+    JumpToMark(MarkName),
+    NavigateSelections {
+        forward: bool,
+    },
+    ToggleProblems,
+    CopyDiagnostic,
+    ReopenClosedTab,
+    CloseTab,
+    CloseTabs(TabClose),
+    TogglePin,
+    ToggleLineEnding,
+    ToggleInlineDiagnostics,
+    ToggleBlame,
+    ToggleInlineSeverity(DiagnosticSeverity),
+    DeleteFile,
+    UndoFileOperation,
+    ToggleReadOnly,
+    ReloadFromDisk,
+    NewBuffer,
+    FocusAiInput,
+    ToggleAiPanel,
+    Undo,
+    Redo,
+    ToggleSoftWrap,
+    Format,
+    FindNext,
+    Copy,
+    Cut,
+    Paste,
+    CodeActions,
+    Completions,
+    ToggleComment,
+    Indent,
+    Unindent,
+    EditLines(LineAction),
+    LayoutArguments(ArgumentAction),
+    EditCursors(CursorAction),
+    ToggleMetrics,
+    ServerLogs,
+    ReviewUnsaved,
+    ExportEditLog,
+    RecoverFromJournal,
+    ToggleTableMode,
+    ToggleDocumentTree,
+    ToggleMemory,
+    ToggleHealthReport,
+    TogglePerformanceMode,
+    MoveCursor {
+        movement: CursorMovement,
+        extend: bool,
+    },
+    DeleteBackward,
+    InsertText(String),
+}
+
+/// 单个数字是编号书签，其余是命名书签
+pub fn mark_name(input: &str) -> MarkName {
+    match input.parse::<u8>() {
+        Ok(number) if number < 10 => MarkName::Number(number),
+        _ => MarkName::Named(input.to_string()),
+    }
+}
+
+/// 编辑区（没有弹出输入框或预览时）按下 `key` 对应的命令
+pub fn route(key: &str, modifiers: &Modifiers, context: KeyContext) -> Option<KeyCommand> {
+    use KeyCommand::*;
+
+    let command = modifiers.platform;
+    let movement = |movement| MoveCursor {
+        movement,
+        extend: modifiers.shift,
+    };
+    let routed = match key {
+        "s" if command => Save,
+        "o" if command && modifiers.alt && modifiers.shift => ToggleDocumentTree,
+        "o" if command && modifiers.alt => QuickInput(QuickInputMode::RecentWorkspace),
+        "o" if command => QuickInput(QuickInputMode::OpenPath),
+        "h" if command && modifiers.shift => ToggleLocalHistory,
+        "w" if command && modifiers.shift => ToggleWorkflows,
+        "h" if modifiers.alt && modifiers.shift => ShowHierarchy(HierarchyDirection::IncomingCalls),
+        "y" if modifiers.alt && modifiers.shift => ShowHierarchy(HierarchyDirection::Supertypes),
+        "F12" if modifiers.alt && modifiers.shift => Peek(PeekKind::References),
+        "F12" if modifiers.alt => Peek(PeekKind::Definition),
+        "F4" if context.peek_open => CyclePeek {
+            backward: modifiers.shift,
+        },
+        "F8" => JumpToDiagnostic {
+            forward: !modifiers.shift,
+        },
+        "F2" if command && modifiers.shift => QuickInput(QuickInputMode::SetMark),
+        "F2" if command && modifiers.alt => QuickInput(QuickInputMode::GotoMark),
+        "F2" if command => ToggleBookmark,
+        "F2" => JumpToNextMark {
+            forward: !modifiers.shift,
+        },
+        "-" | "_" if modifiers.control => NavigateSelections {
+            forward: modifiers.shift,
+        },
+        digit if modifiers.control && digit.len() == 1 && digit.as_bytes()[0].is_ascii_digit() => {
+            if modifiers.shift {
+                SetMark(mark_name(digit))
+            } else {
+                JumpToMark(mark_name(digit))
+            }
+        }
+        "m" if command && modifiers.shift => ToggleProblems,
+        "c" if command && modifiers.alt => CopyDiagnostic,
+        "Enter" if context.peek_open && !modifiers.modified() => OpenPeekLocation,
+        "Escape" if context.peek_open => ClosePeek,
+        "t" if command && modifiers.alt && modifiers.shift => ToggleTableMode,
+        "t" if command && modifiers.shift => ReopenClosedTab,
+        "t" if command && modifiers.alt => CloseTabs(TabClose::Others),
+        "w" if command && modifiers.alt => CloseTabs(TabClose::All),
+        "p" if command && modifiers.alt && modifiers.shift => TogglePerformanceMode,
+        "p" if command && modifiers.alt => TogglePin,
+        "p" if command && modifiers.shift => QuickInput(QuickInputMode::RunTool),
+        "l" if command && modifiers.alt => ToggleLineEnding,
+        "d" if command && modifiers.alt => ToggleInlineDiagnostics,
+        "b" if command && modifiers.alt => ToggleBlame,
+        "1" if command && modifiers.alt => ToggleInlineSeverity(DiagnosticSeverity::Error),
+        "2" if command && modifiers.alt => ToggleInlineSeverity(DiagnosticSeverity::Warning),
+        "3" if command && modifiers.alt => ToggleInlineSeverity(DiagnosticSeverity::Information),
+        "4" if command && modifiers.alt => ToggleInlineSeverity(DiagnosticSeverity::Hint),
+        "w" if command => CloseTab,
+        "e" if command && modifiers.shift => QuickInput(QuickInputMode::ExportSettings),
+        "i" if command && modifiers.shift => QuickInput(QuickInputMode::ImportSettings),
+        "r" if command && modifiers.shift => QuickInput(QuickInputMode::RenameBuffer),
+        "l" if command && modifiers.shift => QuickInput(QuickInputMode::SetLanguage),
+        "n" if command && modifiers.alt && modifiers.shift => QuickInput(QuickInputMode::NewFolder),
+        "n" if command && modifiers.alt => QuickInput(QuickInputMode::NewFile),
+        "m" if command && modifiers.alt => QuickInput(QuickInputMode::MoveFile),
+        "Backspace" if command && modifiers.alt => DeleteFile,
+        "z" if command && modifiers.alt => UndoFileOperation,
+        "e" if command && modifiers.alt => ToggleReadOnly,
+        "r" if command && modifiers.alt => ReloadFromDisk,
+        "n" if command => NewBuffer,
+        "p" if command && context.ai_panel_open => FocusAiInput,
+        "z" if command => Undo,
+        "z" if modifiers.alt => ToggleSoftWrap,
+        "y" if command => Redo,
+        "f" if modifiers.alt && modifiers.shift => Format,
+        "f" if command && modifiers.alt => QuickInput(QuickInputMode::Replace),
+        "f" if command && modifiers.shift => QuickInput(QuickInputMode::SearchWorkspace),
+        "f" if command => QuickInput(QuickInputMode::Find),
+        "g" if command && modifiers.shift => ToggleSourceControl,
+        "g" if command && modifiers.alt => QuickInput(QuickInputMode::GitCommand),
+        "g" if command => FindNext,
+        "c" if command => Copy,
+        "x" if command => Cut,
+        "v" if command && modifiers.shift => QuickInput(QuickInputMode::PickClipboard),
+        "v" if command => Paste,
+        "," if command && modifiers.alt && modifiers.shift => LayoutArguments(ArgumentAction::Join),
+        "," if command && modifiers.alt => LayoutArguments(ArgumentAction::Split),
+        "." if command && modifiers.alt => LayoutArguments(ArgumentAction::Wrap),
+        "." if command => CodeActions,
+        "i" if command && modifiers.alt => QuickInput(QuickInputMode::InlineThread),
+        "i" if command => Completions,
+        "/" if command => ToggleComment,
+        "]" if command => Indent,
+        "[" if command => Unindent,
+        " " if modifiers.control => ToggleAiPanel,
+        "k" if command && modifiers.alt && modifiers.shift => ToggleMemory,
+        "h" if command && modifiers.alt => ToggleHealthReport,
+        "k" if command && modifiers.shift => EditLines(LineAction::Delete),
+        "k" if command && modifiers.alt => ToggleMetrics,
+        "u" if command && modifiers.shift => ServerLogs,
+        "u" if command && modifiers.alt => ReviewUnsaved,
+        "u" if command => QuickInput(QuickInputMode::TransformCase),
+        "j" if command && modifiers.alt && modifiers.shift => RecoverFromJournal,
+        "j" if command && modifiers.alt => ExportEditLog,
+        "j" if command && modifiers.shift => QuickInput(QuickInputMode::ReplayEditLog),
+        "ArrowUp" | "Up" if command && modifiers.alt => EditCursors(CursorAction::AddAbove),
+        "ArrowDown" | "Down" if command && modifiers.alt => EditCursors(CursorAction::AddBelow),
+        "d" if command => EditCursors(CursorAction::AddNextOccurrence),
+        "i" if modifiers.alt && modifiers.shift => EditCursors(CursorAction::SplitIntoLines),
+        "Escape" if context.multiple_cursors => EditCursors(CursorAction::Collapse),
+        "ArrowUp" | "Up" | "ArrowDown" | "Down" if modifiers.alt && modifiers.shift => {
+            EditLines(LineAction::Duplicate)
+        }
+        "ArrowUp" | "Up" if modifiers.alt => EditLines(LineAction::Move(LineDirection::Up)),
+        "ArrowDown" | "Down" if modifiers.alt => EditLines(LineAction::Move(LineDirection::Down)),
+        "ArrowLeft" | "Left" => movement(CursorMovement::Left),
+        "ArrowRight" | "Right" => movement(CursorMovement::Right),
+        "ArrowUp" | "Up" => movement(CursorMovement::Up),
+        "ArrowDown" | "Down" => movement(CursorMovement::Down),
+        "Home" if command => movement(CursorMovement::DocumentStart),
+        "End" if command => movement(CursorMovement::DocumentEnd),
+        "Home" => movement(CursorMovement::Home),
+        "End" => movement(CursorMovement::End),
+        "PageUp" => movement(CursorMovement::PageUp),
+        "PageDown" => movement(CursorMovement::PageDown),
+        _ if modifiers.modified() => return None,
+        "Backspace" => DeleteBackward,
+        "Enter" => InsertText("\n".to_string()),
+        "Tab" => Indent,
+        _ if key.len() == 1 => InsertText(key.to_string()),
+        _ => return None,
+    };
+    Some(routed)
+}
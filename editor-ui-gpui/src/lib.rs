@@ -1,5 +1,30 @@
 pub mod ai_panel;
+mod composer_review;
+mod document_tree;
+mod edit_preview;
+mod edit_replay;
 pub mod editor_view;
+mod external_tools;
+mod health_report;
+mod hierarchy;
+mod inline_thread;
+pub mod keymap;
+mod local_history;
+mod memory_panel;
+mod metrics_panel;
+mod notebook;
+mod peek;
+mod problems;
+mod pull_requests;
+mod settings_archive;
+mod source_control;
+mod table_mode;
+mod tasks;
+pub mod theme;
+mod workflows;
+mod workspace_search;
 
-pub use ai_panel::AIPanel;
+pub use ai_panel::{AIPanel, AIPanelTab};
 pub use editor_view::EditorView;
+pub use keymap::{KeyCommand, KeyContext};
+pub use theme::SyntaxTheme;
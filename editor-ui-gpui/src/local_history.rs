@@ -0,0 +1,228 @@
+//! 本地历史：列出已保存文件的快照，查看与当前内容的差异并恢复
+
+use crate::editor_view::EditorView;
+use editor_core_project::diff_lines;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::time::SystemTime;
+
+impl EditorView {
+    /// 切换本地历史面板
+    pub fn toggle_local_history(&mut self, cx: &mut Context<'_, Self>) {
+        self.show_local_history = !self.show_local_history;
+        self.history_diff = None;
+        if self.show_local_history {
+            self.load_local_history(cx);
+        }
+        cx.notify();
+    }
+
+    fn load_local_history(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(history) = self.buffer_manager.local_history().cloned() else {
+            return;
+        };
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let snapshots = history.snapshots(&path).unwrap_or_default();
+                let _ = this.update(&mut app, |view, cx| {
+                    view.history_snapshots = snapshots;
+                    view.history_diff = None;
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 对比历史版本与当前缓冲区内容
+    fn show_history_diff(&mut self, index: usize, cx: &mut Context<'_, Self>) {
+        let Some(history) = self.buffer_manager.local_history().cloned() else {
+            return;
+        };
+        let Some(snapshot) = self.history_snapshots.get(index).cloned() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let current = match buffer_manager.get_buffer(&snapshot.file_path).await {
+                    Some(handle) => handle.lock().await.get_text().await,
+                    None => String::new(),
+                };
+                let old = history.read(&snapshot).unwrap_or_default();
+                let diff = diff_lines(&old, &current);
+
+                let _ = this.update(&mut app, |view, cx| {
+                    view.history_diff = Some((index, diff));
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 将当前文件恢复到历史版本（需要再次保存）
+    fn restore_history_snapshot(&mut self, index: usize, cx: &mut Context<'_, Self>) {
+        let Some(snapshot) = self.history_snapshots.get(index).cloned() else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+
+            async move {
+                let result = buffer_manager.restore_snapshot(&snapshot).await;
+                let _ = this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(_) => view.set_status(format!(
+                            "已恢复到 {} 的版本，保存后生效",
+                            Self::format_age(snapshot.timestamp)
+                        )),
+                        Err(e) => view.set_status(format!("无法恢复历史版本: {}", e)),
+                    }
+                    view.history_diff = None;
+                    view.refresh_buffer_view(cx);
+                    cx.notify();
+                });
+
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn format_age(timestamp: SystemTime) -> String {
+        let secs = SystemTime::now()
+            .duration_since(timestamp)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        match secs {
+            0..=59 => format!("{} 秒前", secs),
+            60..=3599 => format!("{} 分钟前", secs / 60),
+            3600..=86399 => format!("{} 小时前", secs / 3600),
+            _ => format!("{} 天前", secs / 86400),
+        }
+    }
+
+    pub(crate) fn render_local_history(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut panel = div()
+            .w(px(320.0))
+            .flex()
+            .flex_col()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_color(rgb(0x9ad1ff))
+                    .text_sm()
+                    .child("Local History"),
+            );
+
+        if self.history_snapshots.is_empty() {
+            panel = panel.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_sm()
+                    .text_color(rgb(0x666666))
+                    .child("当前文件还没有保存记录"),
+            );
+        }
+
+        for (idx, snapshot) in self.history_snapshots.iter().enumerate() {
+            let is_selected = self
+                .history_diff
+                .as_ref()
+                .map(|(selected, _)| *selected == idx)
+                .unwrap_or(false);
+
+            panel = panel.child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .px_3()
+                    .py_1()
+                    .text_sm()
+                    .bg(if is_selected {
+                        rgb(0x1f1f1f)
+                    } else {
+                        rgb(0x141414)
+                    })
+                    .child(Self::format_age(snapshot.timestamp))
+                    .child(
+                        div()
+                            .flex()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .id(("history-diff", idx as u64))
+                                    .px_2()
+                                    .rounded(px(4.0))
+                                    .bg(rgb(0x3a3a3a))
+                                    .cursor_pointer()
+                                    .child("Diff")
+                                    .on_click(cx.listener(
+                                        move |view: &mut EditorView, _, _, cx| {
+                                            view.show_history_diff(idx, cx)
+                                        },
+                                    )),
+                            )
+                            .child(
+                                div()
+                                    .id(("history-restore", idx as u64))
+                                    .px_2()
+                                    .rounded(px(4.0))
+                                    .bg(rgb(0x2e7d32))
+                                    .cursor_pointer()
+                                    .child("Restore")
+                                    .on_click(cx.listener(
+                                        move |view: &mut EditorView, _, _, cx| {
+                                            view.restore_history_snapshot(idx, cx)
+                                        },
+                                    )),
+                            ),
+                    ),
+            );
+        }
+
+        if let Some((_, diff)) = &self.history_diff {
+            let lines = div()
+                .id("history-diff-view")
+                .flex_1()
+                .flex()
+                .flex_col()
+                .mt_2()
+                .p_2()
+                .border_t_1()
+                .border_color(rgb(0x2a2a2a))
+                .text_xs()
+                .overflow_scroll();
+
+            panel = panel.child(lines.children(Self::diff_rows(diff, 0x8ef1a2, 0xff8a80)));
+        }
+
+        panel
+    }
+}
@@ -0,0 +1,250 @@
+//! 内存面板：按缓冲区列出内存占用，释放空闲缓冲区的缓存
+
+use crate::editor_view::EditorView;
+use editor_core_project::BufferMemoryReport;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::time::Duration;
+
+impl EditorView {
+    /// 每分钟释放闲置缓冲区的缓存，闲置时长由配置决定
+    pub(crate) fn start_cache_eviction(&mut self, cx: &mut Context<'_, Self>) {
+        let minutes = self.config.editor.evict_idle_buffers_minutes;
+        if minutes == 0 {
+            return;
+        }
+        let buffer_manager = self.buffer_manager.clone();
+        let idle = Duration::from_secs(minutes * 60);
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                loop {
+                    app.background_executor()
+                        .timer(Duration::from_secs(60))
+                        .await;
+                    let evicted = buffer_manager.evict_idle_caches(idle).await;
+                    if evicted.is_empty() {
+                        continue;
+                    }
+                    log::info!("Evicted caches of {} idle buffers", evicted.len());
+                    this.update(&mut app, |view, cx| {
+                        if view.memory_report.is_some() {
+                            view.refresh_memory_report(cx);
+                        }
+                    })?;
+                }
+            }
+        });
+        self.cache_eviction = Some(task);
+    }
+
+    /// 切换内存诊断面板
+    pub fn toggle_memory_panel(&mut self, cx: &mut Context<'_, Self>) {
+        if self.memory_report.take().is_some() {
+            cx.notify();
+        } else {
+            self.refresh_memory_report(cx);
+        }
+    }
+
+    fn refresh_memory_report(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let report = buffer_manager.memory_report().await;
+                this.update(&mut app, |view, cx| {
+                    view.memory_report = Some(report);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 立即释放除当前缓冲区外所有已保存缓冲区的缓存
+    fn free_buffer_caches(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let evicted = buffer_manager.evict_idle_caches(Duration::ZERO).await;
+                let report = buffer_manager.memory_report().await;
+                this.update(&mut app, |view, cx| {
+                    view.set_status(format!("已释放 {} 个缓冲区的缓存", evicted.len()));
+                    view.memory_report = Some(report);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn format_bytes(bytes: usize) -> String {
+        const KB: f64 = 1024.0;
+        let bytes = bytes as f64;
+        if bytes < KB {
+            format!("{} B", bytes)
+        } else if bytes < KB * KB {
+            format!("{:.1} KB", bytes / KB)
+        } else {
+            format!("{:.1} MB", bytes / KB / KB)
+        }
+    }
+
+    pub(crate) fn render_memory(
+        &self,
+        report: &[BufferMemoryReport],
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x3a3a3a))
+                .cursor_pointer()
+                .child(label)
+        };
+        let row = |label: String, detail: String| {
+            div()
+                .flex()
+                .flex_col()
+                .px_3()
+                .py_2()
+                .border_b_1()
+                .border_color(rgb(0x1f1f1f))
+                .child(div().text_sm().text_color(rgb(0xffffff)).child(label))
+                .child(div().text_xs().text_color(rgb(0x888888)).child(detail))
+        };
+
+        let buffers_total: usize = report
+            .iter()
+            .map(|entry| entry.memory.total() + entry.saved_text_bytes)
+            .sum();
+        let index_bytes = self
+            .code_index
+            .as_ref()
+            .map_or(0, |index| index.approximate_bytes());
+        let width_bytes: usize = self
+            .line_prefix_widths
+            .iter()
+            .map(|widths| widths.len() * std::mem::size_of::<f32>())
+            .sum();
+        let clipboard_bytes: usize = self.clipboard.iter().map(|entry| entry.text().len()).sum();
+
+        let mut panel = div()
+            .id("memory-panel")
+            .w(px(360.0))
+            .flex()
+            .flex_col()
+            .overflow_scroll()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .child(div().text_color(rgb(0x9ad1ff)).child(format!(
+                        "Memory {}",
+                        Self::format_bytes(
+                            buffers_total + index_bytes + width_bytes + clipboard_bytes
+                        )
+                    )))
+                    .child(
+                        div()
+                            .flex()
+                            .gap_1()
+                            .text_xs()
+                            .child(button("memory-refresh", "刷新").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| view.refresh_memory_report(cx),
+                            )))
+                            .child(button("memory-free", "释放缓存").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| view.free_buffer_caches(cx),
+                            )))
+                            .child(button("memory-close", "关闭").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| {
+                                    view.memory_report = None;
+                                    cx.notify();
+                                },
+                            ))),
+                    ),
+            )
+            .child(row(
+                "代码索引".to_string(),
+                match &self.code_index {
+                    Some(index) => format!(
+                        "{} 个片段 · {}",
+                        index.len(),
+                        Self::format_bytes(index_bytes)
+                    ),
+                    None => "未建立".to_string(),
+                },
+            ))
+            .child(row(
+                "行宽缓存".to_string(),
+                format!(
+                    "当前缓冲区 {} 行 · {}",
+                    self.line_prefix_widths.len(),
+                    Self::format_bytes(width_bytes)
+                ),
+            ))
+            .child(row(
+                "剪贴板历史".to_string(),
+                format!(
+                    "{} 条 · {}",
+                    self.clipboard.len(),
+                    Self::format_bytes(clipboard_bytes)
+                ),
+            ));
+
+        for entry in report {
+            let name = self
+                .display_names
+                .get(&entry.path)
+                .cloned()
+                .unwrap_or_else(|| entry.path.display().to_string());
+            let memory = &entry.memory;
+            let mut detail = format!(
+                "文本 {} · rope {} · 撤销 {} · 磁盘副本 {}",
+                Self::format_bytes(memory.text_bytes),
+                Self::format_bytes(memory.rope_bytes),
+                Self::format_bytes(memory.undo_bytes),
+                Self::format_bytes(entry.saved_text_bytes),
+            );
+            if memory.edit_log_bytes > 0 {
+                detail.push_str(&format!(
+                    " · 编辑日志 {}",
+                    Self::format_bytes(memory.edit_log_bytes)
+                ));
+            }
+            detail.push_str(&format!(" · 闲置 {} 分钟", entry.idle.as_secs() / 60));
+            if entry.evicted {
+                detail.push_str(" · 缓存已释放");
+            }
+            panel = panel.child(row(
+                format!(
+                    "{} ({})",
+                    name,
+                    Self::format_bytes(memory.total() + entry.saved_text_bytes)
+                ),
+                detail,
+            ));
+        }
+
+        panel
+    }
+}
@@ -0,0 +1,174 @@
+//! 性能面板：显示本地记录的按键到渲染、语言服务器请求和 AI 请求的延迟，并可复制报告
+
+use crate::editor_view::EditorView;
+use editor_infra::MetricSummary;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::time::Duration;
+
+impl EditorView {
+    /// 切换性能诊断面板，打开时每秒刷新
+    pub fn toggle_metrics_panel(&mut self, cx: &mut Context<'_, Self>) {
+        self.show_metrics = !self.show_metrics;
+
+        if self.show_metrics {
+            let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+
+                async move {
+                    loop {
+                        app.background_executor()
+                            .timer(Duration::from_secs(1))
+                            .await;
+                        this.update(&mut app, |_, cx| cx.notify())?;
+                    }
+                }
+            });
+            self.metrics_ticker = Some(task);
+        } else {
+            self.metrics_ticker = None;
+        }
+        cx.notify();
+    }
+
+    fn metric_summary_line(summary: &MetricSummary) -> String {
+        format!(
+            "n={} · p50 {:.1} · p90 {:.1} · p99 {:.1} · max {:.1} ms",
+            summary.count, summary.p50, summary.p90, summary.p99, summary.max
+        )
+    }
+
+    /// 把所有指标的统计复制为纯文本，方便附在问题反馈里
+    fn copy_metrics_report(&mut self, cx: &mut Context<'_, Self>) {
+        let report: Vec<String> = self
+            .metrics
+            .metrics()
+            .into_iter()
+            .filter_map(|metric| {
+                let summary = self.metrics.summary(&metric)?;
+                Some(format!(
+                    "{}: {}",
+                    metric.label(),
+                    Self::metric_summary_line(&summary)
+                ))
+            })
+            .collect();
+        cx.write_to_clipboard(gpui::ClipboardItem::new_string(report.join("\n")));
+        self.set_status("已复制性能报告");
+        cx.notify();
+    }
+
+    pub(crate) fn render_metrics(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        // 每个指标的图表显示最近的样本
+        const CHART_SAMPLES: usize = 60;
+        const CHART_HEIGHT: f32 = 36.0;
+
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x3a3a3a))
+                .cursor_pointer()
+                .child(label)
+        };
+        let mut panel = div()
+            .id("metrics-panel")
+            .w(px(360.0))
+            .flex()
+            .flex_col()
+            .overflow_scroll()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .child(div().text_color(rgb(0x9ad1ff)).child("Performance"))
+                    .child(
+                        div()
+                            .flex()
+                            .gap_1()
+                            .text_xs()
+                            .child(button("metrics-copy", "Copy").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| view.copy_metrics_report(cx),
+                            )))
+                            .child(button("metrics-clear", "Clear").on_click(cx.listener(
+                                |view: &mut EditorView, _, _, cx| {
+                                    view.metrics.clear();
+                                    cx.notify();
+                                },
+                            ))),
+                    ),
+            );
+
+        let metrics = self.metrics.metrics();
+        if metrics.is_empty() {
+            panel = panel.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_sm()
+                    .text_color(rgb(0x666666))
+                    .child("还没有记录到数据"),
+            );
+        }
+
+        for metric in metrics {
+            let samples = self.metrics.samples(&metric);
+            let Some(summary) = MetricSummary::from_samples(&samples) else {
+                continue;
+            };
+            let recent = &samples[samples.len().saturating_sub(CHART_SAMPLES)..];
+            let scale = recent.iter().copied().fold(f64::EPSILON, f64::max);
+            let mut chart = div()
+                .h(px(CHART_HEIGHT))
+                .mt_1()
+                .flex()
+                .items_end()
+                .gap(px(1.0));
+            for sample in recent {
+                let height = (sample / scale * CHART_HEIGHT as f64).max(1.0) as f32;
+                let color = if *sample >= summary.p90 {
+                    rgb(0xe57373)
+                } else {
+                    rgb(0x42a5f5)
+                };
+                chart = chart.child(div().w(px(4.0)).h(px(height)).bg(color));
+            }
+
+            panel = panel.child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x1f1f1f))
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(rgb(0xffffff))
+                            .child(metric.label()),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x888888))
+                            .child(Self::metric_summary_line(&summary)),
+                    )
+                    .child(chart),
+            );
+        }
+
+        panel
+    }
+}
@@ -0,0 +1,740 @@
+//! Jupyter 笔记本的单元格视图：编辑、运行单元格，并让 AI 解释或修复单元格
+
+use crate::editor_view::EditorView;
+use editor_ai::inline_thread::extract_code;
+use editor_ai::AIAction;
+use editor_core_project::{CellKind, CellOutput, KernelInterrupt, Notebook, PythonKernel};
+use gpui::{div, prelude::*, px, rgb, AsyncApp, Context, WeakEntity};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// 以单元格视图打开的 Jupyter 笔记本
+pub(crate) struct NotebookSession {
+    path: PathBuf,
+    notebook: Notebook,
+    selected: usize,
+    /// 正在编辑源码的单元格
+    editing: Option<usize>,
+    /// 第一次运行单元格时启动，关闭笔记本时随之结束
+    kernel: Option<Arc<tokio::sync::Mutex<PythonKernel>>>,
+    /// 中断内核中正在运行的单元格，内核启动后才有
+    interrupt: Option<KernelInterrupt>,
+    /// 正在运行的单元格；运行期间不能增删或移动单元格
+    running: Option<usize>,
+    /// 各单元格最近一次的 AI 回复，不写入文件
+    ai_replies: HashMap<usize, CellAiReply>,
+    dirty: bool,
+    /// 有未保存修改时第一次按 Esc 只提示，再按一次才关闭
+    close_requested: bool,
+}
+
+struct CellAiReply {
+    /// 修复请求的回复，其中的代码可以替换单元格
+    fix: bool,
+    text: String,
+    pending: bool,
+}
+
+impl NotebookSession {
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+impl EditorView {
+    /// 以单元格视图打开笔记本；无法解析时按文本打开
+    pub fn open_notebook(&mut self, path: PathBuf, cx: &mut Context<'_, Self>) {
+        let notebook = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Notebook::parse(&json).map_err(|e| e.to_string()));
+        match notebook {
+            Ok(notebook) => {
+                self.notebook = Some(NotebookSession {
+                    path,
+                    notebook,
+                    selected: 0,
+                    editing: None,
+                    kernel: None,
+                    interrupt: None,
+                    running: None,
+                    ai_replies: HashMap::new(),
+                    dirty: false,
+                    close_requested: false,
+                });
+                self.set_status("Shift+Enter 运行单元格，Enter 编辑，Cmd+S 保存，Esc 关闭笔记本");
+                cx.notify();
+            }
+            Err(e) => {
+                self.set_status(format!("无法解析笔记本，按文本打开: {}", e));
+                self.open_text_file(&path, cx);
+            }
+        }
+    }
+
+    /// 关闭笔记本视图，内核随之结束。有未保存的修改时先提示
+    fn close_notebook(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        if session.dirty && !session.close_requested {
+            session.close_requested = true;
+            self.set_status("笔记本有未保存的修改，Cmd+S 保存，或再按 Esc 放弃修改并关闭");
+        } else {
+            self.notebook = None;
+            self.set_status("已关闭笔记本");
+        }
+        cx.notify();
+    }
+
+    fn save_notebook(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        match std::fs::write(&session.path, session.notebook.to_json()) {
+            Ok(()) => {
+                session.dirty = false;
+                session.close_requested = false;
+                self.set_status("笔记本已保存");
+            }
+            Err(e) => self.set_status(format!("保存笔记本失败: {}", e)),
+        }
+        cx.notify();
+    }
+
+    /// 关闭单元格视图，按文本打开笔记本文件
+    fn open_notebook_source(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(session) = &self.notebook else {
+            return;
+        };
+        if session.dirty {
+            self.set_status("先保存笔记本再查看源码");
+            cx.notify();
+            return;
+        }
+        let path = session.path.clone();
+        self.notebook = None;
+        self.open_text_file(&path, cx);
+    }
+
+    /// 打开笔记本时的按键，返回是否已处理。编辑单元格时按键都交给单元格，
+    /// 其余时候 Esc 关闭、Shift+Enter 运行、上下键选择单元格
+    pub(crate) fn handle_notebook_key(
+        &mut self,
+        key: &str,
+        shift: bool,
+        command: bool,
+        cx: &mut Context<'_, Self>,
+    ) -> bool {
+        let Some(session) = &mut self.notebook else {
+            return false;
+        };
+        let (selected, count) = (session.selected, session.notebook.cells.len());
+        if let Some(idx) = session.editing {
+            self.edit_notebook_cell(idx, key, shift, cx);
+            return true;
+        }
+        match key {
+            "Escape" => self.close_notebook(cx),
+            "Enter" if shift => self.run_notebook_cell(selected, true, cx),
+            "s" if command => self.save_notebook(cx),
+            _ if command => return false,
+            "Enter" => session.editing = Some(selected),
+            "ArrowUp" | "Up" => session.selected = selected.saturating_sub(1),
+            "ArrowDown" | "Down" => session.selected = (selected + 1).min(count - 1),
+            _ => {}
+        }
+        cx.notify();
+        true
+    }
+
+    /// 在单元格视图中编辑源码时的按键：只支持在末尾输入和删除
+    fn edit_notebook_cell(
+        &mut self,
+        idx: usize,
+        key: &str,
+        shift: bool,
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        match key {
+            "Escape" => session.editing = None,
+            "Enter" if shift => {
+                session.editing = None;
+                self.run_notebook_cell(idx, true, cx);
+                return;
+            }
+            _ => {
+                let Some(cell) = session.notebook.cells.get_mut(idx) else {
+                    return;
+                };
+                match key {
+                    "Backspace" => {
+                        cell.source.pop();
+                    }
+                    "Enter" => cell.source.push('\n'),
+                    "Tab" => cell.source.push_str("    "),
+                    "Space" | " " => cell.source.push(' '),
+                    _ if key.chars().count() == 1 => cell.source.push_str(key),
+                    _ => return,
+                }
+                session.dirty = true;
+                session.close_requested = false;
+            }
+        }
+        cx.notify();
+    }
+
+    /// 在内核中运行一个代码单元格，第一次运行时启动内核。`advance` 为真时随后选中下一个单元格
+    fn run_notebook_cell(&mut self, idx: usize, advance: bool, cx: &mut Context<'_, Self>) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        let Some(cell) = session.notebook.cells.get(idx) else {
+            return;
+        };
+        if advance {
+            session.selected = (idx + 1).min(session.notebook.cells.len() - 1);
+        }
+        if cell.kind != CellKind::Code {
+            cx.notify();
+            return;
+        }
+        if session.running.is_some() {
+            self.set_status("上一个单元格还在运行");
+            cx.notify();
+            return;
+        }
+        session.running = Some(idx);
+        let code = cell.source.clone();
+        let kernel = session.kernel.clone();
+        let path = session.path.clone();
+        let python = self.config.editor.notebook_python.clone();
+        self.set_status(format!("正在运行第 {} 个单元格…", idx + 1));
+        cx.notify();
+
+        // 内核进程依赖 tokio 运行时
+        let (started, interrupt) = futures::channel::oneshot::channel();
+        let task = self.task_executor.spawn(async move {
+            let kernel = match kernel {
+                Some(kernel) => kernel,
+                None => Arc::new(tokio::sync::Mutex::new(PythonKernel::start(&python).await?)),
+            };
+            let mut running = kernel.lock().await;
+            let _ = started.send(running.interrupt_handle());
+            let execution = running.execute(&code).await?;
+            drop(running);
+            std::io::Result::Ok((kernel, execution))
+        });
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                // 内核无法启动时发送端被丢弃，不会收到句柄
+                if let Ok(interrupt) = interrupt.await {
+                    this.update(&mut app, |view, _| {
+                        if let Some(session) = view.notebook.as_mut().filter(|s| s.path == path) {
+                            session.interrupt = Some(interrupt);
+                        }
+                    })?;
+                }
+                let result = task.await;
+                this.update(&mut app, |view, cx| {
+                    let Some(session) = view.notebook.as_mut().filter(|s| s.path == path) else {
+                        return;
+                    };
+                    session.running = None;
+                    let message = match result {
+                        Ok(Ok((kernel, execution))) => {
+                            session.kernel = Some(kernel);
+                            session.dirty = true;
+                            let failed = execution.failed;
+                            if let Some(cell) = session.notebook.cells.get_mut(idx) {
+                                cell.outputs = execution.outputs;
+                                cell.execution_count = execution.execution_count;
+                            }
+                            if failed {
+                                "单元格运行出错，可以让 AI 修复".to_string()
+                            } else {
+                                format!("第 {} 个单元格运行完成", idx + 1)
+                            }
+                        }
+                        Ok(Err(e)) => {
+                            // 内核已退出或无法启动，下次运行时重新启动
+                            session.kernel = None;
+                            session.interrupt = None;
+                            format!("内核出错: {}", e)
+                        }
+                        Err(e) => format!("运行单元格失败: {}", e),
+                    };
+                    view.set_status(message);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 让正在运行的单元格以 KeyboardInterrupt 结束，内核和其中的变量保留
+    fn interrupt_notebook_kernel(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(session) = &self.notebook else {
+            return;
+        };
+        let message = match (session.running, session.interrupt) {
+            (None, _) => "没有正在运行的单元格".to_string(),
+            (Some(_), None) => "内核还在启动，稍后再中断".to_string(),
+            (Some(_), Some(interrupt)) => match interrupt.interrupt() {
+                Ok(()) => "正在中断单元格…".to_string(),
+                Err(e) => format!("无法中断内核: {}", e),
+            },
+        };
+        self.set_status(message);
+        cx.notify();
+    }
+
+    /// 结束内核，下次运行单元格时从空的命名空间重新开始
+    fn restart_notebook_kernel(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        if session.running.is_some() {
+            self.set_status("单元格运行中，无法重启内核");
+        } else {
+            session.kernel = None;
+            session.interrupt = None;
+            for cell in &mut session.notebook.cells {
+                cell.execution_count = None;
+            }
+            self.set_status("内核已重启");
+        }
+        cx.notify();
+    }
+
+    /// 增删或移动单元格。运行期间单元格的位置不能变
+    fn edit_notebook_structure(
+        &mut self,
+        edit: impl FnOnce(&mut NotebookSession),
+        cx: &mut Context<'_, Self>,
+    ) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        if session.running.is_some() {
+            self.set_status("单元格运行中，稍后再调整");
+            cx.notify();
+            return;
+        }
+        edit(session);
+        session.ai_replies.clear();
+        session.editing = None;
+        session.dirty = true;
+        session.close_requested = false;
+        if session.notebook.cells.is_empty() {
+            session.notebook.insert_cell(0, CellKind::Code);
+        }
+        session.selected = session.selected.min(session.notebook.cells.len() - 1);
+        cx.notify();
+    }
+
+    /// 让 AI 解释单元格，或根据其中的错误输出修复它
+    fn ask_ai_about_cell(&mut self, idx: usize, fix: bool, cx: &mut Context<'_, Self>) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        let Some(cell) = session.notebook.cells.get(idx) else {
+            return;
+        };
+        let language = session.notebook.language();
+        let action = if fix {
+            AIAction::FixBugs {
+                code: cell.source.clone(),
+                language,
+                error_message: cell.error_text(),
+            }
+        } else {
+            AIAction::ExplainCode {
+                code: cell.source.clone(),
+                language,
+            }
+        };
+        session.ai_replies.insert(
+            idx,
+            CellAiReply {
+                fix,
+                text: String::new(),
+                pending: true,
+            },
+        );
+        let path = session.path.clone();
+        let ai_engine = self.ai_engine.clone();
+        let ai_executor = self.ai_executor.clone();
+        self.set_status("AI 正在回复…");
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let reply = ai_executor
+                    .spawn(async move { ai_engine.run_action(&action, None).await })
+                    .await?;
+                this.update(&mut app, |view, cx| {
+                    let Some(session) = view.notebook.as_mut().filter(|s| s.path == path) else {
+                        return;
+                    };
+                    match reply {
+                        Ok(text) => {
+                            if let Some(reply) = session.ai_replies.get_mut(&idx) {
+                                reply.text = text;
+                                reply.pending = false;
+                            }
+                            view.set_status("AI 已回复");
+                        }
+                        Err(e) => {
+                            session.ai_replies.remove(&idx);
+                            view.set_status(format!("AI 回复失败: {}", e));
+                        }
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 用 AI 修复回复中的代码替换单元格源码
+    fn apply_cell_fix(&mut self, idx: usize, cx: &mut Context<'_, Self>) {
+        let Some(session) = &mut self.notebook else {
+            return;
+        };
+        let Some(reply) = session.ai_replies.remove(&idx) else {
+            return;
+        };
+        if let Some(cell) = session.notebook.cells.get_mut(idx) {
+            cell.source = extract_code(&reply.text);
+            session.dirty = true;
+            session.close_requested = false;
+            self.set_status("已应用修复，Shift+Enter 重新运行");
+        }
+        cx.notify();
+    }
+
+    /// 单元格视图：每个单元格的源码、输出和 AI 回复，取代文本编辑区
+    pub(crate) fn render_notebook(
+        &self,
+        session: &NotebookSession,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let button = |id: (&'static str, usize), label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x2a2a2a))
+                .cursor_pointer()
+                .text_xs()
+                .child(label)
+        };
+        let lines = |text: &str, color| {
+            let mut block = div().flex().flex_col().text_sm().text_color(color);
+            for line in text.trim_end_matches('\n').lines() {
+                block = block.child(div().whitespace_nowrap().child(if line.is_empty() {
+                    " ".to_string()
+                } else {
+                    line.to_string()
+                }));
+            }
+            block
+        };
+
+        let name = session
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let kernel_state = match (&session.kernel, session.running) {
+            (_, Some(_)) => "运行中",
+            (Some(_), None) => "空闲",
+            (None, None) => "未启动",
+        };
+        let toolbar = div()
+            .flex()
+            .items_center()
+            .justify_between()
+            .text_sm()
+            .text_color(rgb(0xaaaaaa))
+            .child(format!(
+                "{}{} · {} · 内核{}",
+                name,
+                if session.dirty { " ●" } else { "" },
+                session.notebook.language(),
+                kernel_state
+            ))
+            .child(
+                div()
+                    .flex()
+                    .gap_1()
+                    .child(button(("notebook-save", 0), "保存").on_click(
+                        cx.listener(|view: &mut EditorView, _, _, cx| view.save_notebook(cx)),
+                    ))
+                    .child(
+                        button(("notebook-add-code", 0), "+ 代码").on_click(cx.listener(
+                            |view: &mut EditorView, _, _, cx| {
+                                view.edit_notebook_structure(
+                                    |session| {
+                                        session.selected = session
+                                            .notebook
+                                            .insert_cell(session.selected + 1, CellKind::Code);
+                                    },
+                                    cx,
+                                )
+                            },
+                        )),
+                    )
+                    .child(button(("notebook-add-markdown", 0), "+ Markdown").on_click(
+                        cx.listener(|view: &mut EditorView, _, _, cx| {
+                            view.edit_notebook_structure(
+                                |session| {
+                                    session.selected = session
+                                        .notebook
+                                        .insert_cell(session.selected + 1, CellKind::Markdown);
+                                },
+                                cx,
+                            )
+                        }),
+                    ))
+                    .child(
+                        button(("notebook-interrupt", 0), "中断").on_click(cx.listener(
+                            |view: &mut EditorView, _, _, cx| view.interrupt_notebook_kernel(cx),
+                        )),
+                    )
+                    .child(
+                        button(("notebook-restart", 0), "重启内核").on_click(cx.listener(
+                            |view: &mut EditorView, _, _, cx| view.restart_notebook_kernel(cx),
+                        )),
+                    )
+                    .child(button(("notebook-source", 0), "源码").on_click(
+                        cx.listener(|view: &mut EditorView, _, _, cx| {
+                            view.open_notebook_source(cx)
+                        }),
+                    ))
+                    .child(button(("notebook-close", 0), "关闭").on_click(
+                        cx.listener(|view: &mut EditorView, _, _, cx| view.close_notebook(cx)),
+                    )),
+            );
+
+        let mut cells = div()
+            .id("notebook-cells")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .overflow_y_scroll();
+        for (idx, cell) in session.notebook.cells.iter().enumerate() {
+            let selected = idx == session.selected;
+            let editing = session.editing == Some(idx);
+            let label = match (cell.kind, cell.execution_count) {
+                (CellKind::Code, _) if session.running == Some(idx) => "[*]".to_string(),
+                (CellKind::Code, Some(count)) => format!("[{}]", count),
+                (CellKind::Code, None) => "[ ]".to_string(),
+                (CellKind::Markdown, _) => "Markdown".to_string(),
+                (CellKind::Raw, _) => "Raw".to_string(),
+            };
+
+            let mut actions = div().flex().gap_1();
+            if cell.kind == CellKind::Code {
+                actions = actions
+                    .child(button(("notebook-run", idx), "运行").on_click(cx.listener(
+                        move |view: &mut EditorView, _, _, cx| {
+                            view.run_notebook_cell(idx, false, cx)
+                        },
+                    )))
+                    .child(
+                        button(("notebook-explain", idx), "解释").on_click(cx.listener(
+                            move |view: &mut EditorView, _, _, cx| {
+                                view.ask_ai_about_cell(idx, false, cx)
+                            },
+                        )),
+                    )
+                    .child(button(("notebook-fix", idx), "修复").on_click(cx.listener(
+                        move |view: &mut EditorView, _, _, cx| {
+                            view.ask_ai_about_cell(idx, true, cx)
+                        },
+                    )));
+            }
+            actions = actions
+                .child(button(("notebook-up", idx), "↑").on_click(cx.listener(
+                    move |view: &mut EditorView, _, _, cx| {
+                        view.edit_notebook_structure(
+                            |session| {
+                                if session.notebook.move_cell(idx, true) {
+                                    session.selected = idx - 1;
+                                }
+                            },
+                            cx,
+                        )
+                    },
+                )))
+                .child(button(("notebook-down", idx), "↓").on_click(cx.listener(
+                    move |view: &mut EditorView, _, _, cx| {
+                        view.edit_notebook_structure(
+                            |session| {
+                                if session.notebook.move_cell(idx, false) {
+                                    session.selected = idx + 1;
+                                }
+                            },
+                            cx,
+                        )
+                    },
+                )))
+                .child(
+                    button(("notebook-delete", idx), "删除").on_click(cx.listener(
+                        move |view: &mut EditorView, _, _, cx| {
+                            view.edit_notebook_structure(
+                                |session| {
+                                    session.notebook.remove_cell(idx);
+                                },
+                                cx,
+                            )
+                        },
+                    )),
+                );
+
+            let source = if editing {
+                format!("{}▏", cell.source)
+            } else if cell.source.is_empty() {
+                " ".to_string()
+            } else {
+                cell.source.clone()
+            };
+            let mut block = div()
+                .id(("notebook-cell", idx))
+                .flex()
+                .flex_col()
+                .gap_1()
+                .p_2()
+                .rounded(px(6.0))
+                .border_1()
+                .border_color(if editing {
+                    rgb(0x4c8dff)
+                } else if selected {
+                    rgb(0x3a5a80)
+                } else {
+                    rgb(0x2a2a2a)
+                })
+                .bg(rgb(0x141414))
+                .on_click(cx.listener(
+                    move |view: &mut EditorView, event: &gpui::ClickEvent, _, cx| {
+                        if let Some(session) = &mut view.notebook {
+                            session.selected = idx;
+                            if event.click_count() >= 2 {
+                                session.editing = Some(idx);
+                            }
+                        }
+                        cx.notify();
+                    },
+                ))
+                .child(
+                    div()
+                        .flex()
+                        .items_center()
+                        .justify_between()
+                        .child(div().text_xs().text_color(rgb(0x888888)).child(label))
+                        .child(actions),
+                )
+                .child(
+                    div()
+                        .p_2()
+                        .rounded(px(4.0))
+                        .bg(if cell.kind == CellKind::Code {
+                            rgb(0x0f0f0f)
+                        } else {
+                            rgb(0x141414)
+                        })
+                        .child(lines(
+                            &source,
+                            if cell.kind == CellKind::Code {
+                                rgb(0xdddddd)
+                            } else {
+                                rgb(0xbbbbbb)
+                            },
+                        )),
+                );
+
+            for output in &cell.outputs {
+                if let Some((mime, bytes)) = output.image() {
+                    let format = if mime == "image/png" {
+                        gpui::ImageFormat::Png
+                    } else {
+                        gpui::ImageFormat::Jpeg
+                    };
+                    block = block.child(
+                        gpui::img(Arc::new(gpui::Image::from_bytes(format, bytes)))
+                            .max_w(px(640.0))
+                            .max_h(px(480.0)),
+                    );
+                } else if let Some(text) = output.text() {
+                    let color = match output {
+                        CellOutput::Error { .. } => rgb(0xff8a8a),
+                        CellOutput::Stream { name, .. } if name == "stderr" => rgb(0xffc777),
+                        _ => rgb(0xaaaaaa),
+                    };
+                    block = block.child(div().px_2().child(lines(&text, color)));
+                }
+            }
+
+            if let Some(reply) = session.ai_replies.get(&idx) {
+                let mut note = div()
+                    .flex()
+                    .flex_col()
+                    .gap_1()
+                    .p_2()
+                    .rounded(px(4.0))
+                    .bg(rgb(0x12223a));
+                if reply.pending {
+                    note = note.child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x888888))
+                            .child("AI 正在回复…"),
+                    );
+                } else {
+                    note = note.child(lines(&reply.text, rgb(0xcfe3ff)));
+                    let mut note_actions = div().flex().gap_1();
+                    if reply.fix {
+                        note_actions = note_actions.child(
+                            button(("notebook-apply-fix", idx), "应用修复").on_click(cx.listener(
+                                move |view: &mut EditorView, _, _, cx| view.apply_cell_fix(idx, cx),
+                            )),
+                        );
+                    }
+                    note = note.child(note_actions.child(
+                        button(("notebook-dismiss-ai", idx), "关闭").on_click(cx.listener(
+                            move |view: &mut EditorView, _, _, cx| {
+                                if let Some(session) = &mut view.notebook {
+                                    session.ai_replies.remove(&idx);
+                                }
+                                cx.notify();
+                            },
+                        )),
+                    ));
+                }
+                block = block.child(note);
+            }
+            cells = cells.child(block);
+        }
+
+        div()
+            .flex_1()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .bg(rgb(0x0f0f0f))
+            .p_4()
+            .child(toolbar)
+            .child(cells)
+    }
+}
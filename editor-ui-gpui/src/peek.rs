@@ -0,0 +1,287 @@
+//! 行内预览：在当前行下方嵌入定义或引用所在的代码，可在多个位置间切换
+
+use crate::editor_view::EditorView;
+use crate::keymap::PeekKind;
+use editor_lsp::{uri_to_path, Position};
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, MouseButton, MouseDownEvent,
+    StatefulInteractiveElement, WeakEntity,
+};
+use std::path::PathBuf;
+
+/// 行内预览区域的固定高度，点击定位时需要跳过这段高度
+pub(crate) const PEEK_HEIGHT: f32 = 220.0;
+
+/// 嵌在当前行下方的定义/引用预览，有独立的滚动区域
+pub(crate) struct PeekView {
+    kind: PeekKind,
+    pub(crate) source: PathBuf,
+    pub(crate) anchor_line: usize,
+    locations: Vec<(PathBuf, usize, usize)>,
+    selected: usize,
+    lines: Vec<String>,
+    scroll_handle: gpui::ScrollHandle,
+}
+
+impl EditorView {
+    /// 向语言服务器查询光标处符号的定义或引用，在当前行下方预览
+    pub(crate) fn peek_at_cursor(&mut self, kind: PeekKind, cx: &mut Context<'_, Self>) {
+        let Some(source) = self.current_file_path.clone() else {
+            return;
+        };
+        let Some(cursor) = self.selection.map(|selection| selection.active) else {
+            return;
+        };
+        let language = self.current_file_language();
+        let uri = self.open_file_uri(&source);
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let snapshot = buffer_manager.file_snapshot(&source).await?;
+                let position = Position::from_cursor(snapshot.rope(), cursor);
+                let request = executor.spawn(async move {
+                    match kind {
+                        PeekKind::Definition => {
+                            manager.request_definition(&language, &uri, position).await
+                        }
+                        PeekKind::References => {
+                            manager.request_references(&language, &uri, position).await
+                        }
+                    }
+                });
+                let result = request.await.map_err(std::io::Error::other).and_then(|r| r);
+                let result = match result {
+                    Ok(found) => {
+                        let mut locations = Vec::new();
+                        for location in found {
+                            let Some(path) = uri_to_path(&location.uri) else {
+                                continue;
+                            };
+                            let start = match buffer_manager.file_snapshot(&path).await {
+                                Ok(snapshot) => location.range.start.to_cursor(snapshot.rope()),
+                                Err(_) => Self::lsp_cursor(&location.range.start),
+                            };
+                            locations.push((path, start.line, start.column));
+                        }
+                        Ok(locations)
+                    }
+                    Err(e) => Err(e),
+                };
+
+                this.update(&mut app, |view, cx| {
+                    let locations: Vec<(PathBuf, usize, usize)> = match result {
+                        Ok(locations) => locations,
+                        Err(e) => {
+                            view.set_status(format!("查询{}失败: {}", kind.label(), e));
+                            return;
+                        }
+                    };
+                    if locations.is_empty() {
+                        view.set_status(format!("未找到{}", kind.label()));
+                        return;
+                    }
+                    view.peek = Some(PeekView {
+                        kind,
+                        source,
+                        anchor_line: cursor.line,
+                        locations,
+                        selected: 0,
+                        lines: Vec::new(),
+                        scroll_handle: gpui::ScrollHandle::new(),
+                    });
+                    view.load_peek_location(cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 读取预览中选中位置所在文件；已打开的文件取缓冲区快照，包含未保存的修改
+    fn load_peek_location(&mut self, cx: &mut Context<'_, Self>) {
+        let Some((path, line, _)) = self
+            .peek
+            .as_ref()
+            .and_then(|peek| peek.locations.get(peek.selected).cloned())
+        else {
+            return;
+        };
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let snapshot = match buffer_manager.get_buffer(&path).await {
+                    Some(handle) => Some(handle.lock().await.snapshot().await),
+                    None => {
+                        let read_path = path.clone();
+                        let content = app
+                            .background_executor()
+                            .spawn(async move { std::fs::read_to_string(read_path) })
+                            .await;
+                        match content {
+                            Ok(content) => Some(
+                                editor_core_text::Buffer::from_text(&content)
+                                    .snapshot()
+                                    .await,
+                            ),
+                            Err(e) => {
+                                log::warn!("Failed to read {}: {}", path.display(), e);
+                                None
+                            }
+                        }
+                    }
+                };
+                let lines: Vec<String> = snapshot
+                    .map(|snapshot| {
+                        (0..snapshot.line_count())
+                            .filter_map(|idx| snapshot.line(idx))
+                            .map(|line| line.trim_end_matches(['\n', '\r']).to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                this.update(&mut app, |view, cx| {
+                    let line_height = view.line_height();
+                    if let Some(peek) = view.peek.as_mut() {
+                        peek.lines = lines;
+                        // 目标行上方留几行上下文
+                        let top = line.saturating_sub(3) as f32 * line_height;
+                        peek.scroll_handle
+                            .set_offset(gpui::point(px(0.0), px(-top)));
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn cycle_peek(&mut self, backwards: bool, cx: &mut Context<'_, Self>) {
+        let Some(peek) = self.peek.as_mut() else {
+            return;
+        };
+        let count = peek.locations.len();
+        peek.selected = if backwards {
+            (peek.selected + count - 1) % count
+        } else {
+            (peek.selected + 1) % count
+        };
+        self.load_peek_location(cx);
+    }
+
+    pub(crate) fn open_peek_location(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(peek) = self.peek.take() else {
+            return;
+        };
+        if let Some((path, line, column)) = peek.locations.get(peek.selected).cloned() {
+            self.open_file_at(&path, line, column, cx);
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn close_peek(&mut self, cx: &mut Context<'_, Self>) {
+        self.peek = None;
+        cx.notify();
+    }
+
+    pub(crate) fn render_peek(
+        &self,
+        peek: &PeekView,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let (path, target_line, _) = peek.locations[peek.selected].clone();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let digits = peek.lines.len().max(1).to_string().len();
+
+        let mut body = div()
+            .id("peek-scroll")
+            .flex_1()
+            .overflow_scroll()
+            .track_scroll(&peek.scroll_handle)
+            .flex()
+            .flex_col();
+        for (idx, line) in peek.lines.iter().enumerate() {
+            body = body.child(
+                div()
+                    .flex()
+                    .gap_3()
+                    .px_2()
+                    .py_1()
+                    .whitespace_nowrap()
+                    .bg(if idx == target_line {
+                        rgb(0x24334e)
+                    } else {
+                        rgb(0x0d1522)
+                    })
+                    .child(div().text_color(rgb(0x5a5a5a)).text_sm().child(format!(
+                        "{:width$}",
+                        idx + 1,
+                        width = digits
+                    )))
+                    .child(div().text_color(rgb(0xdddddd)).child(line.clone())),
+            );
+        }
+
+        div()
+            .h(px(PEEK_HEIGHT))
+            .mx_2()
+            .flex()
+            .flex_col()
+            .rounded(px(6.0))
+            .border_1()
+            .border_color(rgb(0x3b4f6b))
+            .bg(rgb(0x0d1522))
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(|_view: &mut EditorView, _: &MouseDownEvent, _, cx| {
+                    cx.stop_propagation();
+                }),
+            )
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .items_center()
+                    .px_3()
+                    .py_1()
+                    .border_b_1()
+                    .border_color(rgb(0x3b4f6b))
+                    .text_sm()
+                    .child(div().text_color(rgb(0x9ecbff)).child(format!(
+                        "{} · {}:{} ({}/{})",
+                        peek.kind.label(),
+                        name,
+                        target_line + 1,
+                        peek.selected + 1,
+                        peek.locations.len()
+                    )))
+                    .child(
+                        div()
+                            .flex()
+                            .gap_3()
+                            .text_color(rgb(0x888888))
+                            .child("Enter 打开 · F4 下一个 · Esc 关闭")
+                            .child(
+                                div()
+                                    .id("peek-close")
+                                    .cursor_pointer()
+                                    .text_color(rgb(0xcccccc))
+                                    .child("×")
+                                    .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                        view.close_peek(cx);
+                                    })),
+                            ),
+                    ),
+            )
+            .child(body)
+    }
+}
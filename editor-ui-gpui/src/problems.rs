@@ -0,0 +1,460 @@
+//! 诊断导航与问题面板：在诊断之间跳转、按严重程度和来源筛选、复制诊断
+
+use crate::editor_view::EditorView;
+use editor_core_project::BufferManager;
+use editor_infra::TaskExecutor;
+use editor_lsp::{uri_to_path, Diagnostic, DiagnosticSeverity, LspServerManager};
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// 工作区中的一条诊断，列已换算为字符
+#[derive(Debug, Clone)]
+struct Problem {
+    path: PathBuf,
+    start: editor_core_text::Cursor,
+    severity: DiagnosticSeverity,
+    source: Option<String>,
+    code: Option<String>,
+    message: String,
+}
+
+impl Problem {
+    fn from_diagnostic(
+        path: PathBuf,
+        start: editor_core_text::Cursor,
+        diagnostic: &Diagnostic,
+    ) -> Self {
+        Self {
+            path,
+            start,
+            severity: diagnostic.severity_or_error(),
+            source: diagnostic.source.clone(),
+            code: diagnostic.code.as_ref().map(|code| match code {
+                serde_json::Value::String(code) => code.clone(),
+                code => code.to_string(),
+            }),
+            message: diagnostic.message.clone(),
+        }
+    }
+
+    /// 复制到剪贴板的文本，形如 `src/main.rs:3:5: 错误 [rustc E0308] message`
+    fn summary(&self) -> String {
+        let origin: Vec<&str> = self
+            .source
+            .iter()
+            .chain(self.code.iter())
+            .map(String::as_str)
+            .collect();
+        let origin = if origin.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", origin.join(" "))
+        };
+        format!(
+            "{}:{}:{}: {}{} {}",
+            self.path.display(),
+            self.start.line + 1,
+            self.start.column + 1,
+            EditorView::severity_label(self.severity),
+            origin,
+            self.message
+        )
+    }
+
+    /// 按文件、行、列排序的键，F8 按这个顺序跳转
+    fn order_key(&self) -> (&Path, usize, usize) {
+        (&self.path, self.start.line, self.start.column)
+    }
+}
+
+/// 问题面板：工作区的全部诊断，可按严重级别和来源筛选
+pub(crate) struct ProblemsPanel {
+    problems: Vec<Problem>,
+    hidden_severities: HashSet<DiagnosticSeverity>,
+    source: Option<String>,
+}
+
+impl ProblemsPanel {
+    fn visible(&self) -> impl Iterator<Item = &Problem> {
+        self.problems.iter().filter(|problem| {
+            !self.hidden_severities.contains(&problem.severity)
+                && (self.source.is_none() || problem.source == self.source)
+        })
+    }
+
+    fn count(&self, severity: DiagnosticSeverity) -> usize {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity == severity)
+            .count()
+    }
+
+    /// 依次切换到下一个来源，最后回到全部
+    fn cycle_source(&mut self) {
+        let mut sources: Vec<&String> = self
+            .problems
+            .iter()
+            .filter_map(|problem| problem.source.as_ref())
+            .collect();
+        sources.sort();
+        sources.dedup();
+        let next = match &self.source {
+            None => sources.first().copied(),
+            Some(current) => sources
+                .iter()
+                .position(|source| *source == current)
+                .and_then(|idx| sources.get(idx + 1).copied()),
+        };
+        self.source = next.cloned();
+    }
+}
+
+impl EditorView {
+    /// 读取工作区全部诊断，按文件、行、列排序
+    async fn load_problems(
+        buffer_manager: BufferManager,
+        manager: Arc<LspServerManager>,
+        executor: TaskExecutor,
+    ) -> anyhow::Result<Vec<Problem>> {
+        let all = executor
+            .spawn(async move { manager.all_diagnostics().await })
+            .await?;
+        let mut problems = Vec::new();
+        for (uri, diagnostics) in all {
+            let Some(path) = uri_to_path(&uri) else {
+                continue;
+            };
+            let snapshot = buffer_manager.file_snapshot(&path).await.ok();
+            for diagnostic in &diagnostics {
+                let start = match &snapshot {
+                    Some(snapshot) => diagnostic.range.start.to_cursor(snapshot.rope()),
+                    None => Self::lsp_cursor(&diagnostic.range.start),
+                };
+                problems.push(Problem::from_diagnostic(path.clone(), start, diagnostic));
+            }
+        }
+        problems.sort_by(|a, b| a.order_key().cmp(&b.order_key()));
+        Ok(problems)
+    }
+
+    /// 打开或关闭问题面板
+    pub fn toggle_problems(&mut self, cx: &mut Context<'_, Self>) {
+        if self.problems.take().is_none() {
+            self.problems = Some(ProblemsPanel {
+                problems: Vec::new(),
+                hidden_severities: HashSet::new(),
+                source: None,
+            });
+            self.reload_problems(cx);
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn reload_problems(&mut self, cx: &mut Context<'_, Self>) {
+        let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let problems = Self::load_problems(buffer_manager, manager, executor).await?;
+                this.update(&mut app, |view, cx| {
+                    if let Some(panel) = view.problems.as_mut() {
+                        panel.problems = problems;
+                        cx.notify();
+                    }
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 跳到光标之后（或之前）的下一条诊断；当前文件没有时转到工作区中的下一个文件，
+    /// 到头后回绕
+    pub fn jump_to_diagnostic(&mut self, forward: bool, cx: &mut Context<'_, Self>) {
+        let current = self.current_file_path.clone().unwrap_or_default();
+        let cursor = self
+            .selection
+            .map(|selection| selection.active)
+            .unwrap_or(editor_core_text::Cursor::zero());
+        let buffer_manager = self.buffer_manager.clone();
+        let manager = self.lsp_manager.clone();
+        let executor = self.lsp_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let problems = Self::load_problems(buffer_manager, manager, executor).await?;
+                let here = (current.as_path(), cursor.line, cursor.column);
+                let target = if forward {
+                    problems
+                        .iter()
+                        .find(|problem| problem.order_key() > here)
+                        .or_else(|| problems.first())
+                } else {
+                    problems
+                        .iter()
+                        .rev()
+                        .find(|problem| problem.order_key() < here)
+                        .or_else(|| problems.last())
+                }
+                .cloned();
+
+                this.update(&mut app, |view, cx| {
+                    let Some(problem) = target else {
+                        view.set_status("没有诊断");
+                        cx.notify();
+                        return;
+                    };
+                    view.open_file_at(&problem.path, problem.start.line, problem.start.column, cx);
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 把光标处的诊断复制到剪贴板
+    pub fn copy_diagnostic_at_cursor(&mut self, cx: &mut Context<'_, Self>) {
+        let (Some(path), Some(cursor)) = (
+            self.current_file_path.clone(),
+            self.selection.map(|selection| selection.active),
+        ) else {
+            return;
+        };
+        let line_len = self
+            .lines
+            .get(cursor.line)
+            .map(|line| line.trim_end_matches(['\n', '\r']).chars().count())
+            .unwrap_or(0);
+        // 光标落在诊断范围内的优先，否则取该行第一条
+        let on_line: Vec<&Diagnostic> = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                Self::diagnostic_columns(diagnostic, cursor.line, line_len).is_some()
+            })
+            .collect();
+        let diagnostic = on_line
+            .iter()
+            .find(|diagnostic| {
+                Self::diagnostic_columns(diagnostic, cursor.line, line_len)
+                    .is_some_and(|(start, end)| (start..=end).contains(&cursor.column))
+            })
+            .or(on_line.first());
+        let Some(diagnostic) = diagnostic else {
+            self.set_status("光标处没有诊断");
+            cx.notify();
+            return;
+        };
+        // self.diagnostics 的列已是字符列
+        let start = editor_core_text::Cursor::new(
+            diagnostic.range.start.line as usize,
+            diagnostic.range.start.character as usize,
+        );
+        let problem = Problem::from_diagnostic(path, start, diagnostic);
+        self.copy_problem(&problem, cx);
+    }
+
+    fn copy_problem(&mut self, problem: &Problem, cx: &mut Context<'_, Self>) {
+        cx.write_to_clipboard(gpui::ClipboardItem::new_string(problem.summary()));
+        self.set_status("已复制诊断");
+        cx.notify();
+    }
+
+    pub(crate) fn render_problems(
+        &self,
+        panel: &ProblemsPanel,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let mut filters = div().flex().flex_wrap().gap_1().px_3().py_2().text_xs();
+        for severity in [
+            DiagnosticSeverity::Error,
+            DiagnosticSeverity::Warning,
+            DiagnosticSeverity::Information,
+            DiagnosticSeverity::Hint,
+        ] {
+            let shown = !panel.hidden_severities.contains(&severity);
+            let (color, _) = Self::diagnostic_colors(severity);
+            filters = filters.child(
+                div()
+                    .id(("problems-severity", severity as u8 as u64))
+                    .px_2()
+                    .rounded(px(4.0))
+                    .cursor_pointer()
+                    .bg(if shown { rgb(0x2a2a2a) } else { rgb(0x181818) })
+                    .text_color(if shown { rgb(color) } else { rgb(0x555555) })
+                    .child(format!(
+                        "{} {}",
+                        Self::severity_label(severity),
+                        panel.count(severity)
+                    ))
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        if let Some(panel) = view.problems.as_mut() {
+                            if !panel.hidden_severities.remove(&severity) {
+                                panel.hidden_severities.insert(severity);
+                            }
+                        }
+                        cx.notify();
+                    })),
+            );
+        }
+        filters = filters.child(
+            div()
+                .id("problems-source")
+                .px_2()
+                .rounded(px(4.0))
+                .cursor_pointer()
+                .bg(rgb(0x2a2a2a))
+                .text_color(rgb(0xcccccc))
+                .child(format!(
+                    "来源: {}",
+                    panel.source.as_deref().unwrap_or("全部")
+                ))
+                .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                    if let Some(panel) = view.problems.as_mut() {
+                        panel.cycle_source();
+                    }
+                    cx.notify();
+                })),
+        );
+
+        let root = std::env::current_dir().unwrap_or_default();
+        let mut list = div()
+            .id("problems-list")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .overflow_scroll();
+        let visible: Vec<&Problem> = panel.visible().collect();
+        if visible.is_empty() {
+            list = list.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_sm()
+                    .text_color(rgb(0x777777))
+                    .child("没有问题"),
+            );
+        }
+        for (idx, problem) in visible.into_iter().enumerate() {
+            let (color, _) = Self::diagnostic_colors(problem.severity);
+            let location = format!(
+                "{}:{}",
+                problem
+                    .path
+                    .strip_prefix(&root)
+                    .unwrap_or(&problem.path)
+                    .display(),
+                problem.start.line + 1
+            );
+            let open = problem.clone();
+            let copy = problem.clone();
+            list = list.child(
+                div()
+                    .flex()
+                    .items_start()
+                    .gap_2()
+                    .px_3()
+                    .py_1()
+                    .text_sm()
+                    .child(
+                        div()
+                            .w(px(8.0))
+                            .mt_1()
+                            .h(px(8.0))
+                            .rounded(px(4.0))
+                            .bg(rgb(color)),
+                    )
+                    .child(
+                        div()
+                            .id(("problem", idx as u64))
+                            .flex_1()
+                            .flex()
+                            .flex_col()
+                            .cursor_pointer()
+                            .child(
+                                div().text_color(rgb(0xdddddd)).child(
+                                    problem
+                                        .message
+                                        .lines()
+                                        .next()
+                                        .unwrap_or_default()
+                                        .to_string(),
+                                ),
+                            )
+                            .child(div().text_xs().text_color(rgb(0x777777)).child(
+                                match &problem.source {
+                                    Some(source) => format!("{} · {}", location, source),
+                                    None => location,
+                                },
+                            ))
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.open_file_at(
+                                    &open.path,
+                                    open.start.line,
+                                    open.start.column,
+                                    cx,
+                                )
+                            })),
+                    )
+                    .child(
+                        div()
+                            .id(("problem-copy", idx as u64))
+                            .px_1()
+                            .cursor_pointer()
+                            .text_color(rgb(0x888888))
+                            .child("⧉")
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.copy_problem(&copy, cx)
+                            })),
+                    ),
+            );
+        }
+
+        div()
+            .w(px(360.0))
+            .flex()
+            .flex_col()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .child(
+                        div()
+                            .text_color(rgb(0x9ad1ff))
+                            .child(format!("问题 ({})", panel.problems.len())),
+                    )
+                    .child(
+                        div()
+                            .id("problems-close")
+                            .px_2()
+                            .cursor_pointer()
+                            .text_color(rgb(0xcccccc))
+                            .child("×")
+                            .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                view.problems = None;
+                                cx.notify();
+                            })),
+                    ),
+            )
+            .child(filters)
+            .child(list)
+    }
+}
@@ -0,0 +1,357 @@
+//! 拉取请求：列出 GitHub/GitLab 上当前仓库的拉取请求，查看差异、检出分支和发表评论
+
+use crate::editor_view::EditorView;
+use crate::keymap::QuickInputMode;
+use editor_core_project::{
+    parse_unified_diff, FileDiff, ForgeClient, ForgeRepository, GitRepository, PullRequest,
+};
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+
+/// 正在差异视图中查看的 PR/MR
+pub(crate) struct PullRequestReview {
+    request: PullRequest,
+    files: Vec<FileDiff>,
+}
+
+impl EditorView {
+    /// 读取当前仓库 origin 所在托管平台上打开的 PR/MR
+    pub(crate) fn load_pull_requests(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(repository) = std::env::current_dir()
+            .ok()
+            .and_then(|dir| GitRepository::discover(&dir))
+        else {
+            self.set_status("当前工作区不在 Git 仓库中");
+            return;
+        };
+        let forges = self.config.editor.forges.clone();
+        let executor = self.task_executor.clone();
+        self.pull_requests.clear();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = executor
+                    .spawn(async move {
+                        let url = repository.remote_url("origin").await?;
+                        let forge =
+                            ForgeRepository::from_remote(&url, &forges).ok_or_else(|| {
+                                anyhow::anyhow!("origin（{}）不在 GitHub 或 GitLab 上", url)
+                            })?;
+                        let client = ForgeClient::new(forge)?;
+                        let requests = client.pull_requests().await?;
+                        anyhow::Ok((client, requests))
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result);
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok((client, requests)) => {
+                            let noun = client.repository().noun();
+                            view.set_status(if requests.is_empty() {
+                                format!("{} 没有打开的 {}", client.repository().path, noun)
+                            } else {
+                                format!(
+                                    "{} 个打开的 {}，输入筛选，回车查看第一个",
+                                    requests.len(),
+                                    noun
+                                )
+                            });
+                            view.forge = Some(client);
+                            view.pull_requests = requests;
+                        }
+                        Err(e) => view.set_status(format!("无法读取 PR/MR: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn filtered_pull_requests(&self, filter: &str) -> Vec<PullRequest> {
+        let filter = filter.to_lowercase();
+        self.pull_requests
+            .iter()
+            .filter(|request| {
+                format!("#{} {} {}", request.number, request.title, request.author)
+                    .to_lowercase()
+                    .contains(&filter)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 在差异视图中查看 PR/MR 的改动
+    pub(crate) fn open_pull_request(&mut self, request: PullRequest, cx: &mut Context<'_, Self>) {
+        let Some(client) = self.forge.clone() else {
+            return;
+        };
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        let executor = self.task_executor.clone();
+        self.set_status(format!("正在读取 #{} 的改动…", request.number));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let number = request.number;
+                let result = executor
+                    .spawn(async move { client.diff(number).await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(diff) => {
+                            view.set_status(format!(
+                                "#{} {}，Esc 关闭",
+                                request.number, request.title
+                            ));
+                            view.pull_request_review = Some(PullRequestReview {
+                                request,
+                                files: parse_unified_diff(&diff),
+                            });
+                        }
+                        Err(e) => view.set_status(format!("无法读取 #{} 的改动: {}", number, e)),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 把正在查看的 PR/MR 取到本地分支并切换过去
+    fn checkout_pull_request(&mut self, cx: &mut Context<'_, Self>) {
+        let (Some(client), Some(review)) = (&self.forge, &self.pull_request_review) else {
+            return;
+        };
+        let Some(repository) = std::env::current_dir()
+            .ok()
+            .and_then(|dir| GitRepository::discover(&dir))
+        else {
+            self.set_status("当前工作区不在 Git 仓库中");
+            return;
+        };
+        let kind = client.repository().kind;
+        let head = review.request.head_ref(kind);
+        let branch = review.request.local_branch(kind);
+        let executor = self.task_executor.clone();
+        self.set_status(format!("正在检出 {}…", branch));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let target = branch.clone();
+                let result = executor
+                    .spawn(async move {
+                        repository
+                            .checkout_remote_ref("origin", &head, &target)
+                            .await
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+
+                // 工作区文件的变化由文件监视重新载入已打开的缓冲区
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) => view.set_status(format!("已检出到分支 {}", branch)),
+                        Err(e) => view.set_status(format!("检出 {} 失败: {}", branch, e)),
+                    }
+                    view.reload_file_tree(cx);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 在正在查看的 PR/MR 的对话中发表评论
+    pub(crate) fn comment_on_pull_request(&mut self, body: String, cx: &mut Context<'_, Self>) {
+        let (Some(client), Some(review)) = (self.forge.clone(), &self.pull_request_review) else {
+            self.set_status("先打开一个 PR/MR");
+            return;
+        };
+        let number = review.request.number;
+        let executor = self.task_executor.clone();
+        self.set_status(format!("正在发表评论到 #{}…", number));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let result = executor
+                    .spawn(async move { client.comment(number, &body).await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) => view.set_status(format!("已在 #{} 发表评论", number)),
+                        Err(e) => view.set_status(format!("评论失败: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    pub(crate) fn render_pull_requests(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::PickPullRequest {
+            return list;
+        }
+
+        for request in self.filtered_pull_requests(self.quick_open_input.trim()) {
+            list = list.child(
+                div()
+                    .id(("pull-request", request.number))
+                    .flex()
+                    .justify_between()
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(rgb(0xd0d0d0))
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(format!("#{} {}", request.number, request.title))
+                    .child(div().text_xs().text_color(rgb(0x777777)).child(format!(
+                        "{} · {} → {}",
+                        request.author, request.source_branch, request.target_branch
+                    )))
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.open_pull_request(request.clone(), cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    pub(crate) fn render_pull_request_review(
+        &self,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let Some(review) = &self.pull_request_review else {
+            return div();
+        };
+        let request = &review.request;
+
+        let mut rows = div()
+            .id("pull-request-diff")
+            .mt_2()
+            .max_h(px(480.0))
+            .overflow_scroll()
+            .flex()
+            .flex_col()
+            .gap_1();
+        if review.files.is_empty() {
+            rows = rows.child(div().text_sm().text_color(rgb(0x666666)).child("没有改动"));
+        }
+        for file in &review.files {
+            rows = rows
+                .child(
+                    div()
+                        .mt_1()
+                        .text_sm()
+                        .text_color(rgb(0xffffff))
+                        .child(file.path.clone()),
+                )
+                .child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .pl_4()
+                        .text_xs()
+                        .font_family("monospace")
+                        .children(Self::diff_rows(&file.lines, 0x81c784, 0xe57373)),
+                );
+        }
+
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded(px(4.0))
+                .bg(rgb(0x3a3a3a))
+                .text_xs()
+                .cursor_pointer()
+                .child(label)
+        };
+        div().absolute().inset_0().child(
+            div()
+                .w(px(760.0))
+                .p_4()
+                .rounded(px(10.0))
+                .bg(rgb(0x121212))
+                .border_1()
+                .border_color(rgb(0x2a2a2a))
+                .shadow_lg()
+                .mx_auto()
+                .mt(px(80.0))
+                .child(
+                    div()
+                        .flex()
+                        .justify_between()
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .child(
+                                    div()
+                                        .text_color(rgb(0xffffff))
+                                        .child(format!("#{} {}", request.number, request.title)),
+                                )
+                                .child(div().text_xs().text_color(rgb(0x888888)).child(format!(
+                                    "{} · {} → {} · {}",
+                                    request.author,
+                                    request.source_branch,
+                                    request.target_branch,
+                                    request.url
+                                ))),
+                        )
+                        .child(
+                            div()
+                                .flex()
+                                .gap_1()
+                                .child(button("pull-request-checkout", "Checkout").on_click(
+                                    cx.listener(|view: &mut EditorView, _, _, cx| {
+                                        view.checkout_pull_request(cx)
+                                    }),
+                                ))
+                                .child(button("pull-request-comment", "Comment").on_click(
+                                    cx.listener(|view: &mut EditorView, _, _, cx| {
+                                        view.begin_quick_input(
+                                            QuickInputMode::CommentPullRequest,
+                                            cx,
+                                        )
+                                    }),
+                                ))
+                                .child(button("pull-request-close", "✕").on_click(cx.listener(
+                                    |view: &mut EditorView, _, _, cx| {
+                                        view.pull_request_review = None;
+                                        cx.notify();
+                                    },
+                                ))),
+                        ),
+                )
+                .child(rows),
+        )
+    }
+}
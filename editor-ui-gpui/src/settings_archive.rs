@@ -0,0 +1,231 @@
+//! 设置归档：把编辑器设置导出为一个归档，导入时逐项处理冲突
+
+use crate::editor_view::EditorView;
+use crate::SyntaxTheme;
+use editor_infra::{ConflictResolution, SettingsArchive};
+use gpui::{div, prelude::*, px, rgb, Context, InteractiveElement, StatefulInteractiveElement};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// 等待用户处理冲突的配置导入
+pub(crate) struct PendingImport {
+    archive: SettingsArchive,
+    conflicts: Vec<String>,
+    resolutions: HashMap<String, ConflictResolution>,
+}
+
+impl EditorView {
+    pub(crate) fn resolve_input_path(input: &str) -> PathBuf {
+        let mut path = PathBuf::from(input);
+        if path.is_relative() {
+            if let Ok(cwd) = std::env::current_dir() {
+                path = cwd.join(path);
+            }
+        }
+        path
+    }
+
+    /// 导出全部配置（设置、快捷键、代码片段、主题、提示词模板）为单个归档
+    pub fn export_settings(&mut self, target: String, cx: &mut Context<'_, Self>) {
+        let config_dir = editor_infra::paths::config_dir();
+        let path = if target.is_empty() {
+            config_dir.join("fusang-settings.toml")
+        } else {
+            Self::resolve_input_path(&target)
+        };
+
+        let result = SettingsArchive::export(&self.config, &config_dir)
+            .and_then(|archive| archive.save_to_file(&path));
+        match result {
+            Ok(_) => self.set_status(format!("配置已导出到 {}", path.display())),
+            Err(e) => self.set_status(format!("导出配置失败: {}", e)),
+        }
+        cx.notify();
+    }
+
+    /// 读取配置归档；存在冲突时先让用户逐项选择
+    pub fn import_settings(&mut self, source: String, cx: &mut Context<'_, Self>) {
+        let path = Self::resolve_input_path(&source);
+        let config_dir = editor_infra::paths::config_dir();
+
+        let loaded = SettingsArchive::load_from_file(&path).and_then(|archive| {
+            let conflicts = archive.conflicts(&self.config, &config_dir)?;
+            Ok((archive, conflicts))
+        });
+        match loaded {
+            Ok((archive, conflicts)) if conflicts.is_empty() => {
+                self.pending_import = Some(PendingImport {
+                    archive,
+                    conflicts,
+                    resolutions: HashMap::new(),
+                });
+                self.apply_pending_import(cx);
+            }
+            Ok((archive, conflicts)) => {
+                self.set_status(format!(
+                    "导入有 {} 项冲突，请选择保留哪一方",
+                    conflicts.len()
+                ));
+                self.pending_import = Some(PendingImport {
+                    archive,
+                    conflicts,
+                    resolutions: HashMap::new(),
+                });
+            }
+            Err(e) => self.set_status(format!("无法读取配置归档: {}", e)),
+        }
+        cx.notify();
+    }
+
+    fn toggle_import_resolution(&mut self, key: String, cx: &mut Context<'_, Self>) {
+        if let Some(pending) = &mut self.pending_import {
+            let resolution = pending
+                .resolutions
+                .entry(key)
+                .or_insert(ConflictResolution::KeepExisting);
+            *resolution = match resolution {
+                ConflictResolution::KeepExisting => ConflictResolution::UseImported,
+                ConflictResolution::UseImported => ConflictResolution::KeepExisting,
+            };
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn apply_pending_import(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(pending) = self.pending_import.take() else {
+            return;
+        };
+        let config_dir = editor_infra::paths::config_dir();
+        let mut config = self.config.clone();
+
+        let result = pending
+            .archive
+            .apply(&mut config, &config_dir, &pending.resolutions)
+            .and_then(|summary| {
+                std::fs::create_dir_all(&config_dir)?;
+                config.save_to_file(&config_dir.join("config.toml"))?;
+                Ok(summary)
+            });
+
+        match result {
+            Ok(summary) => {
+                let ai_engine = self.ai_engine.clone();
+                let ai_config = config.ai.clone();
+                self.ai_executor
+                    .spawn(async move { ai_engine.update_config(ai_config).await });
+                self.config = config;
+                let theme = SyntaxTheme::named(&self.config.ui.theme).unwrap_or_default();
+                self.syntax_theme = theme;
+                if let Some(panel) = &self.ai_panel {
+                    panel.update(cx, |panel, _| panel.set_syntax_theme(theme));
+                }
+                self.set_status(format!(
+                    "配置已导入：{} 项更新，{} 项保留本地",
+                    summary.imported.len(),
+                    summary.kept.len()
+                ));
+                self.refresh_buffer_view(cx);
+            }
+            Err(e) => self.set_status(format!("导入配置失败: {}", e)),
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn render_import_conflicts(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let Some(pending) = &self.pending_import else {
+            return div();
+        };
+
+        let mut rows = div().flex().flex_col().gap_1().mt_2();
+        for (idx, key) in pending.conflicts.iter().enumerate() {
+            let use_imported =
+                pending.resolutions.get(key) == Some(&ConflictResolution::UseImported);
+            let key_for_click = key.clone();
+
+            rows = rows.child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .text_sm()
+                    .child(key.clone())
+                    .child(
+                        div()
+                            .id(("import-conflict", idx as u64))
+                            .px_2()
+                            .rounded(px(4.0))
+                            .bg(if use_imported {
+                                rgb(0x1a4d8f)
+                            } else {
+                                rgb(0x3a3a3a)
+                            })
+                            .cursor_pointer()
+                            .child(if use_imported {
+                                "使用导入"
+                            } else {
+                                "保留本地"
+                            })
+                            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                                view.toggle_import_resolution(key_for_click.clone(), cx)
+                            })),
+                    ),
+            );
+        }
+
+        div().absolute().inset_0().child(
+            div()
+                .w(px(520.0))
+                .p_4()
+                .rounded(px(10.0))
+                .bg(rgb(0x121212))
+                .border_1()
+                .border_color(rgb(0x2a2a2a))
+                .shadow_lg()
+                .mx_auto()
+                .mt(px(120.0))
+                .child(div().text_color(rgb(0xffffff)).child("Import Settings"))
+                .child(
+                    div()
+                        .mt_1()
+                        .text_sm()
+                        .text_color(rgb(0x888888))
+                        .child("以下项目与本地配置不同，点击切换保留哪一方"),
+                )
+                .child(rows)
+                .child(
+                    div()
+                        .flex()
+                        .gap_2()
+                        .mt_3()
+                        .child(
+                            div()
+                                .id("import-apply")
+                                .px_3()
+                                .py_1()
+                                .rounded(px(6.0))
+                                .bg(rgb(0x2e7d32))
+                                .cursor_pointer()
+                                .child("Apply")
+                                .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                    view.apply_pending_import(cx)
+                                })),
+                        )
+                        .child(
+                            div()
+                                .id("import-cancel")
+                                .px_3()
+                                .py_1()
+                                .rounded(px(6.0))
+                                .bg(rgb(0x3a3a3a))
+                                .cursor_pointer()
+                                .child("Cancel")
+                                .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                    view.pending_import = None;
+                                    view.set_status("已取消导入配置");
+                                    cx.notify();
+                                })),
+                        ),
+                ),
+        )
+    }
+}
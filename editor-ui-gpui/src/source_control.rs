@@ -0,0 +1,616 @@
+//! 源代码管理：stash 与工作树面板，以及快速输入框中的 Git 命令列表
+
+use crate::editor_view::EditorView;
+use crate::keymap::QuickInputMode;
+use editor_core_project::{GitRepository, Stash, Worktree};
+use gpui::{div, prelude::*, px, rgb, AsyncApp, Context, WeakEntity};
+use std::path::{Path, PathBuf};
+
+/// 源代码管理面板和 Git 命令列表共用的仓库状态
+pub(crate) struct SourceControl {
+    repository: GitRepository,
+    stashes: Vec<Stash>,
+    worktrees: Vec<Worktree>,
+    /// 有未解决冲突的文件，相对仓库根目录
+    conflicts: Vec<PathBuf>,
+}
+
+/// Git 命令列表中的一项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum GitAction {
+    PullRequests,
+    Stash,
+    ApplyStash(usize),
+    PopStash(usize),
+    DropStash(usize),
+    AddWorktree,
+    SwitchWorktree(PathBuf),
+}
+
+impl GitAction {
+    fn label(&self) -> String {
+        match self {
+            GitAction::PullRequests => "Pull Request: 查看打开的 PR/MR".to_string(),
+            GitAction::Stash => "Stash: 暂存当前修改".to_string(),
+            GitAction::ApplyStash(index) => format!("Stash: 应用 stash@{{{}}}", index),
+            GitAction::PopStash(index) => format!("Stash: 弹出 stash@{{{}}}", index),
+            GitAction::DropStash(index) => format!("Stash: 丢弃 stash@{{{}}}", index),
+            GitAction::AddWorktree => "Worktree: 新建工作树".to_string(),
+            GitAction::SwitchWorktree(path) => format!("Worktree: 切换到 {}", path.display()),
+        }
+    }
+
+    /// 丢弃 stash 总要确认；工作区有冲突时，改动工作区或离开它也要确认
+    fn needs_confirmation(&self, conflicted: bool) -> bool {
+        match self {
+            GitAction::DropStash(_) => true,
+            GitAction::ApplyStash(_) | GitAction::PopStash(_) | GitAction::SwitchWorktree(_) => {
+                conflicted
+            }
+            GitAction::PullRequests | GitAction::Stash | GitAction::AddWorktree => false,
+        }
+    }
+}
+
+impl EditorView {
+    /// 切换源代码管理面板
+    pub fn toggle_source_control(&mut self, cx: &mut Context<'_, Self>) {
+        self.show_source_control = !self.show_source_control;
+        if self.show_source_control {
+            self.load_source_control(cx);
+        }
+        cx.notify();
+    }
+
+    /// 在后台读取当前工作区所在仓库的 stash、工作树和冲突文件
+    pub(crate) fn load_source_control(&mut self, cx: &mut Context<'_, Self>) {
+        let Some(repository) = std::env::current_dir()
+            .ok()
+            .and_then(|dir| GitRepository::discover(&dir))
+        else {
+            self.source_control = None;
+            self.set_status("当前工作区不在 Git 仓库中");
+            return;
+        };
+        let executor = self.task_executor.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let reader = repository.clone();
+                // tokio::process 需要 tokio 运行时
+                let result = executor
+                    .spawn(async move {
+                        anyhow::Ok((
+                            reader.stashes().await?,
+                            reader.worktrees().await?,
+                            reader.conflicted_files().await?,
+                        ))
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result);
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok((stashes, worktrees, conflicts)) => {
+                            view.source_control = Some(SourceControl {
+                                repository,
+                                stashes,
+                                worktrees,
+                                conflicts,
+                            });
+                        }
+                        Err(e) => view.set_status(format!("无法读取 Git 仓库: {}", e)),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    fn git_actions(&self) -> Vec<GitAction> {
+        let Some(git) = &self.source_control else {
+            return Vec::new();
+        };
+        let mut actions = vec![GitAction::PullRequests, GitAction::Stash];
+        for stash in &git.stashes {
+            actions.extend([
+                GitAction::ApplyStash(stash.index),
+                GitAction::PopStash(stash.index),
+                GitAction::DropStash(stash.index),
+            ]);
+        }
+        actions.push(GitAction::AddWorktree);
+        actions.extend(
+            git.worktrees
+                .iter()
+                .filter(|worktree| worktree.path != git.repository.root())
+                .map(|worktree| GitAction::SwitchWorktree(worktree.path.clone())),
+        );
+        actions
+    }
+
+    pub(crate) fn filtered_git_actions(&self, filter: &str) -> Vec<GitAction> {
+        let filter = filter.to_lowercase();
+        self.git_actions()
+            .into_iter()
+            .filter(|action| action.label().to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    /// Git 命令列表中一项的补充说明：stash 的描述或工作树的分支
+    fn git_action_detail(&self, action: &GitAction) -> String {
+        let Some(git) = &self.source_control else {
+            return String::new();
+        };
+        match action {
+            GitAction::ApplyStash(index)
+            | GitAction::PopStash(index)
+            | GitAction::DropStash(index) => git
+                .stashes
+                .iter()
+                .find(|stash| stash.index == *index)
+                .map(|stash| stash.message.clone())
+                .unwrap_or_default(),
+            GitAction::SwitchWorktree(path) => git
+                .worktrees
+                .iter()
+                .find(|worktree| &worktree.path == path)
+                .map(Self::worktree_branch)
+                .unwrap_or_default(),
+            GitAction::Stash => "包括未跟踪的文件".to_string(),
+            GitAction::PullRequests | GitAction::AddWorktree => String::new(),
+        }
+    }
+
+    fn worktree_branch(worktree: &Worktree) -> String {
+        match &worktree.branch {
+            Some(branch) => branch.clone(),
+            None => format!("分离于 {}", &worktree.head[..worktree.head.len().min(7)]),
+        }
+    }
+
+    /// 执行 Git 命令。需要确认的命令第一次只提示，再执行一次同一命令才真正运行
+    pub(crate) fn run_git_action(&mut self, action: GitAction, cx: &mut Context<'_, Self>) {
+        self.quick_open_active = false;
+        self.quick_open_input.clear();
+        match action {
+            GitAction::AddWorktree => {
+                self.pending_git_action = None;
+                self.begin_quick_input(QuickInputMode::AddWorktree, cx);
+                return;
+            }
+            GitAction::PullRequests => {
+                self.pending_git_action = None;
+                self.begin_quick_input(QuickInputMode::PickPullRequest, cx);
+                return;
+            }
+            _ => {}
+        }
+        let Some(repository) = self
+            .source_control
+            .as_ref()
+            .map(|git| git.repository.clone())
+        else {
+            self.set_status("当前工作区不在 Git 仓库中");
+            return;
+        };
+        let confirmed = self.pending_git_action.take().as_ref() == Some(&action);
+        let executor = self.task_executor.clone();
+        let label = action.label();
+        self.set_status(format!("正在执行 {}…", label));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                // 面板里的冲突状态可能已经过时，执行前重新检查
+                let reader = repository.clone();
+                let conflicts = executor
+                    .spawn(async move { reader.conflicted_files().await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+                let conflicts = match conflicts {
+                    Ok(conflicts) => conflicts.len(),
+                    Err(e) => {
+                        this.update(&mut app, |view, cx| {
+                            view.set_status(format!("无法读取 Git 仓库: {}", e));
+                            cx.notify();
+                        })?;
+                        return anyhow::Ok(());
+                    }
+                };
+
+                if !confirmed && action.needs_confirmation(conflicts > 0) {
+                    this.update(&mut app, |view, cx| {
+                        view.set_status(if conflicts > 0 {
+                            format!(
+                                "工作区有 {} 个文件存在未解决的冲突，再执行一次「{}」以继续",
+                                conflicts, label
+                            )
+                        } else {
+                            format!("丢弃后无法恢复，再执行一次「{}」以确认", label)
+                        });
+                        view.pending_git_action = Some(action);
+                        cx.notify();
+                    })?;
+                    return anyhow::Ok(());
+                }
+
+                if let GitAction::SwitchWorktree(path) = &action {
+                    this.update(&mut app, |view, cx| {
+                        view.switch_worktree(path, cx);
+                        cx.notify();
+                    })?;
+                    return anyhow::Ok(());
+                }
+
+                let result = executor
+                    .spawn(async move {
+                        match action {
+                            GitAction::Stash => repository.stash("").await,
+                            GitAction::ApplyStash(index) => {
+                                repository.apply_stash(index, false).await
+                            }
+                            GitAction::PopStash(index) => repository.apply_stash(index, true).await,
+                            GitAction::DropStash(index) => repository.drop_stash(index).await,
+                            GitAction::PullRequests
+                            | GitAction::AddWorktree
+                            | GitAction::SwitchWorktree(_) => Ok(()),
+                        }
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+
+                // 工作区文件的变化由文件监视重新载入已打开的缓冲区
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) => view.set_status(format!("{} 已完成", label)),
+                        Err(e) => view.set_status(format!("{} 失败: {}", label, e)),
+                    }
+                    view.load_source_control(cx);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 新建工作树。输入为分支名，后面可以跟上目录，默认放在仓库旁的「仓库名-分支名」
+    pub(crate) fn add_worktree(&mut self, input: &str, cx: &mut Context<'_, Self>) {
+        let Some(repository) = self
+            .source_control
+            .as_ref()
+            .map(|git| git.repository.clone())
+        else {
+            self.set_status("当前工作区不在 Git 仓库中");
+            return;
+        };
+        let (branch, path) = match input.split_once(char::is_whitespace) {
+            Some((branch, path)) => (branch.to_string(), Self::resolve_input_path(path.trim())),
+            None => {
+                let name = repository
+                    .root()
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let directory = format!("{}-{}", name, input.replace('/', "-"));
+                (
+                    input.to_string(),
+                    repository.root().with_file_name(directory),
+                )
+            }
+        };
+        let executor = self.task_executor.clone();
+        self.set_status(format!("正在新建工作树 {}…", path.display()));
+        cx.notify();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let target = path.clone();
+                let result = executor
+                    .spawn(async move { repository.add_worktree(&target, &branch).await })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result.map_err(anyhow::Error::from));
+
+                this.update(&mut app, |view, cx| {
+                    match result {
+                        Ok(()) => view.set_status(format!(
+                            "已在 {} 新建工作树，可在源代码管理面板或 Git 命令中切换",
+                            path.display()
+                        )),
+                        Err(e) => view.set_status(format!("新建工作树失败: {}", e)),
+                    }
+                    view.load_source_control(cx);
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 把工作区切换到另一个工作树，已打开的文件保持打开
+    pub(crate) fn switch_worktree(&mut self, path: &Path, cx: &mut Context<'_, Self>) {
+        if let Err(e) = std::env::set_current_dir(path) {
+            self.set_status(format!("无法切换到 {}: {}", path.display(), e));
+            return;
+        }
+        self.set_status(format!("已切换到工作树 {}", path.display()));
+        if let Ok(root) = std::env::current_dir() {
+            self.recent.record_workspace(&root);
+            self.save_recent(cx);
+        }
+        self.workspace_files.clear();
+        self.reload_file_tree(cx);
+        self.load_source_control(cx);
+        self.load_issue_linker(cx);
+        self.refresh_git_status(cx);
+    }
+
+    pub(crate) fn render_git_actions(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut list = div().mt_2().flex().flex_col().gap_1();
+        if self.quick_input_mode != QuickInputMode::GitCommand {
+            return list;
+        }
+
+        for (index, action) in self
+            .filtered_git_actions(self.quick_open_input.trim())
+            .into_iter()
+            .enumerate()
+        {
+            let pending = self.pending_git_action.as_ref() == Some(&action);
+            list = list.child(
+                div()
+                    .id(("git-action", index as u64))
+                    .flex()
+                    .justify_between()
+                    .px_2()
+                    .py_1()
+                    .rounded(px(4.0))
+                    .text_sm()
+                    .text_color(if pending {
+                        rgb(0xffc56b)
+                    } else {
+                        rgb(0xd0d0d0)
+                    })
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0x1f1f1f)))
+                    .child(action.label())
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x777777))
+                            .child(self.git_action_detail(&action)),
+                    )
+                    .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                        view.run_git_action(action.clone(), cx);
+                    })),
+            );
+        }
+
+        list
+    }
+
+    fn render_git_button(
+        &self,
+        id: (&'static str, u64),
+        label: &'static str,
+        action: GitAction,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let pending = self.pending_git_action.as_ref() == Some(&action);
+        div()
+            .id(id)
+            .px_2()
+            .rounded(px(4.0))
+            .bg(if pending {
+                rgb(0x6a4a1a)
+            } else {
+                rgb(0x3a3a3a)
+            })
+            .text_xs()
+            .cursor_pointer()
+            .child(label)
+            .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                view.run_git_action(action.clone(), cx);
+            }))
+    }
+
+    pub(crate) fn render_source_control(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let section = |title: String| {
+            div()
+                .flex()
+                .justify_between()
+                .px_3()
+                .py_1()
+                .bg(rgb(0x1a1a1a))
+                .text_xs()
+                .text_color(rgb(0x888888))
+                .child(title)
+        };
+        let mut panel = div()
+            .id("source-control")
+            .w(px(320.0))
+            .flex()
+            .flex_col()
+            .overflow_y_scroll()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_color(rgb(0x9ad1ff))
+                    .text_sm()
+                    .child("Source Control")
+                    .child(
+                        div()
+                            .flex()
+                            .gap_1()
+                            .child(self.render_git_button(
+                                ("git-pull-requests", 0),
+                                "PRs",
+                                GitAction::PullRequests,
+                                cx,
+                            ))
+                            .child(self.render_git_button(
+                                ("git-stash", 0),
+                                "Stash",
+                                GitAction::Stash,
+                                cx,
+                            )),
+                    ),
+            );
+
+        let Some(git) = &self.source_control else {
+            return panel.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_sm()
+                    .text_color(rgb(0x666666))
+                    .child("当前工作区不在 Git 仓库中"),
+            );
+        };
+
+        if !git.conflicts.is_empty() {
+            panel = panel.child(
+                section(format!("{} 个文件有未解决的冲突", git.conflicts.len()))
+                    .text_color(rgb(0xff8a80)),
+            );
+            for path in &git.conflicts {
+                panel = panel.child(
+                    div()
+                        .px_3()
+                        .py_1()
+                        .text_xs()
+                        .text_color(rgb(0xff8a80))
+                        .child(path.display().to_string()),
+                );
+            }
+        }
+
+        panel = panel.child(section(format!("Stashes ({})", git.stashes.len())));
+        if git.stashes.is_empty() {
+            panel = panel.child(
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_xs()
+                    .text_color(rgb(0x666666))
+                    .child("没有 stash"),
+            );
+        }
+        for stash in &git.stashes {
+            let index = stash.index as u64;
+            panel = panel.child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .px_3()
+                    .py_1()
+                    .border_b_1()
+                    .border_color(rgb(0x1f1f1f))
+                    .text_sm()
+                    .child(
+                        div()
+                            .flex()
+                            .justify_between()
+                            .child(div().text_color(rgb(0xffffff)).child(stash.reference()))
+                            .child(
+                                div()
+                                    .flex()
+                                    .gap_1()
+                                    .child(self.render_git_button(
+                                        ("git-stash-apply", index),
+                                        "Apply",
+                                        GitAction::ApplyStash(stash.index),
+                                        cx,
+                                    ))
+                                    .child(self.render_git_button(
+                                        ("git-stash-pop", index),
+                                        "Pop",
+                                        GitAction::PopStash(stash.index),
+                                        cx,
+                                    ))
+                                    .child(self.render_git_button(
+                                        ("git-stash-drop", index),
+                                        "Drop",
+                                        GitAction::DropStash(stash.index),
+                                        cx,
+                                    )),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(0x888888))
+                            .child(stash.message.clone()),
+                    ),
+            );
+        }
+
+        panel = panel.child(
+            section(format!("Worktrees ({})", git.worktrees.len())).child(self.render_git_button(
+                ("git-worktree-add", 0),
+                "Add",
+                GitAction::AddWorktree,
+                cx,
+            )),
+        );
+        for (index, worktree) in git.worktrees.iter().enumerate() {
+            let current = worktree.path == git.repository.root();
+            let mut row = div()
+                .flex()
+                .justify_between()
+                .px_3()
+                .py_1()
+                .border_b_1()
+                .border_color(rgb(0x1f1f1f))
+                .text_sm()
+                .child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .child(
+                            div()
+                                .text_color(rgb(0xffffff))
+                                .child(Self::worktree_branch(worktree)),
+                        )
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(0x888888))
+                                .child(worktree.path.display().to_string()),
+                        ),
+                );
+            row = if current {
+                row.child(div().text_xs().text_color(rgb(0x8ef1a2)).child("当前"))
+            } else {
+                row.child(self.render_git_button(
+                    ("git-worktree-switch", index as u64),
+                    "Switch",
+                    GitAction::SwitchWorktree(worktree.path.clone()),
+                    cx,
+                ))
+            };
+            panel = panel.child(row);
+        }
+
+        panel
+    }
+}
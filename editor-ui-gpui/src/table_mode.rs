@@ -0,0 +1,341 @@
+//! 表格模式：以表格显示 CSV/TSV 文件，支持按列排序和单元格编辑
+
+use crate::editor_view::EditorView;
+use editor_core_text::{detect_delimiter, Table};
+use futures::FutureExt;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::path::PathBuf;
+use unicode_width::UnicodeWidthChar;
+
+/// 表格视图最多显示的行数
+const TABLE_ROWS: usize = 1000;
+/// 表格视图中一列最多显示的字符宽度
+const TABLE_COLUMN_WIDTH: usize = 40;
+
+/// 以表格查看的 CSV/TSV 文件。修改单元格就是修改缓冲区，可以撤销
+pub(crate) struct TableMode {
+    pub(crate) path: PathBuf,
+    pub(crate) table: Table,
+    /// 解析表格时缓冲区的版本，缓冲区变了就重新解析
+    pub(crate) version: usize,
+    /// 排序的列和是否升序，只影响显示顺序
+    sort: Option<(usize, bool)>,
+    /// 所选单元格：文件中的行（0 为表头）和列
+    pub(crate) selected: (usize, usize),
+    /// 正在编辑的单元格内容
+    editing: Option<String>,
+}
+
+impl TableMode {
+    /// 按显示顺序排列的行在文件中的下标，表头在最前
+    fn display_rows(&self) -> Vec<usize> {
+        if self.table.rows.is_empty() {
+            return Vec::new();
+        }
+        let body = match self.sort {
+            Some((column, ascending)) => self.table.sorted_rows(column, ascending),
+            None => (1..self.table.rows.len()).collect(),
+        };
+        std::iter::once(0).chain(body).collect()
+    }
+}
+
+impl EditorView {
+    pub(crate) fn is_table_file(path: &Option<PathBuf>) -> bool {
+        path.as_ref()
+            .and_then(|path| path.extension())
+            .is_some_and(|ext| detect_delimiter(&ext.to_string_lossy(), "").is_some())
+    }
+
+    /// 在表格和文本视图之间切换当前的 CSV/TSV 文件
+    pub fn toggle_table_mode(&mut self, cx: &mut Context<'_, Self>) {
+        if self.table_mode.take().is_some() {
+            self.set_status("已回到文本视图");
+            cx.notify();
+            return;
+        }
+        let Some(path) = self.current_file_path.clone() else {
+            return;
+        };
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let buffer_manager = self.buffer_manager.clone();
+
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let Some(handle) = buffer_manager.get_buffer(&path).await else {
+                    return anyhow::Ok(());
+                };
+                let (text, version) = {
+                    let buffer = handle.lock().await;
+                    (buffer.get_text().await, buffer.version())
+                };
+                this.update(&mut app, |view, cx| {
+                    if view.current_file_path.as_ref() != Some(&path) {
+                        return;
+                    }
+                    match detect_delimiter(&extension, &text) {
+                        Some(delimiter) => {
+                            view.table_mode = Some(TableMode {
+                                path,
+                                table: Table::parse(&text, delimiter),
+                                version,
+                                sort: None,
+                                selected: (0, 0),
+                                editing: None,
+                            });
+                            view.set_status(
+                                "表格视图：方向键选择，Enter 编辑单元格，点击表头排序，Esc 回到文本",
+                            );
+                        }
+                        None => view.set_status("只有 CSV/TSV 文件可以按表格查看"),
+                    }
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+    }
+
+    /// 表格视图中的按键：选择单元格，或编辑所选单元格。直接输入会替换单元格的内容
+    pub(crate) fn handle_table_key(&mut self, key: &str, cx: &mut Context<'_, Self>) {
+        let Some(table) = &mut self.table_mode else {
+            return;
+        };
+        if let Some(value) = &mut table.editing {
+            match key {
+                "Escape" => table.editing = None,
+                "Enter" | "Tab" => return self.commit_table_edit(cx),
+                "Backspace" => {
+                    value.pop();
+                }
+                "Space" | " " => value.push(' '),
+                _ if key.chars().count() == 1 => value.push_str(key),
+                _ => return,
+            }
+            cx.notify();
+            return;
+        }
+
+        let rows = table.display_rows();
+        let position = rows
+            .iter()
+            .position(|&row| row == table.selected.0)
+            .unwrap_or(0);
+        let (row, column) = table.selected;
+        match key {
+            "Escape" => return self.toggle_table_mode(cx),
+            "ArrowUp" | "Up" => table.selected.0 = rows[..position].last().copied().unwrap_or(row),
+            "ArrowDown" | "Down" => {
+                table.selected.0 = rows.get(position + 1).copied().unwrap_or(row)
+            }
+            "ArrowLeft" | "Left" => table.selected.1 = column.saturating_sub(1),
+            "ArrowRight" | "Right" | "Tab" => {
+                table.selected.1 = (column + 1).min(table.table.column_count().saturating_sub(1))
+            }
+            "Enter" => {
+                let value = table.table.cell(row, column).map(|cell| cell.value.clone());
+                table.editing = Some(value.unwrap_or_default());
+            }
+            "Space" | " " => table.editing = Some(" ".to_string()),
+            _ if key.chars().count() == 1 => table.editing = Some(key.to_string()),
+            _ => return,
+        }
+        cx.notify();
+    }
+
+    /// 把正在编辑的单元格写回缓冲区，作为一步可撤销的编辑
+    fn commit_table_edit(&mut self, cx: &mut Context<'_, Self>) {
+        if !self.ensure_writable(cx) {
+            return;
+        }
+        let Some(table) = &mut self.table_mode else {
+            return;
+        };
+        let Some(value) = table.editing.take() else {
+            return;
+        };
+        let (row, column) = table.selected;
+        if table
+            .table
+            .cell(row, column)
+            .is_some_and(|cell| cell.value == value)
+        {
+            cx.notify();
+            return;
+        }
+        let Some((range, text)) = table.table.edit_for(row, column, &value) else {
+            cx.notify();
+            return;
+        };
+        let path = table.path.clone();
+
+        let applied = self.buffer_manager.submit_edit(&path, move |buffer| {
+            async move { buffer.replace_range(range.start, range.len(), &text).await }.boxed()
+        });
+        cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                applied.await;
+                this.update(&mut app, |view, cx| view.refresh_buffer_view(cx))?;
+                anyhow::Ok(())
+            }
+        })
+        .detach();
+        cx.notify();
+    }
+
+    /// 表格视图：各列按内容对齐，表头固定在顶部，点击表头按该列升序、降序排序或取消排序
+    pub(crate) fn render_table(
+        &self,
+        table: &TableMode,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let char_w = self.char_width();
+        let widths = table.table.column_widths(TABLE_COLUMN_WIDTH);
+        let column_px = |column: usize| px((widths[column].max(1) as f32 + 2.0) * char_w);
+        let clip = |value: &str| {
+            let value = value.replace(['\n', '\r'], " ");
+            let mut clipped = String::new();
+            let mut width = 0;
+            for ch in value.chars() {
+                width += ch.width().unwrap_or(0);
+                if width > TABLE_COLUMN_WIDTH {
+                    clipped.pop();
+                    clipped.push('…');
+                    break;
+                }
+                clipped.push(ch);
+            }
+            clipped
+        };
+        let render_row = |row: usize, header: bool| {
+            let mut line = div().flex().flex_none();
+            for column in 0..widths.len() {
+                let selected = table.selected == (row, column);
+                let text = match (&table.editing, table.table.cell(row, column)) {
+                    (Some(value), _) if selected => format!("{}▏", clip(value)),
+                    (_, Some(cell)) => clip(&cell.value),
+                    (_, None) => String::new(),
+                };
+                let arrow = match table.sort {
+                    Some((sorted, ascending)) if header && sorted == column => {
+                        if ascending {
+                            " ▲"
+                        } else {
+                            " ▼"
+                        }
+                    }
+                    _ => "",
+                };
+                let mut cell = div()
+                    .id(("table-cell", row * widths.len() + column))
+                    .flex_none()
+                    .w(column_px(column))
+                    .px_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .border_r_1()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .child(format!("{}{}", text, arrow));
+                if header {
+                    cell = cell.bg(rgb(0x252526)).text_color(rgb(0xe0e0e0));
+                }
+                if selected {
+                    cell = cell.bg(if table.editing.is_some() {
+                        rgb(0x1f3b5c)
+                    } else {
+                        rgb(0x264f78)
+                    });
+                }
+                cell = cell.on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                    let Some(table) = &mut view.table_mode else {
+                        return;
+                    };
+                    if header && table.editing.is_none() {
+                        table.sort = match table.sort {
+                            Some((sorted, true)) if sorted == column => Some((column, false)),
+                            Some((sorted, false)) if sorted == column => None,
+                            _ => Some((column, true)),
+                        };
+                    }
+                    table.selected = (row, column);
+                    cx.notify();
+                }));
+                line = line.child(cell);
+            }
+            line
+        };
+
+        let rows = table.display_rows();
+        let mut body = div().flex().flex_col();
+        for &row in rows.iter().skip(1).take(TABLE_ROWS) {
+            body = body.child(render_row(row, false));
+        }
+        let hidden = rows.len().saturating_sub(TABLE_ROWS + 1);
+        if hidden > 0 {
+            body = body.child(
+                div()
+                    .px_1()
+                    .py_2()
+                    .text_color(rgb(0x888888))
+                    .child(format!("还有 {} 行未显示，回到文本视图查看全部", hidden)),
+            );
+        }
+        let name = table
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let delimiter = match table.table.delimiter {
+            '\t' => "制表符".to_string(),
+            delimiter => format!("“{}”", delimiter),
+        };
+
+        div()
+            .flex_1()
+            .flex()
+            .flex_col()
+            .bg(rgb(0x1e1e1e))
+            .child(
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_sm()
+                    .text_color(rgb(0xaaaaaa))
+                    .child(format!(
+                        "{}{} · {} 行 × {} 列 · 以{}分隔",
+                        name,
+                        if self.is_dirty { " ●" } else { "" },
+                        table.table.rows.len().saturating_sub(1),
+                        widths.len(),
+                        delimiter
+                    )),
+            )
+            .child(
+                // 表头在纵向滚动区域之外，横向与表体一起滚动
+                div().id("table-scroll").flex_1().overflow_x_scroll().child(
+                    div()
+                        .h_full()
+                        .flex()
+                        .flex_col()
+                        .children(rows.first().map(|&header| render_row(header, true)))
+                        .child(
+                            div()
+                                .id("table-body")
+                                .flex_1()
+                                .overflow_y_scroll()
+                                .child(body),
+                        ),
+                ),
+            )
+    }
+}
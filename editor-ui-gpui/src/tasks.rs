@@ -0,0 +1,48 @@
+//! 编辑区后台刷新任务的句柄：同一种刷新不重复排队
+
+use gpui::Task;
+use std::collections::HashMap;
+
+/// 按当前状态重新计算、只关心最新结果的后台刷新
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Refresh {
+    BufferView,
+    LineChanges,
+    BufferStats,
+    BracketMatch,
+    Search,
+}
+
+/// 每种刷新进行中的一轮。同一种刷新同时只跑一轮：进行中时新的请求只记下版本号，
+/// 这一轮结束后按最新状态再跑一轮，不会堆积过时的快照，也不会旧结果覆盖新结果
+#[derive(Default)]
+pub struct RefreshTasks {
+    running: HashMap<Refresh, Task<anyhow::Result<()>>>,
+    requested: HashMap<Refresh, u64>,
+    next_version: u64,
+}
+
+impl RefreshTasks {
+    /// 请求一轮刷新，返回它的版本号；同一种刷新正在进行时返回 None
+    pub fn begin(&mut self, kind: Refresh) -> Option<u64> {
+        self.next_version += 1;
+        self.requested.insert(kind, self.next_version);
+        (!self.running.contains_key(&kind)).then_some(self.next_version)
+    }
+
+    /// 保存 [`RefreshTasks::begin`] 开始的这一轮的任务
+    pub fn track(&mut self, kind: Refresh, task: Task<anyhow::Result<()>>) {
+        self.running.insert(kind, task);
+    }
+
+    /// 结束第 `version` 轮，返回期间是否有新的请求需要再跑一轮
+    pub fn finish(&mut self, kind: Refresh, version: u64) -> bool {
+        // 由这一轮自己调用，任务还没跑完，放手让它结束而不是取消它
+        if let Some(task) = self.running.remove(&kind) {
+            task.detach();
+        }
+        self.requested
+            .get(&kind)
+            .is_some_and(|latest| *latest > version)
+    }
+}
@@ -0,0 +1,82 @@
+//! 语法高亮的配色：编辑区和 AI 面板中的代码块共用同一套
+
+use editor_core_text::{Highlighter, SyntaxSpan, TokenKind};
+use gpui::{rgb, HighlightStyle, StyledText};
+use std::ops::Range;
+
+/// 各类记号的颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxTheme {
+    pub keyword: u32,
+    pub type_name: u32,
+    pub function: u32,
+    pub string: u32,
+    pub number: u32,
+    pub comment: u32,
+}
+
+impl Default for SyntaxTheme {
+    /// 深色主题
+    fn default() -> Self {
+        Self {
+            keyword: 0xc586c0,
+            type_name: 0x4ec9b0,
+            function: 0xdcdcaa,
+            string: 0xce9178,
+            number: 0xb5cea8,
+            comment: 0x6a9955,
+        }
+    }
+}
+
+impl SyntaxTheme {
+    /// `ui.theme` 指定的配色，不认识的名称返回 None
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::default()),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self, kind: TokenKind) -> u32 {
+        match kind {
+            TokenKind::Keyword => self.keyword,
+            TokenKind::Type => self.type_name,
+            TokenKind::Function => self.function,
+            TokenKind::String => self.string,
+            TokenKind::Number => self.number,
+            TokenKind::Comment => self.comment,
+        }
+    }
+
+    /// 一行代码的高亮区间，可直接交给 [`StyledText::with_highlights`]
+    pub fn line_highlights(&self, spans: &[SyntaxSpan]) -> Vec<(Range<usize>, HighlightStyle)> {
+        spans
+            .iter()
+            .map(|span| {
+                let style = HighlightStyle {
+                    color: Some(rgb(self.color(span.kind)).into()),
+                    ..Default::default()
+                };
+                (span.range.clone(), style)
+            })
+            .collect()
+    }
+
+    /// 把一段代码按语言高亮成逐行的文本，语言可以是 `rust` 这样的标识或 `rs` 这样的扩展名
+    pub fn highlight_code(&self, language: &str, code: &str) -> Vec<StyledText> {
+        let spans = Highlighter::for_language(language).highlight(code);
+        code.lines()
+            .zip(spans)
+            .map(|(line, spans)| {
+                // 空行也要占一行的高度
+                let text = StyledText::new(if line.is_empty() { " " } else { line }.to_string());
+                if spans.is_empty() {
+                    text
+                } else {
+                    text.with_highlights(self.line_highlights(&spans))
+                }
+            })
+            .collect()
+    }
+}
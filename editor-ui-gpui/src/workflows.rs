@@ -0,0 +1,176 @@
+//! 定时工作流：按计划在后台运行工作流，并在面板中显示下次运行时间和上次结果
+
+use crate::editor_view::EditorView;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, InteractiveElement, StatefulInteractiveElement,
+    WeakEntity,
+};
+use std::time::{Duration, SystemTime};
+
+impl Drop for EditorView {
+    fn drop(&mut self) {
+        if let Some(handle) = self.scheduler_handle.take() {
+            handle.abort();
+        }
+    }
+}
+
+impl EditorView {
+    /// 启动定时 workflow 调度
+    pub(crate) fn start_workflow_scheduler(&mut self) {
+        if self.workflow_scheduler.is_empty() || self.scheduler_handle.is_some() {
+            return;
+        }
+
+        let ai_engine = self.ai_engine.clone();
+        let handle = self
+            .workflow_scheduler
+            .start(&self.ai_executor, move |workflow| {
+                let ai_engine = ai_engine.clone();
+                async move {
+                    ai_engine
+                        .run_workflow(&workflow, "")
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }
+            });
+        self.scheduler_handle = Some(handle);
+    }
+
+    /// 切换 workflow 面板，打开时每秒刷新运行状态
+    pub fn toggle_workflows_panel(&mut self, cx: &mut Context<'_, Self>) {
+        self.show_workflows = !self.show_workflows;
+
+        if self.show_workflows {
+            let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+                let mut app = cx.clone();
+
+                async move {
+                    loop {
+                        app.background_executor()
+                            .timer(Duration::from_secs(1))
+                            .await;
+                        this.update(&mut app, |_, cx| cx.notify())?;
+                    }
+                }
+            });
+            self.workflows_ticker = Some(task);
+        } else {
+            self.workflows_ticker = None;
+        }
+        cx.notify();
+    }
+
+    fn format_until(timestamp: SystemTime) -> String {
+        let secs = timestamp
+            .duration_since(SystemTime::now())
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        match secs {
+            0 => "即将运行".to_string(),
+            1..=59 => format!("{} 秒后", secs),
+            60..=3599 => format!("{} 分钟后", secs / 60),
+            _ => format!("{} 小时后", secs / 3600),
+        }
+    }
+
+    pub(crate) fn render_workflows(&self, cx: &mut Context<'_, Self>) -> impl IntoElement {
+        let mut panel = div()
+            .w(px(320.0))
+            .flex()
+            .flex_col()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_color(rgb(0x9ad1ff))
+                    .text_sm()
+                    .child("Workflows"),
+            );
+
+        let statuses = self.workflow_scheduler.statuses();
+        if statuses.is_empty() {
+            panel = panel.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_sm()
+                    .text_color(rgb(0x666666))
+                    .child("没有配置定时触发的 workflow"),
+            );
+        }
+
+        for (idx, status) in statuses.into_iter().enumerate() {
+            let last_run = status
+                .last_run
+                .map(Self::format_age)
+                .unwrap_or_else(|| "从未运行".to_string());
+            let state = if status.running {
+                "运行中…".to_string()
+            } else if let Some(error) = &status.last_error {
+                format!("失败: {}", error)
+            } else {
+                format!("下次: {}", Self::format_until(status.next_run))
+            };
+            let name = status.name.clone();
+
+            panel = panel.child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x1f1f1f))
+                    .text_sm()
+                    .child(
+                        div()
+                            .flex()
+                            .justify_between()
+                            .child(div().text_color(rgb(0xffffff)).child(status.name.clone()))
+                            .child(
+                                div()
+                                    .id(("workflow-run", idx as u64))
+                                    .px_2()
+                                    .rounded(px(4.0))
+                                    .bg(rgb(0x3a3a3a))
+                                    .cursor_pointer()
+                                    .child("Run")
+                                    .on_click(cx.listener(
+                                        move |view: &mut EditorView, _, _, cx| {
+                                            view.workflow_scheduler
+                                                .run_now(&name, SystemTime::now());
+                                            view.set_status(format!("已排队运行 {}", name));
+                                            cx.notify();
+                                        },
+                                    )),
+                            ),
+                    )
+                    .child(div().text_xs().text_color(rgb(0x888888)).child(format!(
+                        "每 {} 秒 · 上次: {} · 已运行 {} 次",
+                        status.interval.as_secs(),
+                        last_run,
+                        status.run_count
+                    )))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(if status.last_error.is_some() {
+                                rgb(0xff8a80)
+                            } else {
+                                rgb(0x8ef1a2)
+                            })
+                            .child(state),
+                    ),
+            );
+        }
+
+        panel
+    }
+}
@@ -0,0 +1,220 @@
+//! 工作区搜索：按 .gitignore 规则并行搜索整个工作区，在结果面板中按文件列出匹配
+
+use crate::editor_view::{EditorView, SKIPPED_DIRS};
+use editor_core_project::{ProjectSearch, ProjectSearchOptions, ProjectSearchResults, Workspace};
+use editor_core_text::SearchQuery;
+use gpui::{
+    div, prelude::*, px, rgb, AsyncApp, Context, HighlightStyle, InteractiveElement,
+    StatefulInteractiveElement, StyledText, WeakEntity,
+};
+
+impl EditorView {
+    /// 在整个工作区中查找，使用当前的查找选项；新的查找会取消还在进行的上一次
+    pub(crate) fn search_workspace(&mut self, text: String, cx: &mut Context<'_, Self>) {
+        let query = match SearchQuery::new(&text, self.search_options) {
+            Ok(query) => query,
+            Err(e) => {
+                self.set_status(format!("无效的查找: {}", e));
+                return;
+            }
+        };
+        let Ok(root) = std::env::current_dir() else {
+            return;
+        };
+        let workspace = match Workspace::single_root(&root) {
+            Ok(workspace) => workspace,
+            Err(e) => {
+                self.set_status(format!("无法打开工作区: {}", e));
+                return;
+            }
+        };
+        let search = ProjectSearch::new(
+            query,
+            ProjectSearchOptions {
+                follow_symlinks: self.config.editor.follow_symlinks,
+                skip_dirs: SKIPPED_DIRS.iter().map(|dir| dir.to_string()).collect(),
+                ..Default::default()
+            },
+        );
+        self.set_status(format!("正在工作区中查找 {}…", text));
+        cx.notify();
+
+        let task = cx.spawn(move |this: WeakEntity<EditorView>, cx: &mut AsyncApp| {
+            let mut app = cx.clone();
+            async move {
+                let results = app
+                    .background_executor()
+                    .spawn(async move { search.run(&workspace) })
+                    .await;
+
+                this.update(&mut app, |view, cx| {
+                    let files = results.by_file().len();
+                    view.set_status(if results.limit_reached {
+                        format!(
+                            "{} 个文件中找到 {} 处，已达上限，请缩小查找范围",
+                            files,
+                            results.matches.len()
+                        )
+                    } else {
+                        format!(
+                            "搜索了 {} 个文件，{} 个文件中找到 {} 处",
+                            results.files_searched,
+                            files,
+                            results.matches.len()
+                        )
+                    });
+                    view.workspace_search = Some((text, results));
+                    view.workspace_search_task = None;
+                    cx.notify();
+                })?;
+                anyhow::Ok(())
+            }
+        });
+        self.workspace_search_task = Some(task);
+    }
+
+    /// 一行文本，其中第 `columns` 个字符起的部分加上底色
+    fn highlighted_line(text: &str, columns: std::ops::Range<usize>) -> StyledText {
+        let byte = |column: usize| {
+            text.char_indices()
+                .nth(column)
+                .map_or(text.len(), |(index, _)| index)
+        };
+        let range = byte(columns.start)..byte(columns.end);
+        let styled = StyledText::new(text.to_string());
+        if range.is_empty() {
+            return styled;
+        }
+        styled.with_highlights([(
+            range,
+            HighlightStyle {
+                background_color: Some(rgb(0x5a4a14).into()),
+                ..Default::default()
+            },
+        )])
+    }
+
+    pub(crate) fn render_workspace_search(
+        &self,
+        query: &str,
+        results: &ProjectSearchResults,
+        cx: &mut Context<'_, Self>,
+    ) -> impl IntoElement {
+        let root = std::env::current_dir().unwrap_or_default();
+        let mut list = div()
+            .id("workspace-search-list")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .overflow_scroll();
+        if results.matches.is_empty() {
+            list = list.child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_sm()
+                    .text_color(rgb(0x777777))
+                    .child("没有找到"),
+            );
+        }
+
+        let mut index = 0u64;
+        for (path, matches) in results.by_file() {
+            list = list.child(
+                div()
+                    .px_3()
+                    .pt_2()
+                    .text_sm()
+                    .text_color(rgb(0xffffff))
+                    .child(format!(
+                        "{} ({})",
+                        path.strip_prefix(&root).unwrap_or(path).display(),
+                        matches.len()
+                    )),
+            );
+            for found in matches {
+                let line = found.start.line;
+                // 跨行的匹配只标出第一行的部分
+                let end = if found.end.line == line {
+                    found.end.column
+                } else {
+                    found.line_text.chars().count()
+                };
+                let context_line = |number: usize, text: &String| {
+                    div()
+                        .text_color(rgb(0x666666))
+                        .child(format!("{:>5}  {}", number + 1, text))
+                };
+                let mut rows = div().flex().flex_col().text_xs().font_family("monospace");
+                let before_start = line - found.context_before.len();
+                for (offset, text) in found.context_before.iter().enumerate() {
+                    rows = rows.child(context_line(before_start + offset, text));
+                }
+                rows = rows.child(
+                    div()
+                        .flex()
+                        .text_color(rgb(0xdddddd))
+                        .child(format!("{:>5}  ", line + 1))
+                        .child(Self::highlighted_line(
+                            &found.line_text,
+                            found.start.column..end,
+                        )),
+                );
+                for (offset, text) in found.context_after.iter().enumerate() {
+                    rows = rows.child(context_line(line + 1 + offset, text));
+                }
+
+                let (path, column) = (found.path.clone(), found.start.column);
+                list = list.child(
+                    div()
+                        .id(("workspace-search-match", index))
+                        .px_3()
+                        .py_1()
+                        .cursor_pointer()
+                        .hover(|style| style.bg(rgb(0x1f1f1f)))
+                        .child(rows)
+                        .on_click(cx.listener(move |view: &mut EditorView, _, _, cx| {
+                            view.open_file_at(&path, line, column, cx)
+                        })),
+                );
+                index += 1;
+            }
+        }
+
+        div()
+            .w(px(420.0))
+            .flex()
+            .flex_col()
+            .bg(rgb(0x141414))
+            .border_l_1()
+            .border_color(rgb(0x2a2a2a))
+            .child(
+                div()
+                    .flex()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(rgb(0x2a2a2a))
+                    .text_sm()
+                    .child(
+                        div()
+                            .text_color(rgb(0x9ad1ff))
+                            .child(format!("Search: {}", query)),
+                    )
+                    .child(
+                        div()
+                            .id("workspace-search-close")
+                            .px_2()
+                            .cursor_pointer()
+                            .text_color(rgb(0x888888))
+                            .child("✕")
+                            .on_click(cx.listener(|view: &mut EditorView, _, _, cx| {
+                                view.workspace_search = None;
+                                cx.notify();
+                            })),
+                    ),
+            )
+            .child(list)
+    }
+}
@@ -1,16 +1,51 @@
 use anyhow::Result;
+use editor_infra::ipc::{self, IpcServer};
+use editor_infra::startup;
 use editor_ui_gpui::EditorView;
 use gpui::{AppContext, Application, WindowOptions};
 
 fn main() -> Result<()> {
-    let app = Application::new();
-    app.run(|app| {
-        let window = app
-            .open_window(WindowOptions::default(), |_window, cx| {
-                cx.new(|cx| {
-                    let mut view = EditorView::new(cx);
-                    view.initialize(cx);
-                    view
+    let profile = startup::profile();
+    // --profile-startup：第一帧画出后在 stderr 打印各启动阶段的耗时
+    profile.print_on_first_frame(std::env::args().any(|arg| arg == "--profile-startup"));
+
+    // 命令行带 fusang:// 链接时，优先交给已运行的实例
+    let deep_link = std::env::args()
+        .skip(1)
+        .find(|arg| arg.starts_with(&format!("{}://", editor_infra::deep_link::SCHEME)));
+    if let Some(link) = &deep_link {
+        if profile.phase("deep link handoff", || ipc::send_to_running_instance(link)) {
+            return Ok(());
+        }
+    }
+
+    let (link_sender, link_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let _ipc_server = profile
+        .phase("ipc server", || IpcServer::start(link_sender.clone()))
+        .map_err(|e| eprintln!("Failed to start IPC server: {}", e))
+        .ok();
+    if let Some(link) = deep_link {
+        let _ = link_sender.send(link);
+    }
+
+    let app = profile.phase("application", Application::new);
+    let url_sender = link_sender.clone();
+    app.on_open_urls(move |urls| {
+        for url in urls {
+            let _ = url_sender.send(url);
+        }
+    });
+
+    app.run(move |app| {
+        let window = profile
+            .phase("window", || {
+                app.open_window(WindowOptions::default(), |_window, cx| {
+                    cx.new(|cx| {
+                        let mut view = profile.phase("editor view", || EditorView::new(cx));
+                        profile.phase("initialize", || view.initialize(cx));
+                        view.listen_for_deep_links(link_receiver, cx);
+                        view
+                    })
                 })
             })
             .expect("failed to open window");
@@ -20,7 +55,7 @@ fn main() -> Result<()> {
             .expect("failed to get editor view");
 
         app.observe_keystrokes(move |event, _, cx| {
-            view.update(cx, |view, cx| view.handle_key_event(&event, cx));
+            view.update(cx, |view, cx| view.handle_key_event(event, cx));
         })
         .detach();
 
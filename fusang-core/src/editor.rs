@@ -0,0 +1,264 @@
+use crate::CoreError;
+use editor_ai::{AIAction, AIEngine, ComposerReply, WorkingSet};
+use editor_core_project::{BufferManager, Workspace};
+use editor_core_text::{Buffer, BufferSnapshot, Cursor};
+use editor_infra::Config;
+use editor_lsp::protocol::Hover;
+use editor_lsp::{CompletionItem, Diagnostic, Location, LspServerManager, Position};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// One editor session: a workspace, its open buffers, the AI engine and the language
+/// servers. Paths may be absolute or relative to the first workspace root; the ones
+/// returned are always absolute.
+pub struct Editor {
+    config: Config,
+    workspace: Option<Workspace>,
+    buffers: BufferManager,
+    lsp: Arc<LspServerManager>,
+    ai: Arc<AIEngine>,
+}
+
+impl Editor {
+    pub fn new(config: Config) -> Self {
+        Self {
+            ai: Arc::new(AIEngine::new(config.ai.clone())),
+            config,
+            workspace: None,
+            buffers: BufferManager::new(),
+            lsp: Arc::new(LspServerManager::new()),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn open_workspace(&mut self, root: impl AsRef<Path>) -> Result<&Workspace, CoreError> {
+        let workspace = Workspace::single_root(root)?;
+        Ok(self.workspace.insert(workspace))
+    }
+
+    pub fn workspace(&self) -> Option<&Workspace> {
+        self.workspace.as_ref()
+    }
+
+    /// The underlying buffers, for anything the facade does not cover.
+    pub fn buffers(&self) -> &BufferManager {
+        &self.buffers
+    }
+
+    pub fn ai(&self) -> &Arc<AIEngine> {
+        &self.ai
+    }
+
+    pub fn lsp(&self) -> &Arc<LspServerManager> {
+        &self.lsp
+    }
+
+    /// `path` made absolute against the workspace root.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> Result<PathBuf, CoreError> {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            return Ok(path.to_path_buf());
+        }
+        let workspace = self.workspace.as_ref().ok_or(CoreError::NoWorkspace)?;
+        Ok(workspace.root_paths[0].join(path))
+    }
+
+    /// Opens a file and returns its absolute path. A file that is already open keeps
+    /// its buffer, unsaved edits included.
+    pub async fn open(&self, path: impl AsRef<Path>) -> Result<PathBuf, CoreError> {
+        let path = self.resolve(path)?;
+        if self.buffers.get_buffer(&path).await.is_none() {
+            self.buffers.open_file(&path).await?;
+        }
+        Ok(path)
+    }
+
+    pub async fn close(&self, path: impl AsRef<Path>) -> Result<(), CoreError> {
+        let path = self.resolve(path)?;
+        self.buffers.close_file(&path).await?;
+        Ok(())
+    }
+
+    pub async fn snapshot(&self, path: impl AsRef<Path>) -> Result<BufferSnapshot, CoreError> {
+        let buffer = self.buffer(path).await?;
+        let snapshot = buffer.lock().await.snapshot().await;
+        Ok(snapshot)
+    }
+
+    pub async fn text(&self, path: impl AsRef<Path>) -> Result<String, CoreError> {
+        Ok(self.snapshot(path).await?.text())
+    }
+
+    /// Replaces `range` with `text` as one undo step.
+    pub async fn replace(
+        &self,
+        path: impl AsRef<Path>,
+        range: Range<Cursor>,
+        text: &str,
+    ) -> Result<(), CoreError> {
+        let buffer = self.buffer(path).await?;
+        let mut buffer = buffer.lock().await;
+        let snapshot = buffer.snapshot().await;
+        let start = snapshot.cursor_to_char(range.start);
+        let end = snapshot.cursor_to_char(range.end).max(start);
+        buffer.replace_range(start, end - start, text).await;
+        Ok(())
+    }
+
+    pub async fn insert(
+        &self,
+        path: impl AsRef<Path>,
+        at: Cursor,
+        text: &str,
+    ) -> Result<(), CoreError> {
+        self.replace(path, at..at, text).await
+    }
+
+    /// Undoes the last edit; false when there was nothing to undo.
+    pub async fn undo(&self, path: impl AsRef<Path>) -> Result<bool, CoreError> {
+        let buffer = self.buffer(path).await?;
+        let undone = buffer.lock().await.undo().await;
+        Ok(undone)
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), CoreError> {
+        let path = self.resolve(path)?;
+        self.buffer(&path).await?;
+        self.buffers.save_file(&path).await?;
+        Ok(())
+    }
+
+    pub async fn unsaved_files(&self) -> Vec<PathBuf> {
+        self.buffers.get_unsaved_files().await
+    }
+
+    /// Runs an AI action and returns the model's reply. `model` defaults to the one
+    /// in the config.
+    pub async fn run_ai_action(
+        &self,
+        action: &AIAction,
+        model: Option<&str>,
+    ) -> Result<String, CoreError> {
+        Ok(self.ai.run_action(action, model).await?)
+    }
+
+    /// Asks the model for edits to the given open files. Nothing is applied; the
+    /// patches come back for the caller to review.
+    pub async fn propose_edits(
+        &self,
+        instruction: &str,
+        paths: &[PathBuf],
+        working_set: &WorkingSet,
+        model: Option<&str>,
+    ) -> Result<ComposerReply, CoreError> {
+        let mut files = Vec::new();
+        for path in paths {
+            let display = match &self.workspace {
+                Some(workspace) => workspace
+                    .relative_path(path)
+                    .unwrap_or_else(|| path.clone()),
+                None => path.clone(),
+            };
+            files.push((display.display().to_string(), self.text(path).await?));
+        }
+        Ok(self
+            .ai
+            .propose_edits(instruction, &files, working_set, model)
+            .await?)
+    }
+
+    /// Starts the configured language servers in the workspace root. Servers that fail
+    /// to start are skipped; their errors are returned with their language.
+    pub async fn start_language_servers(&self) -> Result<Vec<(String, std::io::Error)>, CoreError> {
+        let workspace = self.workspace.as_ref().ok_or(CoreError::NoWorkspace)?;
+        let root_uri = uri(&workspace.root_paths[0]);
+        let mut failed = Vec::new();
+        if !self.config.lsp.enabled {
+            return Ok(failed);
+        }
+        for server in &self.config.lsp.servers {
+            if let Err(e) = self.lsp.start_server_for_language(server, &root_uri).await {
+                failed.push((server.language.clone(), e));
+            }
+        }
+        Ok(failed)
+    }
+
+    pub async fn hover(
+        &self,
+        path: impl AsRef<Path>,
+        at: Cursor,
+    ) -> Result<Option<Hover>, CoreError> {
+        let (language, uri, position) = self.sync(path, at).await?;
+        Ok(self.lsp.request_hover(&language, &uri, position).await?)
+    }
+
+    pub async fn definition(
+        &self,
+        path: impl AsRef<Path>,
+        at: Cursor,
+    ) -> Result<Vec<Location>, CoreError> {
+        let (language, uri, position) = self.sync(path, at).await?;
+        Ok(self
+            .lsp
+            .request_definition(&language, &uri, position)
+            .await?)
+    }
+
+    pub async fn completions(
+        &self,
+        path: impl AsRef<Path>,
+        at: Cursor,
+    ) -> Result<Vec<CompletionItem>, CoreError> {
+        let (language, uri, position) = self.sync(path, at).await?;
+        Ok(self
+            .lsp
+            .request_completion(&language, &uri, position)
+            .await?)
+    }
+
+    /// The last diagnostics the server published for the file.
+    pub async fn diagnostics(&self, path: impl AsRef<Path>) -> Result<Vec<Diagnostic>, CoreError> {
+        let path = self.resolve(path)?;
+        Ok(self.lsp.get_diagnostics(&uri(&path)).await)
+    }
+
+    /// Stops the language servers. Unsaved buffers are left as they are.
+    pub async fn shutdown(&self) -> Result<(), CoreError> {
+        self.lsp.shutdown_all().await?;
+        Ok(())
+    }
+
+    async fn buffer(&self, path: impl AsRef<Path>) -> Result<Arc<Mutex<Buffer>>, CoreError> {
+        let path = self.resolve(path)?;
+        self.buffers
+            .get_buffer(&path)
+            .await
+            .ok_or(CoreError::NotOpen(path))
+    }
+
+    /// Sends the current text to the file's server so a request sees the latest edits.
+    async fn sync(
+        &self,
+        path: impl AsRef<Path>,
+        at: Cursor,
+    ) -> Result<(String, String, Position), CoreError> {
+        let path = self.resolve(path)?;
+        let snapshot = self.snapshot(&path).await?;
+        let language = self.buffers.language(&path).await;
+        let uri = uri(&path);
+        self.lsp
+            .sync_document(&language, &uri, &snapshot.text(), snapshot.version())
+            .await?;
+        Ok((language, uri, Position::from_cursor(snapshot.rope(), at)))
+    }
+}
+
+fn uri(path: &Path) -> String {
+    editor_lsp::path_to_uri(path)
+}
@@ -0,0 +1,18 @@
+use editor_ai::AIEngineError;
+use editor_core_project::WorkspaceError;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CoreError {
+    #[error("no workspace is open")]
+    NoWorkspace,
+    #[error("{} is not open", .0.display())]
+    NotOpen(PathBuf),
+    #[error(transparent)]
+    Workspace(#[from] WorkspaceError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Ai(#[from] AIEngineError),
+}
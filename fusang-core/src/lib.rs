@@ -0,0 +1,28 @@
+//! The editor engine without a UI: open a workspace, edit buffers, run AI actions and
+//! query language servers. Everything here is plain async Rust on tokio, so a CLI, a
+//! test or another frontend can drive the same engine the GPUI editor uses.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), fusang_core::CoreError> {
+//! use fusang_core::{Config, Cursor, Editor};
+//!
+//! let mut editor = Editor::new(Config::default());
+//! editor.open_workspace("/path/to/project")?;
+//! let path = editor.open("src/main.rs").await?;
+//! editor.insert(&path, Cursor::new(0, 0), "// hello\n").await?;
+//! editor.save(&path).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod editor;
+pub mod error;
+
+pub use editor::Editor;
+pub use error::CoreError;
+
+pub use editor_ai::{AIAction, AIEngine};
+pub use editor_core_project::Workspace;
+pub use editor_core_text::{BufferSnapshot, Cursor};
+pub use editor_infra::Config;
+pub use editor_lsp::{CompletionItem, Diagnostic, Location};
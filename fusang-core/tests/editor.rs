@@ -0,0 +1,49 @@
+use fusang_core::{Config, CoreError, Cursor, Editor};
+
+#[tokio::test]
+async fn edits_files_in_a_workspace_without_a_ui() {
+    let root = std::env::temp_dir().join(format!("fusang-core-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(root.join("src/lib.rs"), "fn a() {}\n").unwrap();
+
+    let mut editor = Editor::new(Config::default());
+    assert!(matches!(
+        editor.open("src/lib.rs").await,
+        Err(CoreError::NoWorkspace)
+    ));
+    editor.open_workspace(&root).unwrap();
+
+    let path = editor.open("src/lib.rs").await.unwrap();
+    assert_eq!(path, root.join("src/lib.rs"));
+    editor
+        .replace(&path, Cursor::new(0, 3)..Cursor::new(0, 4), "b")
+        .await
+        .unwrap();
+    editor
+        .insert("src/lib.rs", Cursor::new(1, 0), "fn c() {}\n")
+        .await
+        .unwrap();
+    // Opening again keeps the edits.
+    editor.open("src/lib.rs").await.unwrap();
+    assert_eq!(editor.text(&path).await.unwrap(), "fn b() {}\nfn c() {}\n");
+    assert!(editor.undo(&path).await.unwrap());
+    assert_eq!(editor.unsaved_files().await, std::slice::from_ref(&path));
+
+    editor.save(&path).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "fn b() {}\n");
+    assert!(editor.unsaved_files().await.is_empty());
+    assert!(matches!(
+        editor.text("src/main.rs").await,
+        Err(CoreError::NotOpen(_))
+    ));
+    // No servers are running, so language queries come back empty.
+    assert!(editor
+        .hover(&path, Cursor::new(0, 3))
+        .await
+        .unwrap()
+        .is_none());
+    assert!(editor.diagnostics(&path).await.unwrap().is_empty());
+    editor.shutdown().await.unwrap();
+
+    std::fs::remove_dir_all(root).unwrap();
+}